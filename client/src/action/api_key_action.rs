@@ -0,0 +1,55 @@
+use super::definition::{Action, ActionState};
+use check_mate_common::{CommunicationError, ServerCommand};
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+#[derive(PartialEq, Debug)]
+pub enum ApiKeyRequest {
+    Create { identity: String, label: String },
+    List,
+    Label { id: String, label: String },
+    Revoke(String),
+}
+
+impl Action {
+    pub(crate) async fn manage_api_keys(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        request: &ApiKeyRequest,
+        state: &mut ActionState,
+    ) -> Result<(), CommunicationError> {
+        let command = match request {
+            ApiKeyRequest::Create { identity, label } => {
+                ServerCommand::CreateApiKey(identity.clone(), label.clone())
+            }
+            ApiKeyRequest::List => ServerCommand::ListApiKeys,
+            ApiKeyRequest::Label { id, label } => {
+                ServerCommand::LabelApiKey(id.clone(), label.clone())
+            }
+            ApiKeyRequest::Revoke(id) => ServerCommand::RevokeApiKey(id.clone()),
+        };
+        command.send_async(output_stream).await?;
+
+        match ServerCommand::receive_async(input_stream).await? {
+            ServerCommand::ApiKeys(keys) => {
+                for key in keys {
+                    match key.label.is_empty() {
+                        true => println!("{} {}", key.id, key.identity),
+                        false => println!("{} {}: {}", key.id, key.identity, key.label),
+                    }
+                }
+            }
+            // The secret of a new key can't be read back later, so it's printed alone for scripts to capture it
+            ServerCommand::ApiKeyResult(Ok(key)) => {
+                if matches!(request, ApiKeyRequest::Create { .. }) {
+                    println!("{key}");
+                }
+            }
+            ServerCommand::ApiKeyResult(Err(err)) => {
+                eprintln!("ERROR: {err}");
+                state.exit_code = 1;
+            }
+            _ => panic!("Unexpected command received after managing API keys"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use super::definition::{Action, ActionState};
+use super::output_format::OutputFormat;
+use check_mate_common::{ClientAvailability, CommunicationError, ServerCommand};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum AvailabilityWindow {
+    /// Last 24 hours.
+    Day,
+
+    /// Last 7 days.
+    Week,
+
+    /// Last 30 days.
+    Month,
+}
+
+impl AvailabilityWindow {
+    fn get_duration(self) -> Duration {
+        let days = match self {
+            AvailabilityWindow::Day => 1,
+            AvailabilityWindow::Week => 7,
+            AvailabilityWindow::Month => 30,
+        };
+        Duration::from_secs(days * 24 * 3600)
+    }
+}
+
+#[derive(Serialize)]
+struct JsonClientAvailability<'a> {
+    name: &'a str,
+    availability: Option<f64>, // percentage, None if no status is known within the window
+    ok_seconds: u64,
+    error_seconds: u64,
+}
+
+fn format_availability(clients: &[ClientAvailability], output_format: OutputFormat) -> String {
+    match output_format {
+        OutputFormat::Text => clients
+            .iter()
+            .map(|client| match client.get_percentage() {
+                Some(percentage) => format!("{}: {:.3}%\n", client.name, percentage),
+                None => format!("{}: no data\n", client.name),
+            })
+            .collect(),
+        OutputFormat::Json => {
+            let clients = clients
+                .iter()
+                .map(|client| JsonClientAvailability {
+                    name: &client.name,
+                    availability: client.get_percentage(),
+                    ok_seconds: client.ok_seconds,
+                    error_seconds: client.error_seconds,
+                })
+                .collect::<Vec<_>>();
+            let json = serde_json::to_string_pretty(&clients);
+            json.expect("Availability should be serializable") + "\n"
+        }
+    }
+}
+
+impl Action {
+    pub(crate) async fn get_availability(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        window: AvailabilityWindow,
+        output_format: OutputFormat,
+        state: &mut ActionState,
+    ) -> Result<(), CommunicationError> {
+        let command = ServerCommand::GetAvailability(window.get_duration().as_secs());
+        command.send_async(output_stream).await?;
+
+        match ServerCommand::receive_async(input_stream).await? {
+            ServerCommand::Availability(Ok(clients)) => {
+                print!("{}", format_availability(&clients, output_format));
+            }
+            ServerCommand::Availability(Err(err)) => {
+                eprintln!("ERROR: {err}");
+                state.exit_code = 1;
+            }
+            _ => panic!("Unexpected command received after GetAvailability"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn availability_is_formatted() {
+        let clients = [
+            ClientAvailability {
+                name: "Backup".to_owned(),
+                ok_seconds: 86000,
+                error_seconds: 400,
+            },
+            ClientAvailability {
+                name: "Cleanup".to_owned(),
+                ok_seconds: 0,
+                error_seconds: 0,
+            },
+        ];
+        assert_eq!(
+            format_availability(&clients, OutputFormat::Text),
+            "Backup: 99.537%\nCleanup: no data\n"
+        );
+        let json = format_availability(&clients, OutputFormat::Json);
+        let json = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(json[0]["ok_seconds"], 86000);
+        assert_eq!(json[1]["availability"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,234 @@
+use super::definition::Action;
+use check_mate_common::constants::*;
+use check_mate_common::{glob_matches, ClientDetails, CommunicationError, ServerCommand, Severity};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+#[derive(PartialEq, Debug)]
+pub struct BadgeData {
+    pub directory: PathBuf,
+    pub label: String,
+    pub name_filter: Option<String>,
+    pub every: Option<Duration>,
+}
+
+impl BadgeData {
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            label: DEFAULT_BADGE_LABEL.to_owned(),
+            name_filter: None,
+            every: None,
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        match self.name_filter {
+            Some(ref pattern) => glob_matches(pattern, name),
+            None => true,
+        }
+    }
+}
+
+const GREEN: &str = "#4c1";
+const RED: &str = "#e05d44";
+const YELLOW: &str = "#dfb317";
+const GREY: &str = "#9f9f9f";
+
+// Text is not measured, so widths are approximated from the average character width of 11px Verdana
+fn estimate_text_width(text: &str) -> usize {
+    text.chars().count() * 7 + 10
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Renders a badge in the flat style of shields.io, with a grey label on the left and a colored message on the right
+fn render_badge(label: &str, message: &str, color: &str) -> String {
+    let label_width = estimate_text_width(label);
+    let message_width = estimate_text_width(message);
+    let width = label_width + message_width;
+    let label_x = label_width as f32 / 2.0;
+    let message_x = label_width as f32 + message_width as f32 / 2.0;
+    let label = escape_xml(label);
+    let message = escape_xml(message);
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="20" role="img" aria-label="{label}: {message}">
+<title>{label}: {message}</title>
+<linearGradient id="s" x2="0" y2="100%"><stop offset="0" stop-color="#bbb" stop-opacity=".1"/><stop offset="1" stop-opacity=".1"/></linearGradient>
+<clipPath id="r"><rect width="{width}" height="20" rx="3" fill="#fff"/></clipPath>
+<g clip-path="url(#r)"><rect width="{label_width}" height="20" fill="#555"/><rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/><rect width="{width}" height="20" fill="url(#s)"/></g>
+<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">
+<text x="{label_x}" y="15" fill="#010101" fill-opacity=".3">{label}</text><text x="{label_x}" y="14">{label}</text>
+<text x="{message_x}" y="15" fill="#010101" fill-opacity=".3">{message}</text><text x="{message_x}" y="14">{message}</text>
+</g>
+</svg>
+"##
+    )
+}
+
+fn render_overall_badge(label: &str, statuses: &[ClientDetails]) -> String {
+    let count = |severity| statuses.iter().filter(|x| x.severity() == severity).count();
+    let errors = count(Severity::Error);
+    let warnings = count(Severity::Warning);
+    let reported = statuses.iter().filter(|x| x.status.is_some()).count();
+    match (errors, warnings, reported) {
+        (0, 0, 0) => render_badge(label, "unknown", GREY),
+        (0, 0, _) => render_badge(label, "ok", GREEN),
+        (0, 1, _) => render_badge(label, "1 warning", YELLOW),
+        (0, warnings, _) => render_badge(label, &format!("{warnings} warnings"), YELLOW),
+        (1, _, _) => render_badge(label, "1 error", RED),
+        (errors, _, _) => render_badge(label, &format!("{errors} errors"), RED),
+    }
+}
+
+fn render_client_badge(details: &ClientDetails) -> String {
+    let color = match details.severity() {
+        Severity::Ok => GREEN,
+        Severity::Warning => YELLOW,
+        Severity::Error => RED,
+        Severity::Pending | Severity::Unknown => GREY,
+    };
+    render_badge(&details.name, details.severity().as_str(), color)
+}
+
+// Client names can contain anything, so characters which could be problematic in file names or URLs are replaced
+fn get_badge_file_name(client_name: &str) -> String {
+    let stem = client_name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    format!("{stem}.svg")
+}
+
+// Badges are usually served by a web server, so they are replaced atomically to never serve a partial file
+async fn write_badge(path: &Path, badge: &str) -> std::io::Result<()> {
+    let temporary_path = path.with_extension("svg.tmp");
+    tokio::fs::write(&temporary_path, badge).await?;
+    tokio::fs::rename(&temporary_path, path).await
+}
+
+async fn write_badges(data: &BadgeData, statuses: &[ClientDetails]) -> std::io::Result<()> {
+    let clients_directory = data.directory.join(BADGE_CLIENTS_DIRECTORY);
+    tokio::fs::create_dir_all(&clients_directory).await?;
+
+    let overall_badge = render_overall_badge(&data.label, statuses);
+    write_badge(&data.directory.join(BADGE_OVERALL_FILE), &overall_badge).await?;
+
+    let mut file_names = HashSet::new();
+    for details in statuses {
+        let file_name = get_badge_file_name(&details.name);
+        write_badge(
+            &clients_directory.join(&file_name),
+            &render_client_badge(details),
+        )
+        .await?;
+        file_names.insert(file_name);
+    }
+
+    // Remove badges of clients which are gone, so they don't show a status which is no longer known
+    let mut entries = tokio::fs::read_dir(&clients_directory).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if file_name.ends_with(".svg") && !file_names.contains(&file_name) {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+impl Action {
+    pub(crate) async fn write_badges(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &BadgeData,
+    ) -> Result<(), CommunicationError> {
+        loop {
+            let command = ServerCommand::GetClientDetails;
+            command.send_async(output_stream).await?;
+            let statuses = match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::ClientDetails(details) => details
+                    .into_iter()
+                    .filter(|x| data.matches_name(&x.name))
+                    .collect::<Vec<_>>(),
+                _ => panic!("Unexpected command received after GetClientDetails"),
+            };
+            write_badges(data, &statuses).await?;
+
+            match data.every {
+                Some(interval) => tokio::time::sleep(interval).await,
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overall_badge_counts_errors() {
+        fn run(statuses: &[ClientDetails], expected_message: &str, expected_color: &str) {
+            let badge = render_overall_badge("health", statuses);
+            assert!(badge.contains(&format!("aria-label=\"health: {expected_message}\"")));
+            assert!(badge.contains(&format!("fill=\"{expected_color}\"")));
+        }
+
+        let error = || Some(Err("Failed"));
+        let warning = || ClientDetails::new("w", Some(Err("Slow"))).with_warning();
+        run(&[], "unknown", GREY);
+        run(&[ClientDetails::new("a", None)], "unknown", GREY);
+        run(
+            &[
+                ClientDetails::new("a", None),
+                ClientDetails::new("b", Some(Ok(()))),
+            ],
+            "ok",
+            GREEN,
+        );
+        run(
+            &[
+                ClientDetails::new("a", error()),
+                ClientDetails::new("b", Some(Ok(()))),
+            ],
+            "1 error",
+            RED,
+        );
+        run(
+            &[
+                ClientDetails::new("a", error()),
+                ClientDetails::new("b", error()),
+            ],
+            "2 errors",
+            RED,
+        );
+        run(&[warning(), ClientDetails::new("b", Some(Ok(())))], "1 warning", YELLOW);
+        run(&[warning(), warning()], "2 warnings", YELLOW);
+        run(&[warning(), ClientDetails::new("a", error())], "1 error", RED);
+    }
+
+    #[test]
+    fn badge_texts_are_escaped() {
+        let badge = render_client_badge(&ClientDetails::new("<db & \"cache\">", Some(Ok(()))));
+        assert!(badge.contains("<title>&lt;db &amp; &quot;cache&quot;&gt;: ok</title>"));
+        assert!(!badge.contains("<db"));
+    }
+
+    #[test]
+    fn badge_file_names_are_sanitized() {
+        assert_eq!(get_badge_file_name("db-1.local_2"), "db-1.local_2.svg");
+        assert_eq!(get_badge_file_name("../etc/passwd"), ".._etc_passwd.svg");
+        assert_eq!(get_badge_file_name("disk space"), "disk_space.svg");
+    }
+}
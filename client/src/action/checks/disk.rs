@@ -0,0 +1,165 @@
+use super::format_size;
+use check_mate_common::constants::*;
+use std::path::{Path, PathBuf};
+
+#[derive(PartialEq, Debug)]
+pub struct DiskCheck {
+    pub path: PathBuf,
+    pub warning_percent: Option<u8>,
+    pub critical_percent: Option<u8>,
+}
+
+impl DiskCheck {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            warning_percent: None,
+            critical_percent: Some(DEFAULT_DISK_CRITICAL_PERCENT),
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let path = self.path.clone();
+        let usage = tokio::task::spawn_blocking(move || get_disk_usage(&path))
+            .await
+            .map_err(|err| format!("Disk check was interrupted: {err}"))?
+            .map_err(|err| format!("Could not get usage of {}: {err}", self.path.display()))?;
+        get_disk_status(self, &usage)
+    }
+}
+
+// Space reserved for the superuser is neither used nor available, so percentage of used space is computed like in df
+#[derive(Debug)]
+struct DiskUsage {
+    used: u64,
+    available: u64,
+}
+
+impl DiskUsage {
+    fn used_percent(&self) -> f64 {
+        let total = self.used + self.available;
+        if total == 0 {
+            return 0.0;
+        }
+        self.used as f64 * 100.0 / total as f64
+    }
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // Types of statvfs fields differ between platforms
+fn get_disk_usage(path: &Path) -> std::io::Result<DiskUsage> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is a valid null-terminated string and stats points to memory large enough for statvfs
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: statvfs has succeeded, so it has initialized the structure
+    let stats = unsafe { stats.assume_init() };
+
+    let fragment_size = stats.f_frsize as u64;
+    let total = stats.f_blocks as u64 * fragment_size;
+    let free = stats.f_bfree as u64 * fragment_size;
+    Ok(DiskUsage {
+        used: total - free,
+        available: stats.f_bavail as u64 * fragment_size,
+    })
+}
+
+// Without statvfs, the path is matched to the disk with the longest mount point containing it
+#[cfg(not(unix))]
+fn get_disk_usage(path: &Path) -> std::io::Result<DiskUsage> {
+    let path = std::path::absolute(path)?;
+    std::fs::metadata(&path)?;
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    let disk = disks
+        .list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no disk contains the path")
+        })?;
+    Ok(DiskUsage {
+        used: disk.total_space() - disk.available_space(),
+        available: disk.available_space(),
+    })
+}
+
+fn get_disk_status(check: &DiskCheck, usage: &DiskUsage) -> Result<(), String> {
+    let used_percent = usage.used_percent();
+    let exceeds = |threshold: Option<u8>| threshold.is_some_and(|x| used_percent >= x as f64);
+    let severity = if exceeds(check.critical_percent) {
+        "CRITICAL"
+    } else if exceeds(check.warning_percent) {
+        "WARNING"
+    } else {
+        return Ok(());
+    };
+    Err(format!(
+        "{severity}: {} is {used_percent:.1}% full ({} used, {} free)",
+        check.path.display(),
+        format_size(usage.used),
+        format_size(usage.available)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_status_is_computed() {
+        fn run(
+            warning: Option<u8>,
+            critical: Option<u8>,
+            used_gib: u64,
+            expected: Result<(), &str>,
+        ) {
+            let check = DiskCheck {
+                path: PathBuf::from("/var"),
+                warning_percent: warning,
+                critical_percent: critical,
+            };
+            let usage = DiskUsage {
+                used: used_gib << 30,
+                available: (100 - used_gib) << 30,
+            };
+            let status = get_disk_status(&check, &usage);
+            assert_eq!(status, expected.map_err(|x| x.to_owned()));
+        }
+
+        run(Some(80), Some(95), 79, Ok(()));
+        run(
+            Some(80),
+            Some(95),
+            80,
+            Err("WARNING: /var is 80.0% full (80.0 GiB used, 20.0 GiB free)"),
+        );
+        run(
+            Some(80),
+            Some(95),
+            96,
+            Err("CRITICAL: /var is 96.0% full (96.0 GiB used, 4.0 GiB free)"),
+        );
+        run(None, Some(95), 90, Ok(()));
+        run(
+            Some(80),
+            None,
+            99,
+            Err("WARNING: /var is 99.0% full (99.0 GiB used, 1.0 GiB free)"),
+        );
+        run(None, None, 100, Ok(()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn disk_usage_is_read() {
+        let usage = get_disk_usage(Path::new("/")).expect("Root should be accessible");
+        assert!(usage.used + usage.available > 0);
+        assert!(get_disk_usage(Path::new("/nonexistent/path")).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use check_mate_common::constants::*;
+use check_mate_common::format_duration;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::TokioAsyncResolver;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+#[derive(PartialEq, Debug)]
+pub struct DnsCheck {
+    pub name: String,
+    pub expected_addresses: Vec<IpAddr>,
+    pub server: Option<SocketAddr>,
+    pub timeout: Duration,
+}
+
+impl DnsCheck {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            expected_addresses: Vec::new(),
+            server: None,
+            timeout: DEFAULT_DNS_TIMEOUT,
+        }
+    }
+
+    // Resolver can be given with or without a port, e.g. "10.0.0.2" or "[fd00::2]:5353"
+    pub fn parse_server(value: &str) -> Result<SocketAddr, String> {
+        if let Ok(address) = value.parse::<SocketAddr>() {
+            return Ok(address);
+        }
+        match value.parse::<IpAddr>() {
+            Ok(ip) => Ok(SocketAddr::new(ip, DNS_PORT)),
+            Err(_) => Err(format!("Invalid resolver address: {value}")),
+        }
+    }
+
+    fn create_resolver(&self) -> Result<TokioAsyncResolver, String> {
+        let mut options = ResolverOpts::default();
+        options.timeout = self.timeout;
+        options.attempts = 1;
+        options.cache_size = 0;
+        match self.server {
+            Some(server) => {
+                let name_servers =
+                    NameServerConfigGroup::from_ips_clear(&[server.ip()], server.port(), true);
+                let config = ResolverConfig::from_parts(None, Vec::new(), name_servers);
+                Ok(TokioAsyncResolver::tokio(config, options))
+            }
+            None => {
+                let (config, _) =
+                    hickory_resolver::system_conf::read_system_conf().map_err(|err| {
+                        format!("Could not read system resolver configuration: {err}")
+                    })?;
+                Ok(TokioAsyncResolver::tokio(config, options))
+            }
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        // The resolver is created for each run, so changes to the system configuration are picked up
+        let resolver = self.create_resolver()?;
+        let addresses = match resolver.lookup_ip(self.name.as_str()).await {
+            Ok(lookup) => Ok(lookup.iter().collect()),
+            Err(err) => Err(DnsFailure::from(err)),
+        };
+        get_dns_status(self, addresses)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum DnsFailure {
+    NotFound,
+    NoRecords,
+    TimedOut,
+    Other(String),
+}
+
+impl From<ResolveError> for DnsFailure {
+    fn from(err: ResolveError) -> Self {
+        match err.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NXDomain,
+                ..
+            } => DnsFailure::NotFound,
+            ResolveErrorKind::NoRecordsFound { .. } => DnsFailure::NoRecords,
+            ResolveErrorKind::Timeout => DnsFailure::TimedOut,
+            _ => DnsFailure::Other(err.to_string()),
+        }
+    }
+}
+
+fn format_addresses(addresses: &[IpAddr]) -> String {
+    addresses
+        .iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn get_dns_status(
+    check: &DnsCheck,
+    addresses: Result<Vec<IpAddr>, DnsFailure>,
+) -> Result<(), String> {
+    let name = &check.name;
+    let addresses = match addresses {
+        Ok(x) => x,
+        Err(DnsFailure::NotFound) => return Err(format!("{name} does not exist (NXDOMAIN)")),
+        Err(DnsFailure::NoRecords) => return Err(format!("{name} has no address records")),
+        Err(DnsFailure::TimedOut) => {
+            return Err(format!(
+                "Resolving {name} timed out after {}",
+                format_duration(check.timeout)
+            ))
+        }
+        Err(DnsFailure::Other(message)) => {
+            return Err(format!("Could not resolve {name}: {message}"))
+        }
+    };
+
+    // Every expected address has to be returned, but the resolver may return more, e.g. for round-robin records
+    let missing = check
+        .expected_addresses
+        .iter()
+        .filter(|x| !addresses.contains(x))
+        .copied()
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(format!(
+            "{name} resolved to {}, expected {}",
+            format_addresses(&addresses),
+            format_addresses(&missing)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolver_address_is_parsed() {
+        assert_eq!(
+            DnsCheck::parse_server("10.0.0.2"),
+            Ok("10.0.0.2:53".parse().unwrap())
+        );
+        assert_eq!(
+            DnsCheck::parse_server("10.0.0.2:5353"),
+            Ok("10.0.0.2:5353".parse().unwrap())
+        );
+        assert_eq!(
+            DnsCheck::parse_server("fd00::2"),
+            Ok("[fd00::2]:53".parse().unwrap())
+        );
+        assert_eq!(
+            DnsCheck::parse_server("[fd00::2]:5353"),
+            Ok("[fd00::2]:5353".parse().unwrap())
+        );
+        assert!(DnsCheck::parse_server("dns.local").is_err());
+    }
+
+    #[test]
+    fn dns_status_is_computed() {
+        fn run(
+            expected: &[&str],
+            addresses: Result<&[&str], DnsFailure>,
+            expected_status: Result<(), &str>,
+        ) {
+            let check = DnsCheck {
+                expected_addresses: expected.iter().map(|x| x.parse().unwrap()).collect(),
+                ..DnsCheck::new("db.internal".to_owned())
+            };
+            let addresses = addresses.map(|x| x.iter().map(|x| x.parse().unwrap()).collect());
+            let status = get_dns_status(&check, addresses);
+            assert_eq!(status, expected_status.map_err(|x| x.to_owned()));
+        }
+
+        run(&[], Ok(&["10.0.0.5"]), Ok(()));
+        run(&["10.0.0.5"], Ok(&["10.0.0.6", "10.0.0.5"]), Ok(()));
+        run(
+            &["10.0.0.5", "10.0.0.7"],
+            Ok(&["10.0.0.5", "10.0.0.6"]),
+            Err("db.internal resolved to 10.0.0.5, 10.0.0.6, expected 10.0.0.7"),
+        );
+        run(
+            &[],
+            Err(DnsFailure::NotFound),
+            Err("db.internal does not exist (NXDOMAIN)"),
+        );
+        run(
+            &[],
+            Err(DnsFailure::NoRecords),
+            Err("db.internal has no address records"),
+        );
+        run(
+            &[],
+            Err(DnsFailure::TimedOut),
+            Err("Resolving db.internal timed out after 2s"),
+        );
+        run(
+            &[],
+            Err(DnsFailure::Other("No connections available".to_owned())),
+            Err("Could not resolve db.internal: No connections available"),
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use check_mate_common::constants::*;
+use serde::Deserialize;
+use std::path::PathBuf;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(PartialEq, Debug)]
+pub struct DockerCheck {
+    pub container: String,
+    pub socket: PathBuf,
+}
+
+impl DockerCheck {
+    pub fn new(container: String) -> Self {
+        Self {
+            container,
+            socket: PathBuf::from(DEFAULT_DOCKER_SOCKET),
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let response = tokio::time::timeout(DOCKER_TIMEOUT, self.inspect_container())
+            .await
+            .map_err(|_| "Docker daemon did not respond".to_owned())?
+            .map_err(|err| {
+                format!(
+                    "Could not connect to Docker at {}: {err}",
+                    self.socket.display()
+                )
+            })?;
+        let container = parse_inspect_response(&self.container, &response)?;
+        get_container_status(&self.container, &container)
+    }
+
+    // HTTP/1.0 is used, so the daemon closes the connection after the response and doesn't use chunked encoding
+    async fn inspect_container(&self) -> std::io::Result<Vec<u8>> {
+        let mut stream = connect(&self.socket).await?;
+        let request = format!(
+            "GET /containers/{}/json HTTP/1.0\r\nHost: docker\r\n\r\n",
+            self.container
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok(response)
+    }
+}
+
+trait DockerStream: AsyncRead + AsyncWrite + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Unpin> DockerStream for T {}
+
+#[cfg(unix)]
+async fn connect(socket: &std::path::Path) -> std::io::Result<Box<dyn DockerStream>> {
+    Ok(Box::new(tokio::net::UnixStream::connect(socket).await?))
+}
+
+#[cfg(windows)]
+async fn connect(socket: &std::path::Path) -> std::io::Result<Box<dyn DockerStream>> {
+    let pipe = tokio::net::windows::named_pipe::ClientOptions::new().open(socket)?;
+    Ok(Box::new(pipe))
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerInspect {
+    state: ContainerState,
+    #[serde(default)]
+    restart_count: u64,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerState {
+    status: String,
+    #[serde(default)]
+    exit_code: i64,
+    health: Option<ContainerHealth>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct ContainerHealth {
+    status: String,
+    #[serde(default)]
+    log: Vec<HealthLogEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+struct HealthLogEntry {
+    exit_code: i64,
+    output: String,
+}
+
+#[derive(Deserialize)]
+struct DockerError {
+    message: String,
+}
+
+fn parse_inspect_response(container: &str, response: &[u8]) -> Result<ContainerInspect, String> {
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| "Invalid response from Docker".to_owned())?;
+    let status_code = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|x| x.parse::<u16>().ok())
+        .ok_or_else(|| "Invalid response from Docker".to_owned())?;
+
+    match status_code {
+        200 => serde_json::from_str(body)
+            .map_err(|err| format!("Invalid container description from Docker: {err}")),
+        404 => Err(format!("Container {container} does not exist")),
+        _ => {
+            let message = serde_json::from_str::<DockerError>(body)
+                .map(|x| x.message)
+                .unwrap_or_else(|_| body.trim().to_owned());
+            Err(format!("Docker returned status {status_code}: {message}"))
+        }
+    }
+}
+
+fn get_container_status(container: &str, inspect: &ContainerInspect) -> Result<(), String> {
+    let restarts = match inspect.restart_count {
+        1 => "restarted 1 time".to_owned(),
+        count => format!("restarted {count} times"),
+    };
+    let state = &inspect.state;
+    if state.status != "running" {
+        return Err(format!(
+            "{container} is {} (exit code {}, {restarts})",
+            state.status, state.exit_code
+        ));
+    }
+
+    // Containers without a HEALTHCHECK and ones which are still starting are only required to be running
+    match &state.health {
+        Some(health) if health.status == "unhealthy" => {
+            let last_result = health.log.last();
+            let exit_code = last_result.map_or(String::new(), |x| {
+                format!("health check exit code {}, ", x.exit_code)
+            });
+            let output = last_result
+                .map(|x| x.output.trim())
+                .filter(|x| !x.is_empty())
+                .map_or(String::new(), |x| format!(": {x}"));
+            Err(format!(
+                "{container} is unhealthy ({exit_code}{restarts}){output}"
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> ContainerInspect {
+        let response = format!("HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{json}");
+        parse_inspect_response("web", response.as_bytes()).expect("Response should be valid")
+    }
+
+    #[test]
+    fn inspect_response_is_parsed() {
+        let inspect = parse(
+            r#"{"Id":"4f2a","RestartCount":3,"State":{"Status":"running","Running":true,"ExitCode":0,
+            "Health":{"Status":"healthy","FailingStreak":0,"Log":[{"ExitCode":0,"Output":"ok\n"}]}}}"#,
+        );
+        assert_eq!(inspect.restart_count, 3);
+        assert_eq!(inspect.state.status, "running");
+        assert_eq!(inspect.state.health.unwrap().log[0].output, "ok\n");
+
+        let response = b"HTTP/1.0 404 Not Found\r\n\r\n{\"message\":\"No such container: web\"}";
+        let error = parse_inspect_response("web", response).unwrap_err();
+        assert_eq!(error, "Container web does not exist");
+
+        let response =
+            b"HTTP/1.0 500 Internal Server Error\r\n\r\n{\"message\":\"daemon is stopping\"}";
+        let error = parse_inspect_response("web", response).unwrap_err();
+        assert_eq!(error, "Docker returned status 500: daemon is stopping");
+    }
+
+    #[test]
+    fn container_status_is_computed() {
+        fn run(json: &str, expected: Result<(), &str>) {
+            let status = get_container_status("web", &parse(json));
+            assert_eq!(status, expected.map_err(|x| x.to_owned()));
+        }
+
+        run(r#"{"State":{"Status":"running","ExitCode":0}}"#, Ok(()));
+        run(
+            r#"{"RestartCount":0,"State":{"Status":"running","Health":{"Status":"starting","Log":[]}}}"#,
+            Ok(()),
+        );
+        run(
+            r#"{"RestartCount":5,"State":{"Status":"restarting","ExitCode":137}}"#,
+            Err("web is restarting (exit code 137, restarted 5 times)"),
+        );
+        run(
+            r#"{"RestartCount":1,"State":{"Status":"exited","ExitCode":1}}"#,
+            Err("web is exited (exit code 1, restarted 1 time)"),
+        );
+        run(
+            r#"{"RestartCount":2,"State":{"Status":"running","ExitCode":0,"Health":{"Status":"unhealthy",
+            "Log":[{"ExitCode":0,"Output":""},{"ExitCode":1,"Output":"curl: (7) Connection refused\n"}]}}}"#,
+            Err("web is unhealthy (health check exit code 1, restarted 2 times): curl: (7) Connection refused"),
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use super::format_size;
+use check_mate_common::format_duration;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[derive(PartialEq, Debug)]
+pub struct FileCheck {
+    pub path: PathBuf,
+    pub absent: bool,
+    pub max_age: Option<Duration>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+}
+
+impl FileCheck {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            absent: false,
+            max_age: None,
+            min_size: None,
+            max_size: None,
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let file = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => {
+                // Modification time in the future, e.g. after a clock change, is treated as a fresh file
+                let age = metadata
+                    .modified()
+                    .ok()
+                    .map(|x| SystemTime::now().duration_since(x).unwrap_or_default());
+                Some(FileState {
+                    age,
+                    size: metadata.len(),
+                })
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(format!("Could not access {}: {err}", self.path.display())),
+        };
+        get_file_status(self, file.as_ref())
+    }
+}
+
+struct FileState {
+    age: Option<Duration>,
+    size: u64,
+}
+
+// Formats elapsed time with two largest units, e.g. "1d 2h", so it can be compared with the threshold
+fn format_elapsed(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let units = [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)];
+    let mut parts = Vec::new();
+    let mut remaining = seconds;
+    for (unit, unit_seconds) in units {
+        let value = remaining / unit_seconds;
+        remaining %= unit_seconds;
+        if value > 0 || !parts.is_empty() {
+            parts.push(format!("{value}{unit}"));
+        }
+        if parts.len() == 2 {
+            break;
+        }
+    }
+    if parts.is_empty() {
+        "0s".to_owned()
+    } else {
+        parts.join(" ")
+    }
+}
+
+fn get_file_status(check: &FileCheck, file: Option<&FileState>) -> Result<(), String> {
+    let path = check.path.display();
+    let file = match (file, check.absent) {
+        (None, true) => return Ok(()),
+        (None, false) => return Err(format!("{path} does not exist")),
+        (Some(_), true) => return Err(format!("{path} exists")),
+        (Some(file), false) => file,
+    };
+
+    if let Some(max_age) = check.max_age {
+        match file.age {
+            Some(age) if age > max_age => {
+                return Err(format!(
+                    "{path} was modified {} ago, more than {}",
+                    format_elapsed(age),
+                    format_duration(max_age)
+                ))
+            }
+            Some(_) => (),
+            None => return Err(format!("Modification time of {path} is not available")),
+        }
+    }
+    if let Some(min_size) = check.min_size {
+        if file.size < min_size {
+            return Err(format!(
+                "{path} has {}, less than {}",
+                format_size(file.size),
+                format_size(min_size)
+            ));
+        }
+    }
+    if let Some(max_size) = check.max_size {
+        if file.size > max_size {
+            return Err(format!(
+                "{path} has {}, more than {}",
+                format_size(file.size),
+                format_size(max_size)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elapsed_time_is_formatted() {
+        assert_eq!(format_elapsed(Duration::ZERO), "0s");
+        assert_eq!(format_elapsed(Duration::from_secs(59)), "59s");
+        assert_eq!(format_elapsed(Duration::from_secs(61)), "1m 1s");
+        assert_eq!(format_elapsed(Duration::from_secs(3600)), "1h 0m");
+        assert_eq!(format_elapsed(Duration::from_secs(26 * 3600 + 59)), "1d 2h");
+    }
+
+    #[test]
+    fn file_status_is_computed() {
+        let check = FileCheck {
+            max_age: Some(Duration::from_secs(24 * 3600)),
+            min_size: Some(1),
+            max_size: Some(1 << 30),
+            ..FileCheck::new(PathBuf::from("/backups/db.sql.gz"))
+        };
+        let file = |age_hours: u64, size: u64| FileState {
+            age: Some(Duration::from_secs(age_hours * 3600)),
+            size,
+        };
+
+        assert_eq!(get_file_status(&check, Some(&file(3, 1024))), Ok(()));
+        assert_eq!(
+            get_file_status(&check, None),
+            Err("/backups/db.sql.gz does not exist".to_owned())
+        );
+        assert_eq!(
+            get_file_status(&check, Some(&file(26, 1024))),
+            Err("/backups/db.sql.gz was modified 1d 2h ago, more than 1d".to_owned())
+        );
+        assert_eq!(
+            get_file_status(&check, Some(&file(3, 0))),
+            Err("/backups/db.sql.gz has 0 B, less than 1 B".to_owned())
+        );
+        assert_eq!(
+            get_file_status(&check, Some(&file(3, 3 << 30))),
+            Err("/backups/db.sql.gz has 3.0 GiB, more than 1.0 GiB".to_owned())
+        );
+    }
+
+    #[test]
+    fn absent_file_is_ok_when_expected() {
+        let check = FileCheck {
+            absent: true,
+            ..FileCheck::new(PathBuf::from("/var/run/maintenance"))
+        };
+        let file = FileState { age: None, size: 0 };
+        assert_eq!(get_file_status(&check, None), Ok(()));
+        assert_eq!(
+            get_file_status(&check, Some(&file)),
+            Err("/var/run/maintenance exists".to_owned())
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use crate::action::OutputRegex;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Names of priorities, in order of their numeric values, which are the same as in syslog and journalctl
+const PRIORITY_NAMES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+#[derive(PartialEq, Debug)]
+pub struct LogCheck {
+    pub max_priority: u8,
+    pub pattern: Option<OutputRegex>,
+    pub source: Option<String>,
+    position: LogPosition,
+}
+
+// Position in the log after the last run, so each run reports only new entries. Entries are scanned from the start
+// of the client on the first run. It's a runtime state rather than a part of the config, so it's ignored in
+// comparisons.
+#[derive(Debug)]
+struct LogPosition {
+    started: SystemTime,
+    last: Mutex<Option<String>>,
+}
+
+impl PartialEq for LogPosition {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+struct LogEntry {
+    source: String,
+    message: String,
+    position: String,
+}
+
+impl Default for LogCheck {
+    fn default() -> Self {
+        Self {
+            max_priority: 3,
+            pattern: None,
+            source: None,
+            position: LogPosition {
+                started: SystemTime::now(),
+                last: Mutex::new(None),
+            },
+        }
+    }
+}
+
+impl LogCheck {
+    pub fn parse_priority(text: &str) -> Result<u8, String> {
+        let priority = match text.parse::<u8>() {
+            Ok(priority) => Some(priority).filter(|x| (*x as usize) < PRIORITY_NAMES.len()),
+            Err(_) => PRIORITY_NAMES
+                .iter()
+                .position(|x| *x == text)
+                .map(|x| x as u8),
+        };
+        priority.ok_or_else(|| {
+            format!(
+                "Invalid priority. Use a number from 0 to 7 or one of: {}",
+                PRIORITY_NAMES.join(", ")
+            )
+        })
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let last_position = self.position.last.lock().unwrap().clone();
+        let entries = self.read_entries(last_position).await?;
+        if let Some(entry) = entries.last() {
+            *self.position.last.lock().unwrap() = Some(entry.position.clone());
+        }
+        get_log_status(self, &entries)
+    }
+
+    #[cfg(target_os = "linux")]
+    async fn read_entries(&self, last_position: Option<String>) -> Result<Vec<LogEntry>, String> {
+        let mut command = tokio::process::Command::new("journalctl");
+        command.args(["--output=json", "--no-pager", "--quiet"]);
+        command.arg(format!("--priority={}", self.max_priority));
+        if let Some(unit) = &self.source {
+            command.arg(format!("--unit={unit}"));
+        }
+        match last_position {
+            Some(cursor) => command.arg(format!("--after-cursor={cursor}")),
+            None => command.arg(format!("--since=@{}", unix_seconds(self.position.started))),
+        };
+        let output = command
+            .output()
+            .await
+            .map_err(|err| format!("Could not run journalctl: {err}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Could not read journal: {}", stderr.trim()));
+        }
+        Ok(parse_journal_entries(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    #[cfg(windows)]
+    async fn read_entries(&self, last_position: Option<String>) -> Result<Vec<LogEntry>, String> {
+        let channel = self.source.as_deref().unwrap_or("System");
+        let range = match last_position {
+            Some(record_id) => format!("EventRecordID>{record_id}"),
+            None => {
+                let started = chrono::DateTime::<chrono::Utc>::from(self.position.started);
+                format!(
+                    "TimeCreated[@SystemTime>='{}']",
+                    started.format("%Y-%m-%dT%H:%M:%S%.3fZ")
+                )
+            }
+        };
+        let query = format!(
+            "*[System[Level>0 and Level<={} and {range}]]",
+            get_event_level(self.max_priority)
+        );
+        let output = tokio::process::Command::new("wevtutil")
+            .args(["qe", channel, &format!("/q:{query}"), "/f:RenderedXml"])
+            .output()
+            .await
+            .map_err(|err| format!("Could not run wevtutil: {err}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Could not read event log: {}", stderr.trim()));
+        }
+        Ok(parse_event_log_entries(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Journalctl prints one JSON object per line. Messages which are not valid UTF-8 are printed as arrays of bytes.
+#[cfg(any(target_os = "linux", test))]
+fn parse_journal_entries(text: &str) -> Vec<LogEntry> {
+    let field = |entry: &serde_json::Value, name: &str| match &entry[name] {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Array(bytes) => {
+            let bytes = bytes
+                .iter()
+                .filter_map(|x| x.as_u64().map(|x| x as u8))
+                .collect::<Vec<_>>();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        _ => None,
+    };
+    text.lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|entry| {
+            let source = field(&entry, "SYSLOG_IDENTIFIER")
+                .or_else(|| field(&entry, "_SYSTEMD_UNIT"))
+                .or_else(|| field(&entry, "_COMM"))
+                .unwrap_or_default();
+            Some(LogEntry {
+                source,
+                message: field(&entry, "MESSAGE").unwrap_or_default(),
+                position: field(&entry, "__CURSOR")?,
+            })
+        })
+        .collect()
+}
+
+// Event log levels are 1 for critical, 2 for error, 3 for warning, 4 for information and 5 for verbose
+#[cfg(any(windows, test))]
+fn get_event_level(max_priority: u8) -> u8 {
+    match max_priority {
+        0..=2 => 1,
+        3 => 2,
+        4 => 3,
+        5 | 6 => 4,
+        _ => 5,
+    }
+}
+
+// Events are printed by wevtutil as XML elements, one for each event, with the message in the rendering info
+#[cfg(any(windows, test))]
+fn parse_event_log_entries(text: &str) -> Vec<LogEntry> {
+    fn element<'a>(event: &'a str, name: &str) -> Option<&'a str> {
+        let start = event.find(&format!("<{name}>"))? + name.len() + 2;
+        let end = start + event[start..].find(&format!("</{name}>"))?;
+        Some(&event[start..end])
+    }
+    fn provider(event: &str) -> Option<&str> {
+        let start = event.find("<Provider Name='")? + "<Provider Name='".len();
+        let end = start + event[start..].find('\'')?;
+        Some(&event[start..end])
+    }
+    fn unescape(text: &str) -> String {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    }
+
+    text.split("<Event ")
+        .skip(1)
+        .filter_map(|event| {
+            Some(LogEntry {
+                source: unescape(provider(event).unwrap_or_default()),
+                message: unescape(element(event, "Message").unwrap_or_default()),
+                position: element(event, "EventRecordID")?.to_owned(),
+            })
+        })
+        .collect()
+}
+
+fn get_log_status(check: &LogCheck, entries: &[LogEntry]) -> Result<(), String> {
+    let matching = entries
+        .iter()
+        .filter(|entry| {
+            check
+                .pattern
+                .as_ref()
+                .is_none_or(|x| x.0.is_match(&entry.message))
+        })
+        .collect::<Vec<_>>();
+    let Some(last) = matching.last() else {
+        return Ok(());
+    };
+
+    let entries = match matching.len() {
+        1 => "1 new log entry".to_owned(),
+        count => format!("{count} new log entries"),
+    };
+    let message = last.message.lines().next().unwrap_or_default().trim();
+    Err(format!("{entries}, last: {}: {message}", last.source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_are_parsed() {
+        assert_eq!(LogCheck::parse_priority("err"), Ok(3));
+        assert_eq!(LogCheck::parse_priority("warning"), Ok(4));
+        assert_eq!(LogCheck::parse_priority("0"), Ok(0));
+        assert_eq!(LogCheck::parse_priority("7"), Ok(7));
+        assert!(LogCheck::parse_priority("8").is_err());
+        assert!(LogCheck::parse_priority("error").is_err());
+    }
+
+    #[test]
+    fn journal_entries_are_parsed() {
+        let text = concat!(
+            r#"{"__CURSOR":"s=1;i=10","PRIORITY":"3","SYSLOG_IDENTIFIER":"sshd","MESSAGE":"error: kex failed"}"#,
+            "\n",
+            r#"{"__CURSOR":"s=1;i=11","PRIORITY":"2","_COMM":"kernel","MESSAGE":[79,79,77,255]}"#,
+            "\n",
+        );
+        let entries = parse_journal_entries(text);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].source, "sshd");
+        assert_eq!(entries[0].message, "error: kex failed");
+        assert_eq!(entries[1].source, "kernel");
+        assert_eq!(entries[1].message, "OOM\u{FFFD}");
+        assert_eq!(entries[1].position, "s=1;i=11");
+    }
+
+    #[test]
+    fn event_log_entries_are_parsed() {
+        let text = "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System>\
+            <Provider Name='Service Control Manager' Guid='{555908d1}'/><EventID>7031</EventID><Level>2</Level>\
+            <EventRecordID>4242</EventRecordID></System><RenderingInfo Culture='en-US'>\
+            <Message>The Print Spooler service terminated &amp; was restarted.</Message></RenderingInfo></Event>";
+        let entries = parse_event_log_entries(text);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].source, "Service Control Manager");
+        assert_eq!(
+            entries[0].message,
+            "The Print Spooler service terminated & was restarted."
+        );
+        assert_eq!(entries[0].position, "4242");
+        assert_eq!(get_event_level(3), 2);
+    }
+
+    #[test]
+    fn log_status_is_computed() {
+        let entry = |source: &str, message: &str| LogEntry {
+            source: source.to_owned(),
+            message: message.to_owned(),
+            position: String::new(),
+        };
+        let entries = [
+            entry("sshd", "error: kex failed"),
+            entry("kernel", "Out of memory: Killed process 1234\nDetails"),
+        ];
+
+        let check = LogCheck::default();
+        assert_eq!(get_log_status(&check, &[]), Ok(()));
+        assert_eq!(
+            get_log_status(&check, &entries),
+            Err("2 new log entries, last: kernel: Out of memory: Killed process 1234".to_owned())
+        );
+
+        let check = LogCheck {
+            pattern: Some(OutputRegex::parse("kex").unwrap()),
+            ..LogCheck::default()
+        };
+        assert_eq!(
+            get_log_status(&check, &entries),
+            Err("1 new log entry, last: sshd: error: kex failed".to_owned())
+        );
+    }
+}
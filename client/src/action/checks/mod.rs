@@ -0,0 +1,114 @@
+// Built-in checks, which are run by the watch loop instead of an external command. They report statuses directly, so
+// they don't depend on programs being installed or on the format of their output, which can differ between platforms
+// and locales.
+
+mod disk;
+mod dns;
+mod docker;
+mod file;
+#[cfg(any(target_os = "linux", windows))]
+mod log;
+mod ping;
+mod plugin;
+mod process;
+#[cfg(feature = "smart")]
+mod smart;
+mod system;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+mod systemd;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+use super::watch_action::WatchStatus;
+pub use disk::DiskCheck;
+pub use dns::DnsCheck;
+pub use docker::DockerCheck;
+pub use file::FileCheck;
+#[cfg(any(target_os = "linux", windows))]
+pub use log::LogCheck;
+pub use ping::PingCheck;
+pub use plugin::PluginCheck;
+pub use process::ProcessCheck;
+#[cfg(feature = "smart")]
+pub use smart::SmartCheck;
+pub use system::SystemCheck;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+pub use systemd::SystemdCheck;
+#[cfg(feature = "wasm")]
+pub use wasm::WasmCheck;
+
+#[derive(PartialEq, Debug)]
+pub enum BuiltinCheck {
+    Ping(PingCheck),
+    Dns(DnsCheck),
+    Disk(DiskCheck),
+    System(SystemCheck),
+    File(FileCheck),
+    Process(ProcessCheck),
+    Docker(DockerCheck),
+    Plugin(PluginCheck),
+    #[cfg(any(target_os = "linux", windows))]
+    Log(LogCheck),
+    #[cfg(all(target_os = "linux", feature = "systemd"))]
+    Systemd(SystemdCheck),
+    #[cfg(feature = "smart")]
+    Smart(SmartCheck),
+    #[cfg(feature = "wasm")]
+    Wasm(WasmCheck),
+}
+
+impl BuiltinCheck {
+    pub(crate) async fn run(&self) -> WatchStatus {
+        match self {
+            BuiltinCheck::Ping(check) => check.run().await.into(),
+            BuiltinCheck::Dns(check) => check.run().await.into(),
+            BuiltinCheck::Disk(check) => check.run().await.into(),
+            BuiltinCheck::System(check) => check.run().await.into(),
+            BuiltinCheck::File(check) => check.run().await.into(),
+            BuiltinCheck::Process(check) => check.run().await.into(),
+            BuiltinCheck::Docker(check) => check.run().await.into(),
+            BuiltinCheck::Plugin(check) => check.run().await,
+            #[cfg(any(target_os = "linux", windows))]
+            BuiltinCheck::Log(check) => check.run().await.into(),
+            #[cfg(all(target_os = "linux", feature = "systemd"))]
+            BuiltinCheck::Systemd(check) => check.run().await.into(),
+            #[cfg(feature = "smart")]
+            BuiltinCheck::Smart(check) => check.run().await.into(),
+            #[cfg(feature = "wasm")]
+            BuiltinCheck::Wasm(check) => check.run().await,
+        }
+    }
+}
+
+// Formats a size in bytes with a binary unit, e.g. "1.5 GiB"
+fn format_size(bytes: u64) -> String {
+    let units = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next_unit in units {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+    if unit == "B" {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {unit}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_are_formatted() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.0 KiB");
+        assert_eq!(format_size(1536 << 20), "1.5 GiB");
+        assert_eq!(format_size(3 << 40), "3.0 TiB");
+    }
+}
@@ -0,0 +1,291 @@
+use check_mate_common::constants::*;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::{ErrorKind, Read};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[derive(PartialEq, Debug)]
+pub struct PingCheck {
+    pub host: String,
+    pub count: u32,
+    pub timeout: Duration,
+    pub max_loss_percent: u8,
+    pub max_rtt: Option<Duration>,
+}
+
+impl PingCheck {
+    pub fn new(host: String) -> Self {
+        Self {
+            host,
+            count: DEFAULT_PING_COUNT,
+            timeout: DEFAULT_PING_TIMEOUT,
+            max_loss_percent: DEFAULT_PING_MAX_LOSS_PERCENT,
+            max_rtt: None,
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        // Sockets are used in a blocking manner, so the pings are sent from a separate thread
+        let host = self.host.clone();
+        let count = self.count;
+        let timeout = self.timeout;
+        let round_trip_times = tokio::task::spawn_blocking(move || ping(&host, count, timeout))
+            .await
+            .map_err(|err| format!("Ping was interrupted: {err}"))??;
+        get_ping_status(self, &round_trip_times)
+    }
+}
+
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV4_ECHO_REPLY: u8 = 0;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+const ICMP_HEADER_SIZE: usize = 8;
+
+fn compute_checksum(packet: &[u8]) -> u16 {
+    let mut sum = packet
+        .chunks(2)
+        .map(|x| u16::from_be_bytes([x[0], *x.get(1).unwrap_or(&0)]) as u32)
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+// Identifier of the packet is overwritten by the kernel for unprivileged sockets, so replies are matched by the
+// sequence number and a token in the payload instead.
+fn create_echo_request(ipv6: bool, sequence: u16, token: u64) -> Vec<u8> {
+    let request_type = if ipv6 {
+        ICMPV6_ECHO_REQUEST
+    } else {
+        ICMPV4_ECHO_REQUEST
+    };
+    let mut packet = vec![request_type, 0, 0, 0, 0, 0];
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(&token.to_be_bytes());
+
+    // Checksum of ICMPv6 covers the IP pseudo-header, so it's always filled by the kernel
+    if !ipv6 {
+        let checksum = compute_checksum(&packet);
+        packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    }
+    packet
+}
+
+// Returns the sequence number if the packet is an echo reply carrying the token. Some sockets (raw IPv4 sockets and
+// unprivileged sockets on macOS) receive the IPv4 header along with the ICMP message, which is skipped.
+fn parse_echo_reply(ipv6: bool, mut packet: &[u8], token: u64) -> Option<u16> {
+    if !ipv6 && packet.first().is_some_and(|x| x >> 4 == 4) {
+        let header_size = (packet[0] & 0xf) as usize * 4;
+        packet = packet.get(header_size..)?;
+    }
+    let reply_type = if ipv6 {
+        ICMPV6_ECHO_REPLY
+    } else {
+        ICMPV4_ECHO_REPLY
+    };
+    if packet.len() < ICMP_HEADER_SIZE + 8 || packet[0] != reply_type || packet[1] != 0 {
+        return None;
+    }
+    let payload = &packet[ICMP_HEADER_SIZE..ICMP_HEADER_SIZE + 8];
+    if payload != token.to_be_bytes() {
+        return None;
+    }
+    Some(u16::from_be_bytes([packet[6], packet[7]]))
+}
+
+fn create_socket(address: &SocketAddr) -> Result<Socket, String> {
+    let (domain, protocol) = match address {
+        SocketAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+        SocketAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6),
+    };
+
+    // Unprivileged ICMP sockets are preferred, but they may be disabled, e.g. by net.ipv4.ping_group_range on Linux
+    let socket = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+        Ok(x) => x,
+        Err(_) => Socket::new(domain, Type::RAW, Some(protocol)).map_err(|err| {
+            format!("Could not create ICMP socket: {err}. Unprivileged ping may be disabled on this system")
+        })?,
+    };
+    socket
+        .connect(&SockAddr::from(*address))
+        .map_err(|err| format!("Could not connect ICMP socket to {}: {err}", address.ip()))?;
+    Ok(socket)
+}
+
+// Sends echo requests one by one and returns round-trip times of the ones which were answered
+fn ping(host: &str, count: u32, timeout: Duration) -> Result<Vec<Duration>, String> {
+    let address = (host, 0)
+        .to_socket_addrs()
+        .map_err(|err| format!("Could not resolve {host}: {err}"))?
+        .next()
+        .ok_or_else(|| format!("Could not resolve {host}: no addresses found"))?;
+    let ipv6 = matches!(address.ip(), IpAddr::V6(_));
+    let socket = create_socket(&address)?;
+
+    let token = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+        ^ std::process::id() as u64;
+    let mut round_trip_times = Vec::new();
+    for sequence in 0..count {
+        if sequence > 0 {
+            std::thread::sleep(PING_INTERVAL);
+        }
+
+        let sequence = sequence as u16;
+        let request = create_echo_request(ipv6, sequence, token);
+        let start = Instant::now();
+        socket
+            .send(&request)
+            .map_err(|err| format!("Could not send ICMP echo request to {host}: {err}"))?;
+
+        let mut buffer = [0u8; 1024];
+        while let Some(remaining) = timeout.checked_sub(start.elapsed()) {
+            if remaining.is_zero() {
+                break;
+            }
+            socket
+                .set_read_timeout(Some(remaining))
+                .map_err(|err| format!("Could not set ICMP socket timeout: {err}"))?;
+            let size = match (&socket).read(&mut buffer) {
+                Ok(x) => x,
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    break
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                // Errors like unreachable host are reported for the connected socket, which is just a lost packet
+                Err(_) => break,
+            };
+            if parse_echo_reply(ipv6, &buffer[..size], token) == Some(sequence) {
+                round_trip_times.push(start.elapsed());
+                break;
+            }
+        }
+    }
+    Ok(round_trip_times)
+}
+
+fn format_round_trip_time(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn get_ping_status(check: &PingCheck, round_trip_times: &[Duration]) -> Result<(), String> {
+    let sent = check.count;
+    let lost = sent - round_trip_times.len() as u32;
+    if sent > 0 && lost == sent {
+        return Err(format!(
+            "{} is unreachable ({lost} of {sent} packets lost)",
+            check.host
+        ));
+    }
+    let loss_percent = (lost * 100).checked_div(sent).unwrap_or(0);
+    if loss_percent > check.max_loss_percent as u32 {
+        return Err(format!(
+            "Packet loss to {} was {loss_percent}% ({lost} of {sent} packets lost)",
+            check.host
+        ));
+    }
+
+    if let Some(max_rtt) = check.max_rtt {
+        let total = round_trip_times.iter().sum::<Duration>();
+        let average = total / round_trip_times.len().max(1) as u32;
+        if average > max_rtt {
+            return Err(format!(
+                "Average round-trip time to {} was {}, more than {}",
+                check.host,
+                format_round_trip_time(average),
+                format_round_trip_time(max_rtt)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_request_has_valid_checksum() {
+        let packet = create_echo_request(false, 3, 0x0102030405060708);
+        assert_eq!(&packet[..2], [ICMPV4_ECHO_REQUEST, 0]);
+        assert_eq!(&packet[6..8], [0, 3]);
+        assert_eq!(compute_checksum(&packet), 0);
+
+        let packet = create_echo_request(true, 3, 0x0102030405060708);
+        assert_eq!(&packet[..4], [ICMPV6_ECHO_REQUEST, 0, 0, 0]);
+    }
+
+    #[test]
+    fn echo_reply_is_parsed() {
+        let token = 0x0102030405060708;
+        let mut reply = create_echo_request(false, 5, token);
+        reply[0] = ICMPV4_ECHO_REPLY;
+        assert_eq!(parse_echo_reply(false, &reply, token), Some(5));
+        assert_eq!(parse_echo_reply(false, &reply, token + 1), None);
+
+        let mut reply_with_ip_header = vec![0x45];
+        reply_with_ip_header.extend_from_slice(&[0; 19]);
+        reply_with_ip_header.extend_from_slice(&reply);
+        assert_eq!(
+            parse_echo_reply(false, &reply_with_ip_header, token),
+            Some(5)
+        );
+
+        let request = create_echo_request(false, 5, token);
+        assert_eq!(parse_echo_reply(false, &request, token), None);
+
+        let mut reply = create_echo_request(true, 7, token);
+        reply[0] = ICMPV6_ECHO_REPLY;
+        assert_eq!(parse_echo_reply(true, &reply, token), Some(7));
+        assert_eq!(parse_echo_reply(true, &reply[..10], token), None);
+    }
+
+    #[test]
+    fn ping_status_is_computed() {
+        fn run(
+            max_loss_percent: u8,
+            max_rtt: Option<u64>,
+            rtts: &[u64],
+            expected: Result<(), &str>,
+        ) {
+            let check = PingCheck {
+                count: 4,
+                max_loss_percent,
+                max_rtt: max_rtt.map(Duration::from_millis),
+                ..PingCheck::new("db".to_owned())
+            };
+            let rtts = rtts
+                .iter()
+                .map(|x| Duration::from_millis(*x))
+                .collect::<Vec<_>>();
+            let status = get_ping_status(&check, &rtts);
+            assert_eq!(status, expected.map_err(|x| x.to_owned()));
+        }
+
+        run(0, None, &[1, 1, 1, 1], Ok(()));
+        run(
+            0,
+            None,
+            &[1, 1, 1],
+            Err("Packet loss to db was 25% (1 of 4 packets lost)"),
+        );
+        run(25, None, &[1, 1, 1], Ok(()));
+        run(
+            100,
+            None,
+            &[],
+            Err("db is unreachable (4 of 4 packets lost)"),
+        );
+        run(0, Some(10), &[5, 10, 15, 10], Ok(()));
+        run(
+            0,
+            Some(10),
+            &[5, 10, 15, 30],
+            Err("Average round-trip time to db was 15.0ms, more than 10.0ms"),
+        );
+    }
+}
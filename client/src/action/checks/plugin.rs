@@ -0,0 +1,236 @@
+// Plugins are standalone executables implementing a check. On each run, the plugin is started with its arguments and
+// receives a single JSON request on stdin:
+//
+//     {"version": 1, "state": <state returned by the previous run or null>}
+//
+// It has to print a single JSON response to stdout:
+//
+//     {
+//         "status": "ok" | "warning" | "critical" | "unknown",
+//         "message": "Queue is growing",
+//         "metrics": [{"name": "queue", "value": 1200}, {"name": "latency", "value": 2.5, "unit": "s"}],
+//         "state": <any value, passed back on the next run>
+//     }
+//
+// Only the status is required. Non-ok statuses are reported like in the Nagios mode, with metrics appended to the
+// message, e.g. "Queue is growing (queue=1200, latency=2500ms)". Warnings are reported as warnings, critical and unknown
+// statuses as errors. The exit code of the plugin is ignored.
+
+use crate::action::{Action, WatchStatus};
+use check_mate_common::{constants::*, format_duration};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+#[derive(PartialEq, Debug)]
+pub struct PluginCheck {
+    pub path: PathBuf,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+    state: PluginState,
+}
+
+// State returned by the plugin, which is kept between runs. It's a runtime state rather than a part of the config, so
+// it's ignored in comparisons.
+#[derive(Debug, Default)]
+pub(super) struct PluginState(pub(super) Mutex<serde_json::Value>);
+
+impl PartialEq for PluginState {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum PluginStatus {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+#[derive(Deserialize, Debug)]
+struct PluginMetric {
+    name: String,
+    value: f64,
+    #[serde(default)]
+    unit: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub(super) struct PluginResponse {
+    status: PluginStatus,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    metrics: Vec<PluginMetric>,
+    #[serde(default)]
+    pub(super) state: serde_json::Value,
+}
+
+impl PluginCheck {
+    pub fn new(path: PathBuf, args: Vec<String>, timeout: Duration) -> Self {
+        Self {
+            path,
+            args,
+            timeout,
+            state: PluginState::default(),
+        }
+    }
+
+    pub(crate) async fn run(&self) -> WatchStatus {
+        match self.run_plugin().await {
+            Ok(response) => {
+                let status = get_plugin_status(&response);
+                *self.state.0.lock().unwrap() = response.state;
+                status
+            }
+            Err(message) => WatchStatus::error(message),
+        }
+    }
+
+    async fn run_plugin(&self) -> Result<PluginResponse, String> {
+        let request = serde_json::json!({
+            "version": PLUGIN_PROTOCOL_VERSION,
+            "state": *self.state.0.lock().unwrap(),
+        });
+        let path = self.path.to_string_lossy();
+        let mut plugin = tokio::process::Command::new(&self.path)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| Action::describe_spawn_error(&path, err))?;
+
+        // Plugins are not required to read the request, so errors of writing it are ignored
+        let mut stdin = plugin.stdin.take().unwrap();
+        let _ = stdin.write_all(request.to_string().as_bytes()).await;
+        drop(stdin);
+
+        let output = tokio::time::timeout(self.timeout, plugin.wait_with_output())
+            .await
+            .map_err(|_| format!("Plugin timed out after {}", format_duration(self.timeout)))?
+            .map_err(|err| format!("Could not run plugin: {err}"))?;
+
+        parse_plugin_response(&output.stdout, &output.stderr)
+    }
+}
+
+pub(super) fn parse_plugin_response(
+    stdout: &[u8],
+    stderr: &[u8],
+) -> Result<PluginResponse, String> {
+    serde_json::from_slice(stdout).map_err(|err| {
+        // Plugins which crash usually print the reason to stderr
+        let stderr = String::from_utf8_lossy(stderr);
+        match stderr.lines().map(str::trim).find(|x| !x.is_empty()) {
+            Some(line) => format!("Invalid plugin response: {err}, stderr: {line}"),
+            None => format!("Invalid plugin response: {err}"),
+        }
+    })
+}
+
+fn format_metric(metric: &PluginMetric) -> String {
+    let seconds = match metric.unit.as_str() {
+        "s" => Some(metric.value),
+        "ms" => Some(metric.value / 1000.0),
+        _ => None,
+    };
+    match seconds {
+        Some(seconds) if seconds >= 0.0 => format!(
+            "{}={}",
+            metric.name,
+            format_duration(Duration::from_secs_f64(seconds))
+        ),
+        _ => format!("{}={}{}", metric.name, metric.value, metric.unit),
+    }
+}
+
+pub(super) fn get_plugin_status(response: &PluginResponse) -> WatchStatus {
+    let (status, warning) = match response.status {
+        PluginStatus::Ok => return WatchStatus::ok(),
+        PluginStatus::Warning => ("warning", true),
+        PluginStatus::Critical => ("critical", false),
+        PluginStatus::Unknown => ("unknown", false),
+    };
+    let mut message = match response.message.trim() {
+        "" => format!("Plugin status is {status}"),
+        message => message.to_owned(),
+    };
+    if !response.metrics.is_empty() {
+        let metrics = response
+            .metrics
+            .iter()
+            .map(format_metric)
+            .collect::<Vec<_>>();
+        message = format!("{message} ({})", metrics.join(", "));
+    }
+    match warning {
+        true => WatchStatus::warning(message),
+        false => WatchStatus::error(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(json: &str, expected: WatchStatus) {
+        let response =
+            parse_plugin_response(json.as_bytes(), b"").expect("Response should be valid");
+        let status = get_plugin_status(&response);
+        assert_eq!(status, expected);
+    }
+
+    #[test]
+    fn plugin_status_is_computed() {
+        run(
+            r#"{"status":"ok","message":"Queue is empty"}"#,
+            WatchStatus::ok(),
+        );
+        run(
+            r#"{"status":"warning","message":"Queue is growing"}"#,
+            WatchStatus::warning("Queue is growing".to_owned()),
+        );
+        run(
+            r#"{"status":"critical","message":"Queue is stuck","metrics":[{"name":"queue","value":1200},
+            {"name":"latency","value":2.5,"unit":"s"},{"name":"usage","value":97.5,"unit":"%"}]}"#,
+            WatchStatus::error("Queue is stuck (queue=1200, latency=2500ms, usage=97.5%)".to_owned()),
+        );
+        run(
+            r#"{"status":"unknown","message":"Broker is not reachable","state":{"offset":42}}"#,
+            WatchStatus::error("Broker is not reachable".to_owned()),
+        );
+        run(
+            r#"{"status":"critical"}"#,
+            WatchStatus::error("Plugin status is critical".to_owned()),
+        );
+    }
+
+    #[test]
+    fn invalid_plugin_response_is_reported() {
+        let error =
+            parse_plugin_response(b"", b"Traceback (most recent call last):\n").unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid plugin response: EOF while parsing a value at line 1 column 0, \
+            stderr: Traceback (most recent call last):"
+        );
+
+        let error = parse_plugin_response(br#"{"status":"fine"}"#, b"").unwrap_err();
+        assert!(error.starts_with("Invalid plugin response: unknown variant `fine`"));
+    }
+
+    #[test]
+    fn plugin_state_is_returned() {
+        let json = r#"{"status":"ok","state":{"offset":42}}"#;
+        let response = parse_plugin_response(json.as_bytes(), b"").unwrap();
+        assert_eq!(response.state, serde_json::json!({"offset": 42}));
+    }
+}
@@ -0,0 +1,169 @@
+use check_mate_common::glob_matches;
+use std::path::Path;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, ThreadKind, UpdateKind};
+
+#[derive(PartialEq, Debug)]
+pub struct ProcessCheck {
+    pub pattern: String,
+    pub full_command: bool,
+    pub min_count: usize,
+    pub max_count: Option<usize>,
+}
+
+impl ProcessCheck {
+    pub fn new(pattern: String) -> Self {
+        Self {
+            pattern,
+            full_command: false,
+            min_count: 1,
+            max_count: None,
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let mut system = sysinfo::System::new();
+        let refresh_kind = ProcessRefreshKind::nothing().with_cmd(UpdateKind::Always);
+        system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh_kind);
+
+        // Threads are listed as processes on Linux, so they are skipped to not count a process multiple times. The
+        // client itself is skipped, because with --full its command line contains the pattern.
+        let own_pid = sysinfo::get_current_pid().ok();
+        let count = system
+            .processes()
+            .values()
+            .filter(|process| process.thread_kind() != Some(ThreadKind::Userland))
+            .filter(|process| Some(process.pid()) != own_pid)
+            .filter(|process| {
+                let command_line = process
+                    .cmd()
+                    .iter()
+                    .map(|x| x.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>();
+                process_matches(self, &process.name().to_string_lossy(), &command_line)
+            })
+            .count();
+        get_process_status(self, count)
+    }
+}
+
+// Names of processes can be truncated, e.g. to 15 characters on Linux, so the name of the executable from the command
+// line is also matched
+fn process_matches(check: &ProcessCheck, name: &str, command_line: &[String]) -> bool {
+    if check.full_command {
+        return glob_matches(&check.pattern, &command_line.join(" "));
+    }
+    let executable_name = command_line
+        .first()
+        .and_then(|x| Path::new(x).file_name())
+        .map(|x| x.to_string_lossy());
+    glob_matches(&check.pattern, name)
+        || executable_name.is_some_and(|x| glob_matches(&check.pattern, &x))
+}
+
+fn get_process_status(check: &ProcessCheck, count: usize) -> Result<(), String> {
+    let pattern = &check.pattern;
+    let processes = |count: usize| match count {
+        1 => "1 process".to_owned(),
+        _ => format!("{count} processes"),
+    };
+    if count == 0 && check.min_count > 0 {
+        return Err(format!("No process matching {pattern} is running"));
+    }
+    if count < check.min_count {
+        return Err(format!(
+            "Only {} matching {pattern} running, expected at least {}",
+            processes(count),
+            check.min_count
+        ));
+    }
+    if let Some(max_count) = check.max_count {
+        if count > max_count {
+            return Err(format!(
+                "{} matching {pattern} running, expected at most {max_count}",
+                processes(count)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn processes_are_matched() {
+        fn run(pattern: &str, full_command: bool, name: &str, command_line: &str, expected: bool) {
+            let check = ProcessCheck {
+                full_command,
+                ..ProcessCheck::new(pattern.to_owned())
+            };
+            let command_line = command_line
+                .split(' ')
+                .map(|x| x.to_owned())
+                .collect::<Vec<_>>();
+            assert_eq!(process_matches(&check, name, &command_line), expected);
+        }
+
+        run("nginx", false, "nginx", "/usr/sbin/nginx -g daemon", true);
+        run("ngin*", false, "nginx", "/usr/sbin/nginx -g daemon", true);
+        run("apache", false, "nginx", "/usr/sbin/nginx -g daemon", false);
+        run(
+            "prometheus-node-exporter",
+            false,
+            "prometheus-node",
+            "/usr/bin/prometheus-node-exporter",
+            true,
+        );
+        run("python3", false, "python3", "python3 worker.py", true);
+        run("*worker.py*", false, "python3", "python3 worker.py", false);
+        run(
+            "*worker.py*",
+            true,
+            "python3",
+            "python3 worker.py --queue mail",
+            true,
+        );
+    }
+
+    #[test]
+    fn process_status_is_computed() {
+        fn run(
+            min_count: usize,
+            max_count: Option<usize>,
+            count: usize,
+            expected: Result<(), &str>,
+        ) {
+            let check = ProcessCheck {
+                min_count,
+                max_count,
+                ..ProcessCheck::new("worker".to_owned())
+            };
+            let status = get_process_status(&check, count);
+            assert_eq!(status, expected.map_err(|x| x.to_owned()));
+        }
+
+        run(1, None, 1, Ok(()));
+        run(1, None, 0, Err("No process matching worker is running"));
+        run(
+            4,
+            None,
+            1,
+            Err("Only 1 process matching worker running, expected at least 4"),
+        );
+        run(4, Some(4), 4, Ok(()));
+        run(
+            1,
+            Some(4),
+            5,
+            Err("5 processes matching worker running, expected at most 4"),
+        );
+        run(0, Some(0), 0, Ok(()));
+        run(
+            0,
+            Some(0),
+            1,
+            Err("1 process matching worker running, expected at most 0"),
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use serde::Deserialize;
+
+#[derive(PartialEq, Debug)]
+pub struct SmartCheck {
+    pub device: String,
+    pub device_type: Option<String>,
+}
+
+impl SmartCheck {
+    pub fn new(device: String) -> Self {
+        Self {
+            device,
+            device_type: None,
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let mut command = tokio::process::Command::new("smartctl");
+        command.args(["--json", "--health", "--attributes"]);
+        if let Some(device_type) = &self.device_type {
+            command.arg(format!("--device={device_type}"));
+        }
+        command.arg(&self.device);
+        let output = command
+            .output()
+            .await
+            .map_err(|err| format!("Could not run smartctl: {err}"))?;
+
+        let report = serde_json::from_slice::<SmartReport>(&output.stdout)
+            .map_err(|err| format!("Invalid output of smartctl: {err}"))?;
+        get_smart_status(self, output.status.code().unwrap_or(-1), &report)
+    }
+}
+
+// Subset of the JSON output of smartctl 7.0 or newer. ATA and NVMe devices report their health in different sections.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct SmartReport {
+    smartctl: SmartctlInfo,
+    smart_status: Option<SmartStatus>,
+    ata_smart_attributes: Option<AtaAttributes>,
+    nvme_smart_health_information_log: Option<NvmeHealth>,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct SmartctlInfo {
+    messages: Vec<SmartctlMessage>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SmartctlMessage {
+    string: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct SmartStatus {
+    passed: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct AtaAttributes {
+    table: Vec<AtaAttribute>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AtaAttribute {
+    id: u32,
+    name: String,
+    value: u64,
+    thresh: u64,
+    #[serde(default)]
+    when_failed: String,
+    raw: AtaRawValue,
+}
+
+#[derive(Deserialize, Debug)]
+struct AtaRawValue {
+    value: u64,
+}
+
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+struct NvmeHealth {
+    critical_warning: u64,
+    media_errors: u64,
+}
+
+// Sectors which could not be read and are waiting to be remapped. They are not failures according to thresholds of
+// the vendor, but usually precede them.
+const ATA_PENDING_SECTORS_ID: u32 = 197;
+const ATA_OFFLINE_UNCORRECTABLE_ID: u32 = 198;
+
+// Bits 0 and 1 of the exit code of smartctl mean that the device could not be queried. Other bits describe its
+// health, which is read from the report instead.
+const SMARTCTL_QUERY_FAILED_MASK: i32 = 0b11;
+
+// All problems are reported, so a single status describes everything wrong with the disk
+fn get_smart_status(
+    check: &SmartCheck,
+    exit_code: i32,
+    report: &SmartReport,
+) -> Result<(), String> {
+    let device = &check.device;
+    if exit_code < 0 || exit_code & SMARTCTL_QUERY_FAILED_MASK != 0 {
+        let messages = report
+            .smartctl
+            .messages
+            .iter()
+            .map(|x| x.string.as_str())
+            .collect::<Vec<_>>();
+        return Err(format!("Could not query {device}: {}", messages.join("; ")));
+    }
+
+    let mut errors = Vec::new();
+    if report.smart_status.as_ref().is_some_and(|x| !x.passed) {
+        errors.push("overall health self-assessment failed".to_owned());
+    }
+    if let Some(attributes) = &report.ata_smart_attributes {
+        for attribute in &attributes.table {
+            if attribute.when_failed == "now" {
+                errors.push(format!(
+                    "{} is failing (value {}, threshold {})",
+                    attribute.name, attribute.value, attribute.thresh
+                ));
+            }
+        }
+        let count = |id: u32| {
+            attributes
+                .table
+                .iter()
+                .find(|x| x.id == id)
+                .map_or(0, |x| x.raw.value)
+        };
+        let pending = count(ATA_PENDING_SECTORS_ID);
+        if pending > 0 {
+            errors.push(format!("{pending} pending sectors"));
+        }
+        let uncorrectable = count(ATA_OFFLINE_UNCORRECTABLE_ID);
+        if uncorrectable > 0 {
+            errors.push(format!("{uncorrectable} uncorrectable sectors"));
+        }
+    }
+    if let Some(health) = &report.nvme_smart_health_information_log {
+        if health.critical_warning != 0 {
+            errors.push(format!(
+                "critical warning 0x{:02x}",
+                health.critical_warning
+            ));
+        }
+        if health.media_errors > 0 {
+            errors.push(format!("{} media errors", health.media_errors));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{device}: {}", errors.join("; ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(exit_code: i32, json: &str, expected: Result<(), &str>) {
+        let check = SmartCheck::new("/dev/sda".to_owned());
+        let report = serde_json::from_str::<SmartReport>(json).expect("Report should be valid");
+        let status = get_smart_status(&check, exit_code, &report);
+        assert_eq!(status, expected.map_err(|x| x.to_owned()));
+    }
+
+    #[test]
+    fn ata_status_is_computed() {
+        let attribute = |id: u32, name: &str, value: u64, when_failed: &str, raw: u64| {
+            format!(
+                r#"{{"id":{id},"name":"{name}","value":{value},"worst":{value},"thresh":10,
+                "when_failed":"{when_failed}","raw":{{"value":{raw},"string":"{raw}"}}}}"#
+            )
+        };
+        let report = |passed: bool, attributes: &[String]| {
+            format!(
+                r#"{{"smartctl":{{"exit_status":0}},"smart_status":{{"passed":{passed}}},
+                "ata_smart_attributes":{{"revision":16,"table":[{}]}}}}"#,
+                attributes.join(",")
+            )
+        };
+
+        let healthy = [
+            attribute(5, "Reallocated_Sector_Ct", 100, "", 0),
+            attribute(197, "Current_Pending_Sector", 100, "", 0),
+        ];
+        run(0, &report(true, &healthy), Ok(()));
+
+        let failing = [
+            attribute(5, "Reallocated_Sector_Ct", 5, "now", 2000),
+            attribute(187, "Reported_Uncorrect", 90, "past", 3),
+            attribute(197, "Current_Pending_Sector", 100, "", 8),
+            attribute(198, "Offline_Uncorrectable", 100, "", 2),
+        ];
+        run(
+            8,
+            &report(false, &failing),
+            Err("/dev/sda: overall health self-assessment failed; \
+                Reallocated_Sector_Ct is failing (value 5, threshold 10); 8 pending sectors; \
+                2 uncorrectable sectors"),
+        );
+    }
+
+    #[test]
+    fn nvme_status_is_computed() {
+        let report = |critical_warning: u64, media_errors: u64| {
+            format!(
+                r#"{{"smart_status":{{"passed":true}},"nvme_smart_health_information_log":
+                {{"critical_warning":{critical_warning},"percentage_used":3,"media_errors":{media_errors}}}}}"#
+            )
+        };
+        run(0, &report(0, 0), Ok(()));
+        run(
+            0,
+            &report(4, 12),
+            Err("/dev/sda: critical warning 0x04; 12 media errors"),
+        );
+    }
+
+    #[test]
+    fn query_failure_is_reported() {
+        let json = r#"{"smartctl":{"messages":[{"string":"Smartctl open device: /dev/sda failed: Permission denied",
+            "severity":"error"}],"exit_status":2}}"#;
+        run(
+            2,
+            json,
+            Err("Could not query /dev/sda: Smartctl open device: /dev/sda failed: Permission denied"),
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use super::format_size;
+use check_mate_common::constants::*;
+
+#[derive(PartialEq, Debug)]
+pub struct SystemCheck {
+    pub max_load: Option<f64>,
+    pub max_cpu_percent: Option<u8>,
+    pub max_memory_percent: Option<u8>,
+    pub max_swap_percent: Option<u8>,
+}
+
+impl Default for SystemCheck {
+    fn default() -> Self {
+        Self {
+            max_load: None,
+            max_cpu_percent: None,
+            max_memory_percent: Some(DEFAULT_SYSTEM_MAX_MEMORY_PERCENT),
+            max_swap_percent: None,
+        }
+    }
+}
+
+impl SystemCheck {
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let mut system = sysinfo::System::new();
+        let cpu_percent = match self.max_cpu_percent {
+            Some(_) => {
+                // Usage is computed from the difference between two measurements, so the first one is only a baseline
+                system.refresh_cpu_usage();
+                tokio::time::sleep(SYSTEM_CPU_SAMPLE_PERIOD).await;
+                system.refresh_cpu_usage();
+                system.global_cpu_usage() as f64
+            }
+            None => 0.0,
+        };
+        system.refresh_memory();
+
+        let usage = SystemUsage {
+            load: sysinfo::System::load_average().one,
+            cpu_percent,
+            memory_used: system.total_memory() - system.available_memory(),
+            memory_total: system.total_memory(),
+            swap_used: system.used_swap(),
+            swap_total: system.total_swap(),
+        };
+        get_system_status(self, &usage)
+    }
+}
+
+struct SystemUsage {
+    load: f64,
+    cpu_percent: f64,
+    memory_used: u64,
+    memory_total: u64,
+    swap_used: u64,
+    swap_total: u64,
+}
+
+fn get_percent(used: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    used as f64 * 100.0 / total as f64
+}
+
+// All exceeded thresholds are reported, so a single status describes everything wrong with the host
+fn get_system_status(check: &SystemCheck, usage: &SystemUsage) -> Result<(), String> {
+    let mut errors = Vec::new();
+    if let Some(max_load) = check.max_load {
+        if usage.load > max_load {
+            errors.push(format!(
+                "Load average is {:.2}, more than {max_load}",
+                usage.load
+            ));
+        }
+    }
+    if let Some(max_cpu_percent) = check.max_cpu_percent {
+        if usage.cpu_percent > max_cpu_percent as f64 {
+            errors.push(format!(
+                "CPU usage is {:.1}%, more than {max_cpu_percent}%",
+                usage.cpu_percent
+            ));
+        }
+    }
+    let memory_percent = get_percent(usage.memory_used, usage.memory_total);
+    if let Some(max_memory_percent) = check.max_memory_percent {
+        if memory_percent > max_memory_percent as f64 {
+            errors.push(format!(
+                "Memory usage is {memory_percent:.1}% ({} of {}), more than {max_memory_percent}%",
+                format_size(usage.memory_used),
+                format_size(usage.memory_total)
+            ));
+        }
+    }
+    let swap_percent = get_percent(usage.swap_used, usage.swap_total);
+    if let Some(max_swap_percent) = check.max_swap_percent {
+        if swap_percent > max_swap_percent as f64 {
+            errors.push(format!(
+                "Swap usage is {swap_percent:.1}% ({} of {}), more than {max_swap_percent}%",
+                format_size(usage.swap_used),
+                format_size(usage.swap_total)
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_status_is_computed() {
+        let check = SystemCheck {
+            max_load: Some(4.0),
+            max_cpu_percent: Some(90),
+            max_memory_percent: Some(80),
+            max_swap_percent: Some(50),
+        };
+        let mut usage = SystemUsage {
+            load: 3.5,
+            cpu_percent: 85.0,
+            memory_used: 12 << 30,
+            memory_total: 16 << 30,
+            swap_used: 1 << 30,
+            swap_total: 4 << 30,
+        };
+        assert_eq!(get_system_status(&check, &usage), Ok(()));
+
+        usage.memory_used = 14 << 30;
+        let expected = "Memory usage is 87.5% (14.0 GiB of 16.0 GiB), more than 80%";
+        assert_eq!(get_system_status(&check, &usage), Err(expected.to_owned()));
+
+        usage.load = 6.25;
+        usage.cpu_percent = 97.5;
+        usage.swap_used = 3 << 30;
+        let expected = "Load average is 6.25, more than 4; CPU usage is 97.5%, more than 90%; \
+            Memory usage is 87.5% (14.0 GiB of 16.0 GiB), more than 80%; \
+            Swap usage is 75.0% (3.0 GiB of 4.0 GiB), more than 50%";
+        assert_eq!(get_system_status(&check, &usage), Err(expected.to_owned()));
+    }
+
+    #[test]
+    fn missing_swap_is_not_an_error() {
+        let check = SystemCheck {
+            max_swap_percent: Some(0),
+            ..Default::default()
+        };
+        let usage = SystemUsage {
+            load: 0.0,
+            cpu_percent: 0.0,
+            memory_used: 1 << 30,
+            memory_total: 16 << 30,
+            swap_used: 0,
+            swap_total: 0,
+        };
+        assert_eq!(get_system_status(&check, &usage), Ok(()));
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+#[derive(PartialEq, Debug)]
+pub struct SystemdCheck {
+    pub unit: String,
+    pub user: bool,
+    pub allow_inactive: bool,
+}
+
+impl SystemdCheck {
+    pub fn new(unit: String) -> Self {
+        Self {
+            unit,
+            user: false,
+            allow_inactive: false,
+        }
+    }
+
+    pub(crate) async fn run(&self) -> Result<(), String> {
+        let mut command = tokio::process::Command::new("systemctl");
+        if self.user {
+            command.arg("--user");
+        }
+        command.args(["show", &self.unit]);
+        for property in UNIT_PROPERTIES {
+            command.arg(format!("--property={property}"));
+        }
+        let output = command
+            .output()
+            .await
+            .map_err(|err| format!("Could not run systemctl: {err}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("Could not query {}: {}", self.unit, stderr.trim()));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        get_unit_status(self, &parse_unit_properties(&stdout))
+    }
+}
+
+// Properties are printed by systemctl as "Name=value" lines, which unlike the output of "systemctl status" are not
+// localized and don't change between versions
+const UNIT_PROPERTIES: [&str; 6] = [
+    "LoadState",
+    "ActiveState",
+    "SubState",
+    "Result",
+    "ExecMainStatus",
+    "StatusText",
+];
+
+fn parse_unit_properties(text: &str) -> HashMap<&str, &str> {
+    text.lines()
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+// Describes why the unit failed, e.g. "result: exit-code, exit status 1, Database is locked"
+fn describe_failure(properties: &HashMap<&str, &str>) -> String {
+    let mut details = Vec::new();
+    if let Some(result) = properties.get("Result").filter(|x| **x != "success") {
+        details.push(format!("result: {result}"));
+    }
+    if let Some(status) = properties.get("ExecMainStatus").filter(|x| **x != "0") {
+        details.push(format!("exit status {status}"));
+    }
+    if let Some(text) = properties.get("StatusText").filter(|x| !x.is_empty()) {
+        details.push(text.to_string());
+    }
+    if details.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", details.join(", "))
+    }
+}
+
+fn get_unit_status(check: &SystemdCheck, properties: &HashMap<&str, &str>) -> Result<(), String> {
+    let unit = &check.unit;
+    let property = |name| properties.get(name).copied().unwrap_or_default();
+    match property("LoadState") {
+        "loaded" => (),
+        "not-found" => return Err(format!("{unit} does not exist")),
+        state => return Err(format!("{unit} could not be loaded ({state})")),
+    }
+
+    let sub_state = property("SubState");
+    match property("ActiveState") {
+        "active" | "reloading" => Ok(()),
+        // Units which crash are restarted by systemd, so they are only briefly failed
+        "activating" if sub_state == "auto-restart" => Err(format!(
+            "{unit} is restarting after a failure{}",
+            describe_failure(properties)
+        )),
+        "activating" => Ok(()),
+        "failed" => Err(format!("{unit} has failed{}", describe_failure(properties))),
+        // Oneshot units, e.g. ones started by timers, are inactive after completing successfully
+        "inactive" if check.allow_inactive && property("Result") == "success" => Ok(()),
+        state => Err(format!(
+            "{unit} is {state} ({sub_state}){}",
+            describe_failure(properties)
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unit_properties_are_parsed() {
+        let text = "LoadState=loaded\nActiveState=failed\nStatusText=Listening on port=80\n";
+        let properties = parse_unit_properties(text);
+        assert_eq!(properties.get("ActiveState"), Some(&"failed"));
+        assert_eq!(properties.get("StatusText"), Some(&"Listening on port=80"));
+        assert_eq!(properties.len(), 3);
+    }
+
+    #[test]
+    fn unit_status_is_computed() {
+        fn run(allow_inactive: bool, text: &str, expected: Result<(), &str>) {
+            let check = SystemdCheck {
+                allow_inactive,
+                ..SystemdCheck::new("nginx.service".to_owned())
+            };
+            let status = get_unit_status(&check, &parse_unit_properties(text));
+            assert_eq!(status, expected.map_err(|x| x.to_owned()));
+        }
+
+        let loaded = "LoadState=loaded\n";
+        run(
+            false,
+            "LoadState=not-found\nActiveState=inactive",
+            Err("nginx.service does not exist"),
+        );
+        run(
+            false,
+            "LoadState=masked",
+            Err("nginx.service could not be loaded (masked)"),
+        );
+        run(
+            false,
+            &format!("{loaded}ActiveState=active\nSubState=running"),
+            Ok(()),
+        );
+        run(
+            false,
+            &format!("{loaded}ActiveState=activating\nSubState=start"),
+            Ok(()),
+        );
+        run(
+            false,
+            &format!("{loaded}ActiveState=activating\nSubState=auto-restart\nResult=exit-code\nExecMainStatus=1"),
+            Err("nginx.service is restarting after a failure (result: exit-code, exit status 1)"),
+        );
+        run(
+            false,
+            &format!("{loaded}ActiveState=failed\nSubState=failed\nResult=exit-code\nExecMainStatus=2\nStatusText=Port is in use"),
+            Err("nginx.service has failed (result: exit-code, exit status 2, Port is in use)"),
+        );
+        run(
+            false,
+            &format!(
+                "{loaded}ActiveState=inactive\nSubState=dead\nResult=success\nExecMainStatus=0"
+            ),
+            Err("nginx.service is inactive (dead)"),
+        );
+        run(
+            true,
+            &format!(
+                "{loaded}ActiveState=inactive\nSubState=dead\nResult=success\nExecMainStatus=0"
+            ),
+            Ok(()),
+        );
+    }
+}
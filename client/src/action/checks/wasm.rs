@@ -0,0 +1,207 @@
+// WebAssembly modules are checks executed in a sandbox. They can't import any functions, so they have no access to
+// files, network or clock, and they are limited in the amount of executed instructions (fuel) and memory. A module
+// has to export:
+//
+//     memory                              - its linear memory
+//     alloc(size: i32) -> i32             - allocates memory for the request and returns its address
+//     check(address: i32, size: i32) -> i64
+//
+// The request is a JSON object {"version": 1, "args": [...], "state": ...}. The check function returns the address
+// of the response in the upper 32 bits of the result and its size in the lower 32 bits. The response has the same
+// format as for plugins. The module is loaded on each run, so it can be updated without restarting the client.
+
+use super::format_size;
+use super::plugin::{get_plugin_status, parse_plugin_response, PluginResponse, PluginState};
+use crate::action::WatchStatus;
+use check_mate_common::constants::*;
+use std::path::PathBuf;
+use wasmtime::{Engine, Instance, Module, Store, StoreLimits, StoreLimitsBuilder, Trap};
+
+#[derive(PartialEq, Debug)]
+pub struct WasmCheck {
+    pub path: PathBuf,
+    pub args: Vec<String>,
+    pub fuel: u64,
+    pub max_memory: u64,
+    state: PluginState,
+}
+
+impl WasmCheck {
+    pub fn new(path: PathBuf, args: Vec<String>) -> Self {
+        Self {
+            path,
+            args,
+            fuel: DEFAULT_WASM_FUEL,
+            max_memory: DEFAULT_WASM_MAX_MEMORY,
+            state: PluginState::default(),
+        }
+    }
+
+    pub(crate) async fn run(&self) -> WatchStatus {
+        match self.run_module().await {
+            Ok(response) => {
+                let status = get_plugin_status(&response);
+                *self.state.0.lock().unwrap() = response.state;
+                status
+            }
+            Err(message) => WatchStatus::error(message),
+        }
+    }
+
+    async fn run_module(&self) -> Result<PluginResponse, String> {
+        let module = tokio::fs::read(&self.path)
+            .await
+            .map_err(|err| format!("Could not read {}: {err}", self.path.display()))?;
+        let request = serde_json::json!({
+            "version": PLUGIN_PROTOCOL_VERSION,
+            "args": self.args,
+            "state": *self.state.0.lock().unwrap(),
+        });
+        let limits = WasmLimits {
+            fuel: self.fuel,
+            max_memory: self.max_memory,
+        };
+
+        // Modules are compiled and executed synchronously, so they are run outside of the async runtime
+        let response = tokio::task::spawn_blocking(move || {
+            run_module(&module, request.to_string().as_bytes(), &limits)
+        })
+        .await
+        .map_err(|err| format!("WebAssembly check was interrupted: {err}"))??;
+
+        parse_plugin_response(&response, b"")
+    }
+}
+
+struct WasmLimits {
+    fuel: u64,
+    max_memory: u64,
+}
+
+fn describe_error(err: wasmtime::Error, limits: &WasmLimits) -> String {
+    match err.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => format!(
+            "WebAssembly check exceeded the limit of {} instructions",
+            limits.fuel
+        ),
+        _ => format!("WebAssembly check failed: {err:#}"),
+    }
+}
+
+fn run_module(module: &[u8], request: &[u8], limits: &WasmLimits) -> Result<Vec<u8>, String> {
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config).map_err(|err| describe_error(err, limits))?;
+    let module = Module::new(&engine, module)
+        .map_err(|err| format!("Invalid WebAssembly module: {err:#}"))?;
+
+    let store_limits = StoreLimitsBuilder::new()
+        .memory_size(limits.max_memory.try_into().unwrap_or(usize::MAX))
+        .instances(1)
+        .build();
+    let mut store = Store::new(&engine, store_limits);
+    store.limiter(|limits: &mut StoreLimits| limits);
+    store
+        .set_fuel(limits.fuel)
+        .map_err(|err| describe_error(err, limits))?;
+
+    // No imports are provided, so modules requiring any of them fail to instantiate
+    let instance = Instance::new(&mut store, &module, &[]).map_err(|err| {
+        format!(
+            "Could not instantiate WebAssembly module with {} of memory: {err:#}",
+            format_size(limits.max_memory)
+        )
+    })?;
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or_else(|| "WebAssembly module does not export memory".to_owned())?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&mut store, "alloc")
+        .map_err(|err| format!("{err:#}"))?;
+    let check = instance
+        .get_typed_func::<(i32, i32), i64>(&mut store, "check")
+        .map_err(|err| format!("{err:#}"))?;
+
+    let request_size = request.len() as i32;
+    let request_address = alloc
+        .call(&mut store, request_size)
+        .map_err(|err| describe_error(err, limits))?;
+    memory
+        .write(&mut store, request_address as u32 as usize, request)
+        .map_err(|_| "WebAssembly module allocated invalid memory for the request".to_owned())?;
+    let result = check
+        .call(&mut store, (request_address, request_size))
+        .map_err(|err| describe_error(err, limits))?;
+
+    let response_address = (result as u64 >> 32) as usize;
+    let response_size = (result as u64 & 0xFFFF_FFFF) as usize;
+    let mut response = vec![0; response_size];
+    memory
+        .read(&store, response_address, &mut response)
+        .map_err(|_| "WebAssembly module returned response outside of its memory".to_owned())?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: WasmLimits = WasmLimits {
+        fuel: 100_000,
+        max_memory: 1 << 20,
+    };
+
+    // Module which echoes the request into the message of a warning
+    const ECHO_MODULE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (data (i32.const 0) "{\"status\": \"warning\", \"message\": ")
+            (func (export "alloc") (param $size i32) (result i32) (i32.const 1024))
+            (func (export "check") (param $address i32) (param $size i32) (result i64)
+                (memory.copy (i32.const 33) (local.get $address) (local.get $size))
+                (i32.store8 (i32.add (i32.const 33) (local.get $size)) (i32.const 125))
+                (i64.add (i64.shl (i64.const 0) (i64.const 32))
+                    (i64.extend_i32_u (i32.add (local.get $size) (i32.const 34))))))
+    "#;
+
+    #[test]
+    fn module_receives_request_and_returns_response() {
+        let response = run_module(ECHO_MODULE.as_bytes(), br#""hello""#, &LIMITS)
+            .expect("Module should succeed");
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            r#"{"status": "warning", "message": "hello"}"#
+        );
+    }
+
+    #[test]
+    fn module_limits_are_enforced() {
+        let infinite_loop = r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "alloc") (param i32) (result i32) (i32.const 0))
+                (func (export "check") (param i32 i32) (result i64) (loop (br 0)) (i64.const 0)))
+        "#;
+        let error = run_module(infinite_loop.as_bytes(), b"{}", &LIMITS).unwrap_err();
+        assert_eq!(
+            error,
+            "WebAssembly check exceeded the limit of 100000 instructions"
+        );
+
+        let large_memory = r#"(module (memory (export "memory") 32))"#;
+        let error = run_module(large_memory.as_bytes(), b"{}", &LIMITS).unwrap_err();
+        assert!(
+            error.starts_with("Could not instantiate WebAssembly module with 1.0 MiB of memory")
+        );
+
+        let with_import = r#"(module (import "wasi_snapshot_preview1" "fd_write" (func)))"#;
+        let error = run_module(with_import.as_bytes(), b"{}", &LIMITS).unwrap_err();
+        assert!(error.starts_with("Could not instantiate WebAssembly module"));
+    }
+
+    #[test]
+    fn invalid_module_is_reported() {
+        let error = run_module(b"not a module", b"{}", &LIMITS).unwrap_err();
+        assert!(error.starts_with("Invalid WebAssembly module"));
+    }
+}
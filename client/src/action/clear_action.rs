@@ -0,0 +1,13 @@
+use super::definition::Action;
+use check_mate_common::{CommunicationError, ServerCommand};
+use tokio::io::AsyncWrite;
+
+impl Action {
+    pub(crate) async fn clear_client_by_name(
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        name: &str,
+    ) -> Result<(), CommunicationError> {
+        let command = ServerCommand::ClearClientByName(name.into());
+        command.send_async(output_stream).await
+    }
+}
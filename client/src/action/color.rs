@@ -0,0 +1,91 @@
+use check_mate_common::{ClientDetails, Severity};
+use std::io::IsTerminal;
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum ColorChoice {
+    /// Use colors only if stdout is a terminal and NO_COLOR environment variable is not set.
+    #[default]
+    Auto,
+
+    /// Always use colors.
+    Always,
+
+    /// Never use colors.
+    Never,
+}
+
+impl std::fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            ColorChoice::Auto => "auto",
+            ColorChoice::Always => "always",
+            ColorChoice::Never => "never",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+impl ColorChoice {
+    pub(crate) fn is_enabled(&self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+// Wraps the text in ANSI escape codes selected by severity of the client: red for errors, yellow for warnings, green
+// for ok and dim for clients which haven't reported anything yet. The server doesn't track stale or acknowledged
+// statuses, so there are no separate colors for them.
+pub(crate) fn colorize(text: &str, details: &ClientDetails) -> String {
+    let color = match details.severity() {
+        Severity::Pending | Severity::Unknown => DIM,
+        Severity::Ok => GREEN,
+        Severity::Warning => YELLOW,
+        Severity::Error => RED,
+    };
+    format!("{color}{text}{RESET}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_colorized_by_status() {
+        fn run(status: Option<Result<(), String>>, expected: &str) {
+            run_with_warning(status, false, expected);
+        }
+        fn run_with_warning(status: Option<Result<(), String>>, warning: bool, expected: &str) {
+            let details = ClientDetails {
+                name: "Watcher".to_owned(),
+                status,
+                pending: false,
+                warning,
+                age_seconds: 0,
+                tags: Vec::new(),
+            };
+            assert_eq!(colorize("text", &details), expected);
+        }
+        run(None, "\x1b[2mtext\x1b[0m");
+        run(Some(Ok(())), "\x1b[32mtext\x1b[0m");
+        run(Some(Err("fail".to_owned())), "\x1b[31mtext\x1b[0m");
+        run_with_warning(Some(Err("slow".to_owned())), true, "\x1b[33mtext\x1b[0m");
+    }
+
+    #[test]
+    fn explicit_color_choices_are_respected() {
+        assert!(ColorChoice::Always.is_enabled());
+        assert!(!ColorChoice::Never.is_enabled());
+    }
+}
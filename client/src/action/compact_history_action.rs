@@ -0,0 +1,26 @@
+use super::definition::{Action, ActionState};
+use check_mate_common::{CommunicationError, ServerCommand};
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+impl Action {
+    pub(crate) async fn compact_history(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        state: &mut ActionState,
+    ) -> Result<(), CommunicationError> {
+        let command = ServerCommand::CompactHistory;
+        command.send_async(output_stream).await?;
+
+        match ServerCommand::receive_async(input_stream).await? {
+            ServerCommand::HistoryCompacted(Ok(removed)) => {
+                println!("Removed {removed} statuses from history");
+            }
+            ServerCommand::HistoryCompacted(Err(err)) => {
+                eprintln!("ERROR: {err}");
+                state.exit_code = 1;
+            }
+            _ => panic!("Unexpected command received after CompactHistory"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+// Local control interface of a watch client, which lets operators manage a long-lived watcher without restarting
+// it. It's a Unix socket on Unix and a named pipe on Windows. Each connection carries a single command terminated
+// by a newline, to which the client responds with text and closes the connection. The control action of the client
+// can be used to send the commands.
+
+use super::watch_action::WatchStatus;
+use check_mate_common::constants::*;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+#[derive(PartialEq, Debug)]
+pub struct ControlData {
+    pub path: PathBuf,
+    pub command: ControlCommand,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lowercase")]
+pub enum ControlCommand {
+    /// Run the command immediately, even if the watcher is paused.
+    Run,
+
+    /// Stop running the command on schedule and on changes of watched files.
+    Pause,
+
+    /// Resume running the command after it was paused.
+    Resume,
+
+    /// Load the configuration again and restart the watcher with it.
+    Reload,
+
+    /// Show whether the watcher is paused and the result of its last run.
+    Status,
+}
+
+impl std::fmt::Display for ControlCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            ControlCommand::Run => "run",
+            ControlCommand::Pause => "pause",
+            ControlCommand::Resume => "resume",
+            ControlCommand::Reload => "reload",
+            ControlCommand::Status => "status",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+// Commands which have to be executed by the watch loop. Others are handled directly by the socket.
+#[derive(PartialEq, Debug)]
+pub enum ControlRequest {
+    Run,
+    Reload,
+}
+
+// Requests for the watch loop. They come from the control socket and, for checks from a check file, from reloading
+// the file. The channel is kept when the client is restarted after reloading, so requests sent in the meantime are
+// not lost.
+pub struct ControlRequests {
+    sender: UnboundedSender<ControlRequest>,
+    receiver: UnboundedReceiver<ControlRequest>,
+}
+
+impl Default for ControlRequests {
+    fn default() -> Self {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        Self { sender, receiver }
+    }
+}
+
+impl ControlRequests {
+    pub fn sender(&self) -> UnboundedSender<ControlRequest> {
+        self.sender.clone()
+    }
+
+    // The sender is kept along with the receiver, so the channel is never closed
+    pub(crate) async fn wait(&mut self) -> ControlRequest {
+        match self.receiver.recv().await {
+            Some(request) => request,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[derive(Default)]
+struct ControlState {
+    paused: bool,
+    last_status: Option<WatchStatus>,
+    last_run: Option<SystemTime>,
+}
+
+// Listens for commands in the background. It lives in ActionState, so it stays available while reconnecting.
+pub struct ControlSocket {
+    state: Arc<Mutex<ControlState>>,
+    listener_task: tokio::task::JoinHandle<()>,
+    #[cfg(unix)]
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    pub fn new(path: &Path, requests: UnboundedSender<ControlRequest>) -> Result<Self, String> {
+        let state = Arc::new(Mutex::new(ControlState::default()));
+        let listener_task = Self::listen(path, requests, state.clone())?;
+        Ok(Self {
+            state,
+            listener_task,
+            #[cfg(unix)]
+            path: path.to_owned(),
+        })
+    }
+
+    #[cfg(unix)]
+    fn listen(
+        path: &Path,
+        sender: UnboundedSender<ControlRequest>,
+        state: Arc<Mutex<ControlState>>,
+    ) -> Result<tokio::task::JoinHandle<()>, String> {
+        // A socket file left by a client which crashed can be reused, but one which is still listening can't
+        if path.exists() {
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(format!(
+                    "control socket {} is already in use",
+                    path.display()
+                ));
+            }
+            let _ = std::fs::remove_file(path);
+        }
+        let listener = tokio::net::UnixListener::bind(path)
+            .map_err(|err| format!("could not create control socket {}: {err}", path.display()))?;
+        Ok(tokio::spawn(async move {
+            loop {
+                if let Ok((connection, _)) = listener.accept().await {
+                    handle_connection(connection, &sender, &state).await;
+                }
+            }
+        }))
+    }
+
+    #[cfg(windows)]
+    fn listen(
+        path: &Path,
+        sender: UnboundedSender<ControlRequest>,
+        state: Arc<Mutex<ControlState>>,
+    ) -> Result<tokio::task::JoinHandle<()>, String> {
+        use tokio::net::windows::named_pipe::ServerOptions;
+
+        let path = path.to_owned();
+        let mut server = ServerOptions::new()
+            .first_pipe_instance(true)
+            .create(&path)
+            .map_err(|err| format!("could not create control pipe {}: {err}", path.display()))?;
+        Ok(tokio::spawn(async move {
+            loop {
+                if server.connect().await.is_err() {
+                    continue;
+                }
+                // A new instance of the pipe has to be created for the next connection
+                let connection = server;
+                server = match ServerOptions::new().create(&path) {
+                    Ok(x) => x,
+                    Err(_) => return,
+                };
+                handle_connection(connection, &sender, &state).await;
+            }
+        }))
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    pub(crate) fn set_last_status(&self, status: &WatchStatus) {
+        let mut state = self.state.lock().unwrap();
+        state.last_status = Some(status.clone());
+        state.last_run = Some(SystemTime::now());
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.listener_task.abort();
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn handle_connection(
+    connection: impl AsyncRead + AsyncWrite + Unpin,
+    sender: &UnboundedSender<ControlRequest>,
+    state: &Mutex<ControlState>,
+) {
+    let mut connection = BufReader::new(connection);
+    let mut line = String::new();
+    let read = connection.read_line(&mut line);
+    if !matches!(
+        tokio::time::timeout(CONTROL_SOCKET_TIMEOUT, read).await,
+        Ok(Ok(_))
+    ) {
+        return;
+    }
+    let response = execute_control_command(line.trim(), sender, state);
+    let connection = connection.get_mut();
+    let _ = connection.write_all(response.as_bytes()).await;
+    let _ = connection.shutdown().await;
+}
+
+fn execute_control_command(
+    text: &str,
+    sender: &UnboundedSender<ControlRequest>,
+    state: &Mutex<ControlState>,
+) -> String {
+    let Ok(command) = <ControlCommand as clap::ValueEnum>::from_str(text, true) else {
+        return format!("ERROR: Unknown command \"{text}\"\n");
+    };
+    let mut state = state.lock().unwrap();
+    match command {
+        ControlCommand::Run => {
+            let _ = sender.send(ControlRequest::Run);
+        }
+        ControlCommand::Pause => state.paused = true,
+        ControlCommand::Resume => state.paused = false,
+        ControlCommand::Reload => {
+            let _ = sender.send(ControlRequest::Reload);
+        }
+        ControlCommand::Status => return format_control_status(&state),
+    }
+    "OK\n".to_owned()
+}
+
+fn format_control_status(state: &ControlState) -> String {
+    let paused = if state.paused { "paused" } else { "running" };
+    let last_run = match state.last_run {
+        Some(time) => chrono::DateTime::<chrono::Local>::from(time)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        None => "never".to_owned(),
+    };
+    let last_status = match &state.last_status {
+        Some(WatchStatus { result: Ok(()), .. }) => "ok".to_owned(),
+        Some(WatchStatus {
+            result: Err(message),
+            warning: true,
+        }) => format!("warning: {message}"),
+        Some(WatchStatus {
+            result: Err(message),
+            warning: false,
+        }) => format!("error: {message}"),
+        None => "none".to_owned(),
+    };
+    format!("state: {paused}\nlast run: {last_run}\nlast status: {last_status}\n")
+}
+
+pub(crate) async fn send_control_command(
+    path: &Path,
+    command: ControlCommand,
+) -> Result<String, String> {
+    let on_error = |err: std::io::Error| format!("{}: {err}", path.display());
+    #[cfg(unix)]
+    let mut connection = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(on_error)?;
+    #[cfg(windows)]
+    let mut connection = tokio::net::windows::named_pipe::ClientOptions::new()
+        .open(path)
+        .map_err(on_error)?;
+
+    connection
+        .write_all(format!("{command}\n").as_bytes())
+        .await
+        .map_err(on_error)?;
+    let mut response = String::new();
+    let read = tokio::io::AsyncReadExt::read_to_string(&mut connection, &mut response);
+    tokio::time::timeout(CONTROL_SOCKET_TIMEOUT, read)
+        .await
+        .map_err(|_| format!("{}: no response", path.display()))?
+        .map_err(on_error)?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_commands_are_executed() {
+        let (sender, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        let state = Mutex::new(ControlState::default());
+        let run = |text: &str| execute_control_command(text, &sender, &state);
+
+        assert_eq!(run("pause"), "OK\n");
+        assert!(state.lock().unwrap().paused);
+        assert_eq!(run("RESUME"), "OK\n");
+        assert!(!state.lock().unwrap().paused);
+
+        assert_eq!(run("run"), "OK\n");
+        assert_eq!(run("reload"), "OK\n");
+        assert_eq!(requests.try_recv(), Ok(ControlRequest::Run));
+        assert_eq!(requests.try_recv(), Ok(ControlRequest::Reload));
+        assert!(requests.try_recv().is_err());
+
+        assert_eq!(run("jump"), "ERROR: Unknown command \"jump\"\n");
+    }
+
+    #[test]
+    fn control_status_is_formatted() {
+        let mut state = ControlState::default();
+        assert_eq!(
+            format_control_status(&state),
+            "state: running\nlast run: never\nlast status: none\n"
+        );
+
+        state.paused = true;
+        state.last_status = Some(WatchStatus::error("Disk is full".to_owned()));
+        state.last_run = Some(SystemTime::now());
+        let status = format_control_status(&state);
+        assert!(status.starts_with("state: paused\nlast run: "));
+        assert!(status.ends_with("\nlast status: error: Disk is full\n"));
+
+        state.last_status = Some(WatchStatus::warning("Disk is almost full".to_owned()));
+        let status = format_control_status(&state);
+        assert!(status.ends_with("\nlast status: warning: Disk is almost full\n"));
+    }
+}
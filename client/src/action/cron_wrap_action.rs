@@ -0,0 +1,152 @@
+use super::definition::{Action, ActionState};
+use super::read_action::format_age;
+use check_mate_common::constants::*;
+use check_mate_common::{CommunicationError, ServerCommand};
+use chrono::{DateTime, Local};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+#[derive(PartialEq, Debug)]
+pub struct CronWrapData {
+    pub command: String,
+    pub command_args: Vec<String>,
+}
+
+// Result of a single run of the wrapped command
+struct WrappedCommandRun {
+    exit_code: Option<i32>,
+    started: DateTime<Local>,
+    elapsed_seconds: u64,
+    output_tail: Vec<String>,
+}
+
+// Builds the status reported for a finished run. Success needs no explanation, but errors describe when the command
+// started, how long it took and what it printed at the end, which usually explains the failure.
+fn get_wrapped_command_status(run: &WrappedCommandRun) -> Result<(), String> {
+    let outcome = match run.exit_code {
+        Some(0) => return Ok(()),
+        Some(code) => format!("Exit code was {code}"),
+        None => "Command was terminated by a signal".to_owned(),
+    };
+    let mut message = format!(
+        "{outcome} after {} (started at {})",
+        format_age(run.elapsed_seconds),
+        run.started.format("%Y-%m-%d %H:%M:%S")
+    );
+    for line in &run.output_tail {
+        message.push('\n');
+        message.push_str(line);
+    }
+    Err(message)
+}
+
+// Forwards output of the wrapped command as is, so it still reaches cron (and its emails) unchanged. The last lines of
+// both stdout and stderr are remembered for the status.
+async fn forward_output(
+    reader: impl AsyncRead + Unpin,
+    mut writer: impl AsyncWrite + Unpin,
+    tail: &RefCell<VecDeque<String>>,
+) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
+        }
+        let _ = writer.write_all(&line).await;
+        let _ = writer.flush().await;
+
+        let mut tail = tail.borrow_mut();
+        if tail.len() == CRON_WRAP_OUTPUT_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(String::from_utf8_lossy(&line).trim_end().to_owned());
+    }
+}
+
+impl Action {
+    pub(crate) async fn cron_wrap(
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &CronWrapData,
+        state: &mut ActionState,
+    ) -> Result<(), CommunicationError> {
+        // Connection stays open while the command runs, so the client is shown as pending in the meantime
+        ServerCommand::SetStatusPending
+            .send_async(output_stream)
+            .await?;
+
+        let started = Local::now();
+        let start = Instant::now();
+        let subprocess = tokio::process::Command::new(&data.command)
+            .args(&data.command_args)
+            .stdin(Stdio::inherit())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut subprocess = match subprocess {
+            Ok(x) => x,
+            Err(err) => {
+                let message = Self::describe_spawn_error(&data.command, err);
+                eprintln!("ERROR: {}", message);
+                state.exit_code = CRON_WRAP_SPAWN_ERROR_EXIT_CODE;
+                let status = Err(format!("Command was not executed. {message}"));
+                return ServerCommand::PushStatus(status)
+                    .send_async(output_stream)
+                    .await;
+            }
+        };
+
+        let tail = RefCell::new(VecDeque::new());
+        let stdout = subprocess.stdout.take().expect("Stdout should be piped");
+        let stderr = subprocess.stderr.take().expect("Stderr should be piped");
+        let (_, _, exit_status) = tokio::join!(
+            forward_output(stdout, tokio::io::stdout(), &tail),
+            forward_output(stderr, tokio::io::stderr(), &tail),
+            subprocess.wait(),
+        );
+
+        let run = WrappedCommandRun {
+            exit_code: exit_status?.code(),
+            started,
+            elapsed_seconds: start.elapsed().as_secs(),
+            output_tail: tail.into_inner().into(),
+        };
+        state.exit_code = run.exit_code.unwrap_or(1);
+        let status = get_wrapped_command_status(&run);
+        ServerCommand::PushStatus(status)
+            .send_async(output_stream)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn wrapped_command_status_describes_failures() {
+        let mut run = WrappedCommandRun {
+            exit_code: Some(0),
+            started: Local.with_ymd_and_hms(2024, 3, 1, 2, 0, 5).unwrap(),
+            elapsed_seconds: 190,
+            output_tail: vec!["Copying files".to_owned(), "No space left".to_owned()],
+        };
+        assert_eq!(get_wrapped_command_status(&run), Ok(()));
+
+        run.exit_code = Some(2);
+        let expected = "Exit code was 2 after 3m (started at 2024-03-01 02:00:05)\nCopying files\nNo space left";
+        assert_eq!(get_wrapped_command_status(&run), Err(expected.to_owned()));
+
+        run.exit_code = None;
+        run.output_tail.clear();
+        let expected =
+            "Command was terminated by a signal after 3m (started at 2024-03-01 02:00:05)";
+        assert_eq!(get_wrapped_command_status(&run), Err(expected.to_owned()));
+    }
+}
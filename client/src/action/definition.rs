@@ -1,23 +1,91 @@
-use super::watch_action::WatchCommandData;
+use super::api_key_action::ApiKeyRequest;
+use super::availability_action::AvailabilityWindow;
+use super::badge_action::BadgeData;
+use super::control_socket::{ControlData, ControlRequests, ControlSocket};
+use super::cron_wrap_action::CronWrapData;
+use super::export_action::ExportData;
+use super::influx_action::InfluxData;
+use super::metrics_action::MetricsData;
+use super::notify_action::NotifyData;
+use super::output_format::OutputFormat;
+use super::push_action::PushedStatus;
+use super::read_action::ReadMessagesData;
+#[cfg(feature = "redis")]
+use super::redis_action::RedisData;
+use super::secrets_action::SecretsRequest;
+use super::top_action::TopData;
+use super::watch_action::{
+    FileWatcher, RefreshSignal, StreamingState, WatchCommandData, WatchStatus,
+};
 use crate::config::Config;
-use check_mate_common::{CommunicationError, ServerCommand};
+use check_mate_common::CommunicationError;
+use check_mate_sdk::Identity;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::time::Instant;
 use tokio::io::{AsyncBufRead, AsyncWrite};
 
+// There is only one action per process, so its size doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(PartialEq, Debug)]
 pub enum Action {
-    ReadMessages(bool),
+    ReadMessages(ReadMessagesData),
     WatchCommand(WatchCommandData),
     RefreshClientByName(String),
     RefreshAllClients,
-    ListClients,
+    ClearClientByName(String),
+    PushStatus(PushedStatus),
+    CronWrap(CronWrapData),
+    DockerHealth(String),
+    ListClients(OutputFormat),
+    Top(TopData),
+    Notify(NotifyData),
+    WriteBadges(BadgeData),
+    EmitMetrics(MetricsData),
+    ExportToInflux(InfluxData),
+    #[cfg(feature = "redis")]
+    BridgeToRedis(RedisData),
+    GetServerStatistics,
+    GetAvailability(AvailabilityWindow, OutputFormat),
+    CompactHistory,
+    ExportHistory(ExportData),
+    ManageApiKeys(ApiKeyRequest),
+    Drain(bool), // whether to wait until all clients are gone
+    RunChecks(PathBuf),
+    Control(ControlData),
+    Secrets(SecretsRequest),
     Abort,
-    Help,
     Version,
+    Completions(clap_complete::Shell),
+}
+
+// State preserved between consecutive executions of an action, i.e. across reconnections to the server.
+#[derive(Default)]
+pub struct ActionState {
+    pub(crate) last_watch_status: Option<WatchStatus>,
+    pub(crate) offline_watch_statuses: VecDeque<(u64, WatchStatus)>,
+    pub(crate) last_watch_start: Option<Instant>,
+    pub(crate) client_start: Option<Instant>,
+    pub(crate) last_watch_status_sent: Option<Instant>,
+    pub(crate) consecutive_failures: u32,
+    pub(crate) consecutive_successes: u32,
+    pub(crate) status_send_times: VecDeque<Instant>,
+    pub(crate) rate_limited_status: bool,
+    pub(crate) file_watcher: Option<FileWatcher>,
+    pub(crate) refresh_signal: RefreshSignal,
+    pub(crate) control: Option<ControlSocket>,
+    pub(crate) control_requests: ControlRequests,
+    pub(crate) reload_requested: bool,
+    pub(crate) streaming: StreamingState,
+    pub(crate) shutdown_requested: bool,
+    pub(crate) exit_code: i32,
 }
 
 impl Action {
-    pub fn should_reconnect(&self) -> bool {
+    pub fn should_reconnect(&self, state: &ActionState) -> bool {
         matches!(self, Self::WatchCommand(_))
+            && !state.shutdown_requested
+            && !state.reload_requested
     }
 
     pub async fn execute(
@@ -25,25 +93,75 @@ impl Action {
         input_stream: &mut (impl AsyncBufRead + Unpin),
         output_stream: &mut (impl AsyncWrite + Unpin),
         config: &Config,
+        state: &mut ActionState,
     ) -> Result<(), CommunicationError> {
-        if let Some(ref name) = config.client_name {
-            let command = ServerCommand::SetName(name.clone());
-            command.send_async(output_stream).await?;
-        }
+        let identity = Identity {
+            api_key: config.api_key.clone(),
+            namespace: config.namespace.clone(),
+            all_namespaces: config.all_namespaces,
+            name: config.client_name.clone(),
+            tags: config.client_tags.clone(),
+        };
+        identity.introduce(output_stream).await?;
 
         match self {
-            Action::ReadMessages(include_names) => {
-                Self::read(input_stream, output_stream, *include_names).await
+            Action::ReadMessages(data) => Self::read(input_stream, output_stream, data).await,
+            Action::WatchCommand(data) => {
+                Self::watch(input_stream, output_stream, data, state).await
             }
-            Action::WatchCommand(data) => Self::watch(input_stream, output_stream, data).await,
             Action::RefreshClientByName(name) => {
                 Self::refresh_client_by_name(output_stream, name).await
             }
             Action::RefreshAllClients => Self::refresh_all_clients(output_stream).await,
-            Action::ListClients => Self::list_clients(input_stream, output_stream).await,
+            Action::ClearClientByName(name) => {
+                Self::clear_client_by_name(output_stream, name).await
+            }
+            Action::PushStatus(status) => Self::push_status(output_stream, status).await,
+            Action::CronWrap(data) => Self::cron_wrap(output_stream, data, state).await,
+            Action::DockerHealth(name) => {
+                Self::docker_health(input_stream, output_stream, name, state).await
+            }
+            Action::ListClients(output_format) => {
+                Self::list_clients(input_stream, output_stream, *output_format).await
+            }
+            Action::Top(data) => Self::top(input_stream, output_stream, data).await,
+            Action::Notify(data) => Self::notify(input_stream, output_stream, data).await,
+            Action::WriteBadges(data) => {
+                Self::write_badges(input_stream, output_stream, data).await
+            }
+            Action::EmitMetrics(data) => {
+                Self::emit_metrics(input_stream, output_stream, data).await
+            }
+            Action::ExportToInflux(data) => {
+                Self::export_to_influx(input_stream, output_stream, data).await
+            }
+            #[cfg(feature = "redis")]
+            Action::BridgeToRedis(data) => {
+                Self::bridge_to_redis(input_stream, output_stream, data).await
+            }
+            Action::GetServerStatistics => {
+                Self::get_server_statistics(input_stream, output_stream).await
+            }
+            Action::GetAvailability(window, output_format) => {
+                Self::get_availability(input_stream, output_stream, *window, *output_format, state)
+                    .await
+            }
+            Action::CompactHistory => {
+                Self::compact_history(input_stream, output_stream, state).await
+            }
+            Action::ExportHistory(data) => {
+                Self::export_history(input_stream, output_stream, data, state).await
+            }
+            Action::ManageApiKeys(request) => {
+                Self::manage_api_keys(input_stream, output_stream, request, state).await
+            }
+            Action::Drain(wait) => Self::drain(input_stream, output_stream, *wait).await,
             Action::Abort => Self::abort(output_stream).await,
-            Action::Help => panic!("Cannot execute help action"),
+            Action::RunChecks(_) => panic!("Cannot execute run action"),
+            Action::Control(_) => panic!("Cannot execute control action"),
+            Action::Secrets(_) => panic!("Cannot execute secrets action"),
             Action::Version => panic!("Cannot execute version action"),
+            Action::Completions(_) => panic!("Cannot execute completions action"),
         }
     }
 }
@@ -0,0 +1,77 @@
+use super::definition::{Action, ActionState};
+use check_mate_common::{ClientDetails, CommunicationError, ServerCommand};
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+// Returns the reason why the client is unhealthy or None if it's healthy. Multiple clients can share a name, in which
+// case all of them have to be ok. Clients with warnings are still working, so they are healthy. A client which hasn't
+// reported a status yet is not considered healthy, because probes usually have their own grace period for starting up.
+fn get_unhealthy_reason(name: &str, statuses: &[ClientDetails]) -> Option<String> {
+    let mut found = false;
+    for details in statuses.iter().filter(|x| x.name == name) {
+        found = true;
+        match details.status {
+            Some(Ok(_)) => (),
+            Some(Err(_)) if details.warning => (),
+            Some(Err(ref message)) => return Some(message.clone()),
+            None => return Some(details.severity().to_string()),
+        }
+    }
+    if found {
+        None
+    } else {
+        Some("not found".to_owned())
+    }
+}
+
+impl Action {
+    pub(crate) async fn docker_health(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        name: &str,
+        state: &mut ActionState,
+    ) -> Result<(), CommunicationError> {
+        let command = ServerCommand::GetClientDetails;
+        command.send_async(output_stream).await?;
+        let statuses = match ServerCommand::receive_async(input_stream).await? {
+            ServerCommand::ClientDetails(details) => details,
+            _ => panic!("Unexpected command received after GetClientDetails"),
+        };
+
+        // Output is stored by Docker in the health log, so only the reason of a failure is printed
+        if let Some(reason) = get_unhealthy_reason(name, &statuses) {
+            println!("{name}: {reason}");
+            state.exit_code = 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_clients_with_the_name_must_be_ok() {
+        let statuses = [
+            ClientDetails::new("web", Some(Ok(()))),
+            ClientDetails::new("db", Some(Ok(()))),
+            ClientDetails::new("db", Some(Err("Disk full"))),
+            ClientDetails::new("cache", None),
+            ClientDetails::new("queue", Some(Err("Queue is long"))).with_warning(),
+        ];
+        assert_eq!(get_unhealthy_reason("web", &statuses), None);
+        assert_eq!(get_unhealthy_reason("queue", &statuses), None);
+        assert_eq!(
+            get_unhealthy_reason("db", &statuses),
+            Some("Disk full".to_owned())
+        );
+        assert_eq!(
+            get_unhealthy_reason("cache", &statuses),
+            Some("unknown".to_owned())
+        );
+        assert_eq!(
+            get_unhealthy_reason("mail", &statuses),
+            Some("not found".to_owned())
+        );
+    }
+}
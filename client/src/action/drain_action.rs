@@ -0,0 +1,32 @@
+use super::definition::Action;
+use check_mate_common::{constants::*, CommunicationError, ServerCommand};
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+impl Action {
+    pub(crate) async fn drain(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        wait: bool,
+    ) -> Result<(), CommunicationError> {
+        // Draining again only queries the progress, so the command is repeated until all other clients are gone
+        loop {
+            let command = ServerCommand::Drain;
+            command.send_async(output_stream).await?;
+
+            match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::DrainProgress(0) => {
+                    println!("Server is drained");
+                    return Ok(());
+                }
+                ServerCommand::DrainProgress(clients) => {
+                    println!("Server is draining, clients still connected: {clients}");
+                }
+                _ => panic!("Unexpected command received after Drain"),
+            }
+            if !wait {
+                return Ok(());
+            }
+            tokio::time::sleep(DRAIN_PROGRESS_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,206 @@
+// Export of the history of statuses kept by the server, e.g. for offline analysis or importing into BI tools. Every
+// recorded status change becomes one line, either of CSV or of JSON, so exports can be processed line by line. Times
+// are printed in UTC. The server sends large exports in chunks, which are printed as they arrive.
+
+use super::definition::{Action, ActionState};
+use check_mate_common::{
+    parse_duration, CommunicationError, HistoryEntry, ServerCommand, Severity,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum ExportFormat {
+    /// Comma-separated values with a header of time, name, status and message columns.
+    #[default]
+    Csv,
+
+    /// JSON lines, i.e. an object with time, name, status and message fields per line.
+    Json,
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ExportData {
+    pub start: u64,       // unix time in milliseconds
+    pub end: Option<u64>, // unix time in milliseconds, None for the time of the export
+    pub format: ExportFormat,
+}
+
+fn get_unix_milliseconds_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_millis() as u64)
+}
+
+// Parses either an RFC 3339 timestamp, e.g. "2026-10-01T00:00:00Z", or a duration ago, e.g. "7d". Returns the unix
+// time in milliseconds.
+pub fn parse_time(value: &str) -> Result<u64, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return u64::try_from(time.timestamp_millis())
+            .map_err(|_| format!("time \"{value}\" is before 1970"));
+    }
+    let ago = parse_duration(value).map_err(|_| {
+        format!(
+            "invalid time \"{value}\", expected an RFC 3339 timestamp or a duration ago, e.g. 7d"
+        )
+    })?;
+    Ok(get_unix_milliseconds_now().saturating_sub(ago.as_millis() as u64))
+}
+
+fn format_time(time_ms: u64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(time_ms as i64)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[derive(Serialize)]
+struct JsonHistoryEntry<'a> {
+    time: String,
+    name: &'a str,
+    status: Severity,
+    message: Option<&'a str>,
+}
+
+fn format_header(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Csv => "time,name,status,message\n",
+        ExportFormat::Json => "",
+    }
+}
+
+fn format_entries(entries: &[HistoryEntry], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{},{},{},{}\n",
+                    format_time(entry.time_ms),
+                    escape_csv_field(&entry.name),
+                    entry.status,
+                    escape_csv_field(entry.message.as_deref().unwrap_or_default())
+                )
+            })
+            .collect(),
+        ExportFormat::Json => entries
+            .iter()
+            .map(|entry| {
+                let entry = JsonHistoryEntry {
+                    time: format_time(entry.time_ms),
+                    name: &entry.name,
+                    status: entry.status,
+                    message: entry.message.as_deref(),
+                };
+                serde_json::to_string(&entry).expect("History should be serializable") + "\n"
+            })
+            .collect(),
+    }
+}
+
+impl Action {
+    pub(crate) async fn export_history(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &ExportData,
+        state: &mut ActionState,
+    ) -> Result<(), CommunicationError> {
+        let end = data.end.unwrap_or_else(get_unix_milliseconds_now);
+        let command = ServerCommand::ExportHistory(data.start, end);
+        command.send_async(output_stream).await?;
+
+        // An empty chunk ends the export. Errors are sent instead of the first chunk, so there is nothing to print then.
+        let mut header = Some(format_header(data.format));
+        loop {
+            match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::HistoryExport(Ok(entries)) => {
+                    if let Some(header) = header.take() {
+                        print!("{header}");
+                    }
+                    if entries.is_empty() {
+                        break;
+                    }
+                    print!("{}", format_entries(&entries, data.format));
+                }
+                ServerCommand::HistoryExport(Err(err)) => {
+                    eprintln!("ERROR: {err}");
+                    state.exit_code = 1;
+                    break;
+                }
+                _ => panic!("Unexpected command received after ExportHistory"),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_parsed() {
+        assert_eq!(parse_time("2026-10-01T00:00:00Z"), Ok(1790812800000));
+        assert_eq!(parse_time("2026-10-01T02:00:00+02:00"), Ok(1790812800000));
+        let week_ago = get_unix_milliseconds_now() - 7 * 24 * 3600 * 1000;
+        let time = parse_time("7d").unwrap();
+        assert!((week_ago..week_ago + 1000).contains(&time));
+        assert!(parse_time("1969-12-31T00:00:00Z").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn entries_are_formatted() {
+        let entries = [
+            HistoryEntry {
+                time_ms: 1790812800000,
+                name: "Backup".to_owned(),
+                status: Severity::Ok,
+                message: None,
+            },
+            HistoryEntry {
+                time_ms: 1790816400250,
+                name: "Backup".to_owned(),
+                status: Severity::Error,
+                message: Some("No space left, \"/\" is full".to_owned()),
+            },
+        ];
+        assert_eq!(
+            format_entries(&entries, ExportFormat::Csv),
+            "2026-10-01T00:00:00.000Z,Backup,ok,\n\
+             2026-10-01T01:00:00.250Z,Backup,error,\"No space left, \"\"/\"\" is full\"\n"
+        );
+        assert_eq!(
+            format_entries(&entries, ExportFormat::Json),
+            "{\"time\":\"2026-10-01T00:00:00.000Z\",\"name\":\"Backup\",\"status\":\"ok\",\"message\":null}\n\
+             {\"time\":\"2026-10-01T01:00:00.250Z\",\"name\":\"Backup\",\"status\":\"error\",\"message\":\"No space left, \\\"/\\\" is full\"}\n"
+        );
+        assert_eq!(format_entries(&[], ExportFormat::Csv), "");
+        assert_eq!(format_entries(&[], ExportFormat::Json), "");
+        assert_eq!(
+            format_header(ExportFormat::Csv),
+            "time,name,status,message\n"
+        );
+        assert_eq!(format_header(ExportFormat::Json), "");
+    }
+}
@@ -0,0 +1,280 @@
+// Periodic export of statuses in the line protocol of InfluxDB, for dashboards built on the TICK stack. Lines are either
+// posted to the HTTP write API or appended to a file, which can be picked up e.g. by Telegraf. Each client becomes a
+// point tagged with its name and group, which holds the tags of the client joined with commas.
+
+use super::definition::Action;
+use check_mate_common::constants::*;
+use check_mate_common::{glob_matches, ClientDetails, CommunicationError, ServerCommand};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum InfluxDestination {
+    /// URL of the write API, e.g. "http://localhost:8086/api/v2/write?org=ops&bucket=health".
+    Url(String),
+
+    /// File to which the lines are appended.
+    File(PathBuf),
+}
+
+/// Value parser for destinations of the line protocol. Anything which is not an http URL is a path of a file.
+pub fn parse_influx_destination(value: &str) -> Result<InfluxDestination, String> {
+    if value.starts_with("https://") {
+        return Err("https is not supported, use http or a file".to_owned());
+    }
+    match value.starts_with("http://") {
+        true => Ok(InfluxDestination::Url(value.to_owned())),
+        false => Ok(InfluxDestination::File(PathBuf::from(value))),
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct InfluxData {
+    pub destination: InfluxDestination,
+    pub token: Option<String>,
+    pub measurement: String,
+    pub interval: Duration,
+    pub name_filter: Option<String>,
+}
+
+impl InfluxData {
+    pub fn new(destination: InfluxDestination) -> Self {
+        Self {
+            destination,
+            token: None,
+            measurement: DEFAULT_INFLUX_MEASUREMENT.to_owned(),
+            interval: DEFAULT_INFLUX_INTERVAL,
+            name_filter: None,
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        match self.name_filter {
+            Some(ref pattern) => glob_matches(pattern, name),
+            None => true,
+        }
+    }
+}
+
+// Escapes characters which have a special meaning in the given part of a line
+fn escape(text: &str, special_characters: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if special_characters.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Status is 0 when the client is ok and 1 when it's in error. Age is the number of seconds the client has been in its
+// current status. Clients which haven't reported a status yet, e.g. ones only querying the server, are skipped.
+fn format_line(measurement: &str, details: &ClientDetails, timestamp: u128) -> Option<String> {
+    let (status, message) = match details.status.as_ref()? {
+        Ok(_) => (0, "ok"),
+        Err(err) => (1, err.as_str()),
+    };
+
+    let mut line = escape(measurement, &[',', ' ']);
+    line += ",client=";
+    line += &escape(&details.name, &[',', '=', ' ']);
+    if !details.tags.is_empty() {
+        line += ",group=";
+        line += &escape(&details.tags.join(","), &[',', '=', ' ']);
+    }
+
+    Some(format!(
+        "{line} status={status}i,age={}i,message=\"{}\" {timestamp}\n",
+        details.age_seconds,
+        escape(message, &['"', '\\'])
+    ))
+}
+
+// Splits an http URL into the address of the server and the path with the query
+fn split_url(url: &str) -> Result<(String, String), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("Invalid URL {url}"))?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(format!("Invalid URL {url}"));
+    }
+    let address = match host.contains(':') {
+        true => host.to_owned(),
+        false => format!("{host}:80"),
+    };
+    Ok((address, path.to_owned()))
+}
+
+fn check_response(response: &[u8]) -> Result<(), String> {
+    let response = String::from_utf8_lossy(response);
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status_code = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|x| x.parse::<u16>().ok())
+        .ok_or_else(|| "invalid response from InfluxDB".to_owned())?;
+    match status_code {
+        200..=299 => Ok(()),
+        _ => Err(format!(
+            "InfluxDB returned status {status_code}: {}",
+            body.trim()
+        )),
+    }
+}
+
+// HTTP/1.0 is used, so the server closes the connection after the response and doesn't use chunked encoding
+async fn post_lines(url: &str, token: Option<&str>, lines: &str) -> Result<(), String> {
+    let (address, path) = split_url(url)?;
+    let host = address.trim_end_matches(":80");
+    let authorization = token.map_or(String::new(), |x| format!("Authorization: Token {x}\r\n"));
+    let request = format!(
+        "POST {path} HTTP/1.0\r\n\
+         Host: {host}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         {authorization}\r\n\
+         {lines}",
+        lines.len()
+    );
+
+    let send = async {
+        let mut stream = tokio::net::TcpStream::connect(&address).await?;
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    let response = tokio::time::timeout(INFLUX_TIMEOUT, send)
+        .await
+        .map_err(|_| "InfluxDB did not respond".to_owned())?
+        .map_err(|err| format!("could not connect to {address}: {err}"))?;
+    check_response(&response)
+}
+
+async fn append_lines(path: &PathBuf, lines: &str) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(lines.as_bytes()).await
+}
+
+impl Action {
+    pub(crate) async fn export_to_influx(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &InfluxData,
+    ) -> Result<(), CommunicationError> {
+        let mut ticks = tokio::time::interval(data.interval);
+        loop {
+            ticks.tick().await;
+
+            let command = ServerCommand::GetClientDetails;
+            command.send_async(output_stream).await?;
+            let clients = match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::ClientDetails(details) => details,
+                _ => panic!("Unexpected command received after GetClientDetails"),
+            };
+
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos();
+            let lines = clients
+                .iter()
+                .filter(|x| data.matches_name(&x.name))
+                .filter_map(|x| format_line(&data.measurement, x, timestamp))
+                .collect::<String>();
+            if lines.is_empty() {
+                continue;
+            }
+
+            let result = match data.destination {
+                InfluxDestination::Url(ref url) => {
+                    post_lines(url, data.token.as_deref(), &lines).await
+                }
+                InfluxDestination::File(ref path) => append_lines(path, &lines)
+                    .await
+                    .map_err(|err| format!("could not write {}: {err}", path.display())),
+            };
+            if let Err(err) = result {
+                eprintln!("Failed to export statuses to InfluxDB: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_formatted() {
+        let error = ClientDetails::new("db primary", Some(Err("Disk \"data\" is full")))
+            .with_tags(&["db", "prod"])
+            .with_age(30);
+        assert_eq!(
+            format_line("checkmate", &error, 1700000000000000000).unwrap(),
+            "checkmate,client=db\\ primary,group=db\\,prod status=1i,age=30i,message=\"Disk \\\"data\\\" is full\" 1700000000000000000\n"
+        );
+
+        let ok = ClientDetails::new("backup", Some(Ok(()))).with_age(30);
+        assert_eq!(
+            format_line("health,v=1", &ok, 5).unwrap(),
+            "health\\,v=1,client=backup status=0i,age=30i,message=\"ok\" 5\n"
+        );
+
+        let unknown = ClientDetails::new("new", None).with_age(30);
+        assert_eq!(format_line("checkmate", &unknown, 5), None);
+    }
+
+    #[test]
+    fn destinations_are_parsed() {
+        assert_eq!(
+            parse_influx_destination("http://influx:8086/write?db=health"),
+            Ok(InfluxDestination::Url(
+                "http://influx:8086/write?db=health".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse_influx_destination("/var/lib/checkmate.lp"),
+            Ok(InfluxDestination::File(PathBuf::from(
+                "/var/lib/checkmate.lp"
+            )))
+        );
+        assert!(parse_influx_destination("https://influx:8086/write").is_err());
+    }
+
+    #[test]
+    fn urls_are_split() {
+        assert_eq!(
+            split_url("http://influx:8086/api/v2/write?bucket=b"),
+            Ok((
+                "influx:8086".to_owned(),
+                "/api/v2/write?bucket=b".to_owned()
+            ))
+        );
+        assert_eq!(
+            split_url("http://influx"),
+            Ok(("influx:80".to_owned(), "/".to_owned()))
+        );
+        assert!(split_url("http:///write").is_err());
+    }
+
+    #[test]
+    fn responses_are_checked() {
+        assert_eq!(check_response(b"HTTP/1.1 204 No Content\r\n\r\n"), Ok(()));
+        assert_eq!(
+            check_response(b"HTTP/1.1 401 Unauthorized\r\n\r\n{\"message\":\"unauthorized\"}"),
+            Err("InfluxDB returned status 401: {\"message\":\"unauthorized\"}".to_owned())
+        );
+        assert!(check_response(b"garbage").is_err());
+    }
+}
@@ -1,12 +1,26 @@
 use super::definition::Action;
+use super::output_format::{format_client_details_json, OutputFormat};
 use check_mate_common::{CommunicationError, ServerCommand};
 use tokio::io::{AsyncBufRead, AsyncWrite};
 
 impl Action {
     pub(crate) async fn list_clients(
         input_stream: &mut (impl AsyncBufRead + Unpin),
-        output_stream: &mut (impl AsyncWrite + Unpin)
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        output_format: OutputFormat,
     ) -> Result<(), CommunicationError> {
+        if output_format == OutputFormat::Json {
+            let command = ServerCommand::GetClientDetails;
+            command.send_async(output_stream).await?;
+            match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::ClientDetails(details) => {
+                    println!("{}", format_client_details_json(details.iter()));
+                }
+                _ => panic!("Unexpected command received after GetClientDetails"),
+            }
+            return Ok(());
+        }
+
         let command = ServerCommand::ListClients;
         command.send_async(output_stream).await?;
 
@@ -0,0 +1,333 @@
+use super::definition::Action;
+use check_mate_common::constants::*;
+use check_mate_common::{
+    glob_matches, ClientDetails, CommunicationError, ServerCommand, ServerStatistics,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncWrite, AsyncWriteExt};
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum MetricsFormat {
+    /// Lines sent over UDP to a statsd daemon.
+    #[default]
+    Statsd,
+
+    /// Plaintext protocol of Graphite, sent over TCP to Carbon.
+    Graphite,
+}
+
+impl std::fmt::Display for MetricsFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            MetricsFormat::Statsd => "statsd",
+            MetricsFormat::Graphite => "graphite",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct MetricsData {
+    pub address: String,
+    pub format: MetricsFormat,
+    pub prefix: String,
+    pub interval: Duration,
+    pub name_filter: Option<String>,
+}
+
+impl MetricsData {
+    pub fn new(address: String) -> Self {
+        Self {
+            address,
+            format: MetricsFormat::default(),
+            prefix: DEFAULT_METRICS_PREFIX.to_owned(),
+            interval: DEFAULT_METRICS_INTERVAL,
+            name_filter: None,
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        match self.name_filter {
+            Some(ref pattern) => glob_matches(pattern, name),
+            None => true,
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+enum MetricKind {
+    Gauge,
+    Counter,
+}
+
+#[derive(PartialEq, Debug)]
+struct Metric {
+    path: String,
+    value: u64,
+    kind: MetricKind,
+}
+
+impl Metric {
+    fn gauge(path: String, value: u64) -> Self {
+        Self {
+            path,
+            value,
+            kind: MetricKind::Gauge,
+        }
+    }
+
+    fn counter(path: String, value: u64) -> Self {
+        Self {
+            path,
+            value,
+            kind: MetricKind::Counter,
+        }
+    }
+}
+
+// Dots separate levels of metric paths, so client names can't contain them. Other characters which could be
+// problematic for statsd or Graphite are replaced as well.
+fn sanitize_metric_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+// Status of each client is a gauge, which is 0 when it's ok and 1 when it's in error. Clients which haven't reported
+// a status yet are skipped. Counters of the server are cumulative, so for statsd only their increase since the
+// previous report is sent.
+fn collect_metrics(
+    prefix: &str,
+    clients: &[ClientDetails],
+    statistics: &ServerStatistics,
+    previous_statistics: Option<&ServerStatistics>,
+) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    let mut errors = 0;
+    for details in clients {
+        let Some(ref status) = details.status else {
+            continue;
+        };
+        let path = format!("{prefix}.clients.{}", sanitize_metric_name(&details.name));
+        metrics.push(Metric::gauge(
+            format!("{path}.status"),
+            status.is_err() as u64,
+        ));
+        metrics.push(Metric::gauge(format!("{path}.age"), details.age_seconds));
+        errors += status.is_err() as u64;
+    }
+    metrics.push(Metric::gauge(format!("{prefix}.errors"), errors));
+
+    let server = format!("{prefix}.server");
+    metrics.push(Metric::gauge(
+        format!("{server}.uptime"),
+        statistics.uptime_seconds,
+    ));
+    metrics.push(Metric::gauge(
+        format!("{server}.connected_clients"),
+        statistics.connected_clients,
+    ));
+    metrics.push(Metric::gauge(
+        format!("{server}.statuses_stored"),
+        statistics.statuses_stored,
+    ));
+    let counters = [
+        (
+            "commands_processed",
+            statistics.commands_processed,
+            previous_statistics.map(|x| x.commands_processed),
+        ),
+        (
+            "notifications_sent",
+            statistics.notifications_sent,
+            previous_statistics.map(|x| x.notifications_sent),
+        ),
+    ];
+    for (name, value, previous) in counters {
+        // Counters start over when the server restarts, in which case the whole value is the increase
+        let increase = value.checked_sub(previous.unwrap_or(0)).unwrap_or(value);
+        metrics.push(Metric::counter(format!("{server}.{name}"), increase));
+    }
+    metrics
+}
+
+fn format_statsd(metric: &Metric) -> String {
+    let kind = match metric.kind {
+        MetricKind::Gauge => "g",
+        MetricKind::Counter => "c",
+    };
+    format!("{}:{}|{kind}\n", metric.path, metric.value)
+}
+
+fn format_graphite(metric: &Metric, timestamp: u64) -> String {
+    format!("{} {} {timestamp}\n", metric.path, metric.value)
+}
+
+// Lines are grouped into datagrams which fit into a typical MTU, so they are not fragmented
+fn pack_datagrams(lines: impl Iterator<Item = String>) -> Vec<String> {
+    let mut datagrams = Vec::new();
+    let mut datagram = String::new();
+    for line in lines {
+        if !datagram.is_empty() && datagram.len() + line.len() > STATSD_MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut datagram));
+        }
+        datagram.push_str(&line);
+    }
+    if !datagram.is_empty() {
+        datagrams.push(datagram);
+    }
+    datagrams
+}
+
+async fn send_metrics(data: &MetricsData, metrics: &[Metric]) -> std::io::Result<()> {
+    match data.format {
+        MetricsFormat::Statsd => {
+            let socket = tokio::net::UdpSocket::bind("0.0.0.0:0").await?;
+            socket.connect(&data.address).await?;
+            for datagram in pack_datagrams(metrics.iter().map(format_statsd)) {
+                socket.send(datagram.as_bytes()).await?;
+            }
+        }
+        MetricsFormat::Graphite => {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let lines = metrics
+                .iter()
+                .map(|x| format_graphite(x, timestamp))
+                .collect::<String>();
+            let mut stream = tokio::net::TcpStream::connect(&data.address).await?;
+            stream.write_all(lines.as_bytes()).await?;
+            stream.shutdown().await?;
+        }
+    }
+    Ok(())
+}
+
+impl Action {
+    pub(crate) async fn emit_metrics(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &MetricsData,
+    ) -> Result<(), CommunicationError> {
+        let mut previous_statistics = None;
+        let mut ticks = tokio::time::interval(data.interval);
+        loop {
+            ticks.tick().await;
+
+            let command = ServerCommand::GetClientDetails;
+            command.send_async(output_stream).await?;
+            let clients = match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::ClientDetails(details) => details
+                    .into_iter()
+                    .filter(|x| data.matches_name(&x.name))
+                    .collect::<Vec<_>>(),
+                _ => panic!("Unexpected command received after GetClientDetails"),
+            };
+            let command = ServerCommand::GetServerStatistics;
+            command.send_async(output_stream).await?;
+            let statistics = match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::ServerStatistics(statistics) => statistics,
+                _ => panic!("Unexpected command received after GetServerStatistics"),
+            };
+
+            // Graphite stores the cumulative values and computes rates by itself
+            let previous = match data.format {
+                MetricsFormat::Statsd => previous_statistics.as_ref(),
+                MetricsFormat::Graphite => None,
+            };
+            let metrics = collect_metrics(&data.prefix, &clients, &statistics, previous);
+            if let Err(err) = send_metrics(data, &metrics).await {
+                eprintln!("Failed to send metrics to {}: {}", data.address, err);
+            }
+            previous_statistics = Some(statistics);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_are_collected() {
+        let clients = [
+            ClientDetails::new("db.primary", Some(Err("Down"))).with_age(30),
+            ClientDetails::new("backup", Some(Ok(()))).with_age(30),
+            ClientDetails::new("new", None).with_age(30),
+        ];
+        let statistics = ServerStatistics {
+            uptime_seconds: 100,
+            connected_clients: 3,
+            commands_processed: 50,
+            statuses_stored: 2,
+            notifications_sent: 4,
+        };
+        let lines = collect_metrics("cm", &clients, &statistics, None)
+            .iter()
+            .map(format_statsd)
+            .collect::<String>();
+        assert_eq!(
+            lines,
+            "cm.clients.db_primary.status:1|g\n\
+             cm.clients.db_primary.age:30|g\n\
+             cm.clients.backup.status:0|g\n\
+             cm.clients.backup.age:30|g\n\
+             cm.errors:1|g\n\
+             cm.server.uptime:100|g\n\
+             cm.server.connected_clients:3|g\n\
+             cm.server.statuses_stored:2|g\n\
+             cm.server.commands_processed:50|c\n\
+             cm.server.notifications_sent:4|c\n"
+        );
+    }
+
+    #[test]
+    fn counters_report_increase() {
+        let previous = ServerStatistics {
+            commands_processed: 50,
+            notifications_sent: 4,
+            ..Default::default()
+        };
+        let current = ServerStatistics {
+            commands_processed: 80,
+            notifications_sent: 2, // server was restarted
+            ..Default::default()
+        };
+        let metrics = collect_metrics("cm", &[], &current, Some(&previous));
+        let counters = metrics
+            .iter()
+            .filter(|x| x.kind == MetricKind::Counter)
+            .map(|x| (x.path.as_str(), x.value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counters,
+            vec![
+                ("cm.server.commands_processed", 30),
+                ("cm.server.notifications_sent", 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn graphite_lines_have_timestamps() {
+        let metric = Metric::gauge("cm.errors".to_owned(), 2);
+        assert_eq!(
+            format_graphite(&metric, 1700000000),
+            "cm.errors 2 1700000000\n"
+        );
+    }
+
+    #[test]
+    fn statsd_lines_are_packed_into_datagrams() {
+        let line = "x".repeat(STATSD_MAX_DATAGRAM_SIZE / 2 - 1) + "\n";
+        let datagrams = pack_datagrams(std::iter::repeat_n(line.clone(), 3));
+        assert_eq!(datagrams, vec![line.repeat(2), line]);
+        assert!(pack_datagrams(std::iter::empty()).is_empty());
+    }
+}
@@ -1,13 +1,69 @@
 mod abort_action;
+mod api_key_action;
+mod availability_action;
+mod badge_action;
+mod checks;
+mod clear_action;
+mod color;
+mod compact_history_action;
+mod control_socket;
+mod cron_wrap_action;
 mod definition;
+mod docker_health_action;
+mod drain_action;
+mod export_action;
+mod influx_action;
 mod list_clients_action;
+mod metrics_action;
+mod notify_action;
+mod output_format;
+#[cfg(feature = "script")]
+mod output_script;
+mod process_limits;
+mod push_action;
 mod read_action;
+#[cfg(feature = "redis")]
+mod redis_action;
 mod refresh_action;
+mod secrets_action;
+mod stats_action;
+mod top_action;
 mod watch_action;
 
-pub use abort_action::*;
+pub use api_key_action::ApiKeyRequest;
+pub use availability_action::AvailabilityWindow;
+pub use badge_action::BadgeData;
+#[cfg(any(target_os = "linux", windows))]
+pub use checks::LogCheck;
+#[cfg(feature = "smart")]
+pub use checks::SmartCheck;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+pub use checks::SystemdCheck;
+#[cfg(feature = "wasm")]
+pub use checks::WasmCheck;
+pub use checks::{
+    BuiltinCheck, DiskCheck, DnsCheck, DockerCheck, FileCheck, PingCheck, PluginCheck,
+    ProcessCheck, SystemCheck,
+};
+pub use color::ColorChoice;
+pub(crate) use control_socket::send_control_command;
+pub use control_socket::{
+    ControlCommand, ControlData, ControlRequest, ControlRequests, ControlSocket,
+};
+pub use cron_wrap_action::CronWrapData;
 pub use definition::*;
-pub use list_clients_action::*;
-pub use read_action::*;
-pub use refresh_action::*;
+pub use export_action::{parse_time, ExportData, ExportFormat};
+pub use influx_action::{parse_influx_destination, InfluxData, InfluxDestination};
+pub use metrics_action::{MetricsData, MetricsFormat};
+pub use notify_action::NotifyData;
+pub use output_format::OutputFormat;
+#[cfg(feature = "script")]
+pub use output_script::OutputScript;
+pub use process_limits::ProcessLimits;
+pub use push_action::PushedStatus;
+pub use read_action::{GroupBy, ReadMessagesData, SortKey, TimestampFormat};
+#[cfg(feature = "redis")]
+pub use redis_action::RedisData;
+pub use secrets_action::{execute_secrets_request, SecretsRequest};
+pub use top_action::TopData;
 pub use watch_action::*;
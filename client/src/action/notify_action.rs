@@ -0,0 +1,202 @@
+use super::definition::Action;
+use super::output_format::{format_status_change, OutputFormat};
+use check_mate_common::{glob_matches, ClientDetails, CommunicationError, ServerCommand, Severity};
+use std::collections::HashMap;
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+#[derive(PartialEq, Debug, Default)]
+pub struct NotifyData {
+    pub name_filter: Option<String>,
+    pub recoveries: bool,
+}
+
+impl NotifyData {
+    fn matches_name(&self, name: &str) -> bool {
+        match self.name_filter {
+            Some(ref pattern) => glob_matches(pattern, name),
+            None => true,
+        }
+    }
+}
+
+// Returns severity of the reported status, or None if the client hasn't reported anything. Pending flag is ignored,
+// because a client waiting for its next report still has the same status.
+fn get_status_severity(details: &ClientDetails) -> Option<Severity> {
+    match details.status {
+        None => None,
+        Some(Ok(_)) => Some(Severity::Ok),
+        Some(Err(_)) if details.warning => Some(Severity::Warning),
+        Some(Err(_)) => Some(Severity::Error),
+    }
+}
+
+// Returns title and body of a notification about the status change, if it's worth one. Only transitions into a warning
+// or an error are notified, so a client repeating the same error or changing its message doesn't cause a flood of
+// notifications. An error turning into a warning is an improvement, so it isn't notified either.
+fn get_notification(
+    data: &NotifyData,
+    previous_severity: Option<Severity>,
+    details: &ClientDetails,
+) -> Option<(String, String)> {
+    let message = match &details.status {
+        Some(Err(message)) => message.clone(),
+        _ => "ok".to_owned(),
+    };
+    match (previous_severity, get_status_severity(details)?) {
+        (Some(Severity::Error), Severity::Error | Severity::Warning) => None,
+        (_, Severity::Error) => Some((format!("{} has failed", details.name), message)),
+        (Some(Severity::Warning), Severity::Warning) => None,
+        (_, Severity::Warning) => Some((format!("{} has a warning", details.name), message)),
+        (Some(Severity::Error | Severity::Warning), Severity::Ok) if data.recoveries => {
+            Some((format!("{} has recovered", details.name), message))
+        }
+        _ => None,
+    }
+}
+
+fn show_desktop_notification(title: &str, body: &str) {
+    let mut command;
+    if cfg!(target_os = "macos") {
+        // Texts are passed as arguments, so they don't have to be escaped for AppleScript
+        command = tokio::process::Command::new("osascript");
+        command.args([
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 2 of argv) with title (item 1 of argv)",
+            "-e",
+            "end run",
+            title,
+            body,
+        ]);
+    } else if cfg!(windows) {
+        // Texts are passed in environment variables, so they don't have to be escaped for PowerShell
+        command = tokio::process::Command::new("powershell");
+        command.args(["-NoProfile", "-Command", WINDOWS_TOAST_SCRIPT]);
+        command.env("CHECK_MATE_TITLE", title);
+        command.env("CHECK_MATE_BODY", body);
+    } else {
+        command = tokio::process::Command::new("notify-send");
+        command.args(["--app-name=CheckMate", title, body]);
+    }
+
+    // The notification is shown in the background, so status changes are not delayed by it
+    if let Err(err) = command.spawn() {
+        eprintln!("Failed to show desktop notification: {}", err);
+    }
+}
+
+const WINDOWS_TOAST_SCRIPT: &str = "\
+$null = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]
+$template = [Windows.UI.Notifications.ToastTemplateType]::ToastText02
+$xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent($template)
+$texts = $xml.GetElementsByTagName('text')
+$null = $texts.Item(0).AppendChild($xml.CreateTextNode($env:CHECK_MATE_TITLE))
+$null = $texts.Item(1).AppendChild($xml.CreateTextNode($env:CHECK_MATE_BODY))
+$toast = [Windows.UI.Notifications.ToastNotification]::new($xml)
+[Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('CheckMate').Show($toast)";
+
+impl Action {
+    pub(crate) async fn notify(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &NotifyData,
+    ) -> Result<(), CommunicationError> {
+        // Learn current statuses first, so errors which were already there don't raise notifications
+        let command = ServerCommand::GetClientDetails;
+        command.send_async(output_stream).await?;
+        let mut severities = match ServerCommand::receive_async(input_stream).await? {
+            ServerCommand::ClientDetails(details) => details
+                .into_iter()
+                .filter_map(|x| get_status_severity(&x).map(|severity| (x.name, severity)))
+                .collect::<HashMap<_, _>>(),
+            _ => panic!("Unexpected command received after GetClientDetails"),
+        };
+
+        let command = ServerCommand::Subscribe;
+        command.send_async(output_stream).await?;
+        loop {
+            let details = match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::StatusChanged(details) => details,
+                _ => panic!("Unexpected command received after Subscribe"),
+            };
+            if !data.matches_name(&details.name) {
+                continue;
+            }
+            if let Some((title, body)) =
+                get_notification(data, severities.get(&details.name).copied(), &details)
+            {
+                println!("{}", format_status_change(&details, OutputFormat::Text));
+                show_desktop_notification(&title, &body);
+            }
+            if let Some(severity) = get_status_severity(&details) {
+                severities.insert(details.name, severity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notifications_are_raised_on_transitions_into_error() {
+        fn run(
+            recoveries: bool,
+            previous: Option<Severity>,
+            current: Option<Result<(), String>>,
+            expected_title: Option<&str>,
+        ) {
+            run_with_warning(recoveries, previous, current, false, expected_title);
+        }
+        fn run_with_warning(
+            recoveries: bool,
+            previous: Option<Severity>,
+            current: Option<Result<(), String>>,
+            warning: bool,
+            expected_title: Option<&str>,
+        ) {
+            let data = NotifyData {
+                name_filter: None,
+                recoveries,
+            };
+            let details = ClientDetails {
+                name: "backup".to_owned(),
+                status: current,
+                pending: false,
+                warning,
+                age_seconds: 0,
+                tags: Vec::new(),
+            };
+            let notification = get_notification(&data, previous, &details);
+            assert_eq!(notification.map(|x| x.0).as_deref(), expected_title);
+        }
+
+        let error = || Some(Err("No space left".to_owned()));
+        let ok = Some(Severity::Ok);
+        let failed = Some(Severity::Error);
+        let warned = Some(Severity::Warning);
+        run(false, None, error(), Some("backup has failed"));
+        run(false, ok, error(), Some("backup has failed"));
+        run(
+            false,
+            failed,
+            Some(Err("Still no space left".to_owned())),
+            None,
+        );
+        run(false, None, Some(Ok(())), None);
+        run(false, failed, Some(Ok(())), None);
+        run(true, failed, Some(Ok(())), Some("backup has recovered"));
+        run(true, ok, Some(Ok(())), None);
+        run(true, failed, None, None);
+
+        run_with_warning(false, None, error(), true, Some("backup has a warning"));
+        run_with_warning(false, ok, error(), true, Some("backup has a warning"));
+        run_with_warning(false, warned, error(), true, None);
+        run_with_warning(false, failed, error(), true, None);
+        run(false, warned, error(), Some("backup has failed"));
+        run(false, warned, Some(Ok(())), None);
+        run(true, warned, Some(Ok(())), Some("backup has recovered"));
+    }
+}
@@ -0,0 +1,155 @@
+use check_mate_common::{ClientDetails, ClientInfo};
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum OutputFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+
+    /// Array of objects with name, status, message and age (in seconds) fields.
+    Json,
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+pub(crate) fn format_client_details_json<'a>(
+    details: impl Iterator<Item = &'a ClientDetails>,
+) -> String {
+    let details = details.map(ClientInfo::from).collect::<Vec<_>>();
+    serde_json::to_string_pretty(&details).expect("Client details should be serializable")
+}
+
+pub(crate) fn format_status_change(details: &ClientDetails, output_format: OutputFormat) -> String {
+    match output_format {
+        OutputFormat::Text => match details.status {
+            Some(Err(ref message)) if details.warning => {
+                format!("{}: warning: {}", details.name, message)
+            }
+            Some(Err(ref message)) => format!("{}: {}", details.name, message),
+            _ => format!("{}: {}", details.name, details.severity()),
+        },
+        OutputFormat::Json => serde_json::to_string(&ClientInfo::from(details))
+            .expect("Client details should be serializable"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_details_are_formatted_as_json() {
+        let details = [
+            ClientDetails {
+                name: "Unreported".to_owned(),
+                status: None,
+                pending: false,
+                warning: false,
+                age_seconds: 3,
+                tags: Vec::new(),
+            },
+            ClientDetails {
+                name: "Healthy".to_owned(),
+                status: Some(Ok(())),
+                pending: false,
+                warning: false,
+                age_seconds: 5,
+                tags: Vec::new(),
+            },
+            ClientDetails {
+                name: "Broken".to_owned(),
+                status: Some(Err("Disk \"/\" is full".to_owned())),
+                pending: false,
+                warning: false,
+                age_seconds: 120,
+                tags: vec!["db".to_owned()],
+            },
+        ];
+        let json = format_client_details_json(details.iter());
+        let json: serde_json::Value =
+            serde_json::from_str(&json).expect("Output should be valid JSON");
+
+        let expected = serde_json::json!([
+            {"name": "Unreported", "status": "unknown", "message": null, "age": null, "tags": []},
+            {"name": "Healthy", "status": "ok", "message": null, "age": 5, "tags": []},
+            {"name": "Broken", "status": "error", "message": "Disk \"/\" is full", "age": 120, "tags": ["db"]},
+        ]);
+        assert_eq!(json, expected);
+    }
+
+    #[test]
+    fn status_change_is_formatted() {
+        let ok = ClientDetails {
+            name: "Healthy".to_owned(),
+            status: Some(Ok(())),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        };
+        let error = ClientDetails {
+            name: "Broken".to_owned(),
+            status: Some(Err("Disk is full".to_owned())),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        };
+
+        assert_eq!(format_status_change(&ok, OutputFormat::Text), "Healthy: ok");
+        assert_eq!(
+            format_status_change(&error, OutputFormat::Text),
+            "Broken: Disk is full"
+        );
+        assert_eq!(
+            format_status_change(&ok, OutputFormat::Json),
+            r#"{"name":"Healthy","status":"ok","message":null,"age":0,"tags":[]}"#
+        );
+        assert_eq!(
+            format_status_change(&error, OutputFormat::Json),
+            r#"{"name":"Broken","status":"error","message":"Disk is full","age":0,"tags":[]}"#
+        );
+
+        let warning =
+            ClientDetails::new("Degraded", Some(Err("Disk is almost full"))).with_warning();
+        assert_eq!(
+            format_status_change(&warning, OutputFormat::Text),
+            "Degraded: warning: Disk is almost full"
+        );
+        assert_eq!(
+            format_status_change(&warning, OutputFormat::Json),
+            r#"{"name":"Degraded","status":"warning","message":"Disk is almost full","age":0,"tags":[]}"#
+        );
+
+        let pending = ClientDetails {
+            name: "Starting".to_owned(),
+            status: None,
+            pending: true,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        };
+        assert_eq!(
+            format_status_change(&pending, OutputFormat::Text),
+            "Starting: pending"
+        );
+        assert_eq!(
+            format_status_change(&pending, OutputFormat::Json),
+            r#"{"name":"Starting","status":"pending","message":null,"age":null,"tags":[]}"#
+        );
+    }
+
+    #[test]
+    fn empty_client_details_are_formatted_as_empty_json_array() {
+        assert_eq!(format_client_details_json([].iter()), "[]");
+    }
+}
@@ -0,0 +1,174 @@
+use super::watch_action::WatchStatus;
+use check_mate_common::constants::*;
+use rhai::{Dynamic, Engine, Scope, AST};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Rhai script deciding the status of the watched command, for cases where regexes and exit codes aren't expressive
+// enough. The script gets "exit_code" (-1 if not available) and "output" variables. Its last expression is the
+// result, which can be:
+//   - nothing or an empty string, meaning success,
+//   - a string, which is the error message,
+//   - a map like #{status: "warning", message: "Disk almost full"}, where the status is one of ok, warning, critical,
+//     unknown or error. Warnings are reported as such and the other statuses as errors, like in Nagios mode.
+#[derive(Clone)]
+pub struct OutputScript {
+    path: PathBuf,
+    engine: Arc<Engine>,
+    ast: AST,
+}
+
+// Scripts are compared by their paths, so they can be a part of the config
+impl PartialEq for OutputScript {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+impl std::fmt::Debug for OutputScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputScript")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl OutputScript {
+    // Scripts are compiled when parsing arguments, so syntax errors are reported at startup
+    pub fn load(path: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+        let ast = engine
+            .compile_file(PathBuf::from(path))
+            .map_err(|err| err.to_string())?;
+        Ok(Self {
+            path: PathBuf::from(path),
+            engine: Arc::new(engine),
+            ast,
+        })
+    }
+
+    pub(crate) fn evaluate(&self, exit_code: Option<i32>, output: &str) -> WatchStatus {
+        let mut scope = Scope::new();
+        scope.push("exit_code", exit_code.map_or(-1, i64::from));
+        scope.push("output", output.to_owned());
+        match self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+        {
+            Ok(result) => interpret_script_result(result),
+            Err(err) => WatchStatus::error(format!("Script {} failed: {err}", self.path.display())),
+        }
+    }
+}
+
+fn interpret_script_result(result: Dynamic) -> WatchStatus {
+    if result.is_unit() {
+        return WatchStatus::ok();
+    }
+    if result.is_string() {
+        let message = result.into_string().unwrap_or_default();
+        return match message.trim() {
+            "" => WatchStatus::ok(),
+            message => WatchStatus::error(message.to_owned()),
+        };
+    }
+    let Some(map) = result.try_cast::<rhai::Map>() else {
+        return WatchStatus::error("Script returned neither a string nor a map".to_owned());
+    };
+
+    let field = |name: &str| {
+        map.get(name)
+            .and_then(|x| x.clone().into_string().ok())
+            .unwrap_or_default()
+    };
+    let status = field("status");
+    let message = field("message");
+    match status.as_str() {
+        "ok" => WatchStatus::ok(),
+        "warning" => WatchStatus::warning(message),
+        "error" | "critical" | "unknown" => WatchStatus::error(message),
+        _ => WatchStatus::error(format!("Script returned invalid status \"{status}\"")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each script is written to a separate file, because tests are run in parallel
+    fn load(script: &str) -> OutputScript {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let index = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "check_mate_script_{}_{index}.rhai",
+            std::process::id()
+        ));
+        std::fs::write(&path, script).unwrap();
+        let script = OutputScript::load(path.to_str().unwrap()).expect("Script should compile");
+        std::fs::remove_file(&path).unwrap();
+        script
+    }
+
+    fn run(script: &str, exit_code: Option<i32>, output: &str, expected: Result<(), &str>) {
+        let status = load(script).evaluate(exit_code, output);
+        assert_eq!(status, expected.map_err(|x| x.to_owned()).into());
+    }
+
+    #[test]
+    fn script_results_are_interpreted() {
+        let script = r#"
+            if exit_code != 0 {
+                return "Exit code was " + exit_code;
+            }
+            output.trim();
+            let free = parse_int(output);
+            if free < 10 {
+                #{status: "critical", message: `Only ${free}% free`}
+            } else if free < 20 {
+                #{status: "warning", message: `Only ${free}% free`}
+            }
+        "#;
+        run(script, Some(0), "50\n", Ok(()));
+        run(script, Some(0), "5\n", Err("Only 5% free"));
+        run(script, Some(2), "", Err("Exit code was 2"));
+        run(script, None, "", Err("Exit code was -1"));
+
+        let status = load(script).evaluate(Some(0), "15\n");
+        assert_eq!(status, WatchStatus::warning("Only 15% free".to_owned()));
+    }
+
+    #[test]
+    fn invalid_script_results_are_reported() {
+        run(
+            "42",
+            Some(0),
+            "",
+            Err("Script returned neither a string nor a map"),
+        );
+        run(
+            r#"#{status: "bad"}"#,
+            Some(0),
+            "",
+            Err("Script returned invalid status \"bad\""),
+        );
+        run(
+            r#"#{status: "error", message: "Broken"}"#,
+            Some(0),
+            "",
+            Err("Broken"),
+        );
+        run(r#""  ""#, Some(0), "", Ok(()));
+    }
+
+    #[test]
+    fn runaway_script_is_stopped() {
+        let error = load("loop {}").evaluate(Some(0), "").result.unwrap_err();
+        assert!(error.contains("Too many operations"), "{error}");
+    }
+
+    #[test]
+    fn missing_script_is_reported() {
+        assert!(OutputScript::load("/nonexistent/script.rhai").is_err());
+    }
+}
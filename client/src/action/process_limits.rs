@@ -0,0 +1,119 @@
+use std::io;
+
+// Limits applied to the watched command, so heavyweight or untrusted commands can't impact the host. They are
+// applied when the command is spawned, which is platform specific.
+#[derive(PartialEq, Debug, Default, Clone)]
+pub struct ProcessLimits {
+    pub nice: Option<i32>,
+    pub memory_limit: Option<u64>,
+    pub user: Option<String>,
+}
+
+impl ProcessLimits {
+    pub(crate) fn apply(&self, command: &mut tokio::process::Command) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            self.apply_unix(command)
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = command;
+            if *self == Self::default() {
+                Ok(())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Resource limits are not supported on this platform",
+                ))
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn apply_unix(&self, command: &mut tokio::process::Command) -> io::Result<()> {
+        if let Some(ref user) = self.user {
+            let (uid, gid) = lookup_user(user)?;
+            command.uid(uid).gid(gid);
+        }
+
+        let nice = self.nice;
+        let memory_limit = self.memory_limit;
+        if nice.is_none() && memory_limit.is_none() {
+            return Ok(());
+        }
+
+        // SAFETY: The closure runs in the child between fork and exec, so it may only call async-signal-safe
+        // functions. setpriority() and setrlimit() are plain system calls, which don't allocate or take locks.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(nice) = nice {
+                    if libc::setpriority(libc::PRIO_PROCESS, 0, nice) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if let Some(memory_limit) = memory_limit {
+                    let limit = libc::rlimit {
+                        rlim_cur: memory_limit as libc::rlim_t,
+                        rlim_max: memory_limit as libc::rlim_t,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_AS, &limit) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+}
+
+// Returns uid and primary gid of the user with given name
+#[cfg(unix)]
+fn lookup_user(name: &str) -> io::Result<(u32, u32)> {
+    let not_found = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("User \"{name}\" not found"),
+        )
+    };
+    let name = std::ffi::CString::new(name).map_err(|_| not_found())?;
+    let mut buffer = vec![0 as libc::c_char; 16 * 1024];
+    // SAFETY: passwd is a plain C struct, for which all zeroes is a valid value
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: All pointers are valid for the duration of the call and the length matches the buffer. Strings in
+    // passwd point into the buffer, but they are not used.
+    let error = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if error != 0 {
+        return Err(io::Error::from_raw_os_error(error));
+    }
+    if result.is_null() {
+        return Err(not_found());
+    }
+    Ok((passwd.pw_uid, passwd.pw_gid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn users_are_looked_up() {
+        assert_eq!(lookup_user("root").expect("Root should exist"), (0, 0));
+
+        let err = lookup_user("check_mate_nonexistent_user").expect_err("User shouldn't exist");
+        assert_eq!(
+            err.to_string(),
+            "User \"check_mate_nonexistent_user\" not found"
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use super::definition::Action;
+use check_mate_common::{CommunicationError, ServerCommand};
+use tokio::io::{AsyncReadExt, AsyncWrite};
+
+#[derive(PartialEq, Debug)]
+pub enum PushedStatus {
+    Ok,
+    Error(String),
+    ErrorFromStdin,
+}
+
+impl Action {
+    pub(crate) async fn push_status(
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        status: &PushedStatus,
+    ) -> Result<(), CommunicationError> {
+        let status = match status {
+            PushedStatus::Ok => Ok(()),
+            PushedStatus::Error(message) => Err(message.clone()),
+            PushedStatus::ErrorFromStdin => {
+                let mut message = String::new();
+                tokio::io::stdin().read_to_string(&mut message).await?;
+                match message.trim() {
+                    "" => Err("Unknown error".to_owned()),
+                    message => Err(message.to_owned()),
+                }
+            }
+        };
+        let command = ServerCommand::PushStatus(status);
+        command.send_async(output_stream).await
+    }
+}
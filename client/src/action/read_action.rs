@@ -1,28 +1,645 @@
+use super::color::{colorize, ColorChoice};
 use super::definition::Action;
-use check_mate_common::{CommunicationError, ServerCommand};
+use super::output_format::{format_client_details_json, format_status_change, OutputFormat};
+use check_mate_common::constants::*;
+use check_mate_common::{glob_matches, ClientDetails, CommunicationError, ServerCommand, Severity};
+use chrono::{DateTime, Local};
+use std::collections::BTreeMap;
+use std::time::Duration;
 use tokio::io::{AsyncBufRead, AsyncWrite};
 
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum SortKey {
+    /// Sort alphabetically by client name.
+    Name,
+
+    /// Sort by time elapsed since the last status report, most recent first.
+    Age,
+
+    /// Sort errors first, then warnings, clients without any status and ok clients last.
+    Severity,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum GroupBy {
+    /// Group by client tags. Clients with multiple tags appear in multiple groups.
+    Tag,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum TimestampFormat {
+    /// Print how long ago the status was reported, e.g. "for 3h".
+    Relative,
+
+    /// Print the local time at which the status was reported, e.g. "since 09:41".
+    Absolute,
+}
+
+impl std::fmt::Display for TimestampFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = match self {
+            TimestampFormat::Relative => "relative",
+            TimestampFormat::Absolute => "absolute",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub struct ReadMessagesData {
+    pub include_names: bool,
+    pub output_format: OutputFormat,
+    pub follow: bool,
+    pub every: Option<Duration>,
+    pub diff: bool,
+    pub name_filter: Option<String>,
+    pub all: bool,
+    pub color: ColorChoice,
+    pub sort: Option<SortKey>,
+    pub group_by: Option<GroupBy>,
+    pub summary: bool,
+    pub quiet: bool,
+    pub timestamps: Option<TimestampFormat>,
+}
+
+impl Default for ReadMessagesData {
+    fn default() -> Self {
+        Self {
+            include_names: DEFAULT_INCLUDE_NAMES,
+            output_format: OutputFormat::default(),
+            follow: false,
+            every: None,
+            diff: false,
+            name_filter: None,
+            all: false,
+            color: ColorChoice::default(),
+            sort: None,
+            group_by: None,
+            summary: false,
+            quiet: false,
+            timestamps: None,
+        }
+    }
+}
+
+// Counts of statuses of all clients matching the name filter, regardless of whether they were printed. There are
+// no counts of stale or acknowledged statuses, because the server doesn't track such states.
+#[derive(PartialEq, Debug, Default)]
+pub(crate) struct StatusSummary {
+    ok: usize,
+    errors: usize,
+    warnings: usize,
+    pending: usize,
+    unknown: usize,
+}
+
+impl StatusSummary {
+    pub(crate) fn new(statuses: &[ClientDetails]) -> Self {
+        let mut summary = Self::default();
+        for details in statuses {
+            match details.status {
+                None if details.pending => summary.pending += 1,
+                None => summary.unknown += 1,
+                Some(Ok(_)) => summary.ok += 1,
+                Some(Err(_)) if details.warning => summary.warnings += 1,
+                Some(Err(_)) => summary.errors += 1,
+            }
+        }
+        summary
+    }
+}
+
+impl std::fmt::Display for StatusSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors_noun = if self.errors == 1 { "error" } else { "errors" };
+        write!(f, "{} ok, {} {}", self.ok, self.errors, errors_noun)?;
+        if self.warnings > 0 {
+            let warnings_noun = if self.warnings == 1 {
+                "warning"
+            } else {
+                "warnings"
+            };
+            write!(f, ", {} {}", self.warnings, warnings_noun)?;
+        }
+        if self.pending > 0 {
+            write!(f, ", {} pending", self.pending)?;
+        }
+        if self.unknown > 0 {
+            write!(f, ", {} unknown", self.unknown)?;
+        }
+        Ok(())
+    }
+}
+
+impl ReadMessagesData {
+    fn matches_name(&self, name: &str) -> bool {
+        match self.name_filter {
+            Some(ref pattern) => glob_matches(pattern, name),
+            None => true,
+        }
+    }
+}
+
 impl Action {
     pub(crate) async fn read(
         input_stream: &mut (impl AsyncBufRead + Unpin),
         output_stream: &mut (impl AsyncWrite + Unpin),
-        include_names: bool,
+        data: &ReadMessagesData,
     ) -> Result<(), CommunicationError> {
-        let command = ServerCommand::GetStatuses(include_names);
+        if let Some(interval) = data.every {
+            return Self::poll(input_stream, output_stream, data, interval).await;
+        }
+
+        let (statuses, summary) = Self::fetch_statuses(input_stream, output_stream, data).await?;
+        match data.output_format {
+            OutputFormat::Text => print_text_with_summary(&statuses, &summary, data),
+            OutputFormat::Json => println!("{}", format_client_details_json(statuses.iter())),
+        }
+
+        if data.follow {
+            Self::follow(input_stream, output_stream, data).await?;
+        }
+        Ok(())
+    }
+
+    // Returns details of clients which match the name filter. Unless all clients were requested, only the ones
+    // with errors are returned. The summary always covers all clients matching the name filter.
+    async fn fetch_statuses(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &ReadMessagesData,
+    ) -> Result<(Vec<ClientDetails>, StatusSummary), CommunicationError> {
+        let command = ServerCommand::GetClientDetails;
         command.send_async(output_stream).await?;
 
         match ServerCommand::receive_async(input_stream).await? {
-            ServerCommand::Statuses(statuses) => {
-                let mut iter = statuses.iter().peekable();
-                while let Some(status) = iter.next() {
-                    println!("{}", status);
-                    if iter.peek().is_some() {
-                        println!();
+            ServerCommand::ClientDetails(details) => {
+                let details = details
+                    .into_iter()
+                    .filter(|x| data.matches_name(&x.name))
+                    .collect::<Vec<_>>();
+                let summary = StatusSummary::new(&details);
+                let mut details = details
+                    .into_iter()
+                    .filter(|x| data.all || matches!(x.status, Some(Err(_))))
+                    .collect::<Vec<_>>();
+                if let Some(sort_key) = data.sort {
+                    sort_statuses(&mut details, sort_key);
+                }
+                Ok((details, summary))
+            }
+            _ => panic!("Unexpected command received after GetClientDetails"),
+        }
+    }
+
+    // Queries statuses over the same connection on every interval, until the connection is closed
+    async fn poll(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &ReadMessagesData,
+        interval: Duration,
+    ) -> Result<(), CommunicationError> {
+        let mut previous_statuses: Option<Vec<String>> = None;
+        loop {
+            let (statuses, summary) =
+                Self::fetch_statuses(input_stream, output_stream, data).await?;
+            match data.output_format {
+                OutputFormat::Text => {
+                    if data.diff {
+                        let statuses = format_statuses(&statuses, data);
+                        let previous = previous_statuses.as_deref().unwrap_or_default();
+                        for line in diff_statuses(previous, &statuses) {
+                            println!("{}", line);
+                        }
+                        previous_statuses = Some(statuses);
+                    } else {
+                        if previous_statuses.is_some() {
+                            println!("{}", POLL_SEPARATOR);
+                        }
+                        print_text_with_summary(&statuses, &summary, data);
+                        previous_statuses = Some(Vec::new());
                     }
                 }
+                OutputFormat::Json => {
+                    println!("{}", format_client_details_json(statuses.iter()))
+                }
             }
-            _ => panic!("Unexpected command received after GetStatuses"),
+
+            tokio::time::sleep(interval).await;
         }
-        Ok(())
+    }
+
+    // Prints status changes of all clients as they happen, until the connection is closed
+    async fn follow(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &ReadMessagesData,
+    ) -> Result<(), CommunicationError> {
+        let command = ServerCommand::Subscribe;
+        command.send_async(output_stream).await?;
+
+        loop {
+            match ServerCommand::receive_async(input_stream).await? {
+                ServerCommand::StatusChanged(details) => {
+                    if data.matches_name(&details.name) {
+                        let line = format_status_change(&details, data.output_format);
+                        if data.output_format == OutputFormat::Text && data.color.is_enabled() {
+                            println!("{}", colorize(&line, &details));
+                        } else {
+                            println!("{}", line);
+                        }
+                    }
+                }
+                _ => panic!("Unexpected command received after Subscribe"),
+            }
+        }
+    }
+}
+
+const POLL_SEPARATOR: &str = "----------";
+
+fn format_statuses(statuses: &[ClientDetails], data: &ReadMessagesData) -> Vec<String> {
+    let use_color = data.color.is_enabled();
+    let now = Local::now();
+    statuses
+        .iter()
+        .map(|details| {
+            let message = match details.status {
+                None => details.severity().as_str(),
+                Some(Ok(_)) => "ok",
+                Some(Err(ref message)) => message.as_str(),
+            };
+            let mut text = if data.include_names {
+                format!("{}: {}", details.name, message)
+            } else {
+                message.to_owned()
+            };
+            if let (Some(format), Some(_)) = (data.timestamps, &details.status) {
+                let timestamp = format_timestamp(details.age_seconds, format, now);
+                text = format!("{} ({})", text, timestamp);
+            }
+            if use_color {
+                colorize(&text, details)
+            } else {
+                text
+            }
+        })
+        .collect()
+}
+
+fn format_timestamp(age_seconds: u64, format: TimestampFormat, now: DateTime<Local>) -> String {
+    match format {
+        TimestampFormat::Relative => format!("for {}", format_age(age_seconds)),
+        TimestampFormat::Absolute => {
+            let time = now - chrono::Duration::seconds(age_seconds as i64);
+            if time.date_naive() == now.date_naive() {
+                format!("since {}", time.format("%H:%M"))
+            } else {
+                format!("since {}", time.format("%Y-%m-%d %H:%M"))
+            }
+        }
+    }
+}
+
+// Formats the age in its largest unit, e.g. "3h"
+pub(crate) fn format_age(age_seconds: u64) -> String {
+    let units = [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)];
+    units
+        .iter()
+        .find(|(_, unit_seconds)| age_seconds >= *unit_seconds)
+        .map(|(unit, unit_seconds)| format!("{}{}", age_seconds / unit_seconds, unit))
+        .unwrap_or_else(|| format!("{}s", age_seconds))
+}
+
+fn print_text(statuses: &[ClientDetails], data: &ReadMessagesData) {
+    match data.group_by {
+        None => print_statuses(&format_statuses(statuses, data)),
+        Some(GroupBy::Tag) => {
+            let groups = group_statuses_by_tag(statuses);
+            let mut iter = groups.iter().peekable();
+            while let Some((tag, statuses)) = iter.next() {
+                match tag {
+                    Some(tag) => println!("[{}]", tag),
+                    None => println!("[no tag]"),
+                }
+                print_statuses(&format_statuses(statuses, data));
+                if iter.peek().is_some() {
+                    println!();
+                }
+            }
+        }
+    }
+}
+
+fn print_text_with_summary(
+    statuses: &[ClientDetails],
+    summary: &StatusSummary,
+    data: &ReadMessagesData,
+) {
+    if !data.quiet {
+        print_text(statuses, data);
+    }
+    if data.summary || data.quiet {
+        if !data.quiet && !statuses.is_empty() {
+            println!();
+        }
+        println!("{}", summary);
+    }
+}
+
+pub(crate) fn sort_statuses(statuses: &mut [ClientDetails], sort_key: SortKey) {
+    fn severity(details: &ClientDetails) -> u8 {
+        match details.severity() {
+            Severity::Error => 0,
+            Severity::Warning => 1,
+            Severity::Pending | Severity::Unknown => 2,
+            Severity::Ok => 3,
+        }
+    }
+
+    // Names are always used as the last criterion, so the order is deterministic
+    match sort_key {
+        SortKey::Name => statuses.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortKey::Age => {
+            statuses.sort_by(|a, b| (a.age_seconds, &a.name).cmp(&(b.age_seconds, &b.name)))
+        }
+        SortKey::Severity => {
+            statuses.sort_by(|a, b| (severity(a), &a.name).cmp(&(severity(b), &b.name)))
+        }
+    }
+}
+
+// Groups are ordered by tag name, with clients without any tags placed in the last group
+fn group_statuses_by_tag(statuses: &[ClientDetails]) -> Vec<(Option<String>, Vec<ClientDetails>)> {
+    let mut tagged: BTreeMap<&str, Vec<ClientDetails>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for details in statuses {
+        if details.tags.is_empty() {
+            untagged.push(details.clone());
+        }
+        for tag in &details.tags {
+            tagged.entry(tag).or_default().push(details.clone());
+        }
+    }
+
+    let mut groups = tagged
+        .into_iter()
+        .map(|(tag, statuses)| (Some(tag.to_owned()), statuses))
+        .collect::<Vec<_>>();
+    if !untagged.is_empty() {
+        groups.push((None, untagged));
+    }
+    groups
+}
+
+fn print_statuses(statuses: &[String]) {
+    let mut iter = statuses.iter().peekable();
+    while let Some(status) = iter.next() {
+        println!("{}", status);
+        if iter.peek().is_some() {
+            println!();
+        }
+    }
+}
+
+// Returns lines describing statuses which have disappeared (prefixed with '-') and appeared (prefixed with '+')
+fn diff_statuses(previous_statuses: &[String], statuses: &[String]) -> Vec<String> {
+    let removed = previous_statuses
+        .iter()
+        .filter(|status| !statuses.contains(status))
+        .map(|status| format!("- {}", status));
+    let added = statuses
+        .iter()
+        .filter(|status| !previous_statuses.contains(status))
+        .map(|status| format!("+ {}", status));
+    removed.chain(added).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_strings(strings: &[&str]) -> Vec<String> {
+        strings.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn name_filter_is_applied() {
+        let data = ReadMessagesData {
+            name_filter: Some("db-*".to_owned()),
+            ..Default::default()
+        };
+        assert!(data.matches_name("db-primary"));
+        assert!(!data.matches_name("web-primary"));
+
+        let data = ReadMessagesData::default();
+        assert!(data.matches_name("db-primary"));
+        assert!(data.matches_name("web-primary"));
+    }
+
+    #[test]
+    fn statuses_are_formatted() {
+        let statuses = [
+            ClientDetails {
+                name: "Unreported".to_owned(),
+                status: None,
+                pending: false,
+                warning: false,
+                age_seconds: 0,
+                tags: Vec::new(),
+            },
+            ClientDetails {
+                name: "Healthy".to_owned(),
+                status: Some(Ok(())),
+                pending: false,
+                warning: false,
+                age_seconds: 0,
+                tags: Vec::new(),
+            },
+            ClientDetails {
+                name: "Broken".to_owned(),
+                status: Some(Err("Disk is full".to_owned())),
+                pending: false,
+                warning: false,
+                age_seconds: 0,
+                tags: Vec::new(),
+            },
+        ];
+        let mut data = ReadMessagesData {
+            color: ColorChoice::Never,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_statuses(&statuses, &data),
+            to_strings(&["unknown", "ok", "Disk is full"])
+        );
+
+        data.include_names = true;
+        assert_eq!(
+            format_statuses(&statuses, &data),
+            to_strings(&["Unreported: unknown", "Healthy: ok", "Broken: Disk is full"])
+        );
+
+        data.color = ColorChoice::Always;
+        assert_eq!(
+            format_statuses(&statuses, &data),
+            to_strings(&[
+                "\x1b[2mUnreported: unknown\x1b[0m",
+                "\x1b[32mHealthy: ok\x1b[0m",
+                "\x1b[31mBroken: Disk is full\x1b[0m"
+            ])
+        );
+    }
+
+    fn create_details(
+        name: &str,
+        status: Option<Result<(), String>>,
+        age: u64,
+        tags: &[&str],
+    ) -> ClientDetails {
+        ClientDetails {
+            name: name.to_owned(),
+            status,
+            pending: false,
+            warning: false,
+            age_seconds: age,
+            tags: to_strings(tags),
+        }
+    }
+
+    fn get_names(statuses: &[ClientDetails]) -> Vec<&str> {
+        statuses.iter().map(|x| x.name.as_str()).collect()
+    }
+
+    #[test]
+    fn status_summary_is_formatted() {
+        let statuses = vec![
+            create_details("a", Some(Ok(())), 0, &[]),
+            create_details("b", Some(Ok(())), 0, &[]),
+            create_details("c", Some(Err("fail".to_owned())), 0, &[]),
+        ];
+        assert_eq!(StatusSummary::new(&statuses).to_string(), "2 ok, 1 error");
+
+        let statuses = vec![
+            create_details("a", Some(Ok(())), 0, &[]),
+            create_details("b", Some(Err("slow".to_owned())), 0, &[]).with_warning(),
+        ];
+        assert_eq!(
+            StatusSummary::new(&statuses).to_string(),
+            "1 ok, 0 errors, 1 warning"
+        );
+
+        let statuses = vec![
+            create_details("a", None, 0, &[]),
+            create_details("b", Some(Err("fail".to_owned())), 0, &[]),
+            create_details("c", Some(Err("fail".to_owned())), 0, &[]),
+        ];
+        assert_eq!(
+            StatusSummary::new(&statuses).to_string(),
+            "0 ok, 2 errors, 1 unknown"
+        );
+
+        let mut pending = create_details("a", None, 0, &[]);
+        pending.pending = true;
+        let statuses = vec![pending, create_details("b", None, 0, &[])];
+        assert_eq!(
+            StatusSummary::new(&statuses).to_string(),
+            "0 ok, 0 errors, 1 pending, 1 unknown"
+        );
+
+        assert_eq!(StatusSummary::new(&[]).to_string(), "0 ok, 0 errors");
+    }
+
+    #[test]
+    fn timestamps_are_formatted() {
+        use chrono::TimeZone;
+        let now = Local.with_ymd_and_hms(2024, 5, 10, 12, 30, 0).unwrap();
+        let run = |age_seconds, format, expected: &str| {
+            assert_eq!(format_timestamp(age_seconds, format, now), expected);
+        };
+
+        run(0, TimestampFormat::Relative, "for 0s");
+        run(59, TimestampFormat::Relative, "for 59s");
+        run(150, TimestampFormat::Relative, "for 2m");
+        run(3 * 60 * 60 + 5, TimestampFormat::Relative, "for 3h");
+        run(2 * 24 * 60 * 60, TimestampFormat::Relative, "for 2d");
+
+        run(0, TimestampFormat::Absolute, "since 12:30");
+        run(
+            2 * 60 * 60 + 49 * 60,
+            TimestampFormat::Absolute,
+            "since 09:41",
+        );
+        run(
+            24 * 60 * 60,
+            TimestampFormat::Absolute,
+            "since 2024-05-09 12:30",
+        );
+    }
+
+    #[test]
+    fn statuses_are_sorted() {
+        let mut statuses = vec![
+            create_details("b", Some(Ok(())), 5, &[]),
+            create_details("d", Some(Err("fail".to_owned())), 10, &[]),
+            create_details("a", None, 5, &[]),
+            create_details("c", Some(Err("fail".to_owned())), 1, &[]),
+            create_details("e", Some(Err("slow".to_owned())), 7, &[]).with_warning(),
+        ];
+
+        sort_statuses(&mut statuses, SortKey::Name);
+        assert_eq!(get_names(&statuses), ["a", "b", "c", "d", "e"]);
+        sort_statuses(&mut statuses, SortKey::Age);
+        assert_eq!(get_names(&statuses), ["c", "a", "b", "e", "d"]);
+        sort_statuses(&mut statuses, SortKey::Severity);
+        assert_eq!(get_names(&statuses), ["c", "d", "e", "a", "b"]);
+    }
+
+    #[test]
+    fn statuses_are_grouped_by_tag() {
+        let statuses = vec![
+            create_details("a", None, 0, &["web"]),
+            create_details("b", None, 0, &[]),
+            create_details("c", None, 0, &["web", "db"]),
+        ];
+        let groups = group_statuses_by_tag(&statuses);
+        let groups = groups
+            .iter()
+            .map(|(tag, statuses)| (tag.as_deref(), get_names(statuses)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            [
+                (Some("db"), vec!["c"]),
+                (Some("web"), vec!["a", "c"]),
+                (None, vec!["b"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn status_diff_is_computed() {
+        fn run(previous_statuses: &[&str], statuses: &[&str], expected: &[&str]) {
+            let diff = diff_statuses(&to_strings(previous_statuses), &to_strings(statuses));
+            assert_eq!(diff, to_strings(expected));
+        }
+
+        run(&[], &[], &[]);
+        run(&[], &["a: fail"], &["+ a: fail"]);
+        run(&["a: fail"], &[], &["- a: fail"]);
+        run(&["a: fail"], &["a: fail"], &[]);
+        run(
+            &["a: fail"],
+            &["a: other fail"],
+            &["- a: fail", "+ a: other fail"],
+        );
+        run(
+            &["a: fail", "b: fail"],
+            &["b: fail", "c: fail"],
+            &["- a: fail", "+ c: fail"],
+        );
     }
 }
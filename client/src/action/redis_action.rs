@@ -0,0 +1,203 @@
+// Bridge between the server and Redis, so job queues and web applications can react to statuses without talking the
+// CheckMate protocol. Every status change is published to a channel as a JSON object in the same format as read
+// --follow --format json prints. Optionally, current statuses are also mirrored into keys named <PREFIX><NAME>, which
+// are periodically synchronized with the server, so keys of clients which are gone are deleted.
+
+use super::definition::Action;
+use super::output_format::{format_status_change, OutputFormat};
+use check_mate_common::constants::*;
+use check_mate_common::{glob_matches, ClientDetails, CommunicationError, ServerCommand};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::collections::HashSet;
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+#[derive(PartialEq, Debug)]
+pub struct RedisData {
+    pub url: String,
+    pub channel: String,
+    pub mirror_prefix: Option<String>,
+    pub name_filter: Option<String>,
+}
+
+impl RedisData {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            channel: DEFAULT_REDIS_CHANNEL.to_owned(),
+            mirror_prefix: None,
+            name_filter: None,
+        }
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        match self.name_filter {
+            Some(ref pattern) => glob_matches(pattern, name),
+            None => true,
+        }
+    }
+}
+
+// Escapes characters which have a special meaning in patterns of the SCAN command
+fn escape_scan_pattern(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Returns mirrored keys which don't belong to any of the current clients
+fn get_stale_keys(
+    prefix: &str,
+    existing_keys: Vec<String>,
+    current_names: &HashSet<&str>,
+) -> Vec<String> {
+    existing_keys
+        .into_iter()
+        .filter(|key| match key.strip_prefix(prefix) {
+            Some(name) => !current_names.contains(name),
+            None => false,
+        })
+        .collect()
+}
+
+async fn connect(url: &str) -> Result<ConnectionManager, String> {
+    let client =
+        redis::Client::open(url).map_err(|err| format!("Invalid Redis URL {url}: {err}"))?;
+    ConnectionManager::new(client)
+        .await
+        .map_err(|err| format!("Could not connect to Redis at {url}: {err}"))
+}
+
+async fn publish(
+    connection: &mut ConnectionManager,
+    data: &RedisData,
+    details: &ClientDetails,
+) -> redis::RedisResult<()> {
+    let payload = format_status_change(details, OutputFormat::Json);
+    connection
+        .publish::<_, _, ()>(&data.channel, &payload)
+        .await?;
+    if let Some(ref prefix) = data.mirror_prefix {
+        let key = format!("{prefix}{}", details.name);
+        connection.set::<_, _, ()>(key, &payload).await?;
+    }
+    Ok(())
+}
+
+// Writes all current statuses to their keys and deletes keys of clients which are gone
+async fn synchronize_mirror(
+    connection: &mut ConnectionManager,
+    prefix: &str,
+    clients: &[ClientDetails],
+) -> redis::RedisResult<()> {
+    for details in clients {
+        let key = format!("{prefix}{}", details.name);
+        let payload = format_status_change(details, OutputFormat::Json);
+        connection.set::<_, _, ()>(key, payload).await?;
+    }
+
+    let pattern = format!("{}*", escape_scan_pattern(prefix));
+    let mut existing_keys = Vec::new();
+    {
+        let mut keys = connection.scan_match::<_, String>(pattern).await?;
+        while let Some(key) = keys.next_item().await {
+            existing_keys.push(key);
+        }
+    }
+    let current_names = clients.iter().map(|x| x.name.as_str()).collect();
+    let stale_keys = get_stale_keys(prefix, existing_keys, &current_names);
+    if !stale_keys.is_empty() {
+        connection.del::<_, ()>(stale_keys).await?;
+    }
+    Ok(())
+}
+
+impl Action {
+    pub(crate) async fn bridge_to_redis(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &RedisData,
+    ) -> Result<(), CommunicationError> {
+        // The connection manager reconnects on its own, so only failing to connect at startup is fatal
+        let mut connection = connect(&data.url).await.map_err(std::io::Error::other)?;
+        ServerCommand::Subscribe.send_async(output_stream).await?;
+
+        // Mirrored keys are synchronized right away, so keys left by a previous run are cleaned up
+        let mut ticks = tokio::time::interval(REDIS_MIRROR_SYNC_INTERVAL);
+        loop {
+            // Receiving a command is not cancel safe, so the same future is polled until it completes
+            let receive = ServerCommand::receive_async(input_stream);
+            tokio::pin!(receive);
+            let command = loop {
+                tokio::select! {
+                    command = &mut receive => break command?,
+                    _ = ticks.tick(), if data.mirror_prefix.is_some() => {
+                        ServerCommand::GetClientDetails.send_async(output_stream).await?
+                    }
+                }
+            };
+
+            match command {
+                ServerCommand::StatusChanged(details) => {
+                    if !data.matches_name(&details.name) {
+                        continue;
+                    }
+                    if let Err(err) = publish(&mut connection, data, &details).await {
+                        eprintln!(
+                            "Failed to publish status of {} to Redis: {}",
+                            details.name, err
+                        );
+                    }
+                }
+                ServerCommand::ClientDetails(mut details) => {
+                    let Some(ref prefix) = data.mirror_prefix else {
+                        continue;
+                    };
+                    details.retain(|x| data.matches_name(&x.name));
+                    if let Err(err) = synchronize_mirror(&mut connection, prefix, &details).await {
+                        eprintln!("Failed to synchronize statuses in Redis: {}", err);
+                    }
+                }
+                _ => panic!("Unexpected command received by the Redis bridge"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_patterns_are_escaped() {
+        assert_eq!(escape_scan_pattern("checkmate:"), "checkmate:");
+        assert_eq!(escape_scan_pattern("a*b?[c]\\"), "a\\*b\\?\\[c\\]\\\\");
+    }
+
+    #[test]
+    fn stale_keys_are_found() {
+        let existing_keys = ["cm:db", "cm:backup", "cm:", "other:db"]
+            .into_iter()
+            .map(|x| x.to_owned())
+            .collect();
+        let current_names = HashSet::from(["db", "disk"]);
+        assert_eq!(
+            get_stale_keys("cm:", existing_keys, &current_names),
+            vec!["cm:backup".to_owned(), "cm:".to_owned()]
+        );
+    }
+
+    #[test]
+    fn names_are_filtered() {
+        let mut data = RedisData::new("redis://localhost".to_owned());
+        assert!(data.matches_name("backup"));
+        data.name_filter = Some("db-*".to_owned());
+        assert!(data.matches_name("db-1"));
+        assert!(!data.matches_name("backup"));
+    }
+}
@@ -0,0 +1,24 @@
+use check_mate_common::secrets::{encrypt, generate_key, load_key};
+
+#[derive(PartialEq, Debug)]
+pub enum SecretsRequest {
+    GenerateKey,
+    Encrypt { name: String, key_path: String },
+}
+
+// Returns the line to print. The value to encrypt is read from the standard input, so it doesn't end up in the history
+// of the shell or in the list of processes.
+pub fn execute_secrets_request(request: &SecretsRequest) -> Result<String, String> {
+    match request {
+        SecretsRequest::GenerateKey => generate_key(),
+        SecretsRequest::Encrypt { name, key_path } => {
+            let key = load_key(key_path)?;
+            let mut value = String::new();
+            std::io::stdin()
+                .read_line(&mut value)
+                .map_err(|err| format!("could not read the secret: {err}"))?;
+            let value = value.trim_end_matches(['\r', '\n']);
+            Ok(format!("{name}={}", encrypt(&key, name, value)?))
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use super::definition::Action;
+use check_mate_common::{CommunicationError, ServerCommand};
+use tokio::io::{AsyncBufRead, AsyncWrite};
+
+impl Action {
+    pub(crate) async fn get_server_statistics(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+    ) -> Result<(), CommunicationError> {
+        let command = ServerCommand::GetServerStatistics;
+        command.send_async(output_stream).await?;
+
+        match ServerCommand::receive_async(input_stream).await? {
+            ServerCommand::ServerStatistics(statistics) => {
+                println!("Uptime: {}s", statistics.uptime_seconds);
+                println!("Connected clients: {}", statistics.connected_clients);
+                println!("Commands processed: {}", statistics.commands_processed);
+                println!("Statuses stored: {}", statistics.statuses_stored);
+                println!("Notifications sent: {}", statistics.notifications_sent);
+            }
+            _ => panic!("Unexpected command received after GetServerStatistics"),
+        }
+        Ok(())
+    }
+}
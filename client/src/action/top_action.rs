@@ -0,0 +1,398 @@
+use super::definition::Action;
+use super::read_action::{format_age, sort_statuses, SortKey, StatusSummary};
+use check_mate_common::constants::*;
+use check_mate_common::{glob_matches, ClientDetails, CommunicationError, ServerCommand};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufRead, AsyncWrite};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+#[derive(PartialEq, Debug)]
+pub struct TopData {
+    pub interval: Duration,
+    pub name_filter: Option<String>,
+}
+
+impl Default for TopData {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_TOP_INTERVAL,
+            name_filter: None,
+        }
+    }
+}
+
+const KEY_HELP: &str = "q quit | up/down select | r refresh | R refresh all | a ack | s silence";
+
+// Everything displayed by the dashboard. Statuses are queried on every interval, while status changes are received
+// as they happen, so flapping clients are detected even if they change faster than the interval.
+#[derive(Default)]
+struct Dashboard {
+    clients: Vec<ClientDetails>,
+    status_changes: HashMap<String, VecDeque<Instant>>,
+    silenced: HashSet<String>,
+    table_state: TableState,
+}
+
+#[derive(PartialEq, Debug)]
+enum KeyAction {
+    Quit,
+    Send(ServerCommand),
+    Nothing,
+}
+
+impl Dashboard {
+    fn selected_name(&self) -> Option<&str> {
+        let index = self.table_state.selected()?;
+        self.clients.get(index).map(|x| x.name.as_str())
+    }
+
+    // Replaces all clients, keeping the selection on the same client if it still exists
+    fn set_clients(&mut self, mut clients: Vec<ClientDetails>, data: &TopData) {
+        clients.retain(|x| matches_name(data, &x.name));
+        sort_statuses(&mut clients, SortKey::Severity);
+
+        let selected_name = self.selected_name().map(str::to_owned);
+        let selected_index = selected_name
+            .and_then(|name| clients.iter().position(|x| x.name == name))
+            .or(if clients.is_empty() { None } else { Some(0) });
+        self.clients = clients;
+        self.table_state.select(selected_index);
+    }
+
+    fn on_status_changed(&mut self, details: ClientDetails, now: Instant, data: &TopData) {
+        if !matches_name(data, &details.name) {
+            return;
+        }
+        let changes = self.status_changes.entry(details.name.clone()).or_default();
+        changes.push_back(now);
+        while changes.front().is_some_and(|x| now - *x > FLAPPING_WINDOW) {
+            changes.pop_front();
+        }
+        if let Some(client) = self.clients.iter_mut().find(|x| x.name == details.name) {
+            *client = details;
+        }
+    }
+
+    // Returns how many times the status has changed within the flapping window
+    fn count_status_changes(&self, name: &str, now: Instant) -> usize {
+        self.status_changes.get(name).map_or(0, |changes| {
+            changes
+                .iter()
+                .filter(|x| now - **x <= FLAPPING_WINDOW)
+                .count()
+        })
+    }
+
+    fn handle_key(&mut self, event: Event) -> KeyAction {
+        let key = match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => return KeyAction::Nothing,
+        };
+        let selected_name = self.selected_name().map(str::to_owned);
+        match (key.code, selected_name) {
+            (KeyCode::Char('q') | KeyCode::Esc, _) => KeyAction::Quit,
+            (KeyCode::Char('c'), _) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyAction::Quit
+            }
+            (KeyCode::Down | KeyCode::Char('j'), _) => {
+                if self
+                    .table_state
+                    .selected()
+                    .is_some_and(|x| x + 1 < self.clients.len())
+                {
+                    self.table_state.select_next();
+                }
+                KeyAction::Nothing
+            }
+            (KeyCode::Up | KeyCode::Char('k'), _) => {
+                self.table_state.select_previous();
+                KeyAction::Nothing
+            }
+            (KeyCode::Char('R'), _) => KeyAction::Send(ServerCommand::RefreshAllClients),
+            (KeyCode::Char('r'), Some(name)) => {
+                KeyAction::Send(ServerCommand::RefreshClientByName(name))
+            }
+            (KeyCode::Char('a'), Some(name)) => {
+                KeyAction::Send(ServerCommand::ClearClientByName(name))
+            }
+            (KeyCode::Char('s'), Some(name)) => {
+                // Silencing is local to the dashboard, other clients still see the error
+                if !self.silenced.remove(&name) {
+                    self.silenced.insert(name);
+                }
+                KeyAction::Nothing
+            }
+            _ => KeyAction::Nothing,
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, now: Instant) {
+        let [header_area, table_area, footer_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        // Silenced clients are shown, but they don't count as errors
+        let audible_clients = self
+            .clients
+            .iter()
+            .filter(|x| !self.silenced.contains(&x.name))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut header = format!("CheckMate: {}", StatusSummary::new(&audible_clients));
+        if audible_clients.len() < self.clients.len() {
+            header += &format!(", {} silenced", self.clients.len() - audible_clients.len());
+        }
+        frame.render_widget(Line::from(header).style(Modifier::BOLD), header_area);
+
+        let rows = self.clients.iter().map(|details| {
+            let (status, style) = match details.status {
+                None => (
+                    details.severity().as_str(),
+                    Style::new().fg(Color::DarkGray),
+                ),
+                Some(Ok(_)) => ("ok", Style::new().fg(Color::Green)),
+                Some(Err(ref message)) => (message.as_str(), Style::new().fg(Color::Red)),
+            };
+            let (status, style) = match self.silenced.contains(&details.name) {
+                true => (
+                    format!("{status} (silenced)"),
+                    Style::new().fg(Color::DarkGray),
+                ),
+                false => (status.to_owned(), style),
+            };
+            let age = match details.status {
+                Some(_) => format_age(details.age_seconds),
+                None => "-".to_owned(),
+            };
+            let changes = self.count_status_changes(&details.name, now);
+            let changes = match changes >= FLAPPING_THRESHOLD {
+                true => format!("{changes} flapping"),
+                false => changes.to_string(),
+            };
+            Row::new([details.name.clone(), status, age, changes]).style(style)
+        });
+        let widths = [
+            Constraint::Percentage(25),
+            Constraint::Fill(1),
+            Constraint::Length(6),
+            Constraint::Length(12),
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(["Name", "Status", "Age", "Changes"]).style(Modifier::BOLD))
+            .row_highlight_style(Modifier::REVERSED);
+        frame.render_stateful_widget(table, table_area, &mut self.table_state);
+
+        frame.render_widget(Line::from(KEY_HELP).style(Modifier::DIM), footer_area);
+    }
+}
+
+fn matches_name(data: &TopData, name: &str) -> bool {
+    match data.name_filter {
+        Some(ref pattern) => glob_matches(pattern, name),
+        None => true,
+    }
+}
+
+// Reading terminal events blocks, so it's done on a separate thread. The thread ends once the receiver is dropped.
+fn read_terminal_events() -> UnboundedReceiver<std::io::Result<Event>> {
+    let (sender, receiver) = unbounded_channel();
+    std::thread::spawn(move || {
+        while !sender.is_closed() {
+            let event = match event::poll(Duration::from_millis(100)) {
+                Ok(true) => event::read(),
+                Ok(false) => continue,
+                Err(err) => Err(err),
+            };
+            let is_err = event.is_err();
+            if sender.send(event).is_err() || is_err {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+impl Action {
+    pub(crate) async fn top(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &TopData,
+    ) -> Result<(), CommunicationError> {
+        let mut terminal = ratatui::try_init()?;
+        let result = Self::run_dashboard(&mut terminal, input_stream, output_stream, data).await;
+        ratatui::restore();
+        result
+    }
+
+    async fn run_dashboard(
+        terminal: &mut DefaultTerminal,
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &TopData,
+    ) -> Result<(), CommunicationError> {
+        let mut dashboard = Dashboard::default();
+        let mut terminal_events = read_terminal_events();
+        let mut ticks = tokio::time::interval(data.interval);
+        ServerCommand::Subscribe.send_async(output_stream).await?;
+
+        loop {
+            // Receiving a command is not cancel safe, so the same future is polled until it completes
+            let receive = ServerCommand::receive_async(input_stream);
+            tokio::pin!(receive);
+            let command = loop {
+                terminal.draw(|frame| dashboard.draw(frame, Instant::now()))?;
+                tokio::select! {
+                    command = &mut receive => break command?,
+                    _ = ticks.tick() => ServerCommand::GetClientDetails.send_async(output_stream).await?,
+                    event = terminal_events.recv() => {
+                        let event = match event {
+                            Some(event) => event?,
+                            None => return Ok(()),
+                        };
+                        match dashboard.handle_key(event) {
+                            KeyAction::Quit => return Ok(()),
+                            KeyAction::Send(command) => command.send_async(output_stream).await?,
+                            KeyAction::Nothing => (),
+                        }
+                    }
+                }
+            };
+
+            match command {
+                ServerCommand::ClientDetails(details) => dashboard.set_clients(details, data),
+                ServerCommand::StatusChanged(details) => {
+                    dashboard.on_status_changed(details, Instant::now(), data)
+                }
+                _ => panic!("Unexpected command received by the dashboard"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::KeyEvent;
+    use ratatui::Terminal;
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn selection_follows_client_after_update() {
+        let data = TopData::default();
+        let mut dashboard = Dashboard::default();
+        dashboard.set_clients(
+            vec![
+                ClientDetails::new("a", Some(Ok(()))),
+                ClientDetails::new("b", Some(Ok(()))),
+            ],
+            &data,
+        );
+        assert_eq!(dashboard.selected_name(), Some("a"));
+        assert_eq!(dashboard.handle_key(key(KeyCode::Down)), KeyAction::Nothing);
+        assert_eq!(dashboard.handle_key(key(KeyCode::Down)), KeyAction::Nothing);
+        assert_eq!(dashboard.selected_name(), Some("b"));
+
+        // Errors are sorted first, so the selected client moves down
+        let failing = ClientDetails::new("a", Some(Err("Failed")));
+        dashboard.set_clients(vec![failing, ClientDetails::new("b", Some(Ok(())))], &data);
+        assert_eq!(dashboard.selected_name(), Some("b"));
+        assert_eq!(dashboard.table_state.selected(), Some(1));
+
+        dashboard.set_clients(vec![ClientDetails::new("a", Some(Ok(())))], &data);
+        assert_eq!(dashboard.selected_name(), Some("a"));
+        dashboard.set_clients(Vec::new(), &data);
+        assert_eq!(dashboard.selected_name(), None);
+    }
+
+    #[test]
+    fn keys_are_handled() {
+        let data = TopData::default();
+        let mut dashboard = Dashboard::default();
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('r'))),
+            KeyAction::Nothing
+        );
+
+        dashboard.set_clients(
+            vec![ClientDetails::new("backup", Some(Err("Failed")))],
+            &data,
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('r'))),
+            KeyAction::Send(ServerCommand::RefreshClientByName("backup".to_owned()))
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('R'))),
+            KeyAction::Send(ServerCommand::RefreshAllClients)
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('a'))),
+            KeyAction::Send(ServerCommand::ClearClientByName("backup".to_owned()))
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('s'))),
+            KeyAction::Nothing
+        );
+        assert!(dashboard.silenced.contains("backup"));
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('s'))),
+            KeyAction::Nothing
+        );
+        assert!(dashboard.silenced.is_empty());
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('q'))),
+            KeyAction::Quit
+        );
+    }
+
+    #[test]
+    fn flapping_clients_are_detected() {
+        let data = TopData::default();
+        let mut dashboard = Dashboard::default();
+        let start = Instant::now();
+        for minutes in 0..FLAPPING_THRESHOLD as u64 {
+            let now = start + Duration::from_secs(minutes * 60);
+            dashboard.on_status_changed(ClientDetails::new("flappy", Some(Ok(()))), now, &data);
+        }
+        let now = start + Duration::from_secs(FLAPPING_THRESHOLD as u64 * 60);
+        dashboard.on_status_changed(ClientDetails::new("stable", Some(Ok(()))), now, &data);
+        dashboard.set_clients(
+            vec![
+                ClientDetails::new("flappy", None),
+                ClientDetails::new("stable", None),
+            ],
+            &data,
+        );
+
+        let backend = TestBackend::new(60, 6);
+        let mut terminal = Terminal::new(backend).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame, now)).unwrap();
+        let lines = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(60)
+            .map(|line| line.iter().map(|x| x.symbol()).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(lines[0].trim_end(), "CheckMate: 0 ok, 0 errors, 2 unknown");
+        assert!(lines[3].contains("flappy") && lines[3].contains("4 flapping"));
+        assert!(lines[4].contains("stable") && lines[4].trim_end().ends_with('1'));
+
+        let later = start + FLAPPING_WINDOW + Duration::from_secs(90);
+        assert_eq!(dashboard.count_status_changes("flappy", later), 2);
+    }
+}
@@ -1,13 +1,23 @@
-use super::definition::Action;
+use super::checks::BuiltinCheck;
+use super::control_socket::{ControlRequest, ControlSocket};
+use super::definition::{Action, ActionState};
+#[cfg(feature = "script")]
+use super::output_script::OutputScript;
+use super::process_limits::ProcessLimits;
 use check_mate_common::constants::*;
-use check_mate_common::{CommunicationError, ServerCommand};
-use std::time::Duration;
-use tokio::io::{AsyncBufRead, AsyncWrite};
+use check_mate_common::{format_duration, CommunicationError, ServerCommand};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::process::Child;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "PascalCase")]
 pub enum WatchMode {
     /// Empty stdout means success.
     /// Non-empty stdout means error. The first non-empty line is an error message, the rest is ignored.
+    #[default]
     OneLineError,
 
     /// Empty stdout means success.
@@ -22,20 +32,20 @@ pub enum WatchMode {
     /// Exit code other than 0 means error. The first non-empty in stdout line is an error message, the rest is ignored.
     /// If there are no non-empty lines, error message is composed as for ExitCode.
     OneLineErrorExitCode,
-}
 
-impl std::str::FromStr for WatchMode {
-    type Err = ();
+    /// Exit code equal to 0 means error, e.g. when grep finds a match. The first non-empty line in stdout is an error
+    /// message, the rest is ignored. If there are no non-empty lines, error message is composed to contain the exit
+    /// code. Exit code other than 0 means success.
+    InvertedExitCode,
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.trim().to_lowercase().as_str() {
-            "onelineerror" => Ok(Self::OneLineError),
-            "multilineerror" => Ok(Self::MultiLineError),
-            "exitcode" => Ok(Self::ExitCode),
-            "onelineerrorexitcode" => Ok(Self::OneLineErrorExitCode),
-            _ => Err(()),
-        }
-    }
+    /// Stdout is parsed as JSON. Status is extracted from the path set with --json-status. Value of true, 0 or "ok"
+    /// means success, anything else means error. Error message is extracted from the path set with --json-message.
+    Json,
+
+    /// Exit code is interpreted as by Nagios plugins: 0 means success, 1 warning, 2 critical and anything else
+    /// unknown. Warning is reported as such, critical and unknown mean error. Message is the first non-empty line of
+    /// stdout without performance data. If there are no non-empty lines, message is composed as for ExitCode.
+    Nagios,
 }
 
 impl std::fmt::Display for WatchMode {
@@ -45,14 +55,203 @@ impl std::fmt::Display for WatchMode {
             WatchMode::MultiLineError => "MultiLineError",
             WatchMode::ExitCode => "ExitCode",
             WatchMode::OneLineErrorExitCode => "OneLineErrorExitCode",
+            WatchMode::InvertedExitCode => "InvertedExitCode",
+            WatchMode::Json => "Json",
+            WatchMode::Nagios => "Nagios",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "PascalCase")]
+pub enum ShutdownStatus {
+    /// Report an error saying the client is shutting down.
+    #[default]
+    Error,
+
+    /// Report success, so the client is not considered failed after it exits.
+    Ok,
+}
+
+impl std::fmt::Display for ShutdownStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            ShutdownStatus::Error => "Error",
+            ShutdownStatus::Ok => "Ok",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "PascalCase")]
+pub enum CapturedStream {
+    /// Only stdout of the command is inspected.
+    #[default]
+    Stdout,
+
+    /// Only stderr of the command is inspected.
+    Stderr,
+
+    /// Both stdout and stderr of the command are inspected. Stdout lines come first.
+    Both,
+}
+
+impl std::fmt::Display for CapturedStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            CapturedStream::Stdout => "Stdout",
+            CapturedStream::Stderr => "Stderr",
+            CapturedStream::Both => "Both",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "PascalCase")]
+pub enum ScheduleMode {
+    /// Interval is measured from the end of one invocation of the command to the start of the next one.
+    #[default]
+    Delay,
+
+    /// Interval is measured between starts of consecutive invocations, so long-running commands don't shift
+    /// the schedule.
+    Tick,
+}
+
+impl std::fmt::Display for ScheduleMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            ScheduleMode::Delay => "Delay",
+            ScheduleMode::Tick => "Tick",
+        };
+        write!(f, "{}", display_str)
+    }
+}
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, clap::ValueEnum)]
+#[value(rename_all = "PascalCase")]
+pub enum OverlapPolicy {
+    /// Runs requested while the command is still running are dropped. Missed ticks of the schedule are skipped.
+    Skip,
+
+    /// At most one run requested while the command is still running is performed right after it ends.
+    #[default]
+    Queue,
+
+    /// The running command is killed and started again.
+    Restart,
+}
+
+impl std::fmt::Display for OverlapPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let display_str = match self {
+            OverlapPolicy::Skip => "Skip",
+            OverlapPolicy::Queue => "Queue",
+            OverlapPolicy::Restart => "Restart",
         };
         write!(f, "{}", display_str)
     }
 }
 
-impl Default for WatchMode {
-    fn default() -> Self {
-        return WatchMode::OneLineError;
+// Paths to values within JSON output of the command, used in Json watch mode. Paths are composed of object keys
+// and array indices separated with dots, e.g. ".checks.0.ok". A single dot means the whole document.
+#[derive(PartialEq, Debug, Default, Clone)]
+pub struct JsonPaths {
+    pub status: String,
+    pub message: Option<String>,
+}
+
+impl JsonPaths {
+    pub fn parse_path(path: &str) -> Result<String, String> {
+        if path.starts_with('.') {
+            Ok(path.to_owned())
+        } else {
+            Err("path must start with a dot, e.g. .status".to_owned())
+        }
+    }
+
+    fn get<'a>(value: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+        // Convert the path to a JSON pointer, e.g. ".a.b" to "/a/b"
+        let pointer = path
+            .split('.')
+            .skip(1)
+            .filter(|key| !key.is_empty())
+            .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+            .collect::<String>();
+        value.pointer(&pointer)
+    }
+}
+
+// Compiled regex, which can be compared by its pattern, so it can be a part of the config
+#[derive(Debug, Clone)]
+pub struct OutputRegex(pub regex::Regex);
+
+impl PartialEq for OutputRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl OutputRegex {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        regex::Regex::new(pattern)
+            .map(Self)
+            .map_err(|err| err.to_string())
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+
+    // Returns the first capture group of the first match, or the whole match if the regex has no groups. Returns an
+    // empty string if there is no match.
+    fn extract(&self, text: &str) -> String {
+        match self.0.captures(text) {
+            Some(captures) => captures
+                .get(1)
+                .or_else(|| captures.get(0))
+                .map_or("", |x| x.as_str())
+                .trim()
+                .to_owned(),
+            None => String::new(),
+        }
+    }
+}
+
+// Status of the watched command or check. Errors can be reported as warnings, which are less severe, e.g. by Nagios
+// plugins.
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct WatchStatus {
+    pub(crate) result: Result<(), String>,
+    pub(crate) warning: bool, // the error is only a warning
+}
+
+impl WatchStatus {
+    pub(crate) fn ok() -> Self {
+        Ok(()).into()
+    }
+
+    pub(crate) fn error(message: String) -> Self {
+        Err(message).into()
+    }
+
+    pub(crate) fn warning(message: String) -> Self {
+        WatchStatus {
+            result: Err(message),
+            warning: true,
+        }
+    }
+}
+
+impl From<Result<(), String>> for WatchStatus {
+    fn from(result: Result<(), String>) -> Self {
+        WatchStatus {
+            result,
+            warning: false,
+        }
     }
 }
 
@@ -61,9 +260,44 @@ pub struct WatchCommandData {
     pub command: String,
     pub command_args: Vec<String>,
     pub mode: WatchMode,
+    pub json_paths: JsonPaths,
     pub interval: Duration,
+    pub watch_paths: Vec<PathBuf>,
+    pub no_timer: bool,
+    pub control_socket: Option<PathBuf>,
+    pub streaming: bool,
+    pub tail_path: Option<PathBuf>,
+    pub ok_regex: Option<OutputRegex>,
+    pub quiet_period: Option<Duration>,
     pub shell: bool,
+    pub shell_command: String,
     pub delay: Duration,
+    pub jitter: Duration,
+    pub schedule: ScheduleMode,
+    pub align: bool,
+    pub overlap: OverlapPolicy,
+    pub failures_before_error: u32,
+    pub successes_before_ok: u32,
+    pub only_changes: bool,
+    pub reconfirm_interval: Option<Duration>,
+    pub max_updates_per_minute: Option<u32>,
+    pub shutdown_status: ShutdownStatus,
+    pub stream: CapturedStream,
+    pub error_regex: Option<OutputRegex>,
+    pub timeout: Option<Duration>,
+    pub retries: u32,
+    pub retry_delay: Duration,
+    pub on_error: Option<String>,
+    pub on_recover: Option<String>,
+    pub log_output: Option<PathBuf>,
+    pub report_pending: bool,
+    pub grace: Option<Duration>,
+    pub limits: ProcessLimits,
+    pub ssh: Option<String>,
+    pub ssh_command: String,
+    pub check: Option<BuiltinCheck>,
+    #[cfg(feature = "script")]
+    pub script: Option<OutputScript>,
 }
 
 impl WatchCommandData {
@@ -72,16 +306,573 @@ impl WatchCommandData {
             command,
             command_args,
             mode: WatchMode::default(),
+            json_paths: JsonPaths::default(),
             interval: DEFAULT_WATCH_INTERVAL,
+            watch_paths: Vec::new(),
+            no_timer: false,
+            control_socket: None,
+            streaming: false,
+            tail_path: None,
+            ok_regex: None,
+            quiet_period: None,
             shell: DEFAULT_SHELL,
+            shell_command: DEFAULT_SHELL_COMMAND.to_owned(),
             delay: DEFAULT_WATCH_DELAY,
+            jitter: DEFAULT_WATCH_JITTER,
+            schedule: ScheduleMode::default(),
+            align: false,
+            overlap: OverlapPolicy::default(),
+            failures_before_error: DEFAULT_FAILURES_BEFORE_ERROR,
+            successes_before_ok: DEFAULT_SUCCESSES_BEFORE_OK,
+            only_changes: false,
+            reconfirm_interval: None,
+            max_updates_per_minute: None,
+            shutdown_status: ShutdownStatus::default(),
+            stream: CapturedStream::default(),
+            error_regex: None,
+            timeout: None,
+            retries: 0,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            on_error: None,
+            on_recover: None,
+            log_output: None,
+            report_pending: false,
+            grace: None,
+            limits: ProcessLimits::default(),
+            ssh: None,
+            ssh_command: DEFAULT_SSH_COMMAND.to_owned(),
+            check: None,
+            #[cfg(feature = "script")]
+            script: None,
         }
     }
 }
 
+// Watches files and directories for changes, which trigger runs of the watched command. Directories are watched
+// recursively. It lives in ActionState, so changes are noticed also while the client is disconnected.
+pub struct FileWatcher {
+    _watcher: notify::RecommendedWatcher,
+    changes: tokio::sync::mpsc::UnboundedReceiver<()>,
+}
+
+impl FileWatcher {
+    pub fn new(paths: &[PathBuf]) -> Result<Self, String> {
+        use notify::Watcher;
+
+        let (sender, changes) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                // Reading a file is not a change
+                if let Ok(event) = event {
+                    if !matches!(event.kind, notify::EventKind::Access(_)) {
+                        let _ = sender.send(());
+                    }
+                }
+            })
+            .map_err(|err| format!("could not create file watcher: {err}"))?;
+
+        for path in paths {
+            watcher
+                .watch(path, notify::RecursiveMode::Recursive)
+                .map_err(|err| format!("could not watch {}: {err}", path.display()))?;
+        }
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    // Waits until something changes. Saving a file usually generates a burst of events, so events which come
+    // shortly after the first one are merged with it.
+    async fn wait_for_change(&mut self) {
+        if self.changes.recv().await.is_none() {
+            std::future::pending::<()>().await;
+        }
+        tokio::time::sleep(FILE_CHANGE_DEBOUNCE).await;
+        while self.changes.try_recv().is_ok() {}
+    }
+}
+
+async fn wait_for_file_change(file_watcher: &mut Option<FileWatcher>) {
+    match file_watcher {
+        Some(file_watcher) => file_watcher.wait_for_change().await,
+        None => std::future::pending().await,
+    }
+}
+
+// Runs triggered by the interval or by changes of watched files are skipped while paused through the control socket,
+// but runs requested explicitly are not
+fn is_paused(control: &Option<ControlSocket>) -> bool {
+    control.as_ref().is_some_and(|x| x.is_paused())
+}
+
+// Listens for SIGUSR1, which local scripts can send to request an immediate run of the command, just like a refresh
+// signal from the server. It lives in ActionState, so the handler stays installed while reconnecting. The handler
+// has to be installed at startup, because by default the signal terminates the process. Not available on Windows.
+#[derive(Default)]
+pub struct RefreshSignal {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl RefreshSignal {
+    pub fn install() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Self {
+                signal: signal(SignalKind::user_defined1()).ok(),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            Self {}
+        }
+    }
+
+    // Waits for the signal. Never completes if the handler is not installed.
+    async fn wait(&mut self) {
+        #[cfg(unix)]
+        if let Some(ref mut signal) = self.signal {
+            if signal.recv().await.is_some() {
+                return;
+            }
+        }
+        std::future::pending().await
+    }
+}
+
+// Command started once and kept running in streaming mode. Lines it prints are forwarded through a channel, which is
+// closed once the command closes its output. When a file is tailed, there is no subprocess and lines appended to the
+// file are forwarded instead.
+struct StreamingCommand {
+    subprocess: Option<Child>,
+    lines: tokio::sync::mpsc::UnboundedReceiver<String>,
+}
+
+impl StreamingCommand {
+    fn start(data: &WatchCommandData) -> Result<Self, String> {
+        if let Some(ref path) = data.tail_path {
+            let (sender, lines) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(Self::follow_file(path.clone(), sender));
+            return Ok(Self {
+                subprocess: None,
+                lines,
+            });
+        }
+
+        let mut subprocess = Action::create_command(data)
+            .and_then(|mut x| x.spawn())
+            .map_err(|err| Action::describe_spawn_error(&data.command, err))?;
+
+        // The stream which is not inspected still has to be read, so the command doesn't block on a full pipe
+        let (sender, lines) = tokio::sync::mpsc::unbounded_channel();
+        let stdout = subprocess.stdout.take();
+        let stderr = subprocess.stderr.take();
+        let (stdout_sender, stderr_sender) = match data.stream {
+            CapturedStream::Stdout => (Some(sender), None),
+            CapturedStream::Stderr => (None, Some(sender)),
+            CapturedStream::Both => (Some(sender.clone()), Some(sender)),
+        };
+        if let Some(stdout) = stdout {
+            tokio::spawn(Self::forward_lines(stdout, stdout_sender));
+        }
+        if let Some(stderr) = stderr {
+            tokio::spawn(Self::forward_lines(stderr, stderr_sender));
+        }
+        Ok(Self {
+            subprocess: Some(subprocess),
+            lines,
+        })
+    }
+
+    async fn forward_lines(
+        pipe: impl tokio::io::AsyncRead + Unpin,
+        sender: Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    ) {
+        let mut reader = tokio::io::BufReader::new(pipe);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            }
+            if let Some(ref sender) = sender {
+                let line = sanitize_output(&line, MAX_CAPTURED_OUTPUT_BYTES, 1);
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Follows the file like `tail -F`. Only lines appended after the start are forwarded. The file is reopened when it
+    // is replaced, removed or truncated, e.g. by log rotation, and then it is read from the beginning. A missing file
+    // is waited for.
+    async fn follow_file(path: PathBuf, sender: tokio::sync::mpsc::UnboundedSender<String>) {
+        use tokio::io::AsyncSeekExt;
+
+        let mut from_start = false;
+        while !sender.is_closed() {
+            let file = match tokio::fs::File::open(&path).await {
+                Ok(x) => x,
+                Err(_) => {
+                    from_start = true;
+                    tokio::time::sleep(TAIL_POLL_INTERVAL).await;
+                    continue;
+                }
+            };
+            let Ok(metadata) = file.metadata().await else {
+                continue;
+            };
+            let identity = file_identity(&metadata);
+            let mut position = if from_start { 0 } else { metadata.len() };
+            from_start = true;
+            let mut reader = tokio::io::BufReader::new(file);
+            if reader
+                .seek(std::io::SeekFrom::Start(position))
+                .await
+                .is_err()
+            {
+                continue;
+            }
+
+            // Partial lines are kept until the rest of them is written
+            let mut line = Vec::new();
+            loop {
+                match reader.read_until(b'\n', &mut line).await {
+                    Ok(0) => (),
+                    Ok(read_bytes) => {
+                        position += read_bytes as u64;
+                        if line.ends_with(b"\n") {
+                            let text = sanitize_output(&line, MAX_CAPTURED_OUTPUT_BYTES, 1);
+                            if sender.send(text).is_err() {
+                                return;
+                            }
+                            line.clear();
+                        }
+                        continue;
+                    }
+                    Err(_) => break,
+                }
+
+                tokio::time::sleep(TAIL_POLL_INTERVAL).await;
+                match tokio::fs::metadata(&path).await {
+                    Ok(x) if file_identity(&x) == identity && x.len() >= position => (),
+                    _ => break,
+                }
+            }
+        }
+    }
+}
+
+// Identifies the file behind a path, so its replacement can be detected. Where it's not available, only truncation
+// of the file is detected.
+fn file_identity(metadata: &std::fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(metadata.ino())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+// State of streaming mode. It lives in ActionState, so the command keeps running while the client reconnects.
+#[derive(Default)]
+pub struct StreamingState {
+    command: Option<StreamingCommand>,
+    restart_at: Option<Instant>,
+    quiet_since: Option<Instant>,
+}
+
+// Interprets a line printed by the command in streaming mode. Lines matching the ok regex mean success. Other lines
+// are errors, unless the error regex is set and doesn't match them. Returns None for lines, which don't affect the
+// status.
+fn interpret_streaming_line(
+    line: &str,
+    error_regex: Option<&OutputRegex>,
+    ok_regex: Option<&OutputRegex>,
+) -> Option<Result<(), String>> {
+    if ok_regex.is_some_and(|x| x.is_match(line)) {
+        return Some(Ok(()));
+    }
+    let message = match error_regex {
+        Some(error_regex) => error_regex.extract(line),
+        None => line.trim().to_owned(),
+    };
+    if message.is_empty() {
+        None
+    } else {
+        Some(Err(message))
+    }
+}
+
+const HOOK_STATUS_ENV: &str = "CHECK_MATE_STATUS";
+const HOOK_MESSAGE_ENV: &str = "CHECK_MATE_MESSAGE";
+
+// Returns the hook, which should be run when the reported status changes from the previous one to the new one.
+// The first reported error is also a change, but the first success is not.
+fn get_status_hook<'a>(
+    data: &'a WatchCommandData,
+    previous_status: Option<&WatchStatus>,
+    status: &WatchStatus,
+) -> Option<&'a str> {
+    match (previous_status.map(|x| &x.result), &status.result) {
+        (Some(Err(_)), Ok(_)) => data.on_recover.as_deref(),
+        (None | Some(Ok(_)), Err(_)) => data.on_error.as_deref(),
+        _ => None,
+    }
+}
+
+// Runs the hook through the shell without waiting for it, so it cannot delay watching. The status is passed to it
+// in environment variables.
+fn run_status_hook(data: &WatchCommandData, hook: &str, status: &WatchStatus) {
+    let mut shell_command = data.shell_command.split_whitespace();
+    let shell = shell_command.next().unwrap_or(DEFAULT_SHELL_COMMAND);
+    let mut subprocess = tokio::process::Command::new(shell);
+    subprocess
+        .args(shell_command)
+        .arg(hook)
+        .stdin(std::process::Stdio::null());
+    match status.result {
+        Ok(_) => subprocess
+            .env(HOOK_STATUS_ENV, "ok")
+            .env(HOOK_MESSAGE_ENV, ""),
+        Err(ref message) if status.warning => subprocess
+            .env(HOOK_STATUS_ENV, "warning")
+            .env(HOOK_MESSAGE_ENV, message),
+        Err(ref message) => subprocess
+            .env(HOOK_STATUS_ENV, "error")
+            .env(HOOK_MESSAGE_ENV, message),
+    };
+    match subprocess.spawn() {
+        Ok(mut subprocess) => {
+            tokio::spawn(async move { subprocess.wait().await });
+        }
+        Err(err) => eprintln!("Failed to run hook \"{hook}\": {err}"),
+    }
+}
+
+// Extends the duration by a random amount from 0 up to jitter, so watchers started at the same time don't run
+// their commands at the same time. Randomness comes from the per-process random keys of the std hasher, which
+// is good enough for this purpose.
+fn with_jitter(duration: Duration, jitter: Duration) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+    let jitter_nanos = jitter.as_nanos() as u64;
+    if jitter_nanos == 0 {
+        return duration;
+    }
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    duration + Duration::from_nanos(random % (jitter_nanos + 1))
+}
+
+// Returns how long to wait before the next invocation of the watched command, not including jitter. With alignment,
+// invocations happen at wall-clock multiples of the interval, e.g. at the top of every minute.
+fn get_time_to_next_run(
+    data: &WatchCommandData,
+    last_run_start: Option<Instant>,
+    now: Instant,
+    time_since_epoch: Duration,
+) -> Duration {
+    if data.interval.is_zero() {
+        return Duration::ZERO;
+    }
+    if data.align {
+        let interval = data.interval.as_millis();
+        let time_since_boundary = time_since_epoch.as_millis() % interval;
+        return Duration::from_millis((interval - time_since_boundary) as u64);
+    }
+    match (data.schedule, last_run_start) {
+        (ScheduleMode::Tick, Some(start)) => {
+            let elapsed = now - start;
+            if data.overlap == OverlapPolicy::Skip && elapsed >= data.interval {
+                let interval = data.interval.as_nanos();
+                Duration::from_nanos((interval - elapsed.as_nanos() % interval) as u64)
+            } else {
+                data.interval.saturating_sub(elapsed)
+            }
+        }
+        _ => data.interval,
+    }
+}
+
+// Returns the status which should be reported, given the status returned by the command. Errors are reported only
+// after the given number of consecutive failures and, once reported, stay until the given number of consecutive
+// successes. This filters out one-off blips. Until the first error is reported, the client is considered ok.
+fn apply_hysteresis(
+    data: &WatchCommandData,
+    state: &mut ActionState,
+    status: WatchStatus,
+) -> WatchStatus {
+    let reporting_error = matches!(state.last_watch_status, Some(ref x) if x.result.is_err());
+    if status.result.is_ok() {
+        state.consecutive_failures = 0;
+        state.consecutive_successes = state.consecutive_successes.saturating_add(1);
+        match state.last_watch_status {
+            Some(ref last)
+                if reporting_error && state.consecutive_successes < data.successes_before_ok =>
+            {
+                last.clone()
+            }
+            _ => status,
+        }
+    } else {
+        state.consecutive_successes = 0;
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if reporting_error || state.consecutive_failures >= data.failures_before_error {
+            status
+        } else {
+            WatchStatus::ok()
+        }
+    }
+}
+
+// Decides whether a status should be sent to the server. In only-changes mode, a status equal to the previous one is
+// skipped, unless the reconfirm interval has elapsed since the last status was sent.
+fn should_send_status(
+    data: &WatchCommandData,
+    status_changed: bool,
+    last_sent: Option<Instant>,
+    now: Instant,
+) -> bool {
+    if !data.only_changes || status_changed {
+        return true;
+    }
+    match (data.reconfirm_interval, last_sent) {
+        (Some(interval), Some(last_sent)) => now - last_sent >= interval,
+        (_, None) => true,
+        (None, Some(_)) => false,
+    }
+}
+
+// Returns whether errors should be ignored, because the client has only just started. Services the command depends
+// on may still be coming up then, e.g. after a reboot.
+fn in_grace_period(data: &WatchCommandData, state: &ActionState, now: Instant) -> bool {
+    match (data.grace, state.client_start) {
+        (Some(grace), Some(client_start)) => now.saturating_duration_since(client_start) < grace,
+        _ => false,
+    }
+}
+
+// Returns how long sending a status has to be delayed to keep the number of updates within the limit. Times of
+// updates which fell out of the window are forgotten.
+fn get_rate_limit_delay(
+    max_updates: Option<u32>,
+    send_times: &mut VecDeque<Instant>,
+    now: Instant,
+) -> Option<Duration> {
+    let max_updates = max_updates? as usize;
+    while send_times
+        .front()
+        .is_some_and(|x| now.duration_since(*x) >= RATE_LIMIT_WINDOW)
+    {
+        send_times.pop_front();
+    }
+    if send_times.len() < max_updates {
+        return None;
+    }
+    let oldest = send_times[send_times.len() - max_updates];
+    Some(RATE_LIMIT_WINDOW - now.duration_since(oldest))
+}
+
+// Converts raw output of the command to text suitable for a status. Invalid UTF-8 sequences are replaced, ANSI escape
+// sequences (e.g. colors) are removed and the text is limited in size. If anything was cut, a marker is appended.
+fn sanitize_output(bytes: &[u8], max_bytes: usize, max_lines: usize) -> String {
+    let mut truncated = bytes.len() > max_bytes;
+    let bytes = &bytes[..bytes.len().min(max_bytes)];
+    let text = strip_ansi_escape_sequences(&String::from_utf8_lossy(bytes));
+
+    let mut lines = text.split_inclusive('\n');
+    let mut text = lines.by_ref().take(max_lines).collect::<String>();
+    truncated |= lines.next().is_some();
+
+    if truncated {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(TRUNCATION_MARKER);
+    }
+    text
+}
+
+const TRUNCATION_MARKER: &str = "[output truncated]";
+
+fn strip_ansi_escape_sequences(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            // Control sequence, e.g. colors. Ends with a byte from the @ to ~ range.
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // Operating system command, e.g. window title. Ends with BEL or ESC \.
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // Other sequences consist of a single character after ESC
+            _ => (),
+        }
+    }
+    result
+}
+
+fn format_log_record(
+    time: chrono::DateTime<chrono::Local>,
+    summary: &str,
+    stdout: &[u8],
+    stderr: &[u8],
+) -> String {
+    let mut record = format!("[{}] {summary}\n", time.format("%Y-%m-%d %H:%M:%S"));
+    for (name, output) in [("stdout", stdout), ("stderr", stderr)] {
+        if output.is_empty() {
+            continue;
+        }
+        record.push_str(&format!("--- {name} ---\n"));
+        record.push_str(&String::from_utf8_lossy(output));
+        if !record.ends_with('\n') {
+            record.push('\n');
+        }
+    }
+    record
+}
+
+async fn append_to_file(path: &Path, text: &str) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(text.as_bytes()).await
+}
+
 #[derive(Clone)]
 struct ExecuteCommandOutput {
     executed: bool,
+    timed_out: bool,
     status: Option<i32>,
     text: String,
 }
@@ -91,112 +882,787 @@ impl Action {
         input_stream: &mut (impl AsyncBufRead + Unpin),
         output_stream: &mut (impl AsyncWrite + Unpin),
         data: &WatchCommandData,
+        state: &mut ActionState,
     ) -> Result<(), CommunicationError> {
-        async fn do_watch(
+        // Watch until the client is asked to shut down. Then report the final status, so the server doesn't keep
+        // the last status of a client which is no longer running.
+        tokio::select! {
+            result = Self::watch_loop(input_stream, output_stream, data, state) => return result,
+            _ = Self::wait_for_shutdown_signal() => (),
+        }
+        state.shutdown_requested = true;
+        let server_command = match data.shutdown_status {
+            ShutdownStatus::Error => {
+                ServerCommand::SetStatusError("Client shutting down".to_owned())
+            }
+            ShutdownStatus::Ok => ServerCommand::SetStatusOk,
+        };
+        server_command.send_async(output_stream).await?;
+        output_stream.shutdown().await?;
+        Ok(())
+    }
+
+    async fn watch_loop(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        output_stream: &mut (impl AsyncWrite + Unpin),
+        data: &WatchCommandData,
+        state: &mut ActionState,
+    ) -> Result<(), CommunicationError> {
+        async fn send_status(
             output_stream: &mut (impl AsyncWrite + Unpin),
-            data: &WatchCommandData,
+            status: &WatchStatus,
         ) -> Result<(), CommunicationError> {
-            // Run command to get its output
-            let command = data.command.to_string();
-            let command_args = data.command_args.to_owned();
-            let command_output = Action::execute_command(&command, &command_args, data.shell).await;
-            let server_command = match Action::process_command_output(command_output, &data.mode) {
+            let server_command = match status.result {
                 Ok(_) => ServerCommand::SetStatusOk,
-                Err(x) => ServerCommand::SetStatusError(x),
+                Err(ref x) if status.warning => ServerCommand::SetStatusWarning(x.clone()),
+                Err(ref x) => ServerCommand::SetStatusError(x.clone()),
             };
+            server_command.send_async(output_stream).await
+        }
+
+        async fn report_status(
+            output_stream: &mut (impl AsyncWrite + Unpin),
+            data: &WatchCommandData,
+            state: &mut ActionState,
+            status: WatchStatus,
+        ) -> Result<(), CommunicationError> {
+            if status.result.is_err() && in_grace_period(data, state, Instant::now()) {
+                return Ok(());
+            }
+            let status = apply_hysteresis(data, state, status);
+            if let Some(hook) = get_status_hook(data, state.last_watch_status.as_ref(), &status) {
+                run_status_hook(data, hook, &status);
+            }
+            if let Some(ref control) = state.control {
+                control.set_last_status(&status);
+            }
 
-            // Send status to the server
-            server_command.send_async(output_stream).await?;
+            // Send status to the server. Remember it first, so it can be resent after reconnecting,
+            // even if sending fails.
+            let status_changed = state.last_watch_status.as_ref() != Some(&status);
+            let now = Instant::now();
+            let send = should_send_status(data, status_changed, state.last_watch_status_sent, now);
+            let status = state.last_watch_status.insert(status);
+            if send {
+                let delay = get_rate_limit_delay(
+                    data.max_updates_per_minute,
+                    &mut state.status_send_times,
+                    now,
+                );
+                if delay.is_some() {
+                    // Only the latest status is sent once the limit allows it
+                    state.rate_limited_status = true;
+                } else {
+                    send_status(output_stream, status).await?;
+                    state.last_watch_status_sent = Some(now);
+                    record_sent_status(data, state, now);
+                }
+            }
             Ok(())
         }
 
-        // Run first iteration
-        tokio::time::sleep(data.delay).await;
-        do_watch(output_stream, data).await?;
+        fn record_sent_status(data: &WatchCommandData, state: &mut ActionState, now: Instant) {
+            if data.max_updates_per_minute.is_some() {
+                state.status_send_times.push_back(now);
+            }
+            state.rate_limited_status = false;
+        }
+
+        // Returns how long to wait before sending a status held back by the rate limit, if there is one
+        fn get_rate_limited_status_wait(
+            data: &WatchCommandData,
+            state: &mut ActionState,
+        ) -> Option<Duration> {
+            if !state.rate_limited_status {
+                return None;
+            }
+            let delay = get_rate_limit_delay(
+                data.max_updates_per_minute,
+                &mut state.status_send_times,
+                Instant::now(),
+            );
+            Some(delay.unwrap_or_default())
+        }
+
+        async fn send_rate_limited_status(
+            output_stream: &mut (impl AsyncWrite + Unpin),
+            data: &WatchCommandData,
+            state: &mut ActionState,
+        ) -> Result<(), CommunicationError> {
+            if let Some(ref status) = state.last_watch_status {
+                send_status(output_stream, status).await?;
+            }
+            let now = Instant::now();
+            state.last_watch_status_sent = Some(now);
+            record_sent_status(data, state, now);
+            Ok(())
+        }
+
+        // Returns whether another run was requested while the command was running
+        async fn do_watch(
+            input_stream: &mut (impl AsyncBufRead + Unpin),
+            output_stream: &mut (impl AsyncWrite + Unpin),
+            data: &WatchCommandData,
+            state: &mut ActionState,
+        ) -> Result<bool, CommunicationError> {
+            let (status, run_pending) =
+                Action::run_watched_command_with_overlap_policy(input_stream, data, state).await?;
+            report_status(output_stream, data, state, status).await?;
+            Ok(run_pending)
+        }
+
+        // Replay statuses gathered while we were offline. Each one is removed only after it is sent, so nothing
+        // is lost if the connection breaks again.
+        while let Some((timestamp, status)) = state.offline_watch_statuses.front() {
+            let command = ServerCommand::ReplayedStatus(*timestamp, status.result.clone());
+            command.send_async(output_stream).await?;
+            state.offline_watch_statuses.pop_front();
+        }
+
+        // Let the server know we are working on the first status, so we are not mistaken for a client which doesn't
+        // report anything
+        if data.report_pending && state.last_watch_status.is_none() {
+            ServerCommand::SetStatusPending
+                .send_async(output_stream)
+                .await?;
+        }
+
+        // Run first iteration. If we have reconnected, the server should learn our status immediately,
+        // so we resend the last one instead of waiting for the command.
+        let mut run_pending = match state.last_watch_status {
+            Some(ref status) => {
+                send_status(output_stream, status).await?;
+                state.last_watch_status_sent = Some(Instant::now());
+                state.rate_limited_status = false;
+                false
+            }
+            None if data.streaming => false,
+            None => {
+                tokio::time::sleep(with_jitter(data.delay, data.jitter)).await;
+                do_watch(input_stream, output_stream, data, state).await?
+            }
+        };
+
+        // In streaming mode the command keeps running and reports statuses by itself. It cannot be rerun or
+        // paused, so refresh signals only resend the last status.
+        if data.streaming {
+            loop {
+                let rate_limit_wait = get_rate_limited_status_wait(data, state);
+                let status = tokio::select! {
+                    status = Self::next_streaming_status(data, &mut state.streaming) => Some(status),
+                    _ = tokio::time::sleep(rate_limit_wait.unwrap_or_default()), if rate_limit_wait.is_some() => {
+                        send_rate_limited_status(output_stream, data, state).await?;
+                        continue;
+                    }
+                    _ = state.refresh_signal.wait() => None,
+                    request = state.control_requests.wait() => match request {
+                        ControlRequest::Run => None,
+                        ControlRequest::Reload => {
+                            state.reload_requested = true;
+                            return Ok(());
+                        }
+                    },
+                    server_command = ServerCommand::receive_async(input_stream) => {
+                        match server_command? {
+                            ServerCommand::Refresh => None,
+                            _ => panic!("Unexpected command received during watch"),
+                        }
+                    }
+                };
+                match status {
+                    Some(status) => report_status(output_stream, data, state, status.into()).await?,
+                    None => {
+                        if let Some(ref status) = state.last_watch_status {
+                            send_status(output_stream, status).await?;
+                            state.last_watch_status_sent = Some(Instant::now());
+                        }
+                    }
+                }
+            }
+        }
+
+        loop {
+            // Reloading is requested through the control socket. The client is restarted with the new
+            // configuration, so the watch ends here.
+            if state.reload_requested {
+                return Ok(());
+            }
+
+            // Wait for either watch interval, change of watched files or refresh signal from server, unless a run
+            // is already pending
+            if !run_pending {
+                let wait = Self::get_wait_before_next_run(data, state);
+                let rate_limit_wait = get_rate_limited_status_wait(data, state);
+                let scheduled = tokio::select! {
+                    _ = tokio::time::sleep(wait), if !data.no_timer => true,
+                    _ = tokio::time::sleep(rate_limit_wait.unwrap_or_default()), if rate_limit_wait.is_some() => {
+                        send_rate_limited_status(output_stream, data, state).await?;
+                        continue;
+                    }
+                    _ = wait_for_file_change(&mut state.file_watcher) => true,
+                    _ = state.refresh_signal.wait() => false,
+                    request = state.control_requests.wait() => match request {
+                        ControlRequest::Run => false,
+                        ControlRequest::Reload => {
+                            state.reload_requested = true;
+                            continue;
+                        }
+                    },
+                    server_command = ServerCommand::receive_async(input_stream) => {
+                        match server_command? {
+                            ServerCommand::Refresh => false,
+                            _ => panic!("Unexpected command received during watch"),
+                        }
+                    }
+                };
+                if scheduled && is_paused(&state.control) {
+                    // Skipped runs count as started, so the interval is kept
+                    state.last_watch_start = Some(Instant::now());
+                    continue;
+                }
+            }
+
+            // Execute command
+            run_pending = do_watch(input_stream, output_stream, data, state).await?;
+        }
+    }
+
+    // Runs the command, while handling refresh signals from the server, changes of watched files and ticks of the
+    // schedule which happen in the meantime according to the overlap policy. Returns the status and whether another
+    // run should follow immediately.
+    async fn run_watched_command_with_overlap_policy(
+        input_stream: &mut (impl AsyncBufRead + Unpin),
+        data: &WatchCommandData,
+        state: &mut ActionState,
+    ) -> Result<(WatchStatus, bool), CommunicationError> {
+        // With the default schedule the interval starts after the command ends, so ticks cannot overlap with it
+        let ticks_during_run =
+            !data.no_timer && (data.align || data.schedule == ScheduleMode::Tick);
+        let mut tick_enabled = ticks_during_run;
+        let mut run_pending = false;
 
+        state.last_watch_start = Some(Instant::now());
+        let tick = tokio::time::sleep(Self::get_wait_before_next_run(data, state));
+        tokio::pin!(tick);
+        let mut run = Box::pin(Self::run_watched_command(data));
         loop {
-            // Wait for either watch interval or refresh signal from server
-            tokio::select! {
-                _ = tokio::time::sleep(data.interval) => (),
+            let tick_elapsed = tokio::select! {
+                status = &mut run => return Ok((status, run_pending)),
+                _ = &mut tick, if tick_enabled => true,
+                _ = wait_for_file_change(&mut state.file_watcher) => false,
+                _ = state.refresh_signal.wait() => false,
+                request = state.control_requests.wait() => match request {
+                    ControlRequest::Run => false,
+                    ControlRequest::Reload => {
+                        // The client is restarted once the command finishes
+                        state.reload_requested = true;
+                        continue;
+                    }
+                },
                 server_command = ServerCommand::receive_async(input_stream) => {
                     match server_command? {
-                        ServerCommand::Refresh => (),
+                        ServerCommand::Refresh => false,
                         _ => panic!("Unexpected command received during watch"),
                     }
                 }
+            };
+
+            match data.overlap {
+                OverlapPolicy::Skip => (),
+                OverlapPolicy::Queue => run_pending = true,
+                OverlapPolicy::Restart => {
+                    // Dropping the previous run kills the command
+                    state.last_watch_start = Some(Instant::now());
+                    run = Box::pin(Self::run_watched_command(data));
+                    let wait = Self::get_wait_before_next_run(data, state);
+                    tick.as_mut().reset(tokio::time::Instant::now() + wait);
+                    tick_enabled = ticks_during_run;
+                    continue;
+                }
+            }
+            if tick_elapsed {
+                tick_enabled = false;
+            }
+        }
+    }
+
+    fn get_wait_before_next_run(data: &WatchCommandData, state: &ActionState) -> Duration {
+        let time_since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let wait = get_time_to_next_run(
+            data,
+            state.last_watch_start,
+            Instant::now(),
+            time_since_epoch,
+        );
+        with_jitter(wait, data.jitter)
+    }
+
+    pub(crate) async fn wait_for_shutdown_signal() {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut terminate =
+                signal(SignalKind::terminate()).expect("SIGTERM handler should be installed");
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => (),
+                _ = terminate.recv() => (),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+
+    // Keeps executing the command while the server is unreachable. Status transitions are buffered along with
+    // their timestamps, so they can be replayed to the server after reconnecting. This function returns only when
+    // reloading is requested, otherwise it is meant to be cancelled once the connection is established.
+    pub(crate) async fn watch_offline(data: &WatchCommandData, state: &mut ActionState) {
+        if data.streaming {
+            loop {
+                tokio::select! {
+                    status = Self::next_streaming_status(data, &mut state.streaming) => {
+                        Self::store_offline_status(data, state, status.into());
+                    }
+                    request = state.control_requests.wait() => {
+                        if request == ControlRequest::Reload {
+                            state.reload_requested = true;
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+
+        match state.last_watch_status {
+            Some(_) => tokio::time::sleep(Self::get_wait_before_next_run(data, state)).await,
+            None => tokio::time::sleep(with_jitter(data.delay, data.jitter)).await,
+        }
+
+        let mut scheduled = true;
+        loop {
+            state.last_watch_start = Some(Instant::now());
+            if !scheduled || !is_paused(&state.control) {
+                let status = Self::run_watched_command(data).await;
+                Self::store_offline_status(data, state, status);
+            }
+
+            let wait = Self::get_wait_before_next_run(data, state);
+            scheduled = tokio::select! {
+                _ = tokio::time::sleep(wait), if !data.no_timer => true,
+                _ = wait_for_file_change(&mut state.file_watcher) => true,
+                _ = state.refresh_signal.wait() => false,
+                request = state.control_requests.wait() => match request {
+                    ControlRequest::Run => false,
+                    ControlRequest::Reload => {
+                        state.reload_requested = true;
+                        return;
+                    }
+                },
+            };
+        }
+    }
+
+    fn store_offline_status(data: &WatchCommandData, state: &mut ActionState, status: WatchStatus) {
+        if status.result.is_err() && in_grace_period(data, state, Instant::now()) {
+            return;
+        }
+        let status = apply_hysteresis(data, state, status);
+        if let Some(hook) = get_status_hook(data, state.last_watch_status.as_ref(), &status) {
+            run_status_hook(data, hook, &status);
+        }
+        if let Some(ref control) = state.control {
+            control.set_last_status(&status);
+        }
+        if state.last_watch_status.as_ref() != Some(&status) {
+            if state.offline_watch_statuses.len() == OFFLINE_STATUS_BUFFER_CAPACITY {
+                state.offline_watch_statuses.pop_front();
+            }
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_millis() as u64);
+            state
+                .offline_watch_statuses
+                .push_back((timestamp, status.clone()));
+            state.last_watch_status = Some(status);
+        }
+    }
+
+    // Waits for the next status in streaming mode, starting the command if it isn't running. When the command exits
+    // or cannot be started, an error is returned and the command is restarted after the interval. Errors are cleared
+    // by lines matching the ok regex or after the quiet period without errors. This function is cancel safe, so
+    // the command keeps running across reconnections.
+    async fn next_streaming_status(
+        data: &WatchCommandData,
+        streaming: &mut StreamingState,
+    ) -> Result<(), String> {
+        loop {
+            if streaming.command.is_none() {
+                if let Some(restart_at) = streaming.restart_at {
+                    tokio::time::sleep_until(restart_at.into()).await;
+                }
+                let now = Instant::now();
+                match StreamingCommand::start(data) {
+                    Ok(command) => {
+                        streaming.command = Some(command);
+                        streaming.restart_at = None;
+                        streaming.quiet_since.get_or_insert(now);
+                    }
+                    Err(message) => {
+                        streaming.restart_at = Some(now + data.interval);
+                        streaming.quiet_since = Some(now);
+                        return Err(message);
+                    }
+                }
+            }
+
+            let quiet_end = match (data.quiet_period, streaming.quiet_since) {
+                (Some(quiet_period), Some(quiet_since)) => Some(quiet_since + quiet_period),
+                _ => None,
+            };
+            let command = streaming
+                .command
+                .as_mut()
+                .expect("Command should be running");
+            let line = tokio::select! {
+                line = command.lines.recv() => line,
+                _ = tokio::time::sleep_until(quiet_end.unwrap_or_else(Instant::now).into()), if quiet_end.is_some() => {
+                    streaming.quiet_since = None;
+                    return Ok(());
+                }
+            };
+
+            let now = Instant::now();
+            let Some(line) = line else {
+                let exit_status = match command.subprocess {
+                    Some(ref mut subprocess) => subprocess.wait().await.ok(),
+                    None => None,
+                };
+                streaming.command = None;
+                streaming.restart_at = Some(now + data.interval);
+                streaming.quiet_since = Some(now);
+                return match exit_status.and_then(|x| x.code()) {
+                    Some(code) => Err(format!("Command exited, exit code was {code}")),
+                    None => Err("Command exited".to_owned()),
+                };
+            };
+            match interpret_streaming_line(&line, data.error_regex.as_ref(), data.ok_regex.as_ref())
+            {
+                Some(Ok(())) => {
+                    streaming.quiet_since = None;
+                    return Ok(());
+                }
+                Some(Err(message)) => {
+                    streaming.quiet_since = Some(now);
+                    return Err(message);
+                }
+                None => (),
+            }
+        }
+    }
+
+    // Failed invocations are retried immediately, so transient failures are not reported
+    async fn run_watched_command(data: &WatchCommandData) -> WatchStatus {
+        let mut retries_left = data.retries;
+        loop {
+            let status = match data.check {
+                Some(ref check) => check.run().await,
+                None => Self::run_command_once(data).await,
+            };
+            if status.result.is_ok() || retries_left == 0 {
+                return status;
             }
+            retries_left -= 1;
+            tokio::time::sleep(data.retry_delay).await;
+        }
+    }
 
-            // Execute command
-            do_watch(output_stream, data).await?;
+    async fn run_command_once(data: &WatchCommandData) -> WatchStatus {
+        let mut command_output = Self::execute_command(data).await;
+        #[cfg(feature = "script")]
+        if let Some(ref script) = data.script {
+            if command_output.executed && !command_output.timed_out {
+                return script.evaluate(command_output.status, &command_output.text);
+            }
+        }
+        if let Some(ref error_regex) = data.error_regex {
+            if command_output.executed && !command_output.timed_out {
+                command_output.text = error_regex.extract(&command_output.text);
+            }
         }
+        Self::process_command_output(command_output, &data.mode, &data.json_paths)
     }
 
-    async fn execute_command(
-        command: &str,
-        command_args: &Vec<String>,
-        shell: bool,
-    ) -> ExecuteCommandOutput {
+    async fn execute_command(data: &WatchCommandData) -> ExecuteCommandOutput {
         // Try to spawn subprocess
-        let mut subprocess;
-        if shell {
-            subprocess = tokio::process::Command::new("sh"); // TODO not really portable...
-            subprocess.arg("-c");
-            let command = format!("{command} {}", command_args.join(" "));
-            subprocess.arg(command);
-        } else {
-            subprocess = tokio::process::Command::new(command);
-            subprocess.args(command_args);
-        };
-        let subprocess = subprocess
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn();
+        let subprocess = Self::create_command(data).and_then(|mut x| x.spawn());
 
         // Handle failure to spawn the subprocess
-        let subprocess = match subprocess {
+        let mut subprocess = match subprocess {
             Ok(x) => x,
             Err(err) => {
-                let text = match err.kind() {
-                    std::io::ErrorKind::NotFound => format!("Executable \"{command}\" not found"),
-                    _ => err.to_string(),
-                };
+                let text = Self::describe_spawn_error(&data.command, err);
+                Self::log_command_output(data, &text, &[], &[]).await;
                 return ExecuteCommandOutput {
                     executed: false,
+                    timed_out: false,
                     status: None,
                     text,
                 };
             }
         };
 
-        // Wait for command to end and handle failure of waiting
-        let subprocess_result = subprocess.wait_with_output().await;
+        // Wait for command to end and handle failure of waiting. If the command runs for too long, kill it.
+        let subprocess_result = match data.timeout {
+            Some(timeout) => {
+                let wait = Self::wait_for_output(&mut subprocess);
+                match tokio::time::timeout(timeout, wait).await {
+                    Ok(x) => x,
+                    Err(_) => {
+                        Self::kill_command(&mut subprocess).await;
+                        let text = format!("Command timed out after {}", format_duration(timeout));
+                        Self::log_command_output(data, &text, &[], &[]).await;
+                        return ExecuteCommandOutput {
+                            executed: true,
+                            timed_out: true,
+                            status: None,
+                            text,
+                        };
+                    }
+                }
+            }
+            None => Self::wait_for_output(&mut subprocess).await,
+        };
         let subprocess_result = match subprocess_result {
             Ok(x) => x,
             Err(err) => {
+                let text = err.to_string();
+                Self::log_command_output(data, &text, &[], &[]).await;
                 return ExecuteCommandOutput {
                     executed: false,
+                    timed_out: false,
                     status: None,
-                    text: err.to_string(),
-                }
+                    text,
+                };
             }
         };
 
+        // Ssh reports its own failures with a dedicated exit code. The command hasn't run then, so its output
+        // cannot be interpreted.
+        if let Some(ref destination) = data.ssh {
+            if subprocess_result.status.code() == Some(SSH_CONNECTION_ERROR_EXIT_CODE) {
+                let stderr = String::from_utf8_lossy(&subprocess_result.stderr);
+                let reason = stderr.lines().find(|x| !x.trim().is_empty()).unwrap_or("");
+                let text = format!(
+                    "Could not connect to {destination} over SSH: {}",
+                    reason.trim()
+                );
+                Self::log_command_output(data, &text, &[], &subprocess_result.stderr).await;
+                return ExecuteCommandOutput {
+                    executed: false,
+                    timed_out: false,
+                    status: None,
+                    text,
+                };
+            }
+        }
+
         // The command has completed. Return information about it
+        let summary = match subprocess_result.status.code() {
+            Some(code) => format!("Exit code was {code}"),
+            None => "Command was terminated by a signal".to_owned(),
+        };
+        let stdout = &subprocess_result.stdout;
+        let stderr = &subprocess_result.stderr;
+        Self::log_command_output(data, &summary, stdout, stderr).await;
         ExecuteCommandOutput {
             executed: true,
+            timed_out: false,
             status: subprocess_result.status.code(),
-            text: String::from_utf8(subprocess_result.stdout)
-                .unwrap_or("Could not parse stdout".to_owned()),
+            text: Self::select_captured_text(
+                subprocess_result.stdout,
+                subprocess_result.stderr,
+                data.stream,
+            ),
+        }
+    }
+
+    // Appends a record of a single run of the command to the file set with --log-output. Unlike the status, which is
+    // summarized, the record contains whole captured output of the command.
+    async fn log_command_output(
+        data: &WatchCommandData,
+        summary: &str,
+        stdout: &[u8],
+        stderr: &[u8],
+    ) {
+        let Some(ref path) = data.log_output else {
+            return;
+        };
+        let record = format_log_record(chrono::Local::now(), summary, stdout, stderr);
+        if let Err(err) = append_to_file(path, &record).await {
+            eprintln!("Failed to write output log {}: {}", path.display(), err);
+        }
+    }
+
+    fn create_command(data: &WatchCommandData) -> std::io::Result<tokio::process::Command> {
+        let command = &data.command;
+        let command_args = &data.command_args;
+
+        let mut subprocess;
+        if let Some(ref destination) = data.ssh {
+            // Ssh joins the command and its arguments with spaces and passes them to the shell on the remote
+            // machine. Quoting makes the command run verbatim, unless it should be interpreted by the shell.
+            let mut ssh_command = data.ssh_command.split_whitespace();
+            let ssh = ssh_command.next().unwrap_or(DEFAULT_SSH_COMMAND);
+            subprocess = tokio::process::Command::new(ssh);
+            subprocess.args(ssh_command).arg("--").arg(destination);
+            let mut remote_command = match data.shell {
+                true => command.clone(),
+                false => Self::quote_shell_argument("sh", command),
+            };
+            for arg in command_args {
+                remote_command.push(' ');
+                remote_command.push_str(&Self::quote_shell_argument("sh", arg));
+            }
+            subprocess.arg(remote_command);
+        } else if data.shell {
+            // Shell command is a program followed by its arguments, e.g. "sh -c". The watched command is passed
+            // to it as the last argument. The command itself is interpreted by the shell, but its arguments are
+            // quoted, so they are passed verbatim.
+            let mut shell_command = data.shell_command.split_whitespace();
+            let shell = shell_command.next().unwrap_or(DEFAULT_SHELL_COMMAND);
+            subprocess = tokio::process::Command::new(shell);
+            subprocess.args(shell_command);
+            let mut command = command.clone();
+            for arg in command_args {
+                command.push(' ');
+                command.push_str(&Self::quote_shell_argument(shell, arg));
+            }
+            subprocess.arg(command);
+        } else {
+            subprocess = tokio::process::Command::new(command);
+            subprocess.args(command_args);
+        };
+        subprocess
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        data.limits.apply(&mut subprocess)?;
+        Ok(subprocess)
+    }
+
+    pub(crate) fn describe_spawn_error(command: &str, err: std::io::Error) -> String {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => format!("Executable \"{command}\" not found"),
+            _ => err.to_string(),
+        }
+    }
+
+    // Quoting rules depend on the shell, so they are selected based on its executable name
+    fn quote_shell_argument(shell: &str, arg: &str) -> String {
+        let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c);
+        if !arg.is_empty() && arg.chars().all(is_safe) {
+            return arg.to_owned();
+        }
+
+        // Paths are split manually, so Windows paths are recognized on all platforms
+        let shell_name = shell
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(shell)
+            .to_lowercase();
+        match shell_name.trim_end_matches(".exe") {
+            "cmd" => format!("\"{}\"", arg.replace('"', "\"\"")),
+            "powershell" | "pwsh" => format!("'{}'", arg.replace('\'', "''")),
+            _ => format!("'{}'", arg.replace('\'', "'\\''")),
+        }
+    }
+
+    // Equivalent of Child::wait_with_output, which doesn't consume the child, so it can still be killed. Output
+    // is read until the end, so the command doesn't block on a full pipe, but only its beginning is stored.
+    async fn wait_for_output(subprocess: &mut Child) -> std::io::Result<std::process::Output> {
+        async fn read_all(
+            pipe: Option<impl tokio::io::AsyncRead + Unpin>,
+        ) -> std::io::Result<Vec<u8>> {
+            let mut buffer = Vec::new();
+            let mut chunk = [0u8; 4096];
+            if let Some(mut pipe) = pipe {
+                loop {
+                    let read_bytes = pipe.read(&mut chunk).await?;
+                    if read_bytes == 0 {
+                        break;
+                    }
+                    // Store one byte over the limit, so truncation can be detected later
+                    let space_left = (MAX_CAPTURED_OUTPUT_BYTES + 1).saturating_sub(buffer.len());
+                    buffer.extend_from_slice(&chunk[..read_bytes.min(space_left)]);
+                }
+            }
+            Ok(buffer)
+        }
+
+        let stdout = read_all(subprocess.stdout.take());
+        let stderr = read_all(subprocess.stderr.take());
+        let (status, stdout, stderr) = tokio::try_join!(subprocess.wait(), stdout, stderr)?;
+        Ok(std::process::Output {
+            status,
+            stdout,
+            stderr,
+        })
+    }
+
+    // Asks the command to terminate gracefully first. If it doesn't, it is killed after a grace period.
+    async fn kill_command(subprocess: &mut Child) {
+        #[cfg(unix)]
+        if let Some(pid) = subprocess.id() {
+            // SAFETY: kill() has no memory safety requirements. The pid belongs to our child, which hasn't
+            // been reaped yet, so it cannot refer to an unrelated process.
+            unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+            let wait = subprocess.wait();
+            if tokio::time::timeout(WATCH_TIMEOUT_GRACE_PERIOD, wait)
+                .await
+                .is_ok()
+            {
+                return;
+            }
+        }
+        let _ = subprocess.kill().await;
+    }
+
+    fn select_captured_text(stdout: Vec<u8>, stderr: Vec<u8>, stream: CapturedStream) -> String {
+        let sanitize = |bytes: Vec<u8>| {
+            sanitize_output(&bytes, MAX_CAPTURED_OUTPUT_BYTES, MAX_CAPTURED_OUTPUT_LINES)
+        };
+        let stdout = || sanitize(stdout);
+        let stderr = || sanitize(stderr);
+        match stream {
+            CapturedStream::Stdout => stdout(),
+            CapturedStream::Stderr => stderr(),
+            CapturedStream::Both => {
+                let (stdout, stderr) = (stdout(), stderr());
+                if stdout.is_empty() || stdout.ends_with('\n') {
+                    stdout + stderr.as_str()
+                } else {
+                    stdout + "\n" + stderr.as_str()
+                }
+            }
         }
     }
 
     fn process_command_output(
         output: ExecuteCommandOutput,
         watch_mode: &WatchMode,
-    ) -> Result<(), String> {
+        json_paths: &JsonPaths,
+    ) -> WatchStatus {
         // Handle case when the command wasn't even executed
         if !output.executed {
-            return Err(format!("Command was not executed. {}", output.text));
+            return WatchStatus::error(format!("Command was not executed. {}", output.text));
+        }
+
+        // Handle case when the command was killed, because it had been running for too long
+        if output.timed_out {
+            return WatchStatus::error(output.text);
         }
 
         // Helper closures
@@ -237,7 +1703,7 @@ impl Action {
         };
 
         // Main match statement. Each WatchMode has to be handled differently.
-        match watch_mode {
+        let result = match watch_mode {
             WatchMode::OneLineError => process_one_line_error(),
             WatchMode::MultiLineError => process_multi_line_error(),
             WatchMode::ExitCode => match output.status {
@@ -249,6 +1715,64 @@ impl Action {
                 Some(x) if x != 0 => process_one_line_error(),
                 Some(x) => process_exit_code(x),
             },
+            WatchMode::InvertedExitCode => match output.status {
+                None => Err("Exit code is not available".to_owned()),
+                Some(0) => process_one_line_error().and(Err("Exit code was 0".to_owned())),
+                Some(_) => Ok(()),
+            },
+            WatchMode::Json => Self::process_json_output(&output.text, json_paths),
+            WatchMode::Nagios => match output.status {
+                None => Err("Exit code is not available".to_owned()),
+                Some(0) => Ok(()),
+                Some(code) => {
+                    // Plugins append performance data after a pipe, which is not meant for humans
+                    let message = output
+                        .text
+                        .lines()
+                        .map(|line| line.split('|').next().unwrap_or_default().trim())
+                        .find(|line| !line.is_empty());
+                    let message = match message {
+                        Some(message) => message.to_owned(),
+                        None => format!("Exit code was {code}"),
+                    };
+                    if code == NAGIOS_WARNING_EXIT_CODE {
+                        return WatchStatus::warning(message);
+                    }
+                    Err(message)
+                }
+            },
+        };
+        result.into()
+    }
+
+    fn process_json_output(text: &str, json_paths: &JsonPaths) -> Result<(), String> {
+        let json: serde_json::Value = match serde_json::from_str(text) {
+            Ok(x) => x,
+            Err(err) => return Err(format!("Could not parse output as JSON: {err}")),
+        };
+
+        let status = match JsonPaths::get(&json, &json_paths.status) {
+            Some(x) => x,
+            None => return Err(format!("Status not found at {}", json_paths.status)),
+        };
+        let is_ok = match status {
+            serde_json::Value::Bool(x) => *x,
+            serde_json::Value::Number(x) => x.as_i64() == Some(0),
+            serde_json::Value::String(x) => x.eq_ignore_ascii_case("ok"),
+            _ => false,
+        };
+        if is_ok {
+            return Ok(());
+        }
+
+        let message = json_paths
+            .message
+            .as_ref()
+            .and_then(|path| JsonPaths::get(&json, path));
+        match message {
+            Some(serde_json::Value::String(x)) => Err(x.clone()),
+            Some(x) => Err(x.to_string()),
+            None => Err(format!("Status was {status}")),
         }
     }
 }
@@ -263,22 +1787,544 @@ mod tests {
             WatchMode::MultiLineError,
             WatchMode::ExitCode,
             WatchMode::OneLineErrorExitCode,
+            WatchMode::InvertedExitCode,
+            WatchMode::Json,
+            WatchMode::Nagios,
         ]
         .into_iter()
     }
 
+    #[test]
+    fn time_to_next_run_is_computed() {
+        let now = Instant::now();
+        let start = now.checked_sub(Duration::from_secs(3)).unwrap();
+        let since_epoch = Duration::from_secs(1_700_000_000) + Duration::from_millis(12_345);
+        let mut data = WatchCommandData::new("echo".to_owned(), Vec::new());
+        data.interval = Duration::from_secs(10);
+
+        let run = |data: &WatchCommandData, start: Option<Instant>, expected: Duration| {
+            assert_eq!(
+                get_time_to_next_run(data, start, now, since_epoch),
+                expected
+            );
+        };
+
+        data.schedule = ScheduleMode::Delay;
+        run(&data, Some(start), Duration::from_secs(10));
+        run(&data, None, Duration::from_secs(10));
+
+        data.schedule = ScheduleMode::Tick;
+        run(&data, Some(start), Duration::from_secs(7));
+        run(&data, None, Duration::from_secs(10));
+        let long_ago = now.checked_sub(Duration::from_secs(30)).unwrap();
+        run(&data, Some(long_ago), Duration::ZERO);
+
+        data.align = true;
+        run(&data, Some(start), Duration::from_millis(7_655));
+        data.interval = Duration::from_secs(60);
+        run(&data, None, Duration::from_millis(60_000 - 32_345));
+
+        data.interval = Duration::ZERO;
+        run(&data, None, Duration::ZERO);
+    }
+
+    #[test]
+    fn missed_ticks_are_skipped_with_skip_overlap_policy() {
+        let now = Instant::now();
+        let since_epoch = Duration::from_secs(1_700_000_000);
+        let mut data = WatchCommandData::new("echo".to_owned(), Vec::new());
+        data.interval = Duration::from_secs(10);
+        data.schedule = ScheduleMode::Tick;
+
+        let run = |data: &WatchCommandData, elapsed: u64, expected: Duration| {
+            let start = now.checked_sub(Duration::from_secs(elapsed)).unwrap();
+            assert_eq!(
+                get_time_to_next_run(data, Some(start), now, since_epoch),
+                expected
+            );
+        };
+
+        data.overlap = OverlapPolicy::Queue;
+        run(&data, 3, Duration::from_secs(7));
+        run(&data, 23, Duration::ZERO);
+
+        data.overlap = OverlapPolicy::Skip;
+        run(&data, 3, Duration::from_secs(7));
+        run(&data, 23, Duration::from_secs(7));
+        run(&data, 10, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn statuses_are_sent_only_on_changes() {
+        let now = Instant::now();
+        let recently = now.checked_sub(Duration::from_secs(5)).unwrap();
+        let long_ago = now.checked_sub(Duration::from_secs(100)).unwrap();
+        let mut data = WatchCommandData::new("echo".to_owned(), Vec::new());
+
+        assert!(should_send_status(&data, false, Some(recently), now));
+        assert!(should_send_status(&data, true, Some(recently), now));
+
+        data.only_changes = true;
+        assert!(should_send_status(&data, true, Some(recently), now));
+        assert!(!should_send_status(&data, false, Some(recently), now));
+        assert!(!should_send_status(&data, false, Some(long_ago), now));
+        assert!(should_send_status(&data, false, None, now));
+
+        data.reconfirm_interval = Some(Duration::from_secs(60));
+        assert!(!should_send_status(&data, false, Some(recently), now));
+        assert!(should_send_status(&data, false, Some(long_ago), now));
+    }
+
+    #[test]
+    fn status_updates_are_rate_limited() {
+        let start = Instant::now();
+        let mut send_times = VecDeque::new();
+        assert_eq!(get_rate_limit_delay(None, &mut send_times, start), None);
+
+        let limit = Some(2);
+        assert_eq!(get_rate_limit_delay(limit, &mut send_times, start), None);
+        send_times.push_back(start);
+        let now = start + Duration::from_secs(10);
+        assert_eq!(get_rate_limit_delay(limit, &mut send_times, now), None);
+        send_times.push_back(now);
+
+        let now = start + Duration::from_secs(15);
+        assert_eq!(
+            get_rate_limit_delay(limit, &mut send_times, now),
+            Some(Duration::from_secs(45))
+        );
+        let now = start + Duration::from_secs(60);
+        assert_eq!(get_rate_limit_delay(limit, &mut send_times, now), None);
+        assert_eq!(send_times.len(), 1);
+    }
+
+    #[test]
+    fn grace_period_starts_with_client() {
+        let mut data = WatchCommandData::new("echo".to_owned(), Vec::new());
+        let mut state = ActionState::default();
+        let client_start = Instant::now();
+        let later = client_start + Duration::from_secs(10);
+        assert!(!in_grace_period(&data, &state, client_start));
+
+        state.client_start = Some(client_start);
+        assert!(!in_grace_period(&data, &state, client_start));
+
+        data.grace = Some(Duration::from_secs(10));
+        assert!(in_grace_period(&data, &state, client_start));
+        assert!(in_grace_period(
+            &data,
+            &state,
+            later - Duration::from_millis(1)
+        ));
+        assert!(!in_grace_period(&data, &state, later));
+    }
+
+    #[test]
+    fn hysteresis_is_applied_to_statuses() {
+        fn run(failures_before_error: u32, successes_before_ok: u32, steps: &[(bool, bool)]) {
+            let mut data = WatchCommandData::new("echo".to_owned(), Vec::new());
+            data.failures_before_error = failures_before_error;
+            data.successes_before_ok = successes_before_ok;
+            let mut state = ActionState::default();
+            for (index, (command_ok, expected_ok)) in steps.iter().enumerate() {
+                let status = match command_ok {
+                    true => Ok(()),
+                    false => Err(format!("fail{index}")),
+                };
+                let status = apply_hysteresis(&data, &mut state, status.into());
+                assert_eq!(status.result.is_ok(), *expected_ok, "step {index}");
+                state.last_watch_status = Some(status);
+            }
+        }
+
+        run(1, 1, &[(true, true), (false, false), (true, true)]);
+        run(
+            3,
+            1,
+            &[
+                (false, true),
+                (false, true),
+                (true, true),
+                (false, true),
+                (false, true),
+                (false, false),
+                (true, true),
+            ],
+        );
+        run(
+            1,
+            2,
+            &[
+                (false, false),
+                (true, false),
+                (false, false),
+                (true, false),
+                (true, true),
+            ],
+        );
+    }
+
+    #[test]
+    fn hysteresis_keeps_latest_error_message() {
+        let mut data = WatchCommandData::new("echo".to_owned(), Vec::new());
+        data.successes_before_ok = 2;
+        let mut state = ActionState::default();
+
+        let first = WatchStatus::warning("first".to_owned());
+        let status = apply_hysteresis(&data, &mut state, first.clone());
+        state.last_watch_status = Some(status);
+        let status = apply_hysteresis(&data, &mut state, WatchStatus::ok());
+        assert_eq!(status, first);
+        state.last_watch_status = Some(status);
+        let second = WatchStatus::error("second".to_owned());
+        let status = apply_hysteresis(&data, &mut state, second.clone());
+        assert_eq!(status, second);
+    }
+
+    #[test]
+    fn jitter_is_added_to_duration() {
+        let duration = Duration::from_secs(10);
+        assert_eq!(with_jitter(duration, Duration::ZERO), duration);
+
+        let jitter = Duration::from_millis(500);
+        for _ in 0..100 {
+            let result = with_jitter(duration, jitter);
+            assert!(result >= duration && result <= duration + jitter);
+        }
+    }
+
+    #[test]
+    fn ssh_command_is_created() {
+        fn run(shell: bool, command: &str, args: &[&str], expected_remote_command: &str) {
+            let mut data = WatchCommandData::new(
+                command.to_owned(),
+                args.iter().map(|x| x.to_string()).collect(),
+            );
+            data.shell = shell;
+            data.ssh = Some("admin@router".to_owned());
+            let subprocess = Action::create_command(&data).expect("Command should be created");
+            let subprocess = subprocess.as_std();
+            assert_eq!(subprocess.get_program(), "ssh");
+            let args = subprocess.get_args().collect::<Vec<_>>();
+            assert_eq!(
+                args,
+                [
+                    "-o",
+                    "BatchMode=yes",
+                    "--",
+                    "admin@router",
+                    expected_remote_command
+                ]
+            );
+        }
+
+        run(false, "df", &["-h", "/"], "df -h /");
+        run(false, "grep", &["a b", "log"], "grep 'a b' log");
+        run(false, "my script", &[], "'my script'");
+        run(true, "df | tail -1", &["it's"], "df | tail -1 'it'\\''s'");
+    }
+
+    #[test]
+    fn shell_arguments_are_quoted() {
+        fn run(shell: &str, arg: &str, expected: &str) {
+            assert_eq!(Action::quote_shell_argument(shell, arg), expected);
+        }
+
+        run("sh", "hello", "hello");
+        run("sh", "--path=/tmp/a.txt", "--path=/tmp/a.txt");
+        run("sh", "", "''");
+        run("sh", "hello world", "'hello world'");
+        run("sh", "a; rm -rf /", "'a; rm -rf /'");
+        run("/bin/bash", "$HOME", "'$HOME'");
+        run("sh", "it's", "'it'\\''s'");
+        run("cmd", "hello", "hello");
+        run("cmd", "a & b", "\"a & b\"");
+        run(
+            "C:\\Windows\\System32\\cmd.exe",
+            "say \"hi\"",
+            "\"say \"\"hi\"\"\"",
+        );
+        run("powershell", "it's", "'it''s'");
+        run("pwsh", "$env:PATH", "'$env:PATH'");
+    }
+
+    #[test]
+    fn error_message_is_extracted_with_regex() {
+        fn run(pattern: &str, text: &str, expected: &str) {
+            let regex = OutputRegex::parse(pattern).expect("Regex should be valid");
+            assert_eq!(regex.extract(text), expected);
+        }
+
+        run("ERROR: (.*)", "info\nERROR: disk full\ninfo", "disk full");
+        run("ERROR: (.*)", "info\nERROR: first\nERROR: second", "first");
+        run("ERROR: (.*)", "info\nwarning", "");
+        run("(?i)fail\\w*", "all good\nFAILED badly", "FAILED");
+        run("code (\\d+)|(timeout)", "got timeout", "timeout");
+
+        assert!(OutputRegex::parse("(unclosed").is_err());
+    }
+
+    #[test]
+    fn log_records_are_formatted() {
+        use chrono::TimeZone;
+        let time = chrono::Local
+            .with_ymd_and_hms(2024, 3, 5, 9, 41, 7)
+            .unwrap();
+
+        let record = format_log_record(time, "Exit code was 1", b"out", b"err\n");
+        assert_eq!(
+            record,
+            "[2024-03-05 09:41:07] Exit code was 1\n--- stdout ---\nout\n--- stderr ---\nerr\n"
+        );
+
+        let record = format_log_record(time, "Exit code was 0", b"", b"");
+        assert_eq!(record, "[2024-03-05 09:41:07] Exit code was 0\n");
+    }
+
+    #[test]
+    fn status_hook_is_selected_on_transitions() {
+        let mut data = WatchCommandData::new("echo".to_owned(), Vec::new());
+        data.on_error = Some("restart".to_owned());
+        data.on_recover = Some("notify".to_owned());
+        let ok = WatchStatus::ok();
+        let error = WatchStatus::error("Failure".to_owned());
+
+        assert_eq!(get_status_hook(&data, None, &ok), None);
+        assert_eq!(get_status_hook(&data, None, &error), Some("restart"));
+        assert_eq!(get_status_hook(&data, Some(&ok), &ok), None);
+        assert_eq!(get_status_hook(&data, Some(&ok), &error), Some("restart"));
+        assert_eq!(get_status_hook(&data, Some(&error), &error), None);
+        assert_eq!(get_status_hook(&data, Some(&error), &ok), Some("notify"));
+
+        data.on_error = None;
+        assert_eq!(get_status_hook(&data, Some(&ok), &error), None);
+    }
+
+    #[test]
+    fn streaming_lines_are_interpreted() {
+        fn run(
+            line: &str,
+            error_regex: Option<&str>,
+            ok_regex: Option<&str>,
+            expected: Option<Result<(), &str>>,
+        ) {
+            let error_regex = error_regex.map(|x| OutputRegex::parse(x).unwrap());
+            let ok_regex = ok_regex.map(|x| OutputRegex::parse(x).unwrap());
+            let result = interpret_streaming_line(line, error_regex.as_ref(), ok_regex.as_ref());
+            assert_eq!(result, expected.map(|x| x.map_err(str::to_owned)));
+        }
+
+        run("Disk full\n", None, None, Some(Err("Disk full")));
+        run("  \n", None, None, None);
+        run("Healthy\n", None, Some("^Healthy"), Some(Ok(())));
+        run(
+            "ERROR: disk full\n",
+            Some("ERROR: (.*)"),
+            None,
+            Some(Err("disk full")),
+        );
+        run("INFO: started\n", Some("ERROR: (.*)"), None, None);
+        run(
+            "ERROR: recovered\n",
+            Some("ERROR: (.*)"),
+            Some("recovered"),
+            Some(Ok(())),
+        );
+    }
+
+    #[test]
+    fn ansi_escape_sequences_are_stripped() {
+        fn run(text: &str, expected: &str) {
+            assert_eq!(strip_ansi_escape_sequences(text), expected);
+        }
+
+        run("plain text", "plain text");
+        run("\x1b[31mred\x1b[0m text", "red text");
+        run("\x1b[1;38;5;196mbold\x1b[m", "bold");
+        run("\x1b]0;title\x07after", "after");
+        run("\x1b]0;title\x1b\\after", "after");
+        run("\x1b=keypad", "keypad");
+        run("unfinished\x1b[31", "unfinished");
+    }
+
+    #[test]
+    fn output_is_sanitized() {
+        fn run(bytes: &[u8], max_bytes: usize, max_lines: usize, expected: &str) {
+            assert_eq!(sanitize_output(bytes, max_bytes, max_lines), expected);
+        }
+
+        run(b"hello\nworld\n", 100, 100, "hello\nworld\n");
+        run(b"bad \xff byte", 100, 100, "bad \u{FFFD} byte");
+        run(b"\x1b[31mError\x1b[0m", 100, 100, "Error");
+        run(b"0123456789", 4, 100, "0123\n[output truncated]");
+        run(b"a\nb\nc\nd\n", 100, 2, "a\nb\n[output truncated]");
+        run(b"a\nb", 100, 2, "a\nb");
+        run(b"", 0, 0, "");
+    }
+
+    #[test]
+    fn captured_text_is_selected_from_streams() {
+        fn run(stdout: &str, stderr: &str, stream: CapturedStream, expected_text: &str) {
+            let text = Action::select_captured_text(stdout.into(), stderr.into(), stream);
+            assert_eq!(text, expected_text);
+        }
+
+        run("out", "err", CapturedStream::Stdout, "out");
+        run("out", "err", CapturedStream::Stderr, "err");
+        run("out", "err", CapturedStream::Both, "out\nerr");
+        run("out\n", "err", CapturedStream::Both, "out\nerr");
+        run("", "err", CapturedStream::Both, "err");
+        run("out", "", CapturedStream::Both, "out\n");
+        run("", "", CapturedStream::Both, "");
+    }
+
     #[test]
     fn given_command_not_executed_when_processing_command_ouptput_then_return_error() {
         let command_output = ExecuteCommandOutput {
             executed: false,
+            timed_out: false,
             status: None,
             text: "Hello".to_owned(),
         };
         let expected_result = Err("Command was not executed. Hello".to_owned());
         for watch_mode in get_all_watch_modes() {
-            let actual_result = Action::process_command_output(command_output.clone(), &watch_mode);
-            assert_eq!(expected_result, actual_result);
+            let actual_result = Action::process_command_output(
+                command_output.clone(),
+                &watch_mode,
+                &JsonPaths::default(),
+            );
+            assert_eq!(expected_result, actual_result.result);
+        }
+    }
+
+    #[test]
+    fn given_command_timed_out_when_processing_command_output_then_return_error() {
+        let command_output = ExecuteCommandOutput {
+            executed: true,
+            timed_out: true,
+            status: None,
+            text: "Command timed out after 5s".to_owned(),
+        };
+        let expected_result = Err("Command timed out after 5s".to_owned());
+        for watch_mode in get_all_watch_modes() {
+            let actual_result = Action::process_command_output(
+                command_output.clone(),
+                &watch_mode,
+                &JsonPaths::default(),
+            );
+            assert_eq!(expected_result, actual_result.result);
+        }
+    }
+
+    #[test]
+    fn given_inverted_exit_code_mode_when_processing_command_output_then_return_correct_result() {
+        fn run(status: Option<i32>, command_stdout: &str, expected_result: Result<(), &str>) {
+            let command_output = ExecuteCommandOutput {
+                executed: true,
+                timed_out: false,
+                status,
+                text: command_stdout.to_owned(),
+            };
+
+            let actual_result = Action::process_command_output(
+                command_output,
+                &WatchMode::InvertedExitCode,
+                &JsonPaths::default(),
+            );
+            assert_eq!(expected_result.map_err(str::to_owned), actual_result.result);
+        }
+
+        run(None, "", Err("Exit code is not available"));
+        run(Some(0), "", Err("Exit code was 0"));
+        run(
+            Some(0),
+            "\n FATAL: out of memory\nFATAL: again",
+            Err("FATAL: out of memory"),
+        );
+        run(Some(1), "", Ok(()));
+        run(Some(2), "hello", Ok(()));
+    }
+
+    #[test]
+    fn given_nagios_mode_when_processing_command_output_then_return_correct_result() {
+        fn run(status: Option<i32>, command_stdout: &str, expected_status: WatchStatus) {
+            let command_output = ExecuteCommandOutput {
+                executed: true,
+                timed_out: false,
+                status,
+                text: command_stdout.to_owned(),
+            };
+
+            let actual_status = Action::process_command_output(
+                command_output,
+                &WatchMode::Nagios,
+                &JsonPaths::default(),
+            );
+            assert_eq!(expected_status, actual_status);
+        }
+
+        let error = |message: &str| WatchStatus::error(message.to_owned());
+        let warning = |message: &str| WatchStatus::warning(message.to_owned());
+        run(None, "", error("Exit code is not available"));
+        run(Some(0), "DISK OK - free space: 80%", WatchStatus::ok());
+        run(
+            Some(1),
+            "DISK WARNING - free space: 15%",
+            warning("DISK WARNING - free space: 15%"),
+        );
+        run(Some(1), "", warning("Exit code was 1"));
+        run(
+            Some(2),
+            "\nDisk full | /=98%;80;90\nmore",
+            error("Disk full"),
+        );
+        run(Some(3), "", error("Exit code was 3"));
+        run(Some(127), "| perf=1", error("Exit code was 127"));
+    }
+
+    #[test]
+    fn given_json_mode_when_processing_command_output_then_return_correct_result() {
+        fn run(text: &str, status: &str, message: Option<&str>, expected_result: Result<(), &str>) {
+            let command_output = ExecuteCommandOutput {
+                executed: true,
+                timed_out: false,
+                status: Some(0),
+                text: text.to_owned(),
+            };
+            let json_paths = JsonPaths {
+                status: status.to_owned(),
+                message: message.map(str::to_owned),
+            };
+            let actual_result =
+                Action::process_command_output(command_output, &WatchMode::Json, &json_paths);
+            assert_eq!(expected_result.map_err(str::to_owned), actual_result.result);
         }
+
+        let text = r#"{"ok": false, "detail": "disk full", "checks": [{"code": 0}, {"code": 2}]}"#;
+        run(text, ".ok", Some(".detail"), Err("disk full"));
+        run(text, ".ok", None, Err("Status was false"));
+        run(text, ".checks.0.code", None, Ok(()));
+        run(
+            text,
+            ".checks.1.code",
+            Some(".checks.1"),
+            Err(r#"{"code":2}"#),
+        );
+        run(text, ".missing", None, Err("Status not found at .missing"));
+        run(r#"{"status": "OK"}"#, ".status", None, Ok(()));
+        run(
+            r#"{"status": "degraded"}"#,
+            ".status",
+            None,
+            Err("Status was \"degraded\""),
+        );
+        run("true", ".", None, Ok(()));
+        run(
+            "not json",
+            ".ok",
+            None,
+            Err("Could not parse output as JSON: expected ident at line 1 column 2"),
+        );
     }
 
     #[test]
@@ -290,14 +2336,18 @@ mod tests {
             for status in statuses {
                 let command_output = ExecuteCommandOutput {
                     executed: true,
+                    timed_out: false,
                     status,
                     text: command_stdout.to_owned(),
                 };
 
                 let watch_mode = WatchMode::OneLineError;
-                let actual_result =
-                    Action::process_command_output(command_output.clone(), &watch_mode);
-                assert_eq!(expected_result, actual_result);
+                let actual_result = Action::process_command_output(
+                    command_output.clone(),
+                    &watch_mode,
+                    &JsonPaths::default(),
+                );
+                assert_eq!(expected_result, actual_result.result);
             }
         }
 
@@ -321,14 +2371,18 @@ mod tests {
             for status in statuses {
                 let command_output = ExecuteCommandOutput {
                     executed: true,
+                    timed_out: false,
                     status,
                     text: command_stdout.to_owned(),
                 };
 
                 let watch_mode = WatchMode::MultiLineError;
-                let actual_result =
-                    Action::process_command_output(command_output.clone(), &watch_mode);
-                assert_eq!(expected_result, actual_result);
+                let actual_result = Action::process_command_output(
+                    command_output.clone(),
+                    &watch_mode,
+                    &JsonPaths::default(),
+                );
+                assert_eq!(expected_result, actual_result.result);
             }
         }
 
@@ -357,14 +2411,18 @@ mod tests {
             for text in texts {
                 let command_output = ExecuteCommandOutput {
                     executed: true,
+                    timed_out: false,
                     status,
                     text: text.to_owned(),
                 };
 
                 let watch_mode = WatchMode::ExitCode;
-                let actual_result =
-                    Action::process_command_output(command_output.clone(), &watch_mode);
-                assert_eq!(expected_result, actual_result);
+                let actual_result = Action::process_command_output(
+                    command_output.clone(),
+                    &watch_mode,
+                    &JsonPaths::default(),
+                );
+                assert_eq!(expected_result, actual_result.result);
             }
         }
 
@@ -381,13 +2439,18 @@ mod tests {
         fn run(status: Option<i32>, command_stdout: &str, expected_result: Result<(), String>) {
             let command_output = ExecuteCommandOutput {
                 executed: true,
+                timed_out: false,
                 status,
                 text: command_stdout.to_owned(),
             };
 
             let watch_mode = WatchMode::OneLineErrorExitCode;
-            let actual_result = Action::process_command_output(command_output.clone(), &watch_mode);
-            assert_eq!(expected_result, actual_result);
+            let actual_result = Action::process_command_output(
+                command_output.clone(),
+                &watch_mode,
+                &JsonPaths::default(),
+            );
+            assert_eq!(expected_result, actual_result.result);
         }
 
         run(None, "hello", Err("Exit code is not available".to_owned()));
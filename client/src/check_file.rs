@@ -0,0 +1,304 @@
+// Many checks can be described in a single TOML file and run concurrently by one client process, instead of
+// starting a separate client for each of them. Each check is reported to the server as a separate client, e.g.:
+//
+//     [[check]]
+//     name = "root-disk"
+//     builtin = ["disk", "/", "--warn", "80%", "--crit", "95%"]
+//     interval = "5m"
+//     tags = ["infra"]
+//
+//     [[check]]
+//     name = "backup"
+//     command = ["./check_backup.sh", "--verbose"]
+//     mode = "ExitCode"
+//     options = ["--retries", "2"]
+//
+// A check either watches a command or runs a built-in check with its arguments, including thresholds. Options are
+// any other arguments of the watch or check actions. Every check is parsed just like its command line would be, so
+// all arguments are validated at startup.
+
+use crate::config::Config;
+use crate::user_defaults::UserDefaults;
+use check_mate_common::CommandLineError;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+#[derive(Deserialize, Default, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+struct CheckFile {
+    #[serde(default, rename = "check")]
+    checks: Vec<CheckEntry>,
+}
+
+#[derive(Deserialize, Default, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+struct CheckEntry {
+    name: String,
+    command: Option<Vec<String>>,
+    builtin: Option<Vec<String>>,
+    interval: Option<String>,
+    mode: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    options: Vec<String>,
+}
+
+impl CheckEntry {
+    // Translates the check into arguments of the watch or check action
+    fn to_args(&self) -> Result<Vec<String>, String> {
+        let mut common_args = vec!["-n".to_owned(), self.name.clone()];
+        if let Some(interval) = &self.interval {
+            common_args.extend(["-w".to_owned(), interval.clone()]);
+        }
+        for tag in &self.tags {
+            common_args.extend(["-t".to_owned(), tag.clone()]);
+        }
+        common_args.extend(self.options.iter().cloned());
+
+        match (&self.command, &self.builtin) {
+            (Some(command), None) => {
+                if command.is_empty() {
+                    return Err("command cannot be empty".to_owned());
+                }
+                let mut args = vec!["watch".to_owned()];
+                args.extend(common_args);
+                if let Some(mode) = &self.mode {
+                    args.extend(["-m".to_owned(), mode.clone()]);
+                }
+                args.push("--".to_owned());
+                args.extend(command.iter().cloned());
+                Ok(args)
+            }
+            (None, Some(builtin)) => {
+                let Some((check, check_args)) = builtin.split_first() else {
+                    return Err("builtin cannot be empty".to_owned());
+                };
+                if self.mode.is_some() {
+                    return Err("mode can only be used with command".to_owned());
+                }
+                let mut args = vec!["check".to_owned(), check.clone()];
+                args.extend(common_args);
+                args.extend(check_args.iter().cloned());
+                Ok(args)
+            }
+            _ => Err("exactly one of command and builtin has to be specified".to_owned()),
+        }
+    }
+}
+
+// Check along with the arguments it was parsed from. The arguments tell whether the check has changed when the file is
+// reloaded.
+pub struct CheckConfig {
+    pub name: String,
+    pub args: Vec<String>,
+    pub config: Config,
+}
+
+fn parse_checks(
+    text: &str,
+    base: &Config,
+    defaults: &UserDefaults,
+) -> Result<Vec<CheckConfig>, String> {
+    let file = toml::from_str::<CheckFile>(text).map_err(|err| err.message().to_owned())?;
+    if file.checks.is_empty() {
+        return Err("no checks defined".to_owned());
+    }
+
+    let mut names = HashSet::new();
+    let mut checks = Vec::new();
+    for entry in &file.checks {
+        if !names.insert(entry.name.as_str()) {
+            return Err(format!(
+                "check \"{}\" is defined more than once",
+                entry.name
+            ));
+        }
+        let on_error = |message: String| format!("check \"{}\": {message}", entry.name);
+        let args = entry.to_args().map_err(on_error)?;
+        let mut config = Config::parse_with_defaults(args.iter().cloned(), defaults)
+            .map_err(|err| on_error(Config::describe_parse_error(err)))?;
+
+        // Connection settings, namespace and tags given to the run action are shared by all checks
+        config.server_addresses = base.server_addresses.clone();
+        config.discovery_domain = base.discovery_domain.clone();
+        config.server_port = base.server_port;
+        config.namespace = base.namespace.clone();
+        config.server_connection_backoff = base.server_connection_backoff;
+        config.server_connection_attempts = base.server_connection_attempts;
+        config.tls_ca = base.tls_ca.clone();
+        config.tls_cert = base.tls_cert.clone();
+        config.tls_key = base.tls_key.clone();
+        config.api_key = base.api_key.clone();
+        let mut tags = base.client_tags.clone();
+        tags.append(&mut config.client_tags);
+        config.client_tags = tags;
+        checks.push(CheckConfig {
+            name: entry.name.clone(),
+            args,
+            config,
+        });
+    }
+    Ok(checks)
+}
+
+pub fn load_checks(
+    path: &Path,
+    base: &Config,
+    defaults: &UserDefaults,
+) -> Result<Vec<CheckConfig>, CommandLineError> {
+    let on_error =
+        |message: String| CommandLineError::InvalidConfigFile(path.display().to_string(), message);
+    let text = std::fs::read_to_string(path).map_err(|err| on_error(err.to_string()))?;
+    parse_checks(&text, base, defaults).map_err(on_error)
+}
+
+// Listens for SIGHUP, which requests reloading the check file. The handler has to be installed at startup, because by
+// default the signal terminates the process. Not available on Windows, where the control socket can be used instead.
+pub struct ReloadSignal {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl ReloadSignal {
+    pub fn install() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Self {
+                signal: signal(SignalKind::hangup()).ok(),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            Self {}
+        }
+    }
+
+    // Waits for the signal. Never completes if the handler is not installed.
+    pub async fn wait(&mut self) {
+        #[cfg(unix)]
+        if let Some(ref mut signal) = self.signal {
+            if signal.recv().await.is_some() {
+                return;
+            }
+        }
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::action::{Action, BuiltinCheck, WatchMode};
+    use std::time::Duration;
+
+    fn parse(text: &str) -> Result<Vec<Config>, String> {
+        let checks = parse_checks(text, &Config::default(), &UserDefaults::default())?;
+        Ok(checks.into_iter().map(|x| x.config).collect())
+    }
+
+    #[test]
+    fn checks_are_parsed() {
+        let text = r#"
+            [[check]]
+            name = "root-disk"
+            builtin = ["disk", "/", "--warn", "80%"]
+            interval = "5m"
+            tags = ["infra"]
+
+            [[check]]
+            name = "backup"
+            command = ["./check_backup.sh", "--verbose"]
+            mode = "ExitCode"
+            options = ["--retries", "2"]
+        "#;
+        let configs = parse(text).expect("Parsing should succeed");
+        assert_eq!(configs.len(), 2);
+
+        assert_eq!(configs[0].client_name.as_deref(), Some("root-disk"));
+        assert_eq!(configs[0].client_tags, vec!["infra".to_owned()]);
+        let Action::WatchCommand(ref data) = configs[0].action else {
+            panic!("Check should be a watch action");
+        };
+        assert_eq!(data.interval, Duration::from_secs(300));
+        let Some(BuiltinCheck::Disk(ref disk)) = data.check else {
+            panic!("Check should be a disk check");
+        };
+        assert_eq!(disk.warning_percent, Some(80));
+
+        assert_eq!(configs[1].client_name.as_deref(), Some("backup"));
+        let Action::WatchCommand(ref data) = configs[1].action else {
+            panic!("Check should be a watch action");
+        };
+        assert_eq!(data.command, "./check_backup.sh");
+        assert_eq!(data.command_args, vec!["--verbose".to_owned()]);
+        assert_eq!(data.mode, WatchMode::ExitCode);
+        assert_eq!(data.retries, 2);
+    }
+
+    #[test]
+    fn connection_settings_are_shared() {
+        let base = Config {
+            server_addresses: vec!["monitoring".to_owned()],
+            server_port: 2000,
+            client_tags: vec!["host".to_owned()],
+            ..Default::default()
+        };
+        let text = r#"
+            [[check]]
+            name = "load"
+            builtin = ["system"]
+            tags = ["infra"]
+        "#;
+        let checks = parse_checks(text, &base, &UserDefaults::default()).unwrap();
+        let configs = checks.into_iter().map(|x| x.config).collect::<Vec<_>>();
+        assert_eq!(configs[0].server_addresses, vec!["monitoring".to_owned()]);
+        assert_eq!(configs[0].server_port, 2000);
+        assert_eq!(
+            configs[0].client_tags,
+            vec!["host".to_owned(), "infra".to_owned()]
+        );
+    }
+
+    #[test]
+    fn invalid_checks_are_reported() {
+        assert_eq!(parse(""), Err("no checks defined".to_owned()));
+        assert_eq!(
+            parse("[[check]]\nname = \"a\""),
+            Err("check \"a\": exactly one of command and builtin has to be specified".to_owned())
+        );
+        assert_eq!(
+            parse("[[check]]\nname = \"a\"\ncommand = [\"ls\"]\nbuiltin = [\"system\"]"),
+            Err("check \"a\": exactly one of command and builtin has to be specified".to_owned())
+        );
+        assert_eq!(
+            parse("[[check]]\nname = \"a\"\nbuiltin = [\"system\"]\nmode = \"ExitCode\""),
+            Err("check \"a\": mode can only be used with command".to_owned())
+        );
+        assert_eq!(
+            parse("[[check]]\nname = \"a\"\ncommand = []"),
+            Err("check \"a\": command cannot be empty".to_owned())
+        );
+        assert_eq!(
+            parse("[[check]]\nname = \"a\"\ncommand = [\"ls\"]\n[[check]]\nname = \"a\"\ncommand = [\"ls\"]"),
+            Err("check \"a\" is defined more than once".to_owned())
+        );
+
+        let error =
+            parse("[[check]]\nname = \"a\"\nbuiltin = [\"disk\", \"/\", \"--warn\", \"lots\"]")
+                .unwrap_err();
+        assert!(
+            error.starts_with("check \"a\": invalid value 'lots'"),
+            "{error}"
+        );
+        let error = parse("[[check]]\nname = \"a\"\nbuiltin = [\"teleport\"]").unwrap_err();
+        assert!(
+            error.starts_with("check \"a\": unrecognized subcommand"),
+            "{error}"
+        );
+
+        parse("[[check]]\nnmae = \"a\"").expect_err("Unknown field should fail");
+    }
+}
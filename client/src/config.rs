@@ -1,507 +1,4099 @@
+use std::path::PathBuf;
 use std::time::Duration;
 
-use crate::action::{Action, WatchCommandData, WatchMode};
+#[cfg(any(target_os = "linux", windows))]
+use crate::action::LogCheck;
+#[cfg(feature = "script")]
+use crate::action::OutputScript;
+#[cfg(feature = "redis")]
+use crate::action::RedisData;
+#[cfg(feature = "smart")]
+use crate::action::SmartCheck;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+use crate::action::SystemdCheck;
+#[cfg(feature = "wasm")]
+use crate::action::WasmCheck;
+use crate::action::{
+    parse_influx_destination, parse_time, Action, ApiKeyRequest, AvailabilityWindow, BadgeData,
+    BuiltinCheck, CapturedStream, ColorChoice, ControlCommand, ControlData, CronWrapData,
+    DiskCheck, DnsCheck, DockerCheck, ExportData, ExportFormat, FileCheck, GroupBy, InfluxData,
+    InfluxDestination, JsonPaths, MetricsData, MetricsFormat, NotifyData, OutputFormat,
+    OutputRegex, OverlapPolicy, PingCheck, PluginCheck, ProcessCheck, ProcessLimits, PushedStatus,
+    ReadMessagesData, ScheduleMode, SecretsRequest, ShutdownStatus, SortKey, SystemCheck,
+    TimestampFormat, TopData, WatchCommandData, WatchMode,
+};
+use crate::user_defaults::{
+    UserDefaults, API_KEY_ENV, CONFIG_FILE_ENV, DISCOVER_ENV, NAMESPACE_ENV, NAME_ENV, PORT_ENV,
+    SERVER_ENV,
+};
+use check_mate_common::secrets::{resolve_secret, SecretsFile};
 use check_mate_common::{
-    constants::*, fetch_arg, fetch_arg_and_parse, fetch_arg_bool, fetch_arg_string,
-    format_args_list, format_text, CommandLineError,
+    constants::*, format_duration, parse_bool, parse_duration, parse_non_empty_string,
+    parse_percent, parse_size, CommandLineError,
 };
+use clap::{error::ErrorKind, ArgAction, Args, CommandFactory, Parser, Subcommand};
 
 #[derive(PartialEq, Debug)]
 pub struct Config {
     pub action: Action,
+    pub server_addresses: Vec<String>,
+    pub additional_server_addresses: Vec<Vec<String>>, // servers combined with the main one by read, list and top
+    pub discovery_domain: Option<String>, // domain with a SRV record of the server, used instead of server addresses
     pub server_port: u16,
     pub client_name: Option<String>,
+    pub client_tags: Vec<String>,
+    pub namespace: Option<String>,
+    pub all_namespaces: bool, // whether clients of all namespaces are read, listed and refreshed
+    pub tls_ca: Option<String>, // TLS is used if the CA of the server is set
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    pub api_key: Option<String>, // key identifying the client to the server, sent before other commands
     pub server_connection_backoff: Duration,
     pub server_connection_attempts: u32,
 }
 
+#[derive(Parser)]
+#[command(
+    name = "check_mate_client",
+    version = VERSION,
+    disable_version_flag = true,
+    subcommand_value_name = "ACTION",
+    subcommand_help_heading = "Actions",
+    about = "Client for CheckMate server. Watches commands and reports their statuses or queries the server.",
+    after_help = user_defaults_help(),
+)]
+struct CommandLine {
+    #[command(subcommand)]
+    action: Option<ActionCommand>,
+
+    /// Print version.
+    #[arg(short = 'v', long = "version")]
+    version: bool,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+#[derive(Args)]
+struct ConnectionArgs {
+    #[arg(
+        short = 'a',
+        long = "address",
+        value_name = "HOSTS",
+        global = true,
+        action = ArgAction::Append,
+        value_parser = parse_server_addresses,
+        help = format!("Set address of the server to connect to. Can be either an IP address or a hostname. Multiple comma-separated addresses can be specified. They will be tried in order, including all addresses a hostname resolves to, until a connection succeeds. An address can be followed by a port, e.g. monitoring:10006, overriding the port set with --port. Default is {DEFAULT_SERVER_ADDRESS}. Read, list and top accept this argument multiple times to combine statuses of independent servers, prefixing names of clients with the first address of their server."),
+    )]
+    address: Vec<String>,
+
+    #[arg(
+        short = 'p',
+        long = "port",
+        global = true,
+        help = format!("Set TCP port of the server to connect to. Default is {DEFAULT_PORT}."),
+    )]
+    port: Option<u16>,
+
+    #[arg(
+        long = "discover",
+        value_name = "DOMAIN",
+        global = true,
+        conflicts_with = "address",
+        value_parser = parse_non_empty_string,
+        help = format!("Discover the server through DNS instead of setting its address. Servers are read from the SRV record of {SERVER_DISCOVERY_SERVICE} service in the given domain, e.g. {SERVER_DISCOVERY_SERVICE}.example.com, and tried in order of their priorities and weights. The record is resolved again each time the client connects."),
+    )]
+    discover: Option<String>,
+
+    /// Set name of this client. Name is optional, but makes it easier to identify clients and allows to refresh them
+    /// by name.
+    #[arg(short = 'n', long = "name", global = true, value_parser = parse_non_empty_string)]
+    name: Option<String>,
+
+    /// Add a tag to this client. Tags can be used to group statuses when reading them. Can be specified multiple
+    /// times.
+    #[arg(short = 't', long = "tag", value_name = "TAG", global = true, value_parser = parse_non_empty_string)]
+    tags: Vec<String>,
+
+    /// Set namespace of this client, so multiple teams can share one server. Clients only see statuses of clients in
+    /// the same namespace, so reading, listing, refreshing and clearing is limited to it. Clients without a namespace
+    /// are in the default one.
+    #[arg(long = "namespace", value_name = "NAME", global = true, value_parser = parse_non_empty_string)]
+    namespace: Option<String>,
+
+    /// See clients of all namespaces instead of the own one. Names of clients outside of the default namespace are
+    /// prefixed with their namespace, e.g. team-a/backup.
+    #[arg(long = "all-namespaces", global = true)]
+    all_namespaces: bool,
+
+    /// Connect to the server over TLS, verifying its certificate with the CA certificate in FILE, given in PEM format.
+    #[arg(long = "tls-ca", value_name = "FILE", global = true)]
+    tls_ca: Option<String>,
+
+    /// Present the certificate chain in FILE, given in PEM format, to the server. Required by servers verifying
+    /// certificates of clients, which then allow only names contained in the certificate.
+    #[arg(
+        long = "tls-cert",
+        value_name = "FILE",
+        global = true,
+        requires = "tls_key"
+    )]
+    tls_cert: Option<String>,
+
+    /// Set the private key of the certificate set with --tls-cert, given in PEM format.
+    #[arg(
+        long = "tls-key",
+        value_name = "FILE",
+        global = true,
+        requires = "tls_cert"
+    )]
+    tls_key: Option<String>,
+
+    /// Authenticate to the server with an API key created with the api-key action. The server names the client after
+    /// the identity of the key, which also decides the role of the client. The key can be referenced instead of given
+    /// in plain text, e.g. env:BACKUP_API_KEY or secret:backup_api_key.
+    #[arg(long = "api-key", value_name = "KEY", global = true, value_parser = parse_non_empty_string)]
+    api_key: Option<String>,
+
+    #[arg(
+        short = 'c',
+        long = "connection-backoff",
+        value_name = "DURATION",
+        global = true,
+        value_parser = parse_duration,
+        help = format!("Set backoff time to wait before retrying after unsuccessful connection to the server. Default is {}.", format_duration(DEFAULT_CONNECTION_BACKOFF)),
+    )]
+    connection_backoff: Option<Duration>,
+
+    #[arg(
+        short = 'r',
+        long = "connection-attempts",
+        value_name = "NUMBER",
+        global = true,
+        help = format!("Set the maximum number of attempts to connect to the server. The value of 0 means infinite attempts. Default is {DEFAULT_MAXIMUM_SERVER_CONNECTION_ATTEMPTS}."),
+    )]
+    connection_attempts: Option<u32>,
+}
+
+// Only one command is parsed per process, so its size doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum ActionCommand {
+    /// Query error statuses from server.
+    Read {
+        /// Set whether client names should be printed along with their statuses.
+        #[arg(
+            short = 'i',
+            long = "include-names",
+            value_name = "BOOLEAN",
+            value_parser = parse_bool,
+            action = ArgAction::Set,
+            default_value_t = DEFAULT_INCLUDE_NAMES,
+        )]
+        include_names: bool,
+
+        /// Set format in which the statuses are printed.
+        #[arg(short = 'o', long = "output", ignore_case = true, default_value_t = OutputFormat::default())]
+        output_format: OutputFormat,
+
+        /// Keep the connection open and print status changes of all clients as they happen.
+        #[arg(long = "follow", conflicts_with = "every")]
+        follow: bool,
+
+        /// Print only statuses of clients with names matching a glob <PATTERN>, e.g. "db-*".
+        #[arg(short = 'f', long = "filter", value_name = "PATTERN")]
+        name_filter: Option<String>,
+
+        /// Print statuses of all clients, including the ones which are ok or haven't reported anything yet.
+        #[arg(long = "all")]
+        all: bool,
+
+        /// Set whether statuses are colored by their severity.
+        #[arg(long = "color", value_name = "WHEN", ignore_case = true, default_value_t = ColorChoice::default())]
+        color: ColorChoice,
+
+        /// Keep the connection open and query the statuses again on every interval.
+        #[arg(short = 'e', long = "every", value_name = "DURATION", value_parser = parse_duration)]
+        every: Option<Duration>,
+
+        /// With --every, print only statuses which have appeared (+) or disappeared (-) since the previous query.
+        #[arg(long = "diff", requires = "every")]
+        diff: bool,
+
+        /// Sort statuses instead of printing them in the order the server has collected them.
+        #[arg(long = "sort", value_name = "KEY", ignore_case = true)]
+        sort: Option<SortKey>,
+
+        /// Print statuses in groups, each preceded by a header.
+        #[arg(long = "group-by", value_name = "KEY", ignore_case = true, conflicts_with_all = ["diff", "follow"])]
+        group_by: Option<GroupBy>,
+
+        /// Print counts of ok, failing and unknown statuses after the statuses.
+        #[arg(long = "summary", conflicts_with = "diff")]
+        summary: bool,
+
+        /// Print only the counts of statuses, as with --summary.
+        #[arg(short = 'q', long = "quiet", conflicts_with_all = ["diff", "follow"])]
+        quiet: bool,
+
+        /// Print when each status was reported, either as time elapsed since then or as local time.
+        #[arg(
+            long = "timestamps",
+            value_name = "FORMAT",
+            ignore_case = true,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "relative",
+            conflicts_with = "diff",
+        )]
+        timestamps: Option<TimestampFormat>,
+    },
+
+    /// Periodically execute <COMMAND> and send its output as status to server.
+    ///
+    /// CheckMate arguments can be passed either before the command, or after it. In the latter case an additional '--'
+    /// separator is necessary to divide the command arguments and CheckMate arguments, for example:
+    /// check_mate_client watch ls -l -- -n Watcher
+    ///
+    /// On Unix, sending SIGUSR1 to the client runs the command immediately, just like a refresh from the server.
+    Watch(WatchArgs),
+
+    /// Follow a log file and report an error whenever a line matching a pattern is appended to it. The error is
+    /// cleared after a period without matching lines. Rotation of the file is handled.
+    Tail {
+        /// Path to the log file.
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+
+        /// Set regex matched against appended lines. Its first capture group (or the whole match) is the error
+        /// message, e.g. "ERROR: (.*)".
+        #[arg(short = 'e', long = "pattern", value_name = "PATTERN", value_parser = OutputRegex::parse)]
+        pattern: OutputRegex,
+
+        #[arg(
+            long = "quiet-period",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            help = format!("Set how long no matching lines have to appear to clear the error. Default is {}.", format_duration(DEFAULT_TAIL_QUIET_PERIOD)),
+        )]
+        quiet_period: Option<Duration>,
+    },
+
+    /// Periodically run a built-in check and send its result as status to server. Unlike watch, it doesn't depend on
+    /// external programs being installed or on the format of their output.
+    Check {
+        #[command(subcommand)]
+        check: CheckCommand,
+
+        #[arg(
+            short = 'w',
+            long = "interval",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            global = true,
+            help = format!("Set interval between runs of the check. Default is {}.", format_duration(DEFAULT_WATCH_INTERVAL)),
+        )]
+        interval: Option<Duration>,
+    },
+
+    /// Run many checks described in a TOML file concurrently, each of them reported as a separate client. Every
+    /// [[check]] table has a unique name and either a command to watch or a built-in check with its arguments, e.g.
+    /// builtin = ["disk", "/", "--warn", "80%"]. Optional fields are interval, mode, tags and options, which are
+    /// any other arguments of the watch or check actions. Connection arguments and tags given to this action apply
+    /// to all checks.
+    ///
+    /// On Unix, sending SIGHUP to the client reloads the file. Added checks are started, removed ones are stopped and
+    /// changed ones are restarted without dropping their connections to the server. Reloading can also be requested
+    /// through the control socket of any check.
+    Run {
+        /// Path to the file describing the checks.
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+    },
+
+    /// Send a command to a watch client started with --control-socket and print its response. The server is not
+    /// involved.
+    Control {
+        /// Path to the control socket of the client.
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Command to send.
+        #[arg(value_name = "COMMAND", ignore_case = true)]
+        command: ControlCommand,
+    },
+
+    /// Instruct the server to notify a client with a name equal to <NAME> to rerun its command immediately and update
+    /// the status.
+    Refresh {
+        /// Name of the client to refresh.
+        #[arg(value_name = "NAME")]
+        client_name: String,
+    },
+
+    /// Instruct the server to notify all its clients to rerun their commands immediately and update the statuses.
+    #[command(name = "refresh_all")]
+    RefreshAll,
+
+    /// Instruct the server to mark status of clients with a name equal to <NAME> as ok, e.g. after an incident has
+    /// been handled. Statuses set with the push action are removed entirely, which cleans up after decommissioned
+    /// clients.
+    Clear {
+        /// Name of the client to clear.
+        #[arg(value_name = "NAME")]
+        client_name: String,
+    },
+
+    /// Set status of a client with a name set by --name and exit. The status is kept by the server after
+    /// disconnecting, until another status is pushed for the same name. This allows reporting from scripts and cron
+    /// jobs without a watching client running in the background.
+    #[command(group = clap::ArgGroup::new("status").required(true))]
+    Push {
+        /// Report success.
+        #[arg(long = "ok", group = "status")]
+        ok: bool,
+
+        /// Report an error with <MESSAGE>. If the message is omitted, it is read from the standard input.
+        #[arg(long = "error", value_name = "MESSAGE", num_args = 0..=1, group = "status")]
+        error: Option<Option<String>>,
+    },
+
+    /// Run <COMMAND> once and push its status under a name set by --name, e.g. to monitor a cron job. The client is
+    /// pending while the command runs. Output of the command is passed through and the client exits with the same
+    /// exit code, so the job behaves as if it wasn't wrapped at all. For example:
+    /// check_mate_client cron-wrap -n backup -- backup.sh --full
+    #[command(name = "cron-wrap")]
+    CronWrap {
+        /// Command to run, followed by its arguments.
+        #[arg(
+            value_name = "COMMAND",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+
+    /// Check whether clients with a name equal to <NAME> are ok and exit with 0 if they are or 1 otherwise. Only the
+    /// reason of a failure is printed. The server is tried only once, unless --connection-attempts is specified, so
+    /// the action can be used directly as a health probe of a container. For example in a Dockerfile:
+    /// HEALTHCHECK CMD check_mate_client docker-health web
+    #[command(name = "docker-health")]
+    DockerHealth {
+        /// Name of the client to check.
+        #[arg(value_name = "NAME")]
+        client_name: String,
+    },
+
+    /// List all existing clients connected to the server.
+    List {
+        /// Set format in which the clients are printed.
+        #[arg(short = 'o', long = "output", ignore_case = true, default_value_t = OutputFormat::default())]
+        output_format: OutputFormat,
+    },
+
+    /// Show a live dashboard of all clients in the terminal. Clients which changed their status often recently are
+    /// marked as flapping. Selected client can be refreshed, acknowledged (cleared) or silenced in the dashboard.
+    Top {
+        #[arg(
+            short = 'e',
+            long = "every",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            help = format!("Set how often the statuses are queried. Default is {}.", format_duration(DEFAULT_TOP_INTERVAL)),
+        )]
+        interval: Option<Duration>,
+
+        /// Show only clients with names matching a glob <PATTERN>, e.g. "db-*".
+        #[arg(short = 'f', long = "filter", value_name = "PATTERN")]
+        name_filter: Option<String>,
+    },
+
+    /// Keep the connection open and raise a desktop notification whenever a client goes into error. Uses
+    /// notify-send on Linux, osascript on macOS and toast notifications on Windows.
+    Notify {
+        /// Notify only about clients with names matching a glob <PATTERN>, e.g. "db-*".
+        #[arg(short = 'f', long = "filter", value_name = "PATTERN")]
+        name_filter: Option<String>,
+
+        /// Notify also when a client recovers from an error.
+        #[arg(long = "recoveries")]
+        recoveries: bool,
+    },
+
+    /// Write SVG badges with statuses to a directory, so they can be embedded in READMEs or wikis. The overall health
+    /// is written to overall.svg and status of each client to clients/<NAME>.svg. Badges of clients which are gone are
+    /// removed.
+    Badge {
+        /// Directory to write the badges to. It's created if it doesn't exist.
+        #[arg(value_name = "DIRECTORY")]
+        directory: PathBuf,
+
+        #[arg(
+            long = "label",
+            value_name = "TEXT",
+            value_parser = parse_non_empty_string,
+            default_value = DEFAULT_BADGE_LABEL,
+            help = "Set label of the overall health badge.",
+        )]
+        label: String,
+
+        /// Write badges only for clients with names matching a glob <PATTERN>, e.g. "db-*".
+        #[arg(short = 'f', long = "filter", value_name = "PATTERN")]
+        name_filter: Option<String>,
+
+        /// Keep the connection open and write the badges again on every interval.
+        #[arg(short = 'e', long = "every", value_name = "DURATION", value_parser = parse_duration)]
+        every: Option<Duration>,
+    },
+
+    /// Keep the connection open and publish every status change to a Redis channel as a JSON object with name, status,
+    /// message, age and tags fields. Current statuses can also be mirrored into Redis keys.
+    #[cfg(feature = "redis")]
+    Redis {
+        /// URL of the Redis server, e.g. "redis://localhost:6379/0". A URL with a password can be referenced instead of
+        /// given in plain text, e.g. env:REDIS_URL.
+        #[arg(value_name = "URL")]
+        url: String,
+
+        #[arg(
+            long = "channel",
+            value_name = "CHANNEL",
+            value_parser = parse_non_empty_string,
+            default_value = DEFAULT_REDIS_CHANNEL,
+            help = "Set the channel status changes are published to.",
+        )]
+        channel: String,
+
+        #[arg(
+            long = "mirror",
+            value_name = "PREFIX",
+            help = format!("Also store the status of each client in a key named <PREFIX><NAME>. Keys are synchronized with the server every {}, so keys of clients which are gone are deleted.", format_duration(REDIS_MIRROR_SYNC_INTERVAL)),
+        )]
+        mirror_prefix: Option<String>,
+
+        /// Publish only statuses of clients with names matching a glob <PATTERN>, e.g. "db-*".
+        #[arg(short = 'f', long = "filter", value_name = "PATTERN")]
+        name_filter: Option<String>,
+    },
+
+    /// Keep the connection open and periodically send metrics to statsd or Graphite. Status of each client is a
+    /// gauge, which is 0 when it's ok and 1 when it's in error. Counters of the server, such as processed commands, are
+    /// sent as well.
+    Metrics {
+        /// Address of the metrics daemon, e.g. "localhost:8125" for statsd or "localhost:2003" for Graphite.
+        #[arg(value_name = "ADDRESS")]
+        daemon_address: String,
+
+        /// Set protocol used to send the metrics.
+        #[arg(long = "format", ignore_case = true, default_value_t = MetricsFormat::default())]
+        format: MetricsFormat,
+
+        #[arg(
+            long = "prefix",
+            value_name = "PREFIX",
+            value_parser = parse_non_empty_string,
+            default_value = DEFAULT_METRICS_PREFIX,
+            help = "Set prefix of paths of all metrics.",
+        )]
+        prefix: String,
+
+        #[arg(
+            short = 'e',
+            long = "every",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            help = format!("Set how often the metrics are sent. Default is {}.", format_duration(DEFAULT_METRICS_INTERVAL)),
+        )]
+        interval: Option<Duration>,
+
+        /// Send metrics only for clients with names matching a glob <PATTERN>, e.g. "db-*".
+        #[arg(short = 'f', long = "filter", value_name = "PATTERN")]
+        name_filter: Option<String>,
+    },
+
+    /// Keep the connection open and periodically export statuses in the line protocol of InfluxDB. Each client becomes
+    /// a point tagged with its name and group, which holds its tags, with fields for its status (0 when it's ok, 1
+    /// when it's in error), the number of seconds it has been in the status and the error message.
+    Influx {
+        /// URL of the HTTP write API, e.g. "http://localhost:8086/api/v2/write?org=ops&bucket=health", or a path of a
+        /// file to which the lines are appended.
+        #[arg(value_name = "DESTINATION", value_parser = parse_influx_destination)]
+        destination: InfluxDestination,
+
+        /// Authenticate to the write API with a <TOKEN>. The token can be referenced instead of given in plain text, e.g.
+        /// env:INFLUX_TOKEN.
+        #[arg(long = "token", value_name = "TOKEN")]
+        token: Option<String>,
+
+        #[arg(
+            long = "measurement",
+            value_name = "NAME",
+            value_parser = parse_non_empty_string,
+            default_value = DEFAULT_INFLUX_MEASUREMENT,
+            help = "Set the measurement points are written to.",
+        )]
+        measurement: String,
+
+        #[arg(
+            short = 'e',
+            long = "every",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            help = format!("Set how often the statuses are exported. Default is {}.", format_duration(DEFAULT_INFLUX_INTERVAL)),
+        )]
+        interval: Option<Duration>,
+
+        /// Export only statuses of clients with names matching a glob <PATTERN>, e.g. "db-*".
+        #[arg(short = 'f', long = "filter", value_name = "PATTERN")]
+        name_filter: Option<String>,
+    },
+
+    /// Query internal statistics of the server, such as uptime and number of connected clients.
+    Stats,
+
+    /// Print availability of clients, i.e. the percentage of time they were ok, within a window of time ending now.
+    /// It's computed from the history of statuses, which the server keeps with --history-file.
+    Availability {
+        /// Set the window of time.
+        #[arg(
+            short = 'w',
+            long = "window",
+            ignore_case = true,
+            default_value = "month"
+        )]
+        window: AvailabilityWindow,
+
+        /// Set format in which the availability is printed.
+        #[arg(short = 'o', long = "output", ignore_case = true, default_value_t = OutputFormat::default())]
+        output_format: OutputFormat,
+    },
+
+    /// Prune the history of statuses according to its retention, set with --history-max-age and --history-max-rows of
+    /// the server, and shrink its file. Print how many statuses were removed. Requires the admin role.
+    CompactHistory,
+
+    /// Print statuses recorded in the history of statuses within a range of time, for offline analysis or importing
+    /// into BI tools. Times are RFC 3339 timestamps, e.g. "2026-10-01T00:00:00Z", or durations ago, e.g. "7d". Requires
+    /// the admin role.
+    Export {
+        /// Set the start of the range. Default is the oldest status in the history.
+        #[arg(long = "from", value_name = "TIME", value_parser = parse_time)]
+        start: Option<u64>,
+
+        /// Set the end of the range. Default is now.
+        #[arg(long = "to", value_name = "TIME", value_parser = parse_time)]
+        end: Option<u64>,
+
+        /// Set format in which the statuses are printed.
+        #[arg(short = 'o', long = "output", ignore_case = true, default_value_t = ExportFormat::default())]
+        format: ExportFormat,
+    },
+
+    /// Manage API keys stored by the server, which identify clients connecting with --api-key. Requires the admin role.
+    ApiKey {
+        #[command(subcommand)]
+        command: ApiKeyCommand,
+    },
+
+    /// Encrypt secrets referenced from the config file and arguments as secret:<NAME>. The server is not involved.
+    Secrets {
+        #[command(subcommand)]
+        command: SecretsCommand,
+    },
+
+    /// Put the server into draining mode before maintenance. It stops accepting new connections, but keeps serving
+    /// the connected clients. Print how many of them are still connected. Requires the admin role.
+    Drain {
+        /// Keep the connection open and print the progress until all other clients disconnect.
+        #[arg(long = "wait")]
+        wait: bool,
+    },
+
+    /// Instruct the server to end execution.
+    Abort,
+
+    /// Print version.
+    Version,
+
+    /// Print shell completion script for the client.
+    Completions {
+        /// Shell to generate the completion script for.
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum ApiKeyCommand {
+    /// Create a key identifying clients as <IDENTITY> and print it. The key can't be printed again later.
+    Create {
+        /// Identity of clients using the key, which becomes their name.
+        #[arg(value_name = "IDENTITY", value_parser = parse_non_empty_string)]
+        identity: String,
+
+        /// Describe the key, e.g. with the host it was created for.
+        #[arg(short = 'l', long = "label", value_name = "LABEL", default_value = "")]
+        label: String,
+    },
+
+    /// Print ids, identities and labels of all keys.
+    List,
+
+    /// Change the label of a key with an id equal to <ID>.
+    Label {
+        /// Id of the key, i.e. its part before the dot.
+        #[arg(value_name = "ID")]
+        id: String,
+
+        /// New label of the key.
+        #[arg(value_name = "LABEL")]
+        label: String,
+    },
+
+    /// Revoke a key with an id equal to <ID>. Clients can no longer connect with it.
+    Revoke {
+        /// Id of the key, i.e. its part before the dot.
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SecretsCommand {
+    /// Print a new random key for the secrets file. It should be stored in a file readable only by the client.
+    GenerateKey,
+
+    /// Read a secret from the standard input and print its line of the secrets file, i.e. <NAME>=<ENCRYPTED VALUE>.
+    Encrypt {
+        /// Name of the secret, which references it as secret:<NAME>.
+        #[arg(value_name = "NAME", value_parser = parse_secret_name)]
+        name: String,
+
+        /// Encrypt with the key in <FILE>, created with generate-key.
+        #[arg(short = 'k', long = "key-file", value_name = "FILE")]
+        key_path: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CheckCommand {
+    /// Send ICMP echo requests to <HOST> and report an error if too many of them are lost or the replies are too slow.
+    Ping {
+        /// Host name or IP address to ping.
+        #[arg(value_name = "HOST")]
+        host: String,
+
+        /// Set the number of echo requests sent on every run.
+        #[arg(long = "count", value_name = "NUMBER", default_value_t = DEFAULT_PING_COUNT, value_parser = clap::value_parser!(u32).range(1..=u16::MAX as i64))]
+        count: u32,
+
+        #[arg(
+            long = "timeout",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            help = format!("Set how long to wait for each reply before considering it lost. Default is {}.", format_duration(DEFAULT_PING_TIMEOUT)),
+        )]
+        timeout: Option<Duration>,
+
+        /// Set the maximum percentage of lost packets which is still considered ok.
+        #[arg(long = "max-loss", value_name = "PERCENT", default_value_t = DEFAULT_PING_MAX_LOSS_PERCENT, value_parser = clap::value_parser!(u8).range(0..=100))]
+        max_loss_percent: u8,
+
+        /// Report an error if the average round-trip time exceeds <DURATION>.
+        #[arg(long = "max-rtt", value_name = "DURATION", value_parser = parse_duration)]
+        max_rtt: Option<Duration>,
+    },
+
+    /// Resolve <NAME> and report an error if it doesn't exist, the resolver doesn't answer or the answer doesn't
+    /// contain the expected addresses.
+    Dns {
+        /// Domain name to resolve.
+        #[arg(value_name = "NAME")]
+        domain_name: String,
+
+        /// Report an error if <ADDRESS> is not among the resolved addresses. Can be specified multiple times.
+        #[arg(long = "expect", value_name = "ADDRESS")]
+        expected_addresses: Vec<std::net::IpAddr>,
+
+        /// Query the resolver at <ADDRESS>, optionally with a port, instead of the ones configured in the system.
+        #[arg(long = "server", value_name = "ADDRESS", value_parser = DnsCheck::parse_server)]
+        server: Option<std::net::SocketAddr>,
+
+        #[arg(
+            long = "timeout",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            help = format!("Set how long to wait for the answer. Default is {}.", format_duration(DEFAULT_DNS_TIMEOUT)),
+        )]
+        timeout: Option<Duration>,
+    },
+
+    /// Report an error if the filesystem containing <PATH> is too full. Errors are prefixed with their severity, e.g.
+    /// "WARNING: /var is 85.2% full (42.6 GiB used, 7.4 GiB free)".
+    Disk {
+        /// Path on the filesystem to check.
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Report a warning if used space reaches <PERCENT>, e.g. 80%.
+        #[arg(long = "warn", value_name = "PERCENT", value_parser = parse_percent)]
+        warning_percent: Option<u8>,
+
+        /// Report a critical error if used space reaches <PERCENT>.
+        #[arg(long = "crit", value_name = "PERCENT", value_parser = parse_percent, default_value_t = DEFAULT_DISK_CRITICAL_PERCENT)]
+        critical_percent: u8,
+    },
+
+    /// Report an error if the host is overloaded. All exceeded thresholds are described in the status. Load average is
+    /// not available on Windows.
+    System {
+        /// Report an error if the 1-minute load average exceeds <LOAD>.
+        #[arg(long = "max-load", value_name = "LOAD")]
+        max_load: Option<f64>,
+
+        /// Report an error if CPU usage exceeds <PERCENT>. Usage is measured over a second.
+        #[arg(long = "max-cpu", value_name = "PERCENT", value_parser = parse_percent)]
+        max_cpu_percent: Option<u8>,
+
+        /// Report an error if memory usage exceeds <PERCENT>.
+        #[arg(long = "max-memory", value_name = "PERCENT", value_parser = parse_percent, default_value_t = DEFAULT_SYSTEM_MAX_MEMORY_PERCENT)]
+        max_memory_percent: u8,
+
+        /// Report an error if swap usage exceeds <PERCENT>.
+        #[arg(long = "max-swap", value_name = "PERCENT", value_parser = parse_percent)]
+        max_swap_percent: Option<u8>,
+    },
+
+    /// Report an error if <PATH> doesn't exist or doesn't meet the given conditions, e.g. to verify that a backup
+    /// has been created recently and is not empty.
+    File {
+        /// Path to the file or directory.
+        #[arg(value_name = "PATH")]
+        path: PathBuf,
+
+        /// Report an error if the path exists instead, e.g. for lock files left after a crash.
+        #[arg(long = "absent", conflicts_with_all = ["max_age", "min_size", "max_size"])]
+        absent: bool,
+
+        /// Report an error if the path was last modified more than <DURATION> ago.
+        #[arg(long = "max-age", value_name = "DURATION", value_parser = parse_duration)]
+        max_age: Option<Duration>,
+
+        /// Report an error if the file is smaller than <SIZE>, e.g. 1 to require a non-empty file.
+        #[arg(long = "min-size", value_name = "SIZE", value_parser = parse_size)]
+        min_size: Option<u64>,
+
+        /// Report an error if the file is larger than <SIZE>.
+        #[arg(long = "max-size", value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+
+    /// Report an error if the number of running processes with names matching a glob <PATTERN> is out of range. By
+    /// default at least one process is required.
+    Process {
+        /// Process name or glob pattern, e.g. "nginx" or "php-fpm*".
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+
+        /// Match the pattern against the whole command line instead of the process name, e.g. "*worker.py*".
+        #[arg(long = "full")]
+        full_command: bool,
+
+        /// Report an error if fewer than <NUMBER> processes are running.
+        #[arg(long = "min", value_name = "NUMBER", default_value_t = 1)]
+        min_count: usize,
+
+        /// Report an error if more than <NUMBER> processes are running. Use 0 to require that no process is running.
+        #[arg(long = "max", value_name = "NUMBER")]
+        max_count: Option<usize>,
+    },
+
+    /// Report an error if a Docker <CONTAINER> is not running or its health check reports it as unhealthy. Errors
+    /// include the exit code and the number of restarts of the container.
+    Docker {
+        /// Name or ID of the container.
+        #[arg(value_name = "CONTAINER")]
+        container: String,
+
+        #[arg(
+            long = "socket",
+            value_name = "PATH",
+            help = format!("Connect to the Docker daemon at <PATH>. Default is {}.", DEFAULT_DOCKER_SOCKET),
+        )]
+        socket: Option<PathBuf>,
+    },
+
+    /// Run an external plugin <PATH>, which reports its status, message and metrics as JSON. On each run the plugin
+    /// receives {"version": 1, "state": ...} on stdin and has to print an object like {"status": "warning",
+    /// "message": "Queue is growing", "metrics": [{"name": "latency", "value": 2.5, "unit": "s"}], "state": ...} to
+    /// stdout. Status is one of ok, warning, critical or unknown. The state is passed back to the plugin on the next
+    /// run.
+    Plugin {
+        #[arg(
+            long = "timeout",
+            value_name = "DURATION",
+            value_parser = parse_duration,
+            help = format!("Report an error if the plugin doesn't respond within <DURATION>. Default is {}.", format_duration(DEFAULT_PLUGIN_TIMEOUT)),
+        )]
+        timeout: Option<Duration>,
+
+        /// Plugin executable, followed by its arguments.
+        #[arg(
+            value_name = "PATH",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+
+    /// Run a check compiled to a WebAssembly module <PATH> in a sandbox without access to files or network. The module
+    /// receives a JSON request and returns a JSON response in the same format as plugins.
+    #[cfg(feature = "wasm")]
+    Wasm {
+        #[arg(
+            long = "fuel",
+            value_name = "NUMBER",
+            help = format!("Report an error if the module executes more than <NUMBER> instructions. Default is {}.", DEFAULT_WASM_FUEL),
+        )]
+        fuel: Option<u64>,
+
+        #[arg(
+            long = "max-memory",
+            value_name = "SIZE",
+            value_parser = parse_size,
+            help = format!("Limit memory of the module to <SIZE>, e.g. 16M. Default is {}M.", DEFAULT_WASM_MAX_MEMORY >> 20),
+        )]
+        max_memory: Option<u64>,
+
+        /// WebAssembly module, followed by arguments passed to it in the request.
+        #[arg(
+            value_name = "PATH",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+
+    /// Report an error if new entries were written to the system log since the last run, i.e. to journald on Linux or
+    /// to the Event Log on Windows. The status is cleared by the next run without new entries.
+    #[cfg(any(target_os = "linux", windows))]
+    Log {
+        /// Report entries with <PRIORITY> or more important, e.g. warning or 4. Default is err.
+        #[arg(long = "priority", value_name = "PRIORITY", value_parser = LogCheck::parse_priority)]
+        max_priority: Option<u8>,
+
+        /// Report only entries with messages matching a regex <PATTERN>.
+        #[arg(short = 'e', long = "pattern", value_name = "PATTERN", value_parser = OutputRegex::parse)]
+        pattern: Option<OutputRegex>,
+
+        /// Report only entries of a systemd unit on Linux or entries of an event log channel on Windows. Default
+        /// channel is System.
+        #[arg(long = "source", value_name = "NAME")]
+        source: Option<String>,
+    },
+
+    /// Report an error if a systemd <UNIT> is not active, with the reason of its failure.
+    #[cfg(all(target_os = "linux", feature = "systemd"))]
+    Systemd {
+        /// Name of the unit, e.g. nginx or backup.timer. Units without a suffix are services.
+        #[arg(value_name = "UNIT")]
+        unit: String,
+
+        /// Check a unit of the user service manager instead of the system one.
+        #[arg(long = "user")]
+        user: bool,
+
+        /// Treat the unit as ok when it's inactive after completing successfully, e.g. for oneshot services run by
+        /// timers.
+        #[arg(long = "allow-inactive")]
+        allow_inactive: bool,
+    },
+
+    /// Report an error if a disk <DEVICE> fails its SMART health assessment, has failing attributes or sectors
+    /// pending reallocation. Requires smartctl and usually root privileges.
+    #[cfg(feature = "smart")]
+    Smart {
+        /// Device to check, e.g. /dev/sda or /dev/nvme0.
+        #[arg(value_name = "DEVICE")]
+        device: String,
+
+        /// Type of the device passed to smartctl, e.g. sat for disks behind USB bridges or megaraid,N for disks
+        /// behind RAID controllers.
+        #[arg(long = "device-type", value_name = "TYPE")]
+        device_type: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// Command to run, followed by its arguments.
+    #[arg(
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true,
+        value_name = "COMMAND"
+    )]
+    command: Vec<String>,
+
+    #[arg(
+        short = 'w',
+        long = "interval",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        help = format!("Set interval between invocations of the watched command. Default is {}.", format_duration(DEFAULT_WATCH_INTERVAL)),
+    )]
+    interval: Option<Duration>,
+
+    /// Invoke the watched command also whenever the file or directory at <PATH> changes. Directories are watched
+    /// recursively. Can be specified multiple times.
+    #[arg(long = "watch-path", value_name = "PATH")]
+    watch_paths: Vec<PathBuf>,
+
+    /// Invoke the watched command only at startup and when a path specified with --watch-path changes, not
+    /// periodically.
+    #[arg(long = "no-timer", requires = "watch_paths")]
+    no_timer: bool,
+
+    /// Listen for control commands on a Unix socket or, on Windows, a named pipe at <PATH>. They let operators run
+    /// the command immediately, pause and resume it, reload the configuration or show the last result without
+    /// restarting the client. Use the control action to send them.
+    #[arg(long = "control-socket", value_name = "PATH")]
+    control_socket: Option<PathBuf>,
+
+    #[arg(
+        short = 'd',
+        long = "delay",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        help = format!("Set delay before the watched command is called for the first time. Default is {}.", format_duration(DEFAULT_WATCH_DELAY)),
+    )]
+    delay: Option<Duration>,
+
+    #[arg(
+        long = "jitter",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        help = format!("Add a random amount of time, up to <DURATION>, to each wait before invoking the watched command. This spreads invocations of many watchers started at the same time. Default is {}.", format_duration(DEFAULT_WATCH_JITTER)),
+    )]
+    jitter: Option<Duration>,
+
+    #[arg(
+        long = "failures-before-error",
+        value_name = "NUMBER",
+        value_parser = clap::value_parser!(u32).range(1..),
+        default_value_t = DEFAULT_FAILURES_BEFORE_ERROR,
+        help = "Set how many consecutive failures of the watched command are needed to report an error.",
+    )]
+    failures_before_error: u32,
+
+    #[arg(
+        long = "successes-before-ok",
+        value_name = "NUMBER",
+        value_parser = clap::value_parser!(u32).range(1..),
+        default_value_t = DEFAULT_SUCCESSES_BEFORE_OK,
+        help = "Set how many consecutive successes of the watched command are needed to recover from a reported error.",
+    )]
+    successes_before_ok: u32,
+
+    /// Send the status to the server only if it differs from the previous one.
+    #[arg(long = "only-changes")]
+    only_changes: bool,
+
+    /// With --only-changes, send the status anyway if it hasn't been sent for <DURATION>.
+    #[arg(long = "reconfirm", value_name = "DURATION", value_parser = parse_duration, requires = "only_changes")]
+    reconfirm_interval: Option<Duration>,
+
+    /// Send at most <NUMBER> status updates per minute. Statuses exceeding the limit are held back and only the latest
+    /// one is sent once the limit allows it, so transitions of a command flapping every few milliseconds are collapsed.
+    #[arg(long = "max-updates-per-minute", value_name = "NUMBER", value_parser = clap::value_parser!(u32).range(1..))]
+    max_updates_per_minute: Option<u32>,
+
+    /// Set what happens when a run of the watched command is requested while it is still running.
+    #[arg(long = "overlap", ignore_case = true, default_value_t = OverlapPolicy::default())]
+    overlap: OverlapPolicy,
+
+    /// Set how the interval between invocations of the watched command is measured.
+    #[arg(long = "schedule", ignore_case = true, default_value_t = ScheduleMode::default())]
+    schedule: ScheduleMode,
+
+    /// Align invocations of the watched command to wall-clock multiples of the interval, e.g. with 1m interval the
+    /// command is invoked at the top of every minute. Only the first invocation happens after the initial delay.
+    #[arg(long = "align")]
+    align: bool,
+
+    /// Set watch mode, which represents how errors are detected and reported.
+    #[arg(short = 'm', long = "mode", ignore_case = true, default_value_t = WatchMode::default())]
+    mode: WatchMode,
+
+    /// In Json mode, set path to the status within the output, e.g. ".ok" or ".checks.0.status".
+    #[arg(long = "json-status", value_name = "PATH", value_parser = JsonPaths::parse_path)]
+    json_status: Option<String>,
+
+    /// In Json mode, set path to the error message within the output, e.g. ".detail".
+    #[arg(long = "json-message", value_name = "PATH", value_parser = JsonPaths::parse_path)]
+    json_message: Option<String>,
+
+    /// Set whether the watched command should be invoked through a shell. The command is interpreted by the shell,
+    /// while its arguments are quoted and passed verbatim.
+    #[arg(
+        short = 's',
+        long = "shell",
+        value_name = "BOOLEAN",
+        value_parser = parse_bool,
+        action = ArgAction::Set,
+        default_value_t = DEFAULT_SHELL,
+    )]
+    shell: bool,
+
+    #[arg(
+        long = "shell-cmd",
+        value_name = "COMMAND",
+        value_parser = parse_non_empty_string,
+        default_value = DEFAULT_SHELL_COMMAND,
+        help = "Set shell program and its arguments used to invoke the watched command when --shell is enabled and to invoke hooks, e.g. \"bash -c\" or \"powershell -Command\".",
+    )]
+    shell_command: String,
+
+    /// Set status reported to the server when the client is interrupted or terminated.
+    #[arg(long = "shutdown-status", ignore_case = true, default_value_t = ShutdownStatus::default())]
+    shutdown_status: ShutdownStatus,
+
+    /// Set which output stream of the watched command is inspected for error messages.
+    #[arg(long = "stream", ignore_case = true, default_value_t = CapturedStream::default())]
+    stream: CapturedStream,
+
+    /// Retry the watched command up to <NUMBER> times when it fails, before reporting an error.
+    #[arg(long = "retries", value_name = "NUMBER", default_value_t = 0)]
+    retries: u32,
+
+    #[arg(
+        long = "retry-delay",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        requires = "retries",
+        help = format!("Set delay between retries of the watched command. Default is {}.", format_duration(DEFAULT_RETRY_DELAY)),
+    )]
+    retry_delay: Option<Duration>,
+
+    /// Scan output of the watched command for a regex and use its first capture group (or the whole match) as the
+    /// output. This way no match means success and the match is the error message, regardless of other output.
+    #[arg(long = "error-regex", value_name = "PATTERN", value_parser = OutputRegex::parse)]
+    error_regex: Option<OutputRegex>,
+
+    /// Decide the status of the watched command with a Rhai script at <PATH>. The script gets exit_code and output
+    /// variables and returns nothing on success, an error message or a map like #{status: "warning", message: "..."}
+    /// with status being ok, warning, critical, unknown or error.
+    #[cfg(feature = "script")]
+    #[arg(
+        long = "script",
+        value_name = "PATH",
+        value_parser = OutputScript::load,
+        conflicts_with_all = ["mode", "error_regex", "streaming"],
+    )]
+    script: Option<OutputScript>,
+
+    /// Start the watched command once and keep it running. Every line it prints is an error message, unless
+    /// --error-regex is set and doesn't match it. Errors are cleared by lines matching --ok-regex or after
+    /// --quiet-period without errors. If the command exits, an error is reported and the command is restarted after
+    /// the interval.
+    #[arg(
+        long = "streaming",
+        conflicts_with_all = ["mode", "timeout", "retries", "overlap", "schedule", "align", "watch_paths", "log_output"],
+    )]
+    streaming: bool,
+
+    /// In streaming mode, report success when the watched command prints a line matching <PATTERN>.
+    #[arg(long = "ok-regex", value_name = "PATTERN", value_parser = OutputRegex::parse, requires = "streaming")]
+    ok_regex: Option<OutputRegex>,
+
+    /// In streaming mode, report success when the watched command prints no errors for <DURATION>.
+    #[arg(long = "quiet-period", value_name = "DURATION", value_parser = parse_duration, requires = "streaming")]
+    quiet_period: Option<Duration>,
+
+    /// Report a pending status right after connecting to the server, before the first status of the watched command
+    /// is known. This way clients which haven't completed their first run can be told apart from other clients.
+    #[arg(long = "report-pending")]
+    report_pending: bool,
+
+    /// Ignore errors of the watched command for <DURATION> after the client starts, e.g. while services are still
+    /// coming up after a reboot. Combine with --report-pending to show the client as pending in the meantime.
+    #[arg(long = "grace", value_name = "DURATION", value_parser = parse_duration)]
+    grace: Option<Duration>,
+
+    /// Run the watched command on a remote machine over SSH, e.g. admin@router. With --shell the command is
+    /// interpreted by the remote shell. Only ssh has to be installed locally and nothing on the remote machine.
+    #[arg(long = "ssh", value_name = "DESTINATION", value_parser = parse_non_empty_string)]
+    ssh: Option<String>,
+
+    #[arg(
+        long = "ssh-cmd",
+        value_name = "COMMAND",
+        value_parser = parse_non_empty_string,
+        default_value = DEFAULT_SSH_COMMAND,
+        requires = "ssh",
+        help = "Set ssh program and its arguments used with --ssh, e.g. \"ssh -i key.pem -p 2222\". By default ssh never asks for passwords.",
+    )]
+    ssh_command: String,
+
+    /// Set niceness of the watched command, from -20 (highest priority) to 19 (lowest priority). Negative values
+    /// require privileges.
+    #[arg(
+        long = "nice",
+        value_name = "NUMBER",
+        value_parser = clap::value_parser!(i32).range(-20..=19),
+        allow_negative_numbers = true
+    )]
+    nice: Option<i32>,
+
+    /// Limit memory which the watched command can allocate to <SIZE>, e.g. 512M or 2G.
+    #[arg(long = "memory-limit", value_name = "SIZE", value_parser = parse_size)]
+    memory_limit: Option<u64>,
+
+    /// Run the watched command as user <NAME>. Usually requires running the client as root.
+    #[arg(long = "user", value_name = "NAME", value_parser = parse_non_empty_string)]
+    user: Option<String>,
+
+    /// Append full output of each invocation of the watched command to the file at <PATH>, along with a timestamp and
+    /// the exit code. This helps debugging, since the status sent to the server is only a summary.
+    #[arg(long = "log-output", value_name = "PATH")]
+    log_output: Option<PathBuf>,
+
+    /// Run <COMMAND> through the shell when an error is reported, e.g. to restart a failing service. The error message
+    /// is passed in CHECK_MATE_MESSAGE environment variable.
+    #[arg(long = "on-error", value_name = "COMMAND", value_parser = parse_non_empty_string)]
+    on_error: Option<String>,
+
+    /// Run <COMMAND> through the shell when the reported status changes from error to success.
+    #[arg(long = "on-recover", value_name = "COMMAND", value_parser = parse_non_empty_string)]
+    on_recover: Option<String>,
+
+    /// Kill the watched command and report an error if it runs longer than <DURATION>.
+    #[arg(long = "timeout", value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+}
+
+fn user_defaults_help() -> String {
+    format!(
+        "Default values of connection arguments can be changed with config files and environment variables. Config \
+        files are read from /etc/check_mate/client.toml and then from check_mate/client.toml in user's config \
+        directory, unless a different path is set in {CONFIG_FILE_ENV} environment variable. Later sources override \
+        earlier ones and command line arguments override all of them. Supported config file keys are address, discover, \
+        port, name, namespace, connection_backoff, connection_attempts, tls_ca, tls_cert, tls_key, api_key, secrets_file and secrets_key_file. Supported environment variables are \
+        {SERVER_ENV}, {DISCOVER_ENV}, {PORT_ENV}, {NAME_ENV}, {NAMESPACE_ENV} and {API_KEY_ENV}. A default discovery domain takes \
+        precedence over a default address, but not over an address given on the command line."
+    )
+}
+
+fn parse_secret_name(name: &str) -> Result<String, String> {
+    if name.is_empty() || name.contains(['=', '#']) || name.trim() != name {
+        Err(
+            "name must be non-empty and can't contain '=', '#' or surrounding whitespace"
+                .to_owned(),
+        )
+    } else {
+        Ok(name.to_owned())
+    }
+}
+
+fn parse_server_addresses(addresses: &str) -> Result<String, String> {
+    if split_server_addresses(addresses).is_empty() {
+        Err("at least one server address must be specified".to_owned())
+    } else {
+        Ok(addresses.to_owned())
+    }
+}
+
+fn split_server_addresses(addresses: &str) -> Vec<String> {
+    addresses
+        .split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
 impl Config {
-    fn parse_action<T>(args: &mut T) -> Result<Action, CommandLineError>
-    where
-        T: Iterator<Item = String>,
-    {
-        let action = fetch_arg(
-            args,
-            CommandLineError::NoValueSpecified("action".to_owned(), "binary name".to_owned()),
-        )?;
-        let action = match action.as_ref() {
-            "read" => Action::ReadMessages(DEFAULT_INCLUDE_NAMES),
-            "watch" => {
-                let command = fetch_arg(
-                    args,
-                    CommandLineError::NoValueSpecified("command to run".to_owned(), action),
-                )?;
-                let mut command_args = Vec::new();
-                for arg in args.by_ref() {
-                    if arg != "--" {
-                        command_args.push(arg);
-                    } else {
-                        break; // end of watch args
-                    }
-                }
-                Action::WatchCommand(WatchCommandData::new(command, command_args))
-            }
-            "refresh" => {
-                let name = fetch_arg(
-                    args,
-                    CommandLineError::NoValueSpecified("client name".to_owned(), action),
-                )?;
-                Action::RefreshClientByName(name)
-            }
-            "refresh_all" => Action::RefreshAllClients,
-            "list" => Action::ListClients,
-            "abort" => Action::Abort,
-            "help" | "-h" => Action::Help,
-            "version" | "-v" => Action::Version,
-            _ => return Err(CommandLineError::InvalidValue("action".into(), action)),
+    // Watch action used to require CheckMate arguments after the command, separated with '--'. Clap expects
+    // the opposite order, so arguments in the old format are reordered before parsing.
+    fn reorder_watch_args(mut args: Vec<String>) -> Vec<String> {
+        let is_old_format = args.first().is_some_and(|arg| arg == "watch")
+            && args.get(1).is_some_and(|arg| !arg.starts_with('-'));
+        if !is_old_format {
+            return args;
+        }
+
+        let separator_index = args.iter().position(|arg| arg == "--");
+        let checkmate_args = match separator_index {
+            Some(index) => args.split_off(index + 1),
+            None => Vec::new(),
         };
-        Ok(action)
+        if separator_index.is_some() {
+            args.pop(); // remove the separator
+        }
+        let command_and_args = args.split_off(1);
+
+        args.extend(checkmate_args);
+        args.push("--".to_owned());
+        args.extend(command_and_args);
+        args
     }
 
-    fn parse_extra_args<T: Iterator<Item = String>>(
-        &mut self,
-        args: &mut T,
-    ) -> Result<(), CommandLineError> {
-        while let Some(arg) = args.next() {
-            match arg.as_ref() {
-                "-p" => {
-                    self.server_port = fetch_arg_and_parse(
-                        args,
-                        || CommandLineError::NoValueSpecified("port".into(), arg.clone()),
-                        |value| CommandLineError::InvalidValue("port".into(), value.into()),
-                    )?;
+    fn create_action(command_line: &mut CommandLine) -> Result<Action, clap::Error> {
+        if command_line.version {
+            return Ok(Action::Version);
+        }
+
+        let action_command = match command_line.action.take() {
+            Some(x) => x,
+            None => {
+                return Err(CommandLine::command()
+                    .error(ErrorKind::MissingSubcommand, "an action must be specified"))
+            }
+        };
+        let action = match action_command {
+            ActionCommand::Read {
+                include_names,
+                output_format,
+                follow,
+                every,
+                diff,
+                name_filter,
+                all,
+                color,
+                sort,
+                group_by,
+                summary,
+                quiet,
+                timestamps,
+            } => {
+                if diff && output_format == OutputFormat::Json {
+                    return Err(CommandLine::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--diff cannot be used with JSON output",
+                    ));
+                }
+                if group_by.is_some() && output_format == OutputFormat::Json {
+                    return Err(CommandLine::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--group-by cannot be used with JSON output",
+                    ));
+                }
+                if (summary || quiet) && output_format == OutputFormat::Json {
+                    return Err(CommandLine::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--summary and --quiet cannot be used with JSON output",
+                    ));
+                }
+                if timestamps.is_some() && output_format == OutputFormat::Json {
+                    return Err(CommandLine::command().error(
+                        ErrorKind::ArgumentConflict,
+                        "--timestamps cannot be used with JSON output, which always contains the age",
+                    ));
                 }
-                "-n" => {
-                    self.client_name = Some(fetch_arg_string(
-                        args,
-                        || CommandLineError::NoValueSpecified("client name".into(), arg.clone()),
-                        || CommandLineError::NoValueSpecified("client name".into(), arg.clone()),
-                    )?);
+                Action::ReadMessages(ReadMessagesData {
+                    include_names,
+                    output_format,
+                    follow,
+                    every,
+                    diff,
+                    name_filter,
+                    all,
+                    color,
+                    sort,
+                    group_by,
+                    summary,
+                    quiet,
+                    timestamps,
+                })
+            }
+            ActionCommand::Watch(watch_args) => {
+                let mut command = watch_args.command.into_iter();
+                let mut data = WatchCommandData::new(
+                    command.next().expect("Clap should require the command"),
+                    command.collect(),
+                );
+                if let Some(interval) = watch_args.interval {
+                    data.interval = interval;
+                }
+                data.watch_paths = watch_args.watch_paths;
+                data.no_timer = watch_args.no_timer;
+                data.control_socket = watch_args.control_socket;
+                if let Some(delay) = watch_args.delay {
+                    data.delay = delay;
                 }
-                "-i" => {
-                    let include_names = match self.action {
-                        Action::ReadMessages(ref mut include_names) => include_names,
-                        _ => return Err(CommandLineError::InvalidArgument(arg)),
-                    };
-                    *include_names = fetch_arg_bool(
-                        args,
-                        || {
-                            CommandLineError::NoValueSpecified(
-                                "a boolean value".into(),
-                                arg.clone(),
-                            )
-                        },
-                        |value| {
-                            CommandLineError::InvalidValue("include names".into(), value.into())
-                        },
-                    )?;
+                if let Some(jitter) = watch_args.jitter {
+                    data.jitter = jitter;
+                }
+                data.failures_before_error = watch_args.failures_before_error;
+                data.successes_before_ok = watch_args.successes_before_ok;
+                data.only_changes = watch_args.only_changes;
+                data.reconfirm_interval = watch_args.reconfirm_interval;
+                data.max_updates_per_minute = watch_args.max_updates_per_minute;
+                data.overlap = watch_args.overlap;
+                data.schedule = watch_args.schedule;
+                data.align = watch_args.align;
+                data.mode = watch_args.mode;
+                match (data.mode, watch_args.json_status) {
+                    (WatchMode::Json, Some(status)) => {
+                        data.json_paths = JsonPaths {
+                            status,
+                            message: watch_args.json_message,
+                        };
+                    }
+                    (WatchMode::Json, None) => {
+                        return Err(CommandLine::command().error(
+                            ErrorKind::MissingRequiredArgument,
+                            "--json-status is required in Json mode",
+                        ));
+                    }
+                    (_, status) => {
+                        if status.is_some() || watch_args.json_message.is_some() {
+                            return Err(CommandLine::command().error(
+                                ErrorKind::ArgumentConflict,
+                                "--json-status and --json-message can only be used in Json mode",
+                            ));
+                        }
+                    }
                 }
-                "-w" => {
-                    let data = match self.action {
-                        Action::WatchCommand(ref mut data) => data,
-                        _ => return Err(CommandLineError::InvalidArgument(arg)),
-                    };
-                    let interval: u64 = fetch_arg_and_parse(
-                        args,
-                        || CommandLineError::NoValueSpecified("watch interval".into(), arg.clone()),
-                        |value| {
-                            CommandLineError::InvalidValue("watch interval".into(), value.into())
-                        },
-                    )?;
-                    data.interval = Duration::from_millis(interval);
+                data.shell = watch_args.shell;
+                data.shell_command = watch_args.shell_command;
+                data.shutdown_status = watch_args.shutdown_status;
+                data.stream = watch_args.stream;
+                data.error_regex = watch_args.error_regex;
+                #[cfg(feature = "script")]
+                {
+                    data.script = watch_args.script;
                 }
-                "-d" => {
-                    let data = match self.action {
-                        Action::WatchCommand(ref mut data) => data,
-                        _ => return Err(CommandLineError::InvalidArgument(arg)),
-                    };
-                    let delay: u64 = fetch_arg_and_parse(
-                        args,
-                        || CommandLineError::NoValueSpecified("initial delay".into(), arg.clone()),
-                        |value| {
-                            CommandLineError::InvalidValue("initial delay".into(), value.into())
-                        },
-                    )?;
-                    data.delay = Duration::from_millis(delay);
+                data.timeout = watch_args.timeout;
+                data.retries = watch_args.retries;
+                if let Some(retry_delay) = watch_args.retry_delay {
+                    data.retry_delay = retry_delay;
                 }
-                "-c" => {
-                    let duration: u64 = fetch_arg_and_parse(
-                        args,
-                        || {
-                            CommandLineError::NoValueSpecified(
-                                "connection backoff".into(),
-                                arg.clone(),
-                            )
-                        },
-                        |value| {
-                            CommandLineError::InvalidValue(
-                                "connection backoff".into(),
-                                value.into(),
-                            )
-                        },
-                    )?;
-                    self.server_connection_backoff = Duration::from_millis(duration);
+                if watch_args.streaming
+                    && watch_args.ok_regex.is_none()
+                    && watch_args.quiet_period.is_none()
+                {
+                    return Err(CommandLine::command().error(
+                        ErrorKind::MissingRequiredArgument,
+                        "--streaming requires --ok-regex or --quiet-period, so errors can be cleared",
+                    ));
                 }
-                "-r" => {
-                    self.server_connection_attempts = fetch_arg_and_parse(
-                        args,
-                        || {
-                            CommandLineError::NoValueSpecified(
-                                "number of connection attempts".into(),
-                                arg.clone(),
-                            )
-                        },
-                        |value| {
-                            CommandLineError::InvalidValue(
-                                "number of connection attempts".into(),
-                                value.into(),
-                            )
-                        },
-                    )?;
+                data.limits = ProcessLimits {
+                    nice: watch_args.nice,
+                    memory_limit: watch_args.memory_limit,
+                    user: watch_args.user,
+                };
+                data.ssh = watch_args.ssh;
+                data.ssh_command = watch_args.ssh_command;
+                data.report_pending = watch_args.report_pending;
+                data.grace = watch_args.grace;
+                data.log_output = watch_args.log_output;
+                data.on_error = watch_args.on_error;
+                data.on_recover = watch_args.on_recover;
+                data.streaming = watch_args.streaming;
+                data.ok_regex = watch_args.ok_regex;
+                data.quiet_period = watch_args.quiet_period;
+                Action::WatchCommand(data)
+            }
+            ActionCommand::Tail {
+                path,
+                pattern,
+                quiet_period,
+            } => {
+                let mut data = WatchCommandData::new(String::new(), Vec::new());
+                data.streaming = true;
+                data.tail_path = Some(path);
+                data.error_regex = Some(pattern);
+                data.quiet_period = Some(quiet_period.unwrap_or(DEFAULT_TAIL_QUIET_PERIOD));
+                Action::WatchCommand(data)
+            }
+            ActionCommand::Check { check, interval } => {
+                let check = match check {
+                    CheckCommand::Ping {
+                        host,
+                        count,
+                        timeout,
+                        max_loss_percent,
+                        max_rtt,
+                    } => BuiltinCheck::Ping(PingCheck {
+                        count,
+                        timeout: timeout.unwrap_or(DEFAULT_PING_TIMEOUT),
+                        max_loss_percent,
+                        max_rtt,
+                        ..PingCheck::new(host)
+                    }),
+                    CheckCommand::Dns {
+                        domain_name,
+                        expected_addresses,
+                        server,
+                        timeout,
+                    } => BuiltinCheck::Dns(DnsCheck {
+                        expected_addresses,
+                        server,
+                        timeout: timeout.unwrap_or(DEFAULT_DNS_TIMEOUT),
+                        ..DnsCheck::new(domain_name)
+                    }),
+                    CheckCommand::Disk {
+                        path,
+                        warning_percent,
+                        critical_percent,
+                    } => BuiltinCheck::Disk(DiskCheck {
+                        warning_percent,
+                        critical_percent: Some(critical_percent),
+                        ..DiskCheck::new(path)
+                    }),
+                    CheckCommand::System {
+                        max_load,
+                        max_cpu_percent,
+                        max_memory_percent,
+                        max_swap_percent,
+                    } => BuiltinCheck::System(SystemCheck {
+                        max_load,
+                        max_cpu_percent,
+                        max_memory_percent: Some(max_memory_percent),
+                        max_swap_percent,
+                    }),
+                    CheckCommand::File {
+                        path,
+                        absent,
+                        max_age,
+                        min_size,
+                        max_size,
+                    } => BuiltinCheck::File(FileCheck {
+                        absent,
+                        max_age,
+                        min_size,
+                        max_size,
+                        ..FileCheck::new(path)
+                    }),
+                    CheckCommand::Process {
+                        pattern,
+                        full_command,
+                        min_count,
+                        max_count,
+                    } => BuiltinCheck::Process(ProcessCheck {
+                        full_command,
+                        // Allowing at most 0 processes implies that none are required
+                        min_count: min_count.min(max_count.unwrap_or(usize::MAX)),
+                        max_count,
+                        ..ProcessCheck::new(pattern)
+                    }),
+                    CheckCommand::Plugin {
+                        timeout,
+                        mut command,
+                    } => {
+                        let args = command.split_off(1);
+                        BuiltinCheck::Plugin(PluginCheck::new(
+                            PathBuf::from(command.remove(0)),
+                            args,
+                            timeout.unwrap_or(DEFAULT_PLUGIN_TIMEOUT),
+                        ))
+                    }
+                    #[cfg(feature = "wasm")]
+                    CheckCommand::Wasm {
+                        fuel,
+                        max_memory,
+                        mut command,
+                    } => {
+                        let args = command.split_off(1);
+                        let mut check = WasmCheck::new(PathBuf::from(command.remove(0)), args);
+                        if let Some(fuel) = fuel {
+                            check.fuel = fuel;
+                        }
+                        if let Some(max_memory) = max_memory {
+                            check.max_memory = max_memory;
+                        }
+                        BuiltinCheck::Wasm(check)
+                    }
+                    CheckCommand::Docker { container, socket } => {
+                        let mut check = DockerCheck::new(container);
+                        if let Some(socket) = socket {
+                            check.socket = socket;
+                        }
+                        BuiltinCheck::Docker(check)
+                    }
+                    #[cfg(any(target_os = "linux", windows))]
+                    CheckCommand::Log {
+                        max_priority,
+                        pattern,
+                        source,
+                    } => {
+                        let mut check = LogCheck::default();
+                        check.pattern = pattern;
+                        check.source = source;
+                        if let Some(max_priority) = max_priority {
+                            check.max_priority = max_priority;
+                        }
+                        BuiltinCheck::Log(check)
+                    }
+                    #[cfg(all(target_os = "linux", feature = "systemd"))]
+                    CheckCommand::Systemd {
+                        unit,
+                        user,
+                        allow_inactive,
+                    } => BuiltinCheck::Systemd(SystemdCheck {
+                        user,
+                        allow_inactive,
+                        ..SystemdCheck::new(unit)
+                    }),
+                    #[cfg(feature = "smart")]
+                    CheckCommand::Smart {
+                        device,
+                        device_type,
+                    } => BuiltinCheck::Smart(SmartCheck {
+                        device_type,
+                        ..SmartCheck::new(device)
+                    }),
+                };
+                let mut data = WatchCommandData::new(String::new(), Vec::new());
+                data.check = Some(check);
+                if let Some(interval) = interval {
+                    data.interval = interval;
                 }
-                "-m" => {
-                    let data = match self.action {
-                        Action::WatchCommand(ref mut data) => data,
-                        _ => return Err(CommandLineError::InvalidArgument(arg)),
-                    };
-                    data.mode = fetch_arg_and_parse(
-                        args,
-                        || CommandLineError::NoValueSpecified("watch mode".into(), arg.clone()),
-                        |value| CommandLineError::InvalidValue("watch mode".into(), value.into()),
-                    )?;
+                Action::WatchCommand(data)
+            }
+            ActionCommand::Refresh { client_name } => Action::RefreshClientByName(client_name),
+            ActionCommand::RefreshAll => Action::RefreshAllClients,
+            ActionCommand::CronWrap { mut command } => {
+                let command_args = command.split_off(1);
+                Action::CronWrap(CronWrapData {
+                    command: command.remove(0),
+                    command_args,
+                })
+            }
+            ActionCommand::Clear { client_name } => Action::ClearClientByName(client_name),
+            ActionCommand::DockerHealth { client_name } => Action::DockerHealth(client_name),
+            ActionCommand::Push { ok: true, .. } => Action::PushStatus(PushedStatus::Ok),
+            ActionCommand::Push { error, .. } => match error.flatten() {
+                Some(message) => Action::PushStatus(PushedStatus::Error(message)),
+                None => Action::PushStatus(PushedStatus::ErrorFromStdin),
+            },
+            ActionCommand::List { output_format } => Action::ListClients(output_format),
+            ActionCommand::Top {
+                interval,
+                name_filter,
+            } => Action::Top(TopData {
+                interval: interval.unwrap_or(DEFAULT_TOP_INTERVAL),
+                name_filter,
+            }),
+            ActionCommand::Notify {
+                name_filter,
+                recoveries,
+            } => Action::Notify(NotifyData {
+                name_filter,
+                recoveries,
+            }),
+            ActionCommand::Badge {
+                directory,
+                label,
+                name_filter,
+                every,
+            } => {
+                let mut data = BadgeData::new(directory);
+                data.label = label;
+                data.name_filter = name_filter;
+                data.every = every;
+                Action::WriteBadges(data)
+            }
+            #[cfg(feature = "redis")]
+            ActionCommand::Redis {
+                url,
+                channel,
+                mirror_prefix,
+                name_filter,
+            } => {
+                let mut data = RedisData::new(url);
+                data.channel = channel;
+                data.mirror_prefix = mirror_prefix;
+                data.name_filter = name_filter;
+                Action::BridgeToRedis(data)
+            }
+            ActionCommand::Metrics {
+                daemon_address,
+                format,
+                prefix,
+                interval,
+                name_filter,
+            } => {
+                let mut data = MetricsData::new(daemon_address);
+                data.format = format;
+                data.prefix = prefix;
+                data.interval = interval.unwrap_or(DEFAULT_METRICS_INTERVAL);
+                data.name_filter = name_filter;
+                Action::EmitMetrics(data)
+            }
+            ActionCommand::Influx {
+                destination,
+                token,
+                measurement,
+                interval,
+                name_filter,
+            } => {
+                let mut data = InfluxData::new(destination);
+                data.token = token;
+                data.measurement = measurement;
+                data.interval = interval.unwrap_or(DEFAULT_INFLUX_INTERVAL);
+                data.name_filter = name_filter;
+                Action::ExportToInflux(data)
+            }
+            ActionCommand::Run { path } => Action::RunChecks(path),
+            ActionCommand::Control { path, command } => {
+                Action::Control(ControlData { path, command })
+            }
+            ActionCommand::Stats => Action::GetServerStatistics,
+            ActionCommand::Availability {
+                window,
+                output_format,
+            } => Action::GetAvailability(window, output_format),
+            ActionCommand::CompactHistory => Action::CompactHistory,
+            ActionCommand::Export { start, end, format } => Action::ExportHistory(ExportData {
+                start: start.unwrap_or(0),
+                end,
+                format,
+            }),
+            ActionCommand::ApiKey { command } => Action::ManageApiKeys(match command {
+                ApiKeyCommand::Create { identity, label } => {
+                    ApiKeyRequest::Create { identity, label }
                 }
-                "-s" => {
-                    let shell = match self.action {
-                        Action::WatchCommand(ref mut data) => &mut data.shell,
-                        _ => return Err(CommandLineError::InvalidArgument(arg)),
-                    };
-                    *shell = fetch_arg_bool(
-                        args,
-                        || {
-                            CommandLineError::NoValueSpecified(
-                                "a boolean value".into(),
-                                arg.clone(),
-                            )
-                        },
-                        |value| CommandLineError::InvalidValue("shell".into(), value.into()),
-                    )?;
+                ApiKeyCommand::List => ApiKeyRequest::List,
+                ApiKeyCommand::Label { id, label } => ApiKeyRequest::Label { id, label },
+                ApiKeyCommand::Revoke { id } => ApiKeyRequest::Revoke(id),
+            }),
+            ActionCommand::Secrets { command } => Action::Secrets(match command {
+                SecretsCommand::GenerateKey => SecretsRequest::GenerateKey,
+                SecretsCommand::Encrypt { name, key_path } => {
+                    SecretsRequest::Encrypt { name, key_path }
                 }
-                _ => return Err(CommandLineError::InvalidArgument(arg)),
+            }),
+            ActionCommand::Drain { wait } => Action::Drain(wait),
+            ActionCommand::Abort => Action::Abort,
+            ActionCommand::Version => Action::Version,
+            ActionCommand::Completions { shell } => Action::Completions(shell),
+        };
+        Ok(action)
+    }
+
+    fn apply_user_defaults(&mut self, defaults: &UserDefaults) -> Result<(), CommandLineError> {
+        if let Some(ref addresses) = defaults.address {
+            if parse_server_addresses(addresses).is_err() {
+                return Err(CommandLineError::InvalidValue(
+                    "server address".into(),
+                    addresses.clone(),
+                ));
+            }
+            self.server_addresses = split_server_addresses(addresses);
+        }
+        if let Some(ref domain) = defaults.discover {
+            if domain.is_empty() {
+                return Err(CommandLineError::InvalidValue(
+                    "discovery domain".into(),
+                    domain.clone(),
+                ));
+            }
+            self.discovery_domain = Some(domain.clone());
+        }
+        if let Some(port) = defaults.port {
+            self.server_port = port;
+        }
+        if let Some(ref name) = defaults.name {
+            if name.is_empty() {
+                return Err(CommandLineError::InvalidValue("client name".into(), name.clone()));
+            }
+            self.client_name = Some(name.clone());
+        }
+        if let Some(ref namespace) = defaults.namespace {
+            if namespace.is_empty() {
+                return Err(CommandLineError::InvalidValue(
+                    "namespace".into(),
+                    namespace.clone(),
+                ));
+            }
+            self.namespace = Some(namespace.clone());
+        }
+        if let Some(backoff) = defaults.connection_backoff {
+            self.server_connection_backoff = backoff;
+        }
+        if let Some(attempts) = defaults.connection_attempts {
+            self.server_connection_attempts = attempts;
+        }
+        if let Some(ref ca) = defaults.tls_ca {
+            self.tls_ca = Some(ca.clone());
+        }
+        if let Some(ref certificate) = defaults.tls_cert {
+            self.tls_cert = Some(certificate.clone());
+        }
+        if let Some(ref key) = defaults.tls_key {
+            self.tls_key = Some(key.clone());
+        }
+        if let Some(ref key) = defaults.api_key {
+            if key.is_empty() {
+                return Err(CommandLineError::InvalidValue(
+                    "API key".into(),
+                    key.clone(),
+                ));
             }
+            self.api_key = Some(key.clone());
         }
         Ok(())
     }
 
-    pub fn parse<T>(mut args: T) -> Result<Config, CommandLineError>
-    where
-        T: Iterator<Item = String>,
-    {
-        let mut config = Config {
-            action: Config::parse_action(&mut args)?,
-            ..Default::default()
+    // Secrets are resolved after all sources are applied, so any of them can reference a secret
+    fn resolve_secrets(&mut self, defaults: &UserDefaults) -> Result<(), String> {
+        let secrets_file = match (&defaults.secrets_file, &defaults.secrets_key_file) {
+            (Some(path), Some(key_path)) => Some(SecretsFile {
+                path: path.clone(),
+                key_path: key_path.clone(),
+            }),
+            (None, None) => None,
+            _ => return Err("secrets_file and secrets_key_file must be set together".to_owned()),
         };
-        if !matches!(config.action, Action::Help | Action::Version) {
-            // Help action doesn't need any more arguments, just print help and exit
-            config.parse_extra_args(&mut args)?;
+        let resolve = |value: &str| {
+            resolve_secret(value, secrets_file.as_ref(), |name| {
+                std::env::var(name).ok()
+            })
+        };
+
+        if let Some(key) = self.api_key.take() {
+            self.api_key = Some(resolve(&key)?);
         }
-        Ok(config)
+        match self.action {
+            Action::ExportToInflux(ref mut data) => {
+                if let Some(token) = data.token.take() {
+                    data.token = Some(resolve(&token)?);
+                }
+            }
+            #[cfg(feature = "redis")]
+            Action::BridgeToRedis(ref mut data) => data.url = resolve(&data.url)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn apply_connection_args(&mut self, args: ConnectionArgs) {
+        let mut servers = args.address.iter().map(|x| split_server_addresses(x));
+        if let Some(addresses) = servers.next() {
+            self.server_addresses = addresses;
+            self.additional_server_addresses = servers.collect();
+            self.discovery_domain = None;
+        }
+        if let Some(domain) = args.discover {
+            self.discovery_domain = Some(domain);
+        }
+        if let Some(port) = args.port {
+            self.server_port = port;
+        }
+        if let Some(name) = args.name {
+            self.client_name = Some(name);
+        }
+        if !args.tags.is_empty() {
+            self.client_tags = args.tags;
+        }
+        if let Some(namespace) = args.namespace {
+            self.namespace = Some(namespace);
+        }
+        self.all_namespaces = args.all_namespaces;
+        if let Some(ca) = args.tls_ca {
+            self.tls_ca = Some(ca);
+        }
+        if let Some(certificate) = args.tls_cert {
+            self.tls_cert = Some(certificate);
+        }
+        if let Some(key) = args.tls_key {
+            self.tls_key = Some(key);
+        }
+        if let Some(key) = args.api_key {
+            self.api_key = Some(key);
+        }
+        if let Some(backoff) = args.connection_backoff {
+            self.server_connection_backoff = backoff;
+        }
+        if let Some(attempts) = args.connection_attempts {
+            self.server_connection_attempts = attempts;
+        }
+    }
+
+    #[cfg(test)]
+    pub fn parse<T>(args: T) -> Result<Config, clap::Error>
+    where
+        T: Iterator<Item = String>,
+    {
+        Self::parse_with_defaults(args, &UserDefaults::default())
+    }
+
+    pub fn parse_with_defaults<T>(args: T, defaults: &UserDefaults) -> Result<Config, clap::Error>
+    where
+        T: Iterator<Item = String>,
+    {
+        let args = Self::reorder_watch_args(args.collect());
+        let binary_name = std::iter::once("check_mate_client".to_owned());
+        let mut command_line = CommandLine::try_parse_from(binary_name.chain(args))?;
+
+        let mut config = Config {
+            action: Self::create_action(&mut command_line)?,
+            ..Default::default()
+        };
+        if matches!(config.action, Action::DockerHealth(_)) {
+            // Health probes must answer quickly, so an unreachable server is reported right away
+            config.server_connection_attempts = 1;
+        }
+        config
+            .apply_user_defaults(defaults)
+            .map_err(|err| CommandLine::command().error(ErrorKind::InvalidValue, err))?;
+        config.apply_connection_args(command_line.connection);
+        config
+            .resolve_secrets(defaults)
+            .map_err(|err| CommandLine::command().error(ErrorKind::InvalidValue, err))?;
+        let action_name = match config.action {
+            Action::PushStatus(_) => Some("push"),
+            Action::CronWrap(_) => Some("cron-wrap"),
+            _ => None,
+        };
+        if let (Some(action_name), None) = (action_name, &config.client_name) {
+            return Err(CommandLine::command().error(
+                ErrorKind::MissingRequiredArgument,
+                format!("{action_name} requires a client name set with --name"),
+            ));
+        }
+        let can_combine_servers = matches!(
+            config.action,
+            Action::ReadMessages(_) | Action::ListClients(_) | Action::Top(_)
+        );
+        if !config.additional_server_addresses.is_empty() && !can_combine_servers {
+            return Err(CommandLine::command().error(
+                ErrorKind::ArgumentConflict,
+                "only read, list and top can combine multiple servers",
+            ));
+        }
+        Ok(config)
+    }
+
+    // Only the first line of the error is meaningful outside of the command line, the rest is a usage hint
+    pub fn describe_parse_error(err: clap::Error) -> String {
+        let rendered = err.render().to_string();
+        let line = rendered.lines().next().unwrap_or_default();
+        line.strip_prefix("error: ").unwrap_or(line).to_owned()
+    }
+
+    pub fn print_completions(shell: clap_complete::Shell) {
+        let mut command = CommandLine::command();
+        clap_complete::generate(shell, &mut command, "check_mate_client", &mut std::io::stdout());
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            action: Action::Abort,
+            server_addresses: vec![DEFAULT_SERVER_ADDRESS.to_owned()],
+            additional_server_addresses: Vec::new(),
+            discovery_domain: None,
+            server_port: DEFAULT_PORT,
+            client_name: None,
+            client_tags: Vec::new(),
+            namespace: None,
+            all_namespaces: false,
+            tls_ca: None,
+            tls_cert: None,
+            tls_key: None,
+            api_key: None,
+            server_connection_backoff: DEFAULT_CONNECTION_BACKOFF,
+            server_connection_attempts: DEFAULT_MAXIMUM_SERVER_CONNECTION_ATTEMPTS,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
+mod tests {
+    use super::*;
+
+    fn to_owned_string_iter(string_slices: &[&str]) -> <Vec<String> as IntoIterator>::IntoIter {
+        let vector: Vec<String> = string_slices
+            .iter()
+            .map(|string_slice| string_slice.to_string())
+            .collect();
+        vector.into_iter()
+    }
+
+    fn parse_error_kind(args: &[&str]) -> ErrorKind {
+        let config = Config::parse(to_owned_string_iter(args));
+        config.expect_err("Parsing should not succeed").kind()
+    }
+
+    #[test]
+    fn read_action_is_parsed() {
+        let args = ["read"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut expected = Config::default();
+        expected.action = Action::ReadMessages(ReadMessagesData::default());
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn read_action_with_include_names_argument_is_parsed() {
+        fn run(include_names: &str, include_names_bool: bool) {
+            let args = ["read", "-i", include_names];
+            let config = Config::parse(to_owned_string_iter(&args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut expected = Config::default();
+            expected.action = Action::ReadMessages(ReadMessagesData {
+                include_names: include_names_bool,
+                ..Default::default()
+            });
+            assert_eq!(config, expected);
+        }
+        run("0", false);
+        run("false", false);
+        run("1", true);
+        run("true", true);
+    }
+
+    #[test]
+    fn read_action_with_invalid_include_names_argument_should_fail() {
+        fn run(include_names: &str) {
+            let args = ["read", "-i", include_names];
+            assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        }
+        run("aa");
+        run("");
+        run("1.");
+        run("1 .");
+    }
+
+    #[test]
+    fn watch_action_is_parsed() {
+        let args = ["watch", "whoami"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut expected = Config::default();
+        expected.action =
+            Action::WatchCommand(WatchCommandData::new("whoami".to_string(), Vec::new()));
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn watch_action_with_command_with_no_args_is_parsed() {
+        let args = ["watch", "whoami"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut expected = Config::default();
+        expected.action =
+            Action::WatchCommand(WatchCommandData::new("whoami".to_string(), Vec::new()));
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn watch_action_with_args_is_parsed() {
+        let args = ["watch", "whoami", "hello", "world"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut expected = Config::default();
+        expected.action = Action::WatchCommand(WatchCommandData::new(
+            "whoami".to_string(),
+            vec!["hello".to_string(), "world".to_string()],
+        ));
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn watch_action_with_dash_args_is_parsed() {
+        let args = ["watch", "whoami", "-p", "101", "--", "-p", "100"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut expected = Config::default();
+        expected.action = Action::WatchCommand(WatchCommandData::new(
+            "whoami".to_string(),
+            vec!["-p".to_string(), "101".to_string()],
+        ));
+        expected.server_port = 100;
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn watch_action_with_mode_argument_is_parsed() {
+        fn run(value: &str, mode: WatchMode) {
+            let args = ["watch", "echo", "a", "--", "-m", value];
+            let config = Config::parse(to_owned_string_iter(&args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data =
+                WatchCommandData::new("echo".to_string(), vec!["a".to_string()]);
+            watch_command_data.mode = mode;
+            let mut expected = Config::default();
+            expected.action = Action::WatchCommand(watch_command_data);
+            assert_eq!(config, expected);
+        }
+        run("OneLineError", WatchMode::OneLineError);
+        run("OneLineErROR", WatchMode::OneLineError);
+        run("MultiLineError", WatchMode::MultiLineError);
+        run("MultiLineErROR", WatchMode::MultiLineError);
+        run("ExitCode", WatchMode::ExitCode);
+        run("ExitCODE", WatchMode::ExitCode);
+        run("OneLineErrorExitCode", WatchMode::OneLineErrorExitCode);
+        run("OneLineErrorExitCODE", WatchMode::OneLineErrorExitCode);
+        run("InvertedExitCode", WatchMode::InvertedExitCode);
+        run("invertedexitcode", WatchMode::InvertedExitCode);
+        run("Nagios", WatchMode::Nagios);
+        run("nagios", WatchMode::Nagios);
+    }
+
+    #[test]
+    fn watch_action_with_shutdown_status_argument_is_parsed() {
+        fn run(value: &str, shutdown_status: ShutdownStatus) {
+            let args = ["watch", "echo", "--", "--shutdown-status", value];
+            let config = Config::parse(to_owned_string_iter(&args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+            watch_command_data.shutdown_status = shutdown_status;
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run("Error", ShutdownStatus::Error);
+        run("error", ShutdownStatus::Error);
+        run("Ok", ShutdownStatus::Ok);
+        run("OK", ShutdownStatus::Ok);
+
+        let args = ["watch", "echo", "--", "--shutdown-status", "Nothing"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn watch_action_with_stream_argument_is_parsed() {
+        fn run(value: &str, stream: CapturedStream) {
+            let args = ["watch", "echo", "--", "--stream", value];
+            let config = Config::parse(to_owned_string_iter(&args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+            watch_command_data.stream = stream;
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run("Stdout", CapturedStream::Stdout);
+        run("stderr", CapturedStream::Stderr);
+        run("BOTH", CapturedStream::Both);
+
+        let args = ["watch", "echo", "--", "--stream", "stdin"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn watch_action_with_shell_command_argument_is_parsed() {
+        let args = ["watch", "echo", "--", "-s", "1", "--shell-cmd", "bash -c"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.shell = true;
+        watch_command_data.shell_command = "bash -c".to_owned();
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--shell-cmd", ""];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_jitter_argument_is_parsed() {
+        let args = ["watch", "echo", "--", "--jitter", "5s"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.jitter = Duration::from_secs(5);
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--jitter", "-5s"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_watch_path_arguments_is_parsed() {
+        let args = [
+            "watch",
+            "echo",
+            "--",
+            "--watch-path",
+            "a.toml",
+            "--watch-path",
+            "dir",
+            "--no-timer",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.watch_paths = vec![PathBuf::from("a.toml"), PathBuf::from("dir")];
+        watch_command_data.no_timer = true;
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--no-timer"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn watch_action_with_scheduling_arguments_is_parsed() {
+        fn run(args: &[&str], schedule: ScheduleMode, align: bool) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+            watch_command_data.schedule = schedule;
+            watch_command_data.align = align;
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run(&["watch", "echo"], ScheduleMode::Delay, false);
+        run(&["watch", "echo", "--", "--schedule", "tick"], ScheduleMode::Tick, false);
+        run(&["watch", "echo", "--", "--schedule", "Delay", "--align"], ScheduleMode::Delay, true);
+
+        let args = ["watch", "echo", "--", "--schedule", "cron"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn watch_action_with_hysteresis_arguments_is_parsed() {
+        let args = [
+            "watch",
+            "echo",
+            "--",
+            "--failures-before-error",
+            "3",
+            "--successes-before-ok",
+            "2",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.failures_before_error = 3;
+        watch_command_data.successes_before_ok = 2;
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--failures-before-error", "0"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        let args = ["watch", "echo", "--", "--successes-before-ok", "x"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_only_changes_arguments_is_parsed() {
+        fn run(args: &[&str], only_changes: bool, reconfirm_interval: Option<Duration>) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+            watch_command_data.only_changes = only_changes;
+            watch_command_data.reconfirm_interval = reconfirm_interval;
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run(&["watch", "echo", "--", "--only-changes"], true, None);
+        run(
+            &["watch", "echo", "--", "--only-changes", "--reconfirm", "5m"],
+            true,
+            Some(Duration::from_secs(300)),
+        );
+
+        let args = ["watch", "echo", "--", "--reconfirm", "5m"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn watch_action_with_max_updates_per_minute_is_parsed() {
+        let args = ["watch", "echo", "--", "--max-updates-per-minute", "6"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.max_updates_per_minute = Some(6);
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--max-updates-per-minute", "0"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_retry_arguments_is_parsed() {
+        let args = ["watch", "echo", "--", "--retries", "2", "--retry-delay", "500ms"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.retries = 2;
+        watch_command_data.retry_delay = Duration::from_millis(500);
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--retries", "-1"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        let args = ["watch", "echo", "--", "--retry-delay", "1s"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn watch_action_with_overlap_argument_is_parsed() {
+        fn run(value: &str, overlap: OverlapPolicy) {
+            let args = ["watch", "echo", "--", "--overlap", value];
+            let config = Config::parse(to_owned_string_iter(&args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+            watch_command_data.overlap = overlap;
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run("Skip", OverlapPolicy::Skip);
+        run("queue", OverlapPolicy::Queue);
+        run("RESTART", OverlapPolicy::Restart);
+
+        let args = ["watch", "echo", "--", "--overlap", "Parallel"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn watch_action_with_streaming_arguments_is_parsed() {
+        let args = [
+            "watch",
+            "tail",
+            "-f",
+            "log",
+            "--",
+            "--streaming",
+            "--ok-regex",
+            "OK",
+            "--quiet-period",
+            "1m",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new(
+            "tail".to_string(),
+            vec!["-f".to_string(), "log".to_string()],
+        );
+        watch_command_data.streaming = true;
+        watch_command_data.ok_regex = Some(OutputRegex::parse("OK").unwrap());
+        watch_command_data.quiet_period = Some(Duration::from_secs(60));
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--streaming"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+        let args = ["watch", "echo", "--", "--quiet-period", "1m"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+        let args = [
+            "watch",
+            "echo",
+            "--",
+            "--streaming",
+            "--quiet-period",
+            "1m",
+            "--timeout",
+            "1s",
+        ];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn watch_action_with_ssh_arguments_is_parsed() {
+        let args = [
+            "watch",
+            "df",
+            "--",
+            "--ssh",
+            "admin@router",
+            "--ssh-cmd",
+            "ssh -p 2222",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("df".to_string(), Vec::new());
+        watch_command_data.ssh = Some("admin@router".to_owned());
+        watch_command_data.ssh_command = "ssh -p 2222".to_owned();
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "df", "--", "--ssh-cmd", "ssh -p 2222"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn watch_action_with_limit_arguments_is_parsed() {
+        let args = [
+            "watch",
+            "echo",
+            "--",
+            "--nice",
+            "-5",
+            "--memory-limit",
+            "512M",
+            "--user",
+            "nobody",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.limits = ProcessLimits {
+            nice: Some(-5),
+            memory_limit: Some(512 * 1024 * 1024),
+            user: Some("nobody".to_owned()),
+        };
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--nice", "20"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        let args = ["watch", "echo", "--", "--memory-limit", "lots"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_report_pending_argument_is_parsed() {
+        let args = ["watch", "echo", "--", "--report-pending"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.report_pending = true;
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn watch_action_with_grace_argument_is_parsed() {
+        let args = ["watch", "echo", "--", "--grace", "2m"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.grace = Some(Duration::from_secs(120));
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn watch_action_with_log_output_argument_is_parsed() {
+        let args = ["watch", "echo", "--", "--log-output", "/tmp/output.log"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.log_output = Some(PathBuf::from("/tmp/output.log"));
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn watch_action_with_hook_arguments_is_parsed() {
+        let args = [
+            "watch",
+            "echo",
+            "--",
+            "--on-error",
+            "systemctl restart app",
+            "--on-recover",
+            "echo ok",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.on_error = Some("systemctl restart app".to_owned());
+        watch_command_data.on_recover = Some("echo ok".to_owned());
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--on-error", ""];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn ping_check_is_parsed() {
+        fn run(args: &[&str], expected_check: PingCheck, expected_interval: Duration) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Ping(expected_check));
+            watch_command_data.interval = expected_interval;
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "ping", "db.local"],
+            PingCheck::new("db.local".to_owned()),
+            DEFAULT_WATCH_INTERVAL,
+        );
+        run(
+            &[
+                "check",
+                "ping",
+                "10.0.0.1",
+                "--count",
+                "5",
+                "--timeout",
+                "500ms",
+                "--max-loss",
+                "20",
+                "--max-rtt",
+                "100ms",
+                "-w",
+                "1m",
+            ],
+            PingCheck {
+                host: "10.0.0.1".to_owned(),
+                count: 5,
+                timeout: Duration::from_millis(500),
+                max_loss_percent: 20,
+                max_rtt: Some(Duration::from_millis(100)),
+            },
+            Duration::from_secs(60),
+        );
+
+        let args = ["check", "ping", "db.local", "--max-loss", "101"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        let args = ["check", "ping", "db.local", "--count", "0"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        let args = ["check"];
+        assert_eq!(
+            parse_error_kind(&args),
+            ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        );
+    }
+
+    #[test]
+    fn dns_check_is_parsed() {
+        fn run(args: &[&str], expected_check: DnsCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Dns(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "dns", "db.internal"],
+            DnsCheck::new("db.internal".to_owned()),
+        );
+        run(
+            &[
+                "check",
+                "dns",
+                "db.internal",
+                "--expect",
+                "10.0.0.5",
+                "--expect",
+                "fd00::5",
+                "--server",
+                "10.0.0.2",
+                "--timeout",
+                "500ms",
+            ],
+            DnsCheck {
+                name: "db.internal".to_owned(),
+                expected_addresses: vec!["10.0.0.5".parse().unwrap(), "fd00::5".parse().unwrap()],
+                server: Some("10.0.0.2:53".parse().unwrap()),
+                timeout: Duration::from_millis(500),
+            },
+        );
+
+        let args = ["check", "dns", "db.internal", "--expect", "db.local"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        let args = ["check", "dns", "db.internal", "--server", "dns.local"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn disk_check_is_parsed() {
+        fn run(args: &[&str], expected_check: DiskCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Disk(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "disk", "/var"],
+            DiskCheck::new(PathBuf::from("/var")),
+        );
+        run(
+            &["check", "disk", "/var", "--warn", "80%", "--crit", "95"],
+            DiskCheck {
+                path: PathBuf::from("/var"),
+                warning_percent: Some(80),
+                critical_percent: Some(95),
+            },
+        );
+
+        let args = ["check", "disk", "/var", "--crit", "120%"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn system_check_is_parsed() {
+        fn run(args: &[&str], expected_check: SystemCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::System(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(&["check", "system"], SystemCheck::default());
+        run(
+            &[
+                "check",
+                "system",
+                "--max-load",
+                "4.5",
+                "--max-cpu",
+                "95%",
+                "--max-memory",
+                "80%",
+                "--max-swap",
+                "50",
+            ],
+            SystemCheck {
+                max_load: Some(4.5),
+                max_cpu_percent: Some(95),
+                max_memory_percent: Some(80),
+                max_swap_percent: Some(50),
+            },
+        );
+
+        let args = ["check", "system", "--max-load", "high"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn file_check_is_parsed() {
+        fn run(args: &[&str], expected_check: FileCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::File(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "file", "/backups/db.sql.gz"],
+            FileCheck::new(PathBuf::from("/backups/db.sql.gz")),
+        );
+        run(
+            &[
+                "check",
+                "file",
+                "/backups/db.sql.gz",
+                "--max-age",
+                "1d",
+                "--min-size",
+                "1",
+                "--max-size",
+                "10G",
+            ],
+            FileCheck {
+                path: PathBuf::from("/backups/db.sql.gz"),
+                absent: false,
+                max_age: Some(Duration::from_secs(24 * 60 * 60)),
+                min_size: Some(1),
+                max_size: Some(10 << 30),
+            },
+        );
+        run(
+            &["check", "file", "/var/run/app.lock", "--absent"],
+            FileCheck {
+                absent: true,
+                ..FileCheck::new(PathBuf::from("/var/run/app.lock"))
+            },
+        );
+
+        let args = [
+            "check",
+            "file",
+            "/var/run/app.lock",
+            "--absent",
+            "--max-age",
+            "1d",
+        ];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn process_check_is_parsed() {
+        fn run(args: &[&str], expected_check: ProcessCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Process(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "process", "nginx"],
+            ProcessCheck::new("nginx".to_owned()),
+        );
+        run(
+            &[
+                "check",
+                "process",
+                "*worker.py*",
+                "--full",
+                "--min",
+                "2",
+                "--max",
+                "4",
+            ],
+            ProcessCheck {
+                pattern: "*worker.py*".to_owned(),
+                full_command: true,
+                min_count: 2,
+                max_count: Some(4),
+            },
+        );
+        run(
+            &["check", "process", "backup", "--max", "0"],
+            ProcessCheck {
+                min_count: 0,
+                max_count: Some(0),
+                ..ProcessCheck::new("backup".to_owned())
+            },
+        );
+    }
+
+    #[test]
+    fn docker_check_is_parsed() {
+        fn run(args: &[&str], expected_check: DockerCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Docker(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "docker", "web"],
+            DockerCheck::new("web".to_owned()),
+        );
+        run(
+            &[
+                "check",
+                "docker",
+                "web",
+                "--socket",
+                "/run/user/1000/docker.sock",
+            ],
+            DockerCheck {
+                container: "web".to_owned(),
+                socket: PathBuf::from("/run/user/1000/docker.sock"),
+            },
+        );
+    }
+
+    #[test]
+    fn plugin_check_is_parsed() {
+        fn run(args: &[&str], expected_check: PluginCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Plugin(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "plugin", "./check_queue"],
+            PluginCheck::new(
+                PathBuf::from("./check_queue"),
+                Vec::new(),
+                DEFAULT_PLUGIN_TIMEOUT,
+            ),
+        );
+        run(
+            &[
+                "check",
+                "plugin",
+                "--timeout",
+                "3s",
+                "./check_queue",
+                "--queue",
+                "mail",
+                "-v",
+            ],
+            PluginCheck::new(
+                PathBuf::from("./check_queue"),
+                to_owned_string_iter(&["--queue", "mail", "-v"]).collect(),
+                Duration::from_secs(3),
+            ),
+        );
+
+        let result = Config::parse(to_owned_string_iter(&["check", "plugin"]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "wasm")]
+    fn wasm_check_is_parsed() {
+        fn run(args: &[&str], expected_check: WasmCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Wasm(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "wasm", "check.wasm"],
+            WasmCheck::new(PathBuf::from("check.wasm"), Vec::new()),
+        );
+        let mut expected = WasmCheck::new(
+            PathBuf::from("check.wasm"),
+            to_owned_string_iter(&["--queue", "mail"]).collect(),
+        );
+        expected.fuel = 5000;
+        expected.max_memory = 16 << 20;
+        run(
+            &[
+                "check",
+                "wasm",
+                "--fuel",
+                "5000",
+                "--max-memory",
+                "16M",
+                "check.wasm",
+                "--queue",
+                "mail",
+            ],
+            expected,
+        );
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", windows))]
+    fn log_check_is_parsed() {
+        fn run(args: &[&str], expected_check: LogCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Log(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(&["check", "log"], LogCheck::default());
+        run(
+            &[
+                "check",
+                "log",
+                "--priority",
+                "warning",
+                "-e",
+                "I/O error",
+                "--source",
+                "kernel",
+            ],
+            {
+                let mut check = LogCheck::default();
+                check.max_priority = 4;
+                check.pattern = Some(OutputRegex::parse("I/O error").unwrap());
+                check.source = Some("kernel".to_owned());
+                check
+            },
+        );
+
+        let result = Config::parse(to_owned_string_iter(&[
+            "check",
+            "log",
+            "--priority",
+            "error",
+        ]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", feature = "systemd"))]
+    fn systemd_check_is_parsed() {
+        fn run(args: &[&str], expected_check: SystemdCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Systemd(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "systemd", "nginx"],
+            SystemdCheck::new("nginx".to_owned()),
+        );
+        run(
+            &[
+                "check",
+                "systemd",
+                "backup.service",
+                "--user",
+                "--allow-inactive",
+            ],
+            SystemdCheck {
+                unit: "backup.service".to_owned(),
+                user: true,
+                allow_inactive: true,
+            },
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "smart")]
+    fn smart_check_is_parsed() {
+        fn run(args: &[&str], expected_check: SmartCheck) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.check = Some(BuiltinCheck::Smart(expected_check));
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["check", "smart", "/dev/sda"],
+            SmartCheck::new("/dev/sda".to_owned()),
+        );
+        run(
+            &["check", "smart", "/dev/sdb", "--device-type", "sat"],
+            SmartCheck {
+                device: "/dev/sdb".to_owned(),
+                device_type: Some("sat".to_owned()),
+            },
+        );
+    }
+
+    #[test]
+    fn tail_action_is_parsed() {
+        fn run(args: &[&str], quiet_period: Duration) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data = WatchCommandData::new(String::new(), Vec::new());
+            watch_command_data.streaming = true;
+            watch_command_data.tail_path = Some(PathBuf::from("/var/log/app.log"));
+            watch_command_data.error_regex = Some(OutputRegex::parse("ERROR: (.*)").unwrap());
+            watch_command_data.quiet_period = Some(quiet_period);
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(
+            &["tail", "/var/log/app.log", "-e", "ERROR: (.*)"],
+            DEFAULT_TAIL_QUIET_PERIOD,
+        );
+        run(
+            &[
+                "tail",
+                "/var/log/app.log",
+                "--pattern",
+                "ERROR: (.*)",
+                "--quiet-period",
+                "10s",
+            ],
+            Duration::from_secs(10),
+        );
+
+        let args = ["tail", "/var/log/app.log"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn watch_action_with_error_regex_argument_is_parsed() {
+        let args = ["watch", "echo", "--", "--error-regex", "ERROR: (.*)"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.error_regex = Some(OutputRegex::parse("ERROR: (.*)").unwrap());
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--error-regex", "[a-"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    #[cfg(feature = "script")]
+    fn watch_action_with_script_argument_is_parsed() {
+        let path =
+            std::env::temp_dir().join(format!("check_mate_config_{}.rhai", std::process::id()));
+        std::fs::write(&path, "if exit_code != 0 { output }").unwrap();
+        let path = path.to_str().unwrap();
+
+        let args = ["watch", "--script", path, "echo"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.script = Some(OutputScript::load(path).unwrap());
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "--script", path, "-m", "ExitCode", "echo"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ArgumentConflict);
+        std::fs::remove_file(path).unwrap();
+
+        let args = ["watch", "--script", "/nonexistent/script.rhai", "echo"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_json_mode_arguments_is_parsed() {
+        let args = [
+            "watch",
+            "echo",
+            "--",
+            "-m",
+            "json",
+            "--json-status",
+            ".ok",
+            "--json-message",
+            ".detail",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.mode = WatchMode::Json;
+        watch_command_data.json_paths = JsonPaths {
+            status: ".ok".to_owned(),
+            message: Some(".detail".to_owned()),
+        };
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "-m", "json"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+        let args = ["watch", "echo", "--", "--json-status", ".ok"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ArgumentConflict);
+        let args = ["watch", "echo", "--", "-m", "json", "--json-status", "ok"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_timeout_argument_is_parsed() {
+        let args = ["watch", "echo", "--", "--timeout", "1m30s"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.timeout = Some(Duration::from_secs(90));
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["watch", "echo", "--", "--timeout", "soon"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn watch_action_with_invalid_mode_argument_should_fail() {
+        fn run(value: &str) {
+            let args = ["watch", "echo", "a", "--", "-m", value];
+            assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
+        }
+        run("OneLineErro");
+        run("");
+        run("OneLineErrorrrrrrr");
+        run("1");
+        run("0");
+        run(".");
+        run("*");
+    }
+
+    #[test]
+    fn watch_action_with_shell_argument_is_parsed() {
+        fn run(value: &str, value_bool: bool) {
+            let args = ["watch", "echo", "a", "--", "-s", value];
+            let config = Config::parse(to_owned_string_iter(&args));
+            let config = config.expect("Parsing should succeed");
+
+            let mut watch_command_data =
+                WatchCommandData::new("echo".to_string(), vec!["a".to_string()]);
+            watch_command_data.shell = value_bool;
+            let mut expected = Config::default();
+            expected.action = Action::WatchCommand(watch_command_data);
+            assert_eq!(config, expected);
+        }
+        run("0", false);
+        run("false", false);
+        run("1", true);
+        run("true", true);
+    }
+
+    #[test]
+    fn watch_action_with_invalid_shell_argument_should_fail() {
+        fn run(value: &str) {
+            let args = ["watch", "echo", "a", "--", "-s", value];
+            assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        }
+        run("aa");
+        run("");
+        run("1.");
+        run("1 .");
+    }
+
+    #[test]
+    fn refresh_action_is_parsed() {
+        let args = ["refresh", "client12"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut expected = Config::default();
+        expected.action = Action::RefreshClientByName("client12".to_string());
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn top_action_is_parsed() {
+        let args = ["top", "-e", "5s", "-f", "db-*"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::Top(TopData {
+                interval: Duration::from_secs(5),
+                name_filter: Some("db-*".to_owned()),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["top"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        assert_eq!(config.action, Action::Top(TopData::default()));
+    }
+
+    #[test]
+    fn notify_action_is_parsed() {
+        let args = ["notify", "-f", "db-*", "--recoveries"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::Notify(NotifyData {
+                name_filter: Some("db-*".to_owned()),
+                recoveries: true,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn badge_action_is_parsed() {
+        let args = ["badge", "/var/www/badges", "--label", "build", "-e", "1m"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut badge_data = BadgeData::new(PathBuf::from("/var/www/badges"));
+        badge_data.label = "build".to_owned();
+        badge_data.every = Some(Duration::from_secs(60));
+        let expected = Config {
+            action: Action::WriteBadges(badge_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["badge", "badges"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        let expected = Action::WriteBadges(BadgeData::new(PathBuf::from("badges")));
+        assert_eq!(config.action, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "redis")]
+    fn redis_action_is_parsed() {
+        let args = [
+            "redis",
+            "redis://cache:6379",
+            "--channel",
+            "health",
+            "--mirror",
+            "health:",
+            "-f",
+            "db-*",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut redis_data = RedisData::new("redis://cache:6379".to_owned());
+        redis_data.channel = "health".to_owned();
+        redis_data.mirror_prefix = Some("health:".to_owned());
+        redis_data.name_filter = Some("db-*".to_owned());
+        assert_eq!(config.action, Action::BridgeToRedis(redis_data));
+
+        let args = ["redis", "redis://localhost"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        let expected = Action::BridgeToRedis(RedisData::new("redis://localhost".to_owned()));
+        assert_eq!(config.action, expected);
+    }
+
+    #[test]
+    fn metrics_action_is_parsed() {
+        let args = [
+            "metrics",
+            "carbon:2003",
+            "--format",
+            "graphite",
+            "--prefix",
+            "prod.health",
+            "-e",
+            "1m",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut metrics_data = MetricsData::new("carbon:2003".to_owned());
+        metrics_data.format = MetricsFormat::Graphite;
+        metrics_data.prefix = "prod.health".to_owned();
+        metrics_data.interval = Duration::from_secs(60);
+        let expected = Config {
+            action: Action::EmitMetrics(metrics_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["metrics", "localhost:8125"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        let expected = Action::EmitMetrics(MetricsData::new("localhost:8125".to_owned()));
+        assert_eq!(config.action, expected);
+    }
+
+    #[test]
+    fn influx_action_is_parsed() {
+        let args = [
+            "influx",
+            "http://influx:8086/api/v2/write?bucket=health",
+            "--token",
+            "secret",
+            "--measurement",
+            "health",
+            "-e",
+            "1m",
+            "-f",
+            "db-*",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let destination =
+            InfluxDestination::Url("http://influx:8086/api/v2/write?bucket=health".to_owned());
+        let mut influx_data = InfluxData::new(destination);
+        influx_data.token = Some("secret".to_owned());
+        influx_data.measurement = "health".to_owned();
+        influx_data.interval = Duration::from_secs(60);
+        influx_data.name_filter = Some("db-*".to_owned());
+        let expected = Config {
+            action: Action::ExportToInflux(influx_data),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["influx", "statuses.lp"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        let destination = InfluxDestination::File("statuses.lp".into());
+        assert_eq!(
+            config.action,
+            Action::ExportToInflux(InfluxData::new(destination))
+        );
+    }
+
+    #[test]
+    fn clear_action_is_parsed() {
+        let args = ["clear", "backup"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::ClearClientByName("backup".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn docker_health_action_is_parsed() {
+        let args = ["docker-health", "web"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::DockerHealth("web".to_string()),
+            server_connection_attempts: 1,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["docker-health", "web", "--connection-attempts", "3"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        assert_eq!(config.server_connection_attempts, 3);
+    }
+
+    #[test]
+    fn cron_wrap_action_is_parsed() {
+        let args = [
+            "cron-wrap",
+            "-n",
+            "backup",
+            "--",
+            "backup.sh",
+            "--full",
+            "-n",
+            "3",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::CronWrap(CronWrapData {
+                command: "backup.sh".to_owned(),
+                command_args: vec!["--full".to_owned(), "-n".to_owned(), "3".to_owned()],
+            }),
+            client_name: Some("backup".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["cron-wrap", "-n", "backup"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+
+        let args = ["cron-wrap", "--", "backup.sh"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
     }
 
-    pub fn print_help() {
-        let intro = "Usage: check_mate_client <action> [<args>]";
-        println!("{}\n", format_text(intro, HELP_MESSAGE_MAX_LINE_WIDTH));
+    #[test]
+    fn push_action_is_parsed() {
+        fn run(args: &[&str], expected_status: PushedStatus) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
 
-        let action_intro = "Available actions:";
-        println!("{}", format_text(action_intro, HELP_MESSAGE_MAX_LINE_WIDTH));
+            let expected = Config {
+                action: Action::PushStatus(expected_status),
+                client_name: Some("backup".to_owned()),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
 
-        let actions = [
-            ("read", "Query error statuses from server".to_owned()),
-            ("watch <command>", "Periodically execute <command> and send its output as status to server.".to_owned()),
-            ("refresh <name>", "Instruct the server to notify a client with a name equal to <name> to rerun its command immediately and update the status.".to_owned()),
-            ("refresh_all", "Instruct the server to notify all its clients to rerun their commands immediately and update the statuses.".to_owned()),
-            ("list", "List all existing clients connected to the server.".to_owned()),
-            ("abort", "Instruct the server to end execution.".to_owned()),
-            ("help", "Print this message.".to_owned()),
-            ("version", "Print version.".to_owned()),
-        ];
-        println!(
-            "{}\n",
-            format_args_list(
-                &actions,
-                HELP_MESSAGE_BASIC_INDENT_WIDTH,
-                HELP_MESSAGE_MAX_LINE_WIDTH
-            )
-        );
-
-        let arguments_intro = "
-            There is a number of additional arguments that can be passed to the client. Some of them are
-            action-specific and will not work with other actions. Arguments are specified after
-            action. For watch action, an additional '--' separator is neccessary to divide the command
-            arguments and CheckMate arguments. Available arguments:";
-        println!(
-            "{}",
-            format_text(arguments_intro, HELP_MESSAGE_MAX_LINE_WIDTH)
-        );
-
-        let watch_modes_descriptions = [
-            " - OneLineError. Empty stdout means success. Non-empty stdout means error. The first non-empty line is an error message, the rest is ignored.",
-            " - MultiLineError. Empty stdout means success. Non-empty stdout means error. All non-empty lines are error message. Empty lines are ignored.",
-            " - ExitCode. Exit code equal to 0 means success. Exit code other than 0 means error. Error message is composed automatically to contain the exit code. The first non-empty in stdout line is an error message, the rest is ignored.",
-            " - OneLineErrorExitCode. Exit code equal to 0 means success. Exit code other than 0 means error. If there are no non-empty lines, error message is composed as for ExitCode."
-        ];
-        let arguments = [
-            ("-p <number>", format!("Set TCP port of the server to connect to. Default is {DEFAULT_PORT}.")),
-            ("-n <string>", "Set name of this client. Name is optional, but makes it easier to identify clients and allows to refresh them by name.".to_owned()),
-            ("-i <boolean>", format!("Only valid with read action. Set whether client names should be printed along with their statuses. Default is {DEFAULT_INCLUDE_NAMES}.", )),
-            ("-w <milliseconds>", format!("Only valid with watch action. Set interval in milliseconds between invocation of the watched command. Default is {}ms.", DEFAULT_WATCH_INTERVAL.as_millis())),
-            ("-d <milliseconds>", format!("Only valid with watch action. Set delay in milliseconds before the watched command is called for the first time. Default is {}ms.", DEFAULT_WATCH_DELAY.as_millis())),
-            ("-m <boolean>", format!("Only valid with watch action. Set watch mode, which represents how errors are detected and reported. Supported modes are listed below. Default is {}.\n{}", WatchMode::default(), watch_modes_descriptions.join("\n"))),
-            ("-s <boolean>", format!("Only valid with watch action. Set whether the watched command should be invoked through default OS shell. Default is {DEFAULT_SHELL}.")),
-            ("-c <milliseconds>", format!("Set backoff time to wait before retrying after unsuccessful connection to the server. Default is {}ms.", DEFAULT_CONNECTION_BACKOFF.as_millis())),
-            ("-r <number>", format!("Set the maximum number of attempts to connect to the server. The value of 0 means infinite attempts. Default is {DEFAULT_MAXIMUM_SERVER_CONNECTION_ATTEMPTS}.")),
-        ];
-        println!(
-            "{}",
-            format_args_list(
-                &arguments,
-                HELP_MESSAGE_BASIC_INDENT_WIDTH,
-                HELP_MESSAGE_MAX_LINE_WIDTH
-            )
+        run(&["push", "-n", "backup", "--ok"], PushedStatus::Ok);
+        run(
+            &["push", "-n", "backup", "--error", "No space left"],
+            PushedStatus::Error("No space left".to_owned()),
+        );
+        run(
+            &["push", "-n", "backup", "--error"],
+            PushedStatus::ErrorFromStdin,
         );
     }
-}
 
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            action: Action::Abort,
-            server_port: DEFAULT_PORT,
-            client_name: None,
-            server_connection_backoff: DEFAULT_CONNECTION_BACKOFF,
-            server_connection_attempts: DEFAULT_MAXIMUM_SERVER_CONNECTION_ATTEMPTS,
-        }
+    #[test]
+    fn push_action_with_invalid_arguments_is_not_parsed() {
+        let args = ["push", "-n", "backup"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
+
+        let args = ["push", "-n", "backup", "--ok", "--error", "Failed"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ArgumentConflict);
+
+        let args = ["push", "--ok"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::MissingRequiredArgument);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn refresh_all_action_is_parsed() {
+        let args = ["refresh_all"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
 
-    fn to_owned_string_iter(string_slices: &[&str]) -> <Vec<String> as IntoIterator>::IntoIter {
-        let vector: Vec<String> = string_slices
-            .iter()
-            .map(|string_slice| string_slice.to_string())
-            .collect();
-        vector.into_iter()
+        let mut expected = Config::default();
+        expected.action = Action::RefreshAllClients;
+        assert_eq!(config, expected);
     }
 
     #[test]
-    fn read_action_is_parsed() {
-        let args = ["read"];
+    fn list_clients_action_is_parsed() {
+        let args = ["list"];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
         let mut expected = Config::default();
-        expected.action = Action::ReadMessages(false);
+        expected.action = Action::ListClients(OutputFormat::Text);
         assert_eq!(config, expected);
     }
 
     #[test]
-    fn read_action_with_include_names_argument_is_parsed() {
-        fn run(include_names: &str, include_names_bool: bool) {
-            let args = ["read", "-i", include_names];
-            let config = Config::parse(to_owned_string_iter(&args));
+    fn read_action_with_follow_argument_is_parsed() {
+        fn run(args: &[&str]) {
+            let config = Config::parse(to_owned_string_iter(args));
             let config = config.expect("Parsing should succeed");
 
-            let mut expected = Config::default();
-            expected.action = Action::ReadMessages(include_names_bool);
+            let expected = Config {
+                action: Action::ReadMessages(ReadMessagesData {
+                    follow: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
             assert_eq!(config, expected);
         }
-        run("0", false);
-        run("false", false);
-        run("1", true);
-        run("true", true);
+        run(&["read", "--follow"]);
     }
 
     #[test]
-    fn read_action_with_invalid_include_names_argument_should_fail() {
-        fn run(include_names: &str) {
-            let args = ["read", "-i", include_names];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let err = config.expect_err("Parsing should fail");
-            let expected =
-                CommandLineError::InvalidValue("include names".into(), include_names.into());
-            assert_eq!(err, expected);
+    fn read_action_with_sort_and_group_by_arguments_is_parsed() {
+        fn run(args: &[&str], sort: Option<SortKey>, group_by: Option<GroupBy>) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let expected = Config {
+                action: Action::ReadMessages(ReadMessagesData {
+                    sort,
+                    group_by,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
         }
-        run("aa");
-        run("");
-        run("1.");
-        run("1 .");
+        run(&["read", "--sort", "name"], Some(SortKey::Name), None);
+        run(&["read", "--sort", "AGE"], Some(SortKey::Age), None);
+        run(&["read", "--sort", "severity"], Some(SortKey::Severity), None);
+        run(&["read", "--group-by", "tag"], None, Some(GroupBy::Tag));
+        run(
+            &["read", "--group-by", "tag", "--sort", "name"],
+            Some(SortKey::Name),
+            Some(GroupBy::Tag),
+        );
+
+        assert_eq!(parse_error_kind(&["read", "--sort", "size"]), ErrorKind::InvalidValue);
+        assert_eq!(parse_error_kind(&["read", "--group-by", "name"]), ErrorKind::InvalidValue);
+        assert_eq!(
+            parse_error_kind(&["read", "--group-by", "tag", "--follow"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "--group-by", "tag", "-o", "json"]),
+            ErrorKind::ArgumentConflict
+        );
     }
 
     #[test]
-    fn watch_action_is_parsed() {
-        let args = ["watch", "whoami"];
+    fn read_action_with_summary_arguments_is_parsed() {
+        fn run(args: &[&str], summary: bool, quiet: bool) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let expected = Config {
+                action: Action::ReadMessages(ReadMessagesData {
+                    summary,
+                    quiet,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run(&["read", "--summary"], true, false);
+        run(&["read", "-q"], false, true);
+        run(&["read", "--quiet", "--summary"], true, true);
+
+        assert_eq!(
+            parse_error_kind(&["read", "-q", "--follow"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "--summary", "-e", "1s", "--diff"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "--summary", "-o", "json"]),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn read_action_with_timestamps_argument_is_parsed() {
+        fn run(args: &[&str], timestamps: Option<TimestampFormat>) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let expected = Config {
+                action: Action::ReadMessages(ReadMessagesData {
+                    timestamps,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run(&["read"], None);
+        run(&["read", "--timestamps"], Some(TimestampFormat::Relative));
+        run(&["read", "--timestamps=relative"], Some(TimestampFormat::Relative));
+        run(&["read", "--timestamps=Absolute"], Some(TimestampFormat::Absolute));
+
+        assert_eq!(
+            parse_error_kind(&["read", "--timestamps=utc"]),
+            ErrorKind::InvalidValue
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "--timestamps", "-e", "1s", "--diff"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "--timestamps", "-o", "json"]),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn client_tags_are_parsed() {
+        let args = ["watch", "echo", "--", "-t", "db", "--tag", "production"];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
-        let mut expected = Config::default();
-        expected.action =
-            Action::WatchCommand(WatchCommandData::new("whoami".to_string(), Vec::new()));
+        let expected = Config {
+            action: Action::WatchCommand(WatchCommandData::new("echo".into(), Vec::new())),
+            client_tags: vec!["db".into(), "production".into()],
+            ..Default::default()
+        };
         assert_eq!(config, expected);
+
+        assert_eq!(
+            parse_error_kind(&["watch", "echo", "--", "-t", ""]),
+            ErrorKind::ValueValidation
+        );
     }
 
     #[test]
-    fn watch_action_with_command_with_no_args_is_parsed() {
-        let args = ["watch", "whoami"];
+    fn namespace_is_parsed() {
+        let args = ["watch", "echo", "--", "--namespace", "team-a"];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
-        let mut expected = Config::default();
-        expected.action =
-            Action::WatchCommand(WatchCommandData::new("whoami".to_string(), Vec::new()));
+        let expected = Config {
+            action: Action::WatchCommand(WatchCommandData::new("echo".into(), Vec::new())),
+            namespace: Some("team-a".into()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["list", "--all-namespaces"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::ListClients(OutputFormat::Text),
+            all_namespaces: true,
+            ..Default::default()
+        };
         assert_eq!(config, expected);
+
+        assert_eq!(
+            parse_error_kind(&["list", "--namespace", ""]),
+            ErrorKind::ValueValidation
+        );
     }
 
     #[test]
-    fn watch_action_with_args_is_parsed() {
-        let args = ["watch", "whoami", "hello", "world"];
+    fn tls_files_are_parsed() {
+        let args = [
+            "list",
+            "--tls-ca",
+            "ca.pem",
+            "--tls-cert",
+            "client.pem",
+            "--tls-key",
+            "client.key",
+        ];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
-        let mut expected = Config::default();
-        expected.action = Action::WatchCommand(WatchCommandData::new(
-            "whoami".to_string(),
-            vec!["hello".to_string(), "world".to_string()],
-        ));
+        let expected = Config {
+            action: Action::ListClients(OutputFormat::Text),
+            tls_ca: Some("ca.pem".into()),
+            tls_cert: Some("client.pem".into()),
+            tls_key: Some("client.key".into()),
+            ..Default::default()
+        };
         assert_eq!(config, expected);
+
+        assert_eq!(
+            parse_error_kind(&["list", "--tls-ca", "ca.pem", "--tls-cert", "client.pem"]),
+            ErrorKind::MissingRequiredArgument
+        );
     }
 
     #[test]
-    fn watch_action_with_dash_args_is_parsed() {
-        let args = ["watch", "whoami", "-p", "101", "--", "-p", "100"];
+    fn api_key_is_parsed() {
+        let args = ["list", "--api-key", "3f9a1c7e.0123456789abcdef"];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
-        let mut expected = Config::default();
-        expected.action = Action::WatchCommand(WatchCommandData::new(
-            "whoami".to_string(),
-            vec!["-p".to_string(), "101".to_string()],
-        ));
-        expected.server_port = 100;
+        let expected = Config {
+            action: Action::ListClients(OutputFormat::Text),
+            api_key: Some("3f9a1c7e.0123456789abcdef".into()),
+            ..Default::default()
+        };
         assert_eq!(config, expected);
+
+        assert_eq!(
+            parse_error_kind(&["list", "--api-key", ""]),
+            ErrorKind::ValueValidation
+        );
     }
 
     #[test]
-    fn watch_action_with_mode_argument_is_parsed() {
-        fn run(value: &str, mode: WatchMode) {
-            let args = ["watch", "echo", "a", "--", "-m", value];
-            let config = Config::parse(to_owned_string_iter(&args));
+    fn api_key_actions_are_parsed() {
+        let parse_action = |args: &[&str]| {
+            let config = Config::parse(to_owned_string_iter(args));
+            config.expect("Parsing should succeed").action
+        };
+        assert_eq!(
+            parse_action(&["api-key", "create", "Backup", "--label", "nightly backup"]),
+            Action::ManageApiKeys(ApiKeyRequest::Create {
+                identity: "Backup".into(),
+                label: "nightly backup".into(),
+            })
+        );
+        assert_eq!(
+            parse_action(&["api-key", "create", "Backup"]),
+            Action::ManageApiKeys(ApiKeyRequest::Create {
+                identity: "Backup".into(),
+                label: String::new(),
+            })
+        );
+        assert_eq!(
+            parse_action(&["api-key", "list"]),
+            Action::ManageApiKeys(ApiKeyRequest::List)
+        );
+        assert_eq!(
+            parse_action(&["api-key", "label", "3f9a1c7e", "weekly backup"]),
+            Action::ManageApiKeys(ApiKeyRequest::Label {
+                id: "3f9a1c7e".into(),
+                label: "weekly backup".into(),
+            })
+        );
+        assert_eq!(
+            parse_action(&["api-key", "revoke", "3f9a1c7e"]),
+            Action::ManageApiKeys(ApiKeyRequest::Revoke("3f9a1c7e".into()))
+        );
+
+        assert_eq!(
+            parse_error_kind(&["api-key", "create", ""]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse_error_kind(&["api-key"]),
+            ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+        );
+    }
+
+    #[test]
+    fn secrets_actions_are_parsed() {
+        let args = ["secrets", "generate-key"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        assert_eq!(config.action, Action::Secrets(SecretsRequest::GenerateKey));
+
+        let args = ["secrets", "encrypt", "influx_token", "-k", "secrets.key"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        assert_eq!(
+            config.action,
+            Action::Secrets(SecretsRequest::Encrypt {
+                name: "influx_token".into(),
+                key_path: "secrets.key".into(),
+            })
+        );
+
+        assert_eq!(
+            parse_error_kind(&["secrets", "encrypt", "influx_token"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            parse_error_kind(&["secrets", "encrypt", "influx=token", "-k", "secrets.key"]),
+            ErrorKind::ValueValidation
+        );
+    }
+
+    #[test]
+    fn secrets_are_resolved() {
+        std::env::set_var("CHECK_MATE_TEST_INFLUX_TOKEN", "s3cr3t");
+        let args = [
+            "influx",
+            "statuses.lp",
+            "--token",
+            "env:CHECK_MATE_TEST_INFLUX_TOKEN",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        let Action::ExportToInflux(data) = config.action else {
+            panic!("Influx action should be parsed");
+        };
+        assert_eq!(data.token, Some("s3cr3t".to_owned()));
+
+        let args = ["list", "--api-key", "env:CHECK_MATE_TEST_MISSING_API_KEY"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
+        let args = ["list", "--api-key", "secret:api_key"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
+
+        let defaults = UserDefaults {
+            secrets_file: Some("secrets".into()),
+            ..Default::default()
+        };
+        let config = Config::parse_with_defaults(to_owned_string_iter(&["list"]), &defaults);
+        let err = config.expect_err("Parsing should not succeed");
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+    }
+
+    #[test]
+    fn discovery_domain_is_parsed() {
+        let args = ["watch", "echo", "--", "--discover", "example.com"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::WatchCommand(WatchCommandData::new("echo".into(), Vec::new())),
+            discovery_domain: Some("example.com".into()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        assert_eq!(
+            parse_error_kind(&["list", "--discover", ""]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse_error_kind(&["list", "--discover", "example.com", "-a", "monitoring"]),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn address_overrides_default_discovery_domain() {
+        let defaults = UserDefaults {
+            discover: Some("example.com".into()),
+            ..Default::default()
+        };
+        let args = ["list", "-a", "monitoring"];
+        let config = Config::parse_with_defaults(to_owned_string_iter(&args), &defaults);
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::ListClients(OutputFormat::Text),
+            server_addresses: vec!["monitoring".into()],
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn read_action_with_all_and_color_arguments_is_parsed() {
+        fn run(args: &[&str], all: bool, color: ColorChoice) {
+            let config = Config::parse(to_owned_string_iter(args));
             let config = config.expect("Parsing should succeed");
 
-            let mut watch_command_data =
-                WatchCommandData::new("echo".to_string(), vec!["a".to_string()]);
-            watch_command_data.mode = mode;
-            let mut expected = Config::default();
-            expected.action = Action::WatchCommand(watch_command_data);
+            let expected = Config {
+                action: Action::ReadMessages(ReadMessagesData {
+                    all,
+                    color,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
             assert_eq!(config, expected);
         }
-        run("OneLineError", WatchMode::OneLineError);
-        run("OneLineErROR", WatchMode::OneLineError);
-        run("MultiLineError", WatchMode::MultiLineError);
-        run("MultiLineErROR", WatchMode::MultiLineError);
-        run("ExitCode", WatchMode::ExitCode);
-        run("ExitCODE", WatchMode::ExitCode);
-        run("OneLineErrorExitCode", WatchMode::OneLineErrorExitCode);
-        run("OneLineErrorExitCODE", WatchMode::OneLineErrorExitCode);
+        run(&["read", "--all"], true, ColorChoice::Auto);
+        run(&["read", "--color", "always"], false, ColorChoice::Always);
+        run(&["read", "--color", "NEVER", "--all"], true, ColorChoice::Never);
+        assert_eq!(
+            parse_error_kind(&["read", "--color", "sometimes"]),
+            ErrorKind::InvalidValue
+        );
     }
 
     #[test]
-    fn watch_action_with_invalid_mode_argument_should_fail() {
-        fn run(value: &str) {
-            let args = ["watch", "echo", "a", "--", "-m", value];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let err = config.expect_err("Parsing should fail");
-            let expected = CommandLineError::InvalidValue("watch mode".into(), value.into());
-            assert_eq!(err, expected);
+    fn read_action_with_name_filter_argument_is_parsed() {
+        fn run(args: &[&str], pattern: &str) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let expected = Config {
+                action: Action::ReadMessages(ReadMessagesData {
+                    name_filter: Some(pattern.to_owned()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
         }
-        run("OneLineErro");
-        run("");
-        run("OneLineErrorrrrrrr");
-        run("1");
-        run("0");
-        run(".");
-        run("*");
+        run(&["read", "-f", "db-*"], "db-*");
+        run(&["read", "--filter", "Watcher"], "Watcher");
+        assert_eq!(parse_error_kind(&["read", "-f"]), ErrorKind::InvalidValue);
     }
 
     #[test]
-    fn watch_action_with_shell_argument_is_parsed() {
-        fn run(value: &str, value_bool: bool) {
-            let args = ["watch", "echo", "a", "--", "-s", value];
-            let config = Config::parse(to_owned_string_iter(&args));
+    fn read_action_with_every_argument_is_parsed() {
+        fn run(args: &[&str], every: Duration, diff: bool) {
+            let config = Config::parse(to_owned_string_iter(args));
             let config = config.expect("Parsing should succeed");
 
-            let mut watch_command_data =
-                WatchCommandData::new("echo".to_string(), vec!["a".to_string()]);
-            watch_command_data.shell = value_bool;
-            let mut expected = Config::default();
-            expected.action = Action::WatchCommand(watch_command_data);
+            let expected = Config {
+                action: Action::ReadMessages(ReadMessagesData {
+                    every: Some(every),
+                    diff,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+        run(&["read", "-e", "5s"], Duration::from_secs(5), false);
+        run(&["read", "--every", "300"], Duration::from_millis(300), false);
+        run(&["read", "-e", "1m", "--diff"], Duration::from_secs(60), true);
+    }
+
+    #[test]
+    fn read_action_with_invalid_every_arguments_should_fail() {
+        assert_eq!(
+            parse_error_kind(&["read", "-e", "abc"]),
+            ErrorKind::ValueValidation
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "--diff"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "-e", "1s", "--follow"]),
+            ErrorKind::ArgumentConflict
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "-e", "1s", "--diff", "-o", "json"]),
+            ErrorKind::ArgumentConflict
+        );
+    }
+
+    #[test]
+    fn output_format_argument_is_parsed() {
+        fn run(args: &[&str], expected_action: Action) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let expected = Config {
+                action: expected_action,
+                ..Default::default()
+            };
             assert_eq!(config, expected);
         }
-        run("0", false);
-        run("false", false);
-        run("1", true);
-        run("true", true);
+
+        let read_json = || {
+            Action::ReadMessages(ReadMessagesData {
+                output_format: OutputFormat::Json,
+                ..Default::default()
+            })
+        };
+        run(&["read", "-o", "json"], read_json());
+        run(&["read", "--output", "JSON"], read_json());
+        run(&["read", "-o", "text"], Action::ReadMessages(ReadMessagesData::default()));
+        run(&["list", "-o", "json"], Action::ListClients(OutputFormat::Json));
+        run(&["list", "--output", "text"], Action::ListClients(OutputFormat::Text));
+
+        assert_eq!(parse_error_kind(&["read", "-o", "xml"]), ErrorKind::InvalidValue);
+        assert_eq!(parse_error_kind(&["list", "-o", "yaml"]), ErrorKind::InvalidValue);
     }
 
     #[test]
-    fn watch_action_with_invalid_shell_argument_should_fail() {
-        fn run(value: &str) {
-            let args = ["watch", "echo", "a", "--", "-s", value];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let err = config.expect_err("Parsing should fail");
-            let expected = CommandLineError::InvalidValue("shell".into(), value.into());
-            assert_eq!(err, expected);
-        }
-        run("aa");
-        run("");
-        run("1.");
-        run("1 .");
+    fn run_action_is_parsed() {
+        let args = ["run", "checks.toml", "-t", "host"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::RunChecks(PathBuf::from("checks.toml")),
+            client_tags: vec!["host".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
     }
 
     #[test]
-    fn refresh_action_is_parsed() {
-        let args = ["refresh", "client12"];
+    fn control_action_is_parsed() {
+        let args = ["control", "/run/watcher.sock", "pause"];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
-        let mut expected = Config::default();
-        expected.action = Action::RefreshClientByName("client12".to_string());
+        let expected = Config {
+            action: Action::Control(ControlData {
+                path: PathBuf::from("/run/watcher.sock"),
+                command: ControlCommand::Pause,
+            }),
+            ..Default::default()
+        };
         assert_eq!(config, expected);
+
+        let args = ["control", "/run/watcher.sock", "jump"];
+        assert_eq!(parse_error_kind(&args), ErrorKind::InvalidValue);
     }
 
     #[test]
-    fn refresh_all_action_is_parsed() {
-        let args = ["refresh_all"];
+    fn watch_action_with_control_socket_is_parsed() {
+        let args = ["watch", "--control-socket", "/run/watcher.sock", "echo"];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
-        let mut expected = Config::default();
-        expected.action = Action::RefreshAllClients;
+        let mut watch_command_data = WatchCommandData::new("echo".to_string(), Vec::new());
+        watch_command_data.control_socket = Some(PathBuf::from("/run/watcher.sock"));
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            ..Default::default()
+        };
         assert_eq!(config, expected);
     }
 
     #[test]
-    fn list_clients_action_is_parsed() {
-        let args = ["list"];
+    fn stats_action_is_parsed() {
+        let args = ["stats"];
         let config = Config::parse(to_owned_string_iter(&args));
         let config = config.expect("Parsing should succeed");
 
-        let mut expected = Config::default();
-        expected.action = Action::ListClients;
+        let expected = Config {
+            action: Action::GetServerStatistics,
+            ..Default::default()
+        };
         assert_eq!(config, expected);
     }
 
+    #[test]
+    fn availability_action_is_parsed() {
+        let config = Config::parse(to_owned_string_iter(&["availability"]));
+        assert_eq!(
+            config.expect("Parsing should succeed").action,
+            Action::GetAvailability(AvailabilityWindow::Month, OutputFormat::Text)
+        );
+
+        let args = ["availability", "-w", "week", "-o", "json"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        assert_eq!(
+            config.expect("Parsing should succeed").action,
+            Action::GetAvailability(AvailabilityWindow::Week, OutputFormat::Json)
+        );
+        let args = ["availability", "-w", "year"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn compact_history_action_is_parsed() {
+        let config = Config::parse(to_owned_string_iter(&["compact-history"]));
+        assert_eq!(
+            config.expect("Parsing should succeed").action,
+            Action::CompactHistory
+        );
+    }
+
+    #[test]
+    fn export_action_is_parsed() {
+        let config = Config::parse(to_owned_string_iter(&["export"]));
+        let expected = ExportData {
+            start: 0,
+            end: None,
+            format: ExportFormat::Csv,
+        };
+        assert_eq!(
+            config.expect("Parsing should succeed").action,
+            Action::ExportHistory(expected)
+        );
+
+        let args = [
+            "export",
+            "--from",
+            "2026-10-01T00:00:00Z",
+            "--to",
+            "2026-11-01T00:00:00Z",
+            "-o",
+            "json",
+        ];
+        let expected = ExportData {
+            start: 1790812800000,
+            end: Some(1793491200000),
+            format: ExportFormat::Json,
+        };
+        assert_eq!(
+            Config::parse(to_owned_string_iter(&args))
+                .expect("Parsing should succeed")
+                .action,
+            Action::ExportHistory(expected)
+        );
+
+        let args = ["export", "--from", "last week"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn drain_action_is_parsed() {
+        let config = Config::parse(to_owned_string_iter(&["drain"]));
+        assert_eq!(
+            config.expect("Parsing should succeed").action,
+            Action::Drain(false)
+        );
+
+        let config = Config::parse(to_owned_string_iter(&["drain", "--wait"]));
+        assert_eq!(
+            config.expect("Parsing should succeed").action,
+            Action::Drain(true)
+        );
+    }
+
     #[test]
     fn abort_action_is_parsed() {
         let args = ["abort"];
@@ -514,31 +4106,24 @@ mod tests {
     }
 
     #[test]
-    fn help_action_is_parsed() {
+    fn help_is_displayed() {
         fn run(args: &[&str]) {
-            let config = Config::parse(to_owned_string_iter(&args));
-            let config = config.expect("Parsing should succeed");
-
-            let mut expected = Config::default();
-            expected.action = Action::Help;
-            assert_eq!(config, expected);
+            assert_eq!(parse_error_kind(args), ErrorKind::DisplayHelp);
         }
 
         run(&["help"]);
-        run(&["help", "-p", "200"]);
+        run(&["help", "watch"]);
         run(&["-h"]);
         run(&["-h", "-n", "client"]);
+        run(&["watch", "--help"]);
     }
 
     #[test]
     fn version_action_is_parsed() {
         fn run(args: &[&str]) {
-            let config = Config::parse(to_owned_string_iter(&args));
+            let config = Config::parse(to_owned_string_iter(args));
             let config = config.expect("Parsing should succeed");
-
-            let mut expected = Config::default();
-            expected.action = Action::Version;
-            assert_eq!(config, expected);
+            assert_eq!(config.action, Action::Version);
         }
 
         run(&["version"]);
@@ -559,6 +4144,145 @@ mod tests {
         assert_eq!(config, expected);
     }
 
+    #[test]
+    fn custom_server_address_is_parsed() {
+        fn run(args: &[&str], addresses: &[&str]) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
+
+            let expected = Config {
+                action: Action::ListClients(OutputFormat::Text),
+                server_addresses: addresses.iter().map(|x| x.to_string()).collect(),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
+        }
+
+        run(&["list", "-a", "192.168.0.10"], &["192.168.0.10"]);
+        run(&["list", "--address", "192.168.0.10"], &["192.168.0.10"]);
+        run(&["list", "-a", "monitoring.example.com"], &["monitoring.example.com"]);
+        run(&["list", "-a", "primary,backup"], &["primary", "backup"]);
+        run(&["list", "-a", " primary , ,backup,"], &["primary", "backup"]);
+    }
+
+    #[test]
+    fn multiple_servers_are_parsed() {
+        let args = ["read", "-a", "prod,prod-backup", "-a", "staging"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::ReadMessages(ReadMessagesData::default()),
+            server_addresses: vec!["prod".into(), "prod-backup".into()],
+            additional_server_addresses: vec![vec!["staging".into()]],
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = [
+            "push", "--ok", "-n", "Backup", "-a", "prod", "-a", "staging",
+        ];
+        assert_eq!(parse_error_kind(&args), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn invalid_server_address_error_is_returned() {
+        fn run(value: &str) {
+            let args = ["list", "-a", value];
+            assert_eq!(parse_error_kind(&args), ErrorKind::ValueValidation);
+        }
+
+        run("");
+        run(",");
+        run(" ");
+        run(" , ,");
+    }
+
+    #[test]
+    fn no_server_address_error_is_returned() {
+        assert_eq!(parse_error_kind(&["list", "-a"]), ErrorKind::InvalidValue);
+        assert_eq!(
+            parse_error_kind(&["list", "--address"]),
+            ErrorKind::InvalidValue
+        );
+    }
+
+    #[test]
+    fn user_defaults_are_applied() {
+        let defaults = UserDefaults {
+            address: Some("primary,backup".into()),
+            discover: Some("example.com".into()),
+            port: Some(2000),
+            name: Some("Watcher".into()),
+            namespace: Some("team-a".into()),
+            connection_backoff: Some(Duration::from_millis(300)),
+            connection_attempts: Some(4),
+            tls_ca: Some("ca.pem".into()),
+            tls_cert: Some("client.pem".into()),
+            tls_key: Some("client.key".into()),
+            api_key: Some("3f9a1c7e.0123456789abcdef".into()),
+            secrets_file: None,
+            secrets_key_file: None,
+        };
+        let args = ["list"];
+        let config = Config::parse_with_defaults(to_owned_string_iter(&args), &defaults);
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::ListClients(OutputFormat::Text),
+            server_addresses: vec!["primary".into(), "backup".into()],
+            additional_server_addresses: Vec::new(),
+            discovery_domain: Some("example.com".into()),
+            server_port: 2000,
+            client_name: Some("Watcher".into()),
+            client_tags: Vec::new(),
+            namespace: Some("team-a".into()),
+            all_namespaces: false,
+            tls_ca: Some("ca.pem".into()),
+            tls_cert: Some("client.pem".into()),
+            tls_key: Some("client.key".into()),
+            api_key: Some("3f9a1c7e.0123456789abcdef".into()),
+            server_connection_backoff: Duration::from_millis(300),
+            server_connection_attempts: 4,
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn command_line_arguments_override_user_defaults() {
+        let defaults = UserDefaults {
+            address: Some("primary".into()),
+            port: Some(2000),
+            name: Some("Watcher".into()),
+            ..Default::default()
+        };
+        let args = ["list", "-a", "backup", "-p", "3000", "-n", "Reader"];
+        let config = Config::parse_with_defaults(to_owned_string_iter(&args), &defaults);
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            action: Action::ListClients(OutputFormat::Text),
+            server_addresses: vec!["backup".into()],
+            server_port: 3000,
+            client_name: Some("Reader".into()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn invalid_user_defaults_error_is_returned() {
+        let defaults = UserDefaults {
+            address: Some(",".into()),
+            ..Default::default()
+        };
+        let args = ["list"];
+        let config = Config::parse_with_defaults(to_owned_string_iter(&args), &defaults);
+        let parse_error = config.expect_err("Parsing should not succeed");
+        assert_eq!(parse_error.kind(), ErrorKind::InvalidValue);
+        assert!(parse_error.to_string().contains("Invalid server address value"));
+    }
+
     #[test]
     fn custom_connection_attempts_option_is_parsed() {
         fn run(value_string: &str, value: u32) {
@@ -627,6 +4351,25 @@ mod tests {
         assert_eq!(config, expected);
     }
 
+    #[test]
+    fn durations_with_units_are_parsed() {
+        let args = [
+            "watch", "echo", "--", "-w", "5s", "-d", "2m", "-c", "1m30s",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let mut watch_command_data = WatchCommandData::new("echo".into(), Vec::new());
+        watch_command_data.interval = Duration::from_secs(5);
+        watch_command_data.delay = Duration::from_secs(120);
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            server_connection_backoff: Duration::from_secs(90),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
     #[test]
     fn multiple_custom_args_are_parsed() {
         let args = [
@@ -645,247 +4388,197 @@ mod tests {
 
     #[test]
     fn no_action_error_is_returned() {
-        let args = [];
-        let config = Config::parse(args.into_iter());
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected =
-            CommandLineError::NoValueSpecified("action".to_owned(), "binary name".to_owned());
-        assert_eq!(parse_error, expected);
+        assert_eq!(parse_error_kind(&[]), ErrorKind::MissingSubcommand);
+        assert_eq!(parse_error_kind(&["-p", "100"]), ErrorKind::MissingSubcommand);
     }
 
     #[test]
     fn no_watch_command_error_is_returned() {
-        let args = ["watch"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected =
-            CommandLineError::NoValueSpecified("command to run".to_owned(), "watch".to_owned());
-        assert_eq!(parse_error, expected);
+        assert_eq!(
+            parse_error_kind(&["watch"]),
+            ErrorKind::MissingRequiredArgument
+        );
+        assert_eq!(
+            parse_error_kind(&["watch", "-w", "100"]),
+            ErrorKind::MissingRequiredArgument
+        );
     }
 
     #[test]
     fn no_client_name_error_to_refresh_is_returned() {
-        let args = ["refresh"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected =
-            CommandLineError::NoValueSpecified("client name".to_owned(), "refresh".to_owned());
-        assert_eq!(parse_error, expected);
-    }
-
-    #[test]
-    fn no_port_error_is_returned() {
-        let args = ["read", "-p"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected = CommandLineError::NoValueSpecified("port".to_string(), "-p".to_string());
-        assert_eq!(parse_error, expected);
-    }
-
-    #[test]
-    fn no_connection_attempts_number_error_is_returned() {
-        let args = ["read", "-r"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected = CommandLineError::NoValueSpecified(
-            "number of connection attempts".to_string(),
-            "-r".to_string(),
+        assert_eq!(
+            parse_error_kind(&["refresh"]),
+            ErrorKind::MissingRequiredArgument
         );
-        assert_eq!(parse_error, expected);
-    }
-
-    #[test]
-    fn no_server_connection_backoff_error_is_returned() {
-        let args = ["read", "-c"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected =
-            CommandLineError::NoValueSpecified("connection backoff".to_string(), "-c".to_string());
-        assert_eq!(parse_error, expected);
     }
 
     #[test]
-    fn no_watch_interval_error_is_returned() {
-        let args = ["watch", "echo", "--", "-w"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected =
-            CommandLineError::NoValueSpecified("watch interval".to_string(), "-w".to_string());
-        assert_eq!(parse_error, expected);
-    }
-
-    #[test]
-    fn no_initial_delay_error_is_returned() {
-        let args = ["watch", "echo", "--", "-d"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
+    fn no_value_error_is_returned() {
+        fn run(args: &[&str]) {
+            assert_eq!(parse_error_kind(args), ErrorKind::InvalidValue);
+        }
 
-        let expected =
-            CommandLineError::NoValueSpecified("initial delay".to_string(), "-d".to_string());
-        assert_eq!(parse_error, expected);
+        run(&["read", "-p"]);
+        run(&["read", "-r"]);
+        run(&["read", "-c"]);
+        run(&["read", "-n"]);
+        run(&["read", "-i"]);
+        run(&["watch", "echo", "--", "-w"]);
+        run(&["watch", "echo", "--", "-d"]);
+        run(&["watch", "echo", "--", "-m"]);
+        run(&["watch", "echo", "--", "-s"]);
     }
 
     #[test]
     fn invalid_action_error_is_returned() {
-        let args = ["jump"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected = CommandLineError::InvalidValue("action".to_string(), "jump".to_string());
-        assert_eq!(parse_error, expected);
+        assert_eq!(parse_error_kind(&["jump"]), ErrorKind::InvalidSubcommand);
     }
 
     #[test]
-    fn no_client_name_error_is_returned() {
-        let args = ["read", "-n"];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected =
-            CommandLineError::NoValueSpecified("client name".to_string(), "-n".to_string());
-        assert_eq!(parse_error, expected);
+    fn invalid_action_error_contains_suggestion() {
+        let config = Config::parse(to_owned_string_iter(&["refrsh", "client12"]));
+        let err = config.expect_err("Parsing should not succeed");
+        assert_eq!(err.kind(), ErrorKind::InvalidSubcommand);
+        assert!(err.to_string().contains("refresh"));
     }
 
     #[test]
     fn empty_client_name_error_is_returned() {
-        let args = ["read", "-n", ""];
-        let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
-
-        let expected =
-            CommandLineError::NoValueSpecified("client name".to_string(), "-n".to_string());
-        assert_eq!(parse_error, expected);
+        assert_eq!(
+            parse_error_kind(&["read", "-n", ""]),
+            ErrorKind::ValueValidation
+        );
     }
 
     #[test]
-    fn invalid_port_error_is_returned() {
-        {
-            let args = ["read", "-p", "-1"];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
-
-            let expected = CommandLineError::InvalidValue("port".to_string(), "-1".to_string());
-            assert_eq!(parse_error, expected);
+    fn invalid_value_error_is_returned() {
+        fn run(args: &[&str]) {
+            assert_eq!(parse_error_kind(args), ErrorKind::ValueValidation);
         }
-        {
-            let args = ["read", "-p", "s"];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
 
-            let expected = CommandLineError::InvalidValue("port".to_string(), "s".to_string());
-            assert_eq!(parse_error, expected);
+        for value in ["s", "2000d", "100000", " ", ""] {
+            run(&["read", "-p", value]);
         }
-        {
-            let args = ["read", "-p", "2000d"];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
-
-            let expected = CommandLineError::InvalidValue("port".to_string(), "2000d".to_string());
-            assert_eq!(parse_error, expected);
+        for value in ["", "ss", "200d"] {
+            run(&["read", "-r", value]);
+        }
+        for value in [" ", "", "40f", "40 f", "abc", "1.5s"] {
+            run(&["read", "-c", value]);
+            run(&["watch", "echo", "--", "-w", value]);
+            run(&["watch", "echo", "--", "-d", value]);
         }
     }
 
     #[test]
-    fn invalid_number_of_connection_attemps_error_is_returned() {
-        fn run(value: &str) {
-            let args = ["read", "-r", value];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
-
-            let expected = CommandLineError::InvalidValue(
-                "number of connection attempts".to_string(),
-                value.to_string(),
-            );
-            assert_eq!(parse_error, expected);
-        }
-        run("");
-        run("-1");
-        run("ss");
-        run("200d");
+    fn negative_value_error_is_returned() {
+        // Negative numbers look like options, so they are not even treated as values
+        assert_eq!(
+            parse_error_kind(&["read", "-p", "-1"]),
+            ErrorKind::UnknownArgument
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "-r", "-1"]),
+            ErrorKind::UnknownArgument
+        );
     }
 
     #[test]
-    fn invalid_server_connection_backoff_error_is_returned() {
-        fn run(value: &str) {
-            let args = ["read", "-c", value];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
-
-            let expected =
-                CommandLineError::InvalidValue("connection backoff".to_string(), value.to_string());
-            assert_eq!(parse_error, expected);
-        }
-        run(" ");
-        run("");
-        run("40f");
-        run("40 f");
-        run("abc");
+    fn invalid_argument_error_is_returned() {
+        assert_eq!(
+            parse_error_kind(&["read", "-k"]),
+            ErrorKind::UnknownArgument
+        );
+        assert_eq!(
+            parse_error_kind(&["read", "--kk"]),
+            ErrorKind::UnknownArgument
+        );
     }
 
     #[test]
-    fn invalid_watch_interval_error_is_returned() {
-        fn run(value: &str) {
-            let args = ["watch", "echo", "--", "-w", value];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
+    fn command_specific_extra_args_return_error_when_used_with_wrong_command() {
+        let command_specific_args = [("-i", "1"), ("-w", "123")];
 
-            let expected =
-                CommandLineError::InvalidValue("watch interval".to_string(), value.to_string());
-            assert_eq!(parse_error, expected);
+        for (arg, value) in command_specific_args {
+            let args = ["abort", arg, value]; // abort is a command with no command-specific args, so we can use it here
+            assert_eq!(parse_error_kind(&args), ErrorKind::UnknownArgument);
         }
-        run(" ");
-        run("");
-        run("40f");
-        run("40 f");
-        run("abc");
     }
 
     #[test]
-    fn invalid_initial_delay_error_is_returned() {
-        fn run(value: &str) {
-            let args = ["watch", "echo", "--", "-d", value];
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
+    fn completions_action_is_parsed() {
+        let args = ["completions", "zsh"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
 
-            let expected =
-                CommandLineError::InvalidValue("initial delay".to_string(), value.to_string());
-            assert_eq!(parse_error, expected);
-        }
-        run(" ");
-        run("");
-        run("40f");
-        run("40 f");
-        run("abc");
+        let expected = Config {
+            action: Action::Completions(clap_complete::Shell::Zsh),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
     }
 
     #[test]
-    fn invalid_argument_error_is_returned() {
-        let args = ["read", "-k"];
+    fn long_options_are_parsed() {
+        let args = [
+            "watch",
+            "--interval",
+            "100",
+            "--delay",
+            "200",
+            "--mode",
+            "ExitCode",
+            "--shell",
+            "1",
+            "--name",
+            "Watcher",
+            "--port",
+            "120",
+            "--connection-backoff",
+            "400",
+            "--connection-attempts",
+            "3",
+            "--",
+            "echo",
+            "a",
+        ];
         let config = Config::parse(to_owned_string_iter(&args));
-        let parse_error = config.expect_err("Parsing should not succeed");
+        let config = config.expect("Parsing should succeed");
 
-        let expected = CommandLineError::InvalidArgument("-k".to_string());
-        assert_eq!(parse_error, expected);
+        let mut watch_command_data = WatchCommandData::new("echo".into(), vec!["a".into()]);
+        watch_command_data.interval = Duration::from_millis(100);
+        watch_command_data.delay = Duration::from_millis(200);
+        watch_command_data.mode = WatchMode::ExitCode;
+        watch_command_data.shell = true;
+        let expected = Config {
+            action: Action::WatchCommand(watch_command_data),
+            server_port: 120,
+            client_name: Some("Watcher".into()),
+            server_connection_backoff: Duration::from_millis(400),
+            server_connection_attempts: 3,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
     }
 
     #[test]
-    fn command_specific_extra_args_return_error_when_used_with_wrong_command() {
-        let command_specific_args = [("-i", "1"), ("-w", "123")];
-
-        for (arg, value) in command_specific_args {
-            let args = ["abort", arg, value]; // abort is a command with no command-specific args, so we can use it here
-            let config = Config::parse(to_owned_string_iter(&args));
-            let parse_error = config.expect_err("Parsing should not succeed");
+    fn watch_action_with_args_before_command_is_parsed() {
+        fn run(args: &[&str]) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let config = config.expect("Parsing should succeed");
 
-            let expected = CommandLineError::InvalidArgument(arg.to_string());
-            assert_eq!(parse_error, expected);
+            let mut watch_command_data =
+                WatchCommandData::new("ls".into(), vec!["-l".into(), "--".into()]);
+            watch_command_data.interval = Duration::from_millis(100);
+            let expected = Config {
+                action: Action::WatchCommand(watch_command_data),
+                client_name: Some("Watcher".into()),
+                ..Default::default()
+            };
+            assert_eq!(config, expected);
         }
+
+        run(&["watch", "-n", "Watcher", "-w", "100", "ls", "-l", "--"]);
+        run(&["watch", "-n", "Watcher", "-w", "100", "--", "ls", "-l", "--"]);
+        run(&["-n", "Watcher", "watch", "-w", "100", "--", "ls", "-l", "--"]);
     }
 }
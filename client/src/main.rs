@@ -1,23 +1,46 @@
-use std::{
-    net::{Ipv4Addr, SocketAddrV4},
-    time::Duration,
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::{
+    io::BufReader,
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
 };
-use tokio::{io::BufReader, net::TcpStream};
+use tokio_rustls::TlsConnector;
 mod action;
+mod check_file;
 mod config;
+mod server_aggregation;
+mod server_discovery;
+mod tls;
+mod user_defaults;
 
+use check_file::{CheckConfig, ReloadSignal};
 use check_mate_common::{constants::*, CommunicationError};
+use check_mate_sdk::{connect_to_any_server, ServerConnection, ServerStream};
 use config::Config;
+use user_defaults::UserDefaults;
 
+// Servers are discovered through DNS on every attempt, if a discovery domain is set. Otherwise addresses are used.
 async fn connect_to_server(
-    server_address: SocketAddrV4,
+    server_addresses: &[String],
+    discovery_domain: Option<&str>,
+    server_port: u16,
+    tls_connector: Option<&TlsConnector>,
     connection_backoff: Duration,
     connection_attemps: u32,
-) -> Option<TcpStream> {
+) -> Option<ServerStream> {
     let mut attempts_made: u32 = 0;
     loop {
         attempts_made += 1;
-        match TcpStream::connect(server_address).await {
+        let connect_result = match discovery_domain {
+            Some(domain) => match server_discovery::discover_servers(domain).await {
+                Ok(addresses) => {
+                    connect_to_any_server(&addresses, server_port, tls_connector).await
+                }
+                Err(err) => Err(err),
+            },
+            None => connect_to_any_server(server_addresses, server_port, tls_connector).await,
+        };
+        match connect_result {
             Ok(ok) => break Some(ok),
             Err(err) => {
                 if connection_attemps > 0 && attempts_made == connection_attemps {
@@ -32,70 +55,398 @@ async fn connect_to_server(
 
 #[tokio::main]
 async fn main() {
-    let config = Config::parse(std::env::args().skip(1));
-    let config = match config {
+    let defaults = match UserDefaults::load() {
         Ok(x) => x,
         Err(err) => {
             eprintln!("ERROR: {}", err);
-            eprintln!("Use help action for more information.");
             std::process::exit(1);
         }
     };
+    let config = match Config::parse_with_defaults(std::env::args().skip(1), &defaults) {
+        Ok(x) => x,
+        Err(err) => err.exit(),
+    };
 
     // Handle simple actions, which do not require connecting to the server
     match config.action {
-        action::Action::Help => {
-            Config::print_help();
-            std::process::exit(0);
-        }
         action::Action::Version => {
             println!("{VERSION}");
             std::process::exit(0);
         }
+        action::Action::Completions(shell) => {
+            Config::print_completions(shell);
+            std::process::exit(0);
+        }
+        action::Action::Control(ref data) => {
+            match action::send_control_command(&data.path, data.command).await {
+                Ok(response) => {
+                    print!("{response}");
+                    std::process::exit(if response.starts_with("ERROR") { 1 } else { 0 });
+                }
+                Err(err) => {
+                    eprintln!("ERROR: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        action::Action::Secrets(ref request) => match action::execute_secrets_request(request) {
+            Ok(output) => {
+                println!("{output}");
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("ERROR: {}", err);
+                std::process::exit(1);
+            }
+        },
         _ => (),
     }
 
-    let server_address = SocketAddrV4::new(Ipv4Addr::LOCALHOST, config.server_port);
+    if let action::Action::RunChecks(_) = config.action {
+        let checks = match load_check_file() {
+            Ok(x) => x,
+            Err(err) => {
+                eprintln!("ERROR: {}", err);
+                std::process::exit(1);
+            }
+        };
+        std::process::exit(run_check_file(checks).await);
+    }
+    std::process::exit(run_client(config, action::ControlRequests::default(), None).await);
+}
 
-    loop {
-        // Connect to server
-        let tcp_stream = connect_to_server(
-            server_address,
-            config.server_connection_backoff,
-            config.server_connection_attempts,
-        )
-        .await;
-        let tcp_stream = match tcp_stream {
-            Some(some) => some,
+// Client running a check from a check file, as seen by the supervisor
+struct SupervisedCheck {
+    args: Vec<String>,
+    configs: UnboundedSender<Config>,
+    requests: UnboundedSender<action::ControlRequest>,
+    task: tokio::task::AbortHandle,
+}
+
+// Connects a client running a check from a check file with its supervisor
+struct Supervision {
+    configs: UnboundedReceiver<Config>,
+    reload_file: UnboundedSender<()>,
+}
+
+// Each check from a check file is an independent client with its own connection to the server. The file is reloaded
+// on SIGHUP or when reloading is requested through a control socket of any check. Added checks are started, removed
+// ones are stopped and changed ones are restarted with the new configuration, keeping their connections. The process
+// exits with the highest exit code of all clients once they have finished. Clients keep non-thread-safe state, so
+// they are all run on the main thread.
+async fn run_check_file(checks: Vec<CheckConfig>) -> i32 {
+    let local_set = tokio::task::LocalSet::new();
+    local_set
+        .run_until(async {
+            let (reload_file, mut reload_requests) = tokio::sync::mpsc::unbounded_channel();
+            let mut reload_signal = ReloadSignal::install();
+            let mut clients = tokio::task::JoinSet::new();
+            let mut running = HashMap::new();
+            apply_checks(checks, &mut running, &mut clients, &reload_file);
+
+            let mut exit_code = 0;
+            loop {
+                let reload = tokio::select! {
+                    result = clients.join_next() => {
+                        match result {
+                            Some(Ok((name, client_exit_code))) => {
+                                running.remove(&name);
+                                exit_code = exit_code.max(client_exit_code);
+                            }
+                            Some(Err(err)) if err.is_cancelled() => (),
+                            Some(Err(_)) => exit_code = exit_code.max(1),
+                            None => break,
+                        }
+                        false
+                    }
+                    _ = reload_requests.recv() => true,
+                    _ = reload_signal.wait() => true,
+                };
+                if reload {
+                    // Requests from many checks are merged into a single reload
+                    while reload_requests.try_recv().is_ok() {}
+                    match load_check_file() {
+                        Ok(checks) => {
+                            apply_checks(checks, &mut running, &mut clients, &reload_file)
+                        }
+                        Err(err) => eprintln!("ERROR: Could not reload check file: {}", err),
+                    }
+                }
+            }
+            exit_code
+        })
+        .await
+}
+
+fn apply_checks(
+    checks: Vec<CheckConfig>,
+    running: &mut HashMap<String, SupervisedCheck>,
+    clients: &mut tokio::task::JoinSet<(String, i32)>,
+    reload_file: &UnboundedSender<()>,
+) {
+    // Stopped checks disconnect from the server, which removes their statuses
+    let names = checks
+        .iter()
+        .map(|x| x.name.as_str())
+        .collect::<HashSet<_>>();
+    running.retain(|name, check| {
+        let keep = names.contains(name.as_str());
+        if !keep {
+            check.task.abort();
+        }
+        keep
+    });
+
+    for check in checks {
+        match running.get_mut(&check.name) {
+            Some(running_check) if running_check.args == check.args => (),
+            Some(running_check) => {
+                running_check.args = check.args;
+                let _ = running_check.configs.send(check.config);
+                let _ = running_check.requests.send(action::ControlRequest::Reload);
+            }
             None => {
-                eprintln!("Failed to connect with server. Aborting.");
-                std::process::exit(1);
+                let (configs, configs_receiver) = tokio::sync::mpsc::unbounded_channel();
+                let requests = action::ControlRequests::default();
+                let supervision = Supervision {
+                    configs: configs_receiver,
+                    reload_file: reload_file.clone(),
+                };
+                let supervised_check = SupervisedCheck {
+                    args: check.args,
+                    configs,
+                    requests: requests.sender(),
+                    task: clients.spawn_local({
+                        let name = check.name.clone();
+                        async move {
+                            (
+                                name,
+                                run_client(check.config, requests, Some(supervision)).await,
+                            )
+                        }
+                    }),
+                };
+                running.insert(check.name, supervised_check);
             }
+        }
+    }
+}
+
+// Loads all checks from the check file, along with all other sources of the configuration
+fn load_check_file() -> Result<Vec<CheckConfig>, String> {
+    let defaults = UserDefaults::load().map_err(|err| err.to_string())?;
+    let config = Config::parse_with_defaults(std::env::args().skip(1), &defaults)
+        .map_err(Config::describe_parse_error)?;
+    let action::Action::RunChecks(ref path) = config.action else {
+        return Err("run action is no longer specified".to_owned());
+    };
+    check_file::load_checks(path, &config, &defaults).map_err(|err| err.to_string())
+}
+
+// Loads the configuration from all its sources again
+fn reload_config() -> Result<Config, String> {
+    let defaults = UserDefaults::load().map_err(|err| err.to_string())?;
+    Config::parse_with_defaults(std::env::args().skip(1), &defaults)
+        .map_err(Config::describe_parse_error)
+}
+
+enum ClientExit {
+    Finished(i32),
+    ReloadRequested,
+}
+
+// Runs the client until it finishes. When reloading is requested, the client is restarted with the new configuration,
+// keeping its connection to the server. Checks from a check file get the configuration from the supervisor, other
+// clients load it again from all its sources. An invalid configuration is reported and the old one is kept.
+async fn run_client(
+    mut config: Config,
+    mut requests: action::ControlRequests,
+    mut supervision: Option<Supervision>,
+) -> i32 {
+    let mut connection = None;
+    loop {
+        let mut action_state = action::ActionState {
+            control_requests: requests,
+            ..Default::default()
         };
+        let client_exit = run_client_once(&config, &mut action_state, &mut connection).await;
+        requests = std::mem::take(&mut action_state.control_requests);
+        drop(action_state);
+        if let ClientExit::Finished(exit_code) = client_exit {
+            return exit_code;
+        }
 
-        // Prepare IO streams
-        let (input_stream, mut output_stream) = tcp_stream.into_split();
-        let mut input_stream = BufReader::new(input_stream);
+        match supervision {
+            Some(ref mut supervision) => {
+                // Reloading requested through the control socket of a check reloads the whole check file
+                let mut new_config = None;
+                while let Ok(x) = supervision.configs.try_recv() {
+                    new_config = Some(x);
+                }
+                match new_config {
+                    Some(x) => config = x,
+                    None => {
+                        let _ = supervision.reload_file.send(());
+                    }
+                }
+            }
+            None => match reload_config() {
+                Ok(x) => config = x,
+                Err(err) => eprintln!("ERROR: Could not reload configuration: {}", err),
+            },
+        }
+    }
+}
+
+async fn run_client_once(
+    config: &Config,
+    action_state: &mut action::ActionState,
+    connection: &mut Option<ServerConnection>,
+) -> ClientExit {
+    if let action::Action::WatchCommand(ref data) = config.action {
+        action_state.client_start = Some(std::time::Instant::now());
+        action_state.refresh_signal = action::RefreshSignal::install();
+        if let Some(ref path) = data.control_socket {
+            match action::ControlSocket::new(path, action_state.control_requests.sender()) {
+                Ok(x) => action_state.control = Some(x),
+                Err(err) => {
+                    eprintln!("ERROR: {}", err);
+                    return ClientExit::Finished(1);
+                }
+            }
+        }
+        if !data.watch_paths.is_empty() {
+            match action::FileWatcher::new(&data.watch_paths) {
+                Ok(x) => action_state.file_watcher = Some(x),
+                Err(err) => {
+                    eprintln!("ERROR: {}", err);
+                    return ClientExit::Finished(1);
+                }
+            }
+        }
+    }
+    let tls_connector = match tls::create_connector(config) {
+        Ok(x) => x,
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            return ClientExit::Finished(1);
+        }
+    };
+    if !config.additional_server_addresses.is_empty() {
+        return run_client_with_multiple_servers(config, action_state, tls_connector.as_ref())
+            .await;
+    }
+    loop {
+        // Connect to server, unless the connection is kept after reloading. Watched command keeps running in the
+        // meantime, so no status changes are missed.
+        if connection.is_none() {
+            let connect = connect_to_server(
+                &config.server_addresses,
+                config.discovery_domain.as_deref(),
+                config.server_port,
+                tls_connector.as_ref(),
+                config.server_connection_backoff,
+                config.server_connection_attempts,
+            );
+            let tcp_stream = match config.action {
+                action::Action::WatchCommand(ref data) => tokio::select! {
+                    tcp_stream = connect => tcp_stream,
+                    _ = action::Action::watch_offline(data, action_state) => {
+                        return ClientExit::ReloadRequested;
+                    }
+                    _ = action::Action::wait_for_shutdown_signal() => break,
+                },
+                _ => connect.await,
+            };
+            let tcp_stream = match tcp_stream {
+                Some(some) => some,
+                None => {
+                    eprintln!("Failed to connect with server. Aborting.");
+                    return ClientExit::Finished(1);
+                }
+            };
+
+            // Prepare IO streams
+            let (input_stream, output_stream) = tokio::io::split(tcp_stream);
+            *connection = Some((BufReader::new(input_stream), output_stream));
+        }
+        let (input_stream, output_stream) = connection.as_mut().unwrap();
 
         // Execute action
         let action_result = config
             .action
-            .execute(&mut input_stream, &mut output_stream, &config)
+            .execute(input_stream, output_stream, config, action_state)
             .await;
 
         // Handle errors
         if let Err(err) = action_result {
+            *connection = None;
             match err {
                 CommunicationError::SocketDisconnected => (),
                 _ => {
                     eprintln!("ERROR: {}", err);
-                    std::process::exit(1);
+                    return ClientExit::Finished(1);
                 }
             }
         }
 
-        if !config.action.should_reconnect() {
+        if !config.action.should_reconnect(action_state) {
             break;
         }
     }
+    if action_state.reload_requested {
+        return ClientExit::ReloadRequested;
+    }
+    ClientExit::Finished(action_state.exit_code)
+}
+
+// Actions reading statuses can combine multiple servers. They are connected to a local multiplexer, which relays their
+// commands to all servers. Reconnecting isn't supported, so losing any server ends the action.
+async fn run_client_with_multiple_servers(
+    config: &Config,
+    action_state: &mut action::ActionState,
+    tls_connector: Option<&TlsConnector>,
+) -> ClientExit {
+    let mut servers = Vec::new();
+    let all_server_addresses =
+        std::iter::once(&config.server_addresses).chain(config.additional_server_addresses.iter());
+    for server_addresses in all_server_addresses {
+        let tcp_stream = connect_to_server(
+            server_addresses,
+            None,
+            config.server_port,
+            tls_connector,
+            config.server_connection_backoff,
+            config.server_connection_attempts,
+        )
+        .await;
+        match tcp_stream {
+            Some(tcp_stream) => servers.push((server_addresses[0].clone(), tcp_stream)),
+            None => {
+                eprintln!(
+                    "Failed to connect with server {}. Aborting.",
+                    server_addresses[0]
+                );
+                return ClientExit::Finished(1);
+            }
+        }
+    }
+
+    let (stream, multiplexer_stream) = tokio::io::duplex(SERVER_AGGREGATION_BUFFER_SIZE);
+    tokio::spawn(server_aggregation::multiplex(multiplexer_stream, servers));
+    let (input_stream, mut output_stream) = tokio::io::split(stream);
+    let mut input_stream = BufReader::new(input_stream);
+    let action_result = config
+        .action
+        .execute(&mut input_stream, &mut output_stream, config, action_state)
+        .await;
+    match action_result {
+        Ok(_) | Err(CommunicationError::SocketDisconnected) => {
+            ClientExit::Finished(action_state.exit_code)
+        }
+        Err(err) => {
+            eprintln!("ERROR: {}", err);
+            ClientExit::Finished(1)
+        }
+    }
 }
@@ -0,0 +1,240 @@
+// Combined view of independent servers for actions reading statuses, e.g. one server per environment. The action talks
+// to a local multiplexer instead of a server. The multiplexer relays commands of the action to all servers and merges
+// their responses into one, prefixing names of clients with the server they come from, e.g. "staging/backup". Commands
+// targeting a client by its name are relayed only to the server of the client.
+
+use check_mate_common::{ClientDetails, CommunicationError, ServerCommand};
+use check_mate_sdk::ServerStream;
+use std::collections::VecDeque;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufReader, DuplexStream};
+use tokio::sync::mpsc;
+
+fn prefix_name(label: &str, name: &str) -> String {
+    format!("{label}/{name}")
+}
+
+fn prefix_details(label: &str, details: ClientDetails) -> ClientDetails {
+    ClientDetails {
+        name: prefix_name(label, &details.name),
+        ..details
+    }
+}
+
+fn prefix_names(label: &str, command: ServerCommand) -> ServerCommand {
+    match command {
+        ServerCommand::ClientDetails(details) => ServerCommand::ClientDetails(
+            details
+                .into_iter()
+                .map(|x| prefix_details(label, x))
+                .collect(),
+        ),
+        ServerCommand::StatusChanged(details) => {
+            ServerCommand::StatusChanged(prefix_details(label, details))
+        }
+        ServerCommand::Clients(names) => {
+            ServerCommand::Clients(names.iter().map(|x| prefix_name(label, x)).collect())
+        }
+        ServerCommand::Statuses(statuses) => {
+            ServerCommand::Statuses(statuses.iter().map(|x| prefix_name(label, x)).collect())
+        }
+        command => command,
+    }
+}
+
+fn expects_response(command: &ServerCommand) -> bool {
+    matches!(
+        command,
+        ServerCommand::GetStatuses(_)
+            | ServerCommand::ListClients
+            | ServerCommand::GetServerStatistics
+            | ServerCommand::GetClientDetails
+    )
+}
+
+// Returns indices of servers the command should be relayed to, along with the command stripped of the server prefix
+fn route_command(labels: &[String], command: ServerCommand) -> (Vec<usize>, ServerCommand) {
+    let find_server = |name: &str| {
+        labels.iter().enumerate().find_map(|(index, label)| {
+            let name = name.strip_prefix(label.as_str())?.strip_prefix('/')?;
+            Some((vec![index], name.to_owned()))
+        })
+    };
+    match command {
+        ServerCommand::RefreshClientByName(ref name) => match find_server(name) {
+            Some((servers, name)) => (servers, ServerCommand::RefreshClientByName(name)),
+            None => (Vec::new(), command),
+        },
+        ServerCommand::ClearClientByName(ref name) => match find_server(name) {
+            Some((servers, name)) => (servers, ServerCommand::ClearClientByName(name)),
+            None => (Vec::new(), command),
+        },
+        command => ((0..labels.len()).collect(), command),
+    }
+}
+
+// Responses are already prefixed. Responses which cannot be combined, e.g. statistics, are taken from the first server.
+fn merge_responses(responses: Vec<ServerCommand>) -> ServerCommand {
+    let mut responses = responses.into_iter();
+    let mut merged = responses
+        .next()
+        .expect("There should be at least one server");
+    for response in responses {
+        match (&mut merged, response) {
+            (ServerCommand::ClientDetails(all), ServerCommand::ClientDetails(details)) => {
+                all.extend(details)
+            }
+            (ServerCommand::Clients(all), ServerCommand::Clients(names)) => all.extend(names),
+            (ServerCommand::Statuses(all), ServerCommand::Statuses(statuses)) => {
+                all.extend(statuses)
+            }
+            _ => (),
+        }
+    }
+    merged
+}
+
+// Commands are written whole, since a partially written command would corrupt the stream
+async fn send_command(
+    stream: &mut (impl AsyncWrite + Unpin),
+    command: &ServerCommand,
+) -> Result<(), CommunicationError> {
+    stream
+        .write_all(&command.to_bytes())
+        .await
+        .map_err(|_| CommunicationError::SocketDisconnected)
+}
+
+// Serves the action connected to the other end of the stream until it or any of the servers disconnects. Servers are
+// given along with labels prefixing names of their clients.
+pub async fn multiplex(
+    stream: DuplexStream,
+    servers: Vec<(String, ServerStream)>,
+) -> Result<(), CommunicationError> {
+    let (action_input, mut action_output) = tokio::io::split(stream);
+    let mut action_input = BufReader::new(action_input);
+
+    let (sender, mut server_messages) = mpsc::channel(servers.len());
+    let mut labels = Vec::new();
+    let mut server_outputs = Vec::new();
+    for (index, (label, stream)) in servers.into_iter().enumerate() {
+        let (input, output) = tokio::io::split(stream);
+        labels.push(label);
+        server_outputs.push(output);
+
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let mut input = BufReader::new(input);
+            loop {
+                let message = ServerCommand::receive_async(&mut input).await;
+                let is_error = message.is_err();
+                if sender.send((index, message)).await.is_err() || is_error {
+                    break;
+                }
+            }
+        });
+    }
+
+    // Each server answers requests in order, so its response belongs to the oldest request it hasn't answered yet
+    let mut pending_responses = VecDeque::<Vec<Option<ServerCommand>>>::new();
+    loop {
+        tokio::select! {
+            command = ServerCommand::receive_async(&mut action_input) => {
+                let command = command?;
+                if expects_response(&command) {
+                    pending_responses.push_back(labels.iter().map(|_| None).collect());
+                }
+                let (servers, command) = route_command(&labels, command);
+                for index in servers {
+                    send_command(&mut server_outputs[index], &command).await?;
+                }
+            }
+            Some((index, message)) = server_messages.recv() => {
+                let message = message.inspect_err(|_| {
+                    eprintln!("ERROR: Lost connection with server {}", labels[index]);
+                })?;
+                match prefix_names(&labels[index], message) {
+                    message @ (ServerCommand::StatusChanged(_) | ServerCommand::Refresh) => {
+                        send_command(&mut action_output, &message).await?;
+                    }
+                    response => {
+                        let slot = pending_responses
+                            .iter_mut()
+                            .find_map(|x| x.get_mut(index).filter(|x| x.is_none()));
+                        if let Some(slot) = slot {
+                            *slot = Some(response);
+                        }
+                    }
+                }
+                while pending_responses.front().is_some_and(|x| x.iter().all(Option::is_some)) {
+                    let responses = pending_responses.pop_front().unwrap_or_default();
+                    let response = merge_responses(responses.into_iter().flatten().collect());
+                    send_command(&mut action_output, &response).await?;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_are_prefixed_and_merged() {
+        let responses = vec![
+            prefix_names(
+                "prod",
+                ServerCommand::ClientDetails(vec![ClientDetails::new("backup", Some(Err("Full")))]),
+            ),
+            prefix_names(
+                "staging",
+                ServerCommand::ClientDetails(vec![ClientDetails::new("backup", Some(Ok(())))]),
+            ),
+        ];
+        assert_eq!(
+            merge_responses(responses),
+            ServerCommand::ClientDetails(vec![
+                ClientDetails::new("prod/backup", Some(Err("Full"))),
+                ClientDetails::new("staging/backup", Some(Ok(()))),
+            ])
+        );
+
+        let responses = vec![
+            prefix_names("prod", ServerCommand::Clients(vec!["disk".to_owned()])),
+            prefix_names("staging", ServerCommand::Clients(Vec::new())),
+        ];
+        assert_eq!(
+            merge_responses(responses),
+            ServerCommand::Clients(vec!["prod/disk".to_owned()])
+        );
+    }
+
+    #[test]
+    fn commands_for_single_client_are_routed_to_its_server() {
+        let labels = ["prod".to_owned(), "staging".to_owned()];
+        assert_eq!(
+            route_command(
+                &labels,
+                ServerCommand::RefreshClientByName("staging/backup".to_owned())
+            ),
+            (
+                vec![1],
+                ServerCommand::RefreshClientByName("backup".to_owned())
+            )
+        );
+        assert_eq!(
+            route_command(
+                &labels,
+                ServerCommand::ClearClientByName("dev/backup".to_owned())
+            ),
+            (
+                Vec::new(),
+                ServerCommand::ClearClientByName("dev/backup".to_owned())
+            )
+        );
+        assert_eq!(
+            route_command(&labels, ServerCommand::GetClientDetails),
+            (vec![0, 1], ServerCommand::GetClientDetails)
+        );
+    }
+}
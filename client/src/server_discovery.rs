@@ -0,0 +1,147 @@
+// Discovery of the server through DNS, so clients of a whole fleet can share the same configuration instead of having
+// addresses set on each host. The server is described by a SRV record of the _checkmate._tcp service in the given
+// domain, e.g. _checkmate._tcp.example.com, which can point to many hosts. Hosts are tried in the order defined by
+// RFC 2782: lower priorities first and, among hosts of the same priority, in a random order weighted by their weights.
+// The record is resolved on every connection attempt, so changes to DNS are picked up when the client reconnects.
+
+use check_mate_common::constants::*;
+use hickory_resolver::TokioAsyncResolver;
+
+#[derive(Debug, PartialEq, Clone)]
+struct ServerRecord {
+    priority: u16,
+    weight: u16,
+    host: String,
+    port: u16,
+}
+
+// Randomness comes from the per-process random keys of the std hasher, which is good enough for load balancing
+fn random_below(bound: u32) -> u32 {
+    use std::hash::{BuildHasher, Hasher};
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (random % bound as u64) as u32
+}
+
+// Orders records in which hosts should be tried. Random numbers below the given bound are provided by the caller, so
+// the order can be tested. Records with zero weight are placed first within their priority, which gives them a small
+// chance of being selected, as recommended by the RFC.
+fn order_records(
+    mut records: Vec<ServerRecord>,
+    mut random_below: impl FnMut(u32) -> u32,
+) -> Vec<ServerRecord> {
+    records.sort_by_key(|x| (x.priority, x.weight != 0));
+    let mut ordered = Vec::new();
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let count = records
+            .iter()
+            .take_while(|x| x.priority == priority)
+            .count();
+        let mut group = records.drain(..count).collect::<Vec<_>>();
+        while !group.is_empty() {
+            let total_weight = group.iter().map(|x| x.weight as u32).sum::<u32>();
+            let selected_weight = random_below(total_weight + 1);
+            let mut running_weight = 0;
+            let index = group
+                .iter()
+                .position(|x| {
+                    running_weight += x.weight as u32;
+                    running_weight >= selected_weight
+                })
+                .unwrap_or(0);
+            ordered.push(group.remove(index));
+        }
+    }
+    ordered
+}
+
+// Returns addresses of servers with their ports, e.g. "monitoring.example.com.:10005", in the order they should be tried
+pub async fn discover_servers(domain: &str) -> Result<Vec<String>, String> {
+    let name = format!("{SERVER_DISCOVERY_SERVICE}.{domain}");
+    let (config, options) = hickory_resolver::system_conf::read_system_conf()
+        .map_err(|err| format!("could not read system resolver configuration: {err}"))?;
+    let resolver = TokioAsyncResolver::tokio(config, options);
+    let lookup = resolver
+        .srv_lookup(name.as_str())
+        .await
+        .map_err(|err| format!("could not resolve {name}: {err}"))?;
+    let records = lookup
+        .iter()
+        .map(|x| ServerRecord {
+            priority: x.priority(),
+            weight: x.weight(),
+            host: x.target().to_utf8(),
+            port: x.port(),
+        })
+        .collect::<Vec<_>>();
+
+    // A single record targeting the root domain means that the service is decidedly not available
+    if let [record] = records.as_slice() {
+        if record.host == "." {
+            return Err(format!("{name} states that there is no server"));
+        }
+    }
+    let addresses = order_records(records, random_below)
+        .into_iter()
+        .map(|x| format!("{}:{}", x.host, x.port))
+        .collect();
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(priority: u16, weight: u16, host: &str) -> ServerRecord {
+        ServerRecord {
+            priority,
+            weight,
+            host: host.to_owned(),
+            port: DEFAULT_PORT,
+        }
+    }
+
+    fn hosts(records: Vec<ServerRecord>) -> Vec<String> {
+        records.into_iter().map(|x| x.host).collect()
+    }
+
+    #[test]
+    fn records_are_ordered_by_priority() {
+        let records = vec![
+            record(20, 0, "backup"),
+            record(10, 0, "primary"),
+            record(30, 0, "fallback"),
+        ];
+        assert_eq!(
+            hosts(order_records(records, |_| 0)),
+            ["primary", "backup", "fallback"]
+        );
+    }
+
+    #[test]
+    fn records_of_the_same_priority_are_ordered_by_weight() {
+        let records = || {
+            vec![
+                record(10, 1, "small"),
+                record(10, 3, "large"),
+                record(10, 0, "spare"),
+                record(20, 5, "backup"),
+            ]
+        };
+        assert_eq!(
+            hosts(order_records(records(), |_| 0)),
+            ["spare", "small", "large", "backup"]
+        );
+        assert_eq!(
+            hosts(order_records(records(), |bound| bound - 1)),
+            ["large", "small", "spare", "backup"]
+        );
+
+        // Running weights of the first selection are 0 for spare, 1 for small and 4 for large
+        let mut random_numbers = vec![2, 0, 0, 0].into_iter();
+        let ordered = order_records(records(), |_| random_numbers.next().unwrap());
+        assert_eq!(hosts(ordered), ["large", "spare", "small", "backup"]);
+    }
+}
@@ -0,0 +1,20 @@
+// TLS of the connection with the server, configured from arguments of the client. Connecting itself is done by the SDK.
+
+use crate::config::Config;
+use check_mate_sdk::create_tls_connector;
+use tokio_rustls::TlsConnector;
+
+// Returns None, if TLS is not enabled
+pub fn create_connector(config: &Config) -> Result<Option<TlsConnector>, String> {
+    let Some(ref ca_path) = config.tls_ca else {
+        return Ok(None);
+    };
+    let client_certificate = match (&config.tls_cert, &config.tls_key) {
+        (Some(certificate_path), Some(key_path)) => {
+            Some((certificate_path.as_str(), key_path.as_str()))
+        }
+        (None, None) => None,
+        _ => return Err("certificate of the client requires its private key".to_owned()),
+    };
+    create_tls_connector(ca_path, client_certificate).map(Some)
+}
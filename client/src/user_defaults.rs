@@ -0,0 +1,291 @@
+// Default values for client arguments can be provided by config files and environment variables, so they don't have
+// to be repeated on every invocation. The sources are applied in the following order, each one overriding values
+// from the previous ones:
+//   1. host-wide config file,
+//   2. per-user config file,
+//   3. environment variables,
+//   4. command line arguments.
+// If CHECK_MATE_CONFIG environment variable is set, it points to the only config file that is read.
+
+use check_mate_common::{parse_duration, CommandLineError};
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const CONFIG_FILE_ENV: &str = "CHECK_MATE_CONFIG";
+pub const SERVER_ENV: &str = "CHECK_MATE_SERVER";
+pub const PORT_ENV: &str = "CHECK_MATE_PORT";
+pub const NAME_ENV: &str = "CHECK_MATE_NAME";
+pub const NAMESPACE_ENV: &str = "CHECK_MATE_NAMESPACE";
+pub const DISCOVER_ENV: &str = "CHECK_MATE_DISCOVER";
+pub const API_KEY_ENV: &str = "CHECK_MATE_API_KEY";
+
+#[derive(Deserialize, Default, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct UserDefaults {
+    pub address: Option<String>,
+    pub discover: Option<String>,
+    pub port: Option<u16>,
+    pub name: Option<String>,
+    pub namespace: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub connection_backoff: Option<Duration>,
+    pub connection_attempts: Option<u32>,
+    pub tls_ca: Option<String>,
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    pub api_key: Option<String>,
+    pub secrets_file: Option<String>, // encrypted secrets referenced as secret:<NAME>
+    pub secrets_key_file: Option<String>,
+}
+
+// Durations can be specified either as a number of milliseconds or as a string with units, e.g. "5s"
+fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawDuration {
+        Milliseconds(u64),
+        Text(String),
+    }
+
+    match RawDuration::deserialize(deserializer)? {
+        RawDuration::Milliseconds(milliseconds) => Ok(Some(Duration::from_millis(milliseconds))),
+        RawDuration::Text(text) => parse_duration(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+impl UserDefaults {
+    pub fn load() -> Result<Self, CommandLineError> {
+        let mut result = Self::default();
+
+        match std::env::var_os(CONFIG_FILE_ENV) {
+            Some(path) => result.merge(Self::parse_file(Path::new(&path))?),
+            None => {
+                for path in Self::get_config_file_paths() {
+                    if path.is_file() {
+                        result.merge(Self::parse_file(&path)?);
+                    }
+                }
+            }
+        }
+
+        result.merge(Self::parse_environment(|name| std::env::var(name).ok())?);
+        Ok(result)
+    }
+
+    fn get_config_file_paths() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+
+        if cfg!(unix) {
+            paths.push(PathBuf::from("/etc/check_mate/client.toml"));
+        }
+
+        let user_config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+        if let Some(user_config_dir) = user_config_dir {
+            paths.push(user_config_dir.join("check_mate").join("client.toml"));
+        }
+
+        paths
+    }
+
+    fn parse_file(path: &Path) -> Result<Self, CommandLineError> {
+        let on_error = |message: String| {
+            CommandLineError::InvalidConfigFile(path.display().to_string(), message)
+        };
+        let text = std::fs::read_to_string(path).map_err(|err| on_error(err.to_string()))?;
+        Self::parse_toml(&text).map_err(on_error)
+    }
+
+    fn parse_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|err| err.message().to_owned())
+    }
+
+    fn parse_environment<F>(get_var: F) -> Result<Self, CommandLineError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let port = match get_var(PORT_ENV) {
+            Some(port) => match port.parse::<u16>() {
+                Ok(x) => Some(x),
+                Err(_) => return Err(CommandLineError::InvalidValue(PORT_ENV.into(), port)),
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            address: get_var(SERVER_ENV),
+            discover: get_var(DISCOVER_ENV),
+            port,
+            name: get_var(NAME_ENV),
+            namespace: get_var(NAMESPACE_ENV),
+            api_key: get_var(API_KEY_ENV),
+            ..Default::default()
+        })
+    }
+
+    fn merge(&mut self, other: Self) {
+        fn merge_value<T>(value: &mut Option<T>, other: Option<T>) {
+            if other.is_some() {
+                *value = other;
+            }
+        }
+
+        merge_value(&mut self.address, other.address);
+        merge_value(&mut self.discover, other.discover);
+        merge_value(&mut self.port, other.port);
+        merge_value(&mut self.name, other.name);
+        merge_value(&mut self.namespace, other.namespace);
+        merge_value(&mut self.connection_backoff, other.connection_backoff);
+        merge_value(&mut self.connection_attempts, other.connection_attempts);
+        merge_value(&mut self.tls_ca, other.tls_ca);
+        merge_value(&mut self.tls_cert, other.tls_cert);
+        merge_value(&mut self.tls_key, other.tls_key);
+        merge_value(&mut self.api_key, other.api_key);
+        merge_value(&mut self.secrets_file, other.secrets_file);
+        merge_value(&mut self.secrets_key_file, other.secrets_key_file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_config_file_is_parsed() {
+        let defaults = UserDefaults::parse_toml("").expect("Parsing should succeed");
+        assert_eq!(defaults, UserDefaults::default());
+    }
+
+    #[test]
+    fn config_file_is_parsed() {
+        let text = "
+            address = \"primary,backup\"
+            discover = \"example.com\"
+            port = 2000
+            name = \"Watcher\"
+            namespace = \"team-a\"
+            connection_backoff = 300
+            connection_attempts = 4
+            tls_ca = \"/etc/check_mate/ca.pem\"
+            tls_cert = \"/etc/check_mate/client.pem\"
+            tls_key = \"/etc/check_mate/client.key\"
+            api_key = \"3f9a1c7e.0123456789abcdef\"
+            secrets_file = \"/etc/check_mate/secrets\"
+            secrets_key_file = \"/etc/check_mate/secrets.key\"
+        ";
+        let defaults = UserDefaults::parse_toml(text).expect("Parsing should succeed");
+
+        let expected = UserDefaults {
+            address: Some("primary,backup".into()),
+            discover: Some("example.com".into()),
+            port: Some(2000),
+            name: Some("Watcher".into()),
+            namespace: Some("team-a".into()),
+            connection_backoff: Some(Duration::from_millis(300)),
+            connection_attempts: Some(4),
+            tls_ca: Some("/etc/check_mate/ca.pem".into()),
+            tls_cert: Some("/etc/check_mate/client.pem".into()),
+            tls_key: Some("/etc/check_mate/client.key".into()),
+            api_key: Some("3f9a1c7e.0123456789abcdef".into()),
+            secrets_file: Some("/etc/check_mate/secrets".into()),
+            secrets_key_file: Some("/etc/check_mate/secrets.key".into()),
+        };
+        assert_eq!(defaults, expected);
+    }
+
+    #[test]
+    fn duration_with_unit_in_config_file_is_parsed() {
+        let defaults = UserDefaults::parse_toml("connection_backoff = \"2s\"")
+            .expect("Parsing should succeed");
+        assert_eq!(defaults.connection_backoff, Some(Duration::from_secs(2)));
+
+        UserDefaults::parse_toml("connection_backoff = \"2 parsecs\"")
+            .expect_err("Invalid duration should fail");
+    }
+
+    #[test]
+    fn invalid_config_file_should_fail() {
+        UserDefaults::parse_toml("port = \"abc\"").expect_err("Invalid type should fail");
+        UserDefaults::parse_toml("port = 100000").expect_err("Port out of range should fail");
+        UserDefaults::parse_toml("prot = 1000").expect_err("Unknown field should fail");
+        UserDefaults::parse_toml("port = ").expect_err("Invalid syntax should fail");
+    }
+
+    #[test]
+    fn environment_variables_are_parsed() {
+        let get_var = |name: &str| match name {
+            SERVER_ENV => Some("monitoring.example.com".to_owned()),
+            PORT_ENV => Some("3000".to_owned()),
+            NAME_ENV => Some("Watcher".to_owned()),
+            NAMESPACE_ENV => Some("team-a".to_owned()),
+            DISCOVER_ENV => Some("example.com".to_owned()),
+            API_KEY_ENV => Some("3f9a1c7e.0123456789abcdef".to_owned()),
+            _ => None,
+        };
+        let defaults = UserDefaults::parse_environment(get_var).expect("Parsing should succeed");
+
+        let expected = UserDefaults {
+            address: Some("monitoring.example.com".into()),
+            discover: Some("example.com".into()),
+            port: Some(3000),
+            name: Some("Watcher".into()),
+            namespace: Some("team-a".into()),
+            api_key: Some("3f9a1c7e.0123456789abcdef".into()),
+            ..Default::default()
+        };
+        assert_eq!(defaults, expected);
+    }
+
+    #[test]
+    fn invalid_port_environment_variable_should_fail() {
+        let get_var = |name: &str| match name {
+            PORT_ENV => Some("abc".to_owned()),
+            _ => None,
+        };
+        let err = UserDefaults::parse_environment(get_var).expect_err("Parsing should fail");
+        assert_eq!(
+            err,
+            CommandLineError::InvalidValue(PORT_ENV.into(), "abc".into())
+        );
+    }
+
+    #[test]
+    fn later_sources_override_earlier_ones() {
+        let mut defaults = UserDefaults {
+            address: Some("host".into()),
+            port: Some(1000),
+            connection_attempts: Some(3),
+            ..Default::default()
+        };
+        defaults.merge(UserDefaults {
+            port: Some(2000),
+            name: Some("Watcher".into()),
+            ..Default::default()
+        });
+
+        let expected = UserDefaults {
+            address: Some("host".into()),
+            discover: None,
+            port: Some(2000),
+            name: Some("Watcher".into()),
+            namespace: None,
+            connection_backoff: None,
+            connection_attempts: Some(3),
+            tls_ca: None,
+            tls_cert: None,
+            tls_key: None,
+            api_key: None,
+            secrets_file: None,
+            secrets_key_file: None,
+        };
+        assert_eq!(defaults, expected);
+    }
+}
@@ -0,0 +1,7 @@
+/// API key stored by the server, sent in response to ListApiKeys. The secret part of the key is never sent back.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ApiKeyDetails {
+    pub id: String,
+    pub identity: String, // name of the client authenticated with the key
+    pub label: String,
+}
@@ -1,142 +1,233 @@
-use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(PartialEq, Debug)]
 pub enum CommandLineError {
-    NoValueSpecified(String, String),
     InvalidValue(String, String),
-    InvalidArgument(String),
+    InvalidConfigFile(String, String),
 }
 
 impl std::fmt::Display for CommandLineError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         match self {
-            Self::NoValueSpecified(name, option) => {
-                write!(f, "Specify a {} after {}", name, option)
-            }
             Self::InvalidValue(name, value) => {
                 write!(f, "Invalid {} value specified: {}", name, value)
             }
-            Self::InvalidArgument(arg) => write!(f, "Invalid argument specified: {}", arg),
+            Self::InvalidConfigFile(path, message) => {
+                write!(f, "Invalid config file {}: {}", path, message)
+            }
         }?;
         Ok(())
     }
 }
 
-pub fn fetch_arg<T: Iterator<Item = String>>(
-    args: &mut T,
-    on_error: CommandLineError,
-) -> Result<String, CommandLineError> {
-    match args.next() {
-        Some(x) => Ok(x),
-        None => Err(on_error),
+/// Value parser for boolean arguments. Accepts both 0/1 and false/true.
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "0" | "false" => Ok(false),
+        "1" | "true" => Ok(true),
+        _ => Err("expected one of 0, 1, false, true".to_owned()),
     }
 }
 
-pub fn fetch_arg_and_parse<Int, T, U, V>(
-    args: &mut T,
-    on_fetch_error: U,
-    on_conversion_error: V,
-) -> Result<Int, CommandLineError>
-where
-    T: Iterator<Item = String>,
-    U: Fn() -> CommandLineError,
-    V: Fn(&str) -> CommandLineError,
-    Int: FromStr,
-{
-    let arg = match args.next() {
-        Some(x) => x,
-        None => return Err(on_fetch_error()),
-    };
+/// Value parser for string arguments, which cannot be empty.
+pub fn parse_non_empty_string(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        Err("value cannot be empty".to_owned())
+    } else {
+        Ok(value.to_owned())
+    }
+}
 
-    let arg = match arg.parse::<Int>() {
-        Ok(x) => x,
-        Err(_) => return Err(on_conversion_error(&arg)),
+/// Value parser for durations. Accepts a number followed by a unit: ms, s, m, h or d. Multiple components can be
+/// concatenated, e.g. 1m30s. A plain number without a unit is treated as milliseconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let error = || {
+        format!(
+            "invalid duration \"{value}\", expected a number with optional unit (ms, s, m, h, d)"
+        )
     };
 
-    Ok(arg)
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(error());
+    }
+    if let Ok(milliseconds) = value.parse::<u64>() {
+        return Ok(Duration::from_millis(milliseconds));
+    }
+
+    let mut result = Duration::ZERO;
+    let mut remaining = value;
+    while !remaining.is_empty() {
+        let number_length = remaining
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(error)?;
+        if number_length == 0 {
+            return Err(error());
+        }
+        let number: u64 = remaining[..number_length].parse().map_err(|_| error())?;
+        remaining = &remaining[number_length..];
+
+        let unit_length = remaining
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(remaining.len());
+        let unit_milliseconds = match &remaining[..unit_length] {
+            "ms" => 1,
+            "s" => 1000,
+            "m" => 60 * 1000,
+            "h" => 60 * 60 * 1000,
+            "d" => 24 * 60 * 60 * 1000,
+            _ => return Err(error()),
+        };
+        remaining = &remaining[unit_length..];
+
+        let milliseconds = number.checked_mul(unit_milliseconds).ok_or_else(error)?;
+        result = result
+            .checked_add(Duration::from_millis(milliseconds))
+            .ok_or_else(error)?;
+    }
+    Ok(result)
 }
 
-pub fn fetch_arg_bool<T, U, V>(
-    args: &mut T,
-    on_fetch_error: U,
-    on_conversion_error: V,
-) -> Result<bool, CommandLineError>
-where
-    T: Iterator<Item = String>,
-    U: Fn() -> CommandLineError,
-    V: Fn(&str) -> CommandLineError,
-{
-    let arg = match args.next() {
-        Some(x) => x,
-        None => return Err(on_fetch_error()),
+/// Value parser for sizes in bytes. Accepts a number followed by an optional binary unit: K, M, G or T, e.g. 512M.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let error =
+        || format!("invalid size \"{value}\", expected a number with optional unit (K, M, G, T)");
+
+    let number_length = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    if number_length == 0 {
+        return Err(error());
+    }
+    let number: u64 = value[..number_length].parse().map_err(|_| error())?;
+    let unit_bytes: u64 = match value[number_length..].to_ascii_uppercase().as_str() {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(error()),
     };
+    number.checked_mul(unit_bytes).ok_or_else(error)
+}
 
-    match arg.as_ref() {
-        "0" | "false" => Ok(false),
-        "1" | "true" => Ok(true),
-        _ => Err(on_conversion_error(&arg)),
+/// Value parser for percentages. Accepts a number from 0 to 100 with an optional percent sign, e.g. 80%.
+pub fn parse_percent(value: &str) -> Result<u8, String> {
+    let number = value.strip_suffix('%').unwrap_or(value);
+    match number.parse::<u8>() {
+        Ok(x) if x <= 100 => Ok(x),
+        _ => Err(format!(
+            "invalid percentage \"{value}\", expected a number from 0 to 100"
+        )),
     }
 }
 
-pub fn fetch_arg_string<T, U, V>(
-    args: &mut T,
-    on_fetch_error: U,
-    on_empty_string: V,
-) -> Result<String, CommandLineError>
-where
-    T: Iterator<Item = String>,
-    U: Fn() -> CommandLineError,
-    V: Fn() -> CommandLineError,
-{
-    let arg = match args.next() {
-        Some(x) => x,
-        None => return Err(on_fetch_error()),
-    };
+/// Formats a duration in the shortest form accepted by parse_duration.
+pub fn format_duration(duration: Duration) -> String {
+    let milliseconds = duration.as_millis();
+    let units = [
+        ("d", 24 * 60 * 60 * 1000),
+        ("h", 60 * 60 * 1000),
+        ("m", 60 * 1000),
+        ("s", 1000),
+    ];
+    for (unit, unit_milliseconds) in units {
+        if milliseconds > 0 && milliseconds.is_multiple_of(unit_milliseconds) {
+            return format!("{}{unit}", milliseconds / unit_milliseconds);
+        }
+    }
+    format!("{milliseconds}ms")
+}
 
-    if arg.is_empty() {
-        return Err(on_empty_string());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_parsed() {
+        fn run(value: &str, expected_milliseconds: u64) {
+            let duration = parse_duration(value).expect("Parsing should succeed");
+            assert_eq!(duration, Duration::from_millis(expected_milliseconds));
+        }
+
+        run("0", 0);
+        run("500", 500);
+        run("500ms", 500);
+        run("5s", 5000);
+        run("2m", 120_000);
+        run("1h", 3_600_000);
+        run("1d", 86_400_000);
+        run("1m30s", 90_000);
+        run("1s500ms", 1500);
+        run(" 10s ", 10_000);
     }
 
-    Ok(arg)
-}
+    #[test]
+    fn invalid_durations_should_fail() {
+        fn run(value: &str) {
+            parse_duration(value).expect_err("Parsing should fail");
+        }
+
+        run("");
+        run(" ");
+        run("s");
+        run("-1");
+        run("10x");
+        run("10 s");
+        run("1.5s");
+        run("ms10");
+        run("99999999999999999999d");
+    }
 
-pub fn format_args_list(
-    arguments: &[(&str, String)],
-    indent_width: usize,
-    max_line_width: usize,
-) -> String {
-    let longest_arg_name: usize = match arguments.iter().map(|x| x.0.len()).max() {
-        Some(x) => x,
-        None => return "".to_owned(),
-    };
+    #[test]
+    fn sizes_are_parsed() {
+        assert_eq!(parse_size("0"), Ok(0));
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("1K"), Ok(1024));
+        assert_eq!(parse_size("512M"), Ok(512 * 1024 * 1024));
+        assert_eq!(parse_size("2g"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("1T"), Ok(1024 * 1024 * 1024 * 1024));
+
+        for value in [
+            "",
+            "M",
+            "-1",
+            "1.5G",
+            "10MB",
+            "1 G",
+            "99999999999999999999T",
+        ] {
+            parse_size(value).expect_err("Parsing should fail");
+        }
+    }
 
-    let indent: String = " ".repeat(indent_width);
-    let separation = "  "; // separation between argument name and argument description
-    let next_line_indent_width = indent_width + longest_arg_name + separation.len();
-    let next_line_indent: String = " ".repeat(next_line_indent_width);
-
-    arguments
-        .iter()
-        .map(|x| {
-            let max_desc_width = max_line_width - next_line_indent_width;
-
-            let arg_name = x.0;
-            let arg_desc = &x.1;
-            let arg_desc = textwrap::wrap(
-                arg_desc,
-                textwrap::Options::new(max_desc_width).subsequent_indent(&next_line_indent),
-            )
-            .join("\n");
-            format!(
-                "{indent}{arg_name:arg_name_width$}{separation}{arg_desc}",
-                arg_name_width = longest_arg_name
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("\n")
-}
+    #[test]
+    fn percentages_are_parsed() {
+        assert_eq!(parse_percent("0"), Ok(0));
+        assert_eq!(parse_percent("80"), Ok(80));
+        assert_eq!(parse_percent("80%"), Ok(80));
+        assert_eq!(parse_percent("100%"), Ok(100));
+
+        for value in ["", "%", "101%", "-1", "80.5%", "80 %", "%80"] {
+            parse_percent(value).expect_err("Parsing should fail");
+        }
+    }
 
-pub fn format_text(text: &str, max_line_width: usize) -> String {
-    let text = textwrap::dedent(text);
-    textwrap::refill(&text, max_line_width)
+    #[test]
+    fn durations_are_formatted() {
+        assert_eq!(format_duration(Duration::ZERO), "0ms");
+        assert_eq!(format_duration(Duration::from_millis(500)), "500ms");
+        assert_eq!(format_duration(Duration::from_millis(1000)), "1s");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1500ms");
+        assert_eq!(format_duration(Duration::from_secs(120)), "2m");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h");
+    }
+
+    #[test]
+    fn formatted_durations_are_parsed_back() {
+        for milliseconds in [0, 1, 999, 1000, 61_000, 3_600_000, 86_400_001] {
+            let duration = Duration::from_millis(milliseconds);
+            assert_eq!(parse_duration(&format_duration(duration)), Ok(duration));
+        }
+    }
 }
@@ -0,0 +1,19 @@
+/// Time a client spent in each status within a window of its history, sent in response to GetAvailability. Time
+/// without a known status, e.g. before the client first reported, is not counted at all.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ClientAvailability {
+    pub name: String,
+    pub ok_seconds: u64,
+    pub error_seconds: u64,
+}
+
+impl ClientAvailability {
+    /// Returns percentage of the known time the client was ok, if any time is known
+    pub fn get_percentage(&self) -> Option<f64> {
+        let known_seconds = self.ok_seconds + self.error_seconds;
+        match known_seconds {
+            0 => None,
+            _ => Some(self.ok_seconds as f64 * 100.0 / known_seconds as f64),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::severity::Severity;
+
+/// Status of a single client, sent in response to GetClientDetails
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ClientDetails {
+    pub name: String,
+    pub status: Option<Result<(), String>>, // None if the client hasn't reported any status yet
+    pub pending: bool,                      // first status is being determined, if status is None
+    pub warning: bool,                      // the error is only a warning, if status is Err
+    pub age_seconds: u64,                   // time elapsed since the last status report
+    pub tags: Vec<String>,
+}
+
+impl ClientDetails {
+    /// Creates details of an untagged client, which has just reported the status
+    pub fn new(name: &str, status: Option<Result<(), &str>>) -> Self {
+        ClientDetails {
+            name: name.to_owned(),
+            status: status.map(|x| x.map_err(|err| err.to_owned())),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|x| x.to_string()).collect();
+        self
+    }
+
+    pub fn with_age(mut self, age_seconds: u64) -> Self {
+        self.age_seconds = age_seconds;
+        self
+    }
+
+    pub fn with_warning(mut self) -> Self {
+        self.warning = true;
+        self
+    }
+
+    pub fn severity(&self) -> Severity {
+        match Severity::from_status(&self.status, self.pending) {
+            Severity::Error if self.warning => Severity::Warning,
+            severity => severity,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::client_details::ClientDetails;
+use crate::severity::Severity;
+use serde::{Deserialize, Serialize};
+
+/// Status of a client as presented outside of the protocol, e.g. in JSON output of the client or by the SDK. Unlike in
+/// ClientDetails, the status is split into a severity and a message of an error or a warning.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub name: String,
+    pub status: Severity,
+    pub message: Option<String>, // only for errors and warnings
+    pub age: Option<u64>, // seconds since the last status report, if the client has reported any
+    pub tags: Vec<String>,
+}
+
+impl From<&ClientDetails> for ClientInfo {
+    fn from(details: &ClientDetails) -> Self {
+        ClientInfo {
+            name: details.name.clone(),
+            status: details.severity(),
+            message: details.status.clone().and_then(Result::err),
+            age: details.status.as_ref().map(|_| details.age_seconds),
+            tags: details.tags.clone(),
+        }
+    }
+}
+
+impl From<ClientDetails> for ClientInfo {
+    fn from(details: ClientDetails) -> Self {
+        ClientInfo {
+            status: details.severity(),
+            age: details.status.as_ref().map(|_| details.age_seconds),
+            message: details.status.and_then(Result::err),
+            name: details.name,
+            tags: details.tags,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_info_is_converted_from_client_details() {
+        let details = ClientDetails {
+            name: "Backup".to_owned(),
+            status: Some(Err("No space left".to_owned())),
+            pending: false,
+            warning: false,
+            age_seconds: 5,
+            tags: vec!["db".to_owned()],
+        };
+        let info = ClientInfo {
+            name: "Backup".to_owned(),
+            status: Severity::Error,
+            message: Some("No space left".to_owned()),
+            age: Some(5),
+            tags: vec!["db".to_owned()],
+        };
+        assert_eq!(ClientInfo::from(&details), info);
+        assert_eq!(ClientInfo::from(details), info);
+
+        let details = ClientDetails {
+            name: "Cleanup".to_owned(),
+            status: None,
+            pending: true,
+            warning: false,
+            age_seconds: 5,
+            tags: Vec::new(),
+        };
+        let info = ClientInfo::from(details);
+        assert_eq!(
+            (info.status, info.message, info.age),
+            (Severity::Pending, None, None)
+        );
+    }
+
+    #[test]
+    fn client_info_is_serialized_as_json() {
+        let info = ClientInfo {
+            name: "Backup".to_owned(),
+            status: Severity::Ok,
+            message: None,
+            age: Some(5),
+            tags: vec!["db".to_owned()],
+        };
+        let json = r#"{"name":"Backup","status":"ok","message":null,"age":5,"tags":["db"]}"#;
+        assert_eq!(serde_json::to_string(&info).unwrap(), json);
+        assert_eq!(serde_json::from_str::<ClientInfo>(json).unwrap(), info);
+    }
+}
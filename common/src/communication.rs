@@ -36,19 +36,42 @@ impl ServerCommand {
     pub async fn receive_async<T: AsyncBufRead + Unpin>(
         input_stream: &mut T,
     ) -> Result<ServerCommand, CommunicationError> {
+        // The stream returns the same unconsumed bytes until they're consumed, so bytes of a command which hasn't
+        // arrived completely are moved aside before reading more. This also allows commands larger than the buffer.
+        let mut pending = Vec::new();
         loop {
-            let buffer = input_stream.fill_buf().await?;
+            // Peer killed with unread data resets the connection instead of closing it
+            let buffer = match input_stream.fill_buf().await {
+                Ok(buffer) => buffer,
+                Err(err) if err.kind() == std::io::ErrorKind::ConnectionReset => {
+                    return Err(CommunicationError::SocketDisconnected);
+                }
+                Err(err) => return Err(err.into()),
+            };
             if buffer.is_empty() {
                 return Err(CommunicationError::SocketDisconnected);
             }
 
-            match ServerCommand::from_bytes(buffer) {
+            let pending_len = pending.len();
+            let parse_result = if pending.is_empty() {
+                ServerCommand::from_bytes(buffer)
+            } else {
+                pending.extend_from_slice(buffer);
+                ServerCommand::from_bytes(&pending)
+            };
+            match parse_result {
                 Ok(parse_result) => {
-                    input_stream.consume(parse_result.bytes_used);
+                    input_stream.consume(parse_result.bytes_used - pending_len);
                     break Ok(parse_result.command);
                 }
                 Err(err) => match err {
-                    ServerCommandError::TooFewBytes => continue,
+                    ServerCommandError::TooFewBytes => {
+                        if pending_len == 0 {
+                            pending.extend_from_slice(buffer);
+                        }
+                        let buffer_len = buffer.len();
+                        input_stream.consume(buffer_len);
+                    }
                     _ => break Err(err.into()),
                 },
             }
@@ -60,9 +83,50 @@ impl ServerCommand {
         stream: &mut (impl AsyncWrite + Unpin),
     ) -> Result<(), CommunicationError> {
         let command_bytes = self.to_bytes();
-        match stream.write(&command_bytes[0..]).await {
+        match stream.write_all(&command_bytes[0..]).await {
             Ok(_) => Ok(()),
             Err(_) => Err(CommunicationError::SocketDisconnected),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClientDetails;
+    use tokio::io::BufReader;
+
+    #[tokio::test]
+    async fn commands_larger_than_buffer_are_received() {
+        let details = (0..1000)
+            .map(|i| ClientDetails::new(&format!("Client{i}"), Some(Err("No space left"))))
+            .collect::<Vec<_>>();
+        let command = ServerCommand::ClientDetails(details);
+        assert!(command.to_bytes().len() > 8 * 1024);
+
+        let (client, server) = tokio::io::duplex(1024);
+        let (input_stream, _) = tokio::io::split(client);
+        let mut input_stream = BufReader::with_capacity(1024, input_stream);
+        let (_, mut output_stream) = tokio::io::split(server);
+        let sent_command = command.clone();
+        let sender = tokio::spawn(async move {
+            sent_command.send_async(&mut output_stream).await.unwrap();
+            ServerCommand::Abort
+                .send_async(&mut output_stream)
+                .await
+                .unwrap();
+            output_stream
+        });
+
+        let receive = async {
+            let first = ServerCommand::receive_async(&mut input_stream).await;
+            let second = ServerCommand::receive_async(&mut input_stream).await;
+            (first.unwrap(), second.unwrap())
+        };
+        let received = tokio::time::timeout(std::time::Duration::from_secs(5), receive)
+            .await
+            .expect("Command should be received");
+        assert_eq!(received, (command, ServerCommand::Abort));
+        sender.await.unwrap();
+    }
+}
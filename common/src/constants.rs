@@ -1,15 +1,94 @@
+use std::net::{IpAddr, Ipv4Addr};
 use std::time::Duration;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub const HELP_MESSAGE_MAX_LINE_WIDTH: usize = 120;
-pub const HELP_MESSAGE_BASIC_INDENT_WIDTH: usize = 2;
-
 pub const DEFAULT_PORT: u16 = 10005;
+pub const DEFAULT_SERVER_ADDRESS: &str = "127.0.0.1";
+pub const DEFAULT_BIND_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
 pub const DEFAULT_CONNECTION_BACKOFF: Duration = Duration::from_millis(500);
 pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_millis(1000);
 pub const DEFAULT_WATCH_DELAY: Duration = Duration::from_millis(0);
+pub const DEFAULT_WATCH_JITTER: Duration = Duration::from_millis(0);
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(0);
+pub const DEFAULT_FAILURES_BEFORE_ERROR: u32 = 1;
+pub const DEFAULT_SUCCESSES_BEFORE_OK: u32 = 1;
 pub const DEFAULT_INCLUDE_NAMES: bool = false;
 pub const DEFAULT_SHELL: bool = false;
+#[cfg(windows)]
+pub const DEFAULT_SHELL_COMMAND: &str = "cmd /C";
+#[cfg(not(windows))]
+pub const DEFAULT_SHELL_COMMAND: &str = "sh -c";
+pub const DEFAULT_SSH_COMMAND: &str = "ssh -o BatchMode=yes";
+pub const SSH_CONNECTION_ERROR_EXIT_CODE: i32 = 255;
+pub const NAGIOS_WARNING_EXIT_CODE: i32 = 1;
 pub const DEFAULT_LOG_EVERY_STATUS: bool = false;
 pub const DEFAULT_MAXIMUM_SERVER_CONNECTION_ATTEMPTS: u32 = 0;
+pub const STATUS_CACHE_CAPACITY: usize = 1024;
+pub const OFFLINE_STATUS_BUFFER_CAPACITY: usize = 256;
+pub const STATUS_CHANGES_CAPACITY: usize = 256;
+pub const HISTORY_EXPORT_CHUNK_BYTES: usize = 4 * 1024;
+pub const DEFAULT_TAIL_QUIET_PERIOD: Duration = Duration::from_secs(5 * 60);
+pub const TAIL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+pub const FILE_CHANGE_DEBOUNCE: Duration = Duration::from_millis(100);
+pub const MAX_CAPTURED_OUTPUT_BYTES: usize = 64 * 1024;
+pub const MAX_CAPTURED_OUTPUT_LINES: usize = 1000;
+pub const DEFAULT_TOP_INTERVAL: Duration = Duration::from_millis(1000);
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+pub const FLAPPING_WINDOW: Duration = Duration::from_secs(10 * 60);
+pub const FLAPPING_THRESHOLD: usize = 4;
+pub const DEFAULT_BADGE_LABEL: &str = "health";
+pub const BADGE_OVERALL_FILE: &str = "overall.svg";
+pub const BADGE_CLIENTS_DIRECTORY: &str = "clients";
+pub const DEFAULT_METRICS_PREFIX: &str = "checkmate";
+pub const DEFAULT_METRICS_INTERVAL: Duration = Duration::from_secs(10);
+pub const STATSD_MAX_DATAGRAM_SIZE: usize = 1432;
+pub const DEFAULT_INFLUX_MEASUREMENT: &str = "checkmate";
+pub const DEFAULT_INFLUX_INTERVAL: Duration = Duration::from_secs(10);
+pub const INFLUX_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const CRON_WRAP_OUTPUT_TAIL_LINES: usize = 10;
+pub const CRON_WRAP_SPAWN_ERROR_EXIT_CODE: i32 = 127;
+pub const DEFAULT_PING_COUNT: u32 = 3;
+pub const DEFAULT_PING_TIMEOUT: Duration = Duration::from_millis(1000);
+pub const DEFAULT_PING_MAX_LOSS_PERCENT: u8 = 50;
+pub const PING_INTERVAL: Duration = Duration::from_millis(200);
+pub const DEFAULT_DNS_TIMEOUT: Duration = Duration::from_millis(2000);
+pub const DNS_PORT: u16 = 53;
+pub const DEFAULT_DISK_CRITICAL_PERCENT: u8 = 90;
+pub const DEFAULT_SYSTEM_MAX_MEMORY_PERCENT: u8 = 90;
+pub const SYSTEM_CPU_SAMPLE_PERIOD: Duration = Duration::from_millis(1000);
+#[cfg(not(windows))]
+pub const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+#[cfg(windows)]
+pub const DEFAULT_DOCKER_SOCKET: &str = r"\\.\pipe\docker_engine";
+pub const DOCKER_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const PLUGIN_PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PLUGIN_TIMEOUT: Duration = Duration::from_millis(10000);
+pub const DEFAULT_WASM_FUEL: u64 = 1_000_000_000;
+pub const DEFAULT_WASM_MAX_MEMORY: u64 = 64 << 20;
+pub const SCRIPT_MAX_OPERATIONS: u64 = 10_000_000;
+pub const CONTROL_SOCKET_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const OTLP_EXPORT_TIMEOUT: Duration = Duration::from_millis(3000);
+pub const DEFAULT_REDIS_CHANNEL: &str = "checkmate";
+pub const REDIS_MIRROR_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+pub const HTTP_REQUEST_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const HTTP_MAX_REQUEST_SIZE: u64 = 64 * 1024;
+pub const HTTP_PING_CHECK_INTERVAL: Duration = Duration::from_millis(1000);
+pub const HTTP_PING_DEFAULT_FAILURE: &str = "Ping reported failure";
+pub const INCIDENT_FEED_CAPACITY: usize = 50;
+pub const NRPE_REQUEST_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const ZABBIX_DEFAULT_PORT: u16 = 10051;
+pub const DEFAULT_ZABBIX_HOST: &str = "checkmate";
+pub const ZABBIX_TIMEOUT: Duration = Duration::from_millis(5000);
+pub const ZABBIX_MAX_RESPONSE_SIZE: usize = 64 * 1024;
+pub const FEDERATION_SYNC_INTERVAL: Duration = Duration::from_millis(10000);
+pub const FEDERATION_RECONNECT_INTERVAL: Duration = Duration::from_millis(5000);
+pub const SERVER_AGGREGATION_BUFFER_SIZE: usize = 64 * 1024;
+pub const REPLICATION_SYNC_INTERVAL: Duration = Duration::from_millis(10000);
+pub const REPLICATION_RECONNECT_INTERVAL: Duration = Duration::from_millis(1000);
+pub const DEFAULT_PROMOTION_TIMEOUT: Duration = Duration::from_millis(30000);
+pub const WATCH_TIMEOUT_GRACE_PERIOD: Duration = Duration::from_millis(2000);
+pub const SERVER_DISCOVERY_SERVICE: &str = "_checkmate._tcp";
+pub const DRAIN_PROGRESS_INTERVAL: Duration = Duration::from_millis(1000);
+pub const DEFAULT_SELF_MONITORING_NAME: &str = "_checkmate_server";
+pub const HISTORY_PRUNING_INTERVAL: Duration = Duration::from_secs(3600);
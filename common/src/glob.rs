@@ -0,0 +1,64 @@
+/// Checks whether text matches a glob pattern. Supported wildcards are '*' matching any sequence of characters
+/// (including an empty one) and '?' matching exactly one character. All other characters are matched literally.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    // Greedy matching with backtracking to the most recent '*'
+    let (mut pattern_index, mut text_index) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while text_index < text.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                last_star = Some((pattern_index, text_index));
+                pattern_index += 1;
+            }
+            Some(&c) if c == '?' || c == text[text_index] => {
+                pattern_index += 1;
+                text_index += 1;
+            }
+            _ => match last_star {
+                Some((star_pattern_index, star_text_index)) => {
+                    pattern_index = star_pattern_index + 1;
+                    text_index = star_text_index + 1;
+                    last_star = Some((star_pattern_index, star_text_index + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_index..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_patterns_are_matched() {
+        assert!(glob_matches("", ""));
+        assert!(glob_matches("Watcher", "Watcher"));
+        assert!(!glob_matches("Watcher", "watcher"));
+        assert!(!glob_matches("Watcher", "Watcher2"));
+        assert!(!glob_matches("Watcher2", "Watcher"));
+        assert!(!glob_matches("", "Watcher"));
+    }
+
+    #[test]
+    fn wildcard_patterns_are_matched() {
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*", "Watcher"));
+        assert!(glob_matches("Watch*", "Watcher"));
+        assert!(glob_matches("*er", "Watcher"));
+        assert!(glob_matches("*tch*", "Watcher"));
+        assert!(glob_matches("W*t*r", "Watcher"));
+        assert!(glob_matches("Watche?", "Watcher"));
+        assert!(glob_matches("???????", "Watcher"));
+        assert!(glob_matches("db-*-backup", "db-eu-west-backup"));
+        assert!(glob_matches("*a*a*a", "aaaaa"));
+        assert!(!glob_matches("Watche?", "Watche"));
+        assert!(!glob_matches("*x*", "Watcher"));
+        assert!(!glob_matches("db-*-backup", "db-eu-west-backups"));
+        assert!(!glob_matches("?", ""));
+    }
+}
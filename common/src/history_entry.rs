@@ -0,0 +1,11 @@
+use crate::severity::Severity;
+use serde::{Deserialize, Serialize};
+
+/// Status change recorded in the history of the server, sent in response to ExportHistory.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time_ms: u64, // unix time in milliseconds
+    pub name: String,
+    pub status: Severity,
+    pub message: Option<String>, // only for errors
+}
@@ -1,9 +1,29 @@
+mod api_key_details;
 mod arg_parsing;
+mod client_availability;
+mod client_details;
+mod client_info;
 mod communication;
 pub mod constants;
+mod glob;
+mod history_entry;
+pub mod secrets;
 mod server_command;
+mod server_statistics;
+mod severity;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tls;
 
+pub use api_key_details::ApiKeyDetails;
 pub use arg_parsing::*;
+pub use client_availability::ClientAvailability;
+pub use client_details::ClientDetails;
+pub use client_info::ClientInfo;
 pub use communication::*;
+pub use glob::glob_matches;
+pub use history_entry::HistoryEntry;
 
 pub use server_command::{ServerCommand, ServerCommandParse, ServerCommandError};
+pub use server_statistics::ServerStatistics;
+pub use severity::Severity;
@@ -0,0 +1,192 @@
+// Secrets referenced from configuration instead of being written in it in plain text. A value of "env:<NAME>" is taken
+// from environment variable NAME and a value of "secret:<NAME>" from an encrypted secrets file. Other values are used as
+// they are. The secrets file has lines of <NAME>=<VALUE>, where values are encrypted with ChaCha20-Poly1305 under a key
+// kept in a separate file. The secrets file can then be distributed along with the configuration and only the key has
+// to be protected. Names of secrets are authenticated along with their values, so values can't be swapped.
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+
+pub const ENV_PREFIX: &str = "env:";
+pub const SECRET_PREFIX: &str = "secret:";
+const KEY_LENGTH: usize = 32;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn fill_random(bytes: &mut [u8]) -> Result<(), String> {
+    SystemRandom::new()
+        .fill(bytes)
+        .map_err(|_| "could not generate random bytes".to_owned())
+}
+
+fn parse_key(key: &str) -> Result<LessSafeKey, String> {
+    let bytes = from_hex(key.trim())
+        .filter(|x| x.len() == KEY_LENGTH)
+        .ok_or("invalid secrets key, expected 64 hexadecimal digits")?;
+    let key = UnboundKey::new(&CHACHA20_POLY1305, &bytes).map_err(|_| "invalid secrets key")?;
+    Ok(LessSafeKey::new(key))
+}
+
+pub fn generate_key() -> Result<String, String> {
+    let mut key = [0u8; KEY_LENGTH];
+    fill_random(&mut key)?;
+    Ok(to_hex(&key))
+}
+
+pub fn load_key(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map(|key| key.trim().to_owned())
+        .map_err(|err| format!("could not read secrets key from {path}: {err}"))
+}
+
+// Returns the encrypted value in hexadecimal, prefixed with its random nonce
+pub fn encrypt(key: &str, name: &str, value: &str) -> Result<String, String> {
+    let key = parse_key(key)?;
+    let mut nonce = [0u8; NONCE_LEN];
+    fill_random(&mut nonce)?;
+    let mut data = value.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(name.as_bytes()),
+        &mut data,
+    )
+    .map_err(|_| format!("could not encrypt secret {name}"))?;
+    Ok(to_hex(&[&nonce[..], &data].concat()))
+}
+
+pub fn decrypt(key: &str, name: &str, encrypted: &str) -> Result<String, String> {
+    let key = parse_key(key)?;
+    let bytes = from_hex(encrypted)
+        .filter(|x| x.len() >= NONCE_LEN)
+        .ok_or_else(|| format!("invalid encrypted value of secret {name}"))?;
+    let (nonce, data) = bytes.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).expect("Nonce should have a valid length");
+    let mut data = data.to_vec();
+    let value = key
+        .open_in_place(nonce, Aad::from(name.as_bytes()), &mut data)
+        .map_err(|_| format!("could not decrypt secret {name}, the key may be wrong"))?;
+    String::from_utf8(value.to_vec()).map_err(|_| format!("secret {name} is not a text"))
+}
+
+pub struct SecretsFile {
+    pub path: String,
+    pub key_path: String,
+}
+
+impl SecretsFile {
+    pub fn get(&self, name: &str) -> Result<String, String> {
+        let path = &self.path;
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| format!("could not read secrets from {path}: {err}"))?;
+        let encrypted = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .find(|(line_name, _)| line_name.trim() == name)
+            .map(|(_, encrypted)| encrypted.trim())
+            .ok_or_else(|| format!("secret {name} not found in {path}"))?;
+        decrypt(&load_key(&self.key_path)?, name, encrypted)
+    }
+}
+
+pub fn resolve_secret<F>(
+    value: &str,
+    secrets_file: Option<&SecretsFile>,
+    get_var: F,
+) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if let Some(name) = value.strip_prefix(ENV_PREFIX) {
+        get_var(name).ok_or_else(|| format!("environment variable {name} is not set"))
+    } else if let Some(name) = value.strip_prefix(SECRET_PREFIX) {
+        match secrets_file {
+            Some(secrets_file) => secrets_file.get(name),
+            None => Err(format!("secret {name} requires a secrets file")),
+        }
+    } else {
+        Ok(value.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_encrypted_and_decrypted() {
+        let key = generate_key().unwrap();
+        let encrypted = encrypt(&key, "influx_token", "s3cr3t").unwrap();
+        assert!(!encrypted.contains(&to_hex(b"s3cr3t")));
+        assert_eq!(
+            decrypt(&key, "influx_token", &encrypted),
+            Ok("s3cr3t".to_owned())
+        );
+        assert_ne!(encrypted, encrypt(&key, "influx_token", "s3cr3t").unwrap());
+
+        assert!(decrypt(&generate_key().unwrap(), "influx_token", &encrypted).is_err());
+        assert!(decrypt(&key, "api_key", &encrypted).is_err());
+        assert!(decrypt(&key, "influx_token", &encrypted[2..]).is_err());
+        assert!(decrypt(&key, "influx_token", "0123").is_err());
+        assert!(encrypt("0123", "influx_token", "s3cr3t").is_err());
+    }
+
+    #[test]
+    fn references_are_resolved() {
+        let get_var = |name: &str| match name {
+            "INFLUX_TOKEN" => Some("s3cr3t".to_owned()),
+            _ => None,
+        };
+        assert_eq!(
+            resolve_secret("env:INFLUX_TOKEN", None, get_var),
+            Ok("s3cr3t".to_owned())
+        );
+        assert!(resolve_secret("env:REDIS_URL", None, get_var).is_err());
+        assert!(resolve_secret("secret:api_key", None, get_var).is_err());
+        assert_eq!(
+            resolve_secret("plain", None, get_var),
+            Ok("plain".to_owned())
+        );
+    }
+
+    #[test]
+    fn secrets_are_read_from_file() {
+        let directory = std::env::temp_dir();
+        let process_id = std::process::id();
+        let path = directory.join(format!("check_mate_secrets_{process_id}"));
+        let key_path = directory.join(format!("check_mate_secrets_key_{process_id}"));
+        let key = generate_key().unwrap();
+        let text = format!(
+            "# secrets of the client\napi_key = {}\n",
+            encrypt(&key, "api_key", "3f9a1c7e.0123456789abcdef").unwrap()
+        );
+        std::fs::write(&path, text).unwrap();
+        std::fs::write(&key_path, format!("{key}\n")).unwrap();
+
+        let secrets_file = SecretsFile {
+            path: path.to_str().unwrap().to_owned(),
+            key_path: key_path.to_str().unwrap().to_owned(),
+        };
+        assert_eq!(
+            resolve_secret("secret:api_key", Some(&secrets_file), |_| None),
+            Ok("3f9a1c7e.0123456789abcdef".to_owned())
+        );
+        assert!(secrets_file.get("influx_token").is_err());
+
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(key_path).unwrap();
+    }
+}
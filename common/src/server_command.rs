@@ -1,7 +1,12 @@
+use crate::api_key_details::ApiKeyDetails;
+use crate::client_availability::ClientAvailability;
+use crate::client_details::ClientDetails;
+use crate::history_entry::HistoryEntry;
+use crate::server_statistics::ServerStatistics;
 use std::string::FromUtf8Error;
 
 /// Command sent from client to server
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ServerCommand {
     // Sent by client
     Abort,
@@ -12,11 +17,40 @@ pub enum ServerCommand {
     RefreshAllClients,
     ListClients,
     SetName(String),
+    GetServerStatistics,
+    ReplayedStatus(u64, Result<(), String>), // status buffered while offline, with unix timestamp in milliseconds
+    GetClientDetails,
+    Subscribe,
+    SetTags(Vec<String>),
+    SetStatusPending,
+    PushStatus(Result<(), String>), // status kept by the server after the client disconnects
+    ClearClientByName(String),
+    SetNamespace(String),
+    SelectAllNamespaces, // scope queries and refreshes to clients of all namespaces instead of the own one
+    Authenticate(String), // API key identifying the client
+    CreateApiKey(String, String), // identity and label
+    ListApiKeys,
+    LabelApiKey(String, String), // id and new label
+    RevokeApiKey(String),        // id
+    Drain,                // stop accepting new connections, but keep serving the current ones
+    GetAvailability(u64), // window in seconds, ending now
+    CompactHistory,
+    ExportHistory(u64, u64), // start and end as unix timestamps in milliseconds
+    SetStatusWarning(String),
 
     // Sent by server
     Statuses(Vec<String>),
     Refresh,
     Clients(Vec<String>),
+    ServerStatistics(ServerStatistics),
+    ClientDetails(Vec<ClientDetails>),
+    StatusChanged(ClientDetails),
+    ApiKeyResult(Result<String, String>), // the new key after creating it, otherwise its id
+    ApiKeys(Vec<ApiKeyDetails>),
+    DrainProgress(u64), // number of other clients still connected
+    Availability(Result<Vec<ClientAvailability>, String>),
+    HistoryCompacted(Result<u64, String>), // number of removed statuses
+    HistoryExport(Result<Vec<HistoryEntry>, String>), // one chunk of the export, an empty one ends it
 }
 
 #[derive(Debug, PartialEq)]
@@ -24,6 +58,7 @@ pub enum ServerCommandError {
     TooFewBytes,
     InvalidStringEncoding,
     InvalidBoolean,
+    InvalidSeverity,
     UnknownCommand,
 }
 
@@ -51,6 +86,35 @@ impl ServerCommand {
     pub(crate) const ID_REFRESH: u8 = 9;
     pub(crate) const ID_LIST_CLIENTS: u8 = 10;
     pub(crate) const ID_CLIENTS: u8 = 11;
+    pub(crate) const ID_GET_SERVER_STATISTICS: u8 = 12;
+    pub(crate) const ID_SERVER_STATISTICS: u8 = 13;
+    pub(crate) const ID_REPLAYED_STATUS: u8 = 14;
+    pub(crate) const ID_GET_CLIENT_DETAILS: u8 = 15;
+    pub(crate) const ID_CLIENT_DETAILS: u8 = 16;
+    pub(crate) const ID_SUBSCRIBE: u8 = 17;
+    pub(crate) const ID_STATUS_CHANGED: u8 = 18;
+    pub(crate) const ID_SET_TAGS: u8 = 19;
+    pub(crate) const ID_SET_STATUS_PENDING: u8 = 20;
+    pub(crate) const ID_PUSH_STATUS: u8 = 21;
+    pub(crate) const ID_CLEAR_CLIENT_BY_NAME: u8 = 22;
+    pub(crate) const ID_SET_NAMESPACE: u8 = 23;
+    pub(crate) const ID_SELECT_ALL_NAMESPACES: u8 = 24;
+    pub(crate) const ID_AUTHENTICATE: u8 = 25;
+    pub(crate) const ID_CREATE_API_KEY: u8 = 26;
+    pub(crate) const ID_LIST_API_KEYS: u8 = 27;
+    pub(crate) const ID_LABEL_API_KEY: u8 = 28;
+    pub(crate) const ID_REVOKE_API_KEY: u8 = 29;
+    pub(crate) const ID_API_KEY_RESULT: u8 = 30;
+    pub(crate) const ID_API_KEYS: u8 = 31;
+    pub(crate) const ID_DRAIN: u8 = 32;
+    pub(crate) const ID_DRAIN_PROGRESS: u8 = 33;
+    pub(crate) const ID_GET_AVAILABILITY: u8 = 34;
+    pub(crate) const ID_AVAILABILITY: u8 = 35;
+    pub(crate) const ID_COMPACT_HISTORY: u8 = 36;
+    pub(crate) const ID_HISTORY_COMPACTED: u8 = 37;
+    pub(crate) const ID_EXPORT_HISTORY: u8 = 38;
+    pub(crate) const ID_HISTORY_EXPORT: u8 = 39;
+    pub(crate) const ID_SET_STATUS_WARNING: u8 = 40;
 
     pub fn from_bytes(bytes: &[u8]) -> Result<ServerCommandParse, ServerCommandError> {
         let mut bytes_used = 0;
@@ -74,7 +138,13 @@ impl ServerCommand {
         let take_dword = |index: &mut usize| -> Result<u32, ServerCommandError> {
             let b = take_bytes(index, 4)?;
             let b = b.try_into().expect("Slice must have a length of 4");
-            let b = u32::from_ne_bytes(b);
+            let b = u32::from_le_bytes(b);
+            Ok(b)
+        };
+        let take_qword = |index: &mut usize| -> Result<u64, ServerCommandError> {
+            let b = take_bytes(index, 8)?;
+            let b = b.try_into().expect("Slice must have a length of 8");
+            let b = u64::from_le_bytes(b);
             Ok(b)
         };
         let take_string = |index: &mut usize| -> Result<String, ServerCommandError> {
@@ -91,6 +161,26 @@ impl ServerCommand {
             }
             Ok(strings)
         };
+        let take_client_details = |index: &mut usize| -> Result<ClientDetails, ServerCommandError> {
+            let name = take_string(index)?;
+            let (status, pending, warning) = match take_bool(index)? {
+                false => (None, take_bool(index)?, false),
+                true => match take_bool(index)? {
+                    false => (Some(Ok(())), false, false),
+                    true => (Some(Err(take_string(index)?)), false, take_bool(index)?),
+                },
+            };
+            let age_seconds = take_qword(index)?;
+            let tags = take_strings(index)?;
+            Ok(ClientDetails {
+                name,
+                status,
+                pending,
+                warning,
+                age_seconds,
+                tags,
+            })
+        };
 
         let command_type = take_bytes(&mut bytes_used, 1)?[0];
         let command = match command_type {
@@ -110,8 +200,151 @@ impl ServerCommand {
             ServerCommand::ID_STATUSES => ServerCommand::Statuses(take_strings(&mut bytes_used)?),
             ServerCommand::ID_REFRESH => ServerCommand::Refresh,
             ServerCommand::ID_LIST_CLIENTS => ServerCommand::ListClients,
-            ServerCommand::ID_CLIENTS => {
-                ServerCommand::Clients(take_strings(&mut bytes_used)?)
+            ServerCommand::ID_CLIENTS => ServerCommand::Clients(take_strings(&mut bytes_used)?),
+            ServerCommand::ID_GET_SERVER_STATISTICS => ServerCommand::GetServerStatistics,
+            ServerCommand::ID_SERVER_STATISTICS => {
+                ServerCommand::ServerStatistics(ServerStatistics {
+                    uptime_seconds: take_qword(&mut bytes_used)?,
+                    connected_clients: take_qword(&mut bytes_used)?,
+                    commands_processed: take_qword(&mut bytes_used)?,
+                    statuses_stored: take_qword(&mut bytes_used)?,
+                    notifications_sent: take_qword(&mut bytes_used)?,
+                })
+            }
+            ServerCommand::ID_REPLAYED_STATUS => {
+                let timestamp = take_qword(&mut bytes_used)?;
+                let status = match take_bool(&mut bytes_used)? {
+                    false => Ok(()),
+                    true => Err(take_string(&mut bytes_used)?),
+                };
+                ServerCommand::ReplayedStatus(timestamp, status)
+            }
+            ServerCommand::ID_GET_CLIENT_DETAILS => ServerCommand::GetClientDetails,
+            ServerCommand::ID_CLIENT_DETAILS => {
+                let details_count = take_dword(&mut bytes_used)?;
+                let mut details = Vec::new();
+                for _ in 0..details_count {
+                    details.push(take_client_details(&mut bytes_used)?);
+                }
+                ServerCommand::ClientDetails(details)
+            }
+            ServerCommand::ID_SUBSCRIBE => ServerCommand::Subscribe,
+            ServerCommand::ID_SET_TAGS => ServerCommand::SetTags(take_strings(&mut bytes_used)?),
+            ServerCommand::ID_SET_STATUS_PENDING => ServerCommand::SetStatusPending,
+            ServerCommand::ID_PUSH_STATUS => {
+                let status = match take_bool(&mut bytes_used)? {
+                    false => Ok(()),
+                    true => Err(take_string(&mut bytes_used)?),
+                };
+                ServerCommand::PushStatus(status)
+            }
+            ServerCommand::ID_CLEAR_CLIENT_BY_NAME => {
+                ServerCommand::ClearClientByName(take_string(&mut bytes_used)?)
+            }
+            ServerCommand::ID_SET_NAMESPACE => {
+                ServerCommand::SetNamespace(take_string(&mut bytes_used)?)
+            }
+            ServerCommand::ID_SELECT_ALL_NAMESPACES => ServerCommand::SelectAllNamespaces,
+            ServerCommand::ID_STATUS_CHANGED => {
+                ServerCommand::StatusChanged(take_client_details(&mut bytes_used)?)
+            }
+            ServerCommand::ID_AUTHENTICATE => {
+                ServerCommand::Authenticate(take_string(&mut bytes_used)?)
+            }
+            ServerCommand::ID_CREATE_API_KEY => ServerCommand::CreateApiKey(
+                take_string(&mut bytes_used)?,
+                take_string(&mut bytes_used)?,
+            ),
+            ServerCommand::ID_LIST_API_KEYS => ServerCommand::ListApiKeys,
+            ServerCommand::ID_LABEL_API_KEY => ServerCommand::LabelApiKey(
+                take_string(&mut bytes_used)?,
+                take_string(&mut bytes_used)?,
+            ),
+            ServerCommand::ID_REVOKE_API_KEY => {
+                ServerCommand::RevokeApiKey(take_string(&mut bytes_used)?)
+            }
+            ServerCommand::ID_API_KEY_RESULT => {
+                let result = match take_bool(&mut bytes_used)? {
+                    false => Ok(take_string(&mut bytes_used)?),
+                    true => Err(take_string(&mut bytes_used)?),
+                };
+                ServerCommand::ApiKeyResult(result)
+            }
+            ServerCommand::ID_API_KEYS => {
+                let keys_count = take_dword(&mut bytes_used)?;
+                let mut keys = Vec::new();
+                for _ in 0..keys_count {
+                    keys.push(ApiKeyDetails {
+                        id: take_string(&mut bytes_used)?,
+                        identity: take_string(&mut bytes_used)?,
+                        label: take_string(&mut bytes_used)?,
+                    });
+                }
+                ServerCommand::ApiKeys(keys)
+            }
+            ServerCommand::ID_DRAIN => ServerCommand::Drain,
+            ServerCommand::ID_DRAIN_PROGRESS => {
+                ServerCommand::DrainProgress(take_qword(&mut bytes_used)?)
+            }
+            ServerCommand::ID_GET_AVAILABILITY => {
+                ServerCommand::GetAvailability(take_qword(&mut bytes_used)?)
+            }
+            ServerCommand::ID_AVAILABILITY => {
+                let result = match take_bool(&mut bytes_used)? {
+                    false => {
+                        let clients_count = take_dword(&mut bytes_used)?;
+                        let mut clients = Vec::new();
+                        for _ in 0..clients_count {
+                            clients.push(ClientAvailability {
+                                name: take_string(&mut bytes_used)?,
+                                ok_seconds: take_qword(&mut bytes_used)?,
+                                error_seconds: take_qword(&mut bytes_used)?,
+                            });
+                        }
+                        Ok(clients)
+                    }
+                    true => Err(take_string(&mut bytes_used)?),
+                };
+                ServerCommand::Availability(result)
+            }
+            ServerCommand::ID_COMPACT_HISTORY => ServerCommand::CompactHistory,
+            ServerCommand::ID_HISTORY_COMPACTED => {
+                let result = match take_bool(&mut bytes_used)? {
+                    false => Ok(take_qword(&mut bytes_used)?),
+                    true => Err(take_string(&mut bytes_used)?),
+                };
+                ServerCommand::HistoryCompacted(result)
+            }
+            ServerCommand::ID_EXPORT_HISTORY => ServerCommand::ExportHistory(
+                take_qword(&mut bytes_used)?,
+                take_qword(&mut bytes_used)?,
+            ),
+            ServerCommand::ID_SET_STATUS_WARNING => {
+                ServerCommand::SetStatusWarning(take_string(&mut bytes_used)?)
+            }
+            ServerCommand::ID_HISTORY_EXPORT => {
+                let result = match take_bool(&mut bytes_used)? {
+                    false => {
+                        let entries_count = take_dword(&mut bytes_used)?;
+                        let mut entries = Vec::new();
+                        for _ in 0..entries_count {
+                            entries.push(HistoryEntry {
+                                time_ms: take_qword(&mut bytes_used)?,
+                                name: take_string(&mut bytes_used)?,
+                                status: take_string(&mut bytes_used)?
+                                    .parse()
+                                    .map_err(|_| ServerCommandError::InvalidSeverity)?,
+                                message: match take_bool(&mut bytes_used)? {
+                                    false => None,
+                                    true => Some(take_string(&mut bytes_used)?),
+                                },
+                            });
+                        }
+                        Ok(entries)
+                    }
+                    true => Err(take_string(&mut bytes_used)?),
+                };
+                ServerCommand::HistoryExport(result)
             }
             _ => return Err(ServerCommandError::UnknownCommand),
         };
@@ -138,6 +371,25 @@ impl ServerCommand {
         fn append_bool(bytes: &mut Vec<u8>, bool: &bool) {
             bytes.push(*bool as u8);
         }
+        fn append_qword(bytes: &mut Vec<u8>, qword: u64) {
+            bytes.extend_from_slice(&qword.to_le_bytes());
+        }
+        fn append_client_details(bytes: &mut Vec<u8>, details: &ClientDetails) {
+            append_string(bytes, &details.name);
+            append_bool(bytes, &details.status.is_some());
+            match details.status {
+                Some(ref status) => {
+                    append_bool(bytes, &status.is_err());
+                    if let Err(message) = status {
+                        append_string(bytes, message);
+                        append_bool(bytes, &details.warning);
+                    }
+                }
+                None => append_bool(bytes, &details.pending),
+            }
+            append_qword(bytes, details.age_seconds);
+            append_strings(bytes, &details.tags);
+        }
 
         match self {
             ServerCommand::Abort => vec![ServerCommand::ID_ABORT],
@@ -175,6 +427,175 @@ impl ServerCommand {
                 append_strings(&mut result, clients);
                 result
             }
+            ServerCommand::GetServerStatistics => vec![ServerCommand::ID_GET_SERVER_STATISTICS],
+            ServerCommand::ServerStatistics(statistics) => {
+                let mut result = vec![ServerCommand::ID_SERVER_STATISTICS];
+                append_qword(&mut result, statistics.uptime_seconds);
+                append_qword(&mut result, statistics.connected_clients);
+                append_qword(&mut result, statistics.commands_processed);
+                append_qword(&mut result, statistics.statuses_stored);
+                append_qword(&mut result, statistics.notifications_sent);
+                result
+            }
+            ServerCommand::GetClientDetails => vec![ServerCommand::ID_GET_CLIENT_DETAILS],
+            ServerCommand::ClientDetails(details) => {
+                let mut result = vec![ServerCommand::ID_CLIENT_DETAILS];
+                result.extend_from_slice(&details.len().to_le_bytes()[0..4]);
+                for client in details {
+                    append_client_details(&mut result, client);
+                }
+                result
+            }
+            ServerCommand::Subscribe => vec![ServerCommand::ID_SUBSCRIBE],
+            ServerCommand::SetTags(tags) => {
+                let mut result = vec![ServerCommand::ID_SET_TAGS];
+                append_strings(&mut result, tags);
+                result
+            }
+            ServerCommand::SetStatusPending => vec![ServerCommand::ID_SET_STATUS_PENDING],
+            ServerCommand::StatusChanged(details) => {
+                let mut result = vec![ServerCommand::ID_STATUS_CHANGED];
+                append_client_details(&mut result, details);
+                result
+            }
+            ServerCommand::ReplayedStatus(timestamp, status) => {
+                let mut result = vec![ServerCommand::ID_REPLAYED_STATUS];
+                append_qword(&mut result, *timestamp);
+                append_bool(&mut result, &status.is_err());
+                if let Err(message) = status {
+                    append_string(&mut result, message);
+                }
+                result
+            }
+            ServerCommand::PushStatus(status) => {
+                let mut result = vec![ServerCommand::ID_PUSH_STATUS];
+                append_bool(&mut result, &status.is_err());
+                if let Err(message) = status {
+                    append_string(&mut result, message);
+                }
+                result
+            }
+            ServerCommand::ClearClientByName(name) => {
+                let mut result = vec![ServerCommand::ID_CLEAR_CLIENT_BY_NAME];
+                append_string(&mut result, name);
+                result
+            }
+            ServerCommand::SetNamespace(namespace) => {
+                let mut result = vec![ServerCommand::ID_SET_NAMESPACE];
+                append_string(&mut result, namespace);
+                result
+            }
+            ServerCommand::SelectAllNamespaces => vec![ServerCommand::ID_SELECT_ALL_NAMESPACES],
+            ServerCommand::Authenticate(key) => {
+                let mut result = vec![ServerCommand::ID_AUTHENTICATE];
+                append_string(&mut result, key);
+                result
+            }
+            ServerCommand::CreateApiKey(identity, label) => {
+                let mut result = vec![ServerCommand::ID_CREATE_API_KEY];
+                append_string(&mut result, identity);
+                append_string(&mut result, label);
+                result
+            }
+            ServerCommand::ListApiKeys => vec![ServerCommand::ID_LIST_API_KEYS],
+            ServerCommand::LabelApiKey(id, label) => {
+                let mut result = vec![ServerCommand::ID_LABEL_API_KEY];
+                append_string(&mut result, id);
+                append_string(&mut result, label);
+                result
+            }
+            ServerCommand::RevokeApiKey(id) => {
+                let mut result = vec![ServerCommand::ID_REVOKE_API_KEY];
+                append_string(&mut result, id);
+                result
+            }
+            ServerCommand::ApiKeyResult(api_key_result) => {
+                let mut result = vec![ServerCommand::ID_API_KEY_RESULT];
+                append_bool(&mut result, &api_key_result.is_err());
+                match api_key_result {
+                    Ok(value) => append_string(&mut result, value),
+                    Err(message) => append_string(&mut result, message),
+                }
+                result
+            }
+            ServerCommand::ApiKeys(keys) => {
+                let mut result = vec![ServerCommand::ID_API_KEYS];
+                result.extend_from_slice(&keys.len().to_le_bytes()[0..4]);
+                for key in keys {
+                    append_string(&mut result, &key.id);
+                    append_string(&mut result, &key.identity);
+                    append_string(&mut result, &key.label);
+                }
+                result
+            }
+            ServerCommand::Drain => vec![ServerCommand::ID_DRAIN],
+            ServerCommand::DrainProgress(clients) => {
+                let mut result = vec![ServerCommand::ID_DRAIN_PROGRESS];
+                append_qword(&mut result, *clients);
+                result
+            }
+            ServerCommand::GetAvailability(window_seconds) => {
+                let mut result = vec![ServerCommand::ID_GET_AVAILABILITY];
+                append_qword(&mut result, *window_seconds);
+                result
+            }
+            ServerCommand::Availability(availability) => {
+                let mut result = vec![ServerCommand::ID_AVAILABILITY];
+                append_bool(&mut result, &availability.is_err());
+                match availability {
+                    Ok(clients) => {
+                        result.extend_from_slice(&clients.len().to_le_bytes()[0..4]);
+                        for client in clients {
+                            append_string(&mut result, &client.name);
+                            append_qword(&mut result, client.ok_seconds);
+                            append_qword(&mut result, client.error_seconds);
+                        }
+                    }
+                    Err(message) => append_string(&mut result, message),
+                }
+                result
+            }
+            ServerCommand::CompactHistory => vec![ServerCommand::ID_COMPACT_HISTORY],
+            ServerCommand::HistoryCompacted(compacted) => {
+                let mut result = vec![ServerCommand::ID_HISTORY_COMPACTED];
+                append_bool(&mut result, &compacted.is_err());
+                match compacted {
+                    Ok(removed) => append_qword(&mut result, *removed),
+                    Err(message) => append_string(&mut result, message),
+                }
+                result
+            }
+            ServerCommand::ExportHistory(start, end) => {
+                let mut result = vec![ServerCommand::ID_EXPORT_HISTORY];
+                append_qword(&mut result, *start);
+                append_qword(&mut result, *end);
+                result
+            }
+            ServerCommand::SetStatusWarning(message) => {
+                let mut result = vec![ServerCommand::ID_SET_STATUS_WARNING];
+                append_string(&mut result, message);
+                result
+            }
+            ServerCommand::HistoryExport(export) => {
+                let mut result = vec![ServerCommand::ID_HISTORY_EXPORT];
+                append_bool(&mut result, &export.is_err());
+                match export {
+                    Ok(entries) => {
+                        result.extend_from_slice(&entries.len().to_le_bytes()[0..4]);
+                        for entry in entries {
+                            append_qword(&mut result, entry.time_ms);
+                            append_string(&mut result, &entry.name);
+                            append_string(&mut result, &entry.status.to_string());
+                            append_bool(&mut result, &entry.message.is_some());
+                            if let Some(ref message) = entry.message {
+                                append_string(&mut result, message);
+                            }
+                        }
+                    }
+                    Err(message) => append_string(&mut result, message),
+                }
+                result
+            }
         }
     }
 }
@@ -188,6 +609,7 @@ pub struct ServerCommandParse {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::severity::Severity;
 
     fn get_expected_serialized_string_length(s: &str) -> usize {
         let string_length_size = 4;
@@ -207,7 +629,7 @@ mod tests {
         get_expected_command_length_no_data() + get_expected_serialized_string_length(s)
     }
 
-    fn get_expected_command_length_string_vec(v: &Vec<String>) -> usize {
+    fn get_expected_command_length_string_vec(v: &[String]) -> usize {
         let header_size = get_expected_command_length_no_data();
         let vec_length_size = 4;
         let strings_size: usize = v
@@ -253,6 +675,377 @@ mod tests {
         assert_eq!(parse_result.bytes_used, 1);
     }
 
+    #[test]
+    fn command_get_server_statistics_is_serialized() {
+        let command = ServerCommand::GetServerStatistics;
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 1);
+    }
+
+    #[test]
+    fn command_server_statistics_is_serialized() {
+        let command = ServerCommand::ServerStatistics(ServerStatistics {
+            uptime_seconds: 3600,
+            connected_clients: 4,
+            commands_processed: 1234,
+            statuses_stored: 3,
+            notifications_sent: 17,
+        });
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_no_data() + 5 * 8
+        );
+    }
+
+    #[test]
+    fn command_get_client_details_is_serialized() {
+        let command = ServerCommand::GetClientDetails;
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 1);
+    }
+
+    #[test]
+    fn command_client_details_is_serialized() {
+        let command = ServerCommand::ClientDetails(vec![
+            ClientDetails {
+                name: "Unreported".to_owned(),
+                status: None,
+                pending: true,
+                warning: false,
+                age_seconds: 0,
+                tags: Vec::new(),
+            },
+            ClientDetails {
+                name: "Healthy".to_owned(),
+                status: Some(Ok(())),
+                pending: false,
+                warning: false,
+                age_seconds: 5,
+                tags: Vec::new(),
+            },
+            ClientDetails {
+                name: "Broken".to_owned(),
+                status: Some(Err("Disk is full".to_owned())),
+                pending: false,
+                warning: false,
+                age_seconds: 120,
+                tags: vec!["db".to_owned(), "production".to_owned()],
+            },
+            ClientDetails::new("Degraded", Some(Err("Disk is almost full"))).with_warning(),
+        ]);
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, bytes.len());
+    }
+
+    #[test]
+    fn command_subscribe_is_serialized() {
+        let command = ServerCommand::Subscribe;
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 1);
+    }
+
+    #[test]
+    fn command_status_changed_is_serialized() {
+        let command = ServerCommand::StatusChanged(ClientDetails {
+            name: "Broken".to_owned(),
+            status: Some(Err("Disk is full".to_owned())),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        });
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_string("Broken")
+                + 3
+                + get_expected_serialized_string_length("Disk is full")
+                + 8
+                + 4
+        );
+    }
+
+    #[test]
+    fn command_set_tags_is_serialized() {
+        let tags = vec!["db".to_owned(), "production".to_owned()];
+        let command = ServerCommand::SetTags(tags.clone());
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_string_vec(&tags)
+        );
+    }
+
+    #[test]
+    fn command_set_status_pending_is_serialized() {
+        let command = ServerCommand::SetStatusPending;
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 1);
+    }
+
+    #[test]
+    fn command_replayed_status_is_serialized() {
+        let command = ServerCommand::ReplayedStatus(1700000000000, Ok(()));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_bool() + 8
+        );
+
+        let message = "Disk is full";
+        let command = ServerCommand::ReplayedStatus(1700000000000, Err(message.to_owned()));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_bool() + 8 + get_expected_serialized_string_length(message)
+        );
+    }
+
+    #[test]
+    fn command_push_status_is_serialized() {
+        let command = ServerCommand::PushStatus(Ok(()));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, get_expected_command_length_bool());
+
+        let message = "Backup failed";
+        let command = ServerCommand::PushStatus(Err(message.to_owned()));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_bool() + get_expected_serialized_string_length(message)
+        );
+    }
+
+    #[test]
+    fn command_clear_client_by_name_is_serialized() {
+        let name = "backup";
+        let command = ServerCommand::ClearClientByName(name.to_owned());
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_string(name)
+        );
+    }
+
+    #[test]
+    fn command_set_namespace_is_serialized() {
+        let namespace = "team-a";
+        let command = ServerCommand::SetNamespace(namespace.to_owned());
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_string(namespace)
+        );
+    }
+
+    #[test]
+    fn command_select_all_namespaces_is_serialized() {
+        let command = ServerCommand::SelectAllNamespaces;
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 1);
+    }
+
+    #[test]
+    fn command_authenticate_is_serialized() {
+        let key = "3f9a1c7e.0123456789abcdef";
+        let command = ServerCommand::Authenticate(key.to_owned());
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_string(key)
+        );
+    }
+
+    #[test]
+    fn api_key_management_commands_are_serialized() {
+        let commands = [
+            ServerCommand::CreateApiKey("Backup".to_owned(), "nightly backup".to_owned()),
+            ServerCommand::ListApiKeys,
+            ServerCommand::LabelApiKey("3f9a1c7e".to_owned(), "weekly backup".to_owned()),
+            ServerCommand::RevokeApiKey("3f9a1c7e".to_owned()),
+        ];
+        for command in commands {
+            let bytes = command.to_bytes();
+            let parse_result =
+                ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+            assert_eq!(parse_result.command, command);
+            assert_eq!(parse_result.bytes_used, bytes.len());
+        }
+    }
+
+    #[test]
+    fn command_api_key_result_is_serialized() {
+        let message = "unknown API key 3f9a1c7e";
+        let command = ServerCommand::ApiKeyResult(Err(message.to_owned()));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_bool() + get_expected_serialized_string_length(message)
+        );
+
+        let command = ServerCommand::ApiKeyResult(Ok("3f9a1c7e".to_owned()));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+    }
+
+    #[test]
+    fn command_api_keys_is_serialized() {
+        let command = ServerCommand::ApiKeys(vec![
+            ApiKeyDetails {
+                id: "3f9a1c7e".to_owned(),
+                identity: "Backup".to_owned(),
+                label: "nightly backup".to_owned(),
+            },
+            ApiKeyDetails {
+                id: "b20d44e1".to_owned(),
+                identity: "Dashboard".to_owned(),
+                label: String::new(),
+            },
+        ]);
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, bytes.len());
+    }
+
+    #[test]
+    fn command_drain_is_serialized() {
+        let command = ServerCommand::Drain;
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 1);
+    }
+
+    #[test]
+    fn command_drain_progress_is_serialized() {
+        let command = ServerCommand::DrainProgress(12);
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 9);
+    }
+
+    #[test]
+    fn command_get_availability_is_serialized() {
+        let command = ServerCommand::GetAvailability(7 * 24 * 3600);
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, 9);
+    }
+
+    #[test]
+    fn command_availability_is_serialized() {
+        let command = ServerCommand::Availability(Ok(vec![
+            ClientAvailability {
+                name: "Backup".to_owned(),
+                ok_seconds: 86000,
+                error_seconds: 400,
+            },
+            ClientAvailability {
+                name: "Cleanup".to_owned(),
+                ok_seconds: 0,
+                error_seconds: 0,
+            },
+        ]));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(parse_result.bytes_used, bytes.len());
+
+        let message = "history is not enabled";
+        let command = ServerCommand::Availability(Err(message.to_owned()));
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_bool() + get_expected_serialized_string_length(message)
+        );
+    }
+
+    #[test]
+    fn history_compaction_commands_are_serialized() {
+        let commands = [
+            ServerCommand::CompactHistory,
+            ServerCommand::HistoryCompacted(Ok(120)),
+            ServerCommand::HistoryCompacted(Err("history is not enabled".to_owned())),
+        ];
+        for command in commands {
+            let bytes = command.to_bytes();
+            let parse_result =
+                ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+            assert_eq!(parse_result.command, command);
+            assert_eq!(parse_result.bytes_used, bytes.len());
+        }
+    }
+
+    #[test]
+    fn history_export_commands_are_serialized() {
+        let commands = [
+            ServerCommand::ExportHistory(1759276800000, 1761955200000),
+            ServerCommand::HistoryExport(Ok(vec![
+                HistoryEntry {
+                    time_ms: 1759276800000,
+                    name: "Backup".to_owned(),
+                    status: Severity::Ok,
+                    message: None,
+                },
+                HistoryEntry {
+                    time_ms: 1759280400000,
+                    name: "Backup".to_owned(),
+                    status: Severity::Error,
+                    message: Some("No space left".to_owned()),
+                },
+            ])),
+            ServerCommand::HistoryExport(Err("history is not enabled".to_owned())),
+        ];
+        for command in commands {
+            let bytes = command.to_bytes();
+            let parse_result =
+                ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+            assert_eq!(parse_result.command, command);
+            assert_eq!(parse_result.bytes_used, bytes.len());
+        }
+    }
+
     #[test]
     fn command_set_status_ok_is_serialized() {
         let command = ServerCommand::SetStatusOk;
@@ -275,6 +1068,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn command_set_status_warning_is_serialized() {
+        let message = "Disk is almost full";
+        let command = ServerCommand::SetStatusWarning(message.to_owned());
+        let bytes = command.to_bytes();
+        let parse_result = ServerCommand::from_bytes(&bytes).expect("Command should deserialize");
+        assert_eq!(parse_result.command, command);
+        assert_eq!(
+            parse_result.bytes_used,
+            get_expected_command_length_string(message)
+        );
+    }
+
     #[test]
     fn command_get_statuses_is_serialized() {
         {
@@ -317,7 +1123,7 @@ mod tests {
         assert_eq!(parse_result.command, command);
         assert_eq!(
             parse_result.bytes_used,
-            get_expected_command_length_string(&name)
+            get_expected_command_length_string(name)
         );
     }
 
@@ -369,12 +1175,12 @@ mod tests {
         let bytes = command.to_bytes();
 
         let bytes = &bytes[0..bytes.len() - 1];
-        let err: ServerCommandError = ServerCommand::from_bytes(&bytes)
+        let err: ServerCommandError = ServerCommand::from_bytes(bytes)
             .expect_err("Command with not enough bytes should not be deserialized");
         assert_eq!(err, ServerCommandError::TooFewBytes);
 
         let bytes = &bytes[0..bytes.len() - 1];
-        let err: ServerCommandError = ServerCommand::from_bytes(&bytes)
+        let err: ServerCommandError = ServerCommand::from_bytes(bytes)
             .expect_err("Command with not enough bytes should not be deserialized");
         assert_eq!(err, ServerCommandError::TooFewBytes);
     }
@@ -398,4 +1204,15 @@ mod tests {
             .expect_err("Command with invalid utf8 string should fail");
         assert_eq!(err, ServerCommandError::InvalidStringEncoding);
     }
+
+    #[test]
+    fn numbers_are_serialized_as_little_endian() {
+        let bytes = ServerCommand::SetName("ab".to_owned()).to_bytes();
+        assert_eq!(bytes, [ServerCommand::ID_SET_NAME, 2, 0, 0, 0, b'a', b'b']);
+        let bytes = ServerCommand::DrainProgress(0x0102).to_bytes();
+        assert_eq!(
+            bytes,
+            [ServerCommand::ID_DRAIN_PROGRESS, 2, 1, 0, 0, 0, 0, 0, 0]
+        );
+    }
 }
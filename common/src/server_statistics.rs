@@ -0,0 +1,9 @@
+/// Snapshot of internal server counters, sent in response to GetServerStatistics
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ServerStatistics {
+    pub uptime_seconds: u64,
+    pub connected_clients: u64,
+    pub commands_processed: u64,
+    pub statuses_stored: u64,
+    pub notifications_sent: u64,
+}
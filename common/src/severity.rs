@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of the status of a client, named the same everywhere statuses are presented as text, e.g. in JSON output of
+/// the client, in the history of the server or by integrations. Warnings are errors, which were reported as less
+/// severe, e.g. by Nagios plugins. Clients which haven't reported any status yet are either pending or unknown.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Ok,
+    Warning,
+    Error,
+    Pending,
+    Unknown,
+}
+
+impl Severity {
+    pub fn from_status(status: &Option<Result<(), String>>, pending: bool) -> Self {
+        match status {
+            Some(Ok(_)) => Severity::Ok,
+            Some(Err(_)) => Severity::Error,
+            None if pending => Severity::Pending,
+            None => Severity::Unknown,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Ok => "ok",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Pending => "pending",
+            Severity::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Severity {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "ok" => Ok(Severity::Ok),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            "pending" => Ok(Severity::Pending),
+            "unknown" => Ok(Severity::Unknown),
+            _ => Err(format!("invalid severity: {text}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn severities_are_named_consistently() {
+        for severity in [
+            Severity::Ok,
+            Severity::Warning,
+            Severity::Error,
+            Severity::Pending,
+            Severity::Unknown,
+        ] {
+            let json = format!("\"{}\"", severity);
+            assert_eq!(serde_json::to_string(&severity).unwrap(), json);
+            assert_eq!(serde_json::from_str::<Severity>(&json).unwrap(), severity);
+            assert_eq!(severity.as_str().parse::<Severity>(), Ok(severity));
+        }
+        assert!("failed".parse::<Severity>().is_err());
+    }
+
+    #[test]
+    fn severity_is_determined_by_status() {
+        assert_eq!(Severity::from_status(&Some(Ok(())), true), Severity::Ok);
+        assert_eq!(
+            Severity::from_status(&Some(Err("a".to_owned())), true),
+            Severity::Error
+        );
+        assert_eq!(Severity::from_status(&None, true), Severity::Pending);
+        assert_eq!(Severity::from_status(&None, false), Severity::Unknown);
+    }
+}
@@ -0,0 +1,158 @@
+// In-process mock of the server for tests of code talking to it, e.g. through the SDK. The mock listens on a free local
+// port, records every command it receives and answers only with scripted responses, so tests can check what was sent
+// without spawning the server and without sleeping.
+
+use crate::server_command::ServerCommand;
+use std::sync::{Arc, Mutex};
+use tokio::io::BufReader;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::task::{JoinHandle, JoinSet};
+
+type Matcher = Box<dyn Fn(&ServerCommand) -> bool + Send + Sync>;
+
+struct ScriptedResponse {
+    matcher: Matcher,
+    responses: Vec<ServerCommand>,
+}
+
+struct Shared {
+    received: watch::Sender<Vec<ServerCommand>>,
+    scripted_responses: Mutex<Vec<ScriptedResponse>>,
+    pushed: broadcast::Sender<ServerCommand>,
+}
+
+impl Shared {
+    fn get_responses(&self, command: &ServerCommand) -> Vec<ServerCommand> {
+        let scripted_responses = self
+            .scripted_responses
+            .lock()
+            .expect("Lock should not be poisoned");
+        scripted_responses
+            .iter()
+            .find(|x| (x.matcher)(command))
+            .map(|x| x.responses.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Mock of the server. Connections are closed and the port is released when it's dropped.
+pub struct MockServer {
+    port: u16,
+    shared: Arc<Shared>,
+    accept_task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let shared = Arc::new(Shared {
+            received: watch::channel(Vec::new()).0,
+            scripted_responses: Mutex::new(Vec::new()),
+            pushed: broadcast::channel(64).0,
+        });
+        let accept_task = tokio::spawn(accept_connections(listener, shared.clone()));
+        Ok(MockServer {
+            port,
+            shared,
+            accept_task,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Answers every received command accepted by the matcher with the given commands. Responses scripted earlier take
+    /// precedence when more of them match.
+    pub fn respond_to(
+        &self,
+        matcher: impl Fn(&ServerCommand) -> bool + Send + Sync + 'static,
+        responses: Vec<ServerCommand>,
+    ) {
+        let mut scripted_responses = self
+            .shared
+            .scripted_responses
+            .lock()
+            .expect("Lock should not be poisoned");
+        scripted_responses.push(ScriptedResponse {
+            matcher: Box::new(matcher),
+            responses,
+        });
+    }
+
+    /// Sends a command to all connected clients, e.g. Refresh or StatusChanged for subscribers
+    pub fn push(&self, command: ServerCommand) {
+        let _ = self.shared.pushed.send(command);
+    }
+
+    /// Returns all commands received so far from all connections, in the order they were received
+    pub fn received(&self) -> Vec<ServerCommand> {
+        self.shared.received.borrow().clone()
+    }
+
+    /// Waits until a command accepted by the predicate is received and returns it. Commands received before the call
+    /// are taken into account too.
+    pub async fn wait_for(&self, predicate: impl Fn(&ServerCommand) -> bool) -> ServerCommand {
+        let mut receiver = self.shared.received.subscribe();
+        let received = receiver
+            .wait_for(|commands| commands.iter().any(&predicate))
+            .await
+            .expect("Mock server should be running");
+        received
+            .iter()
+            .find(|x| predicate(x))
+            .cloned()
+            .expect("Command should be found")
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+// Connections are handled in a set owned by this task, so they're aborted along with it
+async fn accept_connections(listener: TcpListener, shared: Arc<Shared>) {
+    let mut connections = JoinSet::new();
+    loop {
+        match listener.accept().await {
+            Ok((tcp_stream, _)) => {
+                // Finished connections are reaped, so the set doesn't grow with every connection
+                while connections.try_join_next().is_some() {}
+                connections.spawn(handle_connection(tcp_stream, shared.clone()));
+            }
+            Err(err) => eprintln!("Mock server failed to accept connection: {}", err),
+        }
+    }
+}
+
+async fn handle_connection(tcp_stream: TcpStream, shared: Arc<Shared>) {
+    let mut pushed = shared.pushed.subscribe();
+    let (input_stream, mut output_stream) = tcp_stream.into_split();
+    let mut input_stream = BufReader::new(input_stream);
+    loop {
+        let responses = tokio::select! {
+            command = ServerCommand::receive_async(&mut input_stream) => {
+                let Ok(command) = command else {
+                    return;
+                };
+                let responses = shared.get_responses(&command);
+                shared.received.send_modify(|x| x.push(command));
+                responses
+            }
+            command = pushed.recv() => match command {
+                Ok(command) => vec![command],
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+        };
+        for response in responses {
+            if response.send_async(&mut output_stream).await.is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,38 @@
+// Certificates and private keys stored in PEM files, which are used by TLS of both the server and the client
+
+use std::fs::File;
+use std::io::BufReader;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::RootCertStore;
+
+fn open_file(path: &str) -> Result<BufReader<File>, String> {
+    let file = File::open(path).map_err(|err| format!("could not open {path}: {err}"))?;
+    Ok(BufReader::new(file))
+}
+
+pub fn load_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certificates = rustls_pemfile::certs(&mut open_file(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("could not read certificates from {path}: {err}"))?;
+    if certificates.is_empty() {
+        return Err(format!("no certificates found in {path}"));
+    }
+    Ok(certificates)
+}
+
+pub fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>, String> {
+    rustls_pemfile::private_key(&mut open_file(path)?)
+        .map_err(|err| format!("could not read private key from {path}: {err}"))?
+        .ok_or_else(|| format!("no private key found in {path}"))
+}
+
+// Certificates of certificate authorities, which signed certificates of the other side
+pub fn load_root_store(path: &str) -> Result<RootCertStore, String> {
+    let mut root_store = RootCertStore::empty();
+    for certificate in load_certificates(path)? {
+        root_store
+            .add(certificate)
+            .map_err(|err| format!("invalid CA certificate in {path}: {err}"))?;
+    }
+    Ok(root_store)
+}
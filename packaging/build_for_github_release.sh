@@ -24,7 +24,7 @@ echo "package_name: $package_name"
 
 
 printf "\n---------------------- Compiling\n"
-cargo build --release || exit 1
+cargo build --release --features check_mate_client/systemd,check_mate_client/smart || exit 1
 
 printf "\n---------------------- Zipping\n"
 mkdir -p "$dst_dir/$package_name" || exit 1
@@ -0,0 +1,276 @@
+// Python bindings of the SDK, so scripts can report their status, read statuses of other clients and follow their
+// changes without spawning the client for every status. The API is blocking, like most of ops scripts are. Calls
+// release the GIL while they wait for the server and are run by a runtime shared by all objects of the module.
+
+use check_mate_sdk::{ClientDetails, ClientInfo, ConnectOptions, Filter};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+create_exception!(check_mate, Error, PyException);
+
+static RUNTIME: LazyLock<Runtime> =
+    LazyLock::new(|| Runtime::new().expect("Runtime should be created"));
+
+// How often waiting for a status change is interrupted to let Python handle signals, e.g. Ctrl+C
+const SIGNAL_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+fn to_py_err(err: impl std::fmt::Display) -> PyErr {
+    Error::new_err(err.to_string())
+}
+
+// All classes connect with the same keyword arguments, which mirror options of the client
+fn get_connect_options(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<ConnectOptions> {
+    let mut options = ConnectOptions::default();
+    let mut ca = None;
+    let mut certificate = None;
+    let mut key = None;
+    for (name, value) in kwargs.into_iter().flatten() {
+        match name.extract::<String>()?.as_str() {
+            "addresses" => options.server_addresses = value.extract()?,
+            "port" => options.server_port = value.extract()?,
+            "api_key" => options.identity.api_key = value.extract()?,
+            "namespace" => options.identity.namespace = value.extract()?,
+            "all_namespaces" => options.identity.all_namespaces = value.extract()?,
+            "tags" => options.identity.tags = value.extract()?,
+            "ca" => ca = value.extract::<Option<String>>()?,
+            "certificate" => certificate = value.extract::<Option<String>>()?,
+            "key" => key = value.extract::<Option<String>>()?,
+            name => {
+                return Err(PyTypeError::new_err(format!(
+                    "unexpected keyword argument '{name}'"
+                )))
+            }
+        }
+    }
+
+    options.tls_connector = match (ca, certificate, key) {
+        (Some(ca), Some(certificate), Some(key)) => Some(
+            check_mate_sdk::create_tls_connector(&ca, Some((&certificate, &key)))
+                .map_err(to_py_err)?,
+        ),
+        (Some(ca), None, None) => {
+            Some(check_mate_sdk::create_tls_connector(&ca, None).map_err(to_py_err)?)
+        }
+        (None, None, None) => None,
+        _ => {
+            return Err(PyTypeError::new_err(
+                "certificate and key must be given together and require ca",
+            ))
+        }
+    };
+    Ok(options)
+}
+
+#[pyclass(frozen, get_all)]
+pub struct ClientStatus {
+    name: String,
+    status: String, // "ok", "warning", "error", "pending" or "unknown"
+    message: Option<String>,
+    age_seconds: u64,
+    tags: Vec<String>,
+}
+
+impl From<ClientDetails> for ClientStatus {
+    fn from(details: ClientDetails) -> Self {
+        let age_seconds = details.age_seconds;
+        let info = ClientInfo::from(details);
+        ClientStatus {
+            name: info.name,
+            status: info.status.to_string(),
+            message: info.message,
+            age_seconds,
+            tags: info.tags,
+        }
+    }
+}
+
+#[pymethods]
+impl ClientStatus {
+    fn __repr__(&self) -> String {
+        match self.message {
+            Some(ref message) => format!(
+                "ClientStatus(name={:?}, status={:?}, message={:?})",
+                self.name, self.status, message
+            ),
+            None => format!(
+                "ClientStatus(name={:?}, status={:?})",
+                self.name, self.status
+            ),
+        }
+    }
+}
+
+#[pyclass]
+pub struct Reporter {
+    reporter: check_mate_sdk::Reporter,
+}
+
+#[pymethods]
+impl Reporter {
+    #[new]
+    #[pyo3(signature = (name, **kwargs))]
+    fn new(py: Python<'_>, name: String, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let mut options = get_connect_options(kwargs)?;
+        options.identity.name = Some(name);
+        let reporter = py
+            .detach(|| RUNTIME.block_on(check_mate_sdk::Reporter::connect(&options)))
+            .map_err(to_py_err)?;
+        Ok(Reporter { reporter })
+    }
+
+    fn set_ok(&mut self, py: Python<'_>) -> PyResult<()> {
+        py.detach(|| RUNTIME.block_on(self.reporter.set_ok()))
+            .map_err(to_py_err)
+    }
+
+    fn set_error(&mut self, py: Python<'_>, message: String) -> PyResult<()> {
+        py.detach(|| RUNTIME.block_on(self.reporter.set_error(message)))
+            .map_err(to_py_err)
+    }
+
+    fn set_warning(&mut self, py: Python<'_>, message: String) -> PyResult<()> {
+        py.detach(|| RUNTIME.block_on(self.reporter.set_warning(message)))
+            .map_err(to_py_err)
+    }
+
+    fn set_pending(&mut self, py: Python<'_>) -> PyResult<()> {
+        py.detach(|| RUNTIME.block_on(self.reporter.set_pending()))
+            .map_err(to_py_err)
+    }
+}
+
+#[pyclass]
+pub struct Reader {
+    reader: check_mate_sdk::Reader,
+}
+
+#[pymethods]
+impl Reader {
+    #[new]
+    #[pyo3(signature = (**kwargs))]
+    fn new(py: Python<'_>, kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let options = get_connect_options(kwargs)?;
+        let reader = py
+            .detach(|| RUNTIME.block_on(check_mate_sdk::Reader::connect(&options)))
+            .map_err(to_py_err)?;
+        Ok(Reader { reader })
+    }
+
+    fn statuses(&mut self, py: Python<'_>) -> PyResult<Vec<ClientStatus>> {
+        let details = py
+            .detach(|| RUNTIME.block_on(self.reader.statuses()))
+            .map_err(to_py_err)?;
+        Ok(details.into_iter().map(ClientStatus::from).collect())
+    }
+}
+
+// Iterator over status changes. They're received in the background, because receiving a command can't be interrupted
+// midway when Python has a signal to handle.
+#[pyclass]
+pub struct Subscriber {
+    receiver: mpsc::UnboundedReceiver<Result<ClientDetails, check_mate_sdk::Error>>,
+}
+
+#[pymethods]
+impl Subscriber {
+    #[new]
+    #[pyo3(signature = (name_filter=None, tags_filter=Vec::new(), **kwargs))]
+    fn new(
+        py: Python<'_>,
+        name_filter: Option<String>,
+        tags_filter: Vec<String>,
+        kwargs: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        let options = get_connect_options(kwargs)?;
+        let filter = Filter {
+            name: name_filter,
+            tags: tags_filter,
+        };
+        let mut subscriber = py
+            .detach(|| RUNTIME.block_on(check_mate_sdk::Subscriber::connect(&options)))
+            .map_err(to_py_err)?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        RUNTIME.spawn(async move {
+            // The connection is closed once the subscriber is dropped by Python
+            loop {
+                let result = tokio::select! {
+                    result = subscriber.next_matching(&filter) => result,
+                    _ = sender.closed() => break,
+                };
+                let failed = result.is_err();
+                if sender.send(result).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Subscriber { receiver })
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> PyResult<ClientStatus> {
+        loop {
+            let received = py.detach(|| {
+                RUNTIME.block_on(async {
+                    tokio::time::timeout(SIGNAL_CHECK_INTERVAL, self.receiver.recv()).await
+                })
+            });
+            match received {
+                Ok(Some(Ok(details))) => return Ok(details.into()),
+                Ok(Some(Err(err))) => return Err(to_py_err(err)),
+                Ok(None) => return Err(Error::new_err("subscription has already failed")),
+                Err(_) => py.check_signals()?,
+            }
+        }
+    }
+}
+
+#[pymodule]
+fn check_mate(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("Error", m.py().get_type::<Error>())?;
+    m.add_class::<ClientStatus>()?;
+    m.add_class::<Reporter>()?;
+    m.add_class::<Reader>()?;
+    m.add_class::<Subscriber>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_are_named_like_in_the_client() {
+        let details = |status, pending| ClientDetails {
+            name: "Backup".to_owned(),
+            status,
+            pending,
+            warning: false,
+            age_seconds: 5,
+            tags: vec!["prod".to_owned()],
+        };
+
+        let status = ClientStatus::from(details(Some(Err("No space left".to_owned())), false));
+        assert_eq!(status.status, "error");
+        assert_eq!(status.message.as_deref(), Some("No space left"));
+        assert_eq!(status.tags, ["prod"]);
+        let mut warning = details(Some(Err("Disk is almost full".to_owned())), false);
+        warning.warning = true;
+        assert_eq!(ClientStatus::from(warning).status, "warning");
+        assert_eq!(
+            ClientStatus::from(details(Some(Ok(())), false)).status,
+            "ok"
+        );
+        assert_eq!(ClientStatus::from(details(None, true)).status, "pending");
+        assert_eq!(ClientStatus::from(details(None, false)).status, "unknown");
+    }
+}
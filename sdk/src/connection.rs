@@ -0,0 +1,146 @@
+// Connection with the server. Servers are tried in order until one of them accepts the connection, optionally over
+// TLS. Once connected, the client introduces itself, i.e. authenticates and sets its namespace, name and tags, before
+// sending any other commands.
+
+use crate::error::Error;
+use crate::tls::{connect_tls, ServerStream};
+use check_mate_common::{constants::*, CommunicationError, ServerCommand};
+use tokio::io::{AsyncWrite, BufReader, ReadHalf, WriteHalf};
+use tokio::net::{lookup_host, TcpStream};
+use tokio_rustls::TlsConnector;
+
+pub type ServerConnection = (BufReader<ReadHalf<ServerStream>>, WriteHalf<ServerStream>);
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Identity {
+    pub api_key: Option<String>,
+    pub namespace: Option<String>,
+    pub all_namespaces: bool, // scope reading to clients of all namespaces
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Identity {
+    pub async fn introduce(
+        &self,
+        output_stream: &mut (impl AsyncWrite + Unpin),
+    ) -> Result<(), CommunicationError> {
+        // Authentication comes first, because the server names the client after the identity of its key
+        if let Some(ref key) = self.api_key {
+            let command = ServerCommand::Authenticate(key.clone());
+            command.send_async(output_stream).await?;
+        }
+        if let Some(ref namespace) = self.namespace {
+            let command = ServerCommand::SetNamespace(namespace.clone());
+            command.send_async(output_stream).await?;
+        }
+        if self.all_namespaces {
+            ServerCommand::SelectAllNamespaces
+                .send_async(output_stream)
+                .await?;
+        }
+        if let Some(ref name) = self.name {
+            let command = ServerCommand::SetName(name.clone());
+            command.send_async(output_stream).await?;
+        }
+        if !self.tags.is_empty() {
+            let command = ServerCommand::SetTags(self.tags.clone());
+            command.send_async(output_stream).await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct ConnectOptions {
+    pub server_addresses: Vec<String>, // tried in order, each can override the port, e.g. "monitoring:10006"
+    pub server_port: u16,
+    pub tls_connector: Option<TlsConnector>, // None for a plain connection
+    pub identity: Identity,
+}
+
+impl Default for ConnectOptions {
+    fn default() -> Self {
+        Self {
+            server_addresses: vec![DEFAULT_SERVER_ADDRESS.to_owned()],
+            server_port: DEFAULT_PORT,
+            tls_connector: None,
+            identity: Identity::default(),
+        }
+    }
+}
+
+// Addresses can override the port, e.g. "monitoring:10006". IPv6 addresses can't, since they contain colons anyway.
+pub fn split_port(server_address: &str, default_port: u16) -> (&str, u16) {
+    let host_and_port = server_address
+        .rsplit_once(':')
+        .filter(|(host, _)| !host.contains(':'))
+        .and_then(|(host, port)| Some((host, port.parse().ok()?)));
+    host_and_port.unwrap_or((server_address, default_port))
+}
+
+pub async fn connect_to_any_server(
+    server_addresses: &[String],
+    server_port: u16,
+    tls_connector: Option<&TlsConnector>,
+) -> Result<ServerStream, String> {
+    // Try all servers in order. Each of them can resolve to multiple addresses, which are also tried in order.
+    let mut last_error = String::from("no server address specified");
+    for server_address in server_addresses {
+        let (host, port) = split_port(server_address, server_port);
+        let socket_addresses = match lookup_host((host, port)).await {
+            Ok(x) => x,
+            Err(err) => {
+                last_error = format!("could not resolve {server_address}: {err}");
+                continue;
+            }
+        };
+        for socket_address in socket_addresses {
+            let tcp_stream = match TcpStream::connect(socket_address).await {
+                Ok(ok) => ok,
+                Err(err) => {
+                    last_error = format!("{socket_address}: {err}");
+                    continue;
+                }
+            };
+            match tls_connector {
+                Some(tls_connector) => match connect_tls(tls_connector, host, tcp_stream).await {
+                    Ok(ok) => return Ok(ok),
+                    Err(err) => last_error = err,
+                },
+                None => return Ok(ServerStream::Plain(tcp_stream)),
+            }
+        }
+    }
+    Err(last_error)
+}
+
+// Connects to the first available server and introduces the client to it
+pub async fn connect(options: &ConnectOptions) -> Result<ServerConnection, Error> {
+    let stream = connect_to_any_server(
+        &options.server_addresses,
+        options.server_port,
+        options.tls_connector.as_ref(),
+    )
+    .await
+    .map_err(Error::Connection)?;
+    let (input_stream, mut output_stream) = tokio::io::split(stream);
+    options.identity.introduce(&mut output_stream).await?;
+    Ok((BufReader::new(input_stream), output_stream))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_are_split_from_addresses() {
+        assert_eq!(split_port("monitoring", 10005), ("monitoring", 10005));
+        assert_eq!(split_port("monitoring:10006", 10005), ("monitoring", 10006));
+        assert_eq!(
+            split_port("monitoring:http", 10005),
+            ("monitoring:http", 10005)
+        );
+        assert_eq!(split_port("::1", 10005), ("::1", 10005));
+    }
+}
@@ -0,0 +1,26 @@
+use check_mate_common::CommunicationError;
+
+#[derive(Debug)]
+pub enum Error {
+    Connection(String), // none of the servers could be connected
+    Communication(CommunicationError),
+    UnexpectedResponse,
+}
+
+impl From<CommunicationError> for Error {
+    fn from(err: CommunicationError) -> Self {
+        Error::Communication(err)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Connection(err) => write!(f, "could not connect with server: {}", err),
+            Error::Communication(err) => write!(f, "{}", err),
+            Error::UnexpectedResponse => write!(f, "unexpected response from server"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
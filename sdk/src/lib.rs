@@ -0,0 +1,22 @@
+// Library for Rust services which report their health to the server, read statuses of other clients or follow their
+// changes in-process, without running the client binary. The client binary connects to the server with it as well.
+
+mod connection;
+mod error;
+mod reader;
+mod reporter;
+mod subscriber;
+mod tls;
+
+pub use connection::{
+    connect, connect_to_any_server, split_port, ConnectOptions, Identity, ServerConnection,
+};
+pub use error::Error;
+pub use reader::Reader;
+pub use reporter::Reporter;
+pub use subscriber::{Filter, Subscriber};
+pub use tls::{connect_tls, create_tls_connector, ServerStream};
+
+pub use check_mate_common::{ClientDetails, ClientInfo, Severity};
+#[cfg(feature = "testing")]
+pub use check_mate_common::testing;
@@ -0,0 +1,29 @@
+// Reading of statuses of clients from within a process, e.g. to show them on a page of a service. Statuses are limited
+// to the namespace of the reader, unless it selects all namespaces.
+
+use crate::connection::{connect, ConnectOptions, ServerConnection};
+use crate::error::Error;
+use check_mate_common::{ClientDetails, ServerCommand};
+
+pub struct Reader {
+    connection: ServerConnection,
+}
+
+impl Reader {
+    pub async fn connect(options: &ConnectOptions) -> Result<Self, Error> {
+        let connection = connect(options).await?;
+        Ok(Reader { connection })
+    }
+
+    // Returns statuses of all clients, including the ones pushed by clients which have disconnected since
+    pub async fn statuses(&mut self) -> Result<Vec<ClientDetails>, Error> {
+        let (input_stream, output_stream) = &mut self.connection;
+        ServerCommand::GetClientDetails
+            .send_async(output_stream)
+            .await?;
+        match ServerCommand::receive_async(input_stream).await? {
+            ServerCommand::ClientDetails(details) => Ok(details),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+}
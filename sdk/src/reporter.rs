@@ -0,0 +1,74 @@
+// Reporting of the status of a service from within its process. The server keeps the status for as long as the
+// connection lasts, like statuses of watched commands, so a service which dies takes its status with it. A lost
+// connection is established again when the next status is reported, e.g. after the server restarts.
+
+use crate::connection::{connect, ConnectOptions, ServerConnection};
+use crate::error::Error;
+use check_mate_common::{CommunicationError, ServerCommand};
+
+pub struct Reporter {
+    options: ConnectOptions,
+    connection: Option<ServerConnection>,
+}
+
+// Commands sent by the server, such as refresh requests, are not handled. Reading them reveals a closed connection,
+// which writing alone may not. Yielding first lets the runtime notice events of the socket which came meanwhile.
+async fn is_connected(connection: &mut ServerConnection) -> bool {
+    tokio::task::yield_now().await;
+    loop {
+        tokio::select! {
+            biased;
+            result = ServerCommand::receive_async(&mut connection.0) => {
+                if result.is_err() {
+                    return false;
+                }
+            }
+            _ = std::future::ready(()) => return true,
+        }
+    }
+}
+
+impl Reporter {
+    pub async fn connect(options: &ConnectOptions) -> Result<Self, Error> {
+        let connection = connect(options).await?;
+        Ok(Reporter {
+            options: options.clone(),
+            connection: Some(connection),
+        })
+    }
+
+    pub async fn set_ok(&mut self) -> Result<(), Error> {
+        self.send(ServerCommand::SetStatusOk).await
+    }
+
+    pub async fn set_error(&mut self, message: impl Into<String>) -> Result<(), Error> {
+        self.send(ServerCommand::SetStatusError(message.into()))
+            .await
+    }
+
+    pub async fn set_warning(&mut self, message: impl Into<String>) -> Result<(), Error> {
+        self.send(ServerCommand::SetStatusWarning(message.into()))
+            .await
+    }
+
+    pub async fn set_pending(&mut self) -> Result<(), Error> {
+        self.send(ServerCommand::SetStatusPending).await
+    }
+
+    async fn send(&mut self, command: ServerCommand) -> Result<(), Error> {
+        if let Some(ref mut connection) = self.connection {
+            if is_connected(connection).await {
+                match command.send_async(&mut connection.1).await {
+                    Ok(()) => return Ok(()),
+                    Err(CommunicationError::SocketDisconnected) => (),
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            self.connection = None;
+        }
+        let mut connection = connect(&self.options).await?;
+        command.send_async(&mut connection.1).await?;
+        self.connection = Some(connection);
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+// Consuming status changes as they happen, e.g. to write a custom dashboard or a bot posting failures to a chat.
+// Changes are pushed by the server, so they're limited to the namespace of the subscriber like statuses of a reader.
+
+use crate::connection::{connect, ConnectOptions, ServerConnection};
+use crate::error::Error;
+use check_mate_common::{glob_matches, ClientDetails, ServerCommand};
+use std::future::Future;
+
+// Selects clients whose status changes are of interest. An empty filter selects all of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub name: Option<String>, // glob pattern, e.g. "backup-*"
+    pub tags: Vec<String>,    // all of them must be assigned to the client
+}
+
+impl Filter {
+    pub fn client(name: impl Into<String>) -> Self {
+        Filter {
+            name: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn matches(&self, details: &ClientDetails) -> bool {
+        let name_matches = match self.name {
+            Some(ref pattern) => glob_matches(pattern, &details.name),
+            None => true,
+        };
+        name_matches && self.tags.iter().all(|tag| details.tags.contains(tag))
+    }
+}
+
+type Callback = Box<dyn FnMut(&ClientDetails) + Send>;
+
+pub struct Subscriber {
+    connection: ServerConnection,
+    callbacks: Vec<(Filter, Callback)>,
+}
+
+impl Subscriber {
+    pub async fn connect(options: &ConnectOptions) -> Result<Self, Error> {
+        let (input_stream, mut output_stream) = connect(options).await?;
+        ServerCommand::Subscribe
+            .send_async(&mut output_stream)
+            .await?;
+        Ok(Subscriber {
+            connection: (input_stream, output_stream),
+            callbacks: Vec::new(),
+        })
+    }
+
+    // Registers a callback called by run() for every status change selected by the filter
+    pub fn on_change(
+        &mut self,
+        filter: Filter,
+        callback: impl FnMut(&ClientDetails) + Send + 'static,
+    ) -> &mut Self {
+        self.callbacks.push((filter, Box::new(callback)));
+        self
+    }
+
+    // Waits for the next status change of any client. Changes which happen before the subscriber connects are not
+    // reported, so current statuses should be read separately if they are needed.
+    pub async fn next(&mut self) -> Result<ClientDetails, Error> {
+        loop {
+            match ServerCommand::receive_async(&mut self.connection.0).await? {
+                ServerCommand::StatusChanged(details) => return Ok(details),
+                // The server may ask the subscriber to refresh like any other connected client
+                ServerCommand::Refresh => continue,
+                _ => return Err(Error::UnexpectedResponse),
+            }
+        }
+    }
+
+    // Waits for the next status change selected by the filter
+    pub async fn next_matching(&mut self, filter: &Filter) -> Result<ClientDetails, Error> {
+        loop {
+            let details = self.next().await?;
+            if filter.matches(&details) {
+                return Ok(details);
+            }
+        }
+    }
+
+    // Passes status changes to registered callbacks until the connection fails
+    pub async fn run(mut self) -> Error {
+        loop {
+            let details = match self.next().await {
+                Ok(details) => details,
+                Err(err) => return err,
+            };
+            for (filter, callback) in self.callbacks.iter_mut() {
+                if filter.matches(&details) {
+                    callback(&details);
+                }
+            }
+        }
+    }
+
+    // Like run(), but with a single asynchronous handler, e.g. one sending messages over the network
+    pub async fn for_each<F, Fut>(mut self, filter: Filter, mut handler: F) -> Error
+    where
+        F: FnMut(ClientDetails) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        loop {
+            match self.next_matching(&filter).await {
+                Ok(details) => handler(details).await,
+                Err(err) => return err,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_matches_name_and_all_tags() {
+        assert!(Filter::default().matches(&ClientDetails::new("Backup", Some(Ok(())))));
+        assert!(Filter::client("Back*").matches(&ClientDetails::new("Backup", Some(Ok(())))));
+        assert!(!Filter::client("Back*").matches(&ClientDetails::new("Restore", Some(Ok(())))));
+
+        let filter = Filter::default().tag("prod").tag("db");
+        assert!(filter
+            .matches(&ClientDetails::new("Backup", Some(Ok(()))).with_tags(&["db", "prod", "eu"])));
+        assert!(!filter.matches(&ClientDetails::new("Backup", Some(Ok(()))).with_tags(&["prod"])));
+    }
+}
@@ -0,0 +1,91 @@
+// TLS of the connection with the server. The certificate of the server is verified with the given CA, so the client
+// can't be redirected to another machine. When the server requires certificates of clients, the client presents its
+// own certificate, which also determines names the client can use.
+
+use check_mate_common::tls::{load_certificates, load_private_key, load_root_store};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::TlsConnector;
+
+// Certificate of the client is given as paths of the certificate and its private key
+pub fn create_tls_connector(
+    ca_path: &str,
+    client_certificate: Option<(&str, &str)>,
+) -> Result<TlsConnector, String> {
+    let builder = ClientConfig::builder().with_root_certificates(load_root_store(ca_path)?);
+    let client_config = match client_certificate {
+        Some((certificate_path, key_path)) => builder
+            .with_client_auth_cert(
+                load_certificates(certificate_path)?,
+                load_private_key(key_path)?,
+            )
+            .map_err(|err| format!("invalid certificate of the client: {err}"))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(TlsConnector::from(Arc::new(client_config)))
+}
+
+// Connects over TLS to the given host, which is verified against the certificate of the server
+pub async fn connect_tls(
+    connector: &TlsConnector,
+    host: &str,
+    tcp_stream: TcpStream,
+) -> Result<ServerStream, String> {
+    let server_name = ServerName::try_from(host.trim_end_matches('.').to_owned())
+        .map_err(|err| format!("invalid name of the server {host}: {err}"))?;
+    let tls_stream = connector
+        .connect(server_name, tcp_stream)
+        .await
+        .map_err(|err| format!("TLS handshake with {host} failed: {err}"))?;
+    Ok(ServerStream::Tls(Box::new(tls_stream)))
+}
+
+pub enum ServerStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+impl AsyncRead for ServerStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ServerStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            ServerStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            ServerStream::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+// Code of the gRPC service is generated without protoc, so the server can be built without it. Messages are defined by
+// hand in src/grpc.rs and both have to match proto/checkmate.proto.
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route_name: &str, input: &str, output: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route_name)
+                .input_type(format!("super::{input}"))
+                .output_type(format!("super::{output}"))
+                .codec_path("tonic_prost::ProstCodec")
+        };
+        let service = Service::builder()
+            .name("CheckMate")
+            .package("checkmate")
+            .method(
+                method(
+                    "set_status",
+                    "SetStatus",
+                    "SetStatusRequest",
+                    "SetStatusResponse",
+                )
+                .build(),
+            )
+            .method(
+                method(
+                    "get_statuses",
+                    "GetStatuses",
+                    "GetStatusesRequest",
+                    "GetStatusesResponse",
+                )
+                .build(),
+            )
+            .method(
+                method("subscribe", "Subscribe", "SubscribeRequest", "ClientStatus")
+                    .server_streaming()
+                    .build(),
+            )
+            .method(method("refresh", "Refresh", "RefreshRequest", "RefreshResponse").build())
+            .build();
+        Builder::new().build_client(false).compile(&[service]);
+    }
+}
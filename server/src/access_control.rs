@@ -0,0 +1,113 @@
+// Network access control of connections of clients. Addresses are matched against networks given in CIDR notation,
+// e.g. 10.0.0.0/8. A connection is denied if its address belongs to any denied network or if allowed networks are
+// specified and it belongs to none of them. Denying takes precedence, so a single host can be excluded from an allowed
+// network.
+
+use std::net::IpAddr;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct IpNetwork {
+    address: IpAddr,
+    prefix_length: u32,
+}
+
+impl IpNetwork {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_length).unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_length).unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+// Parses a network in CIDR notation. A single address without a prefix length is a network of just this address.
+pub fn parse_network(arg: &str) -> Result<IpNetwork, String> {
+    let (address, prefix_length) = match arg.split_once('/') {
+        Some((address, prefix_length)) => (address, Some(prefix_length)),
+        None => (arg, None),
+    };
+    let address = address
+        .parse::<IpAddr>()
+        .map_err(|_| format!("invalid address {address}"))?
+        .to_canonical();
+    let max_prefix_length = match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    let prefix_length = match prefix_length {
+        Some(prefix_length) => prefix_length
+            .parse::<u32>()
+            .ok()
+            .filter(|x| *x <= max_prefix_length)
+            .ok_or_else(|| format!("invalid prefix length {prefix_length}"))?,
+        None => max_prefix_length,
+    };
+    Ok(IpNetwork {
+        address,
+        prefix_length,
+    })
+}
+
+pub fn is_address_allowed(address: IpAddr, allowed: &[IpNetwork], denied: &[IpNetwork]) -> bool {
+    if denied.iter().any(|x| x.contains(address)) {
+        return false;
+    }
+    allowed.is_empty() || allowed.iter().any(|x| x.contains(address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn networks_are_parsed() {
+        let network = parse_network("10.1.0.0/16").unwrap();
+        assert!(network.contains(address("10.1.2.3")));
+        assert!(!network.contains(address("10.2.0.1")));
+        assert!(network.contains(address("::ffff:10.1.2.3")));
+
+        let network = parse_network("192.168.0.7").unwrap();
+        assert!(network.contains(address("192.168.0.7")));
+        assert!(!network.contains(address("192.168.0.8")));
+
+        let network = parse_network("0.0.0.0/0").unwrap();
+        assert!(network.contains(address("203.0.113.7")));
+        assert!(!network.contains(address("2001:db8::1")));
+
+        let network = parse_network("2001:db8::/32").unwrap();
+        assert!(network.contains(address("2001:db8:1::1")));
+        assert!(!network.contains(address("2001:db9::1")));
+
+        assert!(parse_network("10.0.0.0/33").is_err());
+        assert!(parse_network("10.0.0.0/").is_err());
+        assert!(parse_network("localhost/8").is_err());
+        assert!(parse_network("").is_err());
+    }
+
+    #[test]
+    fn denied_networks_take_precedence() {
+        let allowed = [parse_network("10.0.0.0/8").unwrap()];
+        let denied = [parse_network("10.0.0.13").unwrap()];
+        assert!(is_address_allowed(address("10.0.0.1"), &allowed, &denied));
+        assert!(!is_address_allowed(address("10.0.0.13"), &allowed, &denied));
+        assert!(!is_address_allowed(
+            address("192.168.0.1"),
+            &allowed,
+            &denied
+        ));
+
+        assert!(is_address_allowed(address("192.168.0.1"), &[], &denied));
+        assert!(!is_address_allowed(address("10.0.0.13"), &[], &denied));
+    }
+}
@@ -0,0 +1,148 @@
+// Access control of commands based on identities of clients, e.g. names from their certificates. Each identity can be
+// assigned a role deciding which commands it may send. Clients whose identities have no role assigned, including
+// clients without any identity, get the default role. Commands describing the connection itself, such as setting the
+// name or the namespace, are allowed for every role. Managing API keys and seeing all namespaces are left to admins.
+
+use check_mate_common::ServerCommand;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum Role {
+    ReportOnly, // can report own statuses
+    ReadOnly,   // can read statuses of other clients
+    Refresher,  // can read statuses and refresh clients
+    Admin,      // can send all commands, including clearing statuses and aborting the server
+}
+
+impl Role {
+    pub fn allows(self, command: &ServerCommand) -> bool {
+        match command {
+            ServerCommand::SetName(_)
+            | ServerCommand::SetNamespace(_)
+            | ServerCommand::SetTags(_)
+            | ServerCommand::Authenticate(_) => true,
+            ServerCommand::SetStatusOk
+            | ServerCommand::SetStatusError(_)
+            | ServerCommand::SetStatusWarning(_)
+            | ServerCommand::SetStatusPending
+            | ServerCommand::PushStatus(_)
+            | ServerCommand::ReplayedStatus(_, _) => matches!(self, Role::ReportOnly | Role::Admin),
+            ServerCommand::GetStatuses(_)
+            | ServerCommand::ListClients
+            | ServerCommand::GetClientDetails
+            | ServerCommand::GetServerStatistics
+            | ServerCommand::Subscribe
+            | ServerCommand::GetAvailability(_) => self != Role::ReportOnly,
+            ServerCommand::RefreshClientByName(_) | ServerCommand::RefreshAllClients => {
+                matches!(self, Role::Refresher | Role::Admin)
+            }
+            _ => self == Role::Admin,
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self
+            .to_possible_value()
+            .expect("Roles should not be skipped");
+        write!(f, "{}", name.get_name())
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RoleAssignment {
+    pub identity: String,
+    pub role: Role,
+}
+
+// Parses an assignment in the form of <IDENTITY>=<ROLE>, e.g. "Backup=report-only"
+pub fn parse_role_assignment(arg: &str) -> Result<RoleAssignment, String> {
+    let (identity, role) = arg
+        .rsplit_once('=')
+        .filter(|(identity, _)| !identity.is_empty())
+        .ok_or_else(|| "expected <IDENTITY>=<ROLE>".to_owned())?;
+    let role = Role::from_str(role, true).map_err(|_| format!("invalid role {role}"))?;
+    Ok(RoleAssignment {
+        identity: identity.to_owned(),
+        role,
+    })
+}
+
+// Returns the role of the first identity which has one assigned
+pub fn get_role(identities: &[String], assignments: &[RoleAssignment], default_role: Role) -> Role {
+    identities
+        .iter()
+        .find_map(|identity| assignments.iter().find(|x| &x.identity == identity))
+        .map_or(default_role, |x| x.role)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_allow_their_commands() {
+        let commands = [
+            ServerCommand::SetName("Backup".to_owned()),
+            ServerCommand::SetStatusError("No space left".to_owned()),
+            ServerCommand::GetStatuses(true),
+            ServerCommand::RefreshAllClients,
+            ServerCommand::ClearClientByName("Backup".to_owned()),
+            ServerCommand::Abort,
+        ];
+        let allowed = |role: Role| commands.iter().map(|x| role.allows(x)).collect::<Vec<_>>();
+        assert_eq!(
+            allowed(Role::ReportOnly),
+            [true, true, false, false, false, false]
+        );
+        assert_eq!(
+            allowed(Role::ReadOnly),
+            [true, false, true, false, false, false]
+        );
+        assert_eq!(
+            allowed(Role::Refresher),
+            [true, false, true, true, false, false]
+        );
+        assert_eq!(allowed(Role::Admin), [true, true, true, true, true, true]);
+    }
+
+    #[test]
+    fn only_admins_can_see_all_namespaces() {
+        let command = ServerCommand::SelectAllNamespaces;
+        assert!(!Role::ReportOnly.allows(&command));
+        assert!(!Role::ReadOnly.allows(&command));
+        assert!(!Role::Refresher.allows(&command));
+        assert!(Role::Admin.allows(&command));
+    }
+
+    #[test]
+    fn role_assignments_are_parsed() {
+        let assignment = parse_role_assignment("backup.example.com=report-only").unwrap();
+        assert_eq!(
+            assignment,
+            RoleAssignment {
+                identity: "backup.example.com".to_owned(),
+                role: Role::ReportOnly,
+            }
+        );
+        assert!(parse_role_assignment("Backup").is_err());
+        assert!(parse_role_assignment("=admin").is_err());
+        assert!(parse_role_assignment("Backup=owner").is_err());
+    }
+
+    #[test]
+    fn first_assigned_identity_decides_the_role() {
+        let assignments = [
+            parse_role_assignment("backup.example.com=report-only").unwrap(),
+            parse_role_assignment("Operator=admin").unwrap(),
+        ];
+        let identities = ["Backup".to_owned(), "backup.example.com".to_owned()];
+        assert_eq!(
+            get_role(&identities, &assignments, Role::ReadOnly),
+            Role::ReportOnly
+        );
+        assert_eq!(get_role(&[], &assignments, Role::ReadOnly), Role::ReadOnly);
+        assert_eq!(Role::Refresher.to_string(), "refresher");
+    }
+}
@@ -0,0 +1,265 @@
+// API keys created at runtime by admins, so new reporting hosts can be onboarded without editing the config and
+// restarting the server. A key identifies a client the same way a certificate does, so roles are assigned to its
+// identity. Keys look like <ID>.<SECRET>, where the id is public and used to label and revoke the key. Only a hash of
+// the whole key is stored, so the secret is shown once, when the key is created. Keys are saved to a JSON file, if one
+// is set, and survive restarts of the server.
+
+use check_mate_common::{ApiKeyDetails, ServerCommand};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+const ID_LENGTH: usize = 4;
+const SECRET_LENGTH: usize = 16;
+
+#[derive(Clone)]
+pub struct ApiKeys {
+    locked_data: Arc<Mutex<Vec<ApiKey>>>,
+    path: Option<String>,
+}
+
+#[derive(Clone)]
+struct ApiKey {
+    details: ApiKeyDetails,
+    hash: String,
+}
+
+pub fn is_management_command(command: &ServerCommand) -> bool {
+    matches!(
+        command,
+        ServerCommand::CreateApiKey(_, _)
+            | ServerCommand::ListApiKeys
+            | ServerCommand::LabelApiKey(_, _)
+            | ServerCommand::RevokeApiKey(_)
+    )
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+fn hash_key(key: &str) -> String {
+    to_hex(digest(&SHA256, key.as_bytes()).as_ref())
+}
+
+fn generate_random_hex(length: usize) -> Result<String, String> {
+    let mut bytes = vec![0u8; length];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "could not generate a random key".to_owned())?;
+    Ok(to_hex(&bytes))
+}
+
+impl ApiKeys {
+    // Reads keys saved by previous runs of the server. A missing file is created when the first key is saved.
+    pub fn load(path: Option<&str>) -> Result<Self, String> {
+        let keys = match path {
+            Some(path) if std::path::Path::new(path).exists() => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|err| format!("could not read API keys from {path}: {err}"))?;
+                Self::parse(&text).map_err(|err| format!("invalid API keys in {path}: {err}"))?
+            }
+            _ => Vec::new(),
+        };
+        Ok(ApiKeys {
+            locked_data: Arc::new(Mutex::new(keys)),
+            path: path.map(str::to_owned),
+        })
+    }
+
+    fn parse(text: &str) -> Result<Vec<ApiKey>, String> {
+        let value = serde_json::from_str::<Value>(text).map_err(|err| err.to_string())?;
+        let entries = value.as_array().ok_or("expected an array")?;
+        let field = |entry: &Value, name: &str| -> Result<String, String> {
+            entry[name]
+                .as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| format!("missing field {name}"))
+        };
+        entries
+            .iter()
+            .map(|entry| {
+                Ok(ApiKey {
+                    details: ApiKeyDetails {
+                        id: field(entry, "id")?,
+                        identity: field(entry, "identity")?,
+                        label: field(entry, "label")?,
+                    },
+                    hash: field(entry, "hash")?,
+                })
+            })
+            .collect()
+    }
+
+    fn save(&self, keys: &[ApiKey]) -> Result<(), String> {
+        let Some(ref path) = self.path else {
+            return Ok(());
+        };
+        let entries = keys
+            .iter()
+            .map(|key| {
+                json!({
+                    "id": key.details.id,
+                    "identity": key.details.identity,
+                    "label": key.details.label,
+                    "hash": key.hash,
+                })
+            })
+            .collect::<Vec<_>>();
+        let text = serde_json::to_string_pretty(&entries).expect("API keys should serialize");
+        std::fs::write(path, text)
+            .map_err(|err| format!("could not save API keys to {path}: {err}"))
+    }
+
+    // Applies the change to a copy of the keys, which replaces them only if it could be saved
+    fn modify<T>(
+        &self,
+        change: impl FnOnce(&mut Vec<ApiKey>) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut data = self
+            .locked_data
+            .lock()
+            .expect("ApiKeys mutex should not be poisoned");
+        let mut keys = data.clone();
+        let result = change(&mut keys)?;
+        self.save(&keys)?;
+        *data = keys;
+        Ok(result)
+    }
+
+    // Returns the new key, which is the only time its secret is known
+    pub fn create(&self, identity: String, label: String) -> Result<String, String> {
+        if identity.is_empty() {
+            return Err("identity of the API key must not be empty".to_owned());
+        }
+        self.modify(|keys| {
+            let id = loop {
+                let id = generate_random_hex(ID_LENGTH)?;
+                if !keys.iter().any(|x| x.details.id == id) {
+                    break id;
+                }
+            };
+            let key = format!("{}.{}", id, generate_random_hex(SECRET_LENGTH)?);
+            keys.push(ApiKey {
+                details: ApiKeyDetails {
+                    id,
+                    identity,
+                    label,
+                },
+                hash: hash_key(&key),
+            });
+            Ok(key)
+        })
+    }
+
+    pub fn list(&self) -> Vec<ApiKeyDetails> {
+        let data = self
+            .locked_data
+            .lock()
+            .expect("ApiKeys mutex should not be poisoned");
+        data.iter().map(|x| x.details.clone()).collect()
+    }
+
+    pub fn label(&self, id: &str, label: String) -> Result<(), String> {
+        self.modify(|keys| {
+            let key = keys
+                .iter_mut()
+                .find(|x| x.details.id == id)
+                .ok_or_else(|| format!("unknown API key {id}"))?;
+            key.details.label = label;
+            Ok(())
+        })
+    }
+
+    pub fn revoke(&self, id: &str) -> Result<(), String> {
+        self.modify(|keys| {
+            let index = keys
+                .iter()
+                .position(|x| x.details.id == id)
+                .ok_or_else(|| format!("unknown API key {id}"))?;
+            keys.remove(index);
+            Ok(())
+        })
+    }
+
+    // Returns the identity of the key, if it's valid
+    pub fn authenticate(&self, key: &str) -> Option<String> {
+        let (id, _) = key.split_once('.')?;
+        let hash = hash_key(key);
+        let data = self
+            .locked_data
+            .lock()
+            .expect("ApiKeys mutex should not be poisoned");
+        data.iter()
+            .find(|x| x.details.id == id && x.hash == hash)
+            .map(|x| x.details.identity.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn created_keys_authenticate_their_identities() {
+        let api_keys = ApiKeys::load(None).unwrap();
+        let key = api_keys
+            .create("Backup".to_owned(), "nightly backup".to_owned())
+            .unwrap();
+        let (id, secret) = key.split_once('.').unwrap();
+        assert_eq!(id.len(), ID_LENGTH * 2);
+        assert_eq!(secret.len(), SECRET_LENGTH * 2);
+        assert_eq!(api_keys.authenticate(&key), Some("Backup".to_owned()));
+        assert_eq!(api_keys.authenticate(&format!("{id}.0123")), None);
+        assert_eq!(api_keys.authenticate(id), None);
+
+        assert!(api_keys.create(String::new(), String::new()).is_err());
+    }
+
+    #[test]
+    fn keys_are_labeled_and_revoked() {
+        let api_keys = ApiKeys::load(None).unwrap();
+        let key = api_keys.create("Backup".to_owned(), String::new()).unwrap();
+        let id = key.split_once('.').unwrap().0;
+
+        api_keys.label(id, "weekly backup".to_owned()).unwrap();
+        assert_eq!(
+            api_keys.list(),
+            [ApiKeyDetails {
+                id: id.to_owned(),
+                identity: "Backup".to_owned(),
+                label: "weekly backup".to_owned(),
+            }]
+        );
+
+        api_keys.revoke(id).unwrap();
+        assert!(api_keys.list().is_empty());
+        assert_eq!(api_keys.authenticate(&key), None);
+        assert!(api_keys.revoke(id).is_err());
+        assert!(api_keys.label(id, String::new()).is_err());
+    }
+
+    #[test]
+    fn keys_are_saved_and_loaded() {
+        let path =
+            std::env::temp_dir().join(format!("check_mate_api_keys_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let api_keys = ApiKeys::load(Some(path)).unwrap();
+        let key = api_keys
+            .create("Backup".to_owned(), "nightly backup".to_owned())
+            .unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        assert!(!text.contains(key.split_once('.').unwrap().1));
+
+        let loaded = ApiKeys::load(Some(path)).unwrap();
+        assert_eq!(loaded.list(), api_keys.list());
+        assert_eq!(loaded.authenticate(&key), Some("Backup".to_owned()));
+
+        std::fs::write(path, "[{\"id\": \"3f9a1c7e\"}]").unwrap();
+        assert!(ApiKeys::load(Some(path)).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
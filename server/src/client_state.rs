@@ -1,41 +1,187 @@
-use check_mate_common::ServerCommand;
+use crate::acl::Role;
+use crate::api_keys;
+use crate::namespaces::{qualify_name, Namespace, Scope};
+use crate::status_cache::StatusCache;
+use check_mate_common::{ClientDetails, HistoryEntry, ServerCommand};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
+// Commands accepted from a connection depend on the listener it came from. Control commands, which affect other
+// clients or the server itself, can be limited to a separate admin listener.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Capabilities {
+    All,
+    ReportsAndReads,
+}
+
 pub struct ClientState {
     log_every_status: bool,
+    capabilities: Capabilities,
     name: Option<String>,
-    status: Result<(), String>,
+    identities: Vec<String>, // names allowed by the certificate of the client, any name is allowed if empty
+    role: Role,
+    namespace: Namespace,
+    scope: Scope, // clients visible to this one
+    tags: Vec<String>,
+    status: Result<(), Arc<str>>,
+    status_reported: bool,
+    status_pending: bool,
+    status_warning: bool, // the error is only a warning
+    status_time: Instant,
+    status_cache: StatusCache,
     messages_to_send_queue: (Sender<ServerCommand>, Receiver<ServerCommand>),
+    history_export: VecDeque<Vec<HistoryEntry>>, // chunks of an export, which haven't been sent yet
+    status_changes: Option<broadcast::Receiver<(Namespace, ClientDetails)>>,
 }
 
 pub enum ProcessCommandResult {
     Ok,
+    Abort,
+    StatusChanged,
+    Subscribe,
     GetStatuses(bool),
     RefreshClientByName(String),
     RefreshAllClients,
+    ClearClientByName(String),
     ListClients,
+    GetServerStatistics,
+    GetClientDetails,
+    PushStatus(ClientDetails),
+    CreateApiKey(String, String),
+    ListApiKeys,
+    LabelApiKey(String, String),
+    RevokeApiKey(String),
+    Drain,
+    GetAvailability(u64),
+    CompactHistory,
+    ExportHistory(u64, u64),
 }
 
 impl ClientState {
-    pub fn new(log_every_status: bool) -> Self {
+    pub fn new(
+        log_every_status: bool,
+        capabilities: Capabilities,
+        status_cache: StatusCache,
+    ) -> Self {
         ClientState {
             log_every_status,
+            capabilities,
             name: None,
+            identities: Vec::new(),
+            role: Role::Admin,
+            namespace: None,
+            scope: Scope::default(),
+            tags: Vec::new(),
             status: Ok(()),
+            status_reported: false,
+            status_pending: false,
+            status_warning: false,
+            status_time: Instant::now(),
+            status_cache,
             messages_to_send_queue: channel(2),
+            history_export: VecDeque::new(),
+            status_changes: None,
         }
     }
 
-    pub fn get_status(&self) -> &Result<(), String> {
+    pub fn get_status(&self) -> &Result<(), Arc<str>> {
         &self.status
     }
 
+    pub fn has_reported_status(&self) -> bool {
+        self.status_reported
+    }
+
+    pub fn get_details(&self) -> ClientDetails {
+        let status = match self.status {
+            _ if !self.status_reported => None,
+            Ok(_) => Some(Ok(())),
+            Err(ref err) => Some(Err(err.to_string())),
+        };
+        ClientDetails {
+            name: self.get_name_or_default(),
+            status,
+            pending: self.status_pending && !self.status_reported,
+            warning: self.status_warning && self.status.is_err(),
+            age_seconds: self.status_time.elapsed().as_secs(),
+            tags: self.tags.clone(),
+        }
+    }
+
+    // Marks the status as ok on request of another client, e.g. after an incident has been handled. Returns whether
+    // the status has changed. The watched command may report the error again on its next run.
+    pub fn clear_status(&mut self) -> bool {
+        if self.status.is_ok() {
+            return false;
+        }
+        println!("Client {} was cleared", self.get_name_or_default());
+        self.status = Ok(());
+        self.status_warning = false;
+        self.status_time = Instant::now();
+        true
+    }
+
     pub fn get_name(&self) -> &Option<String> {
         &self.name
     }
 
+    // The name is qualified with the namespace of the client
     pub fn get_name_or_default(&self) -> String {
-        self.name.clone().unwrap_or("<Unknown>".to_owned())
+        let name = self.name.as_deref().unwrap_or("<Unknown>");
+        qualify_name(&self.namespace, name)
+    }
+
+    // Clients authenticated with certificates or API keys are named after them from the start
+    pub fn set_identities(&mut self, identities: Vec<String>) {
+        self.name = identities.first().cloned();
+        self.identities = identities;
+    }
+
+    pub fn get_role(&self) -> Role {
+        self.role
+    }
+
+    pub fn set_role(&mut self, role: Role) {
+        self.role = role;
+    }
+
+    pub fn get_namespace(&self) -> &Namespace {
+        &self.namespace
+    }
+
+    pub fn get_scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    pub fn is_visible_in(&self, scope: &Scope) -> bool {
+        scope
+            .view_name(&self.namespace, &self.get_name_or_default())
+            .is_some()
+    }
+
+    // Unnamed clients can't be addressed by name, even though they are listed as "<Unknown>"
+    pub fn is_named_in(&self, scope: &Scope, name: &str) -> bool {
+        self.name.is_some()
+            && scope
+                .view_name(&self.namespace, &self.get_name_or_default())
+                .as_deref()
+                == Some(name)
+    }
+
+    pub fn is_allowed(&self, command: &ServerCommand) -> bool {
+        let is_control = matches!(
+            command,
+            ServerCommand::Abort
+                | ServerCommand::RefreshClientByName(_)
+                | ServerCommand::RefreshAllClients
+                | ServerCommand::ClearClientByName(_)
+                | ServerCommand::Drain
+                | ServerCommand::CompactHistory
+        ) || api_keys::is_management_command(command);
+        !is_control || self.capabilities == Capabilities::All
     }
 
     pub async fn push_command_to_send(&mut self, command: ServerCommand) {
@@ -46,38 +192,113 @@ impl ClientState {
             .expect("Receiver inside ClientState should never be destroyed");
     }
 
+    // Chunks are queued separately from other commands, because there can be more of them than the queue can hold.
+    // An empty chunk is queued last, so the client knows the export has ended.
+    pub fn push_history_export(&mut self, chunks: Vec<Vec<HistoryEntry>>) {
+        self.history_export.extend(chunks);
+        self.history_export.push_back(Vec::new());
+    }
+
     pub async fn get_command_to_send(&mut self) -> ServerCommand {
-        self.messages_to_send_queue
-            .1
-            .recv()
-            .await
-            .expect("Sender inside ClientState should never be destroyed")
+        if let Some(chunk) = self.history_export.pop_front() {
+            return ServerCommand::HistoryExport(Ok(chunk));
+        }
+        let queue = &mut self.messages_to_send_queue.1;
+        let status_changes = &mut self.status_changes;
+        let scope = &self.scope;
+        tokio::select! {
+            command = queue.recv() => command.expect("Sender inside ClientState should never be destroyed"),
+            details = Self::get_status_change(status_changes, scope) => ServerCommand::StatusChanged(details),
+        }
+    }
+
+    pub fn subscribe_status_changes(
+        &mut self,
+        receiver: broadcast::Receiver<(Namespace, ClientDetails)>,
+    ) {
+        self.status_changes = Some(receiver);
+    }
+
+    // Waits for a status change of any client visible in the scope. Never completes if the client hasn't subscribed.
+    async fn get_status_change(
+        status_changes: &mut Option<broadcast::Receiver<(Namespace, ClientDetails)>>,
+        scope: &Scope,
+    ) -> ClientDetails {
+        loop {
+            let result = match status_changes {
+                Some(ref mut receiver) => receiver.recv().await,
+                None => std::future::pending().await,
+            };
+            match result {
+                Ok((namespace, details)) => match scope.view_details(&namespace, details) {
+                    Some(details) => return details,
+                    None => continue,
+                },
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => *status_changes = None,
+            }
+        }
+    }
+
+    // Stores an error or a warning reported by the client. Returns whether the status has changed.
+    fn set_error(&mut self, new_err: String, warning: bool) -> bool {
+        let is_new_error = match self.status {
+            Ok(_) => true,
+            Err(ref old_err) => **old_err != new_err || self.status_warning != warning,
+        };
+        if is_new_error {
+            self.status = Err(self.status_cache.intern(&new_err));
+            self.status_warning = warning;
+        }
+        let is_change = is_new_error || !self.status_reported;
+        self.status_reported = true;
+        self.status_time = Instant::now();
+        if self.log_every_status || is_new_error {
+            println!(
+                "Client {} has {}: {}",
+                self.get_name_or_default(),
+                if warning { "warning" } else { "error" },
+                self.status.as_ref().unwrap_err()
+            );
+        }
+        is_change
     }
 
     pub fn process_command(&mut self, command: ServerCommand) -> ProcessCommandResult {
+        if !self.is_allowed(&command) {
+            println!(
+                "Client {} is not allowed to send control commands on this port",
+                self.get_name_or_default()
+            );
+            return ProcessCommandResult::Ok;
+        }
+
         match command {
             ServerCommand::Abort => {
                 println!("Received abort command");
-                std::process::exit(0);
+                return ProcessCommandResult::Abort;
             }
             ServerCommand::SetStatusOk => {
+                let is_change = self.status.is_err() || !self.status_reported;
                 if self.log_every_status || self.status.is_err() {
                     println!("Client {} is ok", self.get_name_or_default());
                 }
                 self.status = Ok(());
+                self.status_warning = false;
+                self.status_reported = true;
+                self.status_time = Instant::now();
+                if is_change {
+                    return ProcessCommandResult::StatusChanged;
+                }
             }
             ServerCommand::SetStatusError(new_err) => {
-                let is_new_error = match self.status {
-                    Ok(_) => true,
-                    Err(ref old_err) => *old_err != new_err,
-                };
-                self.status = Err(new_err);
-                if self.log_every_status || is_new_error {
-                    println!(
-                        "Client {} has error: {}",
-                        self.get_name_or_default(),
-                        self.status.as_ref().unwrap_err()
-                    );
+                if self.set_error(new_err, false) {
+                    return ProcessCommandResult::StatusChanged;
+                }
+            }
+            ServerCommand::SetStatusWarning(new_err) => {
+                if self.set_error(new_err, true) {
+                    return ProcessCommandResult::StatusChanged;
                 }
             }
             ServerCommand::GetStatuses(include_names) => {
@@ -87,14 +308,116 @@ impl ClientState {
                 return ProcessCommandResult::RefreshClientByName(name)
             }
             ServerCommand::RefreshAllClients => return ProcessCommandResult::RefreshAllClients,
+            ServerCommand::ClearClientByName(name) => {
+                return ProcessCommandResult::ClearClientByName(name)
+            }
             ServerCommand::ListClients => return ProcessCommandResult::ListClients,
+            ServerCommand::GetServerStatistics => return ProcessCommandResult::GetServerStatistics,
+            ServerCommand::GetClientDetails => return ProcessCommandResult::GetClientDetails,
+            ServerCommand::Subscribe => return ProcessCommandResult::Subscribe,
+            ServerCommand::CreateApiKey(identity, label) => {
+                return ProcessCommandResult::CreateApiKey(identity, label)
+            }
+            ServerCommand::ListApiKeys => return ProcessCommandResult::ListApiKeys,
+            ServerCommand::LabelApiKey(id, label) => {
+                return ProcessCommandResult::LabelApiKey(id, label)
+            }
+            ServerCommand::RevokeApiKey(id) => return ProcessCommandResult::RevokeApiKey(id),
+            ServerCommand::Drain => return ProcessCommandResult::Drain,
+            ServerCommand::GetAvailability(window_seconds) => {
+                return ProcessCommandResult::GetAvailability(window_seconds)
+            }
+            ServerCommand::CompactHistory => return ProcessCommandResult::CompactHistory,
+            ServerCommand::ExportHistory(start, end) => {
+                return ProcessCommandResult::ExportHistory(start, end)
+            }
+            ServerCommand::Authenticate(_) => {
+                panic!("Authentication should be handled before processing commands")
+            }
+            ServerCommand::SetStatusPending => {
+                // Pending status only matters until the first real status is reported
+                if !self.status_reported && !self.status_pending {
+                    println!("Client {} is pending", self.get_name_or_default());
+                    self.status_pending = true;
+                    self.status_time = Instant::now();
+                    return ProcessCommandResult::StatusChanged;
+                }
+            }
+            ServerCommand::PushStatus(status) => {
+                // Pushed status is stored independently of this connection, which is about to be closed
+                return ProcessCommandResult::PushStatus(ClientDetails {
+                    name: self.get_name_or_default(),
+                    status: Some(status),
+                    pending: false,
+                    warning: false,
+                    age_seconds: 0,
+                    tags: self.tags.clone(),
+                });
+            }
+            ServerCommand::SetTags(tags) => {
+                println!("Tags set to {}", tags.join(", "));
+                self.tags = tags;
+            }
+            ServerCommand::ReplayedStatus(timestamp, status) => {
+                // Replayed statuses are only a history of what happened while the client was offline. They are
+                // logged, but the current status is set by a regular status command sent afterwards.
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |x| x.as_millis() as u64);
+                let age_seconds = now.saturating_sub(timestamp) / 1000;
+                match status {
+                    Ok(_) => println!(
+                        "Client {} was ok (replayed, {}s ago)",
+                        self.get_name_or_default(),
+                        age_seconds
+                    ),
+                    Err(err) => println!(
+                        "Client {} had error: {} (replayed, {}s ago)",
+                        self.get_name_or_default(),
+                        err,
+                        age_seconds
+                    ),
+                }
+            }
+            ServerCommand::SetName(name)
+                if !self.identities.is_empty() && !self.identities.contains(&name) =>
+            {
+                println!(
+                    "Client {} is not allowed to use name {}",
+                    self.get_name_or_default(),
+                    name
+                );
+            }
             ServerCommand::SetName(name) => {
                 println!("Name set to {}", name);
                 self.name = Some(name);
             }
+            ServerCommand::SetNamespace(namespace) => {
+                println!("Namespace set to {}", namespace);
+                self.namespace = Some(namespace);
+                if self.scope != Scope::AllNamespaces {
+                    self.scope = Scope::Namespace(self.namespace.clone());
+                }
+            }
+            ServerCommand::SelectAllNamespaces => {
+                println!(
+                    "Client {} selected all namespaces",
+                    self.get_name_or_default()
+                );
+                self.scope = Scope::AllNamespaces;
+            }
             ServerCommand::Statuses(_) => panic!("Unexpected server command"),
             ServerCommand::Refresh => panic!("Unexpected server command"),
             ServerCommand::Clients(_) => panic!("Unexpected server command"),
+            ServerCommand::ServerStatistics(_) => panic!("Unexpected server command"),
+            ServerCommand::ClientDetails(_) => panic!("Unexpected server command"),
+            ServerCommand::StatusChanged(_) => panic!("Unexpected server command"),
+            ServerCommand::ApiKeyResult(_) => panic!("Unexpected server command"),
+            ServerCommand::ApiKeys(_) => panic!("Unexpected server command"),
+            ServerCommand::DrainProgress(_) => panic!("Unexpected server command"),
+            ServerCommand::Availability(_) => panic!("Unexpected server command"),
+            ServerCommand::HistoryCompacted(_) => panic!("Unexpected server command"),
+            ServerCommand::HistoryExport(_) => panic!("Unexpected server command"),
         };
 
         ProcessCommandResult::Ok
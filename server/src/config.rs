@@ -1,79 +1,209 @@
+use crate::access_control::{parse_network, IpNetwork};
+use crate::acl::{parse_role_assignment, Role, RoleAssignment};
+use crate::rate_limit::RateLimitAction;
 use check_mate_common::{
-    constants::*, fetch_arg, fetch_arg_bool, format_args_list, format_text, CommandLineError,
+    constants::*, format_duration, parse_bool, parse_duration, parse_non_empty_string,
 };
+use clap::{ArgAction, CommandFactory, Parser};
+use std::net::IpAddr;
+use std::time::Duration;
 
-#[derive(PartialEq, Debug, Clone)]
+#[derive(PartialEq, Debug, Clone, Parser)]
+#[command(
+    name = "check_mate_server",
+    version = VERSION,
+    disable_version_flag = true,
+    about = "Server for CheckMate. Accumulates statuses reported by clients and serves them on request.",
+)]
 pub struct Config {
+    /// Set TCP port for the server.
+    #[arg(short = 'p', long = "port", value_name = "PORT", default_value_t = DEFAULT_PORT)]
     pub server_port: u16,
+
+    /// Listen on <ADDRESS>, e.g. 0.0.0.0 to accept connections from other hosts. Listeners enabled with --http-port,
+    /// --nrpe-port and --grpc-port use the same address. The admin listener set with --admin-port stays on loopback.
+    #[arg(long = "bind", value_name = "ADDRESS", default_value_t = DEFAULT_BIND_ADDRESS)]
+    pub bind_address: IpAddr,
+
+    /// Accept control commands, i.e. abort, refresh and clear, only on a separate admin listener on <PORT>. The port
+    /// set with --port then accepts only status reports and reads, so clients reporting statuses can't affect others.
+    /// The admin listener is bound to loopback regardless of --bind, so control commands can only be sent locally.
+    #[arg(long = "admin-port", value_name = "PORT")]
+    pub admin_port: Option<u16>,
+
+    /// Accept connections of clients only from addresses within <NETWORK>, given in CIDR notation, e.g. 10.0.0.0/8.
+    /// Can be specified multiple times. By default connections from all addresses are accepted.
+    #[arg(long = "allow", value_name = "NETWORK", value_parser = parse_network)]
+    pub allowed_networks: Vec<IpNetwork>,
+
+    /// Deny connections of clients from addresses within <NETWORK>, given in CIDR notation. Can be specified multiple
+    /// times and takes precedence over --allow, e.g. to exclude a single host from an allowed network.
+    #[arg(long = "deny", value_name = "NETWORK", value_parser = parse_network)]
+    pub denied_networks: Vec<IpNetwork>,
+
+    /// Expect a PROXY protocol header, version 1 or 2, at the beginning of every connection of clients. Use it when
+    /// the server is behind a TCP load balancer, so addresses of clients are known instead of the balancer's one.
+    /// Connections without a valid header are rejected.
+    #[arg(long = "proxy-protocol")]
+    pub proxy_protocol: bool,
+
+    /// Accept connections of clients over TLS with the certificate chain in <FILE>, given in PEM format.
+    #[arg(long = "tls-cert", value_name = "FILE", requires = "tls_key")]
+    pub tls_cert: Option<String>,
+
+    /// Set the private key of the certificate set with --tls-cert, given in PEM format.
+    #[arg(long = "tls-key", value_name = "FILE", requires = "tls_cert")]
+    pub tls_key: Option<String>,
+
+    /// Require clients to present certificates signed by the CA in <FILE>, given in PEM format. Names of clients are
+    /// then taken from their certificates. A client can only use the common name or a DNS name of its certificate.
+    #[arg(long = "tls-client-ca", value_name = "FILE", requires = "tls_cert")]
+    pub tls_client_ca: Option<String>,
+
+    /// Assign a role to clients identified as <IDENTITY>, e.g. "backup.example.com=report-only". Clients are identified
+    /// by names from their certificates or by identities of their API keys. Roles are report-only, read-only,
+    /// refresher and admin. Can be specified multiple times. Commands not allowed by the role end the connection.
+    #[arg(long = "role", value_name = "IDENTITY=ROLE", value_parser = parse_role_assignment)]
+    pub roles: Vec<RoleAssignment>,
+
+    /// Set the role of clients without a role assigned with --role, including clients without any identity.
+    #[arg(long = "default-role", value_name = "ROLE", default_value = "admin")]
+    pub default_role: Role,
+
+    /// Save API keys created with the api-key action of the client to <FILE>, so they survive restarts of the server.
+    /// Without it, created keys are only kept in memory.
+    #[arg(long = "api-keys-file", value_name = "FILE")]
+    pub api_keys_file: Option<String>,
+
+    /// Record every status change in an SQLite database in <FILE>, so availability of clients can be reported for
+    /// past days, weeks and months. The file is created if it doesn't exist.
+    #[arg(long = "history-file", value_name = "FILE")]
+    pub history_file: Option<String>,
+
+    /// Remove statuses older than <DURATION> from the history. The last status of each client is kept regardless of its
+    /// age. Pruning is done every hour and can be triggered with the compact-history command of the client.
+    #[arg(long = "history-max-age", value_name = "DURATION", value_parser = parse_duration, requires = "history_file")]
+    pub history_max_age: Option<Duration>,
+
+    /// Keep at most <NUMBER> latest statuses of each client in the history.
+    #[arg(long = "history-max-rows", value_name = "NUMBER", value_parser = clap::value_parser!(u32).range(1..), requires = "history_file")]
+    pub history_max_rows: Option<u32>,
+
+    /// Set whether the server should log every status received from clients or only when it changes.
+    #[arg(
+        short = 'e',
+        long = "log-every-status",
+        value_name = "BOOLEAN",
+        value_parser = parse_bool,
+        action = ArgAction::Set,
+        default_value_t = DEFAULT_LOG_EVERY_STATUS,
+    )]
     pub log_every_status: bool,
-    pub help: bool,
+
+    /// Limit status reports of each connection to <NUMBER> per second, allowing bursts of up to one second's worth of
+    /// them. Statuses relayed by a downstream server with --upstream share the limit of its connection.
+    #[arg(long = "status-rate-limit", value_name = "NUMBER", value_parser = clap::value_parser!(u32).range(1..))]
+    pub status_rate_limit: Option<u32>,
+
+    /// Set what happens to a client exceeding --status-rate-limit. Throttled clients have their statuses delayed until
+    /// they fit into the limit, warned ones are only logged and others are disconnected.
+    #[arg(
+        long = "rate-limit-action",
+        value_name = "ACTION",
+        default_value = "throttle",
+        requires = "status_rate_limit"
+    )]
+    pub rate_limit_action: RateLimitAction,
+
+    #[arg(
+        long = "self-monitoring",
+        value_name = "NAME",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_SELF_MONITORING_NAME,
+        value_parser = parse_non_empty_string,
+        help = format!("Report health of the server itself, e.g. failures of forwarding to Zabbix, as the status of a virtual client named <NAME>. Default name is {}.", DEFAULT_SELF_MONITORING_NAME),
+    )]
+    pub self_monitoring: Option<String>,
+
+    /// Accept pings over HTTP on <PORT>. GET or POST to /ping/<NAME> pushes an ok status and to /ping/<NAME>/fail
+    /// pushes an error with the request body as its message. With "?interval=<DURATION>" the status turns into an
+    /// error if the next ping doesn't come within the duration. GET to /feed returns an Atom feed of recent incidents.
+    #[arg(long = "http-port", value_name = "PORT")]
+    pub http_port: Option<u16>,
+
+    /// Answer queries of Nagios or Icinga sent with check_nrpe on <PORT>. Supported commands are "check_checkmate",
+    /// which checks all clients, and "check_checkmate!<NAME>", which checks a single client. SSL is not supported, so
+    /// check_nrpe has to be run with -n.
+    #[arg(long = "nrpe-port", value_name = "PORT")]
+    pub nrpe_port: Option<u16>,
+
+    /// Forward status changes to a Zabbix server at <ADDRESS> with the protocol of zabbix_sender. The port defaults
+    /// to 10051. Each client is mapped to trapper items checkmate.status[<NAME>] and checkmate.message[<NAME>].
+    #[arg(long = "zabbix-server", value_name = "ADDRESS")]
+    pub zabbix_server: Option<String>,
+
+    /// Set the name of the host in Zabbix, which items of clients belong to.
+    #[arg(long = "zabbix-host", value_name = "NAME", default_value = DEFAULT_ZABBIX_HOST, requires = "zabbix_server")]
+    pub zabbix_host: String,
+
+    /// Relay statuses of all clients to an upstream CheckMate server at <ADDRESS>, acting as a client on their behalf.
+    /// The port defaults to 10005. Names of clients are prefixed with the site, e.g. "warsaw/backup".
+    #[arg(long = "upstream", value_name = "ADDRESS", requires = "site")]
+    pub upstream: Option<String>,
+
+    /// Set the identifier of this server's site, which prefixes names of clients relayed to the upstream server.
+    #[arg(long = "site", value_name = "NAME", value_parser = parse_non_empty_string, requires = "upstream")]
+    pub site: Option<String>,
+
+    /// Run as a standby of the primary server at <ADDRESS>, mirroring all its statuses. The port defaults to 10005.
+    /// Clients should be given addresses of both servers, e.g. "-a primary,standby", to connect to the standby when the
+    /// primary is down.
+    #[arg(long = "standby-of", value_name = "ADDRESS")]
+    pub standby_of: Option<String>,
+
+    #[arg(
+        long = "promotion-timeout",
+        value_name = "DURATION",
+        value_parser = parse_duration,
+        requires = "standby_of",
+        help = format!("Set how long the primary server has to be unreachable for the standby to promote itself and stop mirroring. Default is {}.", format_duration(DEFAULT_PROMOTION_TIMEOUT)),
+    )]
+    pub promotion_timeout: Option<Duration>,
+
+    /// Serve the gRPC API described by proto/checkmate.proto on <PORT>.
+    #[cfg(feature = "grpc")]
+    #[arg(long = "grpc-port", value_name = "PORT")]
+    pub grpc_port: Option<u16>,
+
+    /// Export spans of commands, status transition events and metrics to an OpenTelemetry collector listening for
+    /// OTLP over gRPC at <URL>, e.g. "http://localhost:4317".
+    #[cfg(feature = "opentelemetry")]
+    #[arg(long = "otlp-endpoint", value_name = "URL")]
+    pub otlp_endpoint: Option<String>,
+
+    /// Print version.
+    #[arg(short = 'v', long = "version")]
     pub version: bool,
+
+    /// Print shell completion script for the server.
+    #[arg(long = "completions", value_name = "SHELL")]
+    pub completions: Option<clap_complete::Shell>,
 }
 
 impl Config {
-    fn parse_options<T: Iterator<Item = String>>(
-        &mut self,
-        args: &mut T,
-    ) -> Result<(), CommandLineError> {
-        while let Some(arg) = args.next() {
-            match arg.as_ref() {
-                "-p" => {
-                    let port =
-                        fetch_arg(args, CommandLineError::NoValueSpecified("port".into(), arg))?;
-                    let port = match port.parse::<u16>() {
-                        Ok(x) => x,
-                        Err(_) => return Err(CommandLineError::InvalidValue("port".into(), port)),
-                    };
-                    self.server_port = port;
-                }
-                "-e" => {
-                    self.log_every_status = fetch_arg_bool(
-                        args,
-                        || {
-                            CommandLineError::NoValueSpecified(
-                                "a boolean value".into(),
-                                arg.clone(),
-                            )
-                        },
-                        |value| {
-                            CommandLineError::InvalidValue("log every status".into(), value.into())
-                        },
-                    )?;
-                }
-                "-h" => {
-                    self.help = true;
-                }
-                "-v" => {
-                    self.version = true;
-                }
-                _ => return Err(CommandLineError::InvalidArgument(arg)),
-            }
-        }
-        Ok(())
-    }
-
-    pub fn parse<T: Iterator<Item = String>>(mut args: T) -> Result<Config, CommandLineError> {
-        let mut config = Config::default();
-        config.parse_options(&mut args)?;
-        Ok(config)
+    pub fn parse<T: Iterator<Item = String>>(args: T) -> Result<Config, clap::Error> {
+        let binary_name = std::iter::once("check_mate_server".to_owned());
+        Config::try_parse_from(binary_name.chain(args))
     }
 
-    pub fn print_help() {
-        let intro = "Usage: check_mate_server [<args>]";
-        println!("{}\n", format_text(intro, HELP_MESSAGE_MAX_LINE_WIDTH));
-
-        let arguments_intro = "Available args:";
-        println!("{}", format_text(arguments_intro, HELP_MESSAGE_MAX_LINE_WIDTH));
-
-        let arguments = [
-            ("-p <port>", format!("Set TCP port for the server. Default is {DEFAULT_PORT}.")),
-            ("-e <boolean>", format!("Set whether the server should log every status received from clients or only when it changes. Default is {DEFAULT_LOG_EVERY_STATUS}.")),
-            ("-h", "Print this message.".to_owned()),
-            ("-v", "Print version.".to_owned()),
-        ];
-        println!(
-            "{}",
-            format_args_list(&arguments, HELP_MESSAGE_BASIC_INDENT_WIDTH, HELP_MESSAGE_MAX_LINE_WIDTH)
+    pub fn print_completions(shell: clap_complete::Shell) {
+        let mut command = Config::command();
+        clap_complete::generate(
+            shell,
+            &mut command,
+            "check_mate_server",
+            &mut std::io::stdout(),
         );
     }
 }
@@ -82,14 +212,44 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             server_port: DEFAULT_PORT,
+            bind_address: DEFAULT_BIND_ADDRESS,
+            admin_port: None,
+            allowed_networks: Vec::new(),
+            denied_networks: Vec::new(),
+            proxy_protocol: false,
+            tls_cert: None,
+            tls_key: None,
+            tls_client_ca: None,
+            roles: Vec::new(),
+            default_role: Role::Admin,
+            api_keys_file: None,
+            history_file: None,
+            history_max_age: None,
+            history_max_rows: None,
             log_every_status: DEFAULT_LOG_EVERY_STATUS,
-            help: false,
+            status_rate_limit: None,
+            rate_limit_action: RateLimitAction::Throttle,
+            self_monitoring: None,
+            http_port: None,
+            nrpe_port: None,
+            zabbix_server: None,
+            zabbix_host: DEFAULT_ZABBIX_HOST.to_owned(),
+            upstream: None,
+            site: None,
+            standby_of: None,
+            promotion_timeout: None,
+            #[cfg(feature = "grpc")]
+            grpc_port: None,
+            #[cfg(feature = "opentelemetry")]
+            otlp_endpoint: None,
             version: false,
+            completions: None,
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
 
@@ -122,6 +282,22 @@ mod tests {
         assert_eq!(config, expected);
     }
 
+    #[test]
+    fn bind_address_is_parsed() {
+        let args = ["--bind", "0.0.0.0"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            bind_address: "0.0.0.0".parse().unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["--bind", "localhost"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
     #[test]
     fn log_every_status_is_parsed() {
         let args = ["-e", "1"];
@@ -132,4 +308,365 @@ mod tests {
         expected.log_every_status = true;
         assert_eq!(config, expected);
     }
+
+    #[test]
+    fn long_options_are_parsed() {
+        let args = ["--port", "123", "--log-every-status", "true"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            server_port: 123,
+            log_every_status: true,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn admin_port_is_parsed() {
+        let args = ["--admin-port", "10006"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            admin_port: Some(10006),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn networks_are_parsed() {
+        let args = [
+            "--allow",
+            "10.0.0.0/8",
+            "--allow",
+            "192.168.0.0/16",
+            "--deny",
+            "10.0.0.13",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            allowed_networks: vec![
+                parse_network("10.0.0.0/8").unwrap(),
+                parse_network("192.168.0.0/16").unwrap(),
+            ],
+            denied_networks: vec![parse_network("10.0.0.13").unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["--allow", "10.0.0.0/40"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn tls_files_are_parsed() {
+        let args = [
+            "--tls-cert",
+            "server.pem",
+            "--tls-key",
+            "server.key",
+            "--tls-client-ca",
+            "ca.pem",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            tls_cert: Some("server.pem".into()),
+            tls_key: Some("server.key".into()),
+            tls_client_ca: Some("ca.pem".into()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["--tls-cert", "server.pem"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+        let args = ["--tls-client-ca", "ca.pem"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn roles_are_parsed() {
+        let args = [
+            "--role",
+            "Backup=report-only",
+            "--role",
+            "Operator=admin",
+            "--default-role",
+            "read-only",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            roles: vec![
+                parse_role_assignment("Backup=report-only").unwrap(),
+                parse_role_assignment("Operator=admin").unwrap(),
+            ],
+            default_role: Role::ReadOnly,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["--default-role", "owner"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn status_rate_limit_is_parsed() {
+        let args = [
+            "--status-rate-limit",
+            "5",
+            "--rate-limit-action",
+            "disconnect",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            status_rate_limit: Some(5),
+            rate_limit_action: RateLimitAction::Disconnect,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["--status-rate-limit", "0"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+        let args = ["--rate-limit-action", "warn"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn history_file_is_parsed() {
+        let args = ["--history-file", "/var/lib/check_mate/history.db"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            history_file: Some("/var/lib/check_mate/history.db".into()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn history_retention_is_parsed() {
+        let args = [
+            "--history-file",
+            "history.db",
+            "--history-max-age",
+            "90d",
+            "--history-max-rows",
+            "1000",
+        ];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            history_file: Some("history.db".into()),
+            history_max_age: Some(Duration::from_secs(90 * 24 * 3600)),
+            history_max_rows: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["--history-max-age", "90d"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+        let args = ["--history-file", "history.db", "--history-max-rows", "0"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn self_monitoring_is_parsed() {
+        let args = ["--self-monitoring"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        assert_eq!(config.self_monitoring, Some("_checkmate_server".into()));
+
+        let args = ["--self-monitoring=warsaw-monitor"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+        assert_eq!(config.self_monitoring, Some("warsaw-monitor".into()));
+
+        let args = ["--self-monitoring="];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    fn api_keys_file_is_parsed() {
+        let args = ["--api-keys-file", "/var/lib/check_mate/api_keys.json"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            api_keys_file: Some("/var/lib/check_mate/api_keys.json".into()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn proxy_protocol_is_parsed() {
+        let args = ["--proxy-protocol"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            proxy_protocol: true,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn http_port_is_parsed() {
+        let args = ["--http-port", "8080"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            http_port: Some(8080),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn nrpe_port_is_parsed() {
+        let args = ["--nrpe-port", "5666"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            nrpe_port: Some(5666),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn zabbix_server_is_parsed() {
+        let args = ["--zabbix-server", "zabbix:10051", "--zabbix-host", "web-1"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            zabbix_server: Some("zabbix:10051".to_owned()),
+            zabbix_host: "web-1".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn upstream_is_parsed() {
+        let args = ["--upstream", "central:10005", "--site", "warsaw"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            upstream: Some("central:10005".to_owned()),
+            site: Some("warsaw".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        assert!(Config::parse(to_owned_string_iter(&["--upstream", "central"])).is_err());
+        assert!(Config::parse(to_owned_string_iter(&["--site", "warsaw"])).is_err());
+    }
+
+    #[test]
+    fn standby_is_parsed() {
+        let args = ["--standby-of", "primary", "--promotion-timeout", "1m"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            standby_of: Some("primary".to_owned()),
+            promotion_timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+
+        let args = ["--promotion-timeout", "1m"];
+        assert!(Config::parse(to_owned_string_iter(&args)).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "grpc")]
+    fn grpc_port_is_parsed() {
+        let args = ["--grpc-port", "50051"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            grpc_port: Some(50051),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    #[cfg(feature = "opentelemetry")]
+    fn otlp_endpoint_is_parsed() {
+        let args = ["--otlp-endpoint", "http://collector:4317"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            otlp_endpoint: Some("http://collector:4317".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn version_is_parsed() {
+        let args = ["-v"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            version: true,
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn completions_shell_is_parsed() {
+        let args = ["--completions", "bash"];
+        let config = Config::parse(to_owned_string_iter(&args));
+        let config = config.expect("Parsing should succeed");
+
+        let expected = Config {
+            completions: Some(clap_complete::Shell::Bash),
+            ..Default::default()
+        };
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn invalid_args_return_error() {
+        fn run(args: &[&str], expected_kind: clap::error::ErrorKind) {
+            let config = Config::parse(to_owned_string_iter(args));
+            let err = config.expect_err("Parsing should not succeed");
+            assert_eq!(err.kind(), expected_kind);
+        }
+
+        run(&["-p"], clap::error::ErrorKind::InvalidValue);
+        run(&["-p", "abc"], clap::error::ErrorKind::ValueValidation);
+        run(&["-e", "2"], clap::error::ErrorKind::ValueValidation);
+        run(&["-k"], clap::error::ErrorKind::UnknownArgument);
+        run(
+            &["--zabbix-host", "web-1"],
+            clap::error::ErrorKind::MissingRequiredArgument,
+        );
+        run(&["-h"], clap::error::ErrorKind::DisplayHelp);
+    }
 }
@@ -0,0 +1,57 @@
+// Draining mode for maintenance, e.g. before upgrading the binary. A draining server stops accepting new connections
+// on the main port, so they go to another instance, but keeps serving clients which are already connected. They can
+// then move over gradually instead of all reconnecting at once when the server is stopped. The admin port, if there is
+// one, still accepts connections, so the progress can be checked.
+
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[derive(Clone)]
+pub struct Draining {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Draining {
+    pub fn new() -> Self {
+        Draining {
+            sender: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    // Returns whether draining has just started, i.e. the server wasn't already draining
+    pub fn start(&self) -> bool {
+        !self.sender.send_replace(true)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    pub async fn wait_for_start(&self) {
+        let mut receiver = self.sender.subscribe();
+        receiver
+            .wait_for(|x| *x)
+            .await
+            .expect("Sender is kept alive by self");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn draining_is_started_once() {
+        let draining = Draining::new();
+        assert!(!draining.is_draining());
+
+        let waiting = tokio::spawn({
+            let draining = draining.clone();
+            async move { draining.wait_for_start().await }
+        });
+        assert!(draining.start());
+        assert!(!draining.start());
+        assert!(draining.is_draining());
+        waiting.await.unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+// Relaying of statuses to an upstream CheckMate server, so servers of separate sites can be aggregated into one central
+// view. The server connects to the upstream one like a client and pushes the status of each of its clients under the
+// name prefixed with the site, e.g. "warsaw/backup". Pushed statuses are kept by the upstream server, so they survive
+// reconnections. Besides relaying changes, all statuses are periodically synchronized, which also clears clients that
+// are gone, e.g. ones which disconnected. Between pushes the connection itself is named after the site and reports an
+// ok status, so the upstream server shows which sites are connected.
+
+use crate::config::Config;
+use crate::pushed_statuses::PushedStatuses;
+use crate::self_monitoring::SelfMonitoring;
+use crate::task_communication::TaskCommunication;
+use check_mate_common::constants::*;
+use check_mate_common::{ClientDetails, CommunicationError, ServerCommand};
+use std::collections::HashMap;
+use tokio::io::{AsyncWrite, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+fn upstream_name(site: &str, name: &str) -> String {
+    format!("{site}/{name}")
+}
+
+// Remembers what was relayed over the current connection, so only actual changes are sent
+#[derive(Default)]
+struct RelayState {
+    statuses: HashMap<String, ClientDetails>,
+    tags: Vec<String>,
+}
+
+impl RelayState {
+    // Returns commands relaying the status of a client, which are empty if the upstream server already has it
+    fn relay_status(&mut self, site: &str, details: ClientDetails) -> Vec<ServerCommand> {
+        let Some(status) = details.status.clone() else {
+            return Vec::new();
+        };
+        let previous = self.statuses.get(&details.name);
+        if previous.is_some_and(|x| x.status == details.status && x.tags == details.tags) {
+            return Vec::new();
+        }
+
+        let mut commands = vec![ServerCommand::SetName(upstream_name(site, &details.name))];
+        if self.tags != details.tags {
+            self.tags = details.tags.clone();
+            commands.push(ServerCommand::SetTags(details.tags.clone()));
+        }
+        commands.push(ServerCommand::PushStatus(status));
+        self.statuses.insert(details.name.clone(), details);
+        commands
+    }
+
+    // Returns commands bringing the upstream server in line with all current statuses
+    fn synchronize(&mut self, site: &str, clients: Vec<ClientDetails>) -> Vec<ServerCommand> {
+        let clients = clients
+            .into_iter()
+            .filter(|x| x.status.is_some())
+            .collect::<Vec<_>>();
+        let mut commands = Vec::new();
+        self.statuses.retain(|name, _| {
+            let is_present = clients.iter().any(|x| &x.name == name);
+            if !is_present {
+                commands.push(ServerCommand::ClearClientByName(upstream_name(site, name)));
+            }
+            is_present
+        });
+        for details in clients {
+            commands.extend(self.relay_status(site, details));
+        }
+        commands
+    }
+}
+
+async fn send_commands(
+    output_stream: &mut (impl AsyncWrite + Unpin),
+    commands: Vec<ServerCommand>,
+) -> Result<(), CommunicationError> {
+    for command in commands {
+        command.send_async(output_stream).await?;
+    }
+    Ok(())
+}
+
+async fn relay(
+    stream: TcpStream,
+    site: &str,
+    task_communication: &TaskCommunication,
+    pushed_statuses: &PushedStatuses,
+) -> Result<(), CommunicationError> {
+    let (input_stream, mut output_stream) = stream.into_split();
+    let mut input_stream = BufReader::new(input_stream);
+    let commands = vec![
+        ServerCommand::SetName(site.to_owned()),
+        ServerCommand::SetStatusOk,
+    ];
+    send_commands(&mut output_stream, commands).await?;
+
+    let mut status_changes = task_communication.subscribe_status_changes();
+    let mut state = RelayState::default();
+    let mut ticks = tokio::time::interval(FEDERATION_SYNC_INTERVAL);
+    loop {
+        let mut commands = tokio::select! {
+            _ = ticks.tick() => {
+                let mut clients = task_communication.get_client_details_for_service().await;
+                clients.extend(pushed_statuses.get_details());
+                state.synchronize(site, clients)
+            }
+            details = status_changes.recv() => match details {
+                Ok((_, details)) => state.relay_status(site, details),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue, // Missed changes come with the next sync
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            // Refresh requests of the upstream server are meant for its own clients, so they are ignored
+            command = ServerCommand::receive_async(&mut input_stream) => {
+                command?;
+                continue;
+            }
+        };
+        if !commands.is_empty() {
+            commands.push(ServerCommand::SetName(site.to_owned()));
+        }
+        send_commands(&mut output_stream, commands).await?;
+    }
+}
+
+pub fn relay_to_upstream(
+    config: &Config,
+    task_communication: TaskCommunication,
+    pushed_statuses: PushedStatuses,
+    self_monitoring: SelfMonitoring,
+) {
+    let (Some(ref upstream), Some(ref site)) = (&config.upstream, &config.site) else {
+        return;
+    };
+    let upstream = match upstream.contains(':') {
+        true => upstream.clone(),
+        false => format!("{upstream}:{DEFAULT_PORT}"),
+    };
+    let site = site.clone();
+    tokio::spawn(async move {
+        loop {
+            match TcpStream::connect(&upstream).await {
+                Ok(stream) => {
+                    println!("Connected to upstream server {}", upstream);
+                    self_monitoring.report("upstream", Ok(()));
+                    let result = relay(stream, &site, &task_communication, &pushed_statuses);
+                    if let Err(err) = result.await {
+                        eprintln!("Lost connection to upstream server: {}", err);
+                        self_monitoring.report("upstream", Err(format!("lost connection: {err}")));
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Failed to connect to upstream server {}: {}", upstream, err);
+                    self_monitoring.report("upstream", Err(format!("could not connect: {err}")));
+                }
+            }
+            tokio::time::sleep(FEDERATION_RECONNECT_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changes_are_relayed() {
+        let mut state = RelayState::default();
+        assert_eq!(
+            state.relay_status("waw", ClientDetails::new("backup", Some(Err("Full")))),
+            [
+                ServerCommand::SetName("waw/backup".to_owned()),
+                ServerCommand::PushStatus(Err("Full".to_owned())),
+            ]
+        );
+        assert_eq!(
+            state.relay_status("waw", ClientDetails::new("backup", Some(Err("Full")))),
+            []
+        );
+        assert_eq!(
+            state.relay_status(
+                "waw",
+                ClientDetails::new("disk", Some(Ok(()))).with_tags(&["db"])
+            ),
+            [
+                ServerCommand::SetName("waw/disk".to_owned()),
+                ServerCommand::SetTags(vec!["db".to_owned()]),
+                ServerCommand::PushStatus(Ok(())),
+            ]
+        );
+        assert_eq!(
+            state.relay_status("waw", ClientDetails::new("reader", None)),
+            []
+        );
+    }
+
+    #[test]
+    fn clients_which_are_gone_are_cleared() {
+        let mut state = RelayState::default();
+        let clients = vec![
+            ClientDetails::new("backup", Some(Ok(()))),
+            ClientDetails::new("disk", Some(Ok(()))),
+        ];
+        assert_eq!(state.synchronize("waw", clients).len(), 4);
+
+        let clients = vec![
+            ClientDetails::new("backup", Some(Ok(()))),
+            ClientDetails::new("disk", None),
+        ];
+        assert_eq!(
+            state.synchronize("waw", clients),
+            [ServerCommand::ClearClientByName("waw/disk".to_owned())]
+        );
+    }
+}
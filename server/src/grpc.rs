@@ -0,0 +1,287 @@
+// Optional gRPC service mirroring the core operations of the native protocol, so services written in other languages
+// can report and read statuses with stubs generated from proto/checkmate.proto instead of implementing the framing of
+// the native protocol. Every call is independent, so statuses set over gRPC are stored like pushed statuses.
+
+use crate::namespaces::Scope;
+use crate::pushed_statuses::PushedStatuses;
+use crate::statistics::Statistics;
+use crate::task_communication::{TaskCommunication, SERVICE_TASK_ID};
+use check_mate_common::ClientDetails;
+use proto::check_mate_server::{CheckMate, CheckMateServer};
+use proto::*;
+use std::pin::Pin;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::{BroadcastStream, TcpListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response};
+
+// Messages have to match proto/checkmate.proto
+pub mod proto {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Status {
+        Unknown = 0,
+        Pending = 1,
+        Ok = 2,
+        Error = 3,
+        Warning = 4,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ClientStatus {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(enumeration = "Status", tag = "2")]
+        pub status: i32,
+        #[prost(string, tag = "3")]
+        pub message: String,
+        #[prost(uint64, tag = "4")]
+        pub age_seconds: u64,
+        #[prost(string, repeated, tag = "5")]
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SetStatusRequest {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, optional, tag = "2")]
+        pub error: Option<String>,
+        #[prost(string, repeated, tag = "3")]
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SetStatusResponse {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetStatusesRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct GetStatusesResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub clients: Vec<ClientStatus>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequest {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RefreshRequest {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct RefreshResponse {}
+
+    include!(concat!(env!("OUT_DIR"), "/checkmate.CheckMate.rs"));
+}
+
+fn to_client_status(details: ClientDetails) -> ClientStatus {
+    let (status, message) = match details.status {
+        None if details.pending => (Status::Pending, String::new()),
+        None => (Status::Unknown, String::new()),
+        Some(Ok(_)) => (Status::Ok, String::new()),
+        Some(Err(message)) if details.warning => (Status::Warning, message),
+        Some(Err(message)) => (Status::Error, message),
+    };
+    ClientStatus {
+        name: details.name,
+        status: status as i32,
+        message,
+        age_seconds: details.age_seconds,
+        tags: details.tags,
+    }
+}
+
+struct CheckMateService {
+    pushed_statuses: PushedStatuses,
+    statistics: Statistics,
+    task_communication: TaskCommunication,
+    log_every_status: bool,
+}
+
+#[tonic::async_trait]
+impl CheckMate for CheckMateService {
+    async fn set_status(
+        &self,
+        request: Request<SetStatusRequest>,
+    ) -> Result<Response<SetStatusResponse>, tonic::Status> {
+        let request = request.into_inner();
+        if request.name.is_empty() {
+            return Err(tonic::Status::invalid_argument("name cannot be empty"));
+        }
+        let details = ClientDetails {
+            name: request.name,
+            status: Some(request.error.map_or(Ok(()), Err)),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: request.tags,
+        };
+        crate::store_pushed_status(
+            None,
+            details,
+            None,
+            &self.pushed_statuses,
+            &self.statistics,
+            &self.task_communication,
+            self.log_every_status,
+        );
+        Ok(Response::new(SetStatusResponse {}))
+    }
+
+    async fn get_statuses(
+        &self,
+        _request: Request<GetStatusesRequest>,
+    ) -> Result<Response<GetStatusesResponse>, tonic::Status> {
+        let mut details = self
+            .task_communication
+            .get_client_details_for_service()
+            .await;
+        details.extend(self.pushed_statuses.get_details());
+        let clients = details.into_iter().map(to_client_status).collect();
+        Ok(Response::new(GetStatusesResponse { clients }))
+    }
+
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<ClientStatus, tonic::Status>> + Send>>;
+
+    // Changes missed because of a lag are skipped
+    async fn subscribe(
+        &self,
+        _request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, tonic::Status> {
+        let status_changes = self.task_communication.subscribe_status_changes();
+        let stream = BroadcastStream::new(status_changes)
+            .filter_map(|change| change.ok().map(|(_, x)| Ok(to_client_status(x))));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn refresh(
+        &self,
+        request: Request<RefreshRequest>,
+    ) -> Result<Response<RefreshResponse>, tonic::Status> {
+        match request.into_inner().name {
+            Some(name) => {
+                self.task_communication
+                    .refresh_client_by_name(SERVICE_TASK_ID, Scope::AllNamespaces, name)
+                    .await
+            }
+            None => {
+                self.task_communication
+                    .refresh_all_clients(SERVICE_TASK_ID, Scope::AllNamespaces)
+                    .await
+            }
+        }
+        Ok(Response::new(RefreshResponse {}))
+    }
+}
+
+pub async fn serve(
+    listener: TcpListener,
+    pushed_statuses: PushedStatuses,
+    statistics: Statistics,
+    task_communication: TaskCommunication,
+    log_every_status: bool,
+) {
+    let service = CheckMateService {
+        pushed_statuses,
+        statistics,
+        task_communication,
+        log_every_status,
+    };
+    let result = tonic::transport::Server::builder()
+        .add_service(CheckMateServer::new(service))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await;
+    if let Err(err) = result {
+        eprintln!("ERROR: gRPC server failed: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> CheckMateService {
+        CheckMateService {
+            pushed_statuses: PushedStatuses::new(),
+            statistics: Statistics::new(),
+            task_communication: TaskCommunication::new(),
+            log_every_status: false,
+        }
+    }
+
+    #[test]
+    fn details_are_converted() {
+        let mut details = ClientDetails {
+            name: "backup".to_owned(),
+            status: None,
+            pending: true,
+            warning: false,
+            age_seconds: 5,
+            tags: vec!["db".to_owned()],
+        };
+        assert_eq!(
+            to_client_status(details.clone()).status,
+            Status::Pending as i32
+        );
+        details.pending = false;
+        assert_eq!(
+            to_client_status(details.clone()).status,
+            Status::Unknown as i32
+        );
+        details.status = Some(Err("No space left".to_owned()));
+        assert_eq!(
+            to_client_status(details.clone()),
+            ClientStatus {
+                name: "backup".to_owned(),
+                status: Status::Error as i32,
+                message: "No space left".to_owned(),
+                age_seconds: 5,
+                tags: vec!["db".to_owned()],
+            }
+        );
+        details.warning = true;
+        assert_eq!(
+            to_client_status(details).status,
+            Status::Warning as i32
+        );
+    }
+
+    #[tokio::test]
+    async fn statuses_are_set_and_read() {
+        let service = service();
+        let mut subscription = service
+            .subscribe(Request::new(SubscribeRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let request = SetStatusRequest {
+            name: "backup".to_owned(),
+            error: Some("No space left".to_owned()),
+            tags: Vec::new(),
+        };
+        service.set_status(Request::new(request)).await.unwrap();
+        let response = service
+            .get_statuses(Request::new(GetStatusesRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.clients.len(), 1);
+        assert_eq!(response.clients[0].message, "No space left");
+
+        let change = subscription.next().await.unwrap().unwrap();
+        assert_eq!(change.name, "backup");
+        assert_eq!(change.status, Status::Error as i32);
+    }
+
+    #[tokio::test]
+    async fn status_without_name_is_rejected() {
+        let request = SetStatusRequest::default();
+        let result = service().set_status(Request::new(request)).await;
+        assert_eq!(result.unwrap_err().code(), tonic::Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,571 @@
+// History of statuses of all clients, kept in an SQLite database, so reports can look further back than the current
+// statuses. Every published status change is recorded along with the time it happened at. A status lasts until the
+// next change of the same client, so statuses within any window of time can be reconstructed from the changes in it
+// and the last change before it. SQLite calls block, so they are run on threads meant for blocking.
+//
+// The database doesn't grow without bound if a retention is set. Statuses older than the maximum age and statuses
+// beyond the maximum count of rows of a client are pruned periodically. The latest status of each client is always
+// kept, so statuses which haven't changed for a long time are still known. Compaction prunes immediately and also
+// shrinks the file, which SQLite doesn't do on its own.
+//
+// Recorded statuses can also be exported as they are, e.g. for offline analysis. Exports are split into chunks of
+// bounded size, so sending them doesn't require one message as large as the whole history.
+
+use crate::namespaces::{Namespace, Scope};
+use crate::self_monitoring::SelfMonitoring;
+use check_mate_common::{constants::*, ClientAvailability, ClientDetails, HistoryEntry, Severity};
+use rusqlite::{params, Connection};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+#[derive(Clone)]
+pub struct History {
+    connection: Option<Arc<Mutex<Connection>>>, // None if disabled
+    retention: Retention,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Retention {
+    pub max_age: Option<Duration>,
+    pub max_rows: Option<u32>, // per client
+}
+
+struct Change {
+    namespace: Namespace,
+    name: String,
+    status: Severity,
+    time: u64, // unix time in milliseconds
+}
+
+pub fn get_unix_milliseconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_millis() as u64)
+}
+
+// Statuses are stored by their names, which are parsed back when they're read
+fn get_severity(row: &rusqlite::Row, index: usize) -> rusqlite::Result<Severity> {
+    let name: String = row.get(index)?;
+    name.parse().map_err(|err: String| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, err.into())
+    })
+}
+
+fn to_error(err: rusqlite::Error) -> String {
+    format!("history database failed: {err}")
+}
+
+// Time a client spent in each status so far, while going through its changes
+struct StatusTimes {
+    status: Severity,
+    since: u64,
+    ok: u64,
+    error: u64,
+}
+
+impl StatusTimes {
+    // Adds the time of the current status, which lasted until the given time, counting only what's within the window
+    fn add_until(&mut self, until: u64, start: u64) {
+        let duration = until.saturating_sub(self.since.max(start));
+        match self.status {
+            // Clients with warnings are still working, so they count as available
+            Severity::Ok | Severity::Warning => self.ok += duration,
+            Severity::Error => self.error += duration,
+            Severity::Pending | Severity::Unknown => (),
+        }
+    }
+}
+
+// Sums up how long each client was ok and failing between start and end. Changes have to be sorted by time, starting
+// with the last changes before the window, which set the statuses at its start.
+fn compute_availability(
+    changes: Vec<Change>,
+    start: u64,
+    end: u64,
+) -> Vec<(Namespace, ClientAvailability)> {
+    let mut clients = BTreeMap::new();
+    for change in changes {
+        let times = clients
+            .entry((change.namespace, change.name))
+            .or_insert_with(|| StatusTimes {
+                status: Severity::Unknown,
+                since: change.time,
+                ok: 0,
+                error: 0,
+            });
+        times.add_until(change.time, start);
+        times.status = change.status;
+        times.since = change.time;
+    }
+    clients
+        .into_iter()
+        .map(|((namespace, name), mut times)| {
+            times.add_until(end, start);
+            let availability = ClientAvailability {
+                name,
+                ok_seconds: times.ok / 1000,
+                error_seconds: times.error / 1000,
+            };
+            (namespace, availability)
+        })
+        .collect()
+}
+
+impl History {
+    // Opens the database, creating it if needed. Without a path history is disabled.
+    pub fn open(path: Option<&str>, retention: Retention) -> Result<Self, String> {
+        let Some(path) = path else {
+            return Ok(History {
+                connection: None,
+                retention,
+            });
+        };
+        let connection = Connection::open(path)
+            .map_err(|err| format!("could not open history database {path}: {err}"))?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS statuses (
+                    time INTEGER NOT NULL,
+                    namespace TEXT,
+                    name TEXT NOT NULL,
+                    status TEXT NOT NULL,
+                    message TEXT
+                );
+                CREATE INDEX IF NOT EXISTS statuses_by_time ON statuses (time);",
+            )
+            .map_err(|err| format!("could not create history database {path}: {err}"))?;
+        Ok(History {
+            connection: Some(Arc::new(Mutex::new(connection))),
+            retention,
+        })
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>, String> {
+        let connection = self
+            .connection
+            .as_ref()
+            .ok_or("history is not enabled, see --history-file of the server")?;
+        Ok(connection
+            .lock()
+            .expect("History mutex should not be poisoned"))
+    }
+
+    fn record(
+        &self,
+        namespace: &Namespace,
+        details: &ClientDetails,
+        time: u64,
+    ) -> Result<(), String> {
+        let message = match details.status {
+            Some(Err(ref err)) => Some(err.as_str()),
+            _ => None,
+        };
+        self.lock()?
+            .execute(
+                "INSERT INTO statuses (time, namespace, name, status, message) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![time, namespace, details.name, details.severity().as_str(), message],
+            )
+            .map_err(to_error)?;
+        Ok(())
+    }
+
+    // Records status changes published by all tasks in the background. Failures are reported by self-monitoring.
+    pub fn record_status_changes(
+        &self,
+        mut status_changes: broadcast::Receiver<(Namespace, ClientDetails)>,
+        self_monitoring: SelfMonitoring,
+    ) {
+        if self.connection.is_none() {
+            return;
+        }
+        let history = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (namespace, details) = match status_changes.recv().await {
+                    Ok(x) => x,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                let time = get_unix_milliseconds(SystemTime::now());
+                let history = history.clone();
+                let result =
+                    tokio::task::spawn_blocking(move || history.record(&namespace, &details, time))
+                        .await
+                        .expect("Recording a status should not panic");
+                if let Err(ref err) = result {
+                    eprintln!("ERROR: {}", err);
+                }
+                self_monitoring.report("history", result);
+            }
+        });
+    }
+
+    // Removes statuses outside of the retention, except for the latest status of each client. Returns how many were
+    // removed.
+    fn prune(&self, now: u64) -> Result<u64, String> {
+        let connection = self.lock()?;
+        let mut removed = 0;
+        if let Some(max_age) = self.retention.max_age {
+            let oldest_time = now.saturating_sub(max_age.as_millis() as u64);
+            removed += connection
+                .execute(
+                    "DELETE FROM statuses WHERE time < ?1 AND rowid NOT IN
+                        (SELECT MAX(rowid) FROM statuses GROUP BY namespace, name)",
+                    [oldest_time],
+                )
+                .map_err(to_error)?;
+        }
+        if let Some(max_rows) = self.retention.max_rows {
+            removed += connection
+                .execute(
+                    "DELETE FROM statuses WHERE rowid IN (SELECT rowid FROM (SELECT rowid, ROW_NUMBER() OVER
+                        (PARTITION BY namespace, name ORDER BY time DESC, rowid DESC) AS position FROM statuses)
+                        WHERE position > ?1)",
+                    [max_rows],
+                )
+                .map_err(to_error)?;
+        }
+        Ok(removed as u64)
+    }
+
+    // Prunes statuses in the background, if a retention is set. Failures are reported by self-monitoring.
+    pub fn prune_periodically(&self, self_monitoring: SelfMonitoring) {
+        let has_retention = self.retention.max_age.is_some() || self.retention.max_rows.is_some();
+        if self.connection.is_none() || !has_retention {
+            return;
+        }
+        let history = self.clone();
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(HISTORY_PRUNING_INTERVAL);
+            loop {
+                ticks.tick().await;
+                let now = get_unix_milliseconds(SystemTime::now());
+                let pruned_history = history.clone();
+                let result = tokio::task::spawn_blocking(move || pruned_history.prune(now))
+                    .await
+                    .expect("Pruning history should not panic");
+                match result {
+                    Ok(0) => (),
+                    Ok(removed) => println!("Removed {} statuses from history", removed),
+                    Err(ref err) => eprintln!("ERROR: {}", err),
+                }
+                self_monitoring.report("history pruning", result.map(|_| ()));
+            }
+        });
+    }
+
+    // Prunes statuses right away and shrinks the database file. Returns how many statuses were removed.
+    pub async fn compact(&self) -> Result<u64, String> {
+        let history = self.clone();
+        let compact = move || {
+            let removed = history.prune(get_unix_milliseconds(SystemTime::now()))?;
+            history.lock()?.execute_batch("VACUUM").map_err(to_error)?;
+            Ok(removed)
+        };
+        tokio::task::spawn_blocking(compact)
+            .await
+            .expect("Compacting history should not panic")
+    }
+
+    fn query_changes(&self, start: u64, end: u64) -> Result<Vec<Change>, String> {
+        let connection = self.lock()?;
+        let read_change = |row: &rusqlite::Row| {
+            Ok(Change {
+                namespace: row.get(0)?,
+                name: row.get(1)?,
+                status: get_severity(row, 2)?,
+                time: row.get(3)?,
+            })
+        };
+        // SQLite takes other columns from the row with the maximum time
+        let mut changes = connection
+            .prepare("SELECT namespace, name, status, MAX(time) FROM statuses WHERE time < ?1 GROUP BY namespace, name")
+            .and_then(|mut x| x.query_map([start], read_change)?.collect::<Result<Vec<_>, _>>())
+            .map_err(to_error)?;
+        changes.sort_by_key(|x| x.time);
+        let changes_in_window = connection
+            .prepare("SELECT namespace, name, status, time FROM statuses WHERE time >= ?1 AND time < ?2 ORDER BY time, rowid")
+            .and_then(|mut x| x.query_map([start, end], read_change)?.collect::<Result<Vec<_>, _>>())
+            .map_err(to_error)?;
+        changes.extend(changes_in_window);
+        Ok(changes)
+    }
+
+    // Returns availability of clients visible in the scope within the window ending at the given time, sorted by their
+    // names
+    pub async fn get_availability(
+        &self,
+        scope: Scope,
+        window: Duration,
+        now: SystemTime,
+    ) -> Result<Vec<ClientAvailability>, String> {
+        let end = get_unix_milliseconds(now);
+        let start = end.saturating_sub(window.as_millis() as u64);
+        let history = self.clone();
+        let changes = tokio::task::spawn_blocking(move || history.query_changes(start, end))
+            .await
+            .expect("Querying history should not panic")?;
+        let mut clients = compute_availability(changes, start, end)
+            .into_iter()
+            .filter_map(|(namespace, client)| {
+                let name = scope.view_name(&namespace, &client.name)?;
+                Some(ClientAvailability { name, ..client })
+            })
+            .collect::<Vec<_>>();
+        clients.sort_by(|x, y| x.name.cmp(&y.name));
+        Ok(clients)
+    }
+
+    // Returns statuses of clients visible in the scope recorded between start and end, sorted by time
+    pub async fn export(
+        &self,
+        scope: Scope,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<HistoryEntry>, String> {
+        // SQLite integers are signed
+        let (start, end) = (start.min(i64::MAX as u64), end.min(i64::MAX as u64));
+        let history = self.clone();
+        let query = move || {
+            let connection = history.lock()?;
+            let read_entry = |row: &rusqlite::Row| {
+                let namespace: Namespace = row.get(1)?;
+                let entry = HistoryEntry {
+                    time_ms: row.get(0)?,
+                    name: row.get(2)?,
+                    status: get_severity(row, 3)?,
+                    message: row.get(4)?,
+                };
+                Ok((namespace, entry))
+            };
+            connection
+                .prepare("SELECT time, namespace, name, status, message FROM statuses WHERE time >= ?1 AND time < ?2 ORDER BY time, rowid")
+                .and_then(|mut x| x.query_map([start, end], read_entry)?.collect::<Result<Vec<_>, _>>())
+                .map_err(to_error)
+        };
+        let entries = tokio::task::spawn_blocking(query)
+            .await
+            .expect("Querying history should not panic")?;
+        let entries = entries
+            .into_iter()
+            .filter_map(|(namespace, entry)| {
+                let name = scope.view_name(&namespace, &entry.name)?;
+                Some(HistoryEntry { name, ..entry })
+            })
+            .collect();
+        Ok(entries)
+    }
+}
+
+// Returns the number of bytes the entry takes in a HistoryExport command
+fn get_encoded_size(entry: &HistoryEntry) -> usize {
+    let message_size = entry.message.as_ref().map_or(0, |x| 4 + x.len());
+    8 + 4 + entry.name.len() + 4 + entry.status.as_str().len() + 1 + message_size
+}
+
+// Splits exported entries into chunks of at most HISTORY_EXPORT_CHUNK_BYTES. An entry larger than that is sent in a
+// chunk of its own.
+pub fn split_export(entries: Vec<HistoryEntry>) -> Vec<Vec<HistoryEntry>> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut chunk_size = 0;
+    for entry in entries {
+        let entry_size = get_encoded_size(&entry);
+        if !chunk.is_empty() && chunk_size + entry_size > HISTORY_EXPORT_CHUNK_BYTES {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_size = 0;
+        }
+        chunk_size += entry_size;
+        chunk.push(entry);
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(name: &str, status: Severity, time: u64) -> Change {
+        Change {
+            namespace: None,
+            name: name.to_owned(),
+            status,
+            time,
+        }
+    }
+
+    #[test]
+    fn availability_is_computed_from_changes() {
+        let changes = vec![
+            change("Backup", Severity::Error, 1_000),
+            change("Backup", Severity::Ok, 20_000),
+            change("Cleanup", Severity::Pending, 30_000),
+            change("Cleanup", Severity::Error, 40_000),
+            change("Backup", Severity::Error, 90_000),
+        ];
+        let clients = compute_availability(changes, 10_000, 110_000);
+        let clients = clients.into_iter().map(|x| x.1).collect::<Vec<_>>();
+        assert_eq!(
+            clients,
+            [
+                ClientAvailability {
+                    name: "Backup".to_owned(),
+                    ok_seconds: 70,
+                    error_seconds: 30,
+                },
+                ClientAvailability {
+                    name: "Cleanup".to_owned(),
+                    ok_seconds: 0,
+                    error_seconds: 70,
+                },
+            ]
+        );
+        assert_eq!(clients[0].get_percentage(), Some(70.0));
+    }
+
+    #[tokio::test]
+    async fn recorded_statuses_are_queried() {
+        let history = History::open(Some(":memory:"), Retention::default()).unwrap();
+        let details = |name: &str, status: Result<(), &str>| ClientDetails {
+            name: name.to_owned(),
+            status: Some(status.map_err(str::to_owned)),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        };
+        let now = SystemTime::now();
+        let ago = |seconds| get_unix_milliseconds(now - Duration::from_secs(seconds));
+        let team = Some("team".to_owned());
+        history
+            .record(&None, &details("Backup", Ok(())), ago(60))
+            .unwrap();
+        history
+            .record(&None, &details("Backup", Err("No space left")), ago(15))
+            .unwrap();
+        history
+            .record(&team, &details("team/Cleanup", Ok(())), ago(1))
+            .unwrap();
+
+        let clients = history
+            .get_availability(Scope::default(), Duration::from_secs(30), now)
+            .await
+            .unwrap();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].name, "Backup");
+        assert_eq!(clients[0].ok_seconds, 15);
+        assert_eq!(clients[0].error_seconds, 15);
+
+        let clients = history
+            .get_availability(Scope::Namespace(team), Duration::from_secs(30), now)
+            .await
+            .unwrap();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].name, "Cleanup");
+
+        let entries = history
+            .export(Scope::AllNamespaces, ago(30), ago(0))
+            .await
+            .unwrap();
+        assert_eq!(
+            entries,
+            [
+                HistoryEntry {
+                    time_ms: ago(15),
+                    name: "Backup".to_owned(),
+                    status: Severity::Error,
+                    message: Some("No space left".to_owned()),
+                },
+                HistoryEntry {
+                    time_ms: ago(1),
+                    name: "team/Cleanup".to_owned(),
+                    status: Severity::Ok,
+                    message: None,
+                },
+            ]
+        );
+        let entries = history.export(Scope::default(), 0, ago(0)).await.unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let disabled = History::open(None, Retention::default()).unwrap();
+        assert!(disabled
+            .get_availability(Scope::default(), Duration::from_secs(30), now)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn statuses_outside_of_retention_are_pruned() {
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(60)),
+            max_rows: Some(2),
+        };
+        let history = History::open(Some(":memory:"), retention).unwrap();
+        let details = |name: &str| ClientDetails {
+            name: name.to_owned(),
+            status: Some(Ok(())),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        };
+        let now = get_unix_milliseconds(SystemTime::now());
+        for time in [now - 300_000, now - 200_000] {
+            history.record(&None, &details("Stable"), time).unwrap();
+        }
+        for time in [now - 30_000, now - 20_000, now - 10_000] {
+            history.record(&None, &details("Flapping"), time).unwrap();
+        }
+
+        assert_eq!(history.compact().await, Ok(2));
+        let changes = history.query_changes(0, now + 1).unwrap();
+        let changes = changes
+            .iter()
+            .map(|x| (x.name.as_str(), now - x.time))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                ("Stable", 200_000),
+                ("Flapping", 20_000),
+                ("Flapping", 10_000)
+            ]
+        );
+        assert_eq!(history.compact().await, Ok(0));
+    }
+
+    #[test]
+    fn exports_are_split_into_bounded_chunks() {
+        let entry = |message_size: usize| HistoryEntry {
+            time_ms: 0,
+            name: "Backup".to_owned(),
+            status: Severity::Error,
+            message: Some("x".repeat(message_size)),
+        };
+        let chunk_sizes = |entries: Vec<HistoryEntry>| {
+            split_export(entries)
+                .iter()
+                .map(|chunk| chunk.len())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(chunk_sizes(Vec::new()), Vec::<usize>::new());
+        assert_eq!(chunk_sizes(vec![entry(10), entry(10)]), [2]);
+        assert_eq!(
+            chunk_sizes((0..10).map(|_| entry(1000)).collect()),
+            [3, 3, 3, 1]
+        );
+        assert_eq!(
+            chunk_sizes(vec![entry(10), entry(10000), entry(10)]),
+            [1, 1, 1]
+        );
+        for chunk in split_export((0..100).map(|_| entry(100)).collect()) {
+            let size = chunk.iter().map(get_encoded_size).sum::<usize>();
+            assert!(size <= HISTORY_EXPORT_CHUNK_BYTES);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+// HTTP endpoint for devices and cron jobs which can't run the client, but can run curl. A request to /ping/<NAME>
+// pushes an ok status and a request to /ping/<NAME>/fail pushes an error with the body of the request as its message.
+// Both GET and POST are accepted. A ping can carry an expected interval in the query, e.g. /ping/backup?interval=1d,
+// in which case the status turns into an error if the next ping doesn't come in time. GET to /feed returns an Atom feed
+// of recent incidents. Only the small subset of HTTP/1.1 needed by such clients is implemented and every connection
+// serves a single request.
+
+use crate::incidents::Incidents;
+use crate::pushed_statuses::PushedStatuses;
+use crate::statistics::Statistics;
+use crate::task_communication::TaskCommunication;
+use check_mate_common::constants::*;
+use check_mate_common::{parse_duration, ClientDetails};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+#[derive(PartialEq, Debug)]
+struct Ping {
+    name: String,
+    status: Result<(), String>,
+    expected_interval: Option<Duration>,
+}
+
+#[derive(PartialEq, Debug)]
+enum HttpRequest {
+    Ping(Ping),
+    Feed,
+}
+
+#[derive(PartialEq, Debug)]
+enum HttpError {
+    BadRequest(String),
+    NotFound,
+    MethodNotAllowed,
+}
+
+impl HttpError {
+    fn status_line(&self) -> &'static str {
+        match self {
+            HttpError::BadRequest(_) => "400 Bad Request",
+            HttpError::NotFound => "404 Not Found",
+            HttpError::MethodNotAllowed => "405 Method Not Allowed",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            HttpError::BadRequest(message) => message.clone(),
+            HttpError::NotFound => "Expected /ping/<NAME>, /ping/<NAME>/fail or /feed".to_owned(),
+            HttpError::MethodNotAllowed => "Method is not allowed for this path".to_owned(),
+        }
+    }
+}
+
+fn bad_request(message: impl Into<String>) -> HttpError {
+    HttpError::BadRequest(message.into())
+}
+
+fn percent_decode(text: &str) -> Result<String, HttpError> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let byte = text
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| bad_request(format!("Invalid percent-encoding in {text}")))?;
+            decoded.push(byte);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| bad_request(format!("Invalid UTF-8 in {text}")))
+}
+
+fn parse_ping(method: &str, target: &str, body: &[u8]) -> Result<Ping, HttpError> {
+    if method != "GET" && method != "POST" {
+        return Err(HttpError::MethodNotAllowed);
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let segments = path
+        .strip_prefix("/ping/")
+        .ok_or(HttpError::NotFound)?
+        .split('/')
+        .collect::<Vec<_>>();
+    let (name, status) = match segments[..] {
+        [name] => (name, Ok(())),
+        [name, "fail"] => {
+            let message = String::from_utf8_lossy(body).trim().to_owned();
+            match message.is_empty() {
+                true => (name, Err(HTTP_PING_DEFAULT_FAILURE.to_owned())),
+                false => (name, Err(message)),
+            }
+        }
+        _ => return Err(HttpError::NotFound),
+    };
+    let name = percent_decode(name)?;
+    if name.is_empty() {
+        return Err(HttpError::NotFound);
+    }
+
+    let mut expected_interval = None;
+    for parameter in query.split('&') {
+        if let Some(value) = parameter.strip_prefix("interval=") {
+            let interval = parse_duration(&percent_decode(value)?).map_err(bad_request)?;
+            expected_interval = Some(interval);
+        }
+    }
+
+    Ok(Ping {
+        name,
+        status,
+        expected_interval,
+    })
+}
+
+fn parse_request(method: &str, target: &str, body: &[u8]) -> Result<HttpRequest, HttpError> {
+    let path = target.split_once('?').map_or(target, |x| x.0);
+    if path != "/feed" {
+        return parse_ping(method, target, body).map(HttpRequest::Ping);
+    }
+    match method {
+        "GET" => Ok(HttpRequest::Feed),
+        _ => Err(HttpError::MethodNotAllowed),
+    }
+}
+
+async fn read_line(stream: &mut (impl AsyncBufRead + Unpin)) -> Result<String, HttpError> {
+    let mut line = String::new();
+    match stream.read_line(&mut line).await {
+        Ok(0) | Err(_) => Err(bad_request("Incomplete request")),
+        Ok(_) => Ok(line.trim_end().to_owned()),
+    }
+}
+
+// Reads the request line, headers and body, which is expected only with Content-Length
+async fn read_request(
+    stream: &mut (impl AsyncBufRead + Unpin),
+) -> Result<(String, String, Vec<u8>), HttpError> {
+    let request_line = read_line(stream).await?;
+    let mut parts = request_line.split(' ');
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(bad_request("Invalid request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(bad_request("Unsupported HTTP version"));
+    }
+    let (method, target) = (method.to_owned(), target.to_owned());
+
+    let mut content_length = 0;
+    loop {
+        let header = read_line(stream).await?;
+        if header.is_empty() {
+            break;
+        }
+        let Some((key, value)) = header.split_once(':') else {
+            return Err(bad_request("Invalid header"));
+        };
+        if key.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| bad_request("Invalid Content-Length"))?;
+        } else if key.eq_ignore_ascii_case("transfer-encoding") {
+            return Err(bad_request("Transfer-Encoding is not supported"));
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    stream
+        .read_exact(&mut body)
+        .await
+        .map_err(|_| bad_request("Incomplete body"))?;
+    Ok((method, target, body))
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    pushed_statuses: &PushedStatuses,
+    statistics: &Statistics,
+    task_communication: &TaskCommunication,
+    incidents: &Incidents,
+    log_every_status: bool,
+) -> std::io::Result<()> {
+    let (input_stream, mut output_stream) = stream.split();
+    let mut input_stream = BufReader::new(input_stream.take(HTTP_MAX_REQUEST_SIZE));
+
+    let request = tokio::time::timeout(HTTP_REQUEST_TIMEOUT, read_request(&mut input_stream))
+        .await
+        .unwrap_or_else(|_| Err(bad_request("Request timed out")));
+    let request = request.and_then(|(method, target, body)| parse_request(&method, &target, &body));
+    let (status_line, content_type, body) = match request {
+        Ok(HttpRequest::Ping(ping)) => {
+            let details = ClientDetails {
+                name: ping.name,
+                status: Some(ping.status),
+                pending: false,
+                warning: false,
+                age_seconds: 0,
+                tags: Vec::new(),
+            };
+            crate::store_pushed_status(
+                None,
+                details,
+                ping.expected_interval,
+                pushed_statuses,
+                statistics,
+                task_communication,
+                log_every_status,
+            );
+            ("200 OK", "text/plain", "OK\n".to_owned())
+        }
+        Ok(HttpRequest::Feed) => (
+            "200 OK",
+            "application/atom+xml; charset=utf-8",
+            incidents.format_atom_feed(),
+        ),
+        Err(err) => (err.status_line(), "text/plain", err.message() + "\n"),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status_line}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    );
+    output_stream.write_all(response.as_bytes()).await?;
+    output_stream.shutdown().await
+}
+
+pub async fn serve(
+    listener: TcpListener,
+    pushed_statuses: PushedStatuses,
+    statistics: Statistics,
+    task_communication: TaskCommunication,
+    incidents: Incidents,
+    log_every_status: bool,
+) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _address)) => stream,
+            Err(err) => {
+                eprintln!("Failed to accept HTTP connection: {}", err);
+                continue;
+            }
+        };
+
+        let pushed_statuses = pushed_statuses.clone();
+        let statistics = statistics.clone();
+        let task_communication = task_communication.clone();
+        let incidents = incidents.clone();
+        tokio::spawn(async move {
+            let result = handle_connection(
+                stream,
+                &pushed_statuses,
+                &statistics,
+                &task_communication,
+                &incidents,
+                log_every_status,
+            )
+            .await;
+            if let Err(err) = result {
+                eprintln!("Failed to respond to HTTP request: {}", err);
+            }
+        });
+    }
+}
+
+// Periodically turns pings which are overdue into errors
+pub async fn expire_overdue_pings(
+    pushed_statuses: PushedStatuses,
+    task_communication: TaskCommunication,
+) {
+    let mut ticks = tokio::time::interval(HTTP_PING_CHECK_INTERVAL);
+    loop {
+        ticks.tick().await;
+        for (namespace, details) in pushed_statuses.expire_overdue() {
+            if let Some(Err(ref err)) = details.status {
+                println!("Client {} is overdue: {}", details.name, err);
+            }
+            task_communication.publish_status_change(namespace, details);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ping(name: &str, status: Result<(), &str>, interval: Option<u64>) -> Ping {
+        Ping {
+            name: name.to_owned(),
+            status: status.map_err(|x| x.to_owned()),
+            expected_interval: interval.map(Duration::from_secs),
+        }
+    }
+
+    #[test]
+    fn pings_are_parsed() {
+        assert_eq!(
+            parse_ping("GET", "/ping/backup", b""),
+            Ok(ping("backup", Ok(()), None))
+        );
+        assert_eq!(
+            parse_ping("POST", "/ping/backup/fail", b"No space left\n"),
+            Ok(ping("backup", Err("No space left"), None))
+        );
+        assert_eq!(
+            parse_ping("GET", "/ping/backup/fail", b""),
+            Ok(ping("backup", Err(HTTP_PING_DEFAULT_FAILURE), None))
+        );
+        assert_eq!(
+            parse_ping("GET", "/ping/disk%20space?interval=5m&x=1", b""),
+            Ok(ping("disk space", Ok(()), Some(300)))
+        );
+    }
+
+    #[test]
+    fn invalid_pings_are_rejected() {
+        assert_eq!(
+            parse_ping("DELETE", "/ping/backup", b""),
+            Err(HttpError::MethodNotAllowed)
+        );
+        assert_eq!(parse_ping("GET", "/", b""), Err(HttpError::NotFound));
+        assert_eq!(parse_ping("GET", "/ping/", b""), Err(HttpError::NotFound));
+        assert_eq!(
+            parse_ping("GET", "/ping/backup/start", b""),
+            Err(HttpError::NotFound)
+        );
+        assert!(matches!(
+            parse_ping("GET", "/ping/backup?interval=soon", b""),
+            Err(HttpError::BadRequest(_))
+        ));
+        assert!(matches!(
+            parse_ping("GET", "/ping/back%up", b""),
+            Err(HttpError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn feed_is_routed() {
+        assert_eq!(parse_request("GET", "/feed", b""), Ok(HttpRequest::Feed));
+        assert_eq!(
+            parse_request("POST", "/feed", b""),
+            Err(HttpError::MethodNotAllowed)
+        );
+        assert_eq!(
+            parse_request("GET", "/ping/feed", b""),
+            Ok(HttpRequest::Ping(ping("feed", Ok(()), None)))
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_are_read() {
+        let mut request: &[u8] =
+            b"POST /ping/backup/fail HTTP/1.1\r\nHost: x\r\ncontent-length: 4\r\n\r\nFull";
+        let request = read_request(&mut request).await;
+        assert_eq!(
+            request,
+            Ok((
+                "POST".to_owned(),
+                "/ping/backup/fail".to_owned(),
+                b"Full".to_vec()
+            ))
+        );
+
+        let mut request: &[u8] = b"GET /ping/backup HTTP/1.1\r\nHost: x\r\n";
+        assert!(read_request(&mut request).await.is_err());
+    }
+}
@@ -0,0 +1,241 @@
+// Recent incidents, which are transitions of clients into an error and recoveries from it, served by the HTTP listener
+// as an Atom feed. This way they can be followed in a feed reader or passed to chat tools which only understand feeds.
+// Only the latest incidents are kept, older ones are dropped.
+
+use crate::namespaces::Namespace;
+use check_mate_common::ClientDetails;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::broadcast;
+
+#[derive(Clone)]
+pub struct Incidents {
+    locked_data: Arc<Mutex<IncidentsData>>,
+}
+
+struct IncidentsData {
+    start_time: SystemTime,
+    capacity: usize,
+    incidents: VecDeque<Incident>,
+    failed_clients: HashSet<String>,
+    next_id: u64,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct Incident {
+    id: u64,
+    name: String,
+    error: Option<String>, // None for recoveries
+    time: SystemTime,
+}
+
+impl Incidents {
+    pub fn new(capacity: usize) -> Self {
+        Self::with_start_time(capacity, SystemTime::now())
+    }
+
+    // Ids of entries contain the time the server was started at, so they don't repeat after a restart
+    fn with_start_time(capacity: usize, start_time: SystemTime) -> Self {
+        let data = IncidentsData {
+            start_time,
+            capacity,
+            incidents: VecDeque::new(),
+            failed_clients: HashSet::new(),
+            next_id: 0,
+        };
+        Incidents {
+            locked_data: Arc::new(Mutex::new(data)),
+        }
+    }
+
+    // Changes of statuses which are not incidents, e.g. the first ok status of a client, are ignored
+    fn record(&self, details: &ClientDetails, time: SystemTime) {
+        let mut data = self
+            .locked_data
+            .lock()
+            .expect("Incidents mutex should not be poisoned");
+
+        let error = match details.status {
+            Some(Err(ref err)) => Some(err.clone()),
+            Some(Ok(_)) if data.failed_clients.remove(&details.name) => None,
+            _ => return,
+        };
+        if error.is_some() {
+            data.failed_clients.insert(details.name.clone());
+        }
+
+        let incident = Incident {
+            id: data.next_id,
+            name: details.name.clone(),
+            error,
+            time,
+        };
+        data.next_id += 1;
+        if data.incidents.len() == data.capacity {
+            data.incidents.pop_front();
+        }
+        data.incidents.push_back(incident);
+    }
+
+    // Records status changes published by all tasks in the background. Changes missed because of a lag are skipped.
+    pub fn record_status_changes(
+        &self,
+        mut status_changes: broadcast::Receiver<(Namespace, ClientDetails)>,
+    ) {
+        let incidents = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match status_changes.recv().await {
+                    Ok((_, details)) => incidents.record(&details, SystemTime::now()),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+
+    // Returns an Atom feed with the newest incidents first
+    pub fn format_atom_feed(&self) -> String {
+        let data = self
+            .locked_data
+            .lock()
+            .expect("Incidents mutex should not be poisoned");
+
+        let start_seconds = format_unix_seconds(data.start_time);
+        let updated = data.incidents.back().map_or(data.start_time, |x| x.time);
+        let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        feed += "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n";
+        feed += "  <title>CheckMate incidents</title>\n";
+        feed += &format!("  <id>urn:checkmate:incidents:{start_seconds}</id>\n");
+        feed += &format!("  <updated>{}</updated>\n", format_rfc3339(updated));
+        feed += "  <author><name>CheckMate</name></author>\n";
+        for incident in data.incidents.iter().rev() {
+            let (title, content) = match incident.error {
+                Some(ref err) => (format!("{} failed", incident.name), err.as_str()),
+                None => (format!("{} recovered", incident.name), "ok"),
+            };
+            feed += "  <entry>\n";
+            feed += &format!("    <title>{}</title>\n", escape_xml(&title));
+            feed += &format!(
+                "    <id>urn:checkmate:incidents:{start_seconds}:{}</id>\n",
+                incident.id
+            );
+            feed += &format!("    <updated>{}</updated>\n", format_rfc3339(incident.time));
+            feed += &format!(
+                "    <content type=\"text\">{}</content>\n",
+                escape_xml(content)
+            );
+            feed += "  </entry>\n";
+        }
+        feed += "</feed>\n";
+        feed
+    }
+}
+
+fn format_unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn format_rfc3339(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn time(seconds: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn only_errors_and_recoveries_are_recorded() {
+        let incidents = Incidents::with_start_time(10, time(0));
+        incidents.record(&ClientDetails::new("backup", Some(Ok(()))), time(1));
+        incidents.record(&ClientDetails::new("backup", None), time(2));
+        incidents.record(&ClientDetails::new("backup", Some(Err("Full"))), time(3));
+        incidents.record(&ClientDetails::new("backup", Some(Ok(()))), time(4));
+        incidents.record(&ClientDetails::new("backup", Some(Ok(()))), time(5));
+
+        let data = incidents.locked_data.lock().unwrap();
+        let recorded = data
+            .incidents
+            .iter()
+            .map(|x| (x.name.as_str(), x.error.as_deref(), x.time))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recorded,
+            [("backup", Some("Full"), time(3)), ("backup", None, time(4))]
+        );
+    }
+
+    #[test]
+    fn oldest_incidents_are_dropped() {
+        let incidents = Incidents::with_start_time(2, time(0));
+        for (index, name) in ["a", "b", "c"].iter().enumerate() {
+            incidents.record(
+                &ClientDetails::new(name, Some(Err("Down"))),
+                time(index as u64),
+            );
+        }
+        let data = incidents.locked_data.lock().unwrap();
+        let names = data
+            .incidents
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["b", "c"]);
+    }
+
+    #[test]
+    fn atom_feed_is_formatted() {
+        let incidents = Incidents::with_start_time(10, time(0));
+        incidents.record(
+            &ClientDetails::new("db", Some(Err("Lag > 5s & rising"))),
+            time(60),
+        );
+        incidents.record(&ClientDetails::new("db", Some(Ok(()))), time(120));
+
+        let feed = incidents.format_atom_feed();
+        assert_eq!(
+            feed,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <feed xmlns=\"http://www.w3.org/2005/Atom\">\n  \
+               <title>CheckMate incidents</title>\n  \
+               <id>urn:checkmate:incidents:0</id>\n  \
+               <updated>1970-01-01T00:02:00Z</updated>\n  \
+               <author><name>CheckMate</name></author>\n  \
+               <entry>\n    \
+                 <title>db recovered</title>\n    \
+                 <id>urn:checkmate:incidents:0:1</id>\n    \
+                 <updated>1970-01-01T00:02:00Z</updated>\n    \
+                 <content type=\"text\">ok</content>\n  \
+               </entry>\n  \
+               <entry>\n    \
+                 <title>db failed</title>\n    \
+                 <id>urn:checkmate:incidents:0:0</id>\n    \
+                 <updated>1970-01-01T00:01:00Z</updated>\n    \
+                 <content type=\"text\">Lag &gt; 5s &amp; rising</content>\n  \
+               </entry>\n\
+             </feed>\n"
+        );
+    }
+}
@@ -0,0 +1,809 @@
+// Server collecting statuses of clients. It's run by the check_mate_server binary, but it can also be embedded in
+// another application or a test harness, which configures it with a builder and stops it through a shutdown handle.
+
+mod access_control;
+mod acl;
+mod api_keys;
+mod client_state;
+mod config;
+mod draining;
+mod federation;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod history;
+mod http_listener;
+mod incidents;
+mod namespaces;
+mod nrpe;
+mod proxy_protocol;
+mod pushed_statuses;
+mod rate_limit;
+mod replication;
+mod self_monitoring;
+mod shutdown;
+mod statistics;
+mod status_cache;
+mod task_communication;
+mod telemetry;
+mod tls;
+mod zabbix;
+
+use api_keys::ApiKeys;
+use check_mate_common::{constants::*, ClientDetails, CommunicationError, ServerCommand};
+use client_state::{Capabilities, ClientState};
+use draining::Draining;
+use history::{History, Retention};
+use incidents::Incidents;
+use namespaces::Namespace;
+use pushed_statuses::PushedStatuses;
+use rate_limit::RateLimiter;
+use self_monitoring::SelfMonitoring;
+use statistics::Statistics;
+use status_cache::StatusCache;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use task_communication::{TaskCommunication, TaskMessage};
+use telemetry::Telemetry;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_rustls::TlsAcceptor;
+
+pub use config::Config;
+pub use shutdown::ShutdownHandle;
+
+// Pushed statuses come both from clients and from the HTTP endpoint, so they are stored the same way for both
+fn store_pushed_status(
+    namespace: Namespace,
+    details: ClientDetails,
+    expected_interval: Option<std::time::Duration>,
+    pushed_statuses: &PushedStatuses,
+    statistics: &Statistics,
+    task_communication: &TaskCommunication,
+    log_every_status: bool,
+) {
+    let previous = pushed_statuses.push(namespace.clone(), details.clone(), expected_interval);
+    let is_change = previous.as_ref().map(|x| &x.status) != Some(&details.status);
+    if previous.is_none() {
+        statistics.on_status_stored();
+    }
+    if log_every_status || is_change {
+        match details.status {
+            Some(Err(ref err)) => println!("Client {} pushed error: {}", details.name, err),
+            _ => println!("Client {} pushed ok", details.name),
+        }
+    }
+    if is_change {
+        task_communication.publish_status_change(namespace, details);
+    }
+}
+
+// Each task gets its own handles to the state shared between tasks, so they have to be passed separately
+#[allow(clippy::too_many_arguments)]
+async fn execute_command_from_client(
+    task_id: usize,
+    client_state: &mut ClientState,
+    receiver: &mut Receiver<TaskMessage>,
+    sender: &Sender<TaskMessage>,
+    task_communication: &mut TaskCommunication,
+    statistics: &Statistics,
+    pushed_statuses: &PushedStatuses,
+    api_keys: &ApiKeys,
+    draining: &Draining,
+    history: &History,
+    telemetry: &Telemetry,
+    shutdown: &ShutdownHandle,
+    log_every_status: bool,
+
+    command: ServerCommand,
+) {
+    let _span = telemetry.start_command_span(task_id, client_state.get_name().as_deref(), &command);
+    statistics.on_command_processed();
+    let had_status = client_state.has_reported_status();
+    let process_result = client_state.process_command(command);
+    if !had_status && client_state.has_reported_status() {
+        statistics.on_status_stored();
+    }
+
+    match process_result {
+        client_state::ProcessCommandResult::Ok => (),
+        client_state::ProcessCommandResult::Abort => shutdown.shutdown(),
+        client_state::ProcessCommandResult::StatusChanged => {
+            task_communication.publish_status_change(
+                client_state.get_namespace().clone(),
+                client_state.get_details(),
+            );
+        }
+        client_state::ProcessCommandResult::Subscribe => {
+            client_state.subscribe_status_changes(task_communication.subscribe_status_changes());
+        }
+        client_state::ProcessCommandResult::PushStatus(details) => {
+            store_pushed_status(
+                client_state.get_namespace().clone(),
+                details,
+                None,
+                pushed_statuses,
+                statistics,
+                task_communication,
+                log_every_status,
+            );
+        }
+        client_state::ProcessCommandResult::GetStatuses(include_names) => {
+            let scope = client_state.get_scope();
+            let mut errors = task_communication
+                .read_messages(task_id, receiver, sender, scope, include_names)
+                .await;
+            for (namespace, details) in pushed_statuses.get_namespaced_details() {
+                let Some(details) = scope.view_details(&namespace, details) else {
+                    continue;
+                };
+                if let Some(Err(err)) = details.status {
+                    match include_names {
+                        true => errors.push(format!("{}: {}", details.name, err)),
+                        false => errors.push(err),
+                    }
+                }
+            }
+            client_state
+                .push_command_to_send(ServerCommand::Statuses(errors))
+                .await;
+        }
+        client_state::ProcessCommandResult::RefreshClientByName(name) => {
+            task_communication
+                .refresh_client_by_name(task_id, client_state.get_scope().clone(), name)
+                .await;
+        }
+        client_state::ProcessCommandResult::ClearClientByName(name) => {
+            // Pushed statuses have no client which could report them again, so they are removed entirely
+            let scope = client_state.get_scope().clone();
+            for (namespace, details) in pushed_statuses.get_namespaced_details() {
+                if scope.view_name(&namespace, &details.name).as_ref() != Some(&name) {
+                    continue;
+                }
+                if let Some(removed) = pushed_statuses.remove(&namespace, &details.name) {
+                    println!("Client {} was cleared", removed.name);
+                    statistics.on_status_removed();
+                    task_communication.publish_status_change(
+                        namespace,
+                        ClientDetails {
+                            status: Some(Ok(())),
+                            ..removed
+                        },
+                    );
+                }
+            }
+            task_communication
+                .clear_client_by_name(task_id, scope, name)
+                .await;
+        }
+        client_state::ProcessCommandResult::RefreshAllClients => {
+            task_communication
+                .refresh_all_clients(task_id, client_state.get_scope().clone())
+                .await;
+        }
+        client_state::ProcessCommandResult::ListClients => {
+            let scope = client_state.get_scope();
+            let mut clients = task_communication
+                .list_clients(task_id, receiver, sender, scope)
+                .await;
+            for (namespace, details) in pushed_statuses.get_namespaced_details() {
+                clients.extend(scope.view_name(&namespace, &details.name));
+            }
+            client_state
+                .push_command_to_send(ServerCommand::Clients(clients))
+                .await;
+        }
+        client_state::ProcessCommandResult::GetClientDetails => {
+            let scope = client_state.get_scope();
+            let mut details = task_communication
+                .get_client_details(task_id, receiver, sender, scope)
+                .await;
+            for (namespace, pushed_details) in pushed_statuses.get_namespaced_details() {
+                details.extend(scope.view_details(&namespace, pushed_details));
+            }
+            client_state
+                .push_command_to_send(ServerCommand::ClientDetails(details))
+                .await;
+        }
+        client_state::ProcessCommandResult::GetServerStatistics => {
+            client_state
+                .push_command_to_send(ServerCommand::ServerStatistics(statistics.snapshot()))
+                .await;
+        }
+        client_state::ProcessCommandResult::CreateApiKey(identity, label) => {
+            let result = api_keys.create(identity.clone(), label);
+            if result.is_ok() {
+                println!("API key for {} was created", identity);
+            }
+            client_state
+                .push_command_to_send(ServerCommand::ApiKeyResult(result))
+                .await;
+        }
+        client_state::ProcessCommandResult::ListApiKeys => {
+            client_state
+                .push_command_to_send(ServerCommand::ApiKeys(api_keys.list()))
+                .await;
+        }
+        client_state::ProcessCommandResult::LabelApiKey(id, label) => {
+            let result = api_keys.label(&id, label).map(|_| id);
+            client_state
+                .push_command_to_send(ServerCommand::ApiKeyResult(result))
+                .await;
+        }
+        client_state::ProcessCommandResult::RevokeApiKey(id) => {
+            let result = api_keys.revoke(&id).map(|_| id);
+            if let Ok(ref id) = result {
+                println!("API key {} was revoked", id);
+            }
+            client_state
+                .push_command_to_send(ServerCommand::ApiKeyResult(result))
+                .await;
+        }
+        client_state::ProcessCommandResult::Drain => {
+            if draining.start() {
+                println!("Draining, new connections are no longer accepted");
+            }
+            // The client asking for the progress doesn't count, as it's going to disconnect anyway
+            let clients = statistics.snapshot().connected_clients.saturating_sub(1);
+            client_state
+                .push_command_to_send(ServerCommand::DrainProgress(clients))
+                .await;
+        }
+        client_state::ProcessCommandResult::GetAvailability(window_seconds) => {
+            let window = std::time::Duration::from_secs(window_seconds);
+            let result = history
+                .get_availability(
+                    client_state.get_scope().clone(),
+                    window,
+                    std::time::SystemTime::now(),
+                )
+                .await;
+            client_state
+                .push_command_to_send(ServerCommand::Availability(result))
+                .await;
+        }
+        client_state::ProcessCommandResult::CompactHistory => {
+            let result = history.compact().await;
+            if let Ok(removed) = result {
+                println!("History was compacted, {} statuses removed", removed);
+            }
+            client_state
+                .push_command_to_send(ServerCommand::HistoryCompacted(result))
+                .await;
+        }
+        client_state::ProcessCommandResult::ExportHistory(start, end) => {
+            let result = history
+                .export(client_state.get_scope().clone(), start, end)
+                .await;
+            match result {
+                Ok(entries) => client_state.push_history_export(history::split_export(entries)),
+                Err(err) => {
+                    client_state
+                        .push_command_to_send(ServerCommand::HistoryExport(Err(err)))
+                        .await
+                }
+            }
+        }
+    }
+}
+
+// API keys identify clients like certificates do, so roles are assigned to their identities the same way. Returns
+// whether the key is valid.
+fn authenticate_client(
+    client_state: &mut ClientState,
+    api_keys: &ApiKeys,
+    config: &Config,
+    client_address: SocketAddr,
+    key: &str,
+) -> bool {
+    let Some(identity) = api_keys.authenticate(key) else {
+        println!("Client at {} presented an invalid API key", client_address);
+        return false;
+    };
+    client_state.set_role(acl::get_role(
+        std::slice::from_ref(&identity),
+        &config.roles,
+        config.default_role,
+    ));
+    println!(
+        "Client at {} authenticated as {} with role {}",
+        client_address,
+        identity,
+        client_state.get_role()
+    );
+    client_state.set_identities(vec![identity]);
+    true
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_client_async(
+    task_id: usize,
+    mut task_communication: TaskCommunication,
+    statistics: Statistics,
+    status_cache: StatusCache,
+    pushed_statuses: PushedStatuses,
+    api_keys: ApiKeys,
+    draining: Draining,
+    history: History,
+    telemetry: Telemetry,
+    shutdown: ShutdownHandle,
+    config: Config,
+    stream: tokio::net::TcpStream,
+    client_address: SocketAddr,
+    capabilities: Capabilities,
+    tls_acceptor: Option<TlsAcceptor>,
+) {
+    // Behind a load balancer connections come from the balancer, which sends the address of the client in a header
+    let mut stream = BufReader::new(stream);
+    let client_address = match config.proxy_protocol {
+        false => client_address,
+        true => match proxy_protocol::read_header(&mut stream).await {
+            Ok(address) => address.unwrap_or(client_address),
+            Err(err) => {
+                eprintln!(
+                    "ERROR: invalid PROXY protocol header from {}: {}",
+                    client_address, err
+                );
+                return;
+            }
+        },
+    };
+    if !access_control::is_address_allowed(
+        client_address.ip(),
+        &config.allowed_networks,
+        &config.denied_networks,
+    ) {
+        println!("Denied connection from {}", client_address);
+        return;
+    }
+
+    // Prepare communication with client, which is encrypted when TLS is enabled
+    let mut identities = Vec::new();
+    let (input_stream, mut output_stream): (
+        Box<dyn AsyncRead + Unpin + Send>,
+        Box<dyn AsyncWrite + Unpin + Send>,
+    ) = match tls_acceptor {
+        Some(tls_acceptor) => {
+            let tls_stream = match tls_acceptor.accept(stream).await {
+                Ok(x) => x,
+                Err(err) => {
+                    eprintln!(
+                        "ERROR: TLS handshake with {} failed: {}",
+                        client_address, err
+                    );
+                    return;
+                }
+            };
+            if let Some(certificate) = tls_stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|x| x.first())
+            {
+                identities = tls::get_identities(certificate);
+            }
+            let (input_stream, output_stream) = tokio::io::split(tls_stream);
+            (Box::new(input_stream), Box::new(output_stream))
+        }
+        None => {
+            let (input_stream, output_stream) = tokio::io::split(stream);
+            (Box::new(input_stream), Box::new(output_stream))
+        }
+    };
+    let mut input_stream = BufReader::new(input_stream);
+
+    let (sender, mut receiver) = channel::<task_communication::TaskMessage>(1);
+    task_communication
+        .register_task(task_id, sender.clone())
+        .await;
+    statistics.on_client_connected();
+
+    let mut client_state = ClientState::new(config.log_every_status, capabilities, status_cache);
+    client_state.set_role(acl::get_role(
+        &identities,
+        &config.roles,
+        config.default_role,
+    ));
+    if let Some(identity) = identities.first() {
+        println!(
+            "Client at {} authenticated as {} with role {}",
+            client_address,
+            identity,
+            client_state.get_role()
+        );
+        client_state.set_identities(identities);
+    }
+    let mut rate_limiter = config
+        .status_rate_limit
+        .map(|rate| RateLimiter::new(rate, config.rate_limit_action));
+
+    // Main loop
+    let main_loop_error = loop {
+        tokio::select! {
+            command = ServerCommand::receive_async(&mut input_stream) => {
+                match command {
+                    // Invalid keys end the connection, so the client doesn't continue with the
+                    // default role
+                    Ok(ServerCommand::Authenticate(key)) => {
+                        if !authenticate_client(
+                            &mut client_state,
+                            &api_keys,
+                            &config,
+                            client_address,
+                            &key,
+                        ) {
+                            break CommunicationError::SocketDisconnected;
+                        }
+                    }
+                    // Commands not allowed by the role of the client end the connection, so it
+                    // doesn't wait for a response in vain
+                    Ok(x) if !client_state.get_role().allows(&x) => {
+                        println!(
+                            "Client {} with role {} is not allowed to send this command",
+                            client_state.get_name_or_default(),
+                            client_state.get_role()
+                        );
+                        break CommunicationError::SocketDisconnected;
+                    }
+                    // Management of API keys is answered, so it ends the connection the same way
+                    // when not allowed on this port
+                    Ok(x)
+                        if api_keys::is_management_command(&x) && !client_state.is_allowed(&x) =>
+                    {
+                        println!(
+                            "Client {} is not allowed to manage API keys on this port",
+                            client_state.get_name_or_default()
+                        );
+                        break CommunicationError::SocketDisconnected;
+                    }
+                    Ok(ServerCommand::Drain) if !client_state.is_allowed(&ServerCommand::Drain) => {
+                        println!(
+                            "Client {} is not allowed to drain the server on this port",
+                            client_state.get_name_or_default()
+                        );
+                        break CommunicationError::SocketDisconnected;
+                    }
+                    Ok(ServerCommand::CompactHistory)
+                        if !client_state.is_allowed(&ServerCommand::CompactHistory) =>
+                    {
+                        println!(
+                            "Client {} is not allowed to compact the history on this port",
+                            client_state.get_name_or_default()
+                        );
+                        break CommunicationError::SocketDisconnected;
+                    }
+                    Ok(x) => {
+                        // Runaway clients are limited before their statuses reach logs and
+                        // subscribers
+                        if let Some(ref mut rate_limiter) = rate_limiter {
+                            if rate_limit::is_limited(&x)
+                                && !rate_limiter.admit(&client_state.get_name_or_default()).await
+                            {
+                                break CommunicationError::SocketDisconnected;
+                            }
+                        }
+                        execute_command_from_client(
+                            task_id,
+                            &mut client_state,
+                            &mut receiver,
+                            &sender,
+                            &mut task_communication,
+                            &statistics,
+                            &pushed_statuses,
+                            &api_keys,
+                            &draining,
+                            &history,
+                            &telemetry,
+                            &shutdown,
+                            config.log_every_status,
+                            x,
+                        )
+                        .await
+                    }
+                    Err(x) => break x,
+                };
+            }
+            task_message = receiver.recv() => {
+                match task_message {
+                    Some(x) => {
+                        task_communication
+                            .process_task_message(x, &mut client_state)
+                            .await
+                    }
+                    None => break CommunicationError::SocketDisconnected,
+                }
+            }
+            command = client_state.get_command_to_send() => {
+                match command.send_async(&mut output_stream).await {
+                    Ok(_) => (),
+                    Err(x) => break x,
+                }
+                if command == ServerCommand::Refresh {
+                    statistics.on_notification_sent();
+                }
+            }
+        }
+    };
+
+    // Handle erorr from the main loop
+    match main_loop_error {
+        CommunicationError::IoError(_) => eprintln!(
+            "ERROR: IO error during communication with client {} at {}",
+            client_state.get_name_or_default(),
+            client_address
+        ),
+        CommunicationError::CommandParseError(_) => eprintln!(
+            "ERROR: client {} at {} sent an incorrect command",
+            client_state.get_name_or_default(),
+            client_address
+        ),
+        CommunicationError::SocketDisconnected => (),
+    }
+
+    task_communication.unregister_task(task_id).await;
+    if client_state.has_reported_status() {
+        statistics.on_status_removed();
+    }
+    statistics.on_client_disconnected();
+    if draining.is_draining() {
+        println!(
+            "Draining, {} clients still connected",
+            statistics.snapshot().connected_clients
+        );
+    }
+}
+
+pub struct ServerBuilder {
+    config: Config,
+}
+
+impl ServerBuilder {
+    // Replaces the whole configuration, e.g. one parsed from arguments
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.server_port = port;
+        self
+    }
+
+    pub fn bind_address(mut self, address: IpAddr) -> Self {
+        self.config.bind_address = address;
+        self
+    }
+
+    pub fn admin_port(mut self, port: u16) -> Self {
+        self.config.admin_port = Some(port);
+        self
+    }
+
+    pub fn http_port(mut self, port: u16) -> Self {
+        self.config.http_port = Some(port);
+        self
+    }
+
+    pub fn build(self) -> Server {
+        Server {
+            config: self.config,
+            shutdown: ShutdownHandle::new(),
+        }
+    }
+
+    pub async fn run(self) -> Result<(), String> {
+        self.build().run().await
+    }
+}
+
+pub struct Server {
+    config: Config,
+    shutdown: ShutdownHandle,
+}
+
+impl Server {
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder {
+            config: Config::default(),
+        }
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    // Serves clients until the server is shut down. Fails only when the server can't be started.
+    pub async fn run(self) -> Result<(), String> {
+        run(self.config, self.shutdown).await
+    }
+}
+
+async fn run(config: Config, shutdown: ShutdownHandle) -> Result<(), String> {
+    let mut task_id: usize = 0;
+
+    let socket_address = SocketAddr::new(config.bind_address, config.server_port);
+    let listener = TcpListener::bind(socket_address);
+    let listener = listener
+        .await
+        .map_err(|err| format!("failed to bind address: {err}"))?;
+    let mut listener = Some(listener);
+
+    // With a separate admin listener, control commands are no longer accepted on the main one. It's always bound to
+    // loopback, so control commands can't be sent from other hosts.
+    let mut admin_listener = None;
+    let mut capabilities = Capabilities::All;
+    if let Some(admin_port) = config.admin_port {
+        let admin_address = SocketAddrV4::new(Ipv4Addr::LOCALHOST, admin_port);
+        admin_listener = Some(
+            TcpListener::bind(admin_address)
+                .await
+                .map_err(|err| format!("failed to bind admin address: {err}"))?,
+        );
+        capabilities = Capabilities::ReportsAndReads;
+    }
+
+    let tls_acceptor = tls::create_acceptor(&config)?;
+
+    let task_communication = TaskCommunication::new();
+    let statistics = Statistics::new();
+    let status_cache = StatusCache::new(STATUS_CACHE_CAPACITY);
+    let pushed_statuses = PushedStatuses::new();
+    let draining = Draining::new();
+    let api_keys = ApiKeys::load(config.api_keys_file.as_deref())?;
+    let retention = Retention {
+        max_age: config.history_max_age,
+        max_rows: config.history_max_rows,
+    };
+    let history = History::open(config.history_file.as_deref(), retention)?;
+    let telemetry = Telemetry::new(&config)?;
+    telemetry.export_status_changes(task_communication.subscribe_status_changes());
+    telemetry.shutdown_on_termination(shutdown.clone());
+    // History subscribes before the virtual client of self-monitoring reports its first status
+    let status_changes_to_record = task_communication.subscribe_status_changes();
+    let self_monitoring = SelfMonitoring::new(
+        config.self_monitoring.clone(),
+        pushed_statuses.clone(),
+        statistics.clone(),
+        task_communication.clone(),
+    );
+    history.record_status_changes(status_changes_to_record, self_monitoring.clone());
+    history.prune_periodically(self_monitoring.clone());
+    zabbix::forward_status_changes(
+        &config,
+        task_communication.subscribe_status_changes(),
+        self_monitoring.clone(),
+    );
+    federation::relay_to_upstream(
+        &config,
+        task_communication.clone(),
+        pushed_statuses.clone(),
+        self_monitoring.clone(),
+    );
+    replication::start_standby(
+        &config,
+        pushed_statuses.clone(),
+        statistics.clone(),
+        task_communication.clone(),
+    );
+
+    if let Some(http_port) = config.http_port {
+        let http_address = SocketAddr::new(config.bind_address, http_port);
+        let http_listener = TcpListener::bind(http_address)
+            .await
+            .map_err(|err| format!("failed to bind HTTP address: {err}"))?;
+        let incidents = Incidents::new(INCIDENT_FEED_CAPACITY);
+        incidents.record_status_changes(task_communication.subscribe_status_changes());
+        tokio::spawn(shutdown.clone().cancel_on_shutdown(http_listener::serve(
+            http_listener,
+            pushed_statuses.clone(),
+            statistics.clone(),
+            task_communication.clone(),
+            incidents,
+            config.log_every_status,
+        )));
+        tokio::spawn(
+            shutdown
+                .clone()
+                .cancel_on_shutdown(http_listener::expire_overdue_pings(
+                    pushed_statuses.clone(),
+                    task_communication.clone(),
+                )),
+        );
+    }
+
+    if let Some(nrpe_port) = config.nrpe_port {
+        let nrpe_address = SocketAddr::new(config.bind_address, nrpe_port);
+        let nrpe_listener = TcpListener::bind(nrpe_address)
+            .await
+            .map_err(|err| format!("failed to bind NRPE address: {err}"))?;
+        tokio::spawn(shutdown.clone().cancel_on_shutdown(nrpe::serve(
+            nrpe_listener,
+            task_communication.clone(),
+            pushed_statuses.clone(),
+        )));
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_port) = config.grpc_port {
+        let grpc_address = SocketAddr::new(config.bind_address, grpc_port);
+        let grpc_listener = TcpListener::bind(grpc_address)
+            .await
+            .map_err(|err| format!("failed to bind gRPC address: {err}"))?;
+        tokio::spawn(shutdown.clone().cancel_on_shutdown(grpc::serve(
+            grpc_listener,
+            pushed_statuses.clone(),
+            statistics.clone(),
+            task_communication.clone(),
+            config.log_every_status,
+        )));
+    }
+
+    loop {
+        let accept = async {
+            match listener {
+                Some(ref listener) => listener.accept().await,
+                None => std::future::pending().await,
+            }
+        };
+        let accept_admin = async {
+            match admin_listener {
+                Some(ref admin_listener) => admin_listener.accept().await,
+                None => std::future::pending().await,
+            }
+        };
+        let (tcp_stream, capabilities) = tokio::select! {
+            tcp_stream = accept => (tcp_stream, capabilities),
+            tcp_stream = accept_admin => (tcp_stream, Capabilities::All),
+            // Closing the listener makes new connections fail right away instead of waiting in the backlog
+            _ = draining.wait_for_start(), if listener.is_some() => {
+                listener = None;
+                continue;
+            }
+            _ = shutdown.wait() => break,
+        };
+        let (tcp_stream, client_address) = match tcp_stream {
+            Ok(ok) => ok,
+            Err(err) => {
+                eprintln!("Failed to connect with client: {}", err);
+                continue;
+            }
+        };
+
+        let task_communication = task_communication.clone();
+        let statistics = statistics.clone();
+        let status_cache = status_cache.clone();
+        let pushed_statuses = pushed_statuses.clone();
+        let api_keys = api_keys.clone();
+        let draining = draining.clone();
+        let history = history.clone();
+        let telemetry = telemetry.clone();
+        let shutdown = shutdown.clone();
+        let config = config.clone();
+        let tls_acceptor = tls_acceptor.clone();
+        let task = tokio::spawn(async move {
+            let client = handle_client_async(
+                task_id,
+                task_communication,
+                statistics,
+                status_cache,
+                pushed_statuses,
+                api_keys,
+                draining,
+                history,
+                telemetry,
+                shutdown.clone(),
+                config,
+                tcp_stream,
+                client_address,
+                capabilities,
+                tls_acceptor,
+            );
+            shutdown.cancel_on_shutdown(client).await;
+        });
+        self_monitoring.watch_task(task);
+
+        task_id += 1;
+    }
+
+    telemetry.shutdown();
+    Ok(())
+}
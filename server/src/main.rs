@@ -1,161 +1,24 @@
-mod client_state;
-mod config;
-mod task_communication;
-
-use check_mate_common::{CommunicationError, ServerCommand, constants::*};
-use client_state::ClientState;
-use config::Config;
-use std::net::{Ipv4Addr, SocketAddrV4};
-use task_communication::{TaskCommunication, TaskMessage};
-use tokio::io::BufReader;
-use tokio::net::TcpListener;
-use tokio::sync::mpsc::{channel, Receiver, Sender};
-
-async fn execute_command_from_client(
-    task_id: usize,
-    client_state: &mut ClientState,
-    receiver: &mut Receiver<TaskMessage>,
-    sender: &Sender<TaskMessage>,
-    task_communication: &mut TaskCommunication,
-
-    command: ServerCommand,
-) {
-    match client_state.process_command(command) {
-        client_state::ProcessCommandResult::Ok => (),
-        client_state::ProcessCommandResult::GetStatuses(include_names) => {
-            let errors = task_communication
-                .read_messages(task_id, receiver, sender, include_names)
-                .await;
-            client_state
-                .push_command_to_send(ServerCommand::Statuses(errors))
-                .await;
-        }
-        client_state::ProcessCommandResult::RefreshClientByName(name) => {
-            task_communication
-                .refresh_client_by_name(task_id, name)
-                .await;
-        }
-        client_state::ProcessCommandResult::RefreshAllClients => {
-            task_communication.refresh_all_clients(task_id).await;
-        }
-        client_state::ProcessCommandResult::ListClients => {
-            let clients = task_communication
-                .list_clients(task_id, receiver, sender)
-                .await;
-            client_state
-                .push_command_to_send(ServerCommand::Clients(clients))
-                .await;
-        }
-    }
-}
-
-async fn handle_client_async(
-    task_id: usize,
-    mut task_communication: TaskCommunication,
-    config: Config,
-    stream: tokio::net::TcpStream,
-) {
-    // Prepare communication with client
-    let (input_stream, mut output_stream) = stream.into_split();
-    let mut input_stream = BufReader::new(input_stream);
-
-    let (sender, mut receiver) = channel::<task_communication::TaskMessage>(1);
-    task_communication
-        .register_task(task_id, sender.clone())
-        .await;
-
-    let mut client_state = ClientState::new(config.log_every_status);
-
-    let mut buffer: Vec<u8> = Vec::new();
-    buffer.resize(100, 0);
-
-    // Main loop
-    let main_loop_error = loop {
-        tokio::select! {
-            command = ServerCommand::receive_async(&mut input_stream) => {
-                match command {
-                    Ok(x) => execute_command_from_client(task_id, &mut client_state, &mut receiver, &sender, &mut task_communication, x).await,
-                    Err(x) => break x,
-                };
-            }
-            task_message = receiver.recv() => {
-                match task_message {
-                    Some(x) => task_communication.process_task_message(x, &mut client_state).await,
-                    None => break CommunicationError::SocketDisconnected,
-                }
-            }
-            command = client_state.get_command_to_send() => {
-                match command.send_async(&mut output_stream).await{
-                    Ok(_) => (),
-                    Err(x) => break x,
-                }
-            }
-        }
-    };
-
-    // Handle erorr from the main loop
-    match main_loop_error {
-        CommunicationError::IoError(_) => eprintln!(
-            "ERROR: IO error during communication with client {}",
-            client_state.get_name_or_default()
-        ),
-        CommunicationError::CommandParseError(_) => eprintln!(
-            "ERROR: client {} sent an incorrect command",
-            client_state.get_name_or_default()
-        ),
-        CommunicationError::SocketDisconnected => (),
-    }
-
-    task_communication.unregister_task(task_id).await;
-}
+use check_mate_common::constants::*;
+use check_mate_server::{Config, Server};
 
 #[tokio::main]
 async fn main() {
-    let config = Config::parse(std::env::args().skip(1));
-    let config = match config {
+    let config = match Config::parse(std::env::args().skip(1)) {
         Ok(x) => x,
-        Err(err) => {
-            println!("ERROR: {}", err);
-            std::process::exit(1);
-        }
+        Err(err) => err.exit(),
     };
 
-    if config.help {
-        Config::print_help();
-        std::process::exit(0);
-    }
     if config.version {
         println!("{VERSION}");
         std::process::exit(0);
     }
+    if let Some(shell) = config.completions {
+        Config::print_completions(shell);
+        std::process::exit(0);
+    }
 
-    let mut task_id: usize = 0;
-
-    let socket_address = SocketAddrV4::new(Ipv4Addr::LOCALHOST, config.server_port);
-    let listener = TcpListener::bind(socket_address);
-    let listener = listener.await.unwrap_or_else(|err| {
-        eprintln!("Failed to bind address: {}", err);
+    if let Err(err) = Server::builder().config(config).run().await {
+        eprintln!("ERROR: {}", err);
         std::process::exit(1);
-    });
-
-    let task_communication = TaskCommunication::new();
-
-    loop {
-        let tcp_stream = listener.accept().await;
-        let (tcp_stream, _client_address) = match tcp_stream {
-            Ok(ok) => ok,
-            Err(err) => {
-                eprintln!("Failed to connect with client: {}", err);
-                continue;
-            }
-        };
-
-        let task_communication = task_communication.clone();
-        let config = config.clone();
-        tokio::spawn(async move {
-            handle_client_async(task_id, task_communication, config, tcp_stream).await;
-        });
-
-        task_id += 1;
     }
 }
@@ -0,0 +1,95 @@
+// Namespaces allow multiple teams to share one server. A client joins a namespace with SetNamespace and from then on
+// it only sees clients of the same namespace, i.e. reading, listing, refreshing and clearing is scoped to it. Clients
+// which haven't joined any namespace are in the default one. A client can also select all namespaces, e.g. when run by
+// an administrator, in which case it sees clients of every namespace.
+//
+// Within the server, names of clients are qualified with their namespace, e.g. "team-a/backup", unless they are in
+// the default namespace. This way services which are not scoped, such as the HTTP listener or federation, can tell
+// clients of different namespaces apart. Since names of clients may contain slashes themselves, the namespace is kept
+// alongside the qualified name wherever scoping is needed.
+
+use check_mate_common::ClientDetails;
+
+pub type Namespace = Option<String>; // None is the default namespace
+
+pub fn qualify_name(namespace: &Namespace, name: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{namespace}/{name}"),
+        None => name.to_owned(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scope {
+    Namespace(Namespace),
+    AllNamespaces,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Scope::Namespace(None)
+    }
+}
+
+impl Scope {
+    // Returns the name under which a client is seen within the scope or None, if the client is not visible in it.
+    // Only clients of all namespaces are seen under their qualified names.
+    pub fn view_name(&self, namespace: &Namespace, qualified_name: &str) -> Option<String> {
+        match self {
+            Scope::AllNamespaces => Some(qualified_name.to_owned()),
+            Scope::Namespace(scope) if scope != namespace => None,
+            Scope::Namespace(None) => Some(qualified_name.to_owned()),
+            Scope::Namespace(Some(scope)) => {
+                let name = qualified_name.strip_prefix(scope.as_str())?;
+                name.strip_prefix('/').map(str::to_owned)
+            }
+        }
+    }
+
+    pub fn view_details(
+        &self,
+        namespace: &Namespace,
+        details: ClientDetails,
+    ) -> Option<ClientDetails> {
+        let name = self.view_name(namespace, &details.name)?;
+        Some(ClientDetails { name, ..details })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_are_seen_only_within_their_namespace() {
+        let team = Some("team".to_owned());
+        let scope = Scope::Namespace(team.clone());
+        assert_eq!(
+            scope.view_name(&team, &qualify_name(&team, "backup")),
+            Some("backup".to_owned())
+        );
+        assert_eq!(scope.view_name(&None, "backup"), None);
+        assert_eq!(
+            scope.view_name(&Some("other".to_owned()), "other/backup"),
+            None
+        );
+
+        let scope = Scope::default();
+        assert_eq!(
+            scope.view_name(&None, "site/backup"),
+            Some("site/backup".to_owned())
+        );
+        assert_eq!(scope.view_name(&team, "team/backup"), None);
+    }
+
+    #[test]
+    fn clients_of_all_namespaces_are_seen_with_qualified_names() {
+        let team = Some("team".to_owned());
+        let scope = Scope::AllNamespaces;
+        assert_eq!(
+            scope.view_name(&team, &qualify_name(&team, "backup")),
+            Some("team/backup".to_owned())
+        );
+        assert_eq!(scope.view_name(&None, "backup"), Some("backup".to_owned()));
+    }
+}
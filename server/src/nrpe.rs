@@ -0,0 +1,334 @@
+// Listener speaking enough of NRPE, the protocol of Nagios remote checks, for Nagios or Icinga to query statuses known
+// to the server with check_nrpe. Only version 2 packets without SSL are supported, so check_nrpe has to be run with -n.
+// Newer check_nrpe sends version 3 packets first, but it falls back to version 2 when the connection is closed, like
+// older NRPE daemons do. Supported commands are:
+//   - check_checkmate - critical if any client is in error, warning if any client has a warning, ok otherwise
+//   - check_checkmate!<NAME> - status of the client with the given name
+// Commands come from the query packet as is, so with check_nrpe they are passed as "-c check_checkmate -a <NAME>".
+
+use crate::pushed_statuses::PushedStatuses;
+use crate::task_communication::TaskCommunication;
+use check_mate_common::constants::*;
+use check_mate_common::{ClientDetails, Severity};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const PACKET_VERSION: u16 = 2;
+const QUERY_PACKET: u16 = 1;
+const RESPONSE_PACKET: u16 = 2;
+const BUFFER_OFFSET: usize = 10;
+const BUFFER_SIZE: usize = 1024;
+const PACKET_SIZE: usize = 1036;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum ServiceState {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+// CRC-32 used by NRPE, which is the same as the one of zlib
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB88320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+// Returns the command carried by a query packet, or None if the packet is not a valid version 2 query
+fn decode_query(packet: &[u8; PACKET_SIZE]) -> Option<String> {
+    let version = u16::from_be_bytes([packet[0], packet[1]]);
+    let packet_type = u16::from_be_bytes([packet[2], packet[3]]);
+    let crc = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+    if version != PACKET_VERSION || packet_type != QUERY_PACKET {
+        return None;
+    }
+
+    let mut unsigned_packet = *packet;
+    unsigned_packet[4..8].fill(0);
+    if crc32(&unsigned_packet) != crc {
+        return None;
+    }
+
+    let buffer = &packet[BUFFER_OFFSET..BUFFER_OFFSET + BUFFER_SIZE];
+    let length = buffer.iter().position(|x| *x == 0).unwrap_or(BUFFER_SIZE);
+    Some(String::from_utf8_lossy(&buffer[..length]).into_owned())
+}
+
+fn encode_response(state: ServiceState, output: &str) -> [u8; PACKET_SIZE] {
+    // The buffer has to end with a null character, so the output is truncated to fit
+    let mut length = output.len().min(BUFFER_SIZE - 1);
+    while !output.is_char_boundary(length) {
+        length -= 1;
+    }
+
+    let mut packet = [0; PACKET_SIZE];
+    packet[0..2].copy_from_slice(&PACKET_VERSION.to_be_bytes());
+    packet[2..4].copy_from_slice(&RESPONSE_PACKET.to_be_bytes());
+    packet[8..10].copy_from_slice(&(state as u16).to_be_bytes());
+    packet[BUFFER_OFFSET..BUFFER_OFFSET + length].copy_from_slice(&output.as_bytes()[..length]);
+    let crc = crc32(&packet);
+    packet[4..8].copy_from_slice(&crc.to_be_bytes());
+    packet
+}
+
+fn check_client(name: &str, clients: &[ClientDetails]) -> (ServiceState, String) {
+    let Some(details) = clients.iter().find(|x| x.name == name) else {
+        return (
+            ServiceState::Unknown,
+            format!("CHECKMATE UNKNOWN - No client named {name}"),
+        );
+    };
+    match details.status {
+        None => (
+            ServiceState::Unknown,
+            format!("CHECKMATE UNKNOWN - {name} is {}", details.severity()),
+        ),
+        Some(Ok(_)) => (ServiceState::Ok, format!("CHECKMATE OK - {name} is ok")),
+        Some(Err(ref err)) if details.warning => (
+            ServiceState::Warning,
+            format!("CHECKMATE WARNING - {name}: {err}"),
+        ),
+        Some(Err(ref err)) => (
+            ServiceState::Critical,
+            format!("CHECKMATE CRITICAL - {name}: {err}"),
+        ),
+    }
+}
+
+fn check_all_clients(clients: &[ClientDetails]) -> (ServiceState, String) {
+    let problems = clients
+        .iter()
+        .filter_map(|x| match x.status {
+            Some(Err(ref err)) => Some(format!("{}: {err}", x.name)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let warnings = clients
+        .iter()
+        .filter(|x| x.severity() == Severity::Warning)
+        .count();
+    let errors = problems.len() - warnings;
+    let performance_data = format!(
+        "errors={errors} warnings={warnings} clients={}",
+        clients.len()
+    );
+    let (state, label) = match (errors, warnings) {
+        (0, 0) => {
+            return (
+                ServiceState::Ok,
+                format!("CHECKMATE OK - No errors | {performance_data}"),
+            )
+        }
+        (0, _) => (ServiceState::Warning, "WARNING"),
+        _ => (ServiceState::Critical, "CRITICAL"),
+    };
+    (
+        state,
+        format!(
+            "CHECKMATE {label} - {} | {performance_data}",
+            problems.join(", ")
+        ),
+    )
+}
+
+fn execute_command(command: &str, clients: &[ClientDetails]) -> (ServiceState, String) {
+    let mut arguments = command.split('!');
+    match (arguments.next(), arguments.next(), arguments.next()) {
+        // Sent by check_nrpe without a command to check the version of the daemon
+        (Some("_NRPE_CHECK"), None, _) => (ServiceState::Ok, format!("CheckMate v{VERSION}")),
+        (Some("check_checkmate"), None, _) => check_all_clients(clients),
+        (Some("check_checkmate"), Some(name), None) => check_client(name, clients),
+        _ => (
+            ServiceState::Unknown,
+            format!("CHECKMATE UNKNOWN - Unsupported command {command}"),
+        ),
+    }
+}
+
+async fn handle_connection(
+    stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    task_communication: &TaskCommunication,
+    pushed_statuses: &PushedStatuses,
+) -> std::io::Result<()> {
+    let mut packet = [0; PACKET_SIZE];
+    stream.read_exact(&mut packet).await?;
+    let Some(command) = decode_query(&packet) else {
+        // Closing the connection makes check_nrpe retry with a version 2 packet
+        return Ok(());
+    };
+
+    let mut clients = task_communication.get_client_details_for_service().await;
+    clients.extend(pushed_statuses.get_details());
+    let (state, output) = execute_command(&command, &clients);
+    stream.write_all(&encode_response(state, &output)).await?;
+    stream.shutdown().await
+}
+
+pub async fn serve(
+    listener: TcpListener,
+    task_communication: TaskCommunication,
+    pushed_statuses: PushedStatuses,
+) {
+    loop {
+        let mut stream = match listener.accept().await {
+            Ok((stream, _address)) => stream,
+            Err(err) => {
+                eprintln!("Failed to accept NRPE connection: {}", err);
+                continue;
+            }
+        };
+
+        let task_communication = task_communication.clone();
+        let pushed_statuses = pushed_statuses.clone();
+        tokio::spawn(async move {
+            let result = tokio::time::timeout(
+                NRPE_REQUEST_TIMEOUT,
+                handle_connection(&mut stream, &task_communication, &pushed_statuses),
+            )
+            .await;
+            match result {
+                Ok(Err(err)) => eprintln!("Failed to respond to NRPE query: {}", err),
+                Err(_) => eprintln!("NRPE query timed out"),
+                Ok(Ok(_)) => (),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_query(command: &str) -> [u8; PACKET_SIZE] {
+        let mut packet = encode_response(ServiceState::Ok, command);
+        packet[2..4].copy_from_slice(&QUERY_PACKET.to_be_bytes());
+        packet[4..10].fill(0);
+        let crc = crc32(&packet);
+        packet[4..8].copy_from_slice(&crc.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn crc_matches_zlib() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn queries_are_decoded() {
+        let packet = encode_query("check_checkmate!backup");
+        assert_eq!(
+            decode_query(&packet),
+            Some("check_checkmate!backup".to_owned())
+        );
+
+        let mut corrupted = packet;
+        corrupted[BUFFER_OFFSET] = b'C';
+        assert_eq!(decode_query(&corrupted), None);
+
+        let mut version_3 = packet;
+        version_3[0..2].copy_from_slice(&3u16.to_be_bytes());
+        assert_eq!(decode_query(&version_3), None);
+    }
+
+    #[test]
+    fn long_output_is_truncated() {
+        let packet = encode_response(ServiceState::Critical, &"ą".repeat(BUFFER_SIZE));
+        let buffer = &packet[BUFFER_OFFSET..BUFFER_OFFSET + BUFFER_SIZE];
+        assert_eq!(buffer[BUFFER_SIZE - 1], 0);
+        assert_eq!(buffer.iter().position(|x| *x == 0), Some(BUFFER_SIZE - 2));
+        assert_eq!(u16::from_be_bytes([packet[8], packet[9]]), 2);
+    }
+
+    #[test]
+    fn commands_are_executed() {
+        let clients = [
+            ClientDetails::new("backup", Some(Err("No space left"))),
+            ClientDetails::new("disk", Some(Ok(()))),
+            ClientDetails::new("new", None),
+            ClientDetails::new("load", Some(Err("Load is high"))).with_warning(),
+        ];
+        assert_eq!(
+            execute_command("check_checkmate", &clients),
+            (
+                ServiceState::Critical,
+                "CHECKMATE CRITICAL - backup: No space left, load: Load is high | errors=1 warnings=1 clients=4".to_owned()
+            )
+        );
+        assert_eq!(
+            execute_command("check_checkmate", &clients[1..]),
+            (
+                ServiceState::Warning,
+                "CHECKMATE WARNING - load: Load is high | errors=0 warnings=1 clients=3".to_owned()
+            )
+        );
+        assert_eq!(
+            execute_command("check_checkmate", &clients[1..3]),
+            (
+                ServiceState::Ok,
+                "CHECKMATE OK - No errors | errors=0 warnings=0 clients=2".to_owned()
+            )
+        );
+        assert_eq!(
+            execute_command("check_checkmate!load", &clients),
+            (
+                ServiceState::Warning,
+                "CHECKMATE WARNING - load: Load is high".to_owned()
+            )
+        );
+        assert_eq!(
+            execute_command("check_checkmate!disk", &clients),
+            (ServiceState::Ok, "CHECKMATE OK - disk is ok".to_owned())
+        );
+        assert_eq!(
+            execute_command("check_checkmate!new", &clients).0,
+            ServiceState::Unknown
+        );
+        assert_eq!(
+            execute_command("check_checkmate!gone", &clients).0,
+            ServiceState::Unknown
+        );
+        assert_eq!(
+            execute_command("check_load", &clients).0,
+            ServiceState::Unknown
+        );
+    }
+
+    #[tokio::test]
+    async fn query_is_answered() {
+        let pushed_statuses = PushedStatuses::new();
+        pushed_statuses.push(
+            None,
+            ClientDetails::new("backup", Some(Err("No space left"))),
+            None,
+        );
+        let (mut client, mut server) = tokio::io::duplex(2 * PACKET_SIZE);
+
+        client
+            .write_all(&encode_query("check_checkmate!backup"))
+            .await
+            .unwrap();
+        handle_connection(&mut server, &TaskCommunication::new(), &pushed_statuses)
+            .await
+            .unwrap();
+        let mut response = [0; PACKET_SIZE];
+        client.read_exact(&mut response).await.unwrap();
+        assert_eq!(
+            u16::from_be_bytes([response[2], response[3]]),
+            RESPONSE_PACKET
+        );
+        assert_eq!(u16::from_be_bytes([response[8], response[9]]), 2);
+        assert!(
+            response[BUFFER_OFFSET..].starts_with(b"CHECKMATE CRITICAL - backup: No space left\0")
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// PROXY protocol of HAProxy, which TCP load balancers use to pass the address of the original client to servers behind
+// them. The load balancer sends a header before any data of the client. Both the human-readable version 1 and the
+// binary version 2 are supported. Health checks made by the load balancer itself send headers without an address, i.e.
+// UNKNOWN in version 1 and LOCAL in version 2, in which case the address of the connection should be used.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+const V1_PREFIX: &[u8] = b"PROXY ";
+const V1_MAX_LENGTH: usize = 107;
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+// Reads the header from the beginning of the stream, leaving data of the client in it. Returns the address of the
+// original client or None, if the header doesn't carry it.
+pub async fn read_header<R: AsyncBufRead + Unpin>(
+    stream: &mut R,
+) -> Result<Option<SocketAddr>, String> {
+    // Even the shortest header of version 1, "PROXY UNKNOWN\r\n", is longer than the signature of version 2
+    let mut start = [0u8; V2_SIGNATURE.len()];
+    stream
+        .read_exact(&mut start)
+        .await
+        .map_err(|err| format!("could not read header: {err}"))?;
+
+    if start == V2_SIGNATURE {
+        let mut fixed_part = [0u8; 4];
+        stream
+            .read_exact(&mut fixed_part)
+            .await
+            .map_err(|err| format!("could not read header: {err}"))?;
+        let length = u16::from_be_bytes([fixed_part[2], fixed_part[3]]);
+        let mut addresses = vec![0u8; length as usize];
+        stream
+            .read_exact(&mut addresses)
+            .await
+            .map_err(|err| format!("could not read header: {err}"))?;
+        parse_v2_header(fixed_part[0], fixed_part[1], &addresses)
+    } else if start.starts_with(V1_PREFIX) {
+        let mut line = start.to_vec();
+        stream
+            .take((V1_MAX_LENGTH - start.len()) as u64)
+            .read_until(b'\n', &mut line)
+            .await
+            .map_err(|err| format!("could not read header: {err}"))?;
+        parse_v1_header(&line)
+    } else {
+        Err("header is missing".to_owned())
+    }
+}
+
+fn parse_v1_header(line: &[u8]) -> Result<Option<SocketAddr>, String> {
+    let line = line
+        .strip_suffix(b"\r\n")
+        .ok_or("header is too long or not terminated")?;
+    let line = std::str::from_utf8(line).map_err(|_| "header is not a text")?;
+    let fields = line.split(' ').collect::<Vec<_>>();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Ok(None),
+        ["PROXY", "TCP4" | "TCP6", source_address, _, source_port, _] => {
+            let address = source_address
+                .parse::<IpAddr>()
+                .map_err(|_| format!("invalid source address {source_address}"))?;
+            let port = source_port
+                .parse::<u16>()
+                .map_err(|_| format!("invalid source port {source_port}"))?;
+            Ok(Some(SocketAddr::new(address, port)))
+        }
+        _ => Err(format!("invalid header \"{line}\"")),
+    }
+}
+
+fn parse_v2_header(
+    version_and_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> Result<Option<SocketAddr>, String> {
+    if version_and_command >> 4 != 2 {
+        return Err(format!("unsupported version {}", version_and_command >> 4));
+    }
+    match version_and_command & 0xF {
+        0 => return Ok(None), // LOCAL
+        1 => (),              // PROXY
+        command => return Err(format!("unsupported command {command}")),
+    }
+
+    // Addresses are followed by optional TLV fields, which are ignored
+    match family >> 4 {
+        1 if addresses.len() >= 12 => {
+            let address = Ipv4Addr::new(addresses[0], addresses[1], addresses[2], addresses[3]);
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(address), port)))
+        }
+        2 if addresses.len() >= 36 => {
+            let address: [u8; 16] = addresses[0..16].try_into().unwrap();
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(address)),
+                port,
+            )))
+        }
+        1 | 2 => Err("addresses are truncated".to_owned()),
+        _ => Ok(None), // unspecified or UNIX sockets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_header(command: u8, family: u8, addresses: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.push(0x20 | command);
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(addresses);
+        header
+    }
+
+    #[tokio::test]
+    async fn v1_header_is_read() {
+        let mut stream: &[u8] = b"PROXY TCP4 203.0.113.7 10.0.0.1 40000 10005\r\ndata";
+        let address = read_header(&mut stream).await;
+        assert_eq!(address, Ok(Some("203.0.113.7:40000".parse().unwrap())));
+        assert_eq!(stream, b"data");
+
+        let mut stream: &[u8] = b"PROXY TCP6 2001:db8::1 2001:db8::2 40000 10005\r\n";
+        let address = read_header(&mut stream).await;
+        assert_eq!(address, Ok(Some("[2001:db8::1]:40000".parse().unwrap())));
+
+        let mut stream: &[u8] = b"PROXY UNKNOWN\r\ndata";
+        assert_eq!(read_header(&mut stream).await, Ok(None));
+        assert_eq!(stream, b"data");
+    }
+
+    #[tokio::test]
+    async fn v2_header_is_read() {
+        let mut addresses = vec![203, 0, 113, 7, 10, 0, 0, 1];
+        addresses.extend_from_slice(&40000u16.to_be_bytes());
+        addresses.extend_from_slice(&10005u16.to_be_bytes());
+        addresses.extend_from_slice(&[0x04, 0x00, 0x01, 0x00]); // TLV field
+        let mut header = v2_header(1, 0x11, &addresses);
+        header.extend_from_slice(b"data");
+        let mut stream = header.as_slice();
+        let address = read_header(&mut stream).await;
+        assert_eq!(address, Ok(Some("203.0.113.7:40000".parse().unwrap())));
+        assert_eq!(stream, b"data");
+
+        let header = v2_header(0, 0x00, &[]);
+        assert_eq!(read_header(&mut header.as_slice()).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn invalid_headers_are_rejected() {
+        let headers: [&[u8]; 5] = [
+            b"\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00",
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 40000\r\n",
+            b"PROXY TCP4 localhost 10.0.0.1 40000 10005\r\n",
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 40000 10005",
+            b"PROXY",
+        ];
+        for header in headers {
+            assert!(read_header(&mut &header[..]).await.is_err());
+        }
+
+        let header = v2_header(1, 0x11, &[203, 0, 113, 7]);
+        assert!(read_header(&mut header.as_slice()).await.is_err());
+        let mut header = v2_header(1, 0x11, &[0; 12]);
+        header[12] = 0x11;
+        assert!(read_header(&mut header.as_slice()).await.is_err());
+    }
+}
@@ -0,0 +1,234 @@
+// Statuses pushed by one-shot clients, e.g. shell scripts or cron jobs, which connect, report a status and disconnect
+// right away. Unlike statuses of watching clients, they must outlive the connection, so they are kept in a map shared
+// by all tasks. Each name has at most one pushed status, which is replaced by the next push for the same name or
+// removed by clearing the name. A status can also be pushed with an expected interval, after which it turns into an
+// error if no other status was pushed for its name in the meantime. Names are qualified with namespaces of the clients
+// which pushed them, so statuses of different namespaces are kept apart.
+
+use crate::namespaces::Namespace;
+use check_mate_common::format_duration;
+use check_mate_common::ClientDetails;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+pub struct PushedStatuses {
+    locked_data: Arc<Mutex<HashMap<(Namespace, String), PushedStatus>>>,
+}
+
+struct PushedStatus {
+    details: ClientDetails,
+    time: Instant,
+    expected_interval: Option<Duration>,
+}
+
+impl PushedStatuses {
+    pub fn new() -> Self {
+        PushedStatuses {
+            locked_data: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Stores the status and returns the previous one pushed for the same name, if there was any. With an expected
+    // interval, the status has to be followed by another push within it. The status is as old as its details say,
+    // which is non-zero only for statuses mirrored from another server.
+    pub fn push(
+        &self,
+        namespace: Namespace,
+        details: ClientDetails,
+        expected_interval: Option<Duration>,
+    ) -> Option<ClientDetails> {
+        let mut data = self
+            .locked_data
+            .lock()
+            .expect("PushedStatuses mutex should not be poisoned");
+
+        let key = (namespace, details.name.clone());
+        let age = Duration::from_secs(details.age_seconds);
+        let status = PushedStatus {
+            details,
+            time: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+            expected_interval,
+        };
+        data.insert(key, status).map(|previous| previous.details)
+    }
+
+    // Removes the status and returns it, if there was any
+    pub fn remove(&self, namespace: &Namespace, name: &str) -> Option<ClientDetails> {
+        let mut data = self
+            .locked_data
+            .lock()
+            .expect("PushedStatuses mutex should not be poisoned");
+
+        let key = (namespace.clone(), name.to_owned());
+        data.remove(&key).map(|removed| removed.details)
+    }
+
+    // Turns statuses which weren't followed by another push within their expected interval into errors and returns
+    // them. Each of them is returned only once, until it's pushed again.
+    pub fn expire_overdue(&self) -> Vec<(Namespace, ClientDetails)> {
+        let mut data = self
+            .locked_data
+            .lock()
+            .expect("PushedStatuses mutex should not be poisoned");
+
+        let mut expired = Vec::new();
+        for ((namespace, _), status) in data.iter_mut() {
+            let Some(interval) = status.expected_interval else {
+                continue;
+            };
+            if status.time.elapsed() < interval {
+                continue;
+            }
+            let message = format!("No ping received for {}", format_duration(interval));
+            status.details.status = Some(Err(message));
+            status.expected_interval = None;
+            let details = ClientDetails {
+                age_seconds: status.time.elapsed().as_secs(),
+                ..status.details.clone()
+            };
+            expired.push((namespace.clone(), details));
+        }
+        expired
+    }
+
+    pub fn get_details(&self) -> Vec<ClientDetails> {
+        self.get_namespaced_details()
+            .into_iter()
+            .map(|(_, details)| details)
+            .collect()
+    }
+
+    pub fn get_namespaced_details(&self) -> Vec<(Namespace, ClientDetails)> {
+        let data = self
+            .locked_data
+            .lock()
+            .expect("PushedStatuses mutex should not be poisoned");
+
+        data.iter()
+            .map(|((namespace, _), status)| {
+                let details = ClientDetails {
+                    age_seconds: status.time.elapsed().as_secs(),
+                    ..status.details.clone()
+                };
+                (namespace.clone(), details)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pushed_status_replaces_previous_one_with_the_same_name() {
+        let pushed_statuses = PushedStatuses::new();
+        assert_eq!(
+            pushed_statuses.push(None, ClientDetails::new("backup", Some(Ok(()))), None),
+            None
+        );
+        assert_eq!(
+            pushed_statuses.push(None, ClientDetails::new("cron", Some(Ok(()))), None),
+            None
+        );
+
+        let previous = pushed_statuses.push(
+            None,
+            ClientDetails::new("backup", Some(Err("No space left"))),
+            None,
+        );
+        assert_eq!(previous, Some(ClientDetails::new("backup", Some(Ok(())))));
+
+        let mut all_details = pushed_statuses.get_details();
+        all_details.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(
+            all_details,
+            [
+                ClientDetails::new("backup", Some(Err("No space left"))),
+                ClientDetails::new("cron", Some(Ok(()))),
+            ]
+        );
+    }
+
+    #[test]
+    fn pushed_statuses_of_different_namespaces_are_kept_apart() {
+        let pushed_statuses = PushedStatuses::new();
+        let team = Some("team".to_owned());
+        pushed_statuses.push(None, ClientDetails::new("team/backup", Some(Ok(()))), None);
+        pushed_statuses.push(
+            team.clone(),
+            ClientDetails::new("team/backup", Some(Ok(()))),
+            None,
+        );
+        assert_eq!(pushed_statuses.get_details().len(), 2);
+
+        assert!(pushed_statuses.remove(&team, "team/backup").is_some());
+        assert_eq!(
+            pushed_statuses.get_namespaced_details(),
+            [(None, ClientDetails::new("team/backup", Some(Ok(()))))]
+        );
+    }
+
+    #[test]
+    fn pushed_status_is_removed() {
+        let pushed_statuses = PushedStatuses::new();
+        pushed_statuses.push(
+            None,
+            ClientDetails::new("backup", Some(Err("No space left"))),
+            None,
+        );
+        pushed_statuses.push(None, ClientDetails::new("cron", Some(Ok(()))), None);
+
+        let removed = pushed_statuses.remove(&None, "backup");
+        assert_eq!(
+            removed,
+            Some(ClientDetails::new("backup", Some(Err("No space left"))))
+        );
+        assert_eq!(pushed_statuses.remove(&None, "backup"), None);
+        assert_eq!(
+            pushed_statuses.get_details(),
+            [ClientDetails::new("cron", Some(Ok(())))]
+        );
+    }
+
+    #[test]
+    fn age_of_status_is_kept() {
+        let pushed_statuses = PushedStatuses::new();
+        let old = ClientDetails::new("backup", Some(Ok(()))).with_age(120);
+        pushed_statuses.push(None, old.clone(), None);
+        assert_eq!(pushed_statuses.get_details(), [old]);
+    }
+
+    #[test]
+    fn overdue_status_is_expired_once() {
+        let pushed_statuses = PushedStatuses::new();
+        pushed_statuses.push(
+            None,
+            ClientDetails::new("backup", Some(Ok(()))),
+            Some(Duration::ZERO),
+        );
+        pushed_statuses.push(
+            None,
+            ClientDetails::new("cron", Some(Ok(()))),
+            Some(Duration::from_secs(60)),
+        );
+        pushed_statuses.push(None, ClientDetails::new("disk", Some(Ok(()))), None);
+
+        let expected = ClientDetails::new("backup", Some(Err("No ping received for 0ms")));
+        assert_eq!(
+            pushed_statuses.expire_overdue(),
+            vec![(None, expected.clone())]
+        );
+        assert!(pushed_statuses.expire_overdue().is_empty());
+        assert!(pushed_statuses.get_details().contains(&expected));
+
+        pushed_statuses.push(
+            None,
+            ClientDetails::new("backup", Some(Ok(()))),
+            Some(Duration::ZERO),
+        );
+        assert_eq!(pushed_statuses.expire_overdue().len(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+// Rate limiting of status reports of each connection, so a runaway client, e.g. a watcher with a zero interval, can't
+// flood logs and subscribers with its statuses. Limits are enforced with a token bucket, which allows short bursts of
+// up to one second's worth of statuses. A client exceeding the limit can be throttled, i.e. its next commands are read
+// only when they fit into the limit, only warned about or disconnected. Each episode of exceeding the limit is logged
+// once, so the log isn't flooded by the warnings themselves.
+
+use check_mate_common::ServerCommand;
+use clap::ValueEnum;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum RateLimitAction {
+    Throttle,
+    Warn,
+    Disconnect,
+}
+
+pub fn is_limited(command: &ServerCommand) -> bool {
+    matches!(
+        command,
+        ServerCommand::SetStatusOk
+            | ServerCommand::SetStatusError(_)
+            | ServerCommand::SetStatusWarning(_)
+            | ServerCommand::SetStatusPending
+            | ServerCommand::PushStatus(_)
+    )
+}
+
+pub struct RateLimiter {
+    rate: u32, // statuses per second
+    action: RateLimitAction,
+    tokens: f64,
+    last_refill: Instant,
+    exceeded: bool,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u32, action: RateLimitAction) -> Self {
+        RateLimiter {
+            rate,
+            action,
+            tokens: rate as f64,
+            last_refill: Instant::now(),
+            exceeded: false,
+        }
+    }
+
+    // Takes a token if there is one. Otherwise returns how long it takes for one to be available.
+    fn take_token(&mut self, now: Instant) -> Option<Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        let rate = self.rate as f64;
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    // Returns whether the connection should be kept
+    pub async fn admit(&mut self, client_name: &str) -> bool {
+        let Some(mut delay) = self.take_token(Instant::now()) else {
+            self.exceeded = false;
+            return true;
+        };
+        if !self.exceeded {
+            let consequence = match self.action {
+                RateLimitAction::Throttle => ", throttling",
+                RateLimitAction::Warn => "",
+                RateLimitAction::Disconnect => ", disconnecting",
+            };
+            println!(
+                "Client {} exceeded the limit of {} statuses per second{}",
+                client_name, self.rate, consequence
+            );
+            self.exceeded = true;
+        }
+        match self.action {
+            RateLimitAction::Throttle => loop {
+                tokio::time::sleep(delay).await;
+                match self.take_token(Instant::now()) {
+                    Some(x) => delay = x,
+                    None => return true,
+                }
+            },
+            RateLimitAction::Warn => true,
+            RateLimitAction::Disconnect => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_refilled_over_time() {
+        let mut limiter = RateLimiter::new(2, RateLimitAction::Warn);
+        let start = limiter.last_refill;
+        assert_eq!(limiter.take_token(start), None);
+        assert_eq!(limiter.take_token(start), None);
+        assert_eq!(limiter.take_token(start), Some(Duration::from_millis(500)));
+
+        let later = start + Duration::from_millis(250);
+        assert_eq!(limiter.take_token(later), Some(Duration::from_millis(250)));
+        let later = start + Duration::from_secs(10);
+        assert_eq!(limiter.take_token(later), None);
+        assert_eq!(limiter.take_token(later), None);
+        assert!(limiter.take_token(later).is_some());
+    }
+
+    #[tokio::test]
+    async fn actions_are_applied_to_exceeding_clients() {
+        let mut limiter = RateLimiter::new(1, RateLimitAction::Disconnect);
+        assert!(limiter.admit("Backup").await);
+        assert!(!limiter.admit("Backup").await);
+
+        let mut limiter = RateLimiter::new(1, RateLimitAction::Warn);
+        assert!(limiter.admit("Backup").await);
+        assert!(limiter.admit("Backup").await);
+        assert!(limiter.exceeded);
+
+        let mut limiter = RateLimiter::new(20, RateLimitAction::Throttle);
+        let start = Instant::now();
+        for _ in 0..22 {
+            assert!(limiter.admit("Backup").await);
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    #[test]
+    fn only_statuses_are_limited() {
+        assert!(is_limited(&ServerCommand::SetStatusOk));
+        assert!(is_limited(&ServerCommand::PushStatus(Ok(()))));
+        assert!(!is_limited(&ServerCommand::ReplayedStatus(0, Ok(()))));
+        assert!(!is_limited(&ServerCommand::GetStatuses(false)));
+    }
+}
@@ -0,0 +1,252 @@
+// Standby mode, in which the server mirrors statuses known to a primary server, so monitoring doesn't have a single
+// point of failure. Clients are given addresses of both servers, e.g. "-a primary,standby", and connect to the standby
+// when the primary is down. The standby follows status changes of the primary and periodically synchronizes all
+// statuses, including names and tags of clients. Mirrored statuses are stored like pushed ones, so they are served to
+// readers as if the standby was the primary. When the primary can't be reached for the promotion timeout, the standby
+// promotes itself and stops mirroring. Mirrored statuses are then kept until clients with the same names report to the
+// standby, which also happens for names of clients already connected to the standby before the promotion. Statuses
+// of all namespaces are mirrored under their qualified names, e.g. "team-a/backup", into the default namespace, so on
+// the standby they are only visible to clients of the default namespace and ones selecting all namespaces.
+
+use crate::config::Config;
+use crate::pushed_statuses::PushedStatuses;
+use crate::statistics::Statistics;
+use crate::task_communication::TaskCommunication;
+use check_mate_common::constants::*;
+use check_mate_common::{format_duration, ClientDetails, CommunicationError, ServerCommand};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use tokio::io::BufReader;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+struct Mirror {
+    names: HashSet<String>,
+    pushed_statuses: PushedStatuses,
+    statistics: Statistics,
+    task_communication: TaskCommunication,
+}
+
+impl Mirror {
+    fn new(
+        pushed_statuses: PushedStatuses,
+        statistics: Statistics,
+        task_communication: TaskCommunication,
+    ) -> Self {
+        Self {
+            names: HashSet::new(),
+            pushed_statuses,
+            statistics,
+            task_communication,
+        }
+    }
+
+    // Clients without a status, e.g. ones only querying the primary, are not mirrored
+    fn mirror_status(&mut self, details: ClientDetails) {
+        if details.status.is_none() {
+            return;
+        }
+        let previous = self.pushed_statuses.push(None, details.clone(), None);
+        if previous.is_none() {
+            self.statistics.on_status_stored();
+        }
+        self.names.insert(details.name.clone());
+        if previous.map(|x| x.status) != Some(details.status.clone()) {
+            self.task_communication.publish_status_change(None, details);
+        }
+    }
+
+    fn forget(&mut self, name: &str) {
+        if self.names.remove(name) && self.pushed_statuses.remove(&None, name).is_some() {
+            self.statistics.on_status_removed();
+        }
+    }
+
+    async fn get_live_names(&self) -> HashSet<String> {
+        let details = self
+            .task_communication
+            .get_client_details_for_service()
+            .await;
+        details
+            .into_iter()
+            .filter(|x| x.status.is_some())
+            .map(|x| x.name)
+            .collect()
+    }
+
+    // Makes mirrored statuses match all statuses of the primary. Clients connected to the standby take precedence.
+    async fn synchronize(&mut self, clients: Vec<ClientDetails>) {
+        let live_names = self.get_live_names().await;
+        let clients = clients
+            .into_iter()
+            .filter(|x| x.status.is_some() && !live_names.contains(&x.name))
+            .collect::<Vec<_>>();
+        let gone_names = self
+            .names
+            .iter()
+            .filter(|name| !clients.iter().any(|x| &x.name == *name))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in gone_names {
+            self.forget(&name);
+        }
+        for details in clients {
+            self.mirror_status(details);
+        }
+    }
+
+    async fn follow(&mut self, stream: TcpStream) -> Result<(), CommunicationError> {
+        let (input_stream, mut output_stream) = stream.into_split();
+        let mut input_stream = BufReader::new(input_stream);
+        ServerCommand::SelectAllNamespaces
+            .send_async(&mut output_stream)
+            .await?;
+        ServerCommand::Subscribe
+            .send_async(&mut output_stream)
+            .await?;
+
+        let mut ticks = tokio::time::interval(REPLICATION_SYNC_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    ServerCommand::GetClientDetails
+                        .send_async(&mut output_stream)
+                        .await?;
+                }
+                command = ServerCommand::receive_async(&mut input_stream) => {
+                    match command? {
+                        ServerCommand::ClientDetails(details) => self.synchronize(details).await,
+                        ServerCommand::StatusChanged(details) => self.mirror_status(details),
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
+    // Mirrored statuses are replaced by ones reported by clients which connected to the standby
+    async fn replace_with_live_clients(mut self) {
+        let mut status_changes = self.task_communication.subscribe_status_changes();
+        loop {
+            for name in self.get_live_names().await {
+                self.forget(&name);
+            }
+            if self.names.is_empty() {
+                return;
+            }
+            match status_changes.recv().await {
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    }
+}
+
+async fn replicate(primary: String, promotion_timeout: Duration, mut mirror: Mirror) {
+    let mut unreachable_since = Instant::now();
+    loop {
+        match TcpStream::connect(&primary).await {
+            Ok(stream) => {
+                println!("Mirroring primary server {}", primary);
+                if let Err(err) = mirror.follow(stream).await {
+                    eprintln!("Lost connection to primary server: {}", err);
+                }
+                unreachable_since = Instant::now();
+            }
+            Err(err) => eprintln!("Failed to connect to primary server {}: {}", primary, err),
+        }
+        if unreachable_since.elapsed() >= promotion_timeout {
+            break;
+        }
+        tokio::time::sleep(REPLICATION_RECONNECT_INTERVAL).await;
+    }
+
+    println!(
+        "Primary server is unreachable for {}, promoting to primary",
+        format_duration(promotion_timeout)
+    );
+    mirror.replace_with_live_clients().await;
+}
+
+pub fn start_standby(
+    config: &Config,
+    pushed_statuses: PushedStatuses,
+    statistics: Statistics,
+    task_communication: TaskCommunication,
+) {
+    let Some(ref primary) = config.standby_of else {
+        return;
+    };
+    let primary = match primary.contains(':') {
+        true => primary.clone(),
+        false => format!("{primary}:{DEFAULT_PORT}"),
+    };
+    let mirror = Mirror::new(pushed_statuses, statistics, task_communication);
+    let promotion_timeout = config
+        .promotion_timeout
+        .unwrap_or(DEFAULT_PROMOTION_TIMEOUT);
+    tokio::spawn(replicate(primary, promotion_timeout, mirror));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mirror() -> Mirror {
+        Mirror::new(
+            PushedStatuses::new(),
+            Statistics::new(),
+            TaskCommunication::new(),
+        )
+    }
+
+    fn sorted_names(pushed_statuses: &PushedStatuses) -> Vec<String> {
+        let mut names = pushed_statuses
+            .get_details()
+            .into_iter()
+            .map(|x| x.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn statuses_of_primary_are_mirrored() {
+        let mut mirror = mirror();
+        let mut status_changes = mirror.task_communication.subscribe_status_changes();
+        mirror
+            .pushed_statuses
+            .push(None, ClientDetails::new("local", Some(Ok(()))), None);
+
+        let clients = vec![
+            ClientDetails::new("backup", Some(Err("Full"))),
+            ClientDetails::new("disk", Some(Ok(()))),
+            ClientDetails::new("<Unknown>", None),
+        ];
+        mirror.synchronize(clients).await;
+        assert_eq!(
+            sorted_names(&mirror.pushed_statuses),
+            ["backup", "disk", "local"]
+        );
+        assert_eq!(status_changes.try_recv().unwrap().1.name, "backup");
+        assert_eq!(status_changes.try_recv().unwrap().1.name, "disk");
+
+        mirror.mirror_status(ClientDetails::new("backup", Some(Err("Full"))));
+        assert!(status_changes.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn statuses_gone_from_primary_are_removed() {
+        let mut mirror = mirror();
+        let clients = vec![
+            ClientDetails::new("backup", Some(Err("Full"))),
+            ClientDetails::new("disk", Some(Ok(()))),
+        ];
+        mirror.synchronize(clients).await;
+        mirror
+            .synchronize(vec![ClientDetails::new("disk", Some(Ok(())))])
+            .await;
+        assert_eq!(sorted_names(&mirror.pushed_statuses), ["disk"]);
+        assert_eq!(mirror.statistics.snapshot().statuses_stored, 1);
+    }
+}
@@ -0,0 +1,185 @@
+// Health of the server itself, reported as the status of a virtual client, so problems of the monitor show up in read
+// like problems of anything else. Components of the server, e.g. forwarding to Zabbix, report their errors here and
+// the status is an error as long as any of them has one. Panics of tasks are kept until the server is restarted, as
+// nothing recovers from them. The status is stored among pushed statuses, so it's visible without any connection.
+
+use crate::pushed_statuses::PushedStatuses;
+use crate::statistics::Statistics;
+use crate::task_communication::TaskCommunication;
+use check_mate_common::ClientDetails;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+#[derive(Clone)]
+pub struct SelfMonitoring {
+    data: Option<Arc<SelfMonitoringData>>, // None if disabled
+}
+
+struct SelfMonitoringData {
+    name: String,
+    errors: Mutex<BTreeMap<&'static str, String>>, // by component
+    pushed_statuses: PushedStatuses,
+    statistics: Statistics,
+    task_communication: TaskCommunication,
+}
+
+fn get_status(errors: &BTreeMap<&'static str, String>) -> Result<(), String> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let errors = errors
+        .iter()
+        .map(|(component, err)| format!("{component}: {err}"))
+        .collect::<Vec<_>>();
+    Err(errors.join("; "))
+}
+
+impl SelfMonitoring {
+    // Registers the virtual client with an ok status, if a name is given
+    pub fn new(
+        name: Option<String>,
+        pushed_statuses: PushedStatuses,
+        statistics: Statistics,
+        task_communication: TaskCommunication,
+    ) -> Self {
+        let data = name.map(|name| {
+            Arc::new(SelfMonitoringData {
+                name,
+                errors: Mutex::new(BTreeMap::new()),
+                pushed_statuses,
+                statistics,
+                task_communication,
+            })
+        });
+        let self_monitoring = SelfMonitoring { data };
+        self_monitoring.publish(Ok(()));
+        self_monitoring
+    }
+
+    fn publish(&self, status: Result<(), String>) {
+        let Some(ref data) = self.data else {
+            return;
+        };
+        let details = ClientDetails {
+            name: data.name.clone(),
+            status: Some(status),
+            pending: false,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        };
+        crate::store_pushed_status(
+            None,
+            details,
+            None,
+            &data.pushed_statuses,
+            &data.statistics,
+            &data.task_communication,
+            false,
+        );
+    }
+
+    // Sets the result of the last operation of the component. The status is published only when it changes.
+    pub fn report(&self, component: &'static str, result: Result<(), String>) {
+        let Some(ref data) = self.data else {
+            return;
+        };
+        let mut errors = data
+            .errors
+            .lock()
+            .expect("SelfMonitoring mutex should not be poisoned");
+        let previous_status = get_status(&errors);
+        match result {
+            Ok(()) => errors.remove(component),
+            Err(err) => errors.insert(component, err),
+        };
+        let status = get_status(&errors);
+        if status != previous_status {
+            self.publish(status);
+        }
+    }
+
+    // Reports a panic of the task, if it happens
+    pub fn watch_task(&self, task: JoinHandle<()>) {
+        if self.data.is_none() {
+            return;
+        }
+        let self_monitoring = self.clone();
+        tokio::spawn(async move {
+            let Err(err) = task.await else {
+                return;
+            };
+            if !err.is_panic() {
+                return;
+            }
+            let payload = err.into_panic();
+            let message = match payload.downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_else(|| "unknown reason".to_owned()),
+            };
+            self_monitoring.report("tasks", Err(format!("a task panicked: {message}")));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create(name: Option<&str>) -> (SelfMonitoring, PushedStatuses) {
+        let pushed_statuses = PushedStatuses::new();
+        let self_monitoring = SelfMonitoring::new(
+            name.map(str::to_owned),
+            pushed_statuses.clone(),
+            Statistics::new(),
+            TaskCommunication::new(),
+        );
+        (self_monitoring, pushed_statuses)
+    }
+
+    fn get_status(pushed_statuses: &PushedStatuses) -> Option<Result<(), String>> {
+        let details = pushed_statuses.get_details();
+        details.into_iter().next().and_then(|x| x.status)
+    }
+
+    #[test]
+    fn errors_of_components_are_combined() {
+        let (self_monitoring, pushed_statuses) = create(Some("_checkmate_server"));
+        assert_eq!(get_status(&pushed_statuses), Some(Ok(())));
+
+        self_monitoring.report("zabbix", Err("request timed out".to_owned()));
+        self_monitoring.report("upstream", Err("connection refused".to_owned()));
+        assert_eq!(
+            get_status(&pushed_statuses),
+            Some(Err(
+                "upstream: connection refused; zabbix: request timed out".to_owned()
+            ))
+        );
+
+        self_monitoring.report("upstream", Ok(()));
+        self_monitoring.report("zabbix", Ok(()));
+        assert_eq!(get_status(&pushed_statuses), Some(Ok(())));
+    }
+
+    #[test]
+    fn disabled_monitoring_reports_nothing() {
+        let (self_monitoring, pushed_statuses) = create(None);
+        self_monitoring.report("zabbix", Err("request timed out".to_owned()));
+        assert_eq!(get_status(&pushed_statuses), None);
+    }
+
+    #[tokio::test]
+    async fn panics_of_tasks_are_reported() {
+        let (self_monitoring, pushed_statuses) = create(Some("_checkmate_server"));
+        self_monitoring.watch_task(tokio::spawn(async { panic!("invalid state") }));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(
+            get_status(&pushed_statuses),
+            Some(Err("tasks: a task panicked: invalid state".to_owned()))
+        );
+    }
+}
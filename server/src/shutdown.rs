@@ -0,0 +1,65 @@
+// Shutdown of the server, requested by the abort command or, when the server is embedded in another application,
+// through any of its handles. The server stops accepting connections and disconnects all clients. Tasks forwarding
+// statuses elsewhere, e.g. to the history, end by themselves once nothing publishes statuses anymore.
+
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> Self {
+        ShutdownHandle {
+            sender: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    pub async fn wait(&self) {
+        let mut receiver = self.sender.subscribe();
+        receiver
+            .wait_for(|x| *x)
+            .await
+            .expect("Sender is kept alive by self");
+    }
+
+    // Runs the future until it completes or the server is shut down, whichever comes first
+    pub(crate) async fn cancel_on_shutdown(self, future: impl Future<Output = ()>) {
+        tokio::select! {
+            _ = future => (),
+            _ = self.wait() => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn futures_are_cancelled_on_shutdown() {
+        let shutdown = ShutdownHandle::new();
+        assert!(!shutdown.is_shut_down());
+        shutdown
+            .clone()
+            .cancel_on_shutdown(std::future::ready(()))
+            .await;
+
+        let cancelled = tokio::spawn(shutdown.clone().cancel_on_shutdown(std::future::pending()));
+        shutdown.shutdown();
+        assert!(shutdown.is_shut_down());
+        cancelled.await.unwrap();
+        shutdown.wait().await;
+    }
+}
@@ -0,0 +1,69 @@
+// Counters describing the server itself, as opposed to statuses of its clients. They are shared between all tasks
+// and updated without locking, so a snapshot may be slightly inconsistent if it's taken while other tasks are busy.
+
+use check_mate_common::ServerStatistics;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+#[derive(Clone)]
+pub struct Statistics {
+    data: Arc<StatisticsData>,
+}
+
+struct StatisticsData {
+    start_time: Instant,
+    connected_clients: AtomicU64,
+    commands_processed: AtomicU64,
+    statuses_stored: AtomicU64,
+    notifications_sent: AtomicU64,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        let data = StatisticsData {
+            start_time: Instant::now(),
+            connected_clients: AtomicU64::new(0),
+            commands_processed: AtomicU64::new(0),
+            statuses_stored: AtomicU64::new(0),
+            notifications_sent: AtomicU64::new(0),
+        };
+        Statistics {
+            data: Arc::new(data),
+        }
+    }
+
+    pub fn on_client_connected(&self) {
+        self.data.connected_clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_client_disconnected(&self) {
+        self.data.connected_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn on_command_processed(&self) {
+        self.data.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_status_stored(&self) {
+        self.data.statuses_stored.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn on_status_removed(&self) {
+        self.data.statuses_stored.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn on_notification_sent(&self) {
+        self.data.notifications_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ServerStatistics {
+        ServerStatistics {
+            uptime_seconds: self.data.start_time.elapsed().as_secs(),
+            connected_clients: self.data.connected_clients.load(Ordering::Relaxed),
+            commands_processed: self.data.commands_processed.load(Ordering::Relaxed),
+            statuses_stored: self.data.statuses_stored.load(Ordering::Relaxed),
+            notifications_sent: self.data.notifications_sent.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+// Watchers tend to report the same error text over and over again, often for hours. Instead of keeping a separate copy
+// of the string for every client and every report, statuses are interned in a small cache shared by all tasks. Each
+// client holds an Arc to the cached string, so identical statuses share a single allocation and can be passed
+// between tasks without copying.
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+pub struct StatusCache {
+    locked_data: Arc<Mutex<HashSet<Arc<str>>>>,
+    capacity: usize,
+}
+
+impl StatusCache {
+    pub fn new(capacity: usize) -> Self {
+        StatusCache {
+            locked_data: Arc::new(Mutex::new(HashSet::new())),
+            capacity,
+        }
+    }
+
+    pub fn intern(&self, status: &str) -> Arc<str> {
+        let mut data = self
+            .locked_data
+            .lock()
+            .expect("StatusCache mutex should not be poisoned");
+
+        if let Some(cached) = data.get(status) {
+            return cached.clone();
+        }
+
+        // Cache is full. Drop all entries which are not used by any client. If all of them are still
+        // used, don't cache the new status at all. It will work, just without deduplication.
+        if data.len() >= self.capacity {
+            data.retain(|cached| Arc::strong_count(cached) > 1);
+        }
+        let status: Arc<str> = Arc::from(status);
+        if data.len() < self.capacity {
+            data.insert(status.clone());
+        }
+        status
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.locked_data.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_statuses_share_allocation() {
+        let cache = StatusCache::new(4);
+        let status1 = cache.intern("Too many files");
+        let status2 = cache.intern("Too many files");
+        let status3 = cache.intern("Too few files");
+        assert!(Arc::ptr_eq(&status1, &status2));
+        assert!(!Arc::ptr_eq(&status1, &status3));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn unused_statuses_are_evicted_when_cache_is_full() {
+        let cache = StatusCache::new(2);
+        let used = cache.intern("used");
+        drop(cache.intern("unused"));
+
+        let new = cache.intern("new");
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(&used, &cache.intern("used")));
+        assert!(Arc::ptr_eq(&new, &cache.intern("new")));
+    }
+
+    #[test]
+    fn statuses_are_not_cached_when_all_entries_are_used() {
+        let cache = StatusCache::new(2);
+        let _used1 = cache.intern("used1");
+        let _used2 = cache.intern("used2");
+
+        let status1 = cache.intern("new");
+        let status2 = cache.intern("new");
+        assert_eq!(&*status1, "new");
+        assert!(!Arc::ptr_eq(&status1, &status2));
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -9,20 +9,34 @@
 //   - one task broadcasts a refresh instruction to all other tasks. The instruction can be either conditional (by name) or unconditional.
 //   - all tasks check whether they should actually refresh based on their client name
 //   - if a task should refresh, it enqueues a refresh signal to send to its client
+//   - clearing clients by name works the same way, but tasks mark their statuses as ok instead
 // 3. Task creation/destruction
+// 4. Status changes
+//   - a task publishes details of its client whenever its status changes
+//   - all tasks which subscribed receive them and forward to their clients
+// 5. Namespaces
+//   - queries, refreshes and clears are broadcast with the scope of the requesting client
+//   - responses carry namespaces of the responding clients, so the requesting task can filter them by its scope
 
 use crate::client_state::ClientState;
-use check_mate_common::ServerCommand;
+use crate::namespaces::{Namespace, Scope};
+use check_mate_common::{constants::*, ClientDetails, ServerCommand};
 use std::ops::DerefMut;
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::{
-    mpsc::{Receiver, Sender},
+    broadcast,
+    mpsc::{channel, Receiver, Sender},
     Mutex,
 };
 
+// Services which don't handle a client, e.g. the gRPC API, are not registered as tasks. They use an id which no task
+// is registered with, so all tasks receive the messages they broadcast.
+pub const SERVICE_TASK_ID: usize = usize::MAX;
+
 #[derive(Clone)]
 pub struct TaskCommunication {
     locked_data: Arc<Mutex<PerThreadDataMap>>,
+    status_changes: broadcast::Sender<(Namespace, ClientDetails)>,
 }
 
 type PerThreadDataMap = HashMap<usize, Arc<Mutex<PerThreadData>>>;
@@ -33,11 +47,14 @@ struct PerThreadData {
 #[derive(Clone)]
 pub enum TaskMessage {
     ReadMessageRequest(Sender<TaskMessage>),
-    ReadMessageResponse(Result<(), String>, String),
-    RefreshByName(String),
-    RefreshAll,
+    ReadMessageResponse(Result<(), Arc<str>>, Namespace, String),
+    RefreshByName(Scope, String),
+    RefreshAll(Scope),
+    ClearByName(Scope, String),
     ListClientsRequest(Sender<TaskMessage>),
-    ListClientsResponse(String),
+    ListClientsResponse(Namespace, String),
+    ClientDetailsRequest(Sender<TaskMessage>),
+    ClientDetailsResponse(Namespace, ClientDetails),
     // Abort,
 }
 
@@ -46,6 +63,7 @@ impl TaskCommunication {
         let result = PerThreadDataMap::new();
         TaskCommunication {
             locked_data: Arc::new(Mutex::new(result)),
+            status_changes: broadcast::channel(STATUS_CHANGES_CAPACITY).0,
         }
     }
 
@@ -67,47 +85,81 @@ impl TaskCommunication {
 
     pub async fn process_task_message(&self, message: TaskMessage, client_state: &mut ClientState) {
         match message {
-            TaskMessage::ReadMessageResponse(_, _) => panic!("Unexpected task message"),
+            TaskMessage::ReadMessageResponse(_, _, _) => panic!("Unexpected task message"),
             TaskMessage::ReadMessageRequest(sender) => {
                 let message = TaskMessage::ReadMessageResponse(
                     client_state.get_status().clone(),
+                    client_state.get_namespace().clone(),
                     client_state.get_name_or_default(),
                 );
                 Self::unicast(sender, message).await;
             }
-            TaskMessage::RefreshByName(ref name) => {
-                if let Some(current_name) = client_state.get_name() {
-                    if current_name == name {
-                        client_state
-                            .push_command_to_send(ServerCommand::Refresh)
-                            .await;
-                    }
+            TaskMessage::RefreshByName(ref scope, ref name) => {
+                if client_state.is_named_in(scope, name) {
+                    client_state
+                        .push_command_to_send(ServerCommand::Refresh)
+                        .await;
+                }
+            }
+            TaskMessage::ClearByName(ref scope, ref name) => {
+                if client_state.is_named_in(scope, name) && client_state.clear_status() {
+                    self.publish_status_change(
+                        client_state.get_namespace().clone(),
+                        client_state.get_details(),
+                    );
                 }
             }
-            TaskMessage::RefreshAll => {
-                client_state
-                    .push_command_to_send(ServerCommand::Refresh)
-                    .await;
+            TaskMessage::RefreshAll(ref scope) => {
+                if client_state.is_visible_in(scope) {
+                    client_state
+                        .push_command_to_send(ServerCommand::Refresh)
+                        .await;
+                }
             }
             TaskMessage::ListClientsRequest(sender) => {
                 let message = TaskMessage::ListClientsResponse(
+                    client_state.get_namespace().clone(),
                     client_state.get_name_or_default(),
                 );
                 Self::unicast(sender, message).await;
             }
-            TaskMessage::ListClientsResponse(_) => panic!("Unexpected task message"),
+            TaskMessage::ListClientsResponse(_, _) => panic!("Unexpected task message"),
+            TaskMessage::ClientDetailsRequest(sender) => {
+                let message = TaskMessage::ClientDetailsResponse(
+                    client_state.get_namespace().clone(),
+                    client_state.get_details(),
+                );
+                Self::unicast(sender, message).await;
+            }
+            TaskMessage::ClientDetailsResponse(_, _) => panic!("Unexpected task message"),
         }
     }
 
-    pub async fn refresh_client_by_name(&self, task_id: usize, name: String) {
+    // Details of status changes carry qualified names, while namespaces allow subscribers to filter them by scope
+    pub fn subscribe_status_changes(&self) -> broadcast::Receiver<(Namespace, ClientDetails)> {
+        self.status_changes.subscribe()
+    }
+
+    pub fn publish_status_change(&self, namespace: Namespace, details: ClientDetails) {
+        // Sending fails only if there are no subscribers, which is fine
+        let _send_result = self.status_changes.send((namespace, details));
+    }
+
+    pub async fn refresh_client_by_name(&self, task_id: usize, scope: Scope, name: String) {
+        let data = self.get_locked_data_snapshot().await;
+        let message = TaskMessage::RefreshByName(scope, name);
+        Self::broadcast(task_id, &data, message).await;
+    }
+
+    pub async fn clear_client_by_name(&self, task_id: usize, scope: Scope, name: String) {
         let data = self.get_locked_data_snapshot().await;
-        let message = TaskMessage::RefreshByName(name);
+        let message = TaskMessage::ClearByName(scope, name);
         Self::broadcast(task_id, &data, message).await;
     }
 
-    pub async fn refresh_all_clients(&self, task_id: usize) {
+    pub async fn refresh_all_clients(&self, task_id: usize, scope: Scope) {
         let data = self.get_locked_data_snapshot().await;
-        let message = TaskMessage::RefreshAll;
+        let message = TaskMessage::RefreshAll(scope);
         Self::broadcast(task_id, &data, message).await;
     }
 
@@ -116,6 +168,7 @@ impl TaskCommunication {
         task_id: usize,
         receiver: &mut Receiver<TaskMessage>,
         sender: &Sender<TaskMessage>,
+        scope: &Scope,
         include_names: bool,
     ) -> Vec<String> {
         let mut data = self.get_locked_data_snapshot().await;
@@ -135,15 +188,19 @@ impl TaskCommunication {
             .await
             .into_iter()
             .filter_map(|message| match message {
-                TaskMessage::ReadMessageResponse(status, name) => match status {
-                    Ok(_) => None,
-                    Err(mut status_string) => {
-                        if include_names {
-                            status_string = format!("{}: {}", name, status_string);
+                TaskMessage::ReadMessageResponse(status, namespace, name) => {
+                    let name = scope.view_name(&namespace, &name)?;
+                    match status {
+                        Ok(_) => None,
+                        Err(status_string) => {
+                            if include_names {
+                                Some(format!("{}: {}", name, status_string))
+                            } else {
+                                Some(status_string.to_string())
+                            }
                         }
-                        Some(status_string)
                     }
-                },
+                }
                 _ => panic!("Unexpected message received"),
             })
             .collect()
@@ -154,6 +211,7 @@ impl TaskCommunication {
         task_id: usize,
         receiver: &mut Receiver<TaskMessage>,
         sender: &Sender<TaskMessage>,
+        scope: &Scope,
     ) -> Vec<String> {
         let mut data = self.get_locked_data_snapshot().await;
 
@@ -171,14 +229,50 @@ impl TaskCommunication {
             .await
             .into_iter()
             .filter_map(|message| match message {
-                TaskMessage::ListClientsResponse(name) => {
-                    Some(name)
-                },
+                TaskMessage::ListClientsResponse(namespace, name) => {
+                    scope.view_name(&namespace, &name)
+                }
                 _ => panic!("Unexpected message received"),
             })
             .collect()
     }
 
+    pub async fn get_client_details(
+        &self,
+        task_id: usize,
+        receiver: &mut Receiver<TaskMessage>,
+        sender: &Sender<TaskMessage>,
+        scope: &Scope,
+    ) -> Vec<ClientDetails> {
+        let mut data = self.get_locked_data_snapshot().await;
+
+        Self::broadcast(
+            task_id,
+            &data,
+            TaskMessage::ClientDetailsRequest(sender.clone()),
+        )
+        .await;
+
+        Self::collect(task_id, &mut data, receiver)
+            .await
+            .into_iter()
+            .filter_map(|message| match message {
+                TaskMessage::ClientDetailsResponse(namespace, details) => {
+                    scope.view_details(&namespace, details)
+                }
+                _ => panic!("Unexpected message received"),
+            })
+            .collect()
+    }
+
+    // Services are not scoped, so they see clients of all namespaces
+    pub async fn get_client_details_for_service(&self) -> Vec<ClientDetails> {
+        let (sender, mut receiver) = channel(1);
+        let scope = Scope::AllNamespaces;
+        self.get_client_details(SERVICE_TASK_ID, &mut receiver, &sender, &scope)
+            .await
+    }
+
     async fn broadcast(task_id: usize, data: &PerThreadDataMap, message: TaskMessage) {
         for (_id, data) in data.iter().filter(|(id, _)| **id != task_id) {
             let per_thread_data = data.lock().await;
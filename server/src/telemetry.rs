@@ -0,0 +1,361 @@
+// Optional export of the server's activity to an OpenTelemetry collector over OTLP (gRPC), so it shows up alongside
+// traces of other applications. Every command received from a client is traced as a span. Every status transition is
+// exported as an event and counted by the checkmate.status.transitions metric. Standard OTEL_* environment variables,
+// e.g. for headers or resource attributes, are honored by the exporters. Without the opentelemetry feature, the
+// telemetry does nothing.
+
+use crate::config::Config;
+use crate::namespaces::Namespace;
+use crate::shutdown::ShutdownHandle;
+use check_mate_common::{ClientDetails, ServerCommand};
+use tokio::sync::broadcast;
+
+#[cfg(feature = "opentelemetry")]
+use {
+    check_mate_common::constants::*,
+    opentelemetry::logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity},
+    opentelemetry::metrics::{Counter, MeterProvider},
+    opentelemetry::trace::{SpanKind, Tracer, TracerProvider},
+    opentelemetry::KeyValue,
+    opentelemetry_otlp::{LogExporter, MetricExporter, SpanExporter, WithExportConfig},
+    opentelemetry_sdk::logs::{SdkLogger, SdkLoggerProvider},
+    opentelemetry_sdk::metrics::SdkMeterProvider,
+    opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider},
+    opentelemetry_sdk::Resource,
+    std::sync::Arc,
+};
+
+#[derive(Clone, Default)]
+pub struct Telemetry {
+    #[cfg(feature = "opentelemetry")]
+    exporter: Option<Arc<Exporter>>,
+}
+
+#[cfg(feature = "opentelemetry")]
+struct Exporter {
+    tracer_provider: SdkTracerProvider,
+    meter_provider: SdkMeterProvider,
+    logger_provider: SdkLoggerProvider,
+    tracer: SdkTracer,
+    logger: SdkLogger,
+    status_transitions: Counter<u64>,
+}
+
+// Span of a single command. It ends when dropped.
+pub struct CommandSpan {
+    #[cfg(feature = "opentelemetry")]
+    _span: Option<opentelemetry_sdk::trace::Span>,
+}
+
+impl Telemetry {
+    #[cfg(feature = "opentelemetry")]
+    pub fn new(config: &Config) -> Result<Self, String> {
+        let Some(ref endpoint) = config.otlp_endpoint else {
+            return Ok(Self::default());
+        };
+        let on_error = |err: opentelemetry_otlp::ExporterBuildError| {
+            format!("could not create OpenTelemetry exporter: {err}")
+        };
+        let resource = Resource::builder().with_service_name(SERVICE_NAME).build();
+
+        let span_exporter = SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .with_timeout(OTLP_EXPORT_TIMEOUT)
+            .build()
+            .map_err(on_error)?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_resource(resource.clone())
+            .with_batch_exporter(span_exporter)
+            .build();
+
+        let metric_exporter = MetricExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .with_timeout(OTLP_EXPORT_TIMEOUT)
+            .build()
+            .map_err(on_error)?;
+        let meter_provider = SdkMeterProvider::builder()
+            .with_resource(resource.clone())
+            .with_periodic_exporter(metric_exporter)
+            .build();
+
+        let log_exporter = LogExporter::builder()
+            .with_tonic()
+            .with_endpoint(endpoint)
+            .with_timeout(OTLP_EXPORT_TIMEOUT)
+            .build()
+            .map_err(on_error)?;
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_resource(resource)
+            .with_batch_exporter(log_exporter)
+            .build();
+
+        let status_transitions = meter_provider
+            .meter(SERVICE_NAME)
+            .u64_counter("checkmate.status.transitions")
+            .with_description("Number of changes of statuses of clients")
+            .build();
+        let exporter = Exporter {
+            tracer: tracer_provider.tracer(SERVICE_NAME),
+            logger: logger_provider.logger(SERVICE_NAME),
+            tracer_provider,
+            meter_provider,
+            logger_provider,
+            status_transitions,
+        };
+        Ok(Self {
+            exporter: Some(Arc::new(exporter)),
+        })
+    }
+
+    #[cfg(not(feature = "opentelemetry"))]
+    pub fn new(_config: &Config) -> Result<Self, String> {
+        Ok(Self::default())
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    pub fn start_command_span(
+        &self,
+        task_id: usize,
+        client_name: Option<&str>,
+        command: &ServerCommand,
+    ) -> CommandSpan {
+        let span = self.exporter.as_ref().map(|exporter| {
+            let mut attributes = vec![KeyValue::new("checkmate.task_id", task_id as i64)];
+            if let Some(name) = client_name {
+                attributes.push(KeyValue::new("checkmate.client.name", name.to_owned()));
+            }
+            exporter
+                .tracer
+                .span_builder(format!("checkmate {}", get_command_name(command)))
+                .with_kind(SpanKind::Server)
+                .with_attributes(attributes)
+                .start(&exporter.tracer)
+        });
+        CommandSpan { _span: span }
+    }
+
+    #[cfg(not(feature = "opentelemetry"))]
+    pub fn start_command_span(
+        &self,
+        _task_id: usize,
+        _client_name: Option<&str>,
+        _command: &ServerCommand,
+    ) -> CommandSpan {
+        CommandSpan {}
+    }
+
+    // Exports status changes published by all tasks in the background. Changes missed because of a lag are skipped.
+    #[cfg(feature = "opentelemetry")]
+    pub fn export_status_changes(
+        &self,
+        mut status_changes: broadcast::Receiver<(Namespace, ClientDetails)>,
+    ) {
+        let Some(ref exporter) = self.exporter else {
+            return;
+        };
+        let exporter = exporter.clone();
+        tokio::spawn(async move {
+            loop {
+                match status_changes.recv().await {
+                    Ok((_, details)) => exporter.export_status_change(&details),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+
+    #[cfg(not(feature = "opentelemetry"))]
+    pub fn export_status_changes(
+        &self,
+        _status_changes: broadcast::Receiver<(Namespace, ClientDetails)>,
+    ) {
+    }
+
+    // The server is normally stopped by a signal, so it's shut down gracefully to export data which is still buffered
+    #[cfg(feature = "opentelemetry")]
+    pub fn shutdown_on_termination(&self, shutdown: ShutdownHandle) {
+        if self.exporter.is_none() {
+            return;
+        }
+        tokio::spawn(async move {
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{signal, SignalKind};
+                let mut terminate =
+                    signal(SignalKind::terminate()).expect("Signal handler should be installed");
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => (),
+                    _ = terminate.recv() => (),
+                }
+            }
+            #[cfg(not(unix))]
+            let _ = tokio::signal::ctrl_c().await;
+            shutdown.shutdown();
+        });
+    }
+
+    #[cfg(not(feature = "opentelemetry"))]
+    pub fn shutdown_on_termination(&self, _shutdown: ShutdownHandle) {}
+
+    // Exports everything which is still buffered. Has to be called before exiting, or the last batch is lost.
+    pub fn shutdown(&self) {
+        #[cfg(feature = "opentelemetry")]
+        if let Some(ref exporter) = self.exporter {
+            // Each provider waits for its last export, so they are shut down in parallel to not multiply the delay
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    exporter
+                        .tracer_provider
+                        .shutdown_with_timeout(OTLP_EXPORT_TIMEOUT)
+                });
+                scope.spawn(|| {
+                    exporter
+                        .meter_provider
+                        .shutdown_with_timeout(OTLP_EXPORT_TIMEOUT)
+                });
+                scope.spawn(|| {
+                    exporter
+                        .logger_provider
+                        .shutdown_with_timeout(OTLP_EXPORT_TIMEOUT)
+                });
+            });
+        }
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+const SERVICE_NAME: &str = "check_mate_server";
+
+#[cfg(feature = "opentelemetry")]
+impl Exporter {
+    fn export_status_change(&self, details: &ClientDetails) {
+        let (status, message) = get_status_attributes(details);
+        let attributes = [
+            KeyValue::new("checkmate.client.name", details.name.clone()),
+            KeyValue::new("checkmate.status", status),
+        ];
+        self.status_transitions.add(1, &attributes);
+
+        let mut record = self.logger.create_log_record();
+        record.set_event_name("checkmate.status_changed");
+        record.set_severity_number(match details.status {
+            Some(Err(_)) => Severity::Warn,
+            _ => Severity::Info,
+        });
+        record.set_body(AnyValue::from(format!("{}: {message}", details.name)));
+        record.add_attribute("checkmate.client.name", details.name.clone());
+        record.add_attribute("checkmate.status", status);
+        if !details.tags.is_empty() {
+            let tags = details.tags.iter().cloned().collect::<AnyValue>();
+            record.add_attribute("checkmate.client.tags", tags);
+        }
+        self.logger.emit(record);
+    }
+}
+
+// Returns the name of the status and its message
+#[cfg(any(feature = "opentelemetry", test))]
+fn get_status_attributes(details: &ClientDetails) -> (&'static str, String) {
+    match details.status {
+        Some(Err(ref message)) => (details.severity().as_str(), message.clone()),
+        _ => (details.severity().as_str(), details.severity().to_string()),
+    }
+}
+
+// Commands carry payloads, so spans are named only after their kind to keep the number of names low
+#[cfg(any(feature = "opentelemetry", test))]
+fn get_command_name(command: &ServerCommand) -> &'static str {
+    match command {
+        ServerCommand::Abort => "Abort",
+        ServerCommand::SetStatusOk => "SetStatusOk",
+        ServerCommand::SetStatusError(_) => "SetStatusError",
+        ServerCommand::GetStatuses(_) => "GetStatuses",
+        ServerCommand::RefreshClientByName(_) => "RefreshClientByName",
+        ServerCommand::RefreshAllClients => "RefreshAllClients",
+        ServerCommand::ListClients => "ListClients",
+        ServerCommand::SetName(_) => "SetName",
+        ServerCommand::GetServerStatistics => "GetServerStatistics",
+        ServerCommand::ReplayedStatus(_, _) => "ReplayedStatus",
+        ServerCommand::GetClientDetails => "GetClientDetails",
+        ServerCommand::Subscribe => "Subscribe",
+        ServerCommand::SetTags(_) => "SetTags",
+        ServerCommand::SetStatusPending => "SetStatusPending",
+        ServerCommand::PushStatus(_) => "PushStatus",
+        ServerCommand::ClearClientByName(_) => "ClearClientByName",
+        ServerCommand::SetNamespace(_) => "SetNamespace",
+        ServerCommand::SelectAllNamespaces => "SelectAllNamespaces",
+        ServerCommand::Authenticate(_) => "Authenticate",
+        ServerCommand::CreateApiKey(_, _) => "CreateApiKey",
+        ServerCommand::ListApiKeys => "ListApiKeys",
+        ServerCommand::LabelApiKey(_, _) => "LabelApiKey",
+        ServerCommand::RevokeApiKey(_) => "RevokeApiKey",
+        ServerCommand::Drain => "Drain",
+        ServerCommand::GetAvailability(_) => "GetAvailability",
+        ServerCommand::CompactHistory => "CompactHistory",
+        ServerCommand::ExportHistory(_, _) => "ExportHistory",
+        ServerCommand::SetStatusWarning(_) => "SetStatusWarning",
+        ServerCommand::Statuses(_) => "Statuses",
+        ServerCommand::Refresh => "Refresh",
+        ServerCommand::Clients(_) => "Clients",
+        ServerCommand::ServerStatistics(_) => "ServerStatistics",
+        ServerCommand::ClientDetails(_) => "ClientDetails",
+        ServerCommand::StatusChanged(_) => "StatusChanged",
+        ServerCommand::ApiKeyResult(_) => "ApiKeyResult",
+        ServerCommand::ApiKeys(_) => "ApiKeys",
+        ServerCommand::DrainProgress(_) => "DrainProgress",
+        ServerCommand::Availability(_) => "Availability",
+        ServerCommand::HistoryCompacted(_) => "HistoryCompacted",
+        ServerCommand::HistoryExport(_) => "HistoryExport",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_attributes_are_described() {
+        let mut details = ClientDetails {
+            name: "backup".to_owned(),
+            status: None,
+            pending: true,
+            warning: false,
+            age_seconds: 0,
+            tags: Vec::new(),
+        };
+        assert_eq!(
+            get_status_attributes(&details),
+            ("pending", "pending".to_owned())
+        );
+        details.status = Some(Ok(()));
+        assert_eq!(get_status_attributes(&details), ("ok", "ok".to_owned()));
+        details.status = Some(Err("Disk is full".to_owned()));
+        assert_eq!(
+            get_status_attributes(&details),
+            ("error", "Disk is full".to_owned())
+        );
+        details.warning = true;
+        assert_eq!(
+            get_status_attributes(&details),
+            ("warning", "Disk is full".to_owned())
+        );
+    }
+
+    #[test]
+    fn commands_are_named_without_payload() {
+        let command = ServerCommand::SetStatusError("Disk is full".to_owned());
+        assert_eq!(get_command_name(&command), "SetStatusError");
+        assert_eq!(get_command_name(&ServerCommand::Subscribe), "Subscribe");
+    }
+
+    #[test]
+    fn disabled_telemetry_does_nothing() {
+        let telemetry = Telemetry::new(&Config::default()).unwrap();
+        let _span = telemetry.start_command_span(0, Some("backup"), &ServerCommand::SetStatusOk);
+        telemetry.export_status_changes(broadcast::channel(1).1);
+        telemetry.shutdown();
+    }
+}
@@ -0,0 +1,60 @@
+// TLS of connections of clients. When a CA of clients is set, clients have to present certificates signed by it, so
+// machines without a certificate can't connect at all. Such clients are also identified by their certificates. Their
+// names are taken from the common name and DNS names of the certificate, so a client can't pretend to be another one.
+
+use crate::config::Config;
+use check_mate_common::tls::{load_certificates, load_private_key, load_root_store};
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+// Returns None, if TLS is not enabled
+pub fn create_acceptor(config: &Config) -> Result<Option<TlsAcceptor>, String> {
+    let (Some(certificate_path), Some(key_path)) = (&config.tls_cert, &config.tls_key) else {
+        return Ok(None);
+    };
+    let certificates = load_certificates(certificate_path)?;
+    let key = load_private_key(key_path)?;
+
+    let builder = ServerConfig::builder();
+    let builder = match config.tls_client_ca {
+        Some(ref ca_path) => {
+            let root_store = load_root_store(ca_path)?;
+            let verifier = WebPkiClientVerifier::builder(Arc::new(root_store))
+                .build()
+                .map_err(|err| format!("invalid CA of clients: {err}"))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let server_config = builder
+        .with_single_cert(certificates, key)
+        .map_err(|err| format!("invalid certificate of the server: {err}"))?;
+    Ok(Some(TlsAcceptor::from(Arc::new(server_config))))
+}
+
+// Returns names which a client with the given certificate can use. The first one is its default name.
+pub fn get_identities(certificate: &CertificateDer) -> Vec<String> {
+    let Ok((_, certificate)) = X509Certificate::from_der(certificate) else {
+        return Vec::new();
+    };
+    let mut identities = certificate
+        .subject()
+        .iter_common_name()
+        .filter_map(|x| x.as_str().ok())
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    if let Ok(Some(alternative_names)) = certificate.subject_alternative_name() {
+        for name in &alternative_names.value.general_names {
+            if let GeneralName::DNSName(name) = name {
+                if !identities.iter().any(|x| x == name) {
+                    identities.push(name.to_string());
+                }
+            }
+        }
+    }
+    identities
+}
@@ -0,0 +1,234 @@
+// Forwarding of status changes to a Zabbix server with the protocol of zabbix_sender, so CheckMate can feed an existing
+// Zabbix installation. Each client is mapped to two trapper items of the configured Zabbix host:
+//   - checkmate.status[<NAME>] - 0 when the client is ok, 1 when it's in error and 2 when its status is unknown
+//   - checkmate.message[<NAME>] - message of the error, or the name of the status otherwise
+// Changes which could not be sent, e.g. because the Zabbix server is down, are not retried.
+
+use crate::config::Config;
+use crate::namespaces::Namespace;
+use crate::self_monitoring::SelfMonitoring;
+use check_mate_common::constants::*;
+use check_mate_common::ClientDetails;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+
+const HEADER: &[u8] = b"ZBXD\x01";
+
+#[derive(PartialEq, Debug)]
+struct Item {
+    key: String,
+    value: String,
+}
+
+// Parameters of item keys have to be quoted when they contain characters with a special meaning
+fn format_item_key(key: &str, name: &str) -> String {
+    let needs_quoting = name.starts_with(' ') || name.contains([',', '[', ']', '"']);
+    match needs_quoting {
+        true => format!("{key}[\"{}\"]", name.replace('"', "\\\"")),
+        false => format!("{key}[{name}]"),
+    }
+}
+
+fn get_items(details: &ClientDetails) -> [Item; 2] {
+    let (status, message) = match details.status {
+        Some(Ok(_)) => ("0", "ok".to_owned()),
+        Some(Err(ref err)) => ("1", err.clone()),
+        None => ("2", details.severity().to_string()),
+    };
+    [
+        Item {
+            key: format_item_key("checkmate.status", &details.name),
+            value: status.to_owned(),
+        },
+        Item {
+            key: format_item_key("checkmate.message", &details.name),
+            value: message,
+        },
+    ]
+}
+
+fn encode_request(host: &str, items: &[Item], clock: u64) -> Vec<u8> {
+    let data = items
+        .iter()
+        .map(|item| {
+            serde_json::json!({
+                "host": host,
+                "key": item.key,
+                "value": item.value,
+                "clock": clock,
+            })
+        })
+        .collect::<Vec<_>>();
+    let body = serde_json::json!({
+        "request": "sender data",
+        "data": data,
+        "clock": clock,
+    })
+    .to_string();
+
+    let mut request = HEADER.to_vec();
+    request.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    request.extend_from_slice(&[0; 4]);
+    request.extend_from_slice(body.as_bytes());
+    request
+}
+
+// Returns info about processed items reported by the Zabbix server
+async fn read_response(stream: &mut (impl AsyncRead + Unpin)) -> Result<String, String> {
+    let mut header = [0; 13];
+    stream
+        .read_exact(&mut header)
+        .await
+        .map_err(|err| format!("could not read response: {err}"))?;
+    if !header.starts_with(HEADER) {
+        return Err("invalid response header".to_owned());
+    }
+    let length = u32::from_le_bytes([header[5], header[6], header[7], header[8]]) as usize;
+    if length > ZABBIX_MAX_RESPONSE_SIZE {
+        return Err(format!("response of {length} bytes is too long"));
+    }
+
+    let mut body = vec![0; length];
+    stream
+        .read_exact(&mut body)
+        .await
+        .map_err(|err| format!("could not read response: {err}"))?;
+    let body = serde_json::from_slice::<serde_json::Value>(&body)
+        .map_err(|err| format!("invalid response: {err}"))?;
+    let info = body["info"].as_str().unwrap_or_default().to_owned();
+    match body["response"].as_str() {
+        Some("success") => Ok(info),
+        _ => Err(format!("request was rejected: {info}")),
+    }
+}
+
+async fn send_items(server: &str, host: &str, items: &[Item]) -> Result<String, String> {
+    let clock = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let request = encode_request(host, items, clock);
+
+    let send = async {
+        let mut stream = TcpStream::connect(server)
+            .await
+            .map_err(|err| format!("could not connect to {server}: {err}"))?;
+        stream
+            .write_all(&request)
+            .await
+            .map_err(|err| format!("could not send request: {err}"))?;
+        read_response(&mut stream).await
+    };
+    tokio::time::timeout(ZABBIX_TIMEOUT, send)
+        .await
+        .unwrap_or_else(|_| Err("request timed out".to_owned()))
+}
+
+// Forwards status changes published by all tasks in the background. Changes which come while a request is in progress
+// are sent together in the next one.
+pub fn forward_status_changes(
+    config: &Config,
+    mut status_changes: broadcast::Receiver<(Namespace, ClientDetails)>,
+    self_monitoring: SelfMonitoring,
+) {
+    let Some(ref server) = config.zabbix_server else {
+        return;
+    };
+    let server = match server.contains(':') {
+        true => server.clone(),
+        false => format!("{server}:{ZABBIX_DEFAULT_PORT}"),
+    };
+    let host = config.zabbix_host.clone();
+    tokio::spawn(async move {
+        loop {
+            let details = match status_changes.recv().await {
+                Ok((_, details)) => details,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let mut items = Vec::from(get_items(&details));
+            while let Ok((_, details)) = status_changes.try_recv() {
+                items.extend(get_items(&details));
+            }
+            let result = send_items(&server, &host, &items).await;
+            if let Err(ref err) = result {
+                eprintln!("Failed to send statuses to Zabbix: {}", err);
+            }
+            self_monitoring.report("zabbix", result.map(|_| ()));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn item_keys_are_quoted_when_needed() {
+        assert_eq!(format_item_key("k", "backup"), "k[backup]");
+        assert_eq!(format_item_key("k", "disk space"), "k[disk space]");
+        assert_eq!(format_item_key("k", "a,b"), "k[\"a,b\"]");
+        assert_eq!(format_item_key("k", "say \"hi\""), "k[\"say \\\"hi\\\"\"]");
+    }
+
+    #[test]
+    fn statuses_are_mapped_to_items() {
+        let items = get_items(&ClientDetails::new("backup", Some(Err("No space left"))));
+        assert_eq!(
+            items,
+            [
+                Item {
+                    key: "checkmate.status[backup]".to_owned(),
+                    value: "1".to_owned()
+                },
+                Item {
+                    key: "checkmate.message[backup]".to_owned(),
+                    value: "No space left".to_owned()
+                },
+            ]
+        );
+        assert_eq!(
+            get_items(&ClientDetails::new("disk", Some(Ok(()))))[0].value,
+            "0"
+        );
+        assert_eq!(
+            get_items(&ClientDetails::new("new", None))[1].value,
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn request_is_encoded() {
+        let items = get_items(&ClientDetails::new("disk", Some(Ok(()))));
+        let request = encode_request("web-1", &items, 1700000000);
+        assert!(request.starts_with(HEADER));
+        let length = u32::from_le_bytes(request[5..9].try_into().unwrap()) as usize;
+        assert_eq!(request.len(), 13 + length);
+
+        let body = serde_json::from_slice::<serde_json::Value>(&request[13..]).unwrap();
+        assert_eq!(body["request"], "sender data");
+        assert_eq!(body["data"][0]["host"], "web-1");
+        assert_eq!(body["data"][1]["key"], "checkmate.message[disk]");
+        assert_eq!(body["data"][1]["clock"], 1700000000);
+    }
+
+    #[tokio::test]
+    async fn responses_are_read() {
+        let encode = |body: &str| {
+            let mut response = HEADER.to_vec();
+            response.extend_from_slice(&(body.len() as u64).to_le_bytes());
+            response.extend_from_slice(body.as_bytes());
+            response
+        };
+        let response = encode(r#"{"response":"success","info":"processed: 2; failed: 0"}"#);
+        assert_eq!(
+            read_response(&mut response.as_slice()).await,
+            Ok("processed: 2; failed: 0".to_owned())
+        );
+        let response = encode(r#"{"response":"failed","info":"bad"}"#);
+        assert!(read_response(&mut response.as_slice()).await.is_err());
+        assert!(read_response(&mut b"HTTP/1.1".as_slice()).await.is_err());
+    }
+}
@@ -0,0 +1,10 @@
+use std::net::{IpAddr, UdpSocket};
+
+pub fn get_non_loopback_address() -> Option<IpAddr> {
+    // Connecting a UDP socket doesn't send anything, it only selects the interface which would be used to reach the
+    // documentation network. Its address is seen by the server as an address of another host.
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:9").ok()?;
+    let address = socket.local_addr().ok()?.ip();
+    (!address.is_loopback()).then_some(address)
+}
@@ -1,3 +1,4 @@
+pub mod address;
 pub mod paths;
 pub mod port;
 pub mod seekable;
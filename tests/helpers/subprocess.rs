@@ -25,6 +25,7 @@ impl Subprocess {
         }
     }
 
+    #[allow(clippy::len_zero)]
     pub fn start_client(name: &str, port: u16, args: &[&str]) -> Subprocess {
         let client_bin = get_cargo_bin("check_mate_client").expect("Client binary should be found");
 
@@ -34,9 +35,15 @@ impl Subprocess {
             port_args.insert(0, "--");
         }
 
+        // Everything after the wrapped command is passed to it, so the port must be specified before
+        let (args_before, args_after) = match args {
+            ["cron-wrap", ..] | ["check", "plugin" | "wasm", ..] => (port_args, args.to_vec()),
+            _ => (args.to_vec(), port_args),
+        };
+
         let child = std::process::Command::new(client_bin)
-            .args(args)
-            .args(port_args)
+            .args(args_before)
+            .args(args_after)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::null())
             .spawn()
@@ -48,6 +55,7 @@ impl Subprocess {
         }
     }
 
+    #[allow(clippy::expect_fun_call)]
     pub fn wait_and_get_output(&mut self, require_success: bool) -> String {
         let out = self
             .child
@@ -61,11 +69,73 @@ impl Subprocess {
         String::from_utf8(out.stdout).expect("Server stdout should be available")
     }
 
+    pub fn wait_and_get_exit_code(&mut self) -> Option<i32> {
+        self.child
+            .take()
+            .unwrap_or_else(|| panic!("{} should not be moved out", self.name))
+            .wait()
+            .unwrap_or_else(|_| panic!("{} should correctly exit", self.name))
+            .code()
+    }
+
+    pub fn wait_for_line(&mut self, expected_line: &str, timeout: std::time::Duration) {
+        let stdout = self
+            .child
+            .as_mut()
+            .and_then(|child| child.stdout.take())
+            .unwrap_or_else(|| panic!("{} stdout should be available", self.name));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let lines = std::io::BufRead::lines(std::io::BufReader::new(stdout));
+            for line in lines.map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match receiver.recv_timeout(remaining) {
+                Ok(line) if line == expected_line => return,
+                Ok(_) => continue,
+                Err(_) => panic!(
+                    "{} should output \"{expected_line}\" within {timeout:?}",
+                    self.name
+                ),
+            }
+        }
+    }
+
     pub fn kill_and_get_output(&mut self) -> String {
         self.kill();
         self.wait_and_get_output(false)
     }
 
+    #[cfg(unix)]
+    pub fn terminate(&mut self) {
+        self.send_signal("TERM");
+    }
+
+    #[cfg(unix)]
+    pub fn send_signal(&mut self, signal: &str) {
+        let child = self
+            .child
+            .as_ref()
+            .unwrap_or_else(|| panic!("{} has already been killed", self.name));
+        let status = std::process::Command::new("kill")
+            .arg(format!("-{signal}"))
+            .arg(child.id().to_string())
+            .status()
+            .unwrap_or_else(|_| panic!("{} should receive signal {signal}", self.name));
+        assert!(
+            status.success(),
+            "{} should receive signal {signal}",
+            self.name
+        );
+    }
+
     pub fn kill(&mut self) {
         match &mut self.child {
             Some(child) => {
@@ -1,4 +1,9 @@
 mod helpers;
+use check_mate_common::{ClientDetails, ServerCommand};
+use check_mate_sdk::testing::MockServer;
+use check_mate_sdk::{ConnectOptions, Filter, Identity, Reader, Reporter, Subscriber};
+use check_mate_server::Server;
+use helpers::address::get_non_loopback_address;
 use helpers::collection_counter::CountableCollection;
 use helpers::port::get_port_number;
 use helpers::seekable::Seekable;
@@ -60,9 +65,7 @@ fn watch_command_through_shell_works() {
         port,
         &[
             "watch",
-            "echo",
-            "aabbcc",
-            "|sed 's/a/A/g'", // TODO not portable
+            "echo aabbcc | sed 's/a/A/g'", // TODO not portable
             "--",
             "-s",
             "1",
@@ -77,28 +80,24 @@ fn watch_command_through_shell_works() {
 }
 
 #[test]
-fn client_reconnects_when_server_restarts() {
-    // TODO this test may fail sporadically due to the sleep being to short. I should make it smarter...
-
+fn watch_command_through_shell_passes_arguments_verbatim() {
     let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
     let _client_watcher = Subprocess::start_client(
         "client_watcher",
         port,
-        &["watch", "echo", "My fail", "--", "-c", "0", "-w", "0"],
+        &["watch", "echo", "a  b", "|", "$HOME", "it's", "--", "-s", "1"],
     );
 
-    for i in 0..2 {
-        let mut server = Subprocess::start_server(&format!("server{i}"), port, &[]);
-        std::thread::sleep(std::time::Duration::from_millis(50));
-        let server_out = server.kill_and_get_output();
-        server_out
-            .lines()
-            .seek("Client <Unknown> has error: My fail");
-    }
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "a  b | $HOME it's\n");
 }
 
 #[test]
-fn when_invalid_command_is_used_it_should_be_contained_in_error_status() {
+fn watch_command_with_stderr_stream_works() {
     let port = get_port_number();
     let _server = Subprocess::start_server("server", port, &[]);
     let _client_watcher = Subprocess::start_client(
@@ -106,8 +105,12 @@ fn when_invalid_command_is_used_it_should_be_contained_in_error_status() {
         port,
         &[
             "watch",
-            "echo aaa eee",
-            "\n\n\n \nsome nice error\nsecond line ignored",
+            "echo 'error on stderr' >&2", // TODO not portable
+            "--",
+            "-s",
+            "1",
+            "--stream",
+            "stderr",
         ],
     );
 
@@ -115,155 +118,2577 @@ fn when_invalid_command_is_used_it_should_be_contained_in_error_status() {
 
     let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
     let client_reader_out = client_reader.wait_and_get_output(true);
-    assert!(client_reader_out.contains("\"echo aaa eee\""));
+    assert_eq!(client_reader_out, "error on stderr\n");
 }
 
 #[test]
-fn read_messages_with_names_works() {
+fn watch_command_exceeding_timeout_is_reported() {
     let port = get_port_number();
     let _server = Subprocess::start_server("server", port, &[]);
-    let _client_watcher1 =
-        Subprocess::start_client("client_watcher1", port, &["watch", "echo", "error1"]);
-    let _client_watcher2 = Subprocess::start_client(
-        "client_watcher2",
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
         port,
-        &["watch", "echo", "error2", "--", "-n", "client2"],
+        &["watch", "sleep", "10", "--", "--timeout", "100ms"],
     );
 
-    std::thread::sleep(std::time::Duration::from_millis(50));
-    let mut client_reader = Subprocess::start_client("client_reader", port, &["read", "-i", "1"]);
-    let client_reader_out = client_reader.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(300));
 
-    client_reader_out
-        .lines()
-        .to_collection_counter()
-        .contains("<Unknown>: error1", 1)
-        .contains("", 1)
-        .contains("client2: error2", 1)
-        .nothing_else();
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Command timed out after 100ms\n");
 }
 
 #[test]
-fn read_messages_with_multiple_clients_works() {
+#[cfg(unix)]
+fn watch_command_through_custom_shell_works() {
     let port = get_port_number();
     let _server = Subprocess::start_server("server", port, &[]);
-    let _client_watcher1 = Subprocess::start_client(
-        "client_watcher1",
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
         port,
         &[
             "watch",
+            "false;",
             "echo",
-            "\n\n\n \nsome nice error\nsecond line ignored",
+            "after failure",
+            "--",
+            "-s",
+            "1",
+            "--shell-cmd",
+            "sh -e -c",
+            "-n",
+            "Shell",
         ],
     );
-    let _client_watcher2 = Subprocess::start_client(
-        "client_watcher2",
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // The shell exits on the first failure, so nothing is printed
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "--all", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Shell: ok\n");
+}
+
+#[test]
+fn watch_reports_pending_status_before_first_run() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_pending = Subprocess::start_client(
+        "client_pending",
         port,
         &[
             "watch",
-            "echo",
-            "\n\n\n \nsome other error\nsecond line ignored",
+            "true",
+            "--",
+            "-d",
+            "1h",
+            "--report-pending",
+            "-n",
+            "Pending",
         ],
     );
+    let _client_silent = Subprocess::start_client(
+        "client_silent",
+        port,
+        &["watch", "true", "--", "-d", "1h", "-n", "Silent"],
+    );
 
     std::thread::sleep(std::time::Duration::from_millis(50));
-    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--all", "-i", "1", "--sort", "name"],
+    );
     let client_reader_out = client_reader.wait_and_get_output(true);
-    client_reader_out
-        .lines()
-        .to_collection_counter()
-        .contains("some nice error", 1)
-        .contains("", 1)
-        .contains("some other error", 1)
-        .nothing_else();
+    assert_eq!(client_reader_out, "Pending: pending\n\nSilent: unknown\n");
 }
 
 #[test]
-fn refreshing_by_name_works() {
+fn pushed_statuses_are_kept_after_disconnecting() {
     let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    for (name, args) in [
+        ("Backup", ["--error", "No space left"]),
+        ("Cleanup", ["--error", "Stale lock"]),
+    ] {
+        let mut client_push = Subprocess::start_client(
+            "client_push",
+            port,
+            &[&["push", "-n", name][..], &args].concat(),
+        );
+        client_push.wait_and_get_output(true);
+    }
+    let mut client_push =
+        Subprocess::start_client("client_push", port, &["push", "-n", "Cleanup", "--ok"]);
+    client_push.wait_and_get_output(true);
 
-    // Start server with log_every_status flag, so we'll be able to see updates of Watcher2 after refreshing it.
-    let mut server = Subprocess::start_server("server", port, &["-e", "1"]);
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--all", "-i", "1", "--sort", "name"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Backup: No space left\n\nCleanup: ok\n");
+}
 
-    // Two watchers are working with very high watch interval, meaning they should only
-    // send status to server once.
-    let mut _client_watcher1 = Subprocess::start_client(
-        "client_watcher1",
+#[test]
+fn pings_are_received_over_http() {
+    use std::io::{Read, Write};
+
+    let port = get_port_number();
+    let http_port = get_port_number();
+    let http_port_arg = http_port.to_string();
+    let _server = Subprocess::start_server("server", port, &["--http-port", &http_port_arg]);
+    let send_request = |request: &str| {
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", http_port)).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    let response = send_request("GET /ping/Backup HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let response = send_request(
+        "POST /ping/Cleanup/fail HTTP/1.1\r\nContent-Length: 10\r\n\r\nStale lock",
+    );
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let response = send_request("GET /ping/Cron?interval=100ms HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let response = send_request("GET /status HTTP/1.1\r\n\r\n");
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
         port,
-        &[
-            "watch", "echo", "Error", "--", "-n", "Watcher1", "-w", "5000",
-        ],
+        &["read", "--all", "-i", "1", "--sort", "name"],
     );
-    let mut _client_watcher2 = Subprocess::start_client(
-        "client_watcher2",
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(
+        client_reader_out,
+        "Backup: ok\n\nCleanup: Stale lock\n\nCron: No ping received for 100ms\n"
+    );
+
+    let response = send_request("GET /feed HTTP/1.1\r\n\r\n");
+    assert!(response.contains("Content-Type: application/atom+xml"));
+    assert!(response.contains("<title>Cleanup failed</title>"));
+    assert!(response.contains("<content type=\"text\">No ping received for 100ms</content>"));
+    assert!(!response.contains("Backup"));
+}
+
+#[test]
+fn statuses_are_relayed_to_upstream_server() {
+    let upstream_port = get_port_number();
+    let port = get_port_number();
+    let upstream_arg = format!("127.0.0.1:{upstream_port}");
+    let _upstream_server = Subprocess::start_server("upstream_server", upstream_port, &[]);
+    let _server = Subprocess::start_server(
+        "server",
         port,
-        &[
-            "watch", "echo", "Error", "--", "-n", "Watcher2", "-w", "5000",
-        ],
+        &["--upstream", &upstream_arg, "--site", "Warsaw"],
     );
-    std::thread::sleep(std::time::Duration::from_millis(50));
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "Disk full", "--", "-n", "Disk"],
+    );
+    let mut client_push =
+        Subprocess::start_client("client_push", port, &["push", "-n", "Backup", "--ok"]);
+    client_push.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(500));
 
-    // Refresh one of the watchers to cause the second status report to server
-    let mut client_refresher =
-        Subprocess::start_client("client_refresher", port, &["refresh", "Watcher2"]);
-    client_refresher.wait_and_get_output(true);
-    std::thread::sleep(std::time::Duration::from_millis(50));
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        upstream_port,
+        &["read", "--all", "-i", "1", "--sort", "name"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(
+        client_reader_out,
+        "Warsaw: ok\n\nWarsaw/Backup: ok\n\nWarsaw/Disk: Disk full\n"
+    );
+}
+
+#[test]
+fn statuses_of_multiple_servers_are_combined() {
+    let port_a = get_port_number();
+    let port_b = get_port_number();
+    let address_b = format!("127.0.0.1:{port_b}");
+    let _server_a = Subprocess::start_server("server_a", port_a, &[]);
+    let _server_b = Subprocess::start_server("server_b", port_b, &[]);
+    let mut client_push = Subprocess::start_client(
+        "client_push_a",
+        port_a,
+        &["push", "-n", "Backup", "--error", "Full"],
+    );
+    client_push.wait_and_get_output(true);
+    let mut client_push =
+        Subprocess::start_client("client_push_b", port_b, &["push", "-n", "Backup", "--ok"]);
+    client_push.wait_and_get_output(true);
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port_a,
+        &["read", "--all", "-i", "1", "-a", "127.0.0.1", "-a", &address_b],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(
+        client_reader_out,
+        format!("127.0.0.1/Backup: Full\n\n{address_b}/Backup: ok\n")
+    );
+}
+
+#[test]
+fn statuses_are_scoped_to_namespaces() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let pushes: [(&str, &[&str]); 3] = [
+        ("client_push_a", &["push", "-n", "Backup", "--error", "Full", "--namespace", "team-a"]),
+        ("client_push_b", &["push", "-n", "Backup", "--error", "Offline", "--namespace", "team-b"]),
+        ("client_push", &["push", "-n", "Cron", "--ok"]),
+    ];
+    for (name, args) in pushes {
+        let mut client_push = Subprocess::start_client(name, port, args);
+        client_push.wait_and_get_output(true);
+    }
+
+    let read = |args: &[&str]| {
+        let args = [&["read", "--all", "-i", "1", "--sort", "name"], args].concat();
+        let mut client_reader = Subprocess::start_client("client_reader", port, &args);
+        client_reader.wait_and_get_output(true)
+    };
+    assert_eq!(read(&["--namespace", "team-a"]), "Backup: Full\n");
+    assert_eq!(read(&[]), "Cron: ok\n");
+    assert_eq!(
+        read(&["--all-namespaces"]),
+        "Cron: ok\n\nteam-a/Backup: Full\n\nteam-b/Backup: Offline\n"
+    );
+}
+
+#[test]
+fn standby_server_mirrors_primary_and_takes_over() {
+    let primary_port = get_port_number();
+    let port = get_port_number();
+    let primary_arg = format!("127.0.0.1:{primary_port}");
+    let mut primary_server = Subprocess::start_server("primary_server", primary_port, &[]);
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &["--standby-of", &primary_arg, "--promotion-timeout", "200ms"],
+    );
+    let mut client_push = Subprocess::start_client(
+        "client_push",
+        primary_port,
+        &["push", "-n", "Backup", "--error", "Full"],
+    );
+    client_push.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let read_standby = || {
+        let mut client_reader =
+            Subprocess::start_client("client_reader", port, &["read", "--all", "-i", "1"]);
+        client_reader.wait_and_get_output(true)
+    };
+    assert_eq!(read_standby(), "Backup: Full\n");
+
+    primary_server.kill();
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "true", "--", "-n", "Backup"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    assert_eq!(read_standby(), "Backup: ok\n");
 
-    // Server should see only one report from Watcher1, but two reports from Watcher2, since
-    // it has been explicitly refreshed.
-    _client_watcher1.kill_and_get_output();
-    _client_watcher2.kill_and_get_output();
     let server_out = server.kill_and_get_output();
     server_out
         .lines()
-        .to_collection_counter()
-        .contains("Name set to Watcher1", 1)
-        .contains("Name set to Watcher2", 1)
-        .contains("Client Watcher1 has error: Error", 1)
-        .contains("Client Watcher2 has error: Error", 2)
-        .nothing_else();
+        .seek(format!("Mirroring primary server {primary_arg}").as_str())
+        .seek("Primary server is unreachable for 200ms, promoting to primary");
 }
 
 #[test]
-fn refreshing_all_works() {
+fn notifications_are_raised_when_clients_fail() {
     let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut client_push = Subprocess::start_client(
+        "client_push",
+        port,
+        &["push", "-n", "Old", "--error", "Failed long ago"],
+    );
+    client_push.wait_and_get_output(true);
 
-    // Start server with log_every_status flag, so we'll be able to see updates of watchers after refreshing them.
-    let mut server = Subprocess::start_server("server", port, &["-e", "1"]);
+    let mut client_notify =
+        Subprocess::start_client("client_notify", port, &["notify", "--recoveries"]);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    for status_args in [&["--ok"][..], &["--error", "No space left"], &["--ok"]] {
+        let args = [&["push", "-n", "Backup"][..], status_args].concat();
+        let mut client_push = Subprocess::start_client("client_push", port, &args);
+        client_push.wait_and_get_output(true);
+    }
+    std::thread::sleep(std::time::Duration::from_millis(50));
 
-    // Two watchers are working with very high watch interval, meaning they should only
-    // send status to server once.
-    let mut _client_watcher1 = Subprocess::start_client(
-        "client_watcher1",
+    let client_notify_out = client_notify.kill_and_get_output();
+    assert_eq!(client_notify_out, "Backup: No space left\nBackup: ok\n");
+}
+
+#[test]
+fn badges_are_written() {
+    let port = get_port_number();
+    let directory = std::env::temp_dir().join(format!("check_mate_badges_{port}"));
+    let stale_badge = directory.join("clients").join("Gone.svg");
+    std::fs::create_dir_all(stale_badge.parent().unwrap()).unwrap();
+    std::fs::write(&stale_badge, "").unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    for args in [
+        &["-n", "Backup", "--ok"][..],
+        &["-n", "Disk space", "--error", "Full"],
+    ] {
+        let args = [&["push"][..], args].concat();
+        let mut client_push = Subprocess::start_client("client_push", port, &args);
+        client_push.wait_and_get_output(true);
+    }
+
+    let directory_arg = directory.to_str().unwrap();
+    let mut client_badge =
+        Subprocess::start_client("client_badge", port, &["badge", directory_arg]);
+    client_badge.wait_and_get_output(true);
+
+    let read_badge = |path: &str| std::fs::read_to_string(directory.join(path)).unwrap();
+    assert!(read_badge("overall.svg").contains("<title>health: 1 error</title>"));
+    assert!(read_badge("clients/Backup.svg").contains("<title>Backup: ok</title>"));
+    assert!(read_badge("clients/Disk_space.svg").contains("<title>Disk space: error</title>"));
+    assert!(!stale_badge.exists());
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn builtin_file_check_reports_statuses() {
+    let port = get_port_number();
+    let directory = std::env::temp_dir().join(format!("check_mate_file_check_{port}"));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join("empty.bak"), "").unwrap();
+    std::fs::write(directory.join("full.bak"), "data").unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut clients = Vec::new();
+    for (name, file) in [
+        ("Empty", "empty.bak"),
+        ("Full", "full.bak"),
+        ("Missing", "missing.bak"),
+    ] {
+        let path = directory.join(file);
+        let args = [
+            "check",
+            "file",
+            path.to_str().unwrap(),
+            "--min-size",
+            "1",
+            "-n",
+            name,
+        ];
+        clients.push(Subprocess::start_client("client_check", port, &args));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
         port,
-        &[
-            "watch", "echo", "Error", "--", "-n", "Watcher1", "-w", "5000",
-        ],
+        &["read", "--all", "-i", "1", "--sort", "name"],
     );
-    let mut _client_watcher2 = Subprocess::start_client(
-        "client_watcher2",
-        port,
-        &[
-            "watch", "echo", "Error", "--", "-n", "Watcher2", "-w", "5000",
-        ],
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    let path = |file: &str| directory.join(file).display().to_string();
+    let expected = format!(
+        "Empty: {} has 0 B, less than 1 B\n\nFull: ok\n\nMissing: {} does not exist\n",
+        path("empty.bak"),
+        path("missing.bak")
     );
-    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(client_reader_out, expected);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
 
-    // Refresh both watchers
-    let mut client_refresher = Subprocess::start_client("client_refresher", port, &["refresh_all"]);
-    client_refresher.wait_and_get_output(true);
-    std::thread::sleep(std::time::Duration::from_millis(50));
+#[test]
+#[cfg(unix)]
+fn plugin_check_reports_status_and_keeps_state() {
+    use std::os::unix::fs::PermissionsExt;
 
-    // Server should see only two reports from both watchers, since all watchers were refreshed.
-    _client_watcher1.kill_and_get_output();
-    _client_watcher2.kill_and_get_output();
-    let server_out = server.kill_and_get_output();
-    server_out
+    let port = get_port_number();
+    let plugin = std::env::temp_dir().join(format!("check_mate_plugin_{port}.sh"));
+    let script = r#"#!/bin/sh
+read request
+case "$request" in
+    *'"state":null'*) echo '{"status": "ok", "state": "started"}' ;;
+    *) echo "{\"status\": \"critical\", \"message\": \"Queue $1 is stuck\", \"metrics\": [{\"name\": \"size\", \"value\": 7}]}" ;;
+esac
+"#;
+    std::fs::write(&plugin, script).unwrap();
+    std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let args = [
+        "check",
+        "plugin",
+        "-n",
+        "Queue",
+        "-w",
+        "300ms",
+        plugin.to_str().unwrap(),
+        "mail",
+    ];
+    let _client = Subprocess::start_client("client_check", port, &args);
+    std::thread::sleep(std::time::Duration::from_millis(600));
+
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "--all", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(
+        client_reader_out,
+        "Queue: Queue mail is stuck (size=7)\n"
+    );
+    std::fs::remove_file(&plugin).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn checks_from_file_are_run_as_separate_clients() {
+    let port = get_port_number();
+    let check_file = std::env::temp_dir().join(format!("check_mate_checks_{port}.toml"));
+    let text = r#"
+[[check]]
+name = "Disk"
+command = ["echo", "Disk is full"]
+interval = "300ms"
+
+[[check]]
+name = "Backup"
+command = ["sh", "-c", "echo Backup failed; exit 2"]
+mode = "ExitCode"
+tags = ["storage"]
+"#;
+    std::fs::write(&check_file, text).unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client =
+        Subprocess::start_client("client_run", port, &["run", check_file.to_str().unwrap()]);
+    std::thread::sleep(std::time::Duration::from_millis(600));
+
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "--all", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    // Checks connect concurrently, so their order is not deterministic
+    let mut lines = client_reader_out
         .lines()
-        .to_collection_counter()
-        .contains("Name set to Watcher1", 1)
-        .contains("Name set to Watcher2", 1)
-        .contains("Client Watcher1 has error: Error", 2)
-        .contains("Client Watcher2 has error: Error", 2)
-        .nothing_else();
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(lines, ["Backup: Exit code was 2", "Disk: Disk is full"]);
+    std::fs::remove_file(&check_file).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn check_file_is_reloaded_on_hangup() {
+    let port = get_port_number();
+    let check_file = std::env::temp_dir().join(format!("check_mate_reload_{port}.toml"));
+    let write_checks = |checks: &[(&str, &str)]| {
+        let text = checks
+            .iter()
+            .map(|(name, message)| {
+                format!("[[check]]\nname = \"{name}\"\ncommand = [\"echo\", \"{message}\"]\n")
+            })
+            .collect::<String>();
+        std::fs::write(&check_file, text).unwrap();
+    };
+    let read_statuses = |name: &str| {
+        let mut client_reader = Subprocess::start_client(name, port, &["read", "--all", "-i", "1"]);
+        let output = client_reader.wait_and_get_output(true);
+        let mut lines = output
+            .lines()
+            .filter(|x| !x.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines
+    };
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    write_checks(&[("Disk", "Disk is full"), ("Backup", "Backup failed")]);
+    let mut client =
+        Subprocess::start_client("client_run", port, &["run", check_file.to_str().unwrap()]);
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(
+        read_statuses("client_reader1"),
+        ["Backup: Backup failed", "Disk: Disk is full"]
+    );
+
+    write_checks(&[("Disk", "Disk is almost full"), ("Memory", "Out of memory")]);
+    client.send_signal("HUP");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(
+        read_statuses("client_reader2"),
+        ["Disk: Disk is almost full", "Memory: Out of memory"]
+    );
+    std::fs::remove_file(&check_file).unwrap();
+}
+
+#[test]
+#[cfg(unix)]
+fn watch_client_is_controlled_through_socket() {
+    let port = get_port_number();
+    let socket = std::env::temp_dir().join(format!("check_mate_control_{port}.sock"));
+    let socket = socket.to_str().unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let args = [
+        "watch",
+        "echo",
+        "Disk is full",
+        "--",
+        "-n",
+        "Watcher",
+        "--control-socket",
+        socket,
+        "-w",
+        "1h",
+    ];
+    let _client = Subprocess::start_client("client_watch", port, &args);
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let control = |name: &str, command: &str| {
+        let mut client = Subprocess::start_client(name, port, &["control", socket, command]);
+        client.wait_and_get_output(true)
+    };
+    assert_eq!(control("client_pause", "pause"), "OK\n");
+    let status = control("client_status", "status");
+    assert!(status.starts_with("state: paused\nlast run: "), "{status}");
+    assert!(
+        status.ends_with("\nlast status: error: Disk is full\n"),
+        "{status}"
+    );
+    assert_eq!(control("client_resume", "resume"), "OK\n");
+    assert!(control("client_status", "status").starts_with("state: running\n"));
+    let _ = std::fs::remove_file(socket);
+}
+
+#[test]
+#[cfg(unix)]
+fn cron_job_status_is_pushed() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let script = "echo Copying files; echo No space left >&2; exit 3";
+    let mut client_failing = Subprocess::start_client(
+        "client_failing",
+        port,
+        &["cron-wrap", "-n", "Failing", "--", "sh", "-c", script],
+    );
+    let client_failing_out = client_failing.wait_and_get_output(false);
+    assert_eq!(client_failing_out, "Copying files\n");
+    let mut client_working = Subprocess::start_client(
+        "client_working",
+        port,
+        &["cron-wrap", "-n", "Working", "--", "echo", "Done"],
+    );
+    client_working.wait_and_get_output(true);
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--all", "-i", "1", "--sort", "name"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    let mut lines = client_reader_out.lines();
+    assert!(lines
+        .next()
+        .unwrap()
+        .starts_with("Failing: Exit code was 3 after 0s (started at "));
+    let mut tail = [lines.next().unwrap(), lines.next().unwrap()];
+    tail.sort();
+    assert_eq!(tail, ["Copying files", "No space left"]);
+    assert_eq!(lines.collect::<Vec<_>>(), ["", "Working: ok"]);
+}
+
+#[test]
+fn docker_health_reports_status_with_exit_code() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let pushes: [&[&str]; 2] = [
+        &["push", "-n", "Web", "--ok"],
+        &["push", "-n", "Db", "--error", "Disk is full"],
+    ];
+    for args in pushes {
+        let mut client_push = Subprocess::start_client("client_push", port, args);
+        client_push.wait_and_get_output(true);
+    }
+
+    let mut client_health =
+        Subprocess::start_client("client_health", port, &["docker-health", "Web"]);
+    assert_eq!(client_health.wait_and_get_output(true), "");
+
+    let mut client_health =
+        Subprocess::start_client("client_health", port, &["docker-health", "Db"]);
+    assert_eq!(
+        client_health.wait_and_get_output(false),
+        "Db: Disk is full\n"
+    );
+    let mut client_health =
+        Subprocess::start_client("client_health", port, &["docker-health", "Db"]);
+    assert_eq!(client_health.wait_and_get_exit_code(), Some(1));
+
+    let mut client_health =
+        Subprocess::start_client("client_health", port, &["docker-health", "Cache"]);
+    assert_eq!(
+        client_health.wait_and_get_output(false),
+        "Cache: not found\n"
+    );
+}
+
+#[test]
+fn clearing_client_by_name_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watch = Subprocess::start_client(
+        "client_watch",
+        port,
+        &[
+            "watch",
+            "echo",
+            "Disk is full",
+            "--",
+            "-d",
+            "0s",
+            "--interval",
+            "1h",
+            "-n",
+            "Disk",
+        ],
+    );
+    for name in ["Backup", "Cron"] {
+        let mut client_push = Subprocess::start_client(
+            "client_push",
+            port,
+            &["push", "-n", name, "--error", "Failed"],
+        );
+        client_push.wait_and_get_output(true);
+    }
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    for name in ["Disk", "Backup"] {
+        let mut client_clear = Subprocess::start_client("client_clear", port, &["clear", name]);
+        client_clear.wait_and_get_output(true);
+    }
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--all", "-i", "1", "--sort", "name"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Cron: Failed\n\nDisk: ok\n");
+}
+
+#[test]
+fn watch_errors_are_ignored_during_grace_period() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_booting = Subprocess::start_client(
+        "client_booting",
+        port,
+        &[
+            "watch",
+            "echo",
+            "down",
+            "--",
+            "--interval",
+            "50ms",
+            "--grace",
+            "1h",
+            "--report-pending",
+            "-n",
+            "Booting",
+        ],
+    );
+    let _client_booted = Subprocess::start_client(
+        "client_booted",
+        port,
+        &[
+            "watch",
+            "echo",
+            "down",
+            "--",
+            "--interval",
+            "50ms",
+            "--grace",
+            "100ms",
+            "-n",
+            "Booted",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--all", "-i", "1", "--sort", "name"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Booted: down\n\nBooting: pending\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn watch_command_is_run_on_sigusr1() {
+    let port = get_port_number();
+    let status_file = std::env::temp_dir().join(format!("check_mate_sigusr1_{port}"));
+    std::fs::write(&status_file, "First fail").unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "cat",
+            status_file.to_str().unwrap(),
+            "--",
+            "-w",
+            "1h",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    std::fs::write(&status_file, "Second fail").unwrap();
+    client_watcher.send_signal("USR1");
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    std::fs::remove_file(&status_file).unwrap();
+    assert_eq!(client_reader_out, "Second fail\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn watch_command_is_run_with_limits() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "sh",
+            "-c",
+            "echo $(nice) $(ulimit -v)",
+            "--",
+            "--nice",
+            "5",
+            "--memory-limit",
+            "256M",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "5 262144\n");
+}
+
+#[test]
+#[cfg(unix)]
+fn watch_command_is_run_over_ssh() {
+    // Fake ssh, which runs the command locally. It fails like ssh for an unknown host.
+    let port = get_port_number();
+    let fake_ssh = std::env::temp_dir().join(format!("check_mate_fake_ssh_{port}"));
+    std::fs::write(
+        &fake_ssh,
+        "[ \"$2\" = bad-host ] && echo 'Could not resolve hostname bad-host' >&2 && exit 255\n\
+         echo \"$2: $(sh -c \"$3\")\"\n",
+    )
+    .unwrap();
+    let ssh_command = format!("sh {}", fake_ssh.display());
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_good = Subprocess::start_client(
+        "client_good",
+        port,
+        &[
+            "watch",
+            "echo",
+            "a  b",
+            "--",
+            "--ssh",
+            "router",
+            "--ssh-cmd",
+            &ssh_command,
+            "-n",
+            "Good",
+        ],
+    );
+    let _client_bad = Subprocess::start_client(
+        "client_bad",
+        port,
+        &[
+            "watch",
+            "true",
+            "--",
+            "--ssh",
+            "bad-host",
+            "--ssh-cmd",
+            &ssh_command,
+            "-n",
+            "Bad",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "-i", "1", "--sort", "name"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    std::fs::remove_file(&fake_ssh).unwrap();
+    assert_eq!(
+        client_reader_out,
+        "Bad: Command was not executed. Could not connect to bad-host over SSH: Could not resolve hostname bad-host\n\nGood: router: a  b\n"
+    );
+}
+
+#[test]
+fn watch_with_only_changes_sends_status_once() {
+    let port = get_port_number();
+    let mut server = Subprocess::start_server("server", port, &["-e", "1"]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "My fail", "--", "-w", "20", "--only-changes"],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+
+    let server_out = server.kill_and_get_output();
+    let reports = server_out
+        .lines()
+        .filter(|line| line.contains("Client <Unknown> has error: My fail"))
+        .count();
+    assert_eq!(reports, 1);
+}
+
+#[test]
+fn watch_retries_failed_command_before_reporting() {
+    let port = get_port_number();
+    let marker_file = std::env::temp_dir().join(format!("check_mate_retry_{port}"));
+    let _ = std::fs::remove_file(&marker_file);
+    let marker_file = marker_file.to_str().unwrap();
+
+    // The command fails only on its first invocation
+    let command = format!("test -f {marker_file} || (touch {marker_file}; echo First attempt failed)");
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            &command,
+            "--",
+            "-s",
+            "1",
+            "-w",
+            "1h",
+            "--retries",
+            "1",
+            "-n",
+            "Retried",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "--all", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    std::fs::remove_file(marker_file).unwrap();
+    assert_eq!(client_reader_out, "Retried: ok\n");
+}
+
+#[test]
+fn watch_command_with_error_regex_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "printf",
+            "starting\\nERROR: disk full\\ndone",
+            "--",
+            "--error-regex",
+            "ERROR: (.*)",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "disk full\n");
+}
+
+#[test]
+fn watch_command_output_is_stripped_of_colors() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "printf", "\\033[31mred error\\033[0m"],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "red error\n");
+}
+
+#[test]
+fn watch_command_with_json_mode_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "echo",
+            r#"{"ok": false, "detail": "Database unreachable"}"#,
+            "--",
+            "-m",
+            "Json",
+            "--json-status",
+            ".ok",
+            "--json-message",
+            ".detail",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Database unreachable\n");
+}
+
+#[test]
+fn watch_command_with_inverted_exit_code_mode_works() {
+    let port = get_port_number();
+    let log_file = std::env::temp_dir().join(format!("check_mate_inverted_{port}"));
+    std::fs::write(
+        &log_file,
+        "INFO: started\nFATAL: out of memory\nINFO: done\n",
+    )
+    .unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "grep",
+            "-q",
+            "FATAL",
+            log_file.to_str().unwrap(),
+            "--",
+            "-m",
+            "InvertedExitCode",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    std::fs::remove_file(&log_file).unwrap();
+    assert_eq!(client_reader_out, "Exit code was 0\n");
+}
+
+#[test]
+fn watch_command_is_run_when_watched_path_changes() {
+    let port = get_port_number();
+    let status_file = std::env::temp_dir().join(format!("check_mate_watch_path_{port}"));
+    std::fs::write(&status_file, "Config invalid").unwrap();
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "cat",
+            status_file.to_str().unwrap(),
+            "--",
+            "--watch-path",
+            status_file.to_str().unwrap(),
+            "--no-timer",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Config invalid\n");
+
+    std::fs::write(&status_file, "").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    std::fs::remove_file(&status_file).unwrap();
+    assert_eq!(client_reader_out, "");
+}
+
+#[test]
+fn watch_command_in_streaming_mode_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "sh",
+            "-c",
+            "echo 'INFO: started'; echo 'ERROR: disk full'; sleep 10",
+            "--",
+            "--streaming",
+            "--error-regex",
+            "ERROR: (.*)",
+            "--quiet-period",
+            "1s",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "disk full\n");
+
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "");
+}
+
+#[test]
+fn tail_reports_matching_lines_of_log_file() {
+    let port = get_port_number();
+    let log_file = std::env::temp_dir().join(format!("check_mate_tail_{port}"));
+    std::fs::write(&log_file, "ERROR: old error\n").unwrap();
+    let append = |line: &str| {
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log_file)
+            .unwrap();
+        file.write_all(line.as_bytes()).unwrap();
+    };
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "tail",
+            log_file.to_str().unwrap(),
+            "-e",
+            "ERROR: (.*)",
+            "--quiet-period",
+            "1s",
+        ],
+    );
+
+    // Lines written before the start are ignored
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    append("INFO: started\nERROR: disk full\n");
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "disk full\n");
+
+    // The error is cleared after the quiet period
+    std::thread::sleep(std::time::Duration::from_millis(1200));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "");
+
+    // Rotated file is read from the beginning
+    std::fs::remove_file(&log_file).unwrap();
+    std::fs::write(&log_file, "ERROR: after rotation\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(800));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    std::fs::remove_file(&log_file).unwrap();
+    assert_eq!(client_reader_out, "after rotation\n");
+}
+
+#[test]
+fn watch_command_output_is_logged_to_file() {
+    let port = get_port_number();
+    let log_file = std::env::temp_dir().join(format!("check_mate_log_output_{port}"));
+    let _ = std::fs::remove_file(&log_file);
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "sh",
+            "-c",
+            "echo first; echo second; echo problem >&2; exit 3",
+            "--",
+            "-m",
+            "ExitCode",
+            "--log-output",
+            log_file.to_str().unwrap(),
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let log = std::fs::read_to_string(&log_file).unwrap();
+    std::fs::remove_file(&log_file).unwrap();
+    let record = log.split_once("] ").unwrap().1;
+    assert_eq!(
+        record,
+        "Exit code was 3\n--- stdout ---\nfirst\nsecond\n--- stderr ---\nproblem\n"
+    );
+}
+
+#[test]
+fn watch_command_runs_hook_on_error() {
+    let port = get_port_number();
+    let hook_file = std::env::temp_dir().join(format!("check_mate_hook_{port}"));
+    let _ = std::fs::remove_file(&hook_file);
+    let hook = format!(
+        "echo \"$CHECK_MATE_STATUS $CHECK_MATE_MESSAGE\" >> {}",
+        hook_file.display()
+    );
+
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "echo",
+            "Disk full",
+            "--",
+            "-w",
+            "50ms",
+            "--on-error",
+            &hook,
+        ],
+    );
+
+    // The hook is run only once, when the error appears
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let hook_output = std::fs::read_to_string(&hook_file).unwrap();
+    std::fs::remove_file(&hook_file).unwrap();
+    assert_eq!(hook_output, "error Disk full\n");
+}
+
+#[test]
+fn watch_command_with_nagios_mode_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "echo 'LOAD WARNING - load average: 5.1 | load1=5.1'; exit 1",
+            "--",
+            "-s",
+            "1",
+            "-m",
+            "Nagios",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "LOAD WARNING - load average: 5.1\n");
+
+    let mut client_lister = Subprocess::start_client("client_lister", port, &["list", "-o", "json"]);
+    let client_lister_out = client_lister.wait_and_get_output(true);
+    assert!(
+        client_lister_out.contains(r#""status": "warning""#),
+        "Unexpected output: {client_lister_out}"
+    );
+}
+
+#[test]
+fn client_reconnects_when_server_restarts() {
+    // TODO this test may fail sporadically due to the sleep being to short. I should make it smarter...
+
+    let port = get_port_number();
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "My fail", "--", "-c", "0", "-w", "0"],
+    );
+
+    for i in 0..2 {
+        let mut server = Subprocess::start_server(&format!("server{i}"), port, &[]);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let server_out = server.kill_and_get_output();
+        server_out
+            .lines()
+            .seek("Client <Unknown> has error: My fail");
+    }
+}
+
+#[test]
+fn client_resends_last_status_immediately_after_reconnecting() {
+    let port = get_port_number();
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "My fail", "--", "-c", "0", "-w", "1h"],
+    );
+
+    let timeout = std::time::Duration::from_secs(10);
+    let mut server = Subprocess::start_server("server0", port, &[]);
+    server.wait_for_line("Client <Unknown> has error: My fail", timeout);
+    server.kill_and_get_output();
+
+    // The command is not executed again for an hour, so the status must come from the cache
+    let mut server = Subprocess::start_server("server1", port, &[]);
+    server.wait_for_line("Client <Unknown> has error: My fail", timeout);
+}
+
+#[test]
+fn client_replays_statuses_gathered_while_offline() {
+    let port = get_port_number();
+    let status_file = std::env::temp_dir().join(format!("check_mate_replay_{port}"));
+    std::fs::write(&status_file, "First fail").unwrap();
+
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "cat",
+            status_file.to_str().unwrap(),
+            "--",
+            "-c",
+            "10",
+            "-w",
+            "10",
+        ],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    std::fs::write(&status_file, "Second fail").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut server = Subprocess::start_server("server", port, &[]);
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let server_out = server.kill_and_get_output();
+    std::fs::remove_file(&status_file).unwrap();
+    // Strip the age of replayed statuses, since it depends on timing
+    server_out
+        .lines()
+        .map(|line| line.split(" (replayed").next().unwrap())
+        .seek("Client <Unknown> had error: First fail")
+        .seek("Client <Unknown> had error: Second fail")
+        .seek("Client <Unknown> has error: Second fail");
+}
+
+#[test]
+#[cfg(unix)]
+fn client_reports_shutdown_when_terminated() {
+    fn run(shutdown_status: &str, expected_log: &str) {
+        let port = get_port_number();
+        let mut server = Subprocess::start_server("server", port, &["-e", "1"]);
+        let mut client_watcher = Subprocess::start_client(
+            "client_watcher",
+            port,
+            &[
+                "watch",
+                "echo",
+                "My fail",
+                "--",
+                "--shutdown-status",
+                shutdown_status,
+            ],
+        );
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        client_watcher.terminate();
+        client_watcher.wait_and_get_output(true);
+
+        let server_out = server.kill_and_get_output();
+        server_out
+            .lines()
+            .seek("Client <Unknown> has error: My fail")
+            .seek(expected_log);
+    }
+
+    run("Error", "Client <Unknown> has error: Client shutting down");
+    run("Ok", "Client <Unknown> is ok");
+}
+
+#[test]
+fn read_and_list_with_json_output_work() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher_ok = Subprocess::start_client(
+        "client_watcher_ok",
+        port,
+        &["watch", "true", "--", "-n", "Healthy"],
+    );
+    let _client_watcher_error = Subprocess::start_client(
+        "client_watcher_error",
+        port,
+        &["watch", "echo", "My fail", "--", "-n", "Broken"],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let expected_broken = r#"  {
+    "name": "Broken",
+    "status": "error",
+    "message": "My fail",
+    "age": 0,
+    "tags": []
+  }"#;
+    let expected_healthy = r#"  {
+    "name": "Healthy",
+    "status": "ok",
+    "message": null,
+    "age": 0,
+    "tags": []
+  }"#;
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read", "-o", "json"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, format!("[\n{expected_broken}\n]\n"));
+
+    let mut client_lister = Subprocess::start_client("client_lister", port, &["list", "-o", "json"]);
+    let client_lister_out = client_lister.wait_and_get_output(true);
+    assert!(
+        client_lister_out == format!("[\n{expected_broken},\n{expected_healthy}\n]\n")
+            || client_lister_out == format!("[\n{expected_healthy},\n{expected_broken}\n]\n"),
+        "Unexpected output: {client_lister_out}"
+    );
+}
+
+#[test]
+fn read_with_follow_prints_status_changes() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read", "--follow"]);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let _client_watcher_error = Subprocess::start_client(
+        "client_watcher_error",
+        port,
+        &["watch", "echo", "My fail", "--", "-n", "Broken"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let _client_watcher_ok = Subprocess::start_client(
+        "client_watcher_ok",
+        port,
+        &["watch", "true", "--", "-n", "Healthy"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Statuses which don't change are not printed again
+    let client_reader_out = client_reader.kill_and_get_output();
+    assert_eq!(client_reader_out, "Broken: My fail\nHealthy: ok\n");
+}
+
+#[test]
+fn read_with_every_and_diff_prints_status_changes() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "-e", "20", "--diff"]);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "My fail"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    client_watcher.kill();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let client_reader_out = client_reader.kill_and_get_output();
+    assert_eq!(client_reader_out, "+ My fail\n- My fail\n");
+}
+
+#[test]
+fn read_with_name_filter_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher_db = Subprocess::start_client(
+        "client_watcher_db",
+        port,
+        &["watch", "echo", "Replication lag", "--", "-n", "db-primary"],
+    );
+    let _client_watcher_web = Subprocess::start_client(
+        "client_watcher_web",
+        port,
+        &["watch", "echo", "Too many requests", "--", "-n", "web-primary"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "-f", "db-*", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "db-primary: Replication lag\n");
+}
+
+#[test]
+fn read_all_with_colors_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "true", "--", "-n", "Healthy"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--all", "--color", "always", "-i", "1"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "\x1b[32mHealthy: ok\x1b[0m\n");
+}
+
+#[test]
+fn read_with_sorting_and_grouping_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher1 = Subprocess::start_client(
+        "client_watcher1",
+        port,
+        &["watch", "echo", "error1", "--", "-n", "B", "-t", "db"],
+    );
+    let _client_watcher2 = Subprocess::start_client(
+        "client_watcher2",
+        port,
+        &["watch", "echo", "error2", "--", "-n", "A", "-t", "db", "-t", "web"],
+    );
+    let _client_watcher3 = Subprocess::start_client(
+        "client_watcher3",
+        port,
+        &["watch", "echo", "error3", "--", "-n", "C"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--sort", "name", "-i", "1"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "A: error2\n\nB: error1\n\nC: error3\n");
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--sort", "name", "--group-by", "tag", "-i", "1"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(
+        client_reader_out,
+        "[db]\nA: error2\n\nB: error1\n\n[web]\nA: error2\n\n[no tag]\nC: error3\n"
+    );
+}
+
+#[test]
+fn read_with_summary_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher1 = Subprocess::start_client(
+        "client_watcher1",
+        port,
+        &["watch", "echo", "error1", "--", "-n", "A"],
+    );
+    let _client_watcher2 = Subprocess::start_client(
+        "client_watcher2",
+        port,
+        &["watch", "true", "--", "-n", "B"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "--summary", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "A: error1\n\n1 ok, 1 error\n");
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read", "-q"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "1 ok, 1 error\n");
+}
+
+#[test]
+fn read_with_timestamps_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "error1", "--", "-n", "A"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &["read", "--timestamps", "-i", "1"],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "A: error1 (for 0s)\n");
+}
+
+#[test]
+fn when_invalid_command_is_used_it_should_be_contained_in_error_status() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "echo aaa eee",
+            "\n\n\n \nsome nice error\nsecond line ignored",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert!(client_reader_out.contains("\"echo aaa eee\""));
+}
+
+#[test]
+fn read_messages_with_names_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher1 =
+        Subprocess::start_client("client_watcher1", port, &["watch", "echo", "error1"]);
+    let _client_watcher2 = Subprocess::start_client(
+        "client_watcher2",
+        port,
+        &["watch", "echo", "error2", "--", "-n", "client2"],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+
+    client_reader_out
+        .lines()
+        .to_collection_counter()
+        .contains("<Unknown>: error1", 1)
+        .contains("", 1)
+        .contains("client2: error2", 1)
+        .nothing_else();
+}
+
+#[test]
+fn read_messages_with_multiple_clients_works() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher1 = Subprocess::start_client(
+        "client_watcher1",
+        port,
+        &[
+            "watch",
+            "echo",
+            "\n\n\n \nsome nice error\nsecond line ignored",
+        ],
+    );
+    let _client_watcher2 = Subprocess::start_client(
+        "client_watcher2",
+        port,
+        &[
+            "watch",
+            "echo",
+            "\n\n\n \nsome other error\nsecond line ignored",
+        ],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    client_reader_out
+        .lines()
+        .to_collection_counter()
+        .contains("some nice error", 1)
+        .contains("", 1)
+        .contains("some other error", 1)
+        .nothing_else();
+}
+
+#[test]
+fn refreshing_by_name_works() {
+    let port = get_port_number();
+
+    // Start server with log_every_status flag, so we'll be able to see updates of Watcher2 after refreshing it.
+    let mut server = Subprocess::start_server("server", port, &["-e", "1"]);
+
+    // Two watchers are working with very high watch interval, meaning they should only
+    // send status to server once.
+    let mut _client_watcher1 = Subprocess::start_client(
+        "client_watcher1",
+        port,
+        &[
+            "watch", "echo", "Error", "--", "-n", "Watcher1", "-w", "5000",
+        ],
+    );
+    let mut _client_watcher2 = Subprocess::start_client(
+        "client_watcher2",
+        port,
+        &[
+            "watch", "echo", "Error", "--", "-n", "Watcher2", "-w", "5000",
+        ],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Refresh one of the watchers to cause the second status report to server
+    let mut client_refresher =
+        Subprocess::start_client("client_refresher", port, &["refresh", "Watcher2"]);
+    client_refresher.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Server should see only one report from Watcher1, but two reports from Watcher2, since
+    // it has been explicitly refreshed.
+    _client_watcher1.kill_and_get_output();
+    _client_watcher2.kill_and_get_output();
+    let server_out = server.kill_and_get_output();
+    server_out
+        .lines()
+        .to_collection_counter()
+        .contains("Name set to Watcher1", 1)
+        .contains("Name set to Watcher2", 1)
+        .contains("Client Watcher1 has error: Error", 1)
+        .contains("Client Watcher2 has error: Error", 2)
+        .nothing_else();
+}
+
+#[test]
+fn refreshing_all_works() {
+    let port = get_port_number();
+
+    // Start server with log_every_status flag, so we'll be able to see updates of watchers after refreshing them.
+    let mut server = Subprocess::start_server("server", port, &["-e", "1"]);
+
+    // Two watchers are working with very high watch interval, meaning they should only
+    // send status to server once.
+    let mut _client_watcher1 = Subprocess::start_client(
+        "client_watcher1",
+        port,
+        &[
+            "watch", "echo", "Error", "--", "-n", "Watcher1", "-w", "5000",
+        ],
+    );
+    let mut _client_watcher2 = Subprocess::start_client(
+        "client_watcher2",
+        port,
+        &[
+            "watch", "echo", "Error", "--", "-n", "Watcher2", "-w", "5000",
+        ],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Refresh both watchers
+    let mut client_refresher = Subprocess::start_client("client_refresher", port, &["refresh_all"]);
+    client_refresher.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Server should see only two reports from both watchers, since all watchers were refreshed.
+    _client_watcher1.kill_and_get_output();
+    _client_watcher2.kill_and_get_output();
+    let server_out = server.kill_and_get_output();
+    server_out
+        .lines()
+        .to_collection_counter()
+        .contains("Name set to Watcher1", 1)
+        .contains("Name set to Watcher2", 1)
+        .contains("Client Watcher1 has error: Error", 2)
+        .contains("Client Watcher2 has error: Error", 2)
+        .nothing_else();
+}
+
+#[test]
+fn server_statistics_are_reported() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher1 = Subprocess::start_client(
+        "client_watcher1",
+        port,
+        &["watch", "echo", "Error", "--", "-n", "Watcher1", "-w", "5000"],
+    );
+    let _client_watcher2 = Subprocess::start_client(
+        "client_watcher2",
+        port,
+        &["watch", "echo", "", "--", "-w", "5000"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client_refresher =
+        Subprocess::start_client("client_refresher", port, &["refresh", "Watcher1"]);
+    client_refresher.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Watcher1 sent its name and two statuses, Watcher2 sent one status, the refresher and the stats
+    // client sent one command each. The stats client itself is also counted as connected.
+    let mut client_stats = Subprocess::start_client("client_stats", port, &["stats"]);
+    let client_stats_out = client_stats.wait_and_get_output(true);
+    client_stats_out
+        .lines()
+        .seek("Connected clients: 3")
+        .seek("Commands processed: 6")
+        .seek("Statuses stored: 2")
+        .seek("Notifications sent: 1");
+}
+
+#[test]
+fn client_connects_to_server_by_hostname() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "some error", "--", "-a", "localhost"],
+    );
+
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    // Server is listening only on IPv4 localhost. If "localhost" resolves to IPv6 address first, the client has to
+    // fall back to the next address.
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "-a", "localhost", "-r", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "some error\n");
+}
+
+#[test]
+fn server_reads_proxy_protocol_header() {
+    use check_mate_common::ServerCommand;
+    use std::io::Write;
+
+    let port = get_port_number();
+    let mut server = Subprocess::start_server("server", port, &["--proxy-protocol"]);
+
+    // Clients connecting directly don't send the header, so they are rejected
+    let mut client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "direct error", "--", "-n", "Direct"],
+    );
+
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream
+        .write_all(b"PROXY TCP4 203.0.113.7 127.0.0.1 40000 10005\r\n")
+        .unwrap();
+    stream
+        .write_all(&ServerCommand::SetName("Proxied".to_owned()).to_bytes())
+        .unwrap();
+    stream
+        .write_all(&ServerCommand::SetStatusError("No space left".to_owned()).to_bytes())
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    client_watcher.kill();
+    let server_out = server.kill_and_get_output();
+    server_out
+        .lines()
+        .seek("Name set to Proxied")
+        .seek("Client Proxied has error: No space left");
+    assert!(!server_out.contains("Direct"));
+}
+
+#[test]
+fn control_commands_are_accepted_only_on_admin_port() {
+    let port = get_port_number();
+    let admin_port = get_port_number();
+    let admin_port_arg = admin_port.to_string();
+    let mut server = Subprocess::start_server("server", port, &["--admin-port", &admin_port_arg]);
+
+    let mut client = Subprocess::start_client("client", port, &["abort", "-n", "Reporter"]);
+    assert!(client.wait_and_get_output(true).is_empty());
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client = Subprocess::start_client("client", admin_port, &["abort", "-n", "Admin"]);
+    assert!(client.wait_and_get_output(true).is_empty());
+
+    let server_out = server.wait_and_get_output(true);
+    server_out
+        .lines()
+        .seek("Name set to Reporter")
+        .seek("Client Reporter is not allowed to send control commands on this port")
+        .seek("Name set to Admin")
+        .seek("Received abort command");
+}
+
+#[test]
+fn admin_port_is_not_reachable_from_other_hosts() {
+    let Some(address) = get_non_loopback_address() else {
+        eprintln!("Skipped, because there is no address other than loopback");
+        return;
+    };
+    let address = address.to_string();
+    let port = get_port_number();
+    let admin_port = get_port_number();
+    let admin_port_arg = admin_port.to_string();
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &["--bind", "0.0.0.0", "--admin-port", &admin_port_arg],
+    );
+
+    let mut client = Subprocess::start_client(
+        "client",
+        port,
+        &["abort", "-n", "Remote", "-a", &address],
+    );
+    assert!(client.wait_and_get_output(true).is_empty());
+    assert!(std::net::TcpStream::connect((address.as_str(), admin_port)).is_err());
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client = Subprocess::start_client("client", admin_port, &["abort", "-n", "Admin"]);
+    assert!(client.wait_and_get_output(true).is_empty());
+
+    let server_out = server.wait_and_get_output(true);
+    server_out
+        .lines()
+        .seek("Name set to Remote")
+        .seek("Client Remote is not allowed to send control commands on this port")
+        .seek("Name set to Admin")
+        .seek("Received abort command");
+}
+
+#[test]
+fn draining_server_keeps_serving_connected_clients() {
+    let port = get_port_number();
+    let admin_port = get_port_number();
+    let admin_port_arg = admin_port.to_string();
+    let mut server = Subprocess::start_server("server", port, &["--admin-port", &admin_port_arg]);
+    let mut client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "some error", "--", "-n", "Watcher"],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client = Subprocess::start_client("client", admin_port, &["drain"]);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(
+        client_out,
+        "Server is draining, clients still connected: 1\n"
+    );
+
+    let mut client = Subprocess::start_client("client", port, &["read", "-r", "1"]);
+    assert_ne!(client.wait_and_get_exit_code(), Some(0));
+    let mut client_reader = Subprocess::start_client("client_reader", admin_port, &["read"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "some error\n");
+
+    client_watcher.kill_and_get_output();
+    let mut client = Subprocess::start_client("client", admin_port, &["drain", "--wait"]);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(client_out, "Server is drained\n");
+
+    let server_out = server.kill_and_get_output();
+    server_out
+        .lines()
+        .seek("Draining, new connections are no longer accepted")
+        .seek("Draining, 0 clients still connected");
+}
+
+#[test]
+fn server_reports_own_health_as_virtual_client() {
+    let port = get_port_number();
+    let zabbix_address = format!("127.0.0.1:{}", get_port_number());
+    let _server = Subprocess::start_server(
+        "server",
+        port,
+        &["--self-monitoring", "--zabbix-server", &zabbix_address],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut client = Subprocess::start_client("client", port, &["list"]);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(client_out, "_checkmate_server\n");
+
+    // Nothing listens on the port of Zabbix, so forwarding the status change fails
+    let mut client = Subprocess::start_client(
+        "client",
+        port,
+        &["push", "-n", "Backup", "--error", "No space left"],
+    );
+    client.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read", "-i", "1"]);
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert!(client_reader_out.contains("_checkmate_server: zabbix: "));
+}
+
+#[test]
+fn availability_is_reported_from_history() {
+    let port = get_port_number();
+    let history_file = std::env::temp_dir().join(format!("check_mate_history_{port}.db"));
+    let _ = std::fs::remove_file(&history_file);
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &["--history-file", history_file.to_str().unwrap()],
+    );
+
+    let mut client = Subprocess::start_client("client", port, &["push", "-n", "Backup", "--ok"]);
+    client.wait_and_get_output(true);
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+
+    let mut client = Subprocess::start_client("client", port, &["availability", "-w", "day"]);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(client_out, "Backup: 100.000%\n");
+
+    server.kill_and_get_output();
+    std::fs::remove_file(history_file).unwrap();
+
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut client = Subprocess::start_client("client", port, &["availability"]);
+    assert_eq!(client.wait_and_get_exit_code(), Some(1));
+}
+
+#[test]
+fn history_is_pruned_on_compaction() {
+    let port = get_port_number();
+    let history_file = std::env::temp_dir().join(format!("check_mate_history_{port}.db"));
+    let _ = std::fs::remove_file(&history_file);
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &[
+            "--history-file",
+            history_file.to_str().unwrap(),
+            "--history-max-rows",
+            "1",
+        ],
+    );
+
+    for status in [&["--ok"][..], &["--error", "No space left"], &["--ok"]] {
+        let args = [&["push", "-n", "Backup"][..], status].concat();
+        let mut client = Subprocess::start_client("client", port, &args);
+        client.wait_and_get_output(true);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+
+    let mut client = Subprocess::start_client("client", port, &["compact-history"]);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(client_out, "Removed 2 statuses from history\n");
+    let mut client = Subprocess::start_client("client", port, &["compact-history"]);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(client_out, "Removed 0 statuses from history\n");
+
+    let server_out = server.kill_and_get_output();
+    assert!(server_out.contains("History was compacted, 2 statuses removed"));
+    std::fs::remove_file(history_file).unwrap();
+
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut client = Subprocess::start_client("client", port, &["compact-history"]);
+    assert_eq!(client.wait_and_get_exit_code(), Some(1));
+}
+
+#[test]
+fn history_is_exported() {
+    let port = get_port_number();
+    let history_file = std::env::temp_dir().join(format!("check_mate_history_{port}.db"));
+    let _ = std::fs::remove_file(&history_file);
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &["--history-file", history_file.to_str().unwrap()],
+    );
+
+    for status in [&["--ok"][..], &["--error", "No space left, \"/\" is full"]] {
+        let args = [&["push", "-n", "Backup"][..], status].concat();
+        let mut client = Subprocess::start_client("client", port, &args);
+        client.wait_and_get_output(true);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+
+    let mut client = Subprocess::start_client("client", port, &["export"]);
+    let client_out = client.wait_and_get_output(true);
+    let lines = client_out.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "time,name,status,message");
+    assert!(lines[1].ends_with("Z,Backup,ok,"));
+    assert!(lines[2].ends_with("Z,Backup,error,\"No space left, \"\"/\"\" is full\""));
+
+    let args = ["export", "--from", "1h", "-o", "json"];
+    let mut client = Subprocess::start_client("client", port, &args);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(client_out.lines().count(), 2);
+    assert!(client_out.contains("\"status\":\"error\""));
+
+    let args = ["export", "--to", "1h"];
+    let mut client = Subprocess::start_client("client", port, &args);
+    let client_out = client.wait_and_get_output(true);
+    assert_eq!(client_out, "time,name,status,message\n");
+
+    server.kill_and_get_output();
+    std::fs::remove_file(history_file).unwrap();
+
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let mut client = Subprocess::start_client("client", port, &["export"]);
+    assert_eq!(client.wait_and_get_exit_code(), Some(1));
+}
+
+#[test]
+fn history_larger_than_buffer_is_exported() {
+    let port = get_port_number();
+    let history_file = std::env::temp_dir().join(format!("check_mate_history_{port}.db"));
+    let _ = std::fs::remove_file(&history_file);
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &["--history-file", history_file.to_str().unwrap()],
+    );
+
+    let message = "No space left ".repeat(150);
+    let message = message.trim_end();
+    for i in 0..8 {
+        let status = if i % 2 == 0 { &["--error", message][..] } else { &["--ok"][..] };
+        let args = [&["push", "-n", "Backup"][..], status].concat();
+        let mut client = Subprocess::start_client("client", port, &args);
+        client.wait_and_get_output(true);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+
+    let mut client = Subprocess::start_client("client", port, &["export"]);
+    let client_out = client.wait_and_get_output(true);
+    assert!(client_out.len() > 8 * 1024);
+    let lines = client_out.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 9);
+    assert_eq!(lines[0], "time,name,status,message");
+    assert_eq!(lines.iter().filter(|x| x.ends_with(message)).count(), 4);
+
+    server.kill_and_get_output();
+    std::fs::remove_file(history_file).unwrap();
+}
+
+#[tokio::test]
+async fn sdk_reports_and_reads_statuses() {
+    let port = get_port_number();
+    let options = |name: Option<&str>| ConnectOptions {
+        server_port: port,
+        identity: Identity {
+            name: name.map(str::to_owned),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut server = Subprocess::start_server("server", port, &[]);
+
+    let mut reporter = Reporter::connect(&options(Some("Backup"))).await.unwrap();
+    reporter.set_error("No space left").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let mut reader = Reader::connect(&options(None)).await.unwrap();
+    let statuses = reader.statuses().await.unwrap();
+    let backup = statuses.iter().find(|x| x.name == "Backup").unwrap();
+    assert_eq!(backup.status, Some(Err("No space left".to_owned())));
+    drop(reader);
+    let server_out = server.kill_and_get_output();
+    server_out
+        .lines()
+        .seek("Client Backup has error: No space left");
+
+    // Reporter connects again after the server restarts
+    let _server = Subprocess::start_server("server", port, &[]);
+    reporter.set_ok().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let mut reader = Reader::connect(&options(None)).await.unwrap();
+    let statuses = reader.statuses().await.unwrap();
+    let backup = statuses.iter().find(|x| x.name == "Backup").unwrap();
+    assert_eq!(backup.status, Some(Ok(())));
+}
+
+#[tokio::test]
+async fn embedded_server_serves_clients_until_shut_down() {
+    let port = get_port_number();
+    let options = |name: Option<&str>| ConnectOptions {
+        server_port: port,
+        identity: Identity {
+            name: name.map(str::to_owned),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let server = Server::builder().port(port).build();
+    let shutdown = server.shutdown_handle();
+    let server = tokio::spawn(server.run());
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let mut reporter = Reporter::connect(&options(Some("Backup"))).await.unwrap();
+    reporter.set_error("No space left").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let mut reader = Reader::connect(&options(None)).await.unwrap();
+    let statuses = reader.statuses().await.unwrap();
+    let backup = statuses.iter().find(|x| x.name == "Backup").unwrap();
+    assert_eq!(backup.status, Some(Err("No space left".to_owned())));
+
+    shutdown.shutdown();
+    assert!(shutdown.is_shut_down());
+    assert_eq!(server.await.unwrap(), Ok(()));
+    assert!(tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn embedded_server_reports_failure_to_start() {
+    let port = get_port_number();
+    let _listener = tokio::net::TcpListener::bind(("127.0.0.1", port))
+        .await
+        .unwrap();
+    let result = Server::builder().port(port).run().await;
+    assert!(result.unwrap_err().starts_with("failed to bind address"));
+}
+
+#[tokio::test]
+async fn sdk_subscriber_receives_status_changes() {
+    let port = get_port_number();
+    let options = |name: Option<&str>| ConnectOptions {
+        server_port: port,
+        identity: Identity {
+            name: name.map(str::to_owned),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let server = Server::builder().port(port).build();
+    let shutdown = server.shutdown_handle();
+    tokio::spawn(server.run());
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let mut subscriber = Subscriber::connect(&options(None)).await.unwrap();
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let mut callback_subscriber = Subscriber::connect(&options(None)).await.unwrap();
+    callback_subscriber.on_change(Filter::client("Back*"), move |details| {
+        sender.send(details.status.clone()).unwrap();
+    });
+    tokio::spawn(callback_subscriber.run());
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let mut database = Reporter::connect(&options(Some("Database"))).await.unwrap();
+    database.set_error("Disk full").await.unwrap();
+    let details = subscriber.next().await.unwrap();
+    assert_eq!(details.name, "Database");
+    assert_eq!(details.status, Some(Err("Disk full".to_owned())));
+
+    let mut backup = Reporter::connect(&options(Some("Backup"))).await.unwrap();
+    backup.set_error("No space left").await.unwrap();
+    let details = subscriber
+        .next_matching(&Filter::client("Backup"))
+        .await
+        .unwrap();
+    assert_eq!(details.status, Some(Err("No space left".to_owned())));
+
+    // Callback is not called for the database, which doesn't match its filter
+    let status = receiver.recv().await.unwrap();
+    assert_eq!(status, Some(Err("No space left".to_owned())));
+    backup.set_ok().await.unwrap();
+    let status = receiver.recv().await.unwrap();
+    assert_eq!(status, Some(Ok(())));
+
+    shutdown.shutdown();
+}
+
+#[tokio::test]
+async fn sdk_is_tested_against_mock_server() {
+    let server = MockServer::start().await.unwrap();
+    let options = |name: Option<&str>| ConnectOptions {
+        server_port: server.port(),
+        identity: Identity {
+            name: name.map(str::to_owned),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut reporter = Reporter::connect(&options(Some("Backup"))).await.unwrap();
+    reporter.set_error("No space left").await.unwrap();
+    let command = server
+        .wait_for(|x| matches!(x, ServerCommand::SetStatusError(_)))
+        .await;
+    assert_eq!(
+        command,
+        ServerCommand::SetStatusError("No space left".to_owned())
+    );
+    assert_eq!(
+        server.received(),
+        [
+            ServerCommand::SetName("Backup".to_owned()),
+            ServerCommand::SetStatusError("No space left".to_owned()),
+        ]
+    );
+
+    let details = ClientDetails {
+        name: "Backup".to_owned(),
+        status: Some(Err("No space left".to_owned())),
+        pending: false,
+        warning: false,
+        age_seconds: 5,
+        tags: Vec::new(),
+    };
+    server.respond_to(
+        |x| *x == ServerCommand::GetClientDetails,
+        vec![ServerCommand::ClientDetails(vec![details.clone()])],
+    );
+    let mut reader = Reader::connect(&options(None)).await.unwrap();
+    let statuses = reader.statuses().await.unwrap();
+    assert_eq!(statuses, std::slice::from_ref(&details));
+
+    let mut subscriber = Subscriber::connect(&options(None)).await.unwrap();
+    server.wait_for(|x| *x == ServerCommand::Subscribe).await;
+    server.push(ServerCommand::StatusChanged(details.clone()));
+    assert_eq!(subscriber.next().await.unwrap(), details);
+}
+
+#[test]
+fn responses_larger_than_buffer_are_received() {
+    let port = get_port_number();
+    let _server = Subprocess::start_server("server", port, &[]);
+    let message = "No space left ".repeat(2000);
+    let _client_watcher =
+        Subprocess::start_client("client_watcher", port, &["watch", "echo", message.trim_end()]);
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_lister = Subprocess::start_client("client_lister", port, &["list", "-o", "json"]);
+    let client_lister_out = client_lister.wait_and_get_output(true);
+    assert!(client_lister_out.contains(message.trim_end()));
+}
+
+#[test]
+fn connections_from_denied_networks_are_rejected() {
+    let port = get_port_number();
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &["--allow", "127.0.0.0/8", "--deny", "127.0.0.1"],
+    );
+
+    let mut client = Subprocess::start_client("client", port, &["abort", "-n", "Aborter"]);
+    assert!(client.wait_and_get_output(true).is_empty());
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let server_out = server.kill_and_get_output();
+    assert!(server_out.contains("Denied connection from 127.0.0.1:"));
+    assert!(!server_out.contains("Aborter"));
+}
+
+#[test]
+fn connections_from_other_hosts_are_checked_against_networks() {
+    let Some(address) = get_non_loopback_address() else {
+        eprintln!("Skipped, because there is no address other than loopback");
+        return;
+    };
+    let address = address.to_string();
+    let port = get_port_number();
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &["--bind", "0.0.0.0", "--allow", &address],
+    );
+
+    let mut client_local = Subprocess::start_client("client_local", port, &["abort", "-n", "Local"]);
+    assert!(client_local.wait_and_get_output(true).is_empty());
+    let mut client_remote = Subprocess::start_client(
+        "client_remote",
+        port,
+        &["abort", "-n", "Remote", "-a", &address],
+    );
+    assert!(client_remote.wait_and_get_output(true).is_empty());
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let server_out = server.kill_and_get_output();
+    assert!(server_out.contains("Denied connection from 127.0.0.1:"));
+    assert!(!server_out.contains(&format!("Denied connection from {address}:")));
+    assert!(!server_out.contains("Local"));
+    assert!(server_out.contains("Remote"), "Unexpected output: {server_out}");
+}
+
+#[test]
+fn clients_are_identified_by_tls_certificates() {
+    let certificate = |name: &str| format!("{}/certs/{name}", env!("CARGO_MANIFEST_DIR"));
+    let (ca, server_cert, server_key) = (
+        certificate("ca.pem"),
+        certificate("server.pem"),
+        certificate("server.key"),
+    );
+    let (backup_cert, backup_key) = (certificate("backup.pem"), certificate("backup.key"));
+    let (reader_cert, reader_key) = (certificate("reader.pem"), certificate("reader.key"));
+
+    let port = get_port_number();
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &[
+            "--tls-cert",
+            &server_cert,
+            "--tls-key",
+            &server_key,
+            "--tls-client-ca",
+            &ca,
+        ],
+    );
+
+    // Clients without a certificate can't connect at all
+    let mut client = Subprocess::start_client("client", port, &["abort", "--tls-ca", &ca]);
+    client.wait_and_get_output(false);
+
+    // Names other than the ones from the certificate are rejected
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "echo",
+            "some error",
+            "--",
+            "-n",
+            "Spoofed",
+            "--tls-ca",
+            &ca,
+            "--tls-cert",
+            &backup_cert,
+            "--tls-key",
+            &backup_key,
+        ],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &[
+            "read",
+            "-i",
+            "1",
+            "--tls-ca",
+            &ca,
+            "--tls-cert",
+            &reader_cert,
+            "--tls-key",
+            &reader_key,
+        ],
+    );
+    let client_reader_out = client_reader.wait_and_get_output(true);
+    assert_eq!(client_reader_out, "Backup: some error\n");
+
+    let server_out = server.kill_and_get_output();
+    server_out
+        .lines()
+        .seek("Client Backup is not allowed to use name Spoofed");
+    assert!(!server_out.contains("Received abort command"));
+}
+
+#[test]
+fn commands_are_limited_by_roles_of_identities() {
+    let certificate = |name: &str| format!("{}/certs/{name}", env!("CARGO_MANIFEST_DIR"));
+    let (ca, server_cert, server_key) = (
+        certificate("ca.pem"),
+        certificate("server.pem"),
+        certificate("server.key"),
+    );
+    let backup_args = [
+        "--tls-ca",
+        &ca,
+        "--tls-cert",
+        &certificate("backup.pem"),
+        "--tls-key",
+        &certificate("backup.key"),
+    ];
+    let reader_args = [
+        "--tls-ca",
+        &ca,
+        "--tls-cert",
+        &certificate("reader.pem"),
+        "--tls-key",
+        &certificate("reader.key"),
+    ];
+
+    let port = get_port_number();
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &[
+            "--tls-cert",
+            &server_cert,
+            "--tls-key",
+            &server_key,
+            "--tls-client-ca",
+            &ca,
+            "--role",
+            "Reader=read-only",
+            "--default-role",
+            "report-only",
+        ],
+    );
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[&["watch", "echo", "some error", "--"], &backup_args[..]].concat(),
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &[&["read", "-i", "1"], &reader_args[..]].concat(),
+    );
+    assert_eq!(
+        client_reader.wait_and_get_output(true),
+        "Backup: some error\n"
+    );
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &[&["read", "--all-namespaces"], &reader_args[..]].concat(),
+    );
+    assert_eq!(client_reader.wait_and_get_output(false), "");
+
+    let mut client_refresher = Subprocess::start_client(
+        "client_refresher",
+        port,
+        &[&["refresh_all"], &reader_args[..]].concat(),
+    );
+    client_refresher.wait_and_get_output(false);
+    let mut client_reader = Subprocess::start_client(
+        "client_reader",
+        port,
+        &[&["read"], &backup_args[..]].concat(),
+    );
+    assert_eq!(client_reader.wait_and_get_output(false), "");
+
+    let server_out = server.kill_and_get_output();
+    assert!(server_out.contains("authenticated as Backup with role report-only"));
+    assert!(server_out
+        .contains("Client Reader with role read-only is not allowed to send this command"));
+    assert!(server_out
+        .contains("Client Backup with role report-only is not allowed to send this command"));
+}
+
+#[test]
+fn clients_are_identified_by_api_keys() {
+    let port = get_port_number();
+    let mut server = Subprocess::start_server("server", port, &["--role", "Backup=report-only"]);
+
+    let mut client_admin = Subprocess::start_client(
+        "client_admin",
+        port,
+        &["api-key", "create", "Backup", "--label", "backup host"],
+    );
+    let key = client_admin.wait_and_get_output(true).trim().to_owned();
+    let id = key
+        .split_once('.')
+        .expect("Key should have an id")
+        .0
+        .to_owned();
+
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &["watch", "echo", "some error", "--", "--api-key", &key],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let mut client_reader = Subprocess::start_client("client_reader", port, &["read", "-i", "1"]);
+    assert_eq!(
+        client_reader.wait_and_get_output(true),
+        "Backup: some error\n"
+    );
+
+    let mut client_admin = Subprocess::start_client("client_admin", port, &["api-key", "list"]);
+    assert_eq!(
+        client_admin.wait_and_get_output(true),
+        format!("{id} Backup: backup host\n")
+    );
+    let mut client_admin =
+        Subprocess::start_client("client_admin", port, &["api-key", "revoke", &id]);
+    client_admin.wait_and_get_output(true);
+    let mut client_admin =
+        Subprocess::start_client("client_admin", port, &["api-key", "revoke", &id]);
+    assert_eq!(client_admin.wait_and_get_exit_code(), Some(1));
+    let mut client_admin = Subprocess::start_client("client_admin", port, &["api-key", "list"]);
+    assert_eq!(client_admin.wait_and_get_output(true), "");
+
+    let mut client_reader =
+        Subprocess::start_client("client_reader", port, &["read", "--api-key", &key]);
+    client_reader.wait_and_get_output(false);
+
+    let server_out = server.kill_and_get_output();
+    assert!(server_out.contains("authenticated as Backup with role report-only"));
+    assert!(server_out.contains(&format!("API key {id} was revoked")));
+    assert!(server_out.contains("presented an invalid API key"));
+}
+
+#[test]
+fn clients_exceeding_status_rate_limit_are_disconnected() {
+    let port = get_port_number();
+    let mut server = Subprocess::start_server(
+        "server",
+        port,
+        &[
+            "--status-rate-limit",
+            "2",
+            "--rate-limit-action",
+            "disconnect",
+        ],
+    );
+    let _client_watcher = Subprocess::start_client(
+        "client_watcher",
+        port,
+        &[
+            "watch",
+            "echo",
+            "some error",
+            "--",
+            "-n",
+            "Runaway",
+            "-w",
+            "10ms",
+        ],
+    );
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    let server_out = server.kill_and_get_output();
+    assert!(server_out
+        .contains("Client Runaway exceeded the limit of 2 statuses per second, disconnecting"));
 }